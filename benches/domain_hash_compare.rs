@@ -0,0 +1,40 @@
+// how long comparing two domain hashes takes against where they first differ.
+// constant_time_eq should take the same at every position, where == returns
+// sooner the earlier the difference.
+//
+//   cargo bench --bench domain_hash_compare
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use void_vault::constant_time_eq;
+
+const ROUNDS: u32 = 2_000_000;
+
+// nanoseconds per comparison
+fn time(compare: impl Fn(&[u8; 64], &[u8; 64]) -> bool, a: &[u8; 64], b: &[u8; 64]) -> f64 {
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(compare(black_box(a), black_box(b)));
+    }
+    started.elapsed().as_secs_f64() * 1e9 / ROUNDS as f64
+}
+
+fn main() {
+    let a: [u8; 64] = std::array::from_fn(|i| (i as u8).wrapping_mul(37) | 1);
+    println!(
+        "{} comparisons per position of the first difference",
+        ROUNDS
+    );
+    println!("  differs at   constant_time_eq   ==");
+    for position in [0, 15, 31, 47, 63] {
+        let mut b = a;
+        b[position] ^= 0x55;
+        let constant = time(|x, y| constant_time_eq(x, y), &a, &b);
+        let short = time(|x, y| x == y, &a, &b);
+        println!(
+            "  byte {:>2}:    {:>10.2}ns      {:>10.2}ns",
+            position, constant, short
+        );
+    }
+}
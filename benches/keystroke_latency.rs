@@ -0,0 +1,67 @@
+// how long a keystroke takes at each point of a 64 character phrase with 7 extra
+// characters per step. every keystroke replays the feedback trail, so the n-th one
+// takes n steps and the late keystrokes are the ones a user waits on.
+//
+//   cargo bench --bench keystroke_latency
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use void_vault::{generate_keystroke_into, Normalization, SavedPassword, StructureSystem};
+
+const PHRASE: &str = "a sixty-four character phrase for timing the keystroke pipeline!";
+const ROUNDS: u32 = 200;
+
+fn config() -> SavedPassword {
+    let pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+    let setup_phrase: Vec<char> = "a latency benchmark phrase".chars().collect();
+    let mut structure = StructureSystem::new(7, 7, 17);
+    structure.generate_structure(&setup_phrase, &pool);
+
+    SavedPassword {
+        name: "latency".to_string(),
+        description: String::new(),
+        structure_system: structure,
+        created_date: 0,
+        extra_chars_count: 7,
+        use_count: 0,
+        normalization: Normalization::CURRENT,
+        fingerprint: None,
+        setup_mode: None,
+        rekeyed_from: None,
+    }
+}
+
+fn main() {
+    assert_eq!(PHRASE.chars().count(), 64);
+    let mut config = config();
+    let mut feedbacks = Vec::with_capacity(PHRASE.len());
+    let mut output = String::new();
+    let mut per_key = [Duration::ZERO; 64];
+
+    for _ in 0..ROUNDS {
+        config.structure_system.full_reset();
+        feedbacks.clear();
+        for (i, ch) in PHRASE.chars().enumerate() {
+            let started = Instant::now();
+            generate_keystroke_into(&mut config, &mut feedbacks, ch as u32, &mut output);
+            per_key[i] += started.elapsed();
+            black_box(&output);
+        }
+    }
+
+    let total: Duration = per_key.iter().sum();
+    println!(
+        "{} rounds of {} keystrokes, extra_chars_count 7",
+        ROUNDS,
+        per_key.len()
+    );
+    for i in [0, 15, 31, 47, 63] {
+        println!("  keystroke {:>2}: {:?}", i + 1, per_key[i] / ROUNDS);
+    }
+    println!(
+        "  mean:          {:?}",
+        total / (ROUNDS * per_key.len() as u32)
+    );
+    println!("  whole phrase:  {:?}", total / ROUNDS);
+}
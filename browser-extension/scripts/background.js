@@ -20,6 +20,9 @@
 const tabStates = new Map();
 let hasCheckedSetup = false;
 
+// well inside the binary's default ten minute exit interval
+const KEEPALIVE_INTERVAL_MS = 60 * 1000;
+
 chrome.runtime.onMessage.addListener((message, sender, sendResponse) => {
 
   switch (message.type) {
@@ -149,7 +152,11 @@ chrome.runtime.onMessage.addListener((message, sender, sendResponse) => {
 
             sendResponse({
               maxLength: msg.max_length || 0,
-              charTypes: msg.char_types || 127
+              charTypes: msg.char_types || 127,
+              noConfusables: msg.no_confusables === true,
+              startsWithLetter: msg.starts_with_letter === true,
+              noLeadingSymbol: msg.no_leading_symbol === true,
+              noTrailingSymbol: msg.no_trailing_symbol === true
             });
           }
         });
@@ -191,7 +198,7 @@ chrome.runtime.onMessage.addListener((message, sender, sendResponse) => {
             tempPort.disconnect();
 
             if (msg.error) {
-              sendResponse({status: 'error', error: msg.error});
+              sendResponse({status: 'error', error: msg.error.message || msg.error});
             } else {
               sendResponse({status: 'success'});
             }
@@ -211,7 +218,11 @@ chrome.runtime.onMessage.addListener((message, sender, sendResponse) => {
           type: 'SET_RULES',
           domain: message.domain,
           max_length: message.maxLength || 0,
-          char_types: message.charTypes || 127
+          char_types: message.charTypes || 127,
+          no_confusables: message.noConfusables === true,
+          starts_with_letter: message.startsWithLetter === true,
+          no_leading_symbol: message.noLeadingSymbol === true,
+          no_trailing_symbol: message.noTrailingSymbol === true
         });
       } catch (e) {
         sendResponse({status: 'error', error: 'Failed to connect to binary: ' + e.message});
@@ -229,6 +240,7 @@ chrome.runtime.onMessage.addListener((message, sender, sendResponse) => {
 function activateStarwell(tabId, domain) {
   const existingState = tabStates.get(tabId);
   if (existingState && existingState.nativePort) {
+    clearInterval(existingState.keepaliveTimer);
     existingState.nativePort.disconnect();
   }
 
@@ -237,10 +249,16 @@ function activateStarwell(tabId, domain) {
     const nativePort = chrome.runtime.connectNative('com.starwell.void_vault');
     console.log('[Starwell Background] Native port connected for tab:', tabId);
 
+    // the binary exits on its own after a quiet spell, keep it around while the tab is active
+    const keepaliveTimer = setInterval(() => {
+      nativePort.postMessage({ type: 'KEEPALIVE' });
+    }, KEEPALIVE_INTERVAL_MS);
+
     tabStates.set(tabId, {
       nativePort: nativePort,
       isActive: true,
-      domain: domain
+      domain: domain,
+      keepaliveTimer: keepaliveTimer
     });
 
     const thisTabId = tabId;
@@ -255,6 +273,10 @@ function activateStarwell(tabId, domain) {
           activeCounter: message.active_counter,
           maxLength: message.max_length || 0,
           charTypes: message.char_types || 127,
+          noConfusables: message.no_confusables === true,
+          startsWithLetter: message.starts_with_letter === true,
+          noLeadingSymbol: message.no_leading_symbol === true,
+          noTrailingSymbol: message.no_trailing_symbol === true,
           isPreviewMode: false
         });
       }
@@ -266,6 +288,10 @@ function activateStarwell(tabId, domain) {
           activeCounter: message.active_counter,
           maxLength: message.max_length || 0,
           charTypes: message.char_types || 127,
+          noConfusables: message.no_confusables === true,
+          startsWithLetter: message.starts_with_letter === true,
+          noLeadingSymbol: message.no_leading_symbol === true,
+          noTrailingSymbol: message.no_trailing_symbol === true,
           isPreviewMode: true
         });
       }
@@ -288,6 +314,15 @@ function activateStarwell(tabId, domain) {
         chrome.tabs.sendMessage(thisTabId, {
           type: 'COUNTER_SET_SUCCESS'
         });
+
+        // the binary restarted the domain at its new counter, so what was typed is gone
+        if (message.active_session_affected) {
+          chrome.tabs.sendMessage(thisTabId, {
+            type: 'UPDATE_PASSWORD',
+            password: '',
+            normalize: false
+          });
+        }
       }
 
       if (message.counter !== undefined && message.status !== 'ready' && message.status !== 'preview' && message.status !== 'cancelled' && message.status !== 'committed') {
@@ -297,17 +332,47 @@ function activateStarwell(tabId, domain) {
         });
       }
 
+      // the binary locked the session after sitting idle, start over for this domain
+      if (message.error && message.error.code === 'session_locked') {
+        chrome.tabs.sendMessage(thisTabId, {
+          type: 'UPDATE_PASSWORD',
+          password: '',
+          normalize: false
+        });
+        nativePort.postMessage({
+          type: 'ACTIVATE',
+          domain: domain
+        });
+      }
+
+      // the binary locked us out for sending too fast, activate again once it allows
+      if (message.error && message.error.code === 'rate_limited' && message.activate) {
+        chrome.tabs.sendMessage(thisTabId, {
+          type: 'UPDATE_PASSWORD',
+          password: '',
+          normalize: false
+        });
+        setTimeout(() => {
+          nativePort.postMessage({
+            type: 'ACTIVATE',
+            domain: domain
+          });
+        }, (message.retry_after || 0) * 1000);
+      }
+
       if (message.output) {
         chrome.tabs.sendMessage(thisTabId, {
           type: 'UPDATE_PASSWORD',
           password: message.output,
-          normalize: true
+          normalize: true,
+          entropyBits: message.entropy_bits
         });
       }
     });
 
     nativePort.onDisconnect.addListener(() => {
       console.log('[Starwell Background] Tab', thisTabId, 'native port disconnected');
+      clearInterval(keepaliveTimer);
       tabStates.delete(thisTabId);
     });
 
@@ -325,6 +390,7 @@ function activateStarwell(tabId, domain) {
 function deactivateStarwell(tabId) {
   const tabState = tabStates.get(tabId);
   if (tabState && tabState.nativePort) {
+    clearInterval(tabState.keepaliveTimer);
     tabState.nativePort.postMessage({ type: 'FINALIZE' });
     tabState.nativePort.disconnect();
     tabStates.delete(tabId);
@@ -339,7 +405,8 @@ function handleInput(character, tabId) {
     return;
   }
 
-  const charCode = character.charCodeAt(0);
+  // full code point, so astral characters match what the terminal modes see
+  const charCode = character.codePointAt(0);
   tabState.nativePort.postMessage({
     charCode: charCode
   });
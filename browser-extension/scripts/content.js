@@ -1108,7 +1108,9 @@ chrome.runtime.onMessage.addListener(async (message, sender, sendResponse) => {
       chrome.storage.local.get(['domainRules'], (result) => {
         const allRules = result.domainRules || {};
 
-        if (maxLength === 0 && message.charTypes === 127) {
+        // the binary holds the ends itself, they are kept only for the popup
+        const holdsEnds = message.startsWithLetter || message.noLeadingSymbol || message.noTrailingSymbol;
+        if (maxLength === 0 && message.charTypes === 127 && !message.noConfusables && !holdsEnds) {
           if (allRules[currentDomain]) {
             delete allRules[currentDomain];
             chrome.storage.local.set({ domainRules: allRules });
@@ -1117,7 +1119,11 @@ chrome.runtime.onMessage.addListener(async (message, sender, sendResponse) => {
           allRules[currentDomain] = {
             enabled: true,
             maxLength: maxLength || null,
-            allowedChars: allowedChars
+            allowedChars: allowedChars,
+            noConfusables: message.noConfusables === true,
+            startsWithLetter: message.startsWithLetter === true,
+            noLeadingSymbol: message.noLeadingSymbol === true,
+            noTrailingSymbol: message.noTrailingSymbol === true
           };
           chrome.storage.local.set({ domainRules: allRules });
         }
@@ -41,6 +41,10 @@ const CHAR_SETS = {
   alphanumeric: 'abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789'
 };
 
+// the ASCII the binary counts as confusable, see pool::is_confusable. with
+// noConfusables nothing is collapsed onto them
+const CONFUSABLE_ASCII = '01IOlo|';
+
 function isEmoji(char) {
   const code = char.codePointAt(0);
   if (!code) return false;
@@ -111,6 +115,13 @@ function normalizePassword(rawPassword, rules) {
       allowedString += CHAR_SETS.extendedSymbols;
     }
 
+    if (rules.noConfusables) {
+      allowedString = Array.from(allowedString)
+        .filter(c => !CONFUSABLE_ASCII.includes(c))
+        .join('');
+      CONFUSABLE_ASCII.split('').forEach(c => allowedSet.delete(c));
+    }
+
     const allowEmojis = rules.allowedChars.includes('emojis');
     const allowExtendedUnicode = rules.allowedChars.includes('extendedUnicode');
 
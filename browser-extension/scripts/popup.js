@@ -132,11 +132,16 @@ function loadRules() {
         domain: currentDomain
       }, (response) => {
         if (response && response.maxLength !== undefined && response.charTypes !== undefined) {
-          if (response.maxLength !== 0 || response.charTypes !== 127) {
+          if (response.maxLength !== 0 || response.charTypes !== 127 || response.noConfusables ||
+              response.startsWithLetter || response.noLeadingSymbol || response.noTrailingSymbol) {
             const rules = {
               enabled: true,
               maxLength: response.maxLength || null,
-              allowedChars: bitfieldToAllowedChars(response.charTypes)
+              allowedChars: bitfieldToAllowedChars(response.charTypes),
+              noConfusables: response.noConfusables === true,
+              startsWithLetter: response.startsWithLetter === true,
+              noLeadingSymbol: response.noLeadingSymbol === true,
+              noTrailingSymbol: response.noTrailingSymbol === true
             };
             displayRules(rules);
 
@@ -173,6 +178,10 @@ function displayRules(rules) {
     document.getElementById('allowExtendedSymbols').checked = allowed.includes('extendedSymbols');
     document.getElementById('allowEmojis').checked = allowed.includes('emojis');
     document.getElementById('allowExtendedUnicode').checked = allowed.includes('extendedUnicode');
+    document.getElementById('noConfusables').checked = rules.noConfusables === true;
+    document.getElementById('startsWithLetter').checked = rules.startsWithLetter === true;
+    document.getElementById('noLeadingSymbol').checked = rules.noLeadingSymbol === true;
+    document.getElementById('noTrailingSymbol').checked = rules.noTrailingSymbol === true;
   } else {
     document.getElementById('enableRules').checked = false;
     document.getElementById('rulesForm').style.display = 'none';
@@ -185,6 +194,10 @@ function displayRules(rules) {
     document.getElementById('allowExtendedSymbols').checked = true;
     document.getElementById('allowEmojis').checked = true;
     document.getElementById('allowExtendedUnicode').checked = true;
+    document.getElementById('noConfusables').checked = false;
+    document.getElementById('startsWithLetter').checked = false;
+    document.getElementById('noLeadingSymbol').checked = false;
+    document.getElementById('noTrailingSymbol').checked = false;
   }
 }
 
@@ -218,11 +231,20 @@ document.getElementById('saveButton').addEventListener('click', () => {
     return;
   }
 
+  const noConfusables = document.getElementById('noConfusables').checked;
+  const startsWithLetter = document.getElementById('startsWithLetter').checked;
+  const noLeadingSymbol = document.getElementById('noLeadingSymbol').checked;
+  const noTrailingSymbol = document.getElementById('noTrailingSymbol').checked;
+
   const rules = {
     enabled: true,
     minLength: minLength ? parseInt(minLength) : null,
     maxLength: maxLength ? parseInt(maxLength) : null,
-    allowedChars: allowedChars
+    allowedChars: allowedChars,
+    noConfusables: noConfusables,
+    startsWithLetter: startsWithLetter,
+    noLeadingSymbol: noLeadingSymbol,
+    noTrailingSymbol: noTrailingSymbol
   };
 
   const maxLengthValue = maxLength ? parseInt(maxLength) : 0;
@@ -232,7 +254,11 @@ document.getElementById('saveButton').addEventListener('click', () => {
     type: 'SET_RULES',
     domain: currentDomain,
     maxLength: maxLengthValue,
-    charTypes: charTypesValue
+    charTypes: charTypesValue,
+    noConfusables: noConfusables,
+    startsWithLetter: startsWithLetter,
+    noLeadingSymbol: noLeadingSymbol,
+    noTrailingSymbol: noTrailingSymbol
   }, (response) => {
     if (chrome.runtime.lastError) {
       showStatus('Error saving to binary: ' + chrome.runtime.lastError.message, 'error');
@@ -0,0 +1,90 @@
+/*
+ * Void Vault C ABI, for embedding the generator in front-ends that aren't Rust.
+ * Hand-maintained alongside src/ffi.rs, link against the cdylib that
+ * `cargo build --release` leaves in target/release (libvoid_vault.so, .dylib or
+ * void_vault.dll).
+ *
+ * A handle is one session, the same one the browser host keeps: open the vault,
+ * activate a domain, feed it characters. It holds the vault's lock until closed,
+ * so the browser host and writing CLI commands wait for it as they would for
+ * each other.
+ *
+ * Every function that returns int32_t returns 0 or more on success and a
+ * negative error code on failure. The codes are the negated exit codes that
+ * `void_vault --list-error-codes` prints.
+ *
+ * A handle is not thread safe, use it from one thread at a time.
+ */
+
+#ifndef VOID_VAULT_H
+#define VOID_VAULT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define VV_ERR_NO_CONFIGURATION (-3)
+#define VV_ERR_STORAGE_FAILURE (-5)
+#define VV_ERR_TABLE_FULL (-6)
+#define VV_ERR_MISSING_DOMAIN (-10)
+#define VV_ERR_BAD_REQUEST (-14)
+#define VV_ERR_VAULT_IN_USE (-19)
+#define VV_ERR_UNKNOWN_ACCOUNT (-21)
+#define VV_ERR_BUFFER_TOO_SMALL (-32)
+
+typedef struct vv_handle vv_handle;
+
+/* what vv_activate reports, the fields of the json-io ACTIVATE reply */
+typedef struct vv_domain_info {
+    uint16_t saved_counter;
+    uint16_t active_counter;
+    /* 0 is no limit */
+    uint16_t max_length;
+    uint8_t char_types;
+    /* non-zero on a PIN domain */
+    uint8_t pin_length;
+    /* non-zero on a passphrase domain */
+    uint8_t word_count;
+    /* the domain was registered by this call */
+    bool created;
+} vv_domain_info;
+
+/*
+ * Opens the vault binary at path, on the configuration named account or the
+ * first one when account is NULL. Returns NULL on failure, with the error code
+ * in *error when error isn't NULL.
+ */
+vv_handle *vv_open(const char *path, const char *account, int32_t *error);
+
+/*
+ * Starts a session on domain, registering it at counter 0 when it is new, and
+ * fills *info when info isn't NULL. Returns 0 or an error code.
+ */
+int32_t vv_activate(vv_handle *handle, const char *domain, vv_domain_info *info);
+
+/*
+ * Types the Unicode code point and writes the output so far to out_buf as
+ * NUL-terminated UTF-8. Returns the output's length in bytes without the NUL,
+ * or an error code. On VV_ERR_BUFFER_TOO_SMALL the character was not taken and
+ * can be fed again with a larger buffer.
+ */
+int32_t vv_feed_char(vv_handle *handle, uint32_t codepoint, char *out_buf, size_t out_len);
+
+/* forgets what was typed and starts over from the activated domain. Returns 0 or an error code */
+int32_t vv_reset(vv_handle *handle);
+
+/*
+ * Zeroes the session and the generator's state, saves the use counters, releases
+ * the vault lock and frees the handle. NULL is ignored.
+ */
+void vv_close(vv_handle *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VOID_VAULT_H */
@@ -168,9 +168,9 @@ Write-Host ""
 Write-Host "1. Reload the extension in your browser (click the reload icon)"
 Write-Host ""
 Write-Host "2. Create your first password shape:"
-Write-Host "   Run: $InstalledBinaryPath"
+Write-Host "   Run: $InstalledBinaryPath setup"
 Write-Host "   Or:  cd $InstallDir"
-Write-Host "        .\$BinaryName"
+Write-Host "        .\$BinaryName setup"
 Write-Host ""
 Write-Host "3. Test the extension:"
 Write-Host "   - Visit any website with a password field"
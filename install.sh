@@ -233,7 +233,7 @@ echo
 echo "1. Reload the extension in your browser (click the reload icon)"
 echo
 echo "2. Create your first password geometry:"
-echo "   $INSTALLED_BINARY_PATH"
+echo "   $INSTALLED_BINARY_PATH setup"
 echo
 echo "3. Test the extension:"
 echo "   - Visit any website with a password field"
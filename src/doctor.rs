@@ -0,0 +1,305 @@
+//! The checks behind `doctor`, for what most often leaves an installation printing
+//! nothing: a vault whose markers went missing, a backup newer than the vault, a
+//! vault that can't be written, a browser manifest starting a binary that moved.
+//! They only read. A [`Finding`] says what is wrong and what to do about it, and
+//! where the fix is safe to apply unattended, [`Finding::fix`] applies it.
+//!
+//! The front-end adds what only it knows, as whether the code changed since the
+//! vault was sealed. Each kind of finding has a bit of the exit code, see
+//! [`exit_code`].
+
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::perms::{self, Exposure};
+use crate::{escape_json, BinaryStorageManager};
+
+/// The name the browsers know the host by, and its manifest's file name.
+pub const HOST_NAME: &str = "com.starwell.void_vault";
+
+/// The exit code bit of findings about the vault's contents.
+pub const VAULT: i32 = 1;
+/// The exit code bit of a backup newer than the vault.
+pub const BACKUP: i32 = 2;
+/// The exit code bit of a vault that can't be written.
+pub const UNWRITABLE: i32 = 4;
+/// The exit code bit of a browser manifest starting a binary that isn't there.
+pub const MANIFEST: i32 = 8;
+/// The exit code bit of a vault others could change.
+pub const EXPOSED: i32 = 16;
+
+/// Set in the exit code whenever something was found, so it never reads as one of
+/// the error codes, which stay under it.
+pub const FOUND: i32 = 64;
+
+/// One thing doctor found wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The vault holds no configuration, so there is nothing to generate with.
+    NoConfiguration(PathBuf),
+    /// The vault's configurations are there, but the file no longer ends with the
+    /// section marker they are read behind.
+    SectionMarker(PathBuf),
+    /// The vault has no domain table of its own.
+    DomainTable(PathBuf),
+    /// The `.bak` beside the vault is newer than the vault: a write died between
+    /// its renames, or the vault was put back from an older copy.
+    StaleBackup(PathBuf),
+    /// The vault, or the directory it is rewritten in, can't be written.
+    Unwritable(PathBuf),
+    /// A browser's manifest, and the binary it starts, which isn't there.
+    Manifest(PathBuf, String),
+    /// Others than the owner can replace the file, see [`perms`].
+    Exposed(PathBuf, Exposure),
+    /// The code of the executable changed since the vault was sealed.
+    CodeChanged(PathBuf),
+}
+
+impl Finding {
+    /// The name it is reported under in JSON.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Finding::NoConfiguration(_) => "no_configuration",
+            Finding::SectionMarker(_) => "section_marker",
+            Finding::DomainTable(_) => "domain_table",
+            Finding::StaleBackup(_) => "stale_backup",
+            Finding::Unwritable(_) => "unwritable",
+            Finding::Manifest(..) => "manifest",
+            Finding::Exposed(_, exposure) => exposure.code(),
+            Finding::CodeChanged(_) => "code_changed",
+        }
+    }
+
+    /// Its bit of the exit code.
+    pub fn bit(&self) -> i32 {
+        match self {
+            Finding::NoConfiguration(_) | Finding::SectionMarker(_) | Finding::DomainTable(_) => {
+                VAULT
+            }
+            Finding::StaleBackup(_) => BACKUP,
+            Finding::Unwritable(_) => UNWRITABLE,
+            Finding::Manifest(..) => MANIFEST,
+            Finding::Exposed(..) | Finding::CodeChanged(_) => EXPOSED,
+        }
+    }
+
+    /// What is wrong, in a sentence.
+    pub fn problem(&self) -> String {
+        match self {
+            Finding::NoConfiguration(vault) => {
+                format!("{} holds no configuration", vault.display())
+            }
+            Finding::SectionMarker(vault) => format!(
+                "{} has configurations, but doesn't end with the marker they are read behind, so none is found",
+                vault.display()
+            ),
+            Finding::DomainTable(vault) => format!(
+                "{} has no domain table, so no domain's counter or rules can be kept",
+                vault.display()
+            ),
+            Finding::StaleBackup(vault) => format!(
+                "{} is newer than the vault itself, a write may have stopped halfway",
+                vault.with_extension("bak").display()
+            ),
+            Finding::Unwritable(path) => format!(
+                "{} can't be written, so new domains, counters and use counts are lost",
+                path.display()
+            ),
+            Finding::Manifest(manifest, binary) => format!(
+                "{} starts {}, which isn't there, so the browser extension gets no answer",
+                manifest.display(),
+                binary
+            ),
+            Finding::Exposed(path, Exposure::FileWritable) => format!(
+                "{} can be written by other users, who could replace it",
+                path.display()
+            ),
+            Finding::Exposed(path, Exposure::DirectoryWritable) => format!(
+                "{} is in a directory other users can write",
+                path.display()
+            ),
+            Finding::CodeChanged(vault) => format!(
+                "the code in front of {} changed since the vault was sealed",
+                vault.display()
+            ),
+        }
+    }
+
+    /// What to do about it.
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            Finding::NoConfiguration(_) => "Run setup, or import-recovery from a recovery sheet",
+            Finding::SectionMarker(_) => "Append the marker again (--fix)",
+            Finding::DomainTable(_) => "Append an empty domain table (--fix)",
+            Finding::StaleBackup(_) => "Check the vault still lists your configurations and domains. If not, copy the .bak over it; if so, shred-backups removes it",
+            Finding::Unwritable(_) => "Give your user write access to it, or move the binary somewhere it has",
+            Finding::Manifest(..) => "Point the manifest at this binary (--fix), or run the installer again",
+            Finding::Exposed(_, Exposure::FileWritable) => "Restrict it to its owner (--fix)",
+            Finding::Exposed(_, Exposure::DirectoryWritable) => "Move it, or remove others' write access to the directory",
+            Finding::CodeChanged(_) => "Unless you upgraded, don't type your phrase into it. After an upgrade, accept-new-binary trusts the new code",
+        }
+    }
+
+    /// Whether [`Finding::fix`] can mend it.
+    pub fn fixable(&self) -> bool {
+        matches!(
+            self,
+            Finding::SectionMarker(_)
+                | Finding::DomainTable(_)
+                | Finding::Manifest(..)
+                | Finding::Exposed(_, Exposure::FileWritable)
+        )
+    }
+
+    /// Mends what it can: appends the missing marker or domain table (the vault as
+    /// it was kept as `.bak`), points a manifest at `executable`, or restricts a
+    /// file to its owner.
+    pub fn fix(&self, executable: &Path) -> io::Result<()> {
+        match self {
+            Finding::SectionMarker(vault) => {
+                BinaryStorageManager::append_section_marker(vault.clone())
+            }
+            Finding::DomainTable(vault) => BinaryStorageManager::append_domain_table(vault.clone()),
+            Finding::Manifest(manifest, _) => {
+                let text = fs::read_to_string(manifest)?;
+                let (span, _) = manifest_binary(&text).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "The manifest has no path")
+                })?;
+                // the installers write forward slashes on Windows too
+                let path = executable.to_string_lossy().replace('\\', "/");
+                let mut fixed = text[..span.start].to_string();
+                fixed.push('"');
+                fixed.push_str(&escape_json(&path));
+                fixed.push('"');
+                fixed.push_str(&text[span.end..]);
+                fs::write(manifest, fixed)
+            }
+            Finding::Exposed(path, Exposure::FileWritable) => perms::restrict(path),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "This needs fixing by hand",
+            )),
+        }
+    }
+}
+
+/// 0 when nothing was found, otherwise [`FOUND`] with the bit of each kind found.
+pub fn exit_code(findings: &[Finding]) -> i32 {
+    if findings.is_empty() {
+        return 0;
+    }
+    findings
+        .iter()
+        .fold(FOUND, |code, finding| code | finding.bit())
+}
+
+/// What is wrong with the vault at `vault`: what it holds, its backup, and
+/// whether it can be written.
+pub fn check_vault(vault: &Path) -> io::Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+
+    // a vault never opened is missing its markers too, the first open adds them
+    let layout = BinaryStorageManager::probe(vault.to_path_buf())?;
+    if layout.configurations == 0 {
+        findings.push(Finding::NoConfiguration(vault.to_path_buf()));
+    } else {
+        if !layout.section_marker {
+            findings.push(Finding::SectionMarker(vault.to_path_buf()));
+        }
+        if !layout.domain_table {
+            findings.push(Finding::DomainTable(vault.to_path_buf()));
+        }
+    }
+
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    if let (Some(backup), Some(current)) = (modified(&vault.with_extension("bak")), modified(vault))
+    {
+        if backup > current {
+            findings.push(Finding::StaleBackup(vault.to_path_buf()));
+        }
+    }
+
+    let directory = vault
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    for path in [vault, directory] {
+        if !perms::writable(path) {
+            findings.push(Finding::Unwritable(path.to_path_buf()));
+        }
+    }
+
+    Ok(findings)
+}
+
+/// The manifests the installers write, where they are.
+pub fn manifest_paths() -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let (base, browsers) = (
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+        ["google-chrome", "chromium", "BraveSoftware/Brave-Browser"],
+    );
+    #[cfg(target_os = "macos")]
+    let (base, browsers) = (
+        std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join("Library/Application Support")),
+        ["Google/Chrome", "Chromium", "BraveSoftware/Brave-Browser"],
+    );
+    #[cfg(windows)]
+    let (base, browsers) = (
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from),
+        [
+            "Google\\Chrome\\User Data",
+            "BraveSoftware\\Brave-Browser\\User Data",
+            "Microsoft\\Edge\\User Data",
+        ],
+    );
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    let (base, browsers): (Option<PathBuf>, [&str; 0]) = (None, []);
+
+    let Some(base) = base else {
+        return Vec::new();
+    };
+    browsers
+        .iter()
+        .map(|browser| {
+            base.join(browser)
+                .join("NativeMessagingHosts")
+                .join(format!("{}.json", HOST_NAME))
+        })
+        .filter(|manifest| manifest.is_file())
+        .collect()
+}
+
+/// The manifests that start a binary that isn't there.
+pub fn check_manifests() -> Vec<Finding> {
+    manifest_paths()
+        .into_iter()
+        .filter_map(|manifest| {
+            let text = fs::read_to_string(&manifest).ok()?;
+            let (_, binary) = manifest_binary(&text)?;
+            (!Path::new(&binary).exists()).then_some(Finding::Manifest(manifest, binary))
+        })
+        .collect()
+}
+
+// the "path" in a manifest: where its string is in the text, quotes included, and
+// what it says
+fn manifest_binary(text: &str) -> Option<(Range<usize>, String)> {
+    let key = text.find("\"path\"")? + "\"path\"".len();
+    let rest = text[key..].trim_start().strip_prefix(':')?.trim_start();
+    let start = text.len() - rest.len();
+    let mut chars = rest.strip_prefix('"')?.char_indices();
+
+    let mut path = String::new();
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => return Some((start..start + 1 + i + 1, path)),
+            '\\' => path.extend(chars.next().map(|(_, escaped)| escaped)),
+            _ => path.push(ch),
+        }
+    }
+    None
+}
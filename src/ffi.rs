@@ -0,0 +1,380 @@
+//! A C ABI over the generator, for front-ends that aren't Rust. The C side is
+//! include/void_vault.h, kept in step with this file by hand.
+//!
+//! A handle is one json-io session: [`vv_open`] loads the vault and holds its lock
+//! the way the browser host does, [`vv_activate`] is ACTIVATE, and every
+//! [`vv_feed_char`] is a `charCode` message, feedback chain, NFC composition and
+//! PIN or passphrase domains included. Failures come back as the negative of the
+//! [`ErrorCode`] exit code, so -18 is `no_configuration`.
+
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
+
+use crate::{
+    diag, ghost_navigate, restart_session, zero_memory, BinaryStorageManager, Diag, DomainTable,
+    ErrorCode, FixedOutput, GenerationOptions, GenerationSession, InstanceLock, OutputRules,
+    PasswordManager, SavedPassword, SessionState, Wiped,
+};
+
+/// What [`vv_activate`] reports for the domain, the fields of an ACTIVATE reply.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VvDomainInfo {
+    pub saved_counter: u16,
+    pub active_counter: u16,
+    pub max_length: u16,
+    pub char_types: u8,
+    pub pin_length: u8,
+    pub word_count: u8,
+    pub created: bool,
+}
+
+/// An open vault and the session typed into it. Opaque to C.
+pub struct VvHandle {
+    manager: PasswordManager,
+    account: usize,
+    table: DomainTable,
+    typing: Typing,
+    path: PathBuf,
+    // last, so the teardown in vv_close runs before another process can write
+    _lock: InstanceLock,
+}
+
+// a session besides the configuration it types into: what json-io keeps for one
+// tab. the C handle and the wasm exports both drive one
+pub(crate) struct Typing {
+    session: SessionState,
+    generation: GenerationSession,
+}
+
+impl Typing {
+    pub(crate) fn new() -> Self {
+        Typing {
+            session: SessionState::empty(),
+            generation: GenerationSession::new(GenerationOptions::KEYSTROKES),
+        }
+    }
+
+    // nothing typed yet, the first keystroke is the one a use gets counted on
+    pub(crate) fn is_fresh(&self) -> bool {
+        self.generation.is_fresh()
+    }
+
+    // ACTIVATE once the counter and rules are known: ghost navigate to where the
+    // domain starts
+    pub(crate) fn activate(
+        &mut self,
+        config: &mut SavedPassword,
+        domain: &str,
+        counter: u16,
+        fixed_output: FixedOutput,
+        rules: OutputRules,
+    ) {
+        let structure = &mut config.structure_system;
+        let domain_hash = structure.hash_domain(domain);
+
+        self.session.scrub();
+        self.session = SessionState {
+            active_domain_hash: Some(domain_hash),
+            saved_counter: counter,
+            active_counter: counter,
+            is_preview_mode: false,
+            initialized: true,
+            fixed_output,
+            rules,
+        };
+
+        structure.full_reset();
+        self.generation.clear();
+        ghost_navigate(structure, &domain_hash, counter);
+    }
+
+    // a charCode message, with the output so far copied to `out_buf` and NUL
+    // terminated. output that doesn't fit leaves the keystroke untaken
+    pub(crate) unsafe fn feed(
+        &mut self,
+        config: &mut SavedPassword,
+        codepoint: u32,
+        out_buf: *mut u8,
+        out_len: usize,
+    ) -> Result<usize, ErrorCode> {
+        if codepoint == 0 || char::from_u32(codepoint).is_none() || out_buf.is_null() {
+            return Err(ErrorCode::BadRequest);
+        }
+        let before = Wiped(self.generation.typed().to_vec());
+
+        let output = self.generation.step(&self.session, config, codepoint);
+
+        if output.len() >= out_len {
+            self.generation.retype(&self.session, config, &before);
+            return Err(ErrorCode::BufferTooSmall);
+        }
+
+        std::ptr::copy_nonoverlapping(output.as_ptr(), out_buf, output.len());
+        *out_buf.add(output.len()) = 0;
+        Ok(output.len())
+    }
+
+    pub(crate) fn reset(&mut self, config: &mut SavedPassword) {
+        restart_session(&self.session, &mut config.structure_system);
+        self.generation.clear();
+    }
+
+    pub(crate) fn scrub(&mut self, config: &mut SavedPassword) {
+        config.structure_system.full_reset();
+        self.generation.clear();
+        self.session.scrub();
+    }
+}
+
+pub(crate) fn failure(code: ErrorCode) -> i32 {
+    -code.exit_code()
+}
+
+// NULL is None, text that isn't UTF-8 is a bad request
+unsafe fn optional_str<'a>(ptr: *const c_char) -> Result<Option<&'a str>, ErrorCode> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(Some)
+        .map_err(|_| ErrorCode::BadRequest)
+}
+
+fn open(path: &str, account: Option<&str>) -> Result<Box<VvHandle>, ErrorCode> {
+    let path = PathBuf::from(path);
+    InstanceLock::set_holder("an application embedding Void Vault");
+    let lock = InstanceLock::acquire(&path, InstanceLock::HOST_WAIT)
+        .map_err(|e| ErrorCode::from_io(&e))?;
+
+    let storage = BinaryStorageManager::open(path.clone(), false, None)
+        .map_err(|e| ErrorCode::from_io(&e))?;
+    let mut manager =
+        PasswordManager::with_storage(storage, true).map_err(|e| ErrorCode::from_io(&e))?;
+    // a damaged configuration is told apart from a missing one
+    let missing = |account: Option<&str>| match manager.find_unusable(account) {
+        Some(_) => ErrorCode::UnusableConfiguration,
+        None if manager.saved_passwords.is_empty() => ErrorCode::NoConfiguration,
+        None => ErrorCode::UnknownAccount,
+    };
+    if manager.saved_passwords.is_empty() {
+        return Err(missing(account));
+    }
+    let account = match account {
+        Some(name) => manager
+            .saved_passwords
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| missing(Some(name)))?,
+        None => 0,
+    };
+    manager.saved_passwords[account]
+        .structure_system
+        .reset_position();
+
+    let mut table = DomainTable::new();
+    if let Err(e) = table.load_from_binary(&path) {
+        diag(
+            Diag::Warning,
+            format_args!("Warning: Could not load domain table: {}", e),
+        );
+    }
+
+    Ok(Box::new(VvHandle {
+        manager,
+        account,
+        table,
+        typing: Typing::new(),
+        path,
+        _lock: lock,
+    }))
+}
+
+/// Opens the vault binary at `path` on the configuration named `account`, or the
+/// first one when `account` is NULL. Returns NULL on failure, with the negative
+/// error code in `*error` when `error` isn't NULL.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string, `account` one or NULL, and `error`
+/// writable or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vv_open(
+    path: *const c_char,
+    account: *const c_char,
+    error: *mut i32,
+) -> *mut VvHandle {
+    let opened = match (optional_str(path), optional_str(account)) {
+        (Ok(Some(path)), Ok(account)) => open(path, account),
+        (Ok(None), _) => Err(ErrorCode::BadRequest),
+        (Err(code), _) | (_, Err(code)) => Err(code),
+    };
+    match opened {
+        Ok(handle) => {
+            if !error.is_null() {
+                *error = 0;
+            }
+            Box::into_raw(handle)
+        }
+        Err(code) => {
+            if !error.is_null() {
+                *error = failure(code);
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Starts a session on `domain`, registering it at counter 0 when it is new, and
+/// fills `*info`. Returns 0, or a negative error code.
+///
+/// # Safety
+///
+/// `handle` must come from [`vv_open`] and not be closed, `domain` must be a
+/// NUL-terminated string and `info` writable or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn vv_activate(
+    handle: *mut VvHandle,
+    domain: *const c_char,
+    info: *mut VvDomainInfo,
+) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return failure(ErrorCode::BadRequest);
+    };
+    let domain = match optional_str(domain) {
+        Ok(Some(domain)) if !domain.is_empty() => domain,
+        Ok(_) => return failure(ErrorCode::MissingDomain),
+        Err(code) => return failure(code),
+    };
+    let structure = &mut handle.manager.saved_passwords[handle.account].structure_system;
+
+    let mut created = false;
+    let counter = match handle.table.get_counter(domain, structure) {
+        Some(counter) => counter,
+        None => {
+            if handle.table.set_counter(domain, 0, structure).is_err() {
+                return failure(ErrorCode::TableFull);
+            }
+            if let Err(e) = handle.table.save_to_binary(&handle.path) {
+                diag(
+                    Diag::Warning,
+                    format_args!("Warning: Could not save domain table: {}", e),
+                );
+            }
+            created = true;
+            0
+        }
+    };
+    let (rules, fixed_output) = handle.table.session_rules(domain, structure);
+    handle.typing.activate(
+        &mut handle.manager.saved_passwords[handle.account],
+        domain,
+        counter,
+        fixed_output,
+        rules,
+    );
+
+    if let Some(info) = info.as_mut() {
+        *info = VvDomainInfo {
+            saved_counter: counter,
+            active_counter: counter,
+            max_length: rules.max_length,
+            char_types: rules.char_types,
+            pin_length: fixed_output.pin_length(),
+            word_count: fixed_output.word_count(),
+            created,
+        };
+    }
+    0
+}
+
+/// Types the character `codepoint` and writes the output so far to `out_buf`,
+/// NUL-terminated. Returns its length in bytes without the NUL, or a negative
+/// error code. When the output doesn't fit in `out_len` bytes the keystroke is
+/// not taken and the result is `-32`, `buffer_too_small`.
+///
+/// # Safety
+///
+/// `handle` must come from [`vv_open`] and not be closed, and `out_buf` must be
+/// writable for `out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn vv_feed_char(
+    handle: *mut VvHandle,
+    codepoint: u32,
+    out_buf: *mut c_char,
+    out_len: usize,
+) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return failure(ErrorCode::BadRequest);
+    };
+    let fresh = handle.typing.is_fresh();
+
+    let config = &mut handle.manager.saved_passwords[handle.account];
+    match handle
+        .typing
+        .feed(config, codepoint, out_buf as *mut u8, out_len)
+    {
+        Ok(written) => {
+            if fresh {
+                handle.manager.record_use(handle.account);
+            }
+            written as i32
+        }
+        Err(code) => failure(code),
+    }
+}
+
+/// Forgets what was typed and goes back to the start of the session, the
+/// activated domain's position. Returns 0, or a negative error code.
+///
+/// # Safety
+///
+/// `handle` must come from [`vv_open`] and not be closed.
+#[no_mangle]
+pub unsafe extern "C" fn vv_reset(handle: *mut VvHandle) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return failure(ErrorCode::BadRequest);
+    };
+    handle
+        .typing
+        .reset(&mut handle.manager.saved_passwords[handle.account]);
+    0
+}
+
+/// Scrubs the session, saves the use counters and the domain table, releases the
+/// vault lock and frees the handle. NULL is ignored.
+///
+/// # Safety
+///
+/// `handle` must come from [`vv_open`], or be NULL, and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vv_close(handle: *mut VvHandle) {
+    if handle.is_null() {
+        return;
+    }
+    let mut handle = Box::from_raw(handle);
+
+    handle
+        .typing
+        .scrub(&mut handle.manager.saved_passwords[handle.account]);
+
+    if handle.table.is_dirty() {
+        let path = handle.path.clone();
+        if let Err(e) = handle.table.save_to_binary(&path) {
+            diag(
+                Diag::Warning,
+                format_args!("Warning: Could not save domain table: {}", e),
+            );
+        }
+    }
+    if let Err(e) = handle.manager.flush_usage() {
+        diag(
+            Diag::Warning,
+            format_args!("Warning: Could not save use counter: {}", e),
+        );
+    }
+    // which domains were used is the domain table's to keep quiet as well. the
+    // configurations zero themselves as they drop
+    zero_memory(handle.table.slots_mut());
+}
@@ -0,0 +1,162 @@
+//! Keeps the secrets in memory out of swap, core dumps and debuggers. The structure
+//! geometry, the feedback chain and the phrase being typed all live in ordinary heap
+//! pages, which the system may write to disk or hand to a crash dump.
+//!
+//! [`apply`] is what the binary does first in every mode, unless `--no-harden` is
+//! given: no core dumps, on Linux no ptrace or `/proc` access from other processes,
+//! and every page locked in RAM when `RLIMIT_MEMLOCK` allows it. When it doesn't, or
+//! on Windows where nothing locks a whole process, only the generation buffers are
+//! locked, see [`lock_buffer`]. Library users are left alone unless they call it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What [`apply`] managed to switch on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hardening {
+    /// `RLIMIT_CORE` is 0, or on Windows a crash neither asks to report nor dumps.
+    pub no_core_dumps: bool,
+    /// Linux only: the process is not dumpable, so other processes of the same user
+    /// can't ptrace it or read its memory either.
+    pub not_dumpable: bool,
+    /// Every page is locked in RAM, those there now and those allocated later.
+    pub memory_locked: bool,
+}
+
+static APPLIED: AtomicBool = AtomicBool::new(false);
+static MEMORY_LOCKED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+#[repr(C)]
+struct Rlimit {
+    rlim_cur: u64,
+    rlim_max: u64,
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn getrlimit(resource: i32, rlim: *mut Rlimit) -> i32;
+    fn setrlimit(resource: i32, rlim: *const Rlimit) -> i32;
+    fn mlockall(flags: i32) -> i32;
+    fn mlock(addr: *const std::ffi::c_void, len: usize) -> i32;
+}
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn prctl(option: i32, ...) -> i32;
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn SetErrorMode(mode: u32) -> u32;
+    fn VirtualLock(address: *mut std::ffi::c_void, size: usize) -> i32;
+}
+
+#[cfg(unix)]
+const RLIMIT_CORE: i32 = 4;
+#[cfg(target_os = "linux")]
+const RLIMIT_MEMLOCK: i32 = 8;
+#[cfg(all(unix, not(target_os = "linux")))]
+const RLIMIT_MEMLOCK: i32 = 6;
+#[cfg(target_os = "linux")]
+const RLIM_INFINITY: u64 = u64::MAX;
+#[cfg(all(unix, not(target_os = "linux")))]
+const RLIM_INFINITY: u64 = i64::MAX as u64;
+
+#[cfg(target_os = "linux")]
+const PR_SET_DUMPABLE: i32 = 4;
+
+/// Hardens the process, each part independently of the others, and reports what
+/// took. A lock limit too low for locking everything is raised to its hard limit
+/// first.
+#[cfg(unix)]
+pub fn apply() -> Hardening {
+    const MCL_CURRENT: i32 = 1;
+    const MCL_FUTURE: i32 = 2;
+
+    let mut hardening = Hardening::default();
+    let no_core = Rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    hardening.no_core_dumps = unsafe { setrlimit(RLIMIT_CORE, &no_core) } == 0;
+
+    #[cfg(target_os = "linux")]
+    {
+        hardening.not_dumpable = unsafe { prctl(PR_SET_DUMPABLE, 0u64, 0u64, 0u64, 0u64) } == 0;
+    }
+
+    // under a finite limit MCL_FUTURE would turn every allocation past it into a
+    // failure, so locking everything needs an unlimited one
+    let mut limit = Rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    let mut unlimited = false;
+    if unsafe { getrlimit(RLIMIT_MEMLOCK, &mut limit) } == 0 && limit.rlim_max == RLIM_INFINITY {
+        limit.rlim_cur = RLIM_INFINITY;
+        unlimited = unsafe { setrlimit(RLIMIT_MEMLOCK, &limit) } == 0;
+    }
+    hardening.memory_locked = unlimited && unsafe { mlockall(MCL_CURRENT | MCL_FUTURE) } == 0;
+
+    MEMORY_LOCKED.store(hardening.memory_locked, Ordering::SeqCst);
+    APPLIED.store(true, Ordering::SeqCst);
+    hardening
+}
+
+#[cfg(windows)]
+pub fn apply() -> Hardening {
+    const SEM_FAILCRITICALERRORS: u32 = 0x0001;
+    const SEM_NOGPFAULTERRORBOX: u32 = 0x0002;
+
+    unsafe { SetErrorMode(SEM_FAILCRITICALERRORS | SEM_NOGPFAULTERRORBOX) };
+    APPLIED.store(true, Ordering::SeqCst);
+    Hardening {
+        no_core_dumps: true,
+        not_dumpable: false,
+        memory_locked: false,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn apply() -> Hardening {
+    Hardening::default()
+}
+
+/// The current `RLIMIT_MEMLOCK` soft limit in bytes, for telling the user why
+/// memory couldn't be locked. None where there is no such limit.
+pub fn memlock_limit() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        let mut limit = Rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        if unsafe { getrlimit(RLIMIT_MEMLOCK, &mut limit) } == 0 {
+            return Some(limit.rlim_cur);
+        }
+    }
+    None
+}
+
+/// Locks the pages under `buffer`'s whole capacity in RAM, once [`apply`] has run
+/// and didn't lock everything already. Best effort: a lock the limit doesn't allow
+/// is skipped, and the pages stay locked until the process exits, as unlocking
+/// could release a page another buffer shares.
+pub(crate) fn lock_buffer<T>(buffer: &Vec<T>) {
+    if !APPLIED.load(Ordering::SeqCst) || MEMORY_LOCKED.load(Ordering::SeqCst) {
+        return;
+    }
+    let len = buffer.capacity() * std::mem::size_of::<T>();
+    if len == 0 {
+        return;
+    }
+
+    #[cfg(unix)]
+    unsafe {
+        mlock(buffer.as_ptr().cast(), len);
+    }
+    #[cfg(windows)]
+    unsafe {
+        VirtualLock(buffer.as_ptr() as *mut std::ffi::c_void, len);
+    }
+}
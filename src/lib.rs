@@ -0,0 +1,7277 @@
+// Void Vault - Password Manager
+// Copyright (C) 2025 Starwell Project
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+//
+// Alternative commercial licensing is available for organizations that
+// wish to use this software without the restrictions of the AGPL-3.0.
+// Contact: Maui_The_Magnificent@proton.me
+
+//! The Void Vault generator. A setup phrase grows a multidimensional structure,
+//! and every keystroke walks it to produce output, so the same structure and the
+//! same keys always give the same password and nothing is stored but the structure.
+//!
+//! The `void_vault` binary is the CLI, terminal and browser-host front-end over
+//! this crate. Its vault is the executable itself, see [`BinaryStorageManager`];
+//! anything else can hand [`PasswordManager::with_storage`] its own [`VaultStorage`].
+//!
+//! ```
+//! use void_vault::{generate_keystroke, Normalization, SavedPassword, StructureSystem};
+//!
+//! let pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+//! let phrase: Vec<char> = "a setup phrase".chars().collect();
+//! let mut structure = StructureSystem::new(7, 7, 17);
+//! structure.generate_structure(&phrase, &pool);
+//!
+//! let mut config = SavedPassword {
+//!     name: "main".to_string(),
+//!     description: String::new(),
+//!     structure_system: structure,
+//!     created_date: 0,
+//!     extra_chars_count: 0,
+//!     use_count: 0,
+//!     normalization: Normalization::CURRENT,
+//!     fingerprint: None,
+//!     setup_mode: None,
+//!     rekeyed_from: None,
+//! };
+//!
+//! // the output is what the last keystroke produced, from a walk started at the origin
+//! let generate = |config: &mut SavedPassword| {
+//!     config.structure_system.full_reset();
+//!     let mut feedbacks = Vec::new();
+//!     let mut last = String::new();
+//!     for key in "hunter2".chars() {
+//!         last = generate_keystroke(config, &mut feedbacks, key as u32).to_string();
+//!     }
+//!     last
+//! };
+//! let first = generate(&mut config);
+//! assert!(!first.is_empty());
+//! assert_eq!(first, generate(&mut config));
+//! ```
+
+pub mod doctor;
+pub mod ffi;
+pub mod harden;
+pub mod nfc;
+pub mod perms;
+pub mod pool;
+pub mod shred;
+pub mod wasm;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// VOID_VAULT_LOG=error|warn|info|debug logs to stderr, and VOID_VAULT_LOG_FILE also
+// appends to that file. a log line says what happened and how long it took, never
+// which domain, phrase, counter, hash or output it happened to
+#[derive(Clone, Copy)]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl LogLevel {
+    fn label(self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
+// highest level that gets written, 0 while logging is off
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(0);
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+pub fn init_logging() {
+    let level = match std::env::var("VOID_VAULT_LOG").as_deref() {
+        Ok("error") => LogLevel::Error as u8,
+        Ok("warn") => LogLevel::Warn as u8,
+        Ok("info") => LogLevel::Info as u8,
+        Ok("debug") => LogLevel::Debug as u8,
+        _ => 0,
+    };
+    LOG_LEVEL.store(level, Ordering::SeqCst);
+
+    if level == 0 {
+        return;
+    }
+
+    if let Ok(path) = std::env::var("VOID_VAULT_LOG_FILE") {
+        let mut options = fs::OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        match options.open(&path) {
+            Ok(file) => {
+                if let Ok(mut log_file) = LOG_FILE.lock() {
+                    *log_file = Some(file);
+                }
+            }
+            Err(e) => diag(
+                Diag::Warning,
+                format_args!("Warning: Could not open log file: {}", e),
+            ),
+        }
+    }
+}
+
+pub fn log(level: LogLevel, args: std::fmt::Arguments) {
+    if level as u8 > LOG_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let line = format!(
+        "[{}.{:03}] {} void_vault[{}]: {}\n",
+        now.as_secs(),
+        now.subsec_millis(),
+        level.label(),
+        std::process::id(),
+        args
+    );
+
+    let _ = io::stderr().write_all(line.as_bytes());
+    if let Ok(mut log_file) = LOG_FILE.lock() {
+        if let Some(file) = log_file.as_mut() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+// the output can hold any code point of the character set. controls (C0, DEL, C1)
+// and the JS line separators are \u escaped, astral characters go out as
+// surrogate pairs so no raw control or 4-byte sequence reaches the browser
+pub fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                escaped.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c if (c as u32) > 0xFFFF => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// messages about the program itself, as opposed to what it generates. they always
+// go to stderr so stdout carries nothing but passwords, command results and json-io
+// frames. warnings always print, notes unless --quiet, details only with --verbose
+#[derive(Clone, Copy)]
+pub enum Diag {
+    Warning,
+    Note,
+    Detail,
+}
+
+// informational chatter is left out, warnings and errors still go to stderr
+pub static QUIET: AtomicBool = AtomicBool::new(false);
+// load details, io's entropy estimate and a lock limit too low to lock memory are added
+pub static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn diag(kind: Diag, args: std::fmt::Arguments) {
+    let quiet = QUIET.load(Ordering::SeqCst);
+    let show = match kind {
+        Diag::Warning => true,
+        Diag::Note => !quiet,
+        Diag::Detail => !quiet && VERBOSE.load(Ordering::SeqCst),
+    };
+    if show {
+        eprintln!("{}", args);
+    }
+}
+
+// --no-persist, or "persist":false on ACTIVATE: the binary is left exactly as it was
+// found. writes stay in memory for the life of the process instead
+pub static NO_PERSIST: AtomicBool = AtomicBool::new(false);
+
+// --duress, or the decoy's unlock secret: from then on the process works on the
+// decoy's domain table, and the decoy stands in for every configuration, see
+// TableRegion and PasswordManager::with_decoy
+pub static DURESS: AtomicBool = AtomicBool::new(false);
+
+// whether a write to the binary should go ahead. the ones that don't are logged, so
+// it can be seen what was only kept in memory
+fn persisting(what: &str) -> bool {
+    if !NO_PERSIST.load(Ordering::SeqCst) {
+        return true;
+    }
+    log(
+        LogLevel::Info,
+        format_args!("no-persist: {} kept in memory, binary not written", what),
+    );
+    false
+}
+
+#[derive(Clone)]
+pub enum ProcessMessage {
+    BinaryUpdated(PathBuf),
+    ShutdownChild,
+    ChildReady,
+    BinaryUpdateComplete,
+}
+
+impl ProcessMessage {
+    // wire format: u32 little-endian body length, then a tag byte and its payload
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        match self {
+            ProcessMessage::BinaryUpdated(path) => {
+                body.push(1);
+                body.extend_from_slice(path.to_string_lossy().as_bytes());
+            }
+            ProcessMessage::ShutdownChild => body.push(2),
+            ProcessMessage::ChildReady => body.push(3),
+            ProcessMessage::BinaryUpdateComplete => body.push(4),
+        }
+
+        let mut bytes = Vec::with_capacity(4 + body.len());
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    fn decode(body: &[u8]) -> io::Result<Self> {
+        match body.first() {
+            Some(1) => Ok(ProcessMessage::BinaryUpdated(PathBuf::from(
+                String::from_utf8_lossy(&body[1..]).into_owned(),
+            ))),
+            Some(2) => Ok(ProcessMessage::ShutdownChild),
+            Some(3) => Ok(ProcessMessage::ChildReady),
+            Some(4) => Ok(ProcessMessage::BinaryUpdateComplete),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unknown process message",
+            )),
+        }
+    }
+}
+
+// the child keeps the terminal on stdin/stdout, so parent and child talk
+// over a private local socket instead
+#[cfg(unix)]
+type IpcStream = std::os::unix::net::UnixStream;
+
+#[cfg(windows)]
+type IpcStream = std::net::TcpStream;
+
+// wasm and the like have no second process to talk to, std's stubs there fail
+// every call at runtime
+#[cfg(not(any(unix, windows)))]
+type IpcStream = std::net::TcpStream;
+
+// windows has no private socket directory, so the child proves it was
+// spawned by us by echoing a token handed over through the environment
+#[cfg(windows)]
+const IPC_TOKEN_ENV: &str = "VOID_VAULT_IPC_TOKEN";
+
+/// 32 hex characters from [`os_random`], for a process to prove it was handed
+/// the token where a socket's permissions can't do that, as the child's and the
+/// daemon's loopback sockets on Windows.
+#[cfg(windows)]
+pub fn handshake_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    os_random(&mut bytes)?;
+    let token = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    zero_memory(&mut bytes);
+    Ok(token)
+}
+
+pub struct ProcessChannel {
+    stream: IpcStream,
+}
+
+impl ProcessChannel {
+    const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let stream = IpcStream::connect(address)?;
+        #[allow(unused_mut)]
+        let mut channel = ProcessChannel { stream };
+
+        #[cfg(windows)]
+        {
+            let token = std::env::var(IPC_TOKEN_ENV).unwrap_or_default();
+            channel.write_frame(token.as_bytes())?;
+        }
+
+        Ok(channel)
+    }
+
+    #[cfg(windows)]
+    fn write_frame(&mut self, body: &[u8]) -> io::Result<()> {
+        self.stream.write_all(&(body.len() as u32).to_le_bytes())?;
+        self.stream.write_all(body)?;
+        self.stream.flush()
+    }
+
+    fn read_frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut length_bytes = [0u8; 4];
+        self.stream.read_exact(&mut length_bytes)?;
+
+        let length = u32::from_le_bytes(length_bytes) as usize;
+        if length == 0 || length > Self::MAX_MESSAGE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Process message has an invalid length",
+            ));
+        }
+
+        let mut body = vec![0u8; length];
+        self.stream.read_exact(&mut body)?;
+        Ok(body)
+    }
+
+    pub fn send(&mut self, message: &ProcessMessage) -> io::Result<()> {
+        self.stream.write_all(&message.encode())?;
+        self.stream.flush()
+    }
+
+    pub fn recv(&mut self) -> io::Result<ProcessMessage> {
+        let body = self.read_frame()?;
+        ProcessMessage::decode(&body)
+    }
+}
+
+pub struct ProcessListener {
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+    #[cfg(unix)]
+    socket_dir: PathBuf,
+    #[cfg(windows)]
+    listener: std::net::TcpListener,
+    #[cfg(windows)]
+    token: String,
+    #[cfg(not(any(unix, windows)))]
+    listener: std::net::TcpListener,
+}
+
+impl ProcessListener {
+    const ACCEPT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+    #[cfg(unix)]
+    pub fn bind() -> io::Result<Self> {
+        use std::os::unix::fs::DirBuilderExt;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let socket_dir =
+            std::env::temp_dir().join(format!("void_vault-{}-{}", std::process::id(), nanos));
+
+        // only we may enter the directory, so only we can reach the socket
+        fs::DirBuilder::new().mode(0o700).create(&socket_dir)?;
+
+        let listener = match std::os::unix::net::UnixListener::bind(socket_dir.join("ipc.sock")) {
+            Ok(l) => l,
+            Err(e) => {
+                let _ = fs::remove_dir(&socket_dir);
+                return Err(e);
+            }
+        };
+
+        Ok(ProcessListener {
+            listener,
+            socket_dir,
+        })
+    }
+
+    #[cfg(windows)]
+    pub fn bind() -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let token = handshake_token()?;
+
+        Ok(ProcessListener { listener, token })
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    pub fn bind() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "No child process on this platform",
+        ))
+    }
+
+    pub fn address(&self) -> io::Result<String> {
+        #[cfg(unix)]
+        {
+            Ok(self
+                .socket_dir
+                .join("ipc.sock")
+                .to_string_lossy()
+                .into_owned())
+        }
+
+        #[cfg(not(unix))]
+        {
+            Ok(self.listener.local_addr()?.to_string())
+        }
+    }
+
+    pub fn configure(&self, command: &mut Command) {
+        #[cfg(windows)]
+        command.env(IPC_TOKEN_ENV, &self.token);
+
+        #[cfg(not(windows))]
+        let _ = command;
+    }
+
+    // polls so a child that dies before connecting can't hang the parent forever
+    pub fn accept(&self, child: &mut Child) -> io::Result<ProcessChannel> {
+        self.listener.set_nonblocking(true)?;
+        let started = std::time::Instant::now();
+
+        let stream = loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if let Some(status) = child.try_wait()? {
+                        return Err(io::Error::other(format!(
+                            "Child exited before connecting: {}",
+                            status
+                        )));
+                    }
+                    if started.elapsed() > Self::ACCEPT_TIMEOUT {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "Child never connected",
+                        ));
+                    }
+                    thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        stream.set_nonblocking(false)?;
+
+        #[allow(unused_mut)]
+        let mut channel = ProcessChannel { stream };
+
+        #[cfg(windows)]
+        {
+            if !constant_time_eq(&channel.read_frame()?, self.token.as_bytes()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "Child presented the wrong token",
+                ));
+            }
+        }
+
+        Ok(channel)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ProcessListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.socket_dir.join("ipc.sock"));
+        let _ = fs::remove_dir(&self.socket_dir);
+    }
+}
+
+// --profile, or VOID_VAULT_PROFILE: the configurations and domain table live in the
+// profile's vault file instead of this executable. set once, before anything loads
+pub struct Profile {
+    pub name: String,
+    vault: PathBuf,
+}
+
+pub static PROFILE: OnceLock<Profile> = OnceLock::new();
+
+// the executable as it was first asked for. once a write renames it to .bak,
+// current_exe names the .bak on Linux, and later writes would go there
+static EXECUTABLE: OnceLock<PathBuf> = OnceLock::new();
+
+// where the configurations and domain table are read and written
+pub fn vault_path() -> io::Result<PathBuf> {
+    match PROFILE.get() {
+        Some(profile) => Ok(profile.vault.clone()),
+        None => match EXECUTABLE.get() {
+            Some(executable) => Ok(executable.clone()),
+            None => {
+                let executable = std::env::current_exe()?;
+                Ok(EXECUTABLE.get_or_init(|| executable).clone())
+            }
+        },
+    }
+}
+
+// void_vault's own directory in the user's config dir. it holds the profile registry
+// and the vault files made for profiles
+pub fn config_dir() -> io::Result<PathBuf> {
+    #[cfg(windows)]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+
+    #[cfg(not(windows))]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    base.map(|dir| dir.join("void_vault")).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "There is no config directory to keep profiles in",
+        )
+    })
+}
+
+// the profiles file in the config dir, one name=path line per profile
+pub struct ProfileRegistry {
+    pub profiles: Vec<(String, PathBuf)>,
+}
+
+impl ProfileRegistry {
+    fn path() -> io::Result<PathBuf> {
+        Ok(config_dir()?.join("profiles"))
+    }
+
+    // no file yet is no profiles yet
+    pub fn load() -> io::Result<Self> {
+        let contents = match fs::read_to_string(Self::path()?) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let profiles = contents
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(name, _)| is_profile_name(name.trim()))
+            .map(|(name, path)| (name.trim().to_string(), PathBuf::from(path.trim())))
+            .collect();
+
+        Ok(ProfileRegistry { profiles })
+    }
+
+    pub fn find(&self, name: &str) -> Option<&PathBuf> {
+        self.profiles
+            .iter()
+            .find(|(profile, _)| profile == name)
+            .map(|(_, path)| path)
+    }
+
+    // written aside and renamed over, so a failed write can't lose the other profiles
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()?;
+        fs::create_dir_all(config_dir()?)?;
+
+        let mut contents = String::new();
+        for (name, vault) in &self.profiles {
+            contents.push_str(&format!("{}={}\n", name, vault.display()));
+        }
+
+        let temp_path = path.with_extension("new");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, &path)
+    }
+}
+
+// names end up in the registry and in vault file names
+pub fn is_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// once at startup. an unknown profile is an error rather than a quiet fallback to
+// the executable's own vault
+pub fn select_profile(name: &str) -> io::Result<()> {
+    let registry = ProfileRegistry::load()?;
+    let vault = registry.find(name).cloned().ok_or_else(|| {
+        ErrorCode::UnknownProfile.error(format!("No profile named '{}', see profiles list", name))
+    })?;
+    if !vault.is_file() {
+        return Err(ErrorCode::StorageFailure.error(format!(
+            "Profile '{}' points at {}, which is not there",
+            name,
+            vault.display()
+        )));
+    }
+
+    diag(
+        Diag::Detail,
+        format_args!("Profile {}: {}", name, vault.display()),
+    );
+    let _ = PROFILE.set(Profile {
+        name: name.to_string(),
+        vault,
+    });
+    Ok(())
+}
+
+// a new profile's vault file. the markers are seeded from a file's first 1024
+// bytes, so it starts with a random header of its own: every profile gets different
+// markers, and one profile's data can't be parsed out of another's file
+pub fn create_profile_vault(path: &Path, name: &str) -> io::Result<()> {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+
+    let mut header = Wiped(Vec::with_capacity(1024));
+    header.extend_from_slice(b"VOID_VAULT_PROFILE\0");
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    while header.len() < 1024 {
+        let random = RandomState::new().hash_one((std::process::id(), header.len()));
+        header.extend_from_slice(&random.to_le_bytes());
+    }
+    header.truncate(1024);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(&header)
+}
+
+/// Where a [`PasswordManager`] keeps its serialized configurations.
+///
+/// The vault binary itself is [`BinaryStorageManager`]; [`MemoryStorage`] keeps
+/// everything in the process, for tests and front-ends that persist elsewhere.
+pub trait VaultStorage {
+    /// Saves a configuration, replacing any stored under the same name.
+    fn store(&mut self, name: String, description: String, data: &[u8]) -> io::Result<()>;
+
+    /// A configuration's serialized bytes and description, borrowed from the storage.
+    fn retrieve(&self, name: &str) -> io::Result<Option<(&[u8], &str)>>;
+
+    /// Every stored configuration's name and description.
+    fn list_all(&self) -> Vec<(String, String)>;
+
+    /// The file other processes writing the same storage lock, if there is one.
+    fn lock_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// [`VaultStorage`] that only lives as long as the process.
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Vec<(String, String, Vec<u8>)>,
+}
+
+impl Drop for MemoryStorage {
+    fn drop(&mut self) {
+        for (_, _, data) in self.entries.iter_mut() {
+            data.wipe();
+        }
+    }
+}
+
+impl VaultStorage for MemoryStorage {
+    fn store(&mut self, name: String, description: String, data: &[u8]) -> io::Result<()> {
+        match self.entries.iter_mut().find(|(n, _, _)| *n == name) {
+            Some(entry) => {
+                entry.2.wipe();
+                entry.1 = description;
+                entry.2.extend_from_slice(data);
+            }
+            None => self.entries.push((name, description, data.to_vec())),
+        }
+        Ok(())
+    }
+
+    fn retrieve(&self, name: &str) -> io::Result<Option<(&[u8], &str)>> {
+        Ok(self
+            .entries
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, description, data)| (data.as_slice(), description.as_str())))
+    }
+
+    fn list_all(&self) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .map(|(name, description, _)| (name.clone(), description.clone()))
+            .collect()
+    }
+}
+
+/// [`VaultStorage`] in the vault binary: configurations appended after the
+/// executable between markers derived from its header.
+pub struct BinaryStorageManager {
+    executable_path: PathBuf,
+    in_memory_cache: HashMap<String, Vec<u8>>,
+    metadata_cache: HashMap<String, String>,
+    binary_modified: bool,
+    parent_mode: bool,
+    channel: Option<Arc<Mutex<ProcessChannel>>>,
+    // derived from the header on first use, then reused until store replaces the file
+    markers: std::cell::OnceCell<StorageMarkers>,
+    header_reads: std::cell::Cell<usize>,
+}
+
+/// What [`BinaryStorageManager::probe`] finds in a vault file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VaultLayout {
+    /// The file ends with its section marker. Without it no configuration is read.
+    pub section_marker: bool,
+    /// How many configuration records start in the file.
+    pub configurations: usize,
+    /// A domain table follows the section marker. Without one the table marker is
+    /// only found in the code, and the domains would be read from there.
+    pub domain_table: bool,
+}
+
+// section, start, end, name and description markers
+type StorageMarkers = (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>);
+
+// the cache holds serialized structures, so it must not outlive us in freed memory
+impl Drop for BinaryStorageManager {
+    fn drop(&mut self) {
+        for data in self.in_memory_cache.values_mut() {
+            data.wipe();
+        }
+    }
+}
+
+impl BinaryStorageManager {
+    // markers are unless reserved, in the zone at the end.
+    // they are also based only on executable properties that won't change
+    fn generate_markers(&self) -> StorageMarkers {
+        // and now we read the binary header to generate portable markers
+        self.header_reads.set(self.header_reads.get() + 1);
+        let mut header_bytes = Vec::new();
+        if let Ok(mut file) = File::open(&self.executable_path) {
+            let mut buffer = [0u8; 1024];
+            if let Ok(n) = file.read(&mut buffer) {
+                header_bytes.extend_from_slice(&buffer[..n]);
+            }
+        }
+
+        // use only the binary header hash for the marker seed
+        let header_seed = {
+            let mut header_hasher = std::collections::hash_map::DefaultHasher::new();
+            header_bytes.hash(&mut header_hasher);
+            header_hasher.finish()
+        };
+
+        let mut rng_state = header_seed;
+
+        let generate_marker = |prefix: &[u8], length: usize, rng: &mut u64| -> Vec<u8> {
+            let mut marker = Vec::with_capacity(prefix.len() + length + 1);
+            marker.extend_from_slice(prefix);
+
+            for _ in 0..length {
+                *rng = rng
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                marker.push((*rng % 255) as u8);
+            }
+
+            marker.push(0);
+            marker
+        };
+
+        let section_marker = generate_marker(b"\0SM", 24, &mut rng_state);
+        let start_marker = generate_marker(b"\0ST", 16, &mut rng_state);
+        let end_marker = generate_marker(b"\0EN", 16, &mut rng_state);
+        let name_marker = generate_marker(b"\0NM", 8, &mut rng_state);
+        let desc_marker = generate_marker(b"\0DS", 8, &mut rng_state);
+
+        (
+            section_marker,
+            start_marker,
+            end_marker,
+            name_marker,
+            desc_marker,
+        )
+    }
+
+    // the markers, reading the header only the first time after opening or a store
+    fn markers(&self) -> StorageMarkers {
+        self.markers.get_or_init(|| self.generate_markers()).clone()
+    }
+
+    /// How many times the executable's header has been read to derive the markers,
+    /// once per open and again only after a store replaced the file.
+    pub fn header_reads(&self) -> usize {
+        self.header_reads.get()
+    }
+
+    fn new(parent_mode: bool, channel: Option<Arc<Mutex<ProcessChannel>>>) -> io::Result<Self> {
+        Self::open(vault_path()?, parent_mode, channel)
+    }
+
+    // a manager for the file with nothing read from it yet
+    fn unopened(
+        executable_path: PathBuf,
+        parent_mode: bool,
+        channel: Option<Arc<Mutex<ProcessChannel>>>,
+    ) -> Self {
+        BinaryStorageManager {
+            executable_path,
+            in_memory_cache: HashMap::new(),
+            metadata_cache: HashMap::new(),
+            binary_modified: false,
+            parent_mode,
+            channel,
+            markers: std::cell::OnceCell::new(),
+            header_reads: std::cell::Cell::new(0),
+        }
+    }
+
+    /// The vault at `executable_path`, which need not be the running executable.
+    pub fn open(
+        executable_path: PathBuf,
+        parent_mode: bool,
+        channel: Option<Arc<Mutex<ProcessChannel>>>,
+    ) -> io::Result<Self> {
+        let mut manager = Self::unopened(executable_path, parent_mode, channel);
+
+        if manager.ensure_end_marker()? {
+            manager.binary_modified = true;
+
+            if !parent_mode {
+                manager.signal_binary_update()?;
+            }
+        }
+
+        manager.load_all_passwords()?;
+
+        Ok(manager)
+    }
+
+    fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        if needle.len() > haystack.len() {
+            return None;
+        }
+
+        for i in 0..=haystack.len() - needle.len() {
+            if haystack[i..i + needle.len()] == needle[..] {
+                return Some(i);
+            }
+        }
+        None
+    }
+    // really important, as without it, the binary would break
+    fn ensure_end_marker(&self) -> io::Result<bool> {
+        let (section_marker, _, _, _, _) = self.markers();
+
+        let file = match File::open(&self.executable_path) {
+            Ok(f) => f,
+            Err(e) => {
+                diag(
+                    Diag::Warning,
+                    format_args!("WARNING: Could not open executable: {}", e),
+                );
+                return Err(e);
+            }
+        };
+
+        let file_size = file.metadata()?.len();
+
+        if file_size < section_marker.len() as u64 {
+            return self.append_end_marker();
+        }
+
+        let mut file = File::open(&self.executable_path)?;
+        let mut end_bytes = vec![0u8; section_marker.len()];
+        file.seek(SeekFrom::End(-(section_marker.len() as i64)))?;
+        file.read_exact(&mut end_bytes)?;
+
+        if end_bytes == section_marker {
+            Ok(false)
+        } else {
+            self.append_end_marker()
+        }
+    }
+
+    fn append_end_marker(&self) -> io::Result<bool> {
+        if !persisting("section marker") {
+            return Ok(false);
+        }
+
+        // an empty domain table too during initial setup, one already there is kept
+        self.writer().rewrite(|image| {
+            image.tables.get_or_insert_with(VaultTables::empty);
+            Ok(())
+        })?;
+
+        log(
+            LogLevel::Info,
+            format_args!("storage: wrote section marker and domain table"),
+        );
+        Ok(true)
+    }
+
+    // the one way the file is written, see VaultWriter
+    fn writer(&self) -> VaultWriter {
+        let (section_marker, _, _, _, _) = self.markers();
+        VaultWriter {
+            path: self.executable_path.clone(),
+            section_marker,
+        }
+    }
+
+    /// What the vault at `executable_path` holds, read without opening it: opening
+    /// appends whatever markers the file is missing.
+    pub fn probe(executable_path: PathBuf) -> io::Result<VaultLayout> {
+        let manager = Self::unopened(executable_path, true, None);
+        let (section_marker, start_marker, _, _, _) = manager.markers();
+
+        let mut file = File::open(&manager.executable_path)?;
+        let mut buffer = Wiped(Vec::new());
+        file.read_to_end(&mut buffer)?;
+
+        let mut configurations = 0;
+        let mut pos = 0;
+        while let Some(offset) = Self::find_pattern(&buffer[pos..], &start_marker) {
+            configurations += 1;
+            pos += offset + start_marker.len();
+        }
+
+        // the table marker is in the code too, the table itself comes after the
+        // first section marker
+        let first_section = Self::find_pattern(&buffer, &section_marker);
+        let domain_table = match (first_section, DomainTable::find_table(&mut file)?) {
+            (Some(section), Some(table)) => {
+                table > section as u64 && table + DomainTable::BYTES as u64 <= buffer.len() as u64
+            }
+            _ => false,
+        };
+
+        Ok(VaultLayout {
+            section_marker: buffer.ends_with(&section_marker),
+            configurations,
+            domain_table,
+        })
+    }
+
+    /// Ends the vault at `executable_path` with its section marker again, for
+    /// configurations that lost it. The file as it was is kept as `.bak`.
+    pub fn append_section_marker(executable_path: PathBuf) -> io::Result<()> {
+        Self::unopened(executable_path, true, None)
+            .writer()
+            .rewrite(|_| Ok(()))
+    }
+
+    /// Gives the vault at `executable_path` an empty domain table, after which it
+    /// ends with its section marker. The file as it was is kept as `.bak`.
+    pub fn append_domain_table(executable_path: PathBuf) -> io::Result<()> {
+        Self::unopened(executable_path, true, None)
+            .writer()
+            .rewrite(|image| {
+                image.tables.get_or_insert_with(VaultTables::empty);
+                Ok(())
+            })
+    }
+    // instead of allowing for multiple passwords and such,
+    // this slimed down version stores one multidimentional structure and its
+    // zones.
+    fn load_all_passwords(&mut self) -> io::Result<()> {
+        for data in self.in_memory_cache.values_mut() {
+            data.wipe();
+        }
+        self.in_memory_cache.clear();
+        self.metadata_cache.clear();
+
+        let (section_marker, start_marker, end_marker, name_marker, desc_marker) = self.markers();
+
+        let file = match File::open(&self.executable_path) {
+            Ok(f) => f,
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        let file_size = file.metadata()?.len();
+
+        if file_size < section_marker.len() as u64 {
+            return Ok(());
+        }
+
+        let mut file = File::open(&self.executable_path)?;
+        let mut end_bytes = vec![0u8; section_marker.len()];
+        file.seek(SeekFrom::End(-(section_marker.len() as i64)))?;
+
+        file.read_exact(&mut end_bytes)?;
+
+        if end_bytes != section_marker {
+            return Ok(());
+        }
+        // new buffer to load said geometric structure
+        let mut file = File::open(&self.executable_path)?;
+        let mut buffer = Wiped(Vec::new());
+
+        file.read_to_end(&mut buffer)?;
+
+        let section_end_pos = buffer.len();
+        let section_start_pos = section_end_pos - section_marker.len();
+
+        let search_begin = section_start_pos.saturating_sub(10 * 1024 * 1024);
+
+        let mut current_pos = search_begin;
+
+        let mut password_positions = Vec::new();
+
+        while current_pos < section_start_pos {
+            match Self::find_pattern(&buffer[current_pos..section_start_pos], &start_marker) {
+                Some(offset) => {
+                    let start_pos = current_pos + offset;
+                    password_positions.push(start_pos);
+                    current_pos = start_pos + start_marker.len();
+                }
+                None => break,
+            }
+        }
+
+        for &start_pos in &password_positions {
+            let current_pos = start_pos + start_marker.len();
+
+            let name_marker_pos =
+                match Self::find_pattern(&buffer[current_pos..section_start_pos], &name_marker) {
+                    Some(offset) => current_pos + offset + name_marker.len(),
+                    None => {
+                        diag(
+                            Diag::Warning,
+                            format_args!(
+                                "WARNING: Start marker without NAME marker at position {}",
+                                start_pos
+                            ),
+                        );
+                        continue;
+                    }
+                };
+
+            let name_end_pos = match buffer[name_marker_pos..section_start_pos]
+                .iter()
+                .position(|&b| b == 0)
+            {
+                Some(offset) => name_marker_pos + offset,
+                None => {
+                    diag(
+                        Diag::Warning,
+                        format_args!(
+                            "WARNING: Name without end marker at position {}",
+                            name_marker_pos
+                        ),
+                    );
+                    continue;
+                }
+            };
+
+            // your geometric name
+            let name_bytes = &buffer[name_marker_pos..name_end_pos];
+            let name = match std::str::from_utf8(name_bytes) {
+                Ok(s) => s.trim().to_string(),
+                Err(_) => {
+                    diag(
+                        Diag::Warning,
+                        format_args!(
+                            "WARNING: Invalid UTF-8 in name at position {}",
+                            name_marker_pos
+                        ),
+                    );
+                    continue;
+                }
+            };
+
+            let data_start = name_end_pos + 1;
+
+            let end_pos_marker =
+                match Self::find_pattern(&buffer[data_start..section_start_pos], &end_marker) {
+                    Some(offset) => data_start + offset,
+                    None => {
+                        diag(
+                            Diag::Warning,
+                            format_args!("WARNING: No end marker found after name '{}'", name),
+                        );
+                        continue;
+                    }
+                };
+
+            let data_size = end_pos_marker - data_start;
+            if data_size == 0 {
+                diag(
+                    Diag::Warning,
+                    format_args!("WARNING: Empty data section for '{}'", name),
+                );
+                continue;
+            }
+
+            if data_size > 10 * 1024 * 1024 {
+                diag(
+                    Diag::Warning,
+                    format_args!(
+                        "WARNING: Data section too large for '{}': {} bytes",
+                        name, data_size
+                    ),
+                );
+                continue;
+            }
+
+            let desc_marker_start = end_pos_marker + end_marker.len();
+
+            let desc_marker_pos = match Self::find_pattern(
+                &buffer[desc_marker_start..section_start_pos],
+                &desc_marker,
+            ) {
+                Some(offset) => desc_marker_start + offset + desc_marker.len(),
+                None => {
+                    diag(
+                        Diag::Warning,
+                        format_args!("WARNING: End marker without DESC marker for '{}'", name),
+                    );
+                    continue;
+                }
+            };
+
+            let desc_end_pos = match buffer[desc_marker_pos..section_start_pos]
+                .iter()
+                .position(|&b| b == 0)
+            {
+                Some(offset) => desc_marker_pos + offset,
+                None => {
+                    diag(
+                        Diag::Warning,
+                        format_args!("WARNING: Description without end marker for '{}'", name),
+                    );
+                    continue;
+                }
+            };
+
+            let desc_bytes = &buffer[desc_marker_pos..desc_end_pos];
+            let description = match std::str::from_utf8(desc_bytes) {
+                Ok(s) => s.trim().to_string(),
+                Err(_) => {
+                    diag(
+                        Diag::Warning,
+                        format_args!(
+                            "WARNING: Invalid UTF-8 in description at position {}",
+                            desc_marker_pos
+                        ),
+                    );
+                    continue;
+                }
+            };
+
+            let data = buffer[data_start..end_pos_marker].to_vec();
+            self.in_memory_cache.insert(name.clone(), data);
+            self.metadata_cache.insert(name.clone(), description);
+        }
+
+        Ok(())
+    }
+
+    // ensures the binary gets updated
+    fn signal_binary_update(&self) -> io::Result<()> {
+        if let Some(channel) = &self.channel {
+            let mut channel = channel
+                .lock()
+                .map_err(|_| io::Error::other("Process channel lock poisoned"))?;
+
+            if let Err(e) =
+                channel.send(&ProcessMessage::BinaryUpdated(self.executable_path.clone()))
+            {
+                diag(
+                    Diag::Warning,
+                    format_args!("Failed to signal binary update: {}", e),
+                );
+                return Err(io::Error::other("Failed to signal binary update"));
+            }
+
+            // hold still until the parent has acknowledged the new binary
+            loop {
+                if let ProcessMessage::BinaryUpdateComplete = channel.recv()? {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// the vault file as its writer sees it: the executable's code, then behind the first
+// section marker the domain tables and the configuration records, and the section
+// marker again at the end
+struct VaultImage {
+    code: Wiped<Vec<u8>>,
+    tables: Option<VaultTables>,
+    records: Wiped<Vec<u8>>,
+}
+
+// the real domain table's region, and the decoy's right after it once it was written
+struct VaultTables {
+    real: Vec<u8>,
+    decoy: Option<Vec<u8>>,
+}
+
+impl VaultTables {
+    fn empty() -> Self {
+        VaultTables {
+            real: vec![0; DomainTable::BYTES],
+            decoy: None,
+        }
+    }
+}
+
+// serializes the writes within the process. callers hold the vault's InstanceLock
+// as well, which keeps other processes out, but isn't reentrant to take here
+static VAULT_WRITES: Mutex<()> = Mutex::new(());
+
+// the one way the vault file is written, whether configurations or a domain table
+// changed. every rewrite reads the file afresh, so neither writer drops what the
+// other wrote, and goes through a synced, verified .new copy, with the file as it
+// was kept as .bak
+struct VaultWriter {
+    path: PathBuf,
+    section_marker: Vec<u8>,
+}
+
+impl VaultWriter {
+    fn lock() -> MutexGuard<'static, ()> {
+        VAULT_WRITES
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // the file taken apart. the tables are where the readers find them, after the
+    // last table marker, and anything else after the first section marker is records
+    fn read(&self) -> io::Result<VaultImage> {
+        let mut buffer = Wiped(Vec::new());
+        File::open(&self.path)?.read_to_end(&mut buffer)?;
+
+        let Some(section) = BinaryStorageManager::find_pattern(&buffer, &self.section_marker)
+        else {
+            return Ok(VaultImage {
+                code: buffer,
+                tables: None,
+                records: Wiped(Vec::new()),
+            });
+        };
+        let body_start = section + self.section_marker.len();
+        let body_end = if buffer.ends_with(&self.section_marker) {
+            (buffer.len() - self.section_marker.len()).max(body_start)
+        } else {
+            buffer.len()
+        };
+        let body = &buffer[body_start..body_end];
+
+        let marker = DOMAIN_TABLE_START_MARKER;
+        let (tables, records) = match body.windows(marker.len()).rposition(|w| w == marker) {
+            None => (None, Wiped(body.to_vec())),
+            Some(at) => {
+                let real_start = at + marker.len();
+                let real = body
+                    .get(real_start..real_start + DomainTable::BYTES)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Domain table region is cut short",
+                        )
+                    })?
+                    .to_vec();
+                let mut end = real_start + DomainTable::BYTES;
+
+                let decoy_start = end + DECOY_TABLE_START_MARKER.len();
+                let decoy = match body.get(decoy_start..decoy_start + DomainTable::BYTES) {
+                    Some(decoy) if body[end..].starts_with(DECOY_TABLE_START_MARKER) => {
+                        end = decoy_start + DomainTable::BYTES;
+                        Some(decoy.to_vec())
+                    }
+                    _ => None,
+                };
+
+                let mut records = Wiped(body[..at].to_vec());
+                records.extend_from_slice(&body[end..]);
+                (Some(VaultTables { real, decoy }), records)
+            }
+        };
+
+        Ok(VaultImage {
+            code: Wiped(buffer[..section].to_vec()),
+            tables,
+            records,
+        })
+    }
+
+    // reads the file, lets `edit` change it and writes it back
+    fn rewrite(&self, edit: impl FnOnce(&mut VaultImage) -> io::Result<()>) -> io::Result<()> {
+        let _writing = Self::lock();
+        let mut image = self.read()?;
+        edit(&mut image)?;
+        self.write(&image)
+    }
+
+    // writes `bytes` straight over `region`'s table. Ok(false) when the region isn't
+    // all there to overwrite, and an error when the file can't be opened for writing,
+    // which a running executable usually can't be
+    fn patch_table(&self, region: TableRegion, bytes: &[u8]) -> io::Result<bool> {
+        let _writing = Self::lock();
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)?;
+        let Some(offset) = DomainTable::find_region(&mut file, region)? else {
+            return Ok(false);
+        };
+        if file.metadata()?.len() < offset + bytes.len() as u64 {
+            return Ok(false);
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(bytes)?;
+        file.sync_data()?;
+        Ok(true)
+    }
+
+    // the file as code, section marker, tables, records and the section marker again,
+    // through a .new copy renamed over it once it is all on disk
+    fn write(&self, image: &VaultImage) -> io::Result<()> {
+        let temp_path = self.path.with_extension("new");
+        let mut new_file = match File::create(&temp_path) {
+            Ok(f) => f,
+            Err(e) => {
+                diag(
+                    Diag::Warning,
+                    format_args!("ERROR: Failed to create temp binary: {}", e),
+                );
+                return Err(e);
+            }
+        };
+
+        new_file.write_all(&image.code)?;
+        new_file.write_all(&self.section_marker)?;
+        if let Some(tables) = &image.tables {
+            new_file.write_all(DOMAIN_TABLE_START_MARKER)?;
+            new_file.write_all(&tables.real)?;
+            if let Some(decoy) = &tables.decoy {
+                new_file.write_all(DECOY_TABLE_START_MARKER)?;
+                new_file.write_all(decoy)?;
+            }
+        }
+        new_file.write_all(&image.records)?;
+        new_file.write_all(&self.section_marker)?;
+        shred::verify_copy(new_file, &temp_path)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(&self.path)?;
+            let mode = metadata.permissions().mode();
+            let mut perms = fs::metadata(&temp_path)?.permissions();
+            perms.set_mode(mode);
+            fs::set_permissions(&temp_path, perms)?;
+        }
+
+        let backup_path = self.path.with_extension("bak");
+        shred::retire_backup(&backup_path);
+
+        if let Err(e) = fs::rename(&self.path, &backup_path) {
+            diag(
+                Diag::Warning,
+                format_args!("ERROR: Failed to create backup: {}", e),
+            );
+            log(
+                LogLevel::Error,
+                format_args!("storage: backup before rewrite failed: {}", e),
+            );
+            return Err(e);
+        }
+
+        if let Err(e) = fs::rename(&temp_path, &self.path) {
+            diag(
+                Diag::Warning,
+                format_args!("ERROR: Failed to replace binary: {}", e),
+            );
+            log(
+                LogLevel::Error,
+                format_args!("storage: replacing the executable failed: {}", e),
+            );
+
+            let _ = fs::rename(&backup_path, &self.path);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl VaultStorage for BinaryStorageManager {
+    fn store(&mut self, name: String, description: String, data: &[u8]) -> io::Result<()> {
+        if let Some(mut previous) = self.in_memory_cache.insert(name.clone(), data.to_vec()) {
+            previous.wipe();
+        }
+        self.metadata_cache
+            .insert(name.clone(), description.clone());
+
+        if !persisting("configuration") {
+            return Ok(());
+        }
+
+        let (_, start_marker, end_marker, name_marker, desc_marker) = self.markers();
+
+        // every configuration once, each store writes them all anew
+        let mut names: Vec<&String> = self.in_memory_cache.keys().collect();
+        names.sort();
+        let mut records = Wiped(Vec::new());
+        for name in names {
+            let description = self
+                .metadata_cache
+                .get(name)
+                .map_or("No description", String::as_str);
+
+            records.extend_from_slice(&start_marker);
+
+            records.extend_from_slice(&name_marker);
+            records.extend_from_slice(name.as_bytes());
+            records.push(0);
+
+            records.extend_from_slice(&self.in_memory_cache[name]);
+
+            records.extend_from_slice(&end_marker);
+
+            records.extend_from_slice(&desc_marker);
+            records.extend_from_slice(description.as_bytes());
+            records.push(0);
+        }
+
+        self.writer().rewrite(|image| {
+            image.records = records;
+            Ok(())
+        })?;
+
+        self.binary_modified = true;
+        // a different file now, derive the markers from it afresh when next needed
+        self.markers = std::cell::OnceCell::new();
+        log(
+            LogLevel::Info,
+            format_args!(
+                "storage: rewrote executable with {} configurations",
+                self.in_memory_cache.len()
+            ),
+        );
+
+        if !self.parent_mode {
+            self.signal_binary_update()?;
+        }
+
+        Ok(())
+    }
+
+    // finds the geometry
+    fn retrieve(&self, name: &str) -> io::Result<Option<(&[u8], &str)>> {
+        if let Some(data) = self.in_memory_cache.get(name) {
+            let description = self
+                .metadata_cache
+                .get(name)
+                .map_or("No description", String::as_str);
+
+            return Ok(Some((data.as_slice(), description)));
+        }
+
+        Ok(None)
+    }
+
+    fn list_all(&self) -> Vec<(String, String)> {
+        let mut all_passwords = Vec::new();
+
+        for name in self.in_memory_cache.keys() {
+            let description = self
+                .metadata_cache
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| "No description".to_string());
+
+            all_passwords.push((name.clone(), description));
+        }
+
+        all_passwords
+    }
+
+    fn lock_path(&self) -> Option<&Path> {
+        Some(&self.executable_path)
+    }
+}
+
+// what this process is, written into the lock file after its pid so whoever waits
+// on the lock can say what holds it
+static LOCK_HOLDER: OnceLock<&'static str> = OnceLock::new();
+
+// one vault binary, one writer. the browser host holds this for its whole
+// lifetime, CLI commands that write take it only for as long as they need
+pub struct InstanceLock {
+    path: PathBuf,
+    #[cfg(unix)]
+    _file: File,
+    #[cfg(windows)]
+    mutex: *mut std::ffi::c_void,
+}
+
+impl InstanceLock {
+    // how long the browser host waits for a CLI write to finish before giving up
+    pub const HOST_WAIT: std::time::Duration = std::time::Duration::from_secs(5);
+    // how long a writing CLI command waits before reporting the vault as busy
+    pub const WRITE_WAIT: std::time::Duration = std::time::Duration::from_secs(2);
+
+    /// Names what this process is in the lock files it writes, "the browser host"
+    /// or "the daemon". Set once, before the first lock; a process that never sets
+    /// it is a void_vault command.
+    pub fn set_holder(holder: &'static str) {
+        let _ = LOCK_HOLDER.set(holder);
+    }
+
+    #[cfg(any(unix, windows))]
+    fn holder() -> &'static str {
+        LOCK_HOLDER.get().copied().unwrap_or("a void_vault command")
+    }
+
+    pub fn acquire(
+        executable_path: &std::path::Path,
+        wait: std::time::Duration,
+    ) -> io::Result<Self> {
+        let path = executable_path.with_extension("lock");
+        let started = std::time::Instant::now();
+
+        loop {
+            if let Some(lock) = Self::try_acquire(&path, executable_path)? {
+                return Ok(lock);
+            }
+
+            if started.elapsed() >= wait {
+                // "<pid> <holder>", or only the pid from a build that didn't say
+                let contents = fs::read_to_string(&path).unwrap_or_default();
+                let (pid, holder) = contents
+                    .trim()
+                    .split_once(' ')
+                    .unwrap_or((contents.trim(), ""));
+                let holder = if holder.is_empty() {
+                    "another Void Vault process"
+                } else {
+                    holder
+                };
+
+                let message = match pid.parse::<u32>() {
+                    Ok(pid) => format!("Vault is in use by {} (pid {})", holder, pid),
+                    Err(_) => format!("Vault is in use by {}", holder),
+                };
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, message));
+            }
+
+            thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
+
+    #[cfg(unix)]
+    fn try_acquire(
+        path: &std::path::Path,
+        _executable_path: &std::path::Path,
+    ) -> io::Result<Option<Self>> {
+        use std::os::unix::fs::MetadataExt;
+        use std::os::unix::io::AsRawFd;
+
+        extern "C" {
+            fn flock(fd: i32, operation: i32) -> i32;
+        }
+
+        const LOCK_EX: i32 = 2;
+        const LOCK_NB: i32 = 4;
+
+        loop {
+            let mut file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+
+            if unsafe { flock(file.as_raw_fd(), LOCK_EX | LOCK_NB) } != 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::WouldBlock {
+                    return Ok(None);
+                }
+                return Err(error);
+            }
+
+            // the previous holder unlinks the file on exit, if that happened
+            // between our open and flock we locked a file nobody else will see
+            let ours = file.metadata()?;
+            match fs::metadata(path) {
+                Ok(on_disk) if on_disk.ino() == ours.ino() && on_disk.dev() == ours.dev() => {}
+                _ => continue,
+            }
+
+            file.set_len(0)?;
+            write!(file, "{} {}", std::process::id(), Self::holder())?;
+            file.flush()?;
+
+            return Ok(Some(InstanceLock {
+                path: path.to_path_buf(),
+                _file: file,
+            }));
+        }
+    }
+
+    #[cfg(windows)]
+    #[allow(non_snake_case)]
+    fn try_acquire(
+        path: &std::path::Path,
+        executable_path: &std::path::Path,
+    ) -> io::Result<Option<Self>> {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn CreateMutexW(
+                lpMutexAttributes: *mut std::ffi::c_void,
+                bInitialOwner: i32,
+                lpName: *const u16,
+            ) -> *mut std::ffi::c_void;
+            fn WaitForSingleObject(hHandle: *mut std::ffi::c_void, dwMilliseconds: u32) -> u32;
+            fn CloseHandle(hObject: *mut std::ffi::c_void) -> i32;
+        }
+
+        const WAIT_OBJECT_0: u32 = 0x0000;
+        const WAIT_ABANDONED: u32 = 0x0080;
+
+        let name = Self::mutex_name(executable_path);
+
+        unsafe {
+            let mutex = CreateMutexW(std::ptr::null_mut(), 0, name.as_ptr());
+            if mutex.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+
+            // abandoned means the last holder died without releasing, that is ours now
+            match WaitForSingleObject(mutex, 0) {
+                WAIT_OBJECT_0 | WAIT_ABANDONED => {}
+                _ => {
+                    CloseHandle(mutex);
+                    return Ok(None);
+                }
+            }
+
+            let lock = InstanceLock {
+                path: path.to_path_buf(),
+                mutex,
+            };
+            fs::write(path, format!("{} {}", std::process::id(), Self::holder()))?;
+
+            Ok(Some(lock))
+        }
+    }
+
+    // the named mutex every process locking the vault at executable_path opens
+    #[cfg(windows)]
+    fn mutex_name(executable_path: &std::path::Path) -> Vec<u16> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        executable_path.hash(&mut hasher);
+        format!("Local\\void_vault-{:016x}", hasher.finish())
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect()
+    }
+
+    /// Whether another process holds the lock on the vault at `executable_path`
+    /// right now. Only looks: the lock file isn't created or written.
+    #[cfg(unix)]
+    pub fn is_held(executable_path: &std::path::Path) -> bool {
+        use std::os::unix::io::AsRawFd;
+
+        extern "C" {
+            fn flock(fd: i32, operation: i32) -> i32;
+        }
+
+        const LOCK_SH: i32 = 1;
+        const LOCK_NB: i32 = 4;
+
+        let Ok(file) = File::open(executable_path.with_extension("lock")) else {
+            return false;
+        };
+        // a shared lock is refused while a writer holds it, and let go with the file
+        unsafe { flock(file.as_raw_fd(), LOCK_SH | LOCK_NB) != 0 }
+    }
+
+    /// Whether another process holds the lock on the vault at `executable_path`
+    /// right now. Only looks: the mutex is opened, never created.
+    #[cfg(windows)]
+    #[allow(non_snake_case)]
+    pub fn is_held(executable_path: &std::path::Path) -> bool {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn OpenMutexW(
+                dwDesiredAccess: u32,
+                bInheritHandle: i32,
+                lpName: *const u16,
+            ) -> *mut std::ffi::c_void;
+            fn WaitForSingleObject(hHandle: *mut std::ffi::c_void, dwMilliseconds: u32) -> u32;
+            fn ReleaseMutex(hMutex: *mut std::ffi::c_void) -> i32;
+            fn CloseHandle(hObject: *mut std::ffi::c_void) -> i32;
+        }
+
+        const SYNCHRONIZE: u32 = 0x0010_0000;
+        const MUTEX_MODIFY_STATE: u32 = 0x0001;
+        const WAIT_TIMEOUT: u32 = 0x0102;
+
+        let name = Self::mutex_name(executable_path);
+        unsafe {
+            let mutex = OpenMutexW(SYNCHRONIZE | MUTEX_MODIFY_STATE, 0, name.as_ptr());
+            if mutex.is_null() {
+                return false;
+            }
+            let held = WaitForSingleObject(mutex, 0) == WAIT_TIMEOUT;
+            if !held {
+                ReleaseMutex(mutex);
+            }
+            CloseHandle(mutex);
+            held
+        }
+    }
+
+    // nothing else runs beside us to hold it
+    #[cfg(not(any(unix, windows)))]
+    pub fn is_held(_executable_path: &std::path::Path) -> bool {
+        false
+    }
+
+    // nothing else runs beside us to lock out
+    #[cfg(not(any(unix, windows)))]
+    fn try_acquire(
+        path: &std::path::Path,
+        _executable_path: &std::path::Path,
+    ) -> io::Result<Option<Self>> {
+        Ok(Some(InstanceLock {
+            path: path.to_path_buf(),
+        }))
+    }
+}
+
+impl Drop for InstanceLock {
+    // the OS drops the lock itself if we crash, a clean exit also removes the file
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+
+        #[cfg(windows)]
+        unsafe {
+            #[link(name = "kernel32")]
+            extern "system" {
+                fn ReleaseMutex(handle: *mut std::ffi::c_void) -> i32;
+                fn CloseHandle(handle: *mut std::ffi::c_void) -> i32;
+            }
+
+            ReleaseMutex(self.mutex);
+            CloseHandle(self.mutex);
+        }
+    }
+}
+
+// Domain table marker for binary layout
+const DOMAIN_TABLE_START_MARKER: &[u8] = b"__DOMAIN_TABLE_START__";
+
+// the decoy's table follows the real one directly, behind a marker of its own. it is
+// only written once something is stored in it
+const DECOY_TABLE_START_MARKER: &[u8] = b"__DECOY_TABLE_START__";
+
+// Domain slot entry (70 bytes total: 64 + 2 + 2 + 1 + 1). fixed_output took over what
+// used to be the padding byte, so older tables load unchanged, see clear_legacy_padding
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct DomainSlot {
+    pub domain_hash: [u8; 64], // Geometric hash of domain name
+    pub counter: u16,          // Password version counter (0-65535)
+    pub max_length: u16,       // Maximum password length (0 = unlimited)
+    pub char_types: u8,        // Bit flags for allowed character types
+    pub fixed_output: u8,      // PIN or passphrase instead of a password, see FixedOutput
+}
+
+impl DomainSlot {
+    pub const EMPTY: Self = DomainSlot {
+        domain_hash: [0u8; 64],
+        counter: 0,
+        max_length: 0,
+        char_types: 127, // All 7 character types enabled by default
+        fixed_output: 0,
+    };
+
+    // the top bit of char_types is not a character type: it asks for digits on the
+    // end of the domain's generated username
+    pub const USERNAME_DIGITS: u8 = 1 << 7;
+
+    // nor is the top bit of max_length a length: it keeps confusable characters out
+    // of the domain's output, see pool::avoid_confusable
+    pub const NO_CONFUSABLES: u16 = 1 << 15;
+
+    // and the three bits below it name the re-key of the setup the domain generates
+    // with, see StructureSystem::lineage. what is left for the length is LENGTH
+    pub const LINEAGE: u16 = 0b111 << 12;
+    pub const LINEAGE_SHIFT: u32 = 12;
+    pub const LENGTH: u16 = (1 << 12) - 1;
+
+    pub fn lineage(&self) -> u8 {
+        ((self.max_length & Self::LINEAGE) >> Self::LINEAGE_SHIFT) as u8
+    }
+
+    pub fn positions(&self) -> Positions {
+        Positions::from_byte(self.fixed_output)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domain_hash == [0u8; 64]
+    }
+
+    // slots holding host settings rather than a domain
+    pub fn is_reserved(&self) -> bool {
+        self.domain_hash == RATE_LIMITS_SLOT_HASH
+            || self.domain_hash == TABLE_FORMAT_SLOT_HASH
+            || self.domain_hash[..16] == UNLOCK_SLOT_TAG
+            || self.domain_hash[..16] == DECOY_SLOT_TAG
+            || self.domain_hash[..16] == CODE_SEAL_SLOT_TAG
+    }
+}
+
+// PIN lengths and passphrase word counts a domain can ask for
+pub const PIN_LENGTHS: std::ops::RangeInclusive<u8> = 4..=16;
+pub const WORD_COUNTS: std::ops::RangeInclusive<u8> = 3..=12;
+
+// what a domain generates when it isn't a password. stored in the slot's fixed_output
+// byte: 0 for a password, the digit count for a PIN, WORDS | the word count for a
+// passphrase. a password with positional rules has POSITIONS | their bits instead,
+// see Positions
+#[derive(Clone, Copy, PartialEq)]
+pub enum FixedOutput {
+    Password,
+    Pin(u8),
+    Words(u8),
+}
+
+impl FixedOutput {
+    const WORDS: u8 = 1 << 7;
+    const POSITIONS: u8 = 1 << 6;
+
+    // lowercase, and the class with '-' in it
+    pub const WORD_CHAR_TYPES: u8 = 1 | 1 << 4;
+
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => FixedOutput::Password,
+            b if b & Self::WORDS != 0 => FixedOutput::Words(b & !Self::WORDS),
+            b if b & Self::POSITIONS != 0 => FixedOutput::Password,
+            b => FixedOutput::Pin(b),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            FixedOutput::Password => 0,
+            FixedOutput::Pin(digits) => digits,
+            FixedOutput::Words(words) => Self::WORDS | words,
+        }
+    }
+
+    pub fn pin_length(self) -> u8 {
+        match self {
+            FixedOutput::Pin(digits) => digits,
+            _ => 0,
+        }
+    }
+
+    pub fn word_count(self) -> u8 {
+        match self {
+            FixedOutput::Words(words) => words,
+            _ => 0,
+        }
+    }
+
+    // digits or words in a (possibly unfinished) output, and how many make it whole
+    pub fn progress(self, output: &str) -> (usize, usize) {
+        match self {
+            FixedOutput::Password => (0, 0),
+            FixedOutput::Pin(digits) => (output.len(), digits as usize),
+            FixedOutput::Words(words) => (
+                output.split('-').filter(|w| !w.is_empty()).count(),
+                words as usize,
+            ),
+        }
+    }
+}
+
+/// Where a password's characters may be, for sites that want it to start with a
+/// letter or turn down a symbol or space at either end. A letter is an ASCII one,
+/// and a symbol anything but an ASCII letter or digit, as those sites check them.
+/// The characters at the output's ends that don't fit are moved by their place in
+/// the character set, see [`pool::spread_to`]. Only a password has them, a PIN or
+/// passphrase has none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Positions {
+    pub starts_with_letter: bool,
+    pub no_leading_symbol: bool,
+    pub no_trailing_symbol: bool,
+}
+
+impl Positions {
+    pub const NONE: Self = Positions {
+        starts_with_letter: false,
+        no_leading_symbol: false,
+        no_trailing_symbol: false,
+    };
+
+    // their bits under FixedOutput::POSITIONS
+    const STARTS_WITH_LETTER: u8 = 1;
+    const NO_LEADING_SYMBOL: u8 = 1 << 1;
+    const NO_TRAILING_SYMBOL: u8 = 1 << 2;
+
+    fn from_byte(byte: u8) -> Self {
+        if byte & FixedOutput::WORDS != 0 || byte & FixedOutput::POSITIONS == 0 {
+            return Positions::NONE;
+        }
+        Positions {
+            starts_with_letter: byte & Self::STARTS_WITH_LETTER != 0,
+            no_leading_symbol: byte & Self::NO_LEADING_SYMBOL != 0,
+            no_trailing_symbol: byte & Self::NO_TRAILING_SYMBOL != 0,
+        }
+    }
+
+    // a password's fixed_output byte, 0 without any
+    fn to_byte(self) -> u8 {
+        let bits = [
+            (self.starts_with_letter, Self::STARTS_WITH_LETTER),
+            (self.no_leading_symbol, Self::NO_LEADING_SYMBOL),
+            (self.no_trailing_symbol, Self::NO_TRAILING_SYMBOL),
+        ]
+        .iter()
+        .filter(|&&(on, _)| on)
+        .fold(0, |bits, &(_, bit)| bits | bit);
+        if bits == 0 {
+            0
+        } else {
+            FixedOutput::POSITIONS | bits
+        }
+    }
+
+    pub fn is_none(self) -> bool {
+        self == Positions::NONE
+    }
+
+    // whether the first, or the last, character is held to anything
+    fn holds_first(self) -> bool {
+        self.starts_with_letter || self.no_leading_symbol
+    }
+
+    fn holds_last(self) -> bool {
+        self.no_trailing_symbol
+    }
+
+    pub fn fits_first(self, ch: char) -> bool {
+        (!self.starts_with_letter || ch.is_ascii_alphabetic())
+            && (!self.no_leading_symbol || ch.is_ascii_alphanumeric())
+    }
+
+    pub fn fits_last(self, ch: char) -> bool {
+        !self.no_trailing_symbol || ch.is_ascii_alphanumeric()
+    }
+}
+
+// present once a table has stored a PIN length. before that fixed_output was padding
+// and may hold anything, so tables without this slot get it zeroed on load. its
+// counter is 2 once a slot was stamped with a re-key: before that max_length could
+// be up to 32767 and the lineage bits were length, see clear_legacy_padding
+const TABLE_FORMAT_SLOT_HASH: [u8; 64] = [0xFE; 64];
+const TABLE_FORMAT_LINEAGE: u16 = 2;
+
+// the rate limits live in a slot of their own so they persist with the table without
+// changing its layout. hash_domain can't realistically produce this value
+const RATE_LIMITS_SLOT_HASH: [u8; 64] = [0xFF; 64];
+
+// the unlock secret's verifier has a slot of its own too. its hash is this tag, then
+// the salt and the stretched secret, so what's stored comes from the secret and a
+// random salt alone and says nothing about any structure
+const UNLOCK_SLOT_TAG: [u8; 16] = [0xFD; 16];
+
+// which configuration is the decoy, kept in the decoy's table: this tag, then the
+// rest of a hash of the configuration's name
+const DECOY_SLOT_TAG: [u8; 16] = [0xFC; 16];
+
+// what the code of the vault file hashed to when it was sealed, kept in the real
+// table: this tag, then the digest, see DomainTable::check_code_seal
+const CODE_SEAL_SLOT_TAG: [u8; 16] = [0xFB; 16];
+
+fn decoy_slot_hash(name: &str) -> [u8; 64] {
+    let mut hash = mix_domain(0, name);
+    hash[..16].copy_from_slice(&DECOY_SLOT_TAG);
+    hash
+}
+
+/// Which of the vault's two domain tables: the one everything normally works on, or
+/// the decoy's, kept alongside it so that under duress counters and rules persist
+/// just the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableRegion {
+    Real,
+    Decoy,
+}
+
+impl TableRegion {
+    /// The decoy's under [`DURESS`], else the real one.
+    pub fn active() -> Self {
+        if DURESS.load(Ordering::SeqCst) {
+            TableRegion::Decoy
+        } else {
+            TableRegion::Real
+        }
+    }
+}
+
+// what checks an unlock secret, and the wrong ones tried in a row since the last
+// right one. stored in the unlock slot, the failures in its counter
+#[derive(Clone, Copy)]
+pub struct UnlockVerifier {
+    pub salt: [u8; 16],
+    pub verifier: [u8; 32],
+    pub failures: u16,
+}
+
+// how fast the json-io host answers: a token bucket refilled at per_second up to
+// burst, and a lockout once lockout_strikes messages in a row were refused
+#[derive(Clone, Copy)]
+pub struct RateLimits {
+    pub per_second: u16,
+    pub burst: u16,
+    pub lockout_strikes: u8,
+}
+
+impl RateLimits {
+    // several times what even a fast typist sends, build with VOID_VAULT_RATE_*
+    // to change the defaults. per_second 0 turns limiting off
+    pub fn defaults() -> Self {
+        RateLimits {
+            per_second: option_env!("VOID_VAULT_RATE_PER_SECOND")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(40),
+            burst: option_env!("VOID_VAULT_RATE_BURST")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(120),
+            lockout_strikes: option_env!("VOID_VAULT_RATE_LOCKOUT_STRIKES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+        }
+    }
+}
+
+/// What [`RateLimiter::check`] makes of one message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateDecision {
+    Allowed,
+    Limited,
+    // refused until the cool-down ends, then only an ACTIVATE gets through
+    LockedOut { retry_after: u64 },
+}
+
+/// The json-io host's token bucket and lockout, see [`RateLimits`].
+pub struct RateLimiter {
+    limits: RateLimits,
+    tokens: f64,
+    last_refill: std::time::Instant,
+    strikes: u32,
+    lockouts: u32,
+    cooldown_until: Option<std::time::Instant>,
+    needs_activate: bool,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        RateLimiter {
+            limits,
+            tokens: limits.burst as f64,
+            last_refill: std::time::Instant::now(),
+            strikes: 0,
+            lockouts: 0,
+            cooldown_until: None,
+            needs_activate: false,
+        }
+    }
+
+    /// Whether one more message gets through now.
+    pub fn check(&mut self, is_activate: bool) -> RateDecision {
+        self.check_at(std::time::Instant::now(), is_activate)
+    }
+
+    /// [`RateLimiter::check`] at `now`, which never goes back.
+    pub fn check_at(&mut self, now: std::time::Instant, is_activate: bool) -> RateDecision {
+        if self.limits.per_second == 0 {
+            return RateDecision::Allowed;
+        }
+
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.last_refill = now;
+
+        let burst = self.limits.burst.max(1) as f64;
+        self.tokens = (self.tokens + elapsed * self.limits.per_second as f64).min(burst);
+
+        if let Some(until) = self.cooldown_until {
+            if now < until {
+                let retry_after = (until - now).as_secs_f64().ceil() as u64;
+                return RateDecision::LockedOut { retry_after };
+            }
+            self.cooldown_until = None;
+        }
+
+        if self.needs_activate && !is_activate {
+            return RateDecision::LockedOut { retry_after: 0 };
+        }
+
+        // a full bucket means things have been quiet, so earlier lockouts are forgiven
+        if self.tokens >= burst {
+            self.lockouts = 0;
+        }
+
+        if self.tokens < 1.0 {
+            self.strikes += 1;
+            if self.strikes >= self.limits.lockout_strikes.max(1) as u32 {
+                // 1s, 2s, 4s ... capped at about four minutes
+                let cooldown = 1u64 << self.lockouts.min(8);
+                self.lockouts += 1;
+                self.strikes = 0;
+                self.needs_activate = true;
+                self.cooldown_until = Some(now + std::time::Duration::from_secs(cooldown));
+                return RateDecision::LockedOut {
+                    retry_after: cooldown,
+                };
+            }
+            return RateDecision::Limited;
+        }
+
+        self.tokens -= 1.0;
+        self.strikes = 0;
+        if is_activate {
+            self.needs_activate = false;
+        }
+        RateDecision::Allowed
+    }
+}
+
+/// The vault's 512 domain slots: counters, output rules and PIN or passphrase
+/// settings, keyed by the geometric hash of each domain name.
+///
+/// A process works on the table loaded from its vault binary through
+/// [`DomainTable::shared`]. A table of its own, from [`DomainTable::new`], is
+/// what tests and other front-ends use when nothing should be written anywhere.
+///
+/// ```
+/// use void_vault::{DomainTable, StructureSystem};
+///
+/// let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+/// let mut structure = StructureSystem::new(42, 7, 17);
+/// structure.generate_structure(&[], &pool);
+/// let mut table = DomainTable::new();
+/// assert_eq!(table.get_counter("example.com", &mut structure), None);
+///
+/// table.increment_counter("example.com", &mut structure).unwrap();
+/// assert_eq!(table.get_counter("example.com", &mut structure), Some(1));
+/// assert!(table.is_dirty());
+/// ```
+pub struct DomainTable {
+    slots: [DomainSlot; 512],
+    // changes that are not yet written to the binary
+    dirty: bool,
+}
+
+// the table this process loaded from its vault binary, see DomainTable::shared
+static DOMAIN_TABLE: Mutex<DomainTable> = Mutex::new(DomainTable::new());
+
+/// The process-wide [`DomainTable`], locked for as long as the handle lives.
+///
+/// Take it for one statement at a time: a second [`DomainTable::shared`] while
+/// the first handle is still alive waits on itself.
+pub struct DomainTableHandle(MutexGuard<'static, DomainTable>);
+
+impl std::ops::Deref for DomainTableHandle {
+    type Target = DomainTable;
+
+    fn deref(&self) -> &DomainTable {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for DomainTableHandle {
+    fn deref_mut(&mut self) -> &mut DomainTable {
+        &mut self.0
+    }
+}
+
+/// Why a domain table change was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainTableError {
+    /// All 512 slots are in use.
+    Full,
+    /// Positional rules asked of a domain that makes a PIN or passphrase.
+    NotPassword,
+}
+
+impl std::fmt::Display for DomainTableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DomainTableError::Full => f.write_str("Domain table full (512 slots)"),
+            DomainTableError::NotPassword => {
+                f.write_str("Positional rules only apply to a password, not a PIN or passphrase")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DomainTableError {}
+
+impl From<DomainTableError> for io::Error {
+    fn from(e: DomainTableError) -> Self {
+        io::Error::other(e)
+    }
+}
+
+impl Default for DomainTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DomainTable {
+    // what the table takes up in the binary, after DOMAIN_TABLE_START_MARKER
+    const BYTES: usize = std::mem::size_of::<[DomainSlot; 512]>();
+
+    /// An empty table, with every slot free.
+    pub const fn new() -> Self {
+        DomainTable {
+            slots: [DomainSlot::EMPTY; 512],
+            dirty: false,
+        }
+    }
+
+    /// Locks the table loaded from this process's vault binary.
+    pub fn shared() -> DomainTableHandle {
+        DomainTableHandle(DOMAIN_TABLE.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Like [`DomainTable::shared`], but `None` instead of waiting when the table
+    /// is already locked, for cleanup that may run while it is.
+    pub fn try_shared() -> Option<DomainTableHandle> {
+        match DOMAIN_TABLE.try_lock() {
+            Ok(guard) => Some(DomainTableHandle(guard)),
+            Err(std::sync::TryLockError::Poisoned(e)) => Some(DomainTableHandle(e.into_inner())),
+            Err(std::sync::TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Whether there are changes not yet written by [`DomainTable::save_to_binary`].
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Marks the table changed, for callers that edit [`DomainTable::slots_mut`].
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn slots(&self) -> &[DomainSlot; 512] {
+        &self.slots
+    }
+
+    pub fn slots_mut(&mut self) -> &mut [DomainSlot; 512] {
+        &mut self.slots
+    }
+
+    // Find slot index for a domain hash. every slot is compared in full, so the time
+    // taken says neither where the match is nor how close the others came
+    fn find_slot_by_hash(&self, hash: &[u8; 64]) -> Option<usize> {
+        let mut found = None;
+        for (idx, slot) in self.slots.iter().enumerate() {
+            let matched = constant_time_eq(&slot.domain_hash, hash) & !slot.is_empty();
+            if matched && found.is_none() {
+                found = Some(idx);
+            }
+        }
+        found
+    }
+
+    pub fn get_counter(&self, domain: &str, structure: &mut StructureSystem) -> Option<u16> {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash)
+            .map(|idx| self.slots[idx].counter)
+    }
+
+    pub fn set_counter(
+        &mut self,
+        domain: &str,
+        counter: u16,
+        structure: &mut StructureSystem,
+    ) -> Result<(), DomainTableError> {
+        let hash = structure.hash_domain(domain);
+
+        if let Some(idx) = self.find_slot_by_hash(&hash) {
+            self.slots[idx].counter = counter;
+            self.dirty = true;
+            return Ok(());
+        }
+
+        if structure.lineage > 0 {
+            self.mark_format(TABLE_FORMAT_LINEAGE)?;
+        }
+        // Find first empty slot
+        if let Some(idx) = self.slots.iter().position(|s| s.is_empty()) {
+            self.slots[idx] = DomainSlot {
+                domain_hash: hash,
+                counter,
+                // 0 = unlimited, if you need to. the lineage of who registered it
+                max_length: (structure.lineage as u16) << DomainSlot::LINEAGE_SHIFT,
+                char_types: 127, // Default: all types enabled
+                fixed_output: 0,
+            };
+            self.dirty = true;
+            Ok(())
+        } else {
+            Err(DomainTableError::Full)
+        }
+    }
+
+    pub fn increment_counter(
+        &mut self,
+        domain: &str,
+        structure: &mut StructureSystem,
+    ) -> Result<u16, DomainTableError> {
+        let current = self.get_counter(domain, structure).unwrap_or(0);
+        let new_counter = current.saturating_add(1);
+        self.set_counter(domain, new_counter, structure)?;
+        Ok(new_counter)
+    }
+
+    /// Frees the domain's slot. `false` when it wasn't registered.
+    pub fn remove(&mut self, domain: &str, structure: &mut StructureSystem) -> bool {
+        let hash = structure.hash_domain(domain);
+
+        match self.find_slot_by_hash(&hash) {
+            Some(idx) => {
+                self.slots[idx] = DomainSlot::EMPTY;
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the registered domains as the CLI and the interactive menu list them
+    pub fn print_list(&self) {
+        let mut count = 0;
+        println!("Registered domains (hashes only - domain names cannot be reversed):\n");
+        for (i, slot) in self.slots.iter().enumerate() {
+            if !slot.is_empty() && !slot.is_reserved() {
+                let hex: String = slot.domain_hash[..16]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                match slot.lineage() {
+                    0 => println!("Slot {}: {}... → v{}", i, hex, slot.counter),
+                    lineage => println!(
+                        "Slot {}: {}... → v{}, re-key {}",
+                        i, hex, slot.counter, lineage
+                    ),
+                }
+                count += 1;
+            }
+        }
+        println!("\nTotal: {} domains registered", count);
+    }
+
+    pub fn get_rate_limits(&self) -> RateLimits {
+        self.find_slot_by_hash(&RATE_LIMITS_SLOT_HASH)
+            .map(|idx| {
+                let slot = &self.slots[idx];
+                RateLimits {
+                    per_second: slot.counter,
+                    burst: slot.max_length,
+                    lockout_strikes: slot.char_types,
+                }
+            })
+            .unwrap_or_else(RateLimits::defaults)
+    }
+
+    pub fn set_rate_limits(&mut self, limits: RateLimits) -> Result<(), DomainTableError> {
+        let idx = match self.find_slot_by_hash(&RATE_LIMITS_SLOT_HASH) {
+            Some(idx) => idx,
+            None => match self.slots.iter().position(|s| s.is_empty()) {
+                Some(idx) => idx,
+                None => return Err(DomainTableError::Full),
+            },
+        };
+
+        self.slots[idx] = DomainSlot {
+            domain_hash: RATE_LIMITS_SLOT_HASH,
+            counter: limits.per_second,
+            max_length: limits.burst,
+            char_types: limits.lockout_strikes,
+            fixed_output: 0,
+        };
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn find_unlock_slot(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.domain_hash[..16] == UNLOCK_SLOT_TAG)
+    }
+
+    // None when the vault has no unlock secret
+    pub fn get_unlock(&self) -> Option<UnlockVerifier> {
+        self.find_unlock_slot().map(|idx| {
+            let slot = &self.slots[idx];
+            let mut salt = [0u8; 16];
+            let mut verifier = [0u8; 32];
+            salt.copy_from_slice(&slot.domain_hash[16..32]);
+            verifier.copy_from_slice(&slot.domain_hash[32..]);
+            UnlockVerifier {
+                salt,
+                verifier,
+                failures: slot.counter,
+            }
+        })
+    }
+
+    // None removes the unlock secret
+    pub fn set_unlock(&mut self, unlock: Option<UnlockVerifier>) -> Result<(), DomainTableError> {
+        let existing = self.find_unlock_slot();
+        let Some(unlock) = unlock else {
+            if let Some(idx) = existing {
+                self.slots[idx] = DomainSlot::EMPTY;
+                self.dirty = true;
+            }
+            return Ok(());
+        };
+        let idx = match existing {
+            Some(idx) => idx,
+            None => match self.slots.iter().position(|s| s.is_empty()) {
+                Some(idx) => idx,
+                None => return Err(DomainTableError::Full),
+            },
+        };
+
+        let mut domain_hash = [0u8; 64];
+        domain_hash[..16].copy_from_slice(&UNLOCK_SLOT_TAG);
+        domain_hash[16..32].copy_from_slice(&unlock.salt);
+        domain_hash[32..].copy_from_slice(&unlock.verifier);
+        self.slots[idx] = DomainSlot {
+            domain_hash,
+            counter: unlock.failures,
+            ..DomainSlot::EMPTY
+        };
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// The digest of everything in the vault binary at `path` before its domain
+    /// table: the executable, for the binary's own vault, a copy of it or a header
+    /// for a profile's. None when there is no table yet.
+    pub fn code_digest(path: &std::path::Path) -> io::Result<Option<[u8; 48]>> {
+        let mut file = File::open(path)?;
+        let Some(table_offset) = Self::find_table(&mut file)? else {
+            return Ok(None);
+        };
+        let code_len = table_offset - DOMAIN_TABLE_START_MARKER.len() as u64;
+        let mut code = Vec::with_capacity(code_len as usize);
+        file.seek(SeekFrom::Start(0))?;
+        file.take(code_len).read_to_end(&mut code)?;
+
+        let mut digest = [0u8; 48];
+        digest.copy_from_slice(&mix_bytes(0, &code)[..48]);
+        Ok(Some(digest))
+    }
+
+    pub fn code_seal(&self) -> Option<[u8; 48]> {
+        self.slots
+            .iter()
+            .find(|slot| slot.domain_hash[..16] == CODE_SEAL_SLOT_TAG)
+            .map(|slot| {
+                let mut digest = [0u8; 48];
+                digest.copy_from_slice(&slot.domain_hash[16..]);
+                digest
+            })
+    }
+
+    // None removes the seal
+    pub fn set_code_seal(&mut self, digest: Option<[u8; 48]>) -> Result<(), DomainTableError> {
+        let existing = self
+            .slots
+            .iter()
+            .position(|slot| slot.domain_hash[..16] == CODE_SEAL_SLOT_TAG);
+        let Some(digest) = digest else {
+            if let Some(idx) = existing {
+                self.slots[idx] = DomainSlot::EMPTY;
+                self.dirty = true;
+            }
+            return Ok(());
+        };
+        let idx = match existing {
+            Some(idx) => idx,
+            None => match self.slots.iter().position(|s| s.is_empty()) {
+                Some(idx) => idx,
+                None => return Err(DomainTableError::Full),
+            },
+        };
+
+        let mut domain_hash = [0u8; 64];
+        domain_hash[..16].copy_from_slice(&CODE_SEAL_SLOT_TAG);
+        domain_hash[16..].copy_from_slice(&digest);
+        self.slots[idx] = DomainSlot {
+            domain_hash,
+            ..DomainSlot::EMPTY
+        };
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Whether the code of the vault binary at `path` is still what this table was
+    /// sealed with. None when it never was. A check the code makes of itself only
+    /// catches a change that doesn't know about it, as a swapped or damaged binary
+    /// that kept the vault section.
+    pub fn check_code_seal(&self, path: &std::path::Path) -> io::Result<Option<bool>> {
+        let Some(seal) = self.code_seal() else {
+            return Ok(None);
+        };
+        Ok(Some(Self::code_digest(path)? == Some(seal)))
+    }
+
+    // whether `name` is the configuration this table marks as the decoy. only the
+    // decoy's table marks one
+    pub fn is_decoy(&self, name: &str) -> bool {
+        self.find_slot_by_hash(&decoy_slot_hash(name)).is_some()
+    }
+
+    // None leaves no configuration marked
+    pub fn set_decoy(&mut self, name: Option<&str>) -> Result<(), DomainTableError> {
+        let existing = self
+            .slots
+            .iter()
+            .position(|slot| slot.domain_hash[..16] == DECOY_SLOT_TAG);
+        let Some(name) = name else {
+            if let Some(idx) = existing {
+                self.slots[idx] = DomainSlot::EMPTY;
+                self.dirty = true;
+            }
+            return Ok(());
+        };
+        let idx = match existing {
+            Some(idx) => idx,
+            None => match self.slots.iter().position(|s| s.is_empty()) {
+                Some(idx) => idx,
+                None => return Err(DomainTableError::Full),
+            },
+        };
+
+        self.slots[idx] = DomainSlot {
+            domain_hash: decoy_slot_hash(name),
+            ..DomainSlot::EMPTY
+        };
+        self.dirty = true;
+        Ok(())
+    }
+
+    // get password rules for domain
+    pub fn get_rules(&self, domain: &str, structure: &mut StructureSystem) -> Option<(u16, u8)> {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash).map(|idx| {
+            let slot = &self.slots[idx];
+            (
+                slot.max_length & DomainSlot::LENGTH,
+                slot.char_types & !DomainSlot::USERNAME_DIGITS,
+            )
+        })
+    }
+
+    pub fn get_fixed_output(&self, domain: &str, structure: &mut StructureSystem) -> FixedOutput {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash)
+            .map(|idx| FixedOutput::from_byte(self.slots[idx].fixed_output))
+            .unwrap_or(FixedOutput::Password)
+    }
+
+    // the rules a session on the domain reports, with what it generates. a PIN is
+    // pin_length digits and a passphrase lowercase words, whatever the stored rules say
+    pub fn get_output_rules(
+        &self,
+        domain: &str,
+        structure: &mut StructureSystem,
+    ) -> (u16, u8, FixedOutput) {
+        let fixed_output = self.get_fixed_output(domain, structure);
+        match fixed_output {
+            FixedOutput::Pin(digits) => (digits as u16, OutputRules::DIGITS, fixed_output),
+            FixedOutput::Words(_) => (0, FixedOutput::WORD_CHAR_TYPES, fixed_output),
+            FixedOutput::Password => {
+                let (max_length, char_types) =
+                    self.get_rules(domain, structure).unwrap_or((0, 127));
+                (max_length, char_types, fixed_output)
+            }
+        }
+    }
+
+    // the rules a session on the domain generates under, see get_output_rules
+    pub fn session_rules(
+        &self,
+        domain: &str,
+        structure: &mut StructureSystem,
+    ) -> (OutputRules, FixedOutput) {
+        let (max_length, char_types, fixed_output) = self.get_output_rules(domain, structure);
+        let rules = OutputRules {
+            max_length,
+            char_types,
+            no_confusables: self.no_confusables(domain, structure),
+            positions: self.positions(domain, structure),
+        };
+        (rules, fixed_output)
+    }
+
+    // Password turns PIN or passphrase output off, a password keeps its positional
+    // rules and a PIN or passphrase drops them. creates the entry like set_rules, and
+    // the format slot the first time so the byte is trusted from then on
+    pub fn set_fixed_output(
+        &mut self,
+        domain: &str,
+        fixed_output: FixedOutput,
+        structure: &mut StructureSystem,
+    ) -> Result<(), DomainTableError> {
+        if self.get_counter(domain, structure).is_none() {
+            self.set_counter(domain, 0, structure)?;
+        }
+        let hash = structure.hash_domain(domain);
+
+        self.mark_format(1)?;
+        if let Some(idx) = self.find_slot_by_hash(&hash) {
+            let slot = &mut self.slots[idx];
+            if fixed_output != FixedOutput::from_byte(slot.fixed_output) {
+                slot.fixed_output = fixed_output.to_byte();
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // a PIN or passphrase domain has none
+    pub fn positions(&self, domain: &str, structure: &mut StructureSystem) -> Positions {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash)
+            .map(|idx| self.slots[idx].positions())
+            .unwrap_or(Positions::NONE)
+    }
+
+    // creates the entry, like set_rules, when the domain isn't there yet and they
+    // change. NotPassword on a PIN or passphrase domain
+    pub fn set_positions(
+        &mut self,
+        domain: &str,
+        positions: Positions,
+        structure: &mut StructureSystem,
+    ) -> Result<(), DomainTableError> {
+        if self.positions(domain, structure) == positions {
+            return Ok(());
+        }
+        if self.get_fixed_output(domain, structure) != FixedOutput::Password {
+            return Err(DomainTableError::NotPassword);
+        }
+        if self.get_counter(domain, structure).is_none() {
+            self.set_counter(domain, 0, structure)?;
+        }
+        let hash = structure.hash_domain(domain);
+
+        self.mark_format(1)?;
+        if let Some(idx) = self.find_slot_by_hash(&hash) {
+            self.slots[idx].fixed_output = positions.to_byte();
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    // adds the format slot, or raises its counter, to at least `format`
+    fn mark_format(&mut self, format: u16) -> Result<(), DomainTableError> {
+        if let Some(idx) = self.find_slot_by_hash(&TABLE_FORMAT_SLOT_HASH) {
+            let slot = &mut self.slots[idx];
+            slot.counter = slot.counter.max(format);
+            return Ok(());
+        }
+        let Some(idx) = self.slots.iter().position(|s| s.is_empty()) else {
+            return Err(DomainTableError::Full);
+        };
+        self.slots[idx] = DomainSlot {
+            domain_hash: TABLE_FORMAT_SLOT_HASH,
+            counter: format,
+            ..DomainSlot::EMPTY
+        };
+        self.dirty = true;
+        Ok(())
+    }
+
+    // a table written before fixed_output existed has whatever was in the padding byte,
+    // and one written before re-keys may have lengths over LENGTH. those are capped
+    // at it, which no output reaches anyway
+    fn clear_legacy_padding(&mut self) {
+        let format = self
+            .find_slot_by_hash(&TABLE_FORMAT_SLOT_HASH)
+            .map(|idx| self.slots[idx].counter);
+        for slot in self.slots.iter_mut() {
+            if format.is_none() {
+                slot.fixed_output = 0;
+            }
+            if format.is_none_or(|format| format < TABLE_FORMAT_LINEAGE)
+                && slot.max_length & DomainSlot::LINEAGE != 0
+            {
+                slot.max_length =
+                    (slot.max_length & DomainSlot::NO_CONFUSABLES) | DomainSlot::LENGTH;
+            }
+        }
+    }
+
+    pub fn username_digits(&self, domain: &str, structure: &mut StructureSystem) -> bool {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash)
+            .map(|idx| self.slots[idx].char_types & DomainSlot::USERNAME_DIGITS != 0)
+            .unwrap_or(false)
+    }
+
+    // creates the entry, like set_rules, when the domain isn't there yet
+    pub fn set_username_digits(
+        &mut self,
+        domain: &str,
+        digits: bool,
+        structure: &mut StructureSystem,
+    ) -> Result<(), DomainTableError> {
+        if self.get_counter(domain, structure).is_none() {
+            self.set_counter(domain, 0, structure)?;
+        }
+        let hash = structure.hash_domain(domain);
+
+        if let Some(idx) = self.find_slot_by_hash(&hash) {
+            if digits {
+                self.slots[idx].char_types |= DomainSlot::USERNAME_DIGITS;
+            } else {
+                self.slots[idx].char_types &= !DomainSlot::USERNAME_DIGITS;
+            }
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    pub fn no_confusables(&self, domain: &str, structure: &mut StructureSystem) -> bool {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash)
+            .map(|idx| self.slots[idx].max_length & DomainSlot::NO_CONFUSABLES != 0)
+            .unwrap_or(false)
+    }
+
+    // creates the entry, like set_rules, when the domain isn't there yet
+    pub fn set_no_confusables(
+        &mut self,
+        domain: &str,
+        no_confusables: bool,
+        structure: &mut StructureSystem,
+    ) -> Result<(), DomainTableError> {
+        if self.get_counter(domain, structure).is_none() {
+            self.set_counter(domain, 0, structure)?;
+        }
+        let hash = structure.hash_domain(domain);
+
+        if let Some(idx) = self.find_slot_by_hash(&hash) {
+            if no_confusables {
+                self.slots[idx].max_length |= DomainSlot::NO_CONFUSABLES;
+            } else {
+                self.slots[idx].max_length &= !DomainSlot::NO_CONFUSABLES;
+            }
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    // which re-key of the setup the domain generates with, None when it isn't there
+    pub fn lineage(&self, domain: &str, structure: &mut StructureSystem) -> Option<u8> {
+        let hash = structure.hash_domain(domain);
+
+        self.find_slot_by_hash(&hash)
+            .map(|idx| self.slots[idx].lineage())
+    }
+
+    /// Moves a domain over to `structure`'s re-key of the setup, at the next counter
+    /// so it starts from a fresh password. The new counter, or `None` when the
+    /// domain isn't registered.
+    pub fn migrate(
+        &mut self,
+        domain: &str,
+        structure: &mut StructureSystem,
+    ) -> Result<Option<u16>, DomainTableError> {
+        let hash = structure.hash_domain(domain);
+
+        let Some(idx) = self.find_slot_by_hash(&hash) else {
+            return Ok(None);
+        };
+        if structure.lineage > 0 {
+            self.mark_format(TABLE_FORMAT_LINEAGE)?;
+        }
+        let slot = &mut self.slots[idx];
+        slot.counter = slot.counter.saturating_add(1);
+        slot.max_length = (slot.max_length & !DomainSlot::LINEAGE)
+            | (structure.lineage as u16) << DomainSlot::LINEAGE_SHIFT;
+        self.dirty = true;
+        Ok(Some(slot.counter))
+    }
+
+    // Set password rules for domain
+    // Creates new entry if domain doesn't exist
+    // returns error if table is full (all 512 slots used. If this happens, rethink your life)
+    pub fn set_rules(
+        &mut self,
+        domain: &str,
+        max_length: u16,
+        char_types: u8,
+        structure: &mut StructureSystem,
+    ) -> Result<(), DomainTableError> {
+        let hash = structure.hash_domain(domain);
+
+        // Try to find existing slot
+        if let Some(idx) = self.find_slot_by_hash(&hash) {
+            let username_digits = self.slots[idx].char_types & DomainSlot::USERNAME_DIGITS;
+            let flags = self.slots[idx].max_length & !DomainSlot::LENGTH;
+            self.slots[idx].max_length = (max_length & DomainSlot::LENGTH) | flags;
+            self.slots[idx].char_types =
+                (char_types & !DomainSlot::USERNAME_DIGITS) | username_digits;
+            self.dirty = true;
+            return Ok(());
+        }
+
+        if structure.lineage > 0 {
+            self.mark_format(TABLE_FORMAT_LINEAGE)?;
+        }
+        if let Some(idx) = self.slots.iter().position(|s| s.is_empty()) {
+            self.slots[idx] = DomainSlot {
+                domain_hash: hash,
+                counter: 0, // New domain starts at counter 0
+                max_length: (max_length & DomainSlot::LENGTH)
+                    | (structure.lineage as u16) << DomainSlot::LINEAGE_SHIFT,
+                char_types: char_types & !DomainSlot::USERNAME_DIGITS,
+                fixed_output: 0,
+            };
+            self.dirty = true;
+            Ok(())
+        } else {
+            Err(DomainTableError::Full)
+        }
+    }
+
+    // the slots as they sit in the binary: their memory, which has the u16s
+    // little-endian, except on a big-endian target where they're swapped into a copy
+    fn as_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        if cfg!(target_endian = "little") {
+            return std::borrow::Cow::Borrowed(unsafe {
+                std::slice::from_raw_parts(self.slots.as_ptr() as *const u8, Self::BYTES)
+            });
+        }
+
+        let mut bytes = Vec::with_capacity(Self::BYTES);
+        for slot in &self.slots {
+            bytes.extend_from_slice(&slot.domain_hash);
+            bytes.extend_from_slice(&slot.counter.to_le_bytes());
+            bytes.extend_from_slice(&slot.max_length.to_le_bytes());
+            bytes.push(slot.char_types);
+            bytes.push(slot.fixed_output);
+        }
+        std::borrow::Cow::Owned(bytes)
+    }
+
+    // where the table starts in `file`, after the last DOMAIN_TABLE_START_MARKER.
+    // searched from the end a block at a time, since each full configuration adds
+    // megabytes after it and the first occurrence is the marker constant itself
+    fn find_table(file: &mut File) -> io::Result<Option<u64>> {
+        const BLOCK: u64 = 64 * 1024;
+        let marker = DOMAIN_TABLE_START_MARKER;
+        let overlap = marker.len() as u64 - 1;
+
+        let len = file.seek(SeekFrom::End(0))?;
+        let mut block = vec![0u8; (BLOCK + overlap) as usize];
+        let mut end = len;
+        while end > 0 {
+            let start = end.saturating_sub(BLOCK);
+            // a marker across the block boundary is caught by reading into the next one
+            let read_end = (end + overlap).min(len);
+            let window = &mut block[..(read_end - start) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(window)?;
+
+            if let Some(pos) = window.windows(marker.len()).rposition(|w| w == marker) {
+                return Ok(Some(start + (pos + marker.len()) as u64));
+            }
+            end = start;
+        }
+        Ok(None)
+    }
+
+    // where `region`'s table starts in `file`. None for the decoy's when it was
+    // never written, so nothing follows the real table's region
+    fn find_region(file: &mut File, region: TableRegion) -> io::Result<Option<u64>> {
+        let Some(offset) = Self::find_table(file)? else {
+            return Ok(None);
+        };
+        if region == TableRegion::Real {
+            return Ok(Some(offset));
+        }
+
+        let marker_start = offset + Self::BYTES as u64;
+        let mut marker = vec![0u8; DECOY_TABLE_START_MARKER.len()];
+        file.seek(SeekFrom::Start(marker_start))?;
+        match file.read_exact(&mut marker) {
+            Ok(()) if marker == DECOY_TABLE_START_MARKER => {
+                Ok(Some(marker_start + marker.len() as u64))
+            }
+            Ok(()) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the table over the one in the vault binary at `path`, the decoy's
+    /// under [`DURESS`], see [`DomainTable::save_region`].
+    pub fn save_to_binary(&mut self, path: &std::path::Path) -> io::Result<()> {
+        self.save_region(path, TableRegion::active())
+    }
+
+    /// Writes the table over `region`'s in the vault binary at `path`.
+    ///
+    /// The table's region is a fixed size, so it is overwritten in place when the
+    /// file can be opened for writing, and the whole binary is only rewritten
+    /// through a `.new` copy when it can't, as while it's the running executable,
+    /// or when the decoy's region is written for the first time.
+    /// Callers hold the vault's [`InstanceLock`], so a store can't move the region
+    /// in between.
+    pub fn save_region(&mut self, path: &std::path::Path, region: TableRegion) -> io::Result<()> {
+        if !persisting("domain table") {
+            return Ok(());
+        }
+
+        let writer = BinaryStorageManager::unopened(path.to_path_buf(), true, None).writer();
+        match writer.patch_table(region, &self.as_bytes()) {
+            Ok(true) => {
+                self.dirty = false;
+                log(
+                    LogLevel::Info,
+                    format_args!("storage: patched domain table in place"),
+                );
+                return Ok(());
+            }
+            Ok(false) => {}
+            Err(e) => log(
+                LogLevel::Debug,
+                format_args!("storage: domain table not patched in place: {}", e),
+            ),
+        }
+
+        // the configurations are taken from the file as it is now, not from memory,
+        // so none a store just wrote is lost
+        writer.rewrite(|image| {
+            let tables = image.tables.as_mut().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "Domain table marker not found")
+            })?;
+            let bytes = self.as_bytes().into_owned();
+            match region {
+                TableRegion::Real => tables.real = bytes,
+                TableRegion::Decoy => tables.decoy = Some(bytes),
+            }
+            Ok(())
+        })?;
+
+        self.dirty = false;
+        log(LogLevel::Info, format_args!("storage: wrote domain table"));
+
+        Ok(())
+    }
+
+    /// Replaces the table with the one in the vault binary at `path`, if it has one,
+    /// the decoy's under [`DURESS`].
+    pub fn load_from_binary(&mut self, path: &std::path::Path) -> io::Result<()> {
+        self.load_region(path, TableRegion::active())
+    }
+
+    /// Replaces the table with `region`'s in the vault binary at `path`. A decoy
+    /// table that was never written loads empty.
+    pub fn load_region(&mut self, path: &std::path::Path, region: TableRegion) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        if region == TableRegion::Decoy {
+            *self = DomainTable::new();
+        }
+
+        if let Some(table_start) = Self::find_region(&mut file, region)? {
+            if file.metadata()?.len() >= table_start + Self::BYTES as u64 {
+                let mut table_data = vec![0u8; Self::BYTES];
+                file.seek(SeekFrom::Start(table_start))?;
+                file.read_exact(&mut table_data)?;
+
+                // every bit pattern is a valid slot, the struct is plain bytes and u16s
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        table_data.as_ptr(),
+                        self.slots.as_mut_ptr() as *mut u8,
+                        Self::BYTES,
+                    );
+                }
+                zero_memory(&mut table_data);
+                for slot in self.slots.iter_mut() {
+                    slot.counter = u16::from_le(slot.counter);
+                    slot.max_length = u16::from_le(slot.max_length);
+                }
+                self.clear_legacy_padding();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Why serialized configuration bytes couldn't be read back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bytes end before the named field does.
+    Truncated(&'static str),
+    /// The named text field isn't UTF-8.
+    InvalidUtf8(&'static str),
+    /// The named field holds a value no configuration can have.
+    Invalid(&'static str),
+    /// The named record is in a format version this build doesn't know, from a
+    /// newer one.
+    UnsupportedVersion(&'static str, u16),
+    /// The named count, declared as the first number, is over the second, the
+    /// most a configuration can have or the rest of the bytes can hold.
+    OverLimit(&'static str, u64, u64),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Truncated(what) => {
+                write!(f, "Invalid data: not enough bytes for {}", what)
+            }
+            DecodeError::InvalidUtf8(what) => write!(f, "Invalid UTF-8 in {}", what),
+            DecodeError::Invalid(what) => write!(f, "Invalid data: unusable {}", what),
+            DecodeError::UnsupportedVersion(what, version) => write!(
+                f,
+                "Unsupported {} format version {}, written by a newer Void Vault",
+                what, version
+            ),
+            DecodeError::OverLimit(what, declared, limit) => write!(
+                f,
+                "Invalid data: {} of {} is over the limit of {}",
+                what, declared, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// The format version [`SavedPassword::to_bytes`] writes, for the record and the
+/// structure in it. Anything that changes what the bytes mean bumps it.
+pub const FORMAT_VERSION: u16 = 5;
+
+// the start of a versioned configuration record and of the structure in it. the
+// headerless layouts from before versioning start with a length and a dimension
+// count, which never match
+const CONFIG_MAGIC: &[u8; 4] = b"VVcf";
+const STRUCTURE_MAGIC: &[u8; 4] = b"VVst";
+
+fn push_format_header(bytes: &mut Vec<u8>, magic: &[u8; 4]) {
+    bytes.extend_from_slice(magic);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+}
+
+// the version after `magic`, moving `offset` past both, or None for a headerless
+// legacy layout, which is read from offset 0
+fn read_format_header(
+    bytes: &[u8],
+    magic: &[u8; 4],
+    offset: &mut usize,
+    what: &'static str,
+) -> Result<Option<u16>, DecodeError> {
+    if bytes.get(..4) != Some(&magic[..]) {
+        return Ok(None);
+    }
+    let Some(&[low, high]) = bytes.get(4..6) else {
+        return Err(DecodeError::Truncated(what));
+    };
+    let version = u16::from_le_bytes([low, high]);
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(what, version));
+    }
+    *offset = 6;
+    Ok(Some(version))
+}
+
+/// The most dimensions a structure can have, so its points fit inline.
+pub const MAX_DIMENSIONS: usize = 16;
+
+/// How many active points building a structure may reach by default, see
+/// [`StructureSystem::max_points`]. The full pool comes to well under this, a
+/// long setup phrase typed into it can come near.
+pub const DEFAULT_MAX_POINTS: usize = 2_000_000;
+
+/// The most codes a stored character set can have. Every assigned Unicode scalar
+/// value fits, with room to spare.
+pub const MAX_CHARACTER_SET: usize = 1_000_000;
+
+/// The fewest codes a character set can have and still generate. Fewer and every
+/// keystroke would pick the same character.
+pub const MIN_CHARACTER_SET: usize = 2;
+
+// drops the codes no output may carry: NUL, and numbers that aren't Unicode scalar
+// values. returns how many went
+fn drop_unusable_codes(character_set: &mut Vec<u32>) -> usize {
+    let before = character_set.len();
+    character_set.retain(|&code| code != 0 && char::from_u32(code).is_some());
+    before - character_set.len()
+}
+
+// a count read from a record, refused when it's over `limit` or more elements of
+// `min_size` bytes than the rest of the record holds, before anything loops over
+// it or allocates for it
+fn check_count(
+    count: usize,
+    limit: usize,
+    remaining: usize,
+    min_size: usize,
+    what: &'static str,
+) -> Result<usize, DecodeError> {
+    let limit = limit.min(remaining / min_size.max(1));
+    if count > limit {
+        return Err(DecodeError::OverLimit(what, count as u64, limit as u64));
+    }
+    Ok(count)
+}
+
+// a point of the structure. coordinates past the structure's dimensions stay 0, and
+// the rest stay within a few dozen of the origin, so they pack into i16s with no
+// allocation per point
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct StructurePoint {
+    coordinates: [i16; MAX_DIMENSIONS],
+}
+
+impl StructurePoint {
+    const ORIGIN: StructurePoint = StructurePoint {
+        coordinates: [0; MAX_DIMENSIONS],
+    };
+
+    fn from_seed(seed: u64, dimensions: usize, range: i32) -> Self {
+        let mut point = StructurePoint::ORIGIN;
+        let mut rng_state = seed;
+
+        for i in 0..dimensions {
+            rng_state = rng_state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let value = ((rng_state % (range as u64 * 2)) as i32) - range;
+            point.coordinates[i] = value as i16;
+        }
+
+        point
+    }
+
+    // the encoding from before points were packed: a u32 coordinate count, then that
+    // many i32s
+    fn from_legacy_bytes(bytes: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if bytes.len() < 4 {
+            return Err(DecodeError::Truncated("StructurePoint"));
+        }
+
+        let coord_count = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let coord_count = check_count(
+            coord_count,
+            MAX_DIMENSIONS,
+            bytes.len() - 4,
+            4,
+            "coordinate count",
+        )?;
+        let required_bytes = 4 + (coord_count * 4);
+        if bytes.len() < required_bytes {
+            return Err(DecodeError::Truncated("coordinates"));
+        }
+
+        let mut point = StructurePoint::ORIGIN;
+        for i in 0..coord_count {
+            let start = 4 + (i * 4);
+            let coord = i32::from_le_bytes([
+                bytes[start],
+                bytes[start + 1],
+                bytes[start + 2],
+                bytes[start + 3],
+            ]);
+            point.coordinates[i] =
+                i16::try_from(coord).map_err(|_| DecodeError::Invalid("coordinate"))?;
+        }
+
+        Ok((point, required_bytes))
+    }
+}
+
+// the packed point encodings write a coordinate as its change from the previous
+// point, zigzagged so small negative changes stay one byte, as an LEB128 varint
+fn push_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], offset: &mut usize, what: &'static str) -> Result<u32, DecodeError> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let Some(&byte) = bytes.get(*offset) else {
+            return Err(DecodeError::Truncated(what));
+        };
+        *offset += 1;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::Invalid(what))
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+// PACKED_POINTS in place of the active point count marks the packed encoding of both
+// point sections, see StructureSystem::to_bytes
+const PACKED_POINTS: u32 = u32::MAX;
+
+// `len` bytes at `offset`, moving it past them. checked, so a corrupt length can
+// neither index past the end nor overflow the offset
+fn read_bytes<'a>(
+    bytes: &'a [u8],
+    offset: &mut usize,
+    len: usize,
+    what: &'static str,
+) -> Result<&'a [u8], DecodeError> {
+    let field = offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(*offset..end))
+        .ok_or(DecodeError::Truncated(what))?;
+    *offset += len;
+    Ok(field)
+}
+
+fn read_array<const N: usize>(
+    bytes: &[u8],
+    offset: &mut usize,
+    what: &'static str,
+) -> Result<[u8; N], DecodeError> {
+    let mut array = [0u8; N];
+    array.copy_from_slice(read_bytes(bytes, offset, N, what)?);
+    Ok(array)
+}
+
+// a u32 length, then that much UTF-8
+fn read_text(bytes: &[u8], offset: &mut usize, what: &'static str) -> Result<String, DecodeError> {
+    let len = u32::from_le_bytes(read_array(bytes, offset, what)?) as usize;
+    let text = read_bytes(bytes, offset, len, what)?;
+    String::from_utf8(text.to_vec()).map_err(|_| DecodeError::InvalidUtf8(what))
+}
+
+fn read_u32(bytes: &[u8], offset: usize, what: &'static str) -> Result<u32, DecodeError> {
+    match bytes.get(offset..offset + 4) {
+        Some(field) => Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]])),
+        None => Err(DecodeError::Truncated(what)),
+    }
+}
+
+fn read_packed_points(
+    bytes: &[u8],
+    offset: &mut usize,
+    dimensions: usize,
+) -> Result<Vec<StructurePoint>, DecodeError> {
+    let count = read_u32(bytes, *offset, "active points count")? as usize;
+    *offset += 4;
+    // a varint, at least a byte, per coordinate
+    let count = check_count(
+        count,
+        usize::MAX,
+        bytes.len() - *offset,
+        dimensions,
+        "active point count",
+    )?;
+
+    let mut points = Vec::with_capacity(count);
+    let mut previous = StructurePoint::ORIGIN;
+    for _ in 0..count {
+        let mut point = StructurePoint::ORIGIN;
+        for dim in 0..dimensions {
+            let change = unzigzag(read_varint(bytes, offset, "active point")?);
+            point.coordinates[dim] =
+                i16::try_from(previous.coordinates[dim] as i64 + change as i64)
+                    .map_err(|_| DecodeError::Invalid("coordinate"))?;
+        }
+        points.push(point);
+        previous = point;
+    }
+    Ok(points)
+}
+
+fn read_packed_mapping(
+    bytes: &[u8],
+    offset: &mut usize,
+    dimensions: usize,
+) -> Result<HashMap<u32, StructurePoint>, DecodeError> {
+    let count = read_u32(bytes, *offset, "char_to_point count")? as usize;
+    *offset += 4;
+    // the keycode's varint and the coordinates'
+    let count = check_count(
+        count,
+        usize::MAX,
+        bytes.len() - *offset,
+        1 + dimensions,
+        "keycode map count",
+    )?;
+
+    let mut mapping = HashMap::with_capacity(count);
+    let mut keycode = 0u32;
+    for _ in 0..count {
+        keycode = keycode
+            .checked_add(read_varint(bytes, offset, "keycode")?)
+            .ok_or(DecodeError::Invalid("keycode"))?;
+        let mut point = StructurePoint::ORIGIN;
+        for dim in 0..dimensions {
+            let coord = unzigzag(read_varint(bytes, offset, "point")?);
+            point.coordinates[dim] =
+                i16::try_from(coord).map_err(|_| DecodeError::Invalid("coordinate"))?;
+        }
+        mapping.insert(keycode, point);
+    }
+    Ok(mapping)
+}
+
+// both point sections as they were written before points were packed: counts, then
+// keycodes and points at full width
+fn read_legacy_points(
+    bytes: &[u8],
+    offset: &mut usize,
+) -> Result<(Vec<StructurePoint>, HashMap<u32, StructurePoint>), DecodeError> {
+    let active_points_count = read_u32(bytes, *offset, "active points count")? as usize;
+    *offset += 4;
+    // each starts with a u32 coordinate count
+    let active_points_count = check_count(
+        active_points_count,
+        usize::MAX,
+        bytes.len() - *offset,
+        4,
+        "active point count",
+    )?;
+
+    let mut active_points = Vec::with_capacity(active_points_count);
+    for _ in 0..active_points_count {
+        if *offset >= bytes.len() {
+            return Err(DecodeError::Truncated("active point Z"));
+        }
+        let (point, bytes_read) = StructurePoint::from_legacy_bytes(&bytes[*offset..])?;
+        active_points.push(point);
+        *offset += bytes_read;
+    }
+
+    let mapping_count = read_u32(bytes, *offset, "char_to_point count")? as usize;
+    *offset += 4;
+    // a u32 keycode and the point's coordinate count
+    let mapping_count = check_count(
+        mapping_count,
+        usize::MAX,
+        bytes.len() - *offset,
+        8,
+        "keycode map count",
+    )?;
+
+    let mut char_to_point = HashMap::with_capacity(mapping_count);
+    for _ in 0..mapping_count {
+        let keycode = read_u32(bytes, *offset, "keycode")?;
+        *offset += 4;
+
+        if *offset >= bytes.len() {
+            return Err(DecodeError::Truncated("point"));
+        }
+        let (point, bytes_read) = StructurePoint::from_legacy_bytes(&bytes[*offset..])?;
+        char_to_point.insert(keycode, point);
+        *offset += bytes_read;
+    }
+
+    Ok((active_points, char_to_point))
+}
+
+// steps over both point sections, in either encoding, without decoding them
+fn skip_points(
+    bytes: &[u8],
+    offset: &mut usize,
+    dimensions: usize,
+    packed: bool,
+) -> Result<(), DecodeError> {
+    // the same limits as reading them, see read_packed_points and read_legacy_points
+    let count = |offset: &mut usize, min_size: usize, what: &'static str| {
+        let count = read_u32(bytes, *offset, what)? as usize;
+        *offset += 4;
+        check_count(count, usize::MAX, bytes.len() - *offset, min_size, what)
+    };
+
+    if packed {
+        *offset += 4;
+        let points = count(offset, dimensions, "active point count")?;
+        skip_varints(bytes, offset, points * dimensions, "active point")?;
+        let mapped = count(offset, 1 + dimensions, "keycode map count")?;
+        return skip_varints(bytes, offset, mapped * (1 + dimensions), "point");
+    }
+
+    let skip_point = |offset: &mut usize| {
+        let coord_count = read_u32(bytes, *offset, "StructurePoint")? as usize;
+        *offset = offset.saturating_add(4 + coord_count.saturating_mul(4));
+        match *offset <= bytes.len() {
+            true => Ok(()),
+            false => Err(DecodeError::Truncated("coordinates")),
+        }
+    };
+    let points = count(offset, 4, "active point count")?;
+    for _ in 0..points {
+        skip_point(offset)?;
+    }
+    let mapped = count(offset, 8, "keycode map count")?;
+    for _ in 0..mapped {
+        *offset += 4;
+        skip_point(offset)?;
+    }
+    Ok(())
+}
+
+fn skip_varints(
+    bytes: &[u8],
+    offset: &mut usize,
+    count: usize,
+    what: &'static str,
+) -> Result<(), DecodeError> {
+    let mut left = count;
+    while left > 0 {
+        let Some(&byte) = bytes.get(*offset) else {
+            return Err(DecodeError::Truncated(what));
+        };
+        *offset += 1;
+        if byte & 0x80 == 0 {
+            left -= 1;
+        }
+    }
+    Ok(())
+}
+
+/// How a structure moves between keystrokes. Part of what a configuration
+/// generates, so it is stored with it and never changes afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    /// Positions in f64, what every configuration stored before [`Motion::Fixed`]
+    /// existed moves by.
+    Float,
+    /// Positions in Q48.16 fixed point, integer math with the rounding spelled out,
+    /// so every target takes exactly the same steps. What new structures use.
+    Fixed,
+}
+
+impl Motion {
+    fn to_byte(self) -> u8 {
+        match self {
+            Motion::Float => 0,
+            Motion::Fixed => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Motion::Float),
+            1 => Some(Motion::Fixed),
+            _ => None,
+        }
+    }
+}
+
+/// Which revision of the keystroke transform a structure generates with. Like
+/// [`Motion`] it is stored with the structure and never changes afterwards, so a
+/// revision only ever applies to structures made after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Only the parity of the path memory nudges the character index, by one either
+    /// way. Every structure stored before format version 2 uses it.
+    V1,
+    /// The whole path memory byte goes into the movement seed and the character
+    /// index.
+    V2,
+    /// V2, with each keystroke's feedback a running 64-bit mix of every output code
+    /// so far instead of its own codes summed mod 256.
+    V3,
+    /// V3, with the domain identifier absorbed from the domain's bytes and the seed
+    /// by an ARX permutation instead of read off a walk.
+    V4,
+    /// V4, with every keystroke walking the same number of times wherever it falls
+    /// in the phrase: a fixed trail drawn from the last feedback instead of one walk
+    /// per keystroke before it.
+    V5,
+    /// V5, with a keystroke's characters sampled along its step up to and including
+    /// where it lands instead of from where it started, and the keycode mixed into
+    /// the first sample.
+    V6,
+    /// V6, with a step longer than the structure is wide reflected off its bounds
+    /// until it lands inside them, where before it could end up past the far one.
+    /// What new structures use.
+    V7,
+}
+
+impl Transform {
+    fn to_byte(self) -> u8 {
+        match self {
+            Transform::V1 => 1,
+            Transform::V2 => 2,
+            Transform::V3 => 3,
+            Transform::V4 => 4,
+            Transform::V5 => 5,
+            Transform::V6 => 6,
+            Transform::V7 => 7,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Transform::V1),
+            2 => Some(Transform::V2),
+            3 => Some(Transform::V3),
+            4 => Some(Transform::V4),
+            5 => Some(Transform::V5),
+            6 => Some(Transform::V6),
+            7 => Some(Transform::V7),
+            _ => None,
+        }
+    }
+
+    /// How many keycodes follow a keystroke's own from [`Transform::V5`] on.
+    pub const TRAIL: usize = 8;
+
+    /// The keycodes a keystroke walks from the start position: its own, offset by
+    /// the feedback of the keystrokes before it, then those feedbacks newest first.
+    /// From [`Transform::V5`] on the feedbacks are replaced by [`Transform::TRAIL`]
+    /// keycodes drawn from the last one, so the work doesn't grow with the phrase.
+    pub fn navigation_sequence(
+        self,
+        keycode: u32,
+        feedbacks: &[u64],
+    ) -> impl Iterator<Item = u32> + '_ {
+        let fold = move |feedback: u64| match self {
+            Transform::V1 | Transform::V2 => feedback as u32,
+            Transform::V3 | Transform::V4 | Transform::V5 | Transform::V6 | Transform::V7 => {
+                (feedback >> 32) as u32 ^ feedback as u32
+            }
+        };
+        let last = feedbacks.last().copied().unwrap_or(0);
+        let offset = match self {
+            Transform::V1 | Transform::V2 => feedbacks.iter().map(|&fb| fb as u32).sum(),
+            // the last one has mixed in all the others
+            Transform::V3 | Transform::V4 | Transform::V5 | Transform::V6 | Transform::V7 => {
+                fold(last)
+            }
+        };
+        let trail = match self {
+            Transform::V5 | Transform::V6 | Transform::V7 => Self::TRAIL,
+            _ => feedbacks.len(),
+        };
+        std::iter::once(keycode.wrapping_add(offset)).chain((0..trail).map(move |i| match self {
+            // splitmix64 over the last feedback, one step per keycode
+            Transform::V5 | Transform::V6 | Transform::V7 => {
+                let mut z = last.wrapping_add((i as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15));
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+                fold(z ^ (z >> 31))
+            }
+            _ => fold(feedbacks[feedbacks.len() - 1 - i]),
+        }))
+    }
+
+    /// The feedback a keystroke leaves for the ones after it, from the codes it
+    /// output.
+    pub fn feedback(self, feedbacks: &[u64], output: impl IntoIterator<Item = u32>) -> u64 {
+        match self {
+            Transform::V1 | Transform::V2 => {
+                let output_sum = output
+                    .into_iter()
+                    .fold(0u64, |sum, code| sum.wrapping_add(code as u64));
+                output_sum % 256
+            }
+            Transform::V3 | Transform::V4 | Transform::V5 | Transform::V6 | Transform::V7 => output
+                .into_iter()
+                .fold(feedbacks.last().copied().unwrap_or(0), |state, code| {
+                    (state ^ code as u64)
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407)
+                }),
+        }
+    }
+
+    // how far along its step, in steps of one over the characters it outputs, a
+    // keystroke's `i`th character is sampled. before V6 the first was at the start,
+    // the same for every keystroke from there, and the landing never sampled
+    fn sample_reach(self, i: usize) -> usize {
+        match self {
+            Transform::V6 | Transform::V7 => i + 1,
+            _ => i,
+        }
+    }
+
+    // whether a step reflects off the bounds until it is inside them, see reflect
+    fn reflects_into_bounds(self) -> bool {
+        matches!(self, Transform::V7)
+    }
+
+    // what the `i`th sample's coordinates are hashed onto
+    fn sample_seed(self, seed: u64, keycode: u32, i: usize) -> u64 {
+        match self {
+            Transform::V6 | Transform::V7 if i == 0 => {
+                seed ^ (keycode as u64).wrapping_mul(0x9E3779B97F4A7C15)
+            }
+            _ => seed,
+        }
+    }
+}
+
+// the domain identifier of Transform::V4: a sponge over 512 bits of state, the
+// seed in the half the domain never touches and the domain's UTF-8 absorbed 32
+// bytes at a time into the other. every bit of the output depends on every byte,
+// so related domains share nothing
+fn mix_domain(seed: u64, domain: &str) -> [u8; 64] {
+    mix_bytes(seed, domain.as_bytes())
+}
+
+// the same over any bytes, as the code a vault is sealed with
+fn mix_bytes(seed: u64, bytes: &[u8]) -> [u8; 64] {
+    const RATE: usize = 32;
+
+    // SHA-512's initial values, nothing up the sleeve
+    let mut state: [u64; 8] = [
+        0x6a09e667f3bcc908,
+        0xbb67ae8584caa73b,
+        0x3c6ef372fe94f82b,
+        0xa54ff53a5f1d36f1,
+        0x510e527fade682d1,
+        0x9b05688c2b3e6c1f,
+        0x1f83d9abfb41bd6b,
+        0x5be0cd19137e2179,
+    ];
+    state[4] ^= seed;
+    state[5] ^= bytes.len() as u64;
+    domain_permute(&mut state);
+
+    // 0x01 after the bytes and 0x80 at the end of their last block
+    let mut padded = bytes.to_vec();
+    padded.push(0x01);
+    padded.resize(padded.len().next_multiple_of(RATE), 0);
+    *padded.last_mut().unwrap() |= 0x80;
+
+    for block in padded.chunks_exact(RATE) {
+        for (lane, word) in state.iter_mut().zip(block.chunks_exact(8)) {
+            *lane ^= u64::from_le_bytes(word.try_into().unwrap());
+        }
+        domain_permute(&mut state);
+    }
+
+    let mut hash = [0u8; 64];
+    for half in hash.chunks_exact_mut(RATE) {
+        for (bytes, lane) in half.chunks_exact_mut(8).zip(&state) {
+            bytes.copy_from_slice(&lane.to_le_bytes());
+        }
+        domain_permute(&mut state);
+    }
+    hash
+}
+
+// twelve rounds of BLAKE2b's G over the eight lanes, as columns of a 4x2 grid and
+// then its diagonals
+fn domain_permute(state: &mut [u64; 8]) {
+    fn g(state: &mut [u64; 8], a: usize, b: usize, c: usize, d: usize) {
+        state[a] = state[a].wrapping_add(state[b]);
+        state[d] = (state[d] ^ state[a]).rotate_right(32);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_right(24);
+        state[a] = state[a].wrapping_add(state[b]);
+        state[d] = (state[d] ^ state[a]).rotate_right(16);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_right(63);
+    }
+
+    for round in 0..12u64 {
+        // a constant per round, or the rounds would all be alike
+        state[0] ^= (round + 1).wrapping_mul(0x9E3779B97F4A7C15);
+        g(state, 0, 2, 4, 6);
+        g(state, 1, 3, 5, 7);
+        g(state, 0, 3, 4, 7);
+        g(state, 1, 2, 5, 6);
+    }
+}
+
+// how many times a step that keeps landing outside is reflected before it is
+// clamped to the bound instead. one reflection per box width it overshoots
+const MAX_REFLECTIONS: usize = 8;
+
+// a coordinate that stepped past a bound, reflected back off it. with `repeat` it
+// keeps reflecting until it is inside, see Transform::V7. without, it reflects once,
+// and a step longer than the box is wide ends up past the other bound
+fn reflect<T>(mut coord: T, min_bound: T, max_bound: T, repeat: bool) -> T
+where
+    T: Copy + PartialOrd + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+{
+    for _ in 0..if repeat { MAX_REFLECTIONS } else { 1 } {
+        if coord < min_bound {
+            coord = min_bound + (min_bound - coord);
+        } else if coord > max_bound {
+            coord = max_bound - (coord - max_bound);
+        } else {
+            return coord;
+        }
+    }
+    if !repeat {
+        coord
+    } else if coord < min_bound {
+        min_bound
+    } else if coord > max_bound {
+        max_bound
+    } else {
+        coord
+    }
+}
+
+// 1.0 in the Q48.16 positions of Motion::Fixed
+const FIXED_ONE: i64 = 1 << 16;
+
+// an f64 step setting or bound in Q48.16. they are small multiples of simple
+// fractions, which scale by 2^16 exactly, so this rounds the same everywhere
+fn to_fixed(value: f64) -> i64 {
+    (value * FIXED_ONE as f64).round() as i64
+}
+
+// [-1, 1) in Q48.16 from the top 17 bits of an LCG state
+fn fixed_unit(rng_state: u64) -> i64 {
+    (rng_state >> 47) as i64 - FIXED_ONE
+}
+
+#[derive(Debug, Clone)]
+struct ContinuousPosition {
+    coordinates: Vec<f64>,
+    // the position under Motion::Fixed, coordinates is under Motion::Float
+    fixed: Vec<i64>,
+}
+
+impl Drop for ContinuousPosition {
+    fn drop(&mut self) {
+        self.coordinates.wipe();
+        self.fixed.wipe();
+    }
+}
+
+// links all previous positions together
+impl ContinuousPosition {
+    fn new(dimensions: usize) -> Self {
+        ContinuousPosition {
+            coordinates: vec![0.0; dimensions],
+            fixed: vec![0; dimensions],
+        }
+    }
+
+    fn hash_position(&self, seed: u64, motion: Motion) -> u64 {
+        match motion {
+            Motion::Float => hash_coordinates(&self.coordinates, seed),
+            Motion::Fixed => self.fixed.iter().fold(seed, |hash, &coord| {
+                hash_coordinate(hash, coord * 1000 / FIXED_ONE)
+            }),
+        }
+    }
+}
+
+// a coordinate goes into a position hash in thousandths, truncated toward zero
+fn hash_coordinate(hash: u64, thousandths: i64) -> u64 {
+    hash.wrapping_mul(31).wrapping_add(thousandths as u64)
+}
+
+fn hash_coordinates(coordinates: &[f64], seed: u64) -> u64 {
+    coordinates.iter().fold(seed, |hash, &coord| {
+        hash_coordinate(hash, (coord * 1000.0) as i64)
+    })
+}
+
+// the start, direction and path of a step, kept between keystrokes so taking one
+// doesn't allocate. they hold positions, so they wipe like one
+#[derive(Default)]
+struct StepBuffers {
+    start: Vec<f64>,
+    direction: Vec<f64>,
+    path: Vec<f64>,
+    fixed_start: Vec<i64>,
+    fixed_direction: Vec<i64>,
+}
+
+impl Drop for StepBuffers {
+    fn drop(&mut self) {
+        self.start.wipe();
+        self.direction.wipe();
+        self.path.wipe();
+        self.fixed_start.wipe();
+        self.fixed_direction.wipe();
+    }
+}
+pub struct StructureSystem {
+    //multiple active and interactable dimensions
+    pub dimensions: usize,
+    //active as in navigational lighthouses, sorted and without repeats
+    active_points: Vec<StructurePoint>,
+    //unifying the geometry translation with movement
+    char_to_point: HashMap<u32, StructurePoint>,
+    //range field for movement
+    pub coordinate_range: i32,
+    //initialization seed for the geometry
+    pub original_seed: u64,
+    //the name of the unique geometry
+    name: String,
+    //zoned coordinate pool for multi-dimensional treversal
+    pub character_set: Vec<u32>,
+
+    current_position: ContinuousPosition,
+    structure_bounds: (Vec<f64>, Vec<f64>),
+    pub base_step_size: f64,
+    pub step_variance: f64,
+
+    accumulated_path_memory: u8,
+    // fixed when the structure is made, see Motion and Transform
+    pub motion: Motion,
+    pub transform: Transform,
+    // how many times the setup this came from has been re-keyed to get here, 0 for
+    // the setup itself. re-keys keep the seed, so the domain slots they share name
+    // the one they generate with by this, see SavedPassword::rekey
+    pub lineage: u8,
+
+    step_buffers: StepBuffers,
+    // false when decoded without its points, which then must not be stored over
+    // the ones in the vault
+    points_loaded: bool,
+    // how many active points building may push, counting repeats not yet settled.
+    // past it the features still draw their points, so what comes after them is
+    // the same, and drop them. not stored, the points themselves are
+    pub max_points: usize,
+    // whether building dropped any for max_points
+    points_capped: bool,
+}
+
+// positions wipe themselves, this covers the rest
+impl Drop for StructureSystem {
+    fn drop(&mut self) {
+        self.active_points.wipe();
+        for point in self.char_to_point.values_mut() {
+            zero_memory(&mut point.coordinates);
+        }
+        self.name.wipe();
+        self.character_set.wipe();
+        self.structure_bounds.0.wipe();
+        self.structure_bounds.1.wipe();
+        unsafe {
+            std::ptr::write_volatile(&mut self.original_seed, 0);
+            std::ptr::write_volatile(&mut self.accumulated_path_memory, 0);
+        }
+    }
+}
+
+impl StructureSystem {
+    // panics past MAX_DIMENSIONS, callers check what they were given
+    pub fn new(seed: u64, dimensions: usize, range: i32) -> Self {
+        assert!(
+            dimensions <= MAX_DIMENSIONS,
+            "a structure has at most {} dimensions",
+            MAX_DIMENSIONS
+        );
+        StructureSystem {
+            dimensions,
+            active_points: Vec::new(),
+            char_to_point: HashMap::new(),
+            coordinate_range: range,
+            original_seed: seed,
+            name: String::from("default"),
+            character_set: Vec::new(),
+            current_position: ContinuousPosition::new(dimensions),
+            structure_bounds: (vec![-30.0; dimensions], vec![30.0; dimensions]),
+            base_step_size: 3.0,
+            step_variance: 2.0,
+            accumulated_path_memory: 0,
+            motion: Motion::Fixed,
+            transform: Transform::V7,
+            lineage: 0,
+            step_buffers: StepBuffers::default(),
+            points_loaded: true,
+            max_points: DEFAULT_MAX_POINTS,
+            points_capped: false,
+        }
+    }
+
+    pub fn reset_position(&mut self) {
+        self.accumulated_path_memory = 0;
+    }
+
+    pub fn full_reset(&mut self) {
+        self.current_position = ContinuousPosition::new(self.dimensions);
+        self.accumulated_path_memory = 0;
+    }
+
+    pub fn transform_char(&mut self, keycode: u32, extra_chars_count: usize) -> Vec<u32> {
+        let mut output_chars = Vec::with_capacity(extra_chars_count + 1);
+        self.transform_char_into(keycode, extra_chars_count, &mut output_chars);
+        output_chars
+    }
+
+    // transform_char appending to the caller's buffer, so a loop over keystrokes can
+    // keep one buffer instead of allocating per step
+    pub fn transform_char_into(
+        &mut self,
+        keycode: u32,
+        extra_chars_count: usize,
+        output: &mut Vec<u32>,
+    ) {
+        self.transform_char_with(keycode, extra_chars_count, |code| output.push(code));
+    }
+
+    fn transform_char_with(
+        &mut self,
+        keycode: u32,
+        extra_chars_count: usize,
+        mut emit: impl FnMut(u32),
+    ) {
+        self.take_step(keycode, extra_chars_count, |structure, char_seed| {
+            // a structure with no characters takes its step and types nothing, rather
+            // than NULs
+            if let Some(code) = structure.apply_path_memory_to_character(char_seed) {
+                emit(code);
+            }
+        });
+    }
+
+    // moves by one keystroke and hands over the position hashes along the step's
+    // path, one per output character. the start, direction and path live in the
+    // step buffers, so nothing is allocated once they have grown to the dimensions
+    fn take_step(&mut self, keycode: u32, extra_chars_count: usize, emit: impl FnMut(&Self, u64)) {
+        match self.motion {
+            Motion::Float => self.take_float_step(keycode, extra_chars_count, emit),
+            Motion::Fixed => self.take_fixed_step(keycode, extra_chars_count, emit),
+        }
+    }
+
+    fn take_float_step(
+        &mut self,
+        keycode: u32,
+        extra_chars_count: usize,
+        mut emit: impl FnMut(&Self, u64),
+    ) {
+        let mut buffers = std::mem::take(&mut self.step_buffers);
+
+        let distance = self.calculate_movement(keycode, &mut buffers.direction);
+        buffers.start.clear();
+        buffers
+            .start
+            .extend_from_slice(&self.current_position.coordinates);
+
+        self.update_position(&buffers.direction, distance);
+
+        let total_chars = extra_chars_count + 1;
+        for i in 0..total_chars {
+            let fraction = self.transform.sample_reach(i) as f64 / total_chars as f64;
+
+            buffers.path.clear();
+            buffers.path.extend_from_slice(&buffers.start);
+            for dim in 0..self.dimensions {
+                buffers.path[dim] += buffers.direction[dim] * distance * fraction;
+            }
+
+            let seed = self.transform.sample_seed(self.original_seed, keycode, i);
+            emit(self, hash_coordinates(&buffers.path, seed));
+        }
+
+        self.step_buffers = buffers;
+    }
+
+    // take_float_step in Q48.16. products shift back down with an arithmetic shift,
+    // which floors, and divisions truncate toward zero, as Rust's integers do
+    fn take_fixed_step(
+        &mut self,
+        keycode: u32,
+        extra_chars_count: usize,
+        mut emit: impl FnMut(&Self, u64),
+    ) {
+        let mut buffers = std::mem::take(&mut self.step_buffers);
+
+        let position_hash = self
+            .current_position
+            .hash_position(self.original_seed, Motion::Fixed);
+        let movement_seed =
+            self.original_seed ^ position_hash ^ (keycode as u64) ^ self.path_memory_seed();
+        self.generate_fixed_direction(movement_seed, &mut buffers.fixed_direction);
+        let distance = self.generate_fixed_distance(movement_seed);
+
+        buffers.fixed_start.clear();
+        buffers
+            .fixed_start
+            .extend_from_slice(&self.current_position.fixed);
+
+        self.update_fixed_position(&buffers.fixed_direction, distance);
+
+        let total_chars = extra_chars_count as i64 + 1;
+        for i in 0..total_chars as usize {
+            let reach = self.transform.sample_reach(i) as i64;
+            let seed = self.transform.sample_seed(self.original_seed, keycode, i);
+            let hash = buffers
+                .fixed_start
+                .iter()
+                .zip(&buffers.fixed_direction)
+                .fold(seed, |hash, (&start, &direction)| {
+                    let coord = start + ((direction * distance) >> 16) * reach / total_chars;
+                    hash_coordinate(hash, coord * 1000 / FIXED_ONE)
+                });
+            emit(self, hash);
+        }
+
+        self.step_buffers = buffers;
+    }
+
+    // a unit vector: each component from the LCG, then divided by the integer
+    // square root of their summed squares
+    fn generate_fixed_direction(&self, seed: u64, direction: &mut Vec<i64>) {
+        direction.clear();
+        let mut rng_state = seed;
+        for _ in 0..self.dimensions {
+            rng_state = rng_state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            direction.push(fixed_unit(rng_state));
+        }
+
+        let squares: u64 = direction.iter().map(|&x| (x * x) as u64).sum();
+        let magnitude = squares.isqrt() as i64;
+        if magnitude > 0 {
+            for value in direction.iter_mut() {
+                *value = *value * FIXED_ONE / magnitude;
+            }
+        }
+    }
+
+    fn generate_fixed_distance(&self, seed: u64) -> i64 {
+        let rng_state = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+
+        let variance = fixed_unit(rng_state);
+        to_fixed(self.base_step_size) + ((variance * to_fixed(self.step_variance)) >> 16)
+    }
+
+    fn update_fixed_position(&mut self, direction: &[i64], distance: i64) {
+        for (i, &step) in direction.iter().enumerate().take(self.dimensions) {
+            let new_coord = self.current_position.fixed[i] + ((step * distance) >> 16);
+
+            let min_bound = to_fixed(self.structure_bounds.0[i]);
+            let max_bound = to_fixed(self.structure_bounds.1[i]);
+
+            self.current_position.fixed[i] = reflect(
+                new_coord,
+                min_bound,
+                max_bound,
+                self.transform.reflects_into_bounds(),
+            );
+        }
+
+        let coord_sum: i64 = self
+            .current_position
+            .fixed
+            .iter()
+            .map(|&coord| coord / FIXED_ONE)
+            .sum();
+
+        self.accumulated_path_memory = self.accumulated_path_memory.wrapping_add(coord_sum as u8);
+    }
+
+    fn calculate_movement(&self, keycode: u32, direction: &mut Vec<f64>) -> f64 {
+        let position_hash = self
+            .current_position
+            .hash_position(self.original_seed, Motion::Float);
+        let movement_seed =
+            self.original_seed ^ position_hash ^ (keycode as u64) ^ self.path_memory_seed();
+
+        self.generate_direction(movement_seed, direction);
+        self.generate_distance(movement_seed)
+    }
+
+    // the path memory spread over a u64, for mixing into seeds. nothing under
+    // Transform::V1, whose steps ignore it
+    fn path_memory_seed(&self) -> u64 {
+        match self.transform {
+            Transform::V1 => 0,
+            Transform::V2
+            | Transform::V3
+            | Transform::V4
+            | Transform::V5
+            | Transform::V6
+            | Transform::V7 => {
+                (self.accumulated_path_memory as u64).wrapping_mul(0x9E3779B97F4A7C15)
+            }
+        }
+    }
+
+    fn generate_direction(&self, seed: u64, direction: &mut Vec<f64>) {
+        direction.clear();
+        direction.resize(self.dimensions, 0.0);
+        let mut rng_state = seed;
+
+        for value in direction.iter_mut() {
+            rng_state = rng_state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            *value = (rng_state as f64 / u64::MAX as f64) * 2.0 - 1.0;
+        }
+
+        let magnitude: f64 = direction.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if magnitude > 0.0 {
+            for value in direction.iter_mut() {
+                *value /= magnitude;
+            }
+        }
+    }
+
+    fn generate_distance(&self, seed: u64) -> f64 {
+        let mut rng_state = seed;
+        rng_state = rng_state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+
+        let variance = (rng_state as f64 / u64::MAX as f64) * 2.0 - 1.0;
+        self.base_step_size + variance * self.step_variance
+    }
+
+    fn update_position(&mut self, direction: &[f64], distance: f64) {
+        for (i, &step) in direction.iter().enumerate().take(self.dimensions) {
+            let new_coord = self.current_position.coordinates[i] + step * distance;
+
+            let (min_bound, max_bound) = (self.structure_bounds.0[i], self.structure_bounds.1[i]);
+
+            self.current_position.coordinates[i] = reflect(
+                new_coord,
+                min_bound,
+                max_bound,
+                self.transform.reflects_into_bounds(),
+            );
+        }
+
+        let coord_sum: i64 = self
+            .current_position
+            .coordinates
+            .iter()
+            .map(|&coord| coord as i64)
+            .sum();
+
+        self.accumulated_path_memory = self.accumulated_path_memory.wrapping_add(coord_sum as u8);
+    }
+
+    // transform_char without the character set: the path's hashes folded to u32, for
+    // --format. the step is the same one transform_char takes
+    pub fn transform_char_raw(&mut self, keycode: u32, extra_chars_count: usize) -> Vec<u32> {
+        let mut output = Vec::with_capacity(extra_chars_count + 1);
+        self.transform_char_raw_into(keycode, extra_chars_count, &mut output);
+        output
+    }
+
+    pub fn transform_char_raw_into(
+        &mut self,
+        keycode: u32,
+        extra_chars_count: usize,
+        output: &mut Vec<u32>,
+    ) {
+        self.take_step(keycode, extra_chars_count, |_, seed| {
+            output.push((seed >> 32) as u32 ^ seed as u32)
+        });
+    }
+
+    fn apply_path_memory_to_character(&self, char_seed: u64) -> Option<u32> {
+        if self.character_set.is_empty() {
+            return None;
+        }
+        if self.transform != Transform::V1 {
+            let mixed = char_seed ^ self.path_memory_seed().rotate_left(32);
+            let index = (mixed % self.character_set.len() as u64) as usize;
+            return Some(self.character_set[index]);
+        }
+        let base_char_index = (char_seed % self.character_set.len() as u64) as usize;
+
+        let final_index = if self.accumulated_path_memory.is_multiple_of(2) {
+            (base_char_index + 1) % self.character_set.len()
+        } else {
+            (base_char_index + self.character_set.len() - 1) % self.character_set.len()
+        };
+
+        Some(self.character_set[final_index])
+    }
+
+    // Scrambles domain name using geometric structure
+    // Returns deterministic 64-byte identifier. before Transform::V4 it is read off
+    // a walk, which is what the domain tables of those structures are keyed by
+    pub fn hash_domain(&mut self, domain: &str) -> [u8; 64] {
+        if matches!(
+            self.transform,
+            Transform::V4 | Transform::V5 | Transform::V6 | Transform::V7
+        ) {
+            return mix_domain(self.original_seed, domain);
+        }
+
+        let saved_position = self.current_position.clone();
+        let saved_seed = self.original_seed;
+        let saved_memory = self.accumulated_path_memory;
+
+        const DOMAIN_HASH_SEED: u64 = 0x444F4D41494E5F48;
+        self.original_seed = DOMAIN_HASH_SEED;
+        self.full_reset();
+
+        let mut hash_bytes = Vec::with_capacity(64);
+
+        for ch in domain.chars() {
+            let keycode = ch as u32;
+
+            let output_codes = self.transform_char(keycode, 7);
+
+            for &code in &output_codes {
+                hash_bytes.push((code % 256) as u8);
+
+                if hash_bytes.len() >= 64 {
+                    break;
+                }
+            }
+
+            if hash_bytes.len() >= 64 {
+                break;
+            }
+        }
+
+        // Pad if domain was very short (e.g., "a.co")
+        while hash_bytes.len() < 64 {
+            let padding_codes = self.transform_char(0, 7);
+            for &code in &padding_codes {
+                hash_bytes.push((code % 256) as u8);
+                if hash_bytes.len() >= 64 {
+                    break;
+                }
+            }
+        }
+
+        self.original_seed = saved_seed;
+        self.current_position = saved_position;
+        self.accumulated_path_memory = saved_memory;
+
+        let mut result = [0u8; 64];
+        result.copy_from_slice(&hash_bytes[..64]);
+        result
+    }
+
+    // a username for the domain from a walk of its own: a distinct seed, then the
+    // domain hash and counter ghost navigated, then letters picked by alternating
+    // consonant and vowel so it can be said out loud. like hash_domain it puts the
+    // position back, so a password being typed carries on undisturbed
+    pub fn derive_username(
+        &mut self,
+        domain_hash: &[u8; 64],
+        counter: u16,
+        length: usize,
+        digits: bool,
+    ) -> String {
+        const CONSONANTS: &[u8] = b"bdfghjklmnprstvz";
+        const VOWELS: &[u8] = b"aeiou";
+
+        let saved_position = self.current_position.clone();
+        let saved_seed = self.original_seed;
+        let saved_memory = self.accumulated_path_memory;
+
+        const USERNAME_SEED: u64 = 0x555345524E414D45;
+        self.original_seed = USERNAME_SEED;
+        self.full_reset();
+        ghost_navigate(self, domain_hash, counter);
+
+        let mut username = String::with_capacity(length + 4);
+        let mut codes = Vec::new();
+        let mut step = 0usize;
+        let wanted = length + if digits { 4 } else { 0 };
+
+        while codes.len() < wanted {
+            let keycode = (domain_hash[step % 64] as u32).wrapping_add(step as u32);
+            codes.extend(self.transform_char(keycode, 7));
+            step += 1;
+        }
+
+        for (i, &code) in codes[..length].iter().enumerate() {
+            let pool = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+            username.push(pool[code as usize % pool.len()] as char);
+        }
+        if digits {
+            for &code in &codes[length..wanted] {
+                username.push(char::from(b'0' + (code % 10) as u8));
+            }
+        }
+
+        self.original_seed = saved_seed;
+        self.current_position = saved_position;
+        self.accumulated_path_memory = saved_memory;
+
+        username
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        push_format_header(&mut bytes, STRUCTURE_MAGIC);
+
+        bytes.extend_from_slice(&(self.dimensions as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.coordinate_range.to_le_bytes());
+        bytes.extend_from_slice(&self.original_seed.to_le_bytes());
+
+        let name_bytes = self.name.as_bytes();
+        bytes.extend_from_slice(&(name_bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name_bytes);
+
+        bytes.extend_from_slice(&(self.character_set.len() as u32).to_le_bytes());
+        for &code in &self.character_set {
+            bytes.extend_from_slice(&code.to_le_bytes());
+        }
+
+        // PACKED_POINTS where the point count used to be, then the count. points go
+        // in their sorted order, each coordinate as its change from the point before
+        bytes.extend_from_slice(&PACKED_POINTS.to_le_bytes());
+        bytes.extend_from_slice(&(self.active_points.len() as u32).to_le_bytes());
+        let mut previous = StructurePoint::ORIGIN;
+        for point in &self.active_points {
+            for dim in 0..self.dimensions {
+                let change = point.coordinates[dim] as i32 - previous.coordinates[dim] as i32;
+                push_varint(&mut bytes, zigzag(change));
+            }
+            previous = *point;
+        }
+
+        // keycodes in order as changes from the one before, the points as they are
+        let mut mapping: Vec<(u32, &StructurePoint)> = self
+            .char_to_point
+            .iter()
+            .map(|(&key, point)| (key, point))
+            .collect();
+        mapping.sort_unstable_by_key(|&(key, _)| key);
+        bytes.extend_from_slice(&(mapping.len() as u32).to_le_bytes());
+        let mut previous_key = 0;
+        for (key, point) in mapping {
+            push_varint(&mut bytes, key - previous_key);
+            for dim in 0..self.dimensions {
+                push_varint(&mut bytes, zigzag(point.coordinates[dim] as i32));
+            }
+            previous_key = key;
+        }
+
+        bytes.extend_from_slice(&self.base_step_size.to_le_bytes());
+        bytes.extend_from_slice(&self.step_variance.to_le_bytes());
+
+        bytes.extend_from_slice(&self.accumulated_path_memory.to_le_bytes());
+        bytes.push(self.motion.to_byte());
+        bytes.push(self.transform.to_byte());
+        bytes.push(self.lineage);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(bytes, true)
+    }
+
+    // with_points false steps over the active points and keycode map, see
+    // SavedPassword::from_bytes_without_points
+    fn decode(bytes: &[u8], with_points: bool) -> Result<Self, DecodeError> {
+        let mut offset = 0;
+        // every field is there from version 1 on, a legacy layout may end early
+        let version = read_format_header(bytes, STRUCTURE_MAGIC, &mut offset, "structure")?;
+        let versioned = version.is_some();
+
+        let dimensions = u32::from_le_bytes(read_array(bytes, &mut offset, "dimensions")?) as usize;
+        let dimensions = check_count(dimensions, MAX_DIMENSIONS, usize::MAX, 1, "dimensions")?;
+        let coordinate_range = i32::from_le_bytes(read_array(bytes, &mut offset, "range")?);
+        let original_seed = u64::from_le_bytes(read_array(bytes, &mut offset, "seed")?);
+
+        let name = read_text(bytes, &mut offset, "name")?;
+
+        // four bytes a code, so a count the rest can't hold is corrupt, and isn't
+        // allocated for
+        let char_set_len =
+            u32::from_le_bytes(read_array(bytes, &mut offset, "character set length")?) as usize;
+        let char_set_len = check_count(
+            char_set_len,
+            MAX_CHARACTER_SET,
+            bytes.len() - offset,
+            4,
+            "character set size",
+        )?;
+        let mut character_set = Vec::with_capacity(char_set_len);
+        for _ in 0..char_set_len {
+            character_set.push(u32::from_le_bytes(read_array(
+                bytes,
+                &mut offset,
+                "character set",
+            )?));
+        }
+        let dropped = drop_unusable_codes(&mut character_set);
+        if dropped > 0 {
+            diag(
+                Diag::Warning,
+                format_args!(
+                    "Dropped {} code(s) that aren't characters from a stored character set",
+                    dropped
+                ),
+            );
+        }
+        if character_set.len() < MIN_CHARACTER_SET {
+            return Err(DecodeError::Invalid("character set"));
+        }
+
+        let packed = read_u32(bytes, offset, "active points count")? == PACKED_POINTS;
+        let (mut active_points, char_to_point) = if !with_points {
+            skip_points(bytes, &mut offset, dimensions, packed)?;
+            (Vec::new(), HashMap::new())
+        } else if packed {
+            offset += 4;
+            let points = read_packed_points(bytes, &mut offset, dimensions)?;
+            let mapping = read_packed_mapping(bytes, &mut offset, dimensions)?;
+            (points, mapping)
+        } else {
+            read_legacy_points(bytes, &mut offset)?
+        };
+        active_points.sort();
+        active_points.dedup();
+
+        let (base_step_size, step_variance) = if bytes.len() - offset >= 16 {
+            (
+                f64::from_le_bytes(read_array(bytes, &mut offset, "step size")?),
+                f64::from_le_bytes(read_array(bytes, &mut offset, "step variance")?),
+            )
+        } else if versioned {
+            return Err(DecodeError::Truncated("step settings"));
+        } else {
+            (3.0, 2.0)
+        };
+
+        let accumulated_path_memory = match bytes.get(offset) {
+            Some(&memory) => {
+                offset += 1;
+                memory
+            }
+            None if versioned => return Err(DecodeError::Truncated("path memory")),
+            None => 0,
+        };
+
+        // legacy layouts moved in f64, unless the one written just before versioning
+        // ends with the motion byte
+        let motion = match bytes.get(offset) {
+            Some(&byte) => {
+                offset += 1;
+                Motion::from_byte(byte).ok_or(DecodeError::Invalid("motion"))?
+            }
+            None if versioned => return Err(DecodeError::Truncated("motion")),
+            None => Motion::Float,
+        };
+
+        // written from version 2 on, everything before it used the first transform
+        let transform = if version.is_some_and(|version| version >= 2) {
+            let byte = read_array::<1>(bytes, &mut offset, "transform")?[0];
+            Transform::from_byte(byte).ok_or(DecodeError::Invalid("transform"))?
+        } else {
+            Transform::V1
+        };
+
+        // written from version 4 on, nothing before it was re-keyed
+        let lineage = if version.is_some_and(|version| version >= 4) {
+            read_array::<1>(bytes, &mut offset, "lineage")?[0]
+        } else {
+            0
+        };
+
+        Ok(StructureSystem {
+            dimensions,
+            active_points,
+            char_to_point,
+            coordinate_range,
+            original_seed,
+            name,
+            character_set,
+            current_position: ContinuousPosition::new(dimensions),
+            structure_bounds: (vec![-30.0; dimensions], vec![30.0; dimensions]),
+            base_step_size,
+            step_variance,
+            accumulated_path_memory,
+            motion,
+            transform,
+            lineage,
+            step_buffers: StepBuffers::default(),
+            points_loaded: with_points,
+            max_points: DEFAULT_MAX_POINTS,
+            points_capped: false,
+        })
+    }
+
+    pub fn set_character_set(&mut self, mut character_set: Vec<u32>) {
+        drop_unusable_codes(&mut character_set);
+        self.character_set = character_set;
+    }
+
+    pub fn generate_structure(&mut self, initial_password: &[char], keycodes: &[u32]) {
+        self.set_character_set(keycodes.to_vec());
+
+        let center = StructurePoint::ORIGIN;
+        self.push_point(center);
+
+        for &keycode in keycodes {
+            let point = StructurePoint::from_seed(
+                self.original_seed ^ (keycode as u64),
+                self.dimensions,
+                self.coordinate_range,
+            );
+
+            self.char_to_point.insert(keycode, point);
+        }
+
+        if !initial_password.is_empty() {
+            let mut current_point = center;
+
+            for (i, &ch) in initial_password.iter().enumerate() {
+                let ch_code = ch as u32;
+
+                let point = if let Some(&point) = self.char_to_point.get(&ch_code) {
+                    point
+                } else {
+                    let new_point = StructurePoint::from_seed(
+                        self.original_seed ^ (ch_code as u64),
+                        self.dimensions,
+                        self.coordinate_range,
+                    );
+                    self.char_to_point.insert(ch_code, new_point);
+                    new_point
+                };
+
+                self.create_path(&current_point, &point);
+
+                if i < initial_password.len() - 1 {
+                    self.create_structure_feature(&point, ch_code, i as u64);
+                }
+
+                current_point = point;
+            }
+
+            self.settle_active_points();
+            diag(
+                Diag::Note,
+                format_args!("generated with {} events", self.active_points.len()),
+            );
+        } else {
+            diag(
+                Diag::Note,
+                format_args!("No initial phrase provided, building on a basic one"),
+            );
+
+            self.create_basic_structure(keycodes);
+            self.settle_active_points();
+        }
+
+        self.calculate_structure_bounds();
+    }
+
+    fn calculate_structure_bounds(&mut self) {
+        let mut min_coords = vec![f64::INFINITY; self.dimensions];
+        let mut max_coords = vec![f64::NEG_INFINITY; self.dimensions];
+
+        for point in &self.active_points {
+            for i in 0..self.dimensions {
+                min_coords[i] = min_coords[i].min(point.coordinates[i] as f64);
+                max_coords[i] = max_coords[i].max(point.coordinates[i] as f64);
+            }
+        }
+
+        for i in 0..self.dimensions {
+            min_coords[i] -= Self::BOUNDS_PADDING;
+            max_coords[i] += Self::BOUNDS_PADDING;
+        }
+
+        self.structure_bounds = (min_coords, max_coords);
+    }
+
+    // how far past its outermost points the structure's bounds reach
+    const BOUNDS_PADDING: f64 = 10.0;
+
+    // widens the bounds to take in the points from `from` on, which were pushed
+    // after they were calculated
+    fn extend_structure_bounds(&mut self, from: usize) {
+        let (min_coords, max_coords) = &mut self.structure_bounds;
+        for point in &self.active_points[from..] {
+            for i in 0..self.dimensions {
+                let coord = point.coordinates[i] as f64;
+                min_coords[i] = min_coords[i].min(coord - Self::BOUNDS_PADDING);
+                max_coords[i] = max_coords[i].max(coord + Self::BOUNDS_PADDING);
+            }
+        }
+    }
+
+    fn create_path(&mut self, start: &StructurePoint, end: &StructurePoint) {
+        let steps = 5;
+        for step in 0..=steps {
+            let mut new_point = StructurePoint::ORIGIN;
+
+            for dim in 0..self.dimensions {
+                let start_coord = start.coordinates[dim] as i32;
+                let end_coord = end.coordinates[dim] as i32;
+                let coord = start_coord + (end_coord - start_coord) * step / steps;
+                new_point.coordinates[dim] = coord as i16;
+            }
+
+            self.push_point(new_point);
+        }
+    }
+    // deterministic structure creation, to ensure complex high dimensional internal structures
+    fn create_structure_feature(&mut self, center: &StructurePoint, char_code: u32, index: u64) {
+        let feature_size = 10 + (char_code % 20) as usize;
+
+        let feature_type = (char_code + index as u32) % 5;
+        let feature_seed = self.original_seed ^ (char_code as u64) ^ index;
+
+        match feature_type {
+            0 => self.create_deterministic_spike(center, feature_size, feature_seed),
+            1 => self.create_deterministic_blob(center, feature_size, feature_seed),
+            2 => self.create_deterministic_ring(center, feature_size, feature_seed),
+            3 => self.create_deterministic_spiral(center, feature_size, feature_seed),
+            _ => self.create_deterministic_scatter(center, feature_size, feature_seed),
+        }
+    }
+
+    fn create_deterministic_spike(&mut self, center: &StructurePoint, size: usize, seed: u64) {
+        let mut direction = StructurePoint::ORIGIN;
+        let mut rng_state = seed;
+
+        for dim in 0..self.dimensions {
+            rng_state = rng_state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            direction.coordinates[dim] = ((rng_state % 3) as i16) - 1;
+        }
+
+        for i in 1..=size as i16 {
+            let mut point = *center;
+            for dim in 0..self.dimensions {
+                point.coordinates[dim] += direction.coordinates[dim] * i;
+            }
+            self.push_point(point);
+        }
+    }
+
+    fn create_deterministic_blob(&mut self, center: &StructurePoint, size: usize, seed: u64) {
+        let mut rng_state = seed;
+
+        for _i in 0..size {
+            let mut point = *center;
+
+            for dim in 0..self.dimensions {
+                rng_state = rng_state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let offset = (rng_state % 5) as i16 - 2;
+                point.coordinates[dim] += offset;
+            }
+
+            self.push_point(point);
+        }
+    }
+
+    fn create_deterministic_ring(&mut self, center: &StructurePoint, size: usize, seed: u64) {
+        let radius = (size / 3) as i32;
+        let mut rng_state = seed;
+
+        for i in 0..size {
+            let angle = (i as f64 / size as f64) * 2.0 * std::f64::consts::PI;
+            let mut point = *center;
+
+            let dims = self.dimensions.min(3);
+
+            if dims >= 2 {
+                point.coordinates[0] += (radius as f64 * angle.cos()) as i16;
+                point.coordinates[1] += (radius as f64 * angle.sin()) as i16;
+
+                if dims >= 3 {
+                    rng_state = rng_state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    point.coordinates[2] += (rng_state % 5) as i16 - 2;
+                }
+            }
+
+            self.push_point(point);
+        }
+    }
+
+    fn create_deterministic_spiral(&mut self, center: &StructurePoint, size: usize, seed: u64) {
+        let mut rng_state = seed;
+
+        for i in 1..=size {
+            let angle = (i as f64 / 4.0) * std::f64::consts::PI;
+            let radius = i as i32 / 2;
+            let mut point = *center;
+
+            let dims = self.dimensions.min(3);
+
+            if dims >= 2 {
+                point.coordinates[0] += (radius as f64 * angle.cos()) as i16;
+                point.coordinates[1] += (radius as f64 * angle.sin()) as i16;
+
+                if dims >= 3 {
+                    rng_state = rng_state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    let z_offset = (rng_state % 3) as i16 + (i as i16 / 3);
+                    point.coordinates[2] += z_offset;
+                }
+            }
+
+            for dim in 3..self.dimensions {
+                rng_state = rng_state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let offset = (rng_state % 7) as i16 - 3;
+                point.coordinates[dim] += offset;
+            }
+
+            self.push_point(point);
+        }
+    }
+
+    fn create_deterministic_scatter(&mut self, center: &StructurePoint, size: usize, seed: u64) {
+        let mut rng_state = seed;
+
+        for _i in 0..size {
+            let mut point = *center;
+
+            for dim in 0..self.dimensions {
+                rng_state = rng_state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let offset = (rng_state % 11) as i16 - 5;
+                point.coordinates[dim] += offset;
+            }
+
+            self.push_point(point);
+        }
+    }
+
+    fn create_basic_structure(&mut self, keycodes: &[u32]) {
+        for &keycode in keycodes {
+            let point = StructurePoint::from_seed(
+                self.original_seed ^ keycode as u64,
+                self.dimensions,
+                self.coordinate_range,
+            );
+            self.char_to_point.insert(keycode, point);
+
+            self.push_point(point);
+
+            let feature_seed = self.original_seed ^ (keycode as u64);
+            let feature_type = feature_seed % 5;
+
+            match feature_type {
+                0 => self.create_deterministic_spike(&point, 5, feature_seed),
+                1 => self.create_deterministic_blob(&point, 6, feature_seed),
+                2 => self.create_deterministic_ring(&point, 7, feature_seed),
+                3 => self.create_deterministic_spiral(&point, 8, feature_seed),
+                _ => self.create_deterministic_scatter(&point, 5, feature_seed),
+            }
+        }
+
+        // in the order of the keycodes, not the map's, which changes from one
+        // process to the next and took the paths with it
+        let points: Vec<_> = keycodes
+            .iter()
+            .filter_map(|keycode| self.char_to_point.get(keycode).copied())
+            .collect();
+        let limit = points.len().min(30);
+
+        for i in 0..limit {
+            if i + 1 < points.len() {
+                self.create_path(&points[i], &points[(i + 1) % points.len()]);
+            }
+        }
+    }
+
+    pub fn modify_with_timing(&mut self, keycode: u32, timing_ms: u64, timestamp: u64) {
+        if let Some(&point) = self.char_to_point.get(&keycode) {
+            let mut timing_point = point;
+
+            let mod_seed = self.original_seed ^ keycode as u64 ^ timing_ms ^ (timestamp % 1000);
+
+            let is_forward = timing_ms.is_multiple_of(2);
+
+            for dim in 0..self.dimensions {
+                let modifier = ((timing_ms + dim as u64) % 5) as i16 - 2;
+
+                if is_forward {
+                    timing_point.coordinates[dim] += modifier;
+                } else {
+                    timing_point.coordinates[dim] -= modifier;
+                }
+            }
+
+            let added = self.active_points.len();
+            self.push_point(timing_point);
+
+            let feature_type = mod_seed % 3;
+
+            if feature_type == 0 {
+                self.create_deterministic_blob(&timing_point, 3, mod_seed);
+            } else if feature_type == 1 {
+                self.create_deterministic_spike(&timing_point, 3, mod_seed);
+            } else {
+                self.create_deterministic_scatter(&timing_point, 3, mod_seed);
+            }
+            // the bounds were calculated before these, and may not reach them
+            self.extend_structure_bounds(added);
+            self.settle_active_points();
+        }
+    }
+
+    // every point building adds goes through here, and is dropped once max_points
+    // are in. the callers draw it either way, so the same phrase caps the same way
+    fn push_point(&mut self, point: StructurePoint) {
+        if self.active_points.len() < self.max_points {
+            self.active_points.push(point);
+        } else {
+            self.points_capped = true;
+        }
+    }
+
+    // back to sorted without repeats after pushing points. what was there is one
+    // sorted run, so this is about linear
+    fn settle_active_points(&mut self) {
+        self.active_points.sort();
+        self.active_points.dedup();
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn active_point_count(&self) -> usize {
+        self.active_points.len()
+    }
+
+    /// Whether building this structure reached [`StructureSystem::max_points`] and
+    /// left points out. Only known for a structure built in this process.
+    pub fn points_capped(&self) -> bool {
+        self.points_capped
+    }
+
+    /// Whether the walk's current position lies within the structure's bounds,
+    /// which under [`Transform::V7`] it always does.
+    pub fn position_in_bounds(&self) -> bool {
+        (0..self.dimensions).all(|i| {
+            let (min_bound, max_bound) = (self.structure_bounds.0[i], self.structure_bounds.1[i]);
+            match self.motion {
+                Motion::Float => {
+                    (min_bound..=max_bound).contains(&self.current_position.coordinates[i])
+                }
+                Motion::Fixed => (to_fixed(min_bound)..=to_fixed(max_bound))
+                    .contains(&self.current_position.fixed[i]),
+            }
+        })
+    }
+}
+
+pub struct SavedPassword {
+    pub name: String,
+    pub description: String,
+    pub structure_system: StructureSystem,
+    pub created_date: u64,
+    pub extra_chars_count: usize,
+    // how many times this configuration has generated output
+    pub use_count: u64,
+    // how input is normalized before generating. a configuration keeps the one it
+    // was created with, so its passwords stay reproducible
+    pub normalization: Normalization,
+    // what this configuration generated from synthetic input where it was created
+    // or first verified, for verify-determinism to compare against
+    pub fingerprint: Option<Fingerprint>,
+    // how setup built the structure. None for records from before format version 3,
+    // which didn't say
+    pub setup_mode: Option<SetupMode>,
+    // the configuration this is a re-key of, see SavedPassword::rekey
+    pub rekeyed_from: Option<String>,
+}
+
+/// The most times one setup can be re-keyed: domain slots name the re-key they
+/// generate with in three bits, see [`DomainSlot::LINEAGE`].
+pub const MAX_LINEAGE: u8 = 7;
+
+/// How setup built a configuration's structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupMode {
+    /// Seeded from the gaps between the setup keystrokes and stepped with their
+    /// timing, so no one can build it again, its owner included.
+    Timed,
+    /// From the phrase and the setup options alone, as create-account and setup
+    /// --reproducible build it. The same phrase and options rebuild it byte for byte.
+    Reproducible,
+}
+
+impl SetupMode {
+    /// The name `inspect` reports it under.
+    pub fn name(self) -> &'static str {
+        match self {
+            SetupMode::Timed => "timed",
+            SetupMode::Reproducible => "reproducible",
+        }
+    }
+
+    // 0 is a record that doesn't say
+    fn to_byte(mode: Option<Self>) -> u8 {
+        match mode {
+            None => 0,
+            Some(SetupMode::Timed) => 1,
+            Some(SetupMode::Reproducible) => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Option<Self>, DecodeError> {
+        match byte {
+            0 => Ok(None),
+            1 => Ok(Some(SetupMode::Timed)),
+            2 => Ok(Some(SetupMode::Reproducible)),
+            _ => Err(DecodeError::Invalid("setup mode")),
+        }
+    }
+}
+
+/// How a configuration normalizes what is typed before generating from it, so the
+/// same text gives the same password whichever way a keyboard composes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Generates from the code points as typed, as configurations from before
+    /// normalization did.
+    None,
+    /// The fixed v1 set: a base followed by one combining mark becomes the Latin-1
+    /// Supplement or Latin Extended-A character, see [`nfc_compose`]. Nothing else
+    /// is composed, a second mark stacked on the result included.
+    V1,
+    /// NFC for the Latin script, Vietnamese, pinyin and stacked marks included,
+    /// see [`nfc`].
+    V2,
+}
+
+impl Normalization {
+    /// What new configurations are created with.
+    pub const CURRENT: Self = Normalization::V2;
+
+    /// The name `inspect` reports it under.
+    pub fn name(self) -> &'static str {
+        match self {
+            Normalization::None => "none",
+            Normalization::V1 => "v1",
+            Normalization::V2 => "v2",
+        }
+    }
+
+    /// The byte a record stores it as. 0 and 1 are what the flag before it was.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Normalization::None => 0,
+            Normalization::V1 => 1,
+            Normalization::V2 => 2,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Normalization::None),
+            1 => Some(Normalization::V1),
+            2 => Some(Normalization::V2),
+            _ => None,
+        }
+    }
+
+    /// `keycodes`, a whole line or phrase, in this normalization's form.
+    pub fn apply(self, keycodes: &mut Vec<u32>) {
+        match self {
+            Normalization::None => {}
+            Normalization::V1 => nfc_normalize(keycodes),
+            Normalization::V2 => nfc::normalize(keycodes),
+        }
+    }
+
+    /// Appends one keystroke to `typed`, which is in this normalization's form
+    /// already. True when what was typed before it changed too, a mark composing
+    /// with the character it follows, so output has to be replayed.
+    pub fn push(self, typed: &mut Vec<u32>, keycode: u32) -> bool {
+        match self {
+            Normalization::None => {}
+            Normalization::V1 => {
+                if let Some(composed) = typed.last().and_then(|&last| nfc_compose(last, keycode)) {
+                    typed.pop();
+                    typed.push(composed);
+                    return true;
+                }
+            }
+            Normalization::V2 => return nfc::push(typed, keycode),
+        }
+        typed.push(keycode);
+        false
+    }
+}
+
+impl Drop for SavedPassword {
+    fn drop(&mut self) {
+        self.name.wipe();
+        self.description.wipe();
+    }
+}
+
+impl SavedPassword {
+    // becomes `decoy` under this one's name, leaving this one's structure to wipe.
+    // the name, description, use count and re-key link are what the listing shows
+    // and stay. everything that describes the setup or shapes the output is the
+    // decoy's: the structure and character set, extra characters, normalization,
+    // when and how it was set up and its fingerprint
+    fn stand_in(&mut self, decoy: &mut SavedPassword) {
+        std::mem::swap(&mut self.structure_system, &mut decoy.structure_system);
+        self.created_date = decoy.created_date;
+        self.extra_chars_count = decoy.extra_chars_count;
+        self.normalization = decoy.normalization;
+        self.fingerprint = decoy.fingerprint;
+        self.setup_mode = decoy.setup_mode;
+    }
+
+    /// A copy of this configuration named `name` that generates `extra_chars_count`
+    /// characters per keystroke, from `character_set` when given. It keeps the
+    /// structure's points and seed, so the entropy of the setup and the domain
+    /// hashes: domains registered with this one keep generating with it until they
+    /// are migrated, see [`DomainTable::migrate`]. `None` once the setup has been
+    /// re-keyed [`MAX_LINEAGE`] times, or for a configuration read without points.
+    pub fn rekey(
+        &self,
+        name: &str,
+        extra_chars_count: usize,
+        character_set: Option<Vec<u32>>,
+    ) -> Option<SavedPassword> {
+        if self.structure_system.lineage >= MAX_LINEAGE || !self.structure_system.points_loaded {
+            return None;
+        }
+        let mut structure_system =
+            StructureSystem::decode(&self.structure_system.to_bytes(), true).ok()?;
+        structure_system.lineage += 1;
+        structure_system.set_name(name.to_string());
+        if let Some(character_set) = character_set {
+            structure_system.set_character_set(character_set);
+        }
+
+        let mut rekeyed = SavedPassword {
+            name: name.to_string(),
+            description: format!("Re-keyed from {}", self.name),
+            structure_system,
+            created_date: self.created_date,
+            extra_chars_count,
+            use_count: 0,
+            normalization: self.normalization,
+            fingerprint: None,
+            setup_mode: self.setup_mode,
+            rekeyed_from: Some(self.name.clone()),
+        };
+        rekeyed.fingerprint = Fingerprint::of(&rekeyed);
+        Some(rekeyed)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        push_format_header(&mut bytes, CONFIG_MAGIC);
+
+        let name_bytes = self.name.as_bytes();
+        bytes.extend_from_slice(&(name_bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name_bytes);
+
+        let desc_bytes = self.description.as_bytes();
+        bytes.extend_from_slice(&(desc_bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(desc_bytes);
+
+        bytes.extend_from_slice(&self.created_date.to_le_bytes());
+        bytes.extend_from_slice(&(self.extra_chars_count as u32).to_le_bytes());
+
+        let structure_bytes = self.structure_system.to_bytes();
+        bytes.extend_from_slice(&(structure_bytes.len() as u32).to_le_bytes());
+        bytes.extend(structure_bytes);
+
+        bytes.extend_from_slice(&self.use_count.to_le_bytes());
+        bytes.push(self.normalization.to_byte());
+        bytes.push(SetupMode::to_byte(self.setup_mode));
+        let rekeyed_from = self.rekeyed_from.as_deref().unwrap_or("").as_bytes();
+        bytes.extend_from_slice(&(rekeyed_from.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(rekeyed_from);
+
+        if let Some(fingerprint) = &self.fingerprint {
+            for stage in [
+                fingerprint.domain_hash,
+                fingerprint.navigation,
+                fingerprint.output,
+            ] {
+                bytes.extend_from_slice(&stage.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(bytes, true)
+    }
+
+    /// The format version a record was written in, 0 for one from before versioning.
+    pub fn format_version(bytes: &[u8]) -> Result<u16, DecodeError> {
+        let mut offset = 0;
+        Ok(read_format_header(bytes, CONFIG_MAGIC, &mut offset, "configuration")?.unwrap_or(0))
+    }
+
+    /// [`SavedPassword::from_bytes`] without the structure's active points and keycode
+    /// map, which only setup reads. Hashing domains and generating work the same and
+    /// decoding is much quicker, but [`PasswordManager`] refuses to store the result,
+    /// as that would drop the points from the vault.
+    pub fn from_bytes_without_points(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(bytes, false)
+    }
+
+    fn check_storable(&self) -> io::Result<()> {
+        if self.structure_system.points_loaded {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "'{}' was read without its structure's points, storing it would lose them",
+                self.name
+            ),
+        ))
+    }
+
+    fn decode(bytes: &[u8], with_points: bool) -> Result<Self, DecodeError> {
+        let mut offset = 0;
+        // every field is there from version 1 on, a legacy record may end early
+        let version = read_format_header(bytes, CONFIG_MAGIC, &mut offset, "configuration")?;
+        let versioned = version.is_some();
+
+        let name = read_text(bytes, &mut offset, "name")?;
+        let description = read_text(bytes, &mut offset, "description")?;
+        let created_date = u64::from_le_bytes(read_array(bytes, &mut offset, "created date")?);
+
+        let extra_chars_count = if bytes.len() - offset >= 4 {
+            u32::from_le_bytes(read_array(bytes, &mut offset, "extra chars count")?) as usize
+        } else if versioned {
+            return Err(DecodeError::Truncated("extra chars count"));
+        } else {
+            diag(
+                Diag::Warning,
+                format_args!("Warning: Using default value for extra_chars_count"),
+            );
+            3
+        };
+
+        let structure_len =
+            u32::from_le_bytes(read_array(bytes, &mut offset, "Structure system length")?) as usize;
+        let structure_bytes = read_bytes(bytes, &mut offset, structure_len, "Structure system")?;
+        let structure_system = StructureSystem::decode(structure_bytes, with_points)?;
+
+        // older configurations were stored without a use counter
+        let use_count = if bytes.len() - offset >= 8 {
+            u64::from_le_bytes(read_array(bytes, &mut offset, "use count")?)
+        } else if versioned {
+            return Err(DecodeError::Truncated("use count"));
+        } else {
+            0
+        };
+
+        // and without normalization, which must stay off for them. before version 5
+        // this was a flag, set for the v1 set
+        let normalization = match bytes.get(offset) {
+            Some(&byte) if version.is_some_and(|version| version >= 5) => {
+                Normalization::from_byte(byte).ok_or(DecodeError::Invalid("normalization"))?
+            }
+            Some(&0) => Normalization::None,
+            Some(_) => Normalization::V1,
+            None if versioned => return Err(DecodeError::Truncated("normalization")),
+            None => Normalization::None,
+        };
+        offset += 1;
+
+        // written from version 3 on
+        let setup_mode = if version.is_some_and(|version| version >= 3) {
+            SetupMode::from_byte(read_array::<1>(bytes, &mut offset, "setup mode")?[0])?
+        } else {
+            None
+        };
+
+        // and from version 4 on, empty for a configuration that is no re-key
+        let rekeyed_from = if version.is_some_and(|version| version >= 4) {
+            Some(read_text(bytes, &mut offset, "re-keyed from")?).filter(|name| !name.is_empty())
+        } else {
+            None
+        };
+
+        // and records from before verify-determinism stored one have no fingerprint.
+        // a legacy record can have bytes left over that are no fingerprint at all
+        let fingerprint = if versioned && offset < bytes.len() {
+            Some(Fingerprint {
+                domain_hash: u64::from_le_bytes(read_array(bytes, &mut offset, "fingerprint")?),
+                navigation: u64::from_le_bytes(read_array(bytes, &mut offset, "fingerprint")?),
+                output: u64::from_le_bytes(read_array(bytes, &mut offset, "fingerprint")?),
+            })
+        } else {
+            None
+        };
+
+        Ok(SavedPassword {
+            name,
+            description,
+            structure_system,
+            created_date,
+            extra_chars_count,
+            use_count,
+            normalization,
+            fingerprint,
+            setup_mode,
+            rekeyed_from,
+        })
+    }
+}
+
+/// The configurations in a vault, and which one generates.
+///
+/// ```
+/// use void_vault::{MemoryStorage, PasswordManager};
+///
+/// let manager = PasswordManager::with_storage(MemoryStorage::default(), true).unwrap();
+/// assert!(manager.saved_passwords.is_empty());
+/// ```
+pub struct PasswordManager<S: VaultStorage = BinaryStorageManager> {
+    pub saved_passwords: Vec<SavedPassword>,
+    storage: S,
+    pub active_structure_idx: Option<usize>,
+    /// Configurations the vault holds that failed to decode, and why. They are left
+    /// out of `saved_passwords`, and stay in the vault untouched.
+    pub unusable: Vec<(String, DecodeError)>,
+    // configurations whose use counter changed since the last save
+    pending_usage: Vec<usize>,
+    // under duress, the decoy standing in for the configurations loaded. their
+    // records must not be written back with its structure
+    standing_in: Option<String>,
+}
+
+impl PasswordManager {
+    /// The vault this process works on, see [`vault_path`].
+    pub fn new(
+        parent_mode: bool,
+        channel: Option<Arc<Mutex<ProcessChannel>>>,
+        silent: bool,
+    ) -> io::Result<Self> {
+        let storage = BinaryStorageManager::new(parent_mode, channel)?;
+        let mut decoys = DomainTable::new();
+        decoys.load_region(&storage.executable_path, TableRegion::Decoy)?;
+        Self::with_decoy(storage, silent, &decoys, DURESS.load(Ordering::SeqCst))
+    }
+}
+
+impl<S: VaultStorage> PasswordManager<S> {
+    /// Loads every configuration `storage` holds. `silent` keeps the progress
+    /// notes off stderr.
+    pub fn with_storage(storage: S, silent: bool) -> io::Result<Self> {
+        Self::with_decoy(storage, silent, &DomainTable::new(), false)
+    }
+
+    /// Like [`PasswordManager::with_storage`], leaving out the configuration `decoys`
+    /// marks as the decoy unless it's the only one. Under `duress` the decoy stands
+    /// in for every other configuration instead: each keeps its name, description,
+    /// use count and `rekeyed_from`, takes every other field from the decoy,
+    /// including its format version, and isn't saved back.
+    pub fn with_decoy(
+        storage: S,
+        silent: bool,
+        decoys: &DomainTable,
+        duress: bool,
+    ) -> io::Result<Self> {
+        let mut manager = PasswordManager {
+            saved_passwords: Vec::new(),
+            storage,
+            active_structure_idx: None,
+            unusable: Vec::new(),
+            pending_usage: Vec::new(),
+            standing_in: None,
+        };
+
+        manager.load_all_passwords(silent, decoys, duress)?;
+
+        if !manager.saved_passwords.is_empty() {
+            manager.active_structure_idx = Some(0);
+            if !silent {
+                diag(
+                    Diag::Detail,
+                    format_args!("Active Structure: {}", manager.saved_passwords[0].name),
+                );
+            }
+        }
+
+        Ok(manager)
+    }
+
+    /// The format version the configuration at `idx` is stored in, see
+    /// [`SavedPassword::format_version`]. None when the vault doesn't hold it.
+    pub fn format_version(&self, idx: usize) -> Option<u16> {
+        let name = &self.saved_passwords.get(idx)?.name;
+        let name = self.standing_in.as_ref().unwrap_or(name);
+        let (bytes, _) = self.storage.retrieve(name).ok()??;
+        SavedPassword::format_version(bytes).ok()
+    }
+
+    /// The configuration that generates for `domain` when `idx` is the one picked:
+    /// the re-key of the same setup the domain's slot names, else `idx` itself.
+    pub fn resolve_domain(&mut self, idx: usize, domain: &str, table: &DomainTable) -> usize {
+        let structure = &mut self.saved_passwords[idx].structure_system;
+        let Some(lineage) = table.lineage(domain, structure) else {
+            return idx;
+        };
+        let seed = structure.original_seed;
+        self.saved_passwords
+            .iter()
+            .position(|p| {
+                p.structure_system.original_seed == seed && p.structure_system.lineage == lineage
+            })
+            .unwrap_or(idx)
+    }
+
+    /// The configuration named `account`, or with None the first, when it's one that
+    /// is in the vault but failed to decode.
+    pub fn find_unusable(&self, account: Option<&str>) -> Option<&(String, DecodeError)> {
+        self.unusable
+            .iter()
+            .find(|(name, _)| account.is_none_or(|account| account == name))
+    }
+
+    /// The configuration named `account`, or else the first that decodes, read with
+    /// [`SavedPassword::from_bytes_without_points`] and without loading the others:
+    /// all the domain table needs to hash a domain. None when there is no such
+    /// configuration.
+    pub fn load_one_without_points(storage: &S, account: Option<&str>) -> Option<SavedPassword> {
+        Self::load_one_with_decoy(storage, account, &DomainTable::new(), false)
+    }
+
+    /// [`PasswordManager::load_one_without_points`], with the decoy `decoys` marks
+    /// left out or standing in as [`PasswordManager::with_decoy`] has it.
+    pub fn load_one_with_decoy(
+        storage: &S,
+        account: Option<&str>,
+        decoys: &DomainTable,
+        duress: bool,
+    ) -> Option<SavedPassword> {
+        let entries = storage.list_all();
+        let decoy = Self::decoy_among(&entries, decoys);
+        let load = |name: &str| {
+            let (data, _) = storage.retrieve(name).ok()??;
+            SavedPassword::from_bytes_without_points(data).ok()
+        };
+
+        let mut password = entries
+            .iter()
+            .filter(|(name, _)| Some(name) != decoy.as_ref())
+            .filter(|(name, _)| account.is_none_or(|account| account == name))
+            .find_map(|(name, description)| {
+                let mut password = load(name)?;
+                password.description = description.clone();
+                Some(password)
+            })?;
+        if let (Some(decoy), true) = (&decoy, duress) {
+            let mut stand_in = load(decoy)?;
+            password.stand_in(&mut stand_in);
+        }
+        Some(password)
+    }
+
+    // the decoy among `entries`, when `decoys` marks one and it isn't all there is
+    fn decoy_among(entries: &[(String, String)], decoys: &DomainTable) -> Option<String> {
+        if entries.len() < 2 {
+            return None;
+        }
+        entries
+            .iter()
+            .find(|(name, _)| decoys.is_decoy(name))
+            .map(|(name, _)| name.clone())
+    }
+
+    fn load_all_passwords(
+        &mut self,
+        silent: bool,
+        decoys: &DomainTable,
+        duress: bool,
+    ) -> io::Result<()> {
+        self.saved_passwords.clear();
+        self.unusable.clear();
+
+        let password_entries = self.storage.list_all();
+        let decoy = Self::decoy_among(&password_entries, decoys);
+
+        for (name, description) in password_entries {
+            if Some(&name) == decoy.as_ref() {
+                continue;
+            }
+            if let Ok(Some((data, _))) = self.storage.retrieve(&name) {
+                match SavedPassword::from_bytes(data) {
+                    Ok(mut password) => {
+                        password.description = description;
+                        self.saved_passwords.push(password);
+                    }
+                    Err(e) => {
+                        diag(
+                            Diag::Warning,
+                            format_args!("Error loading structure '{}': {}", name, e),
+                        );
+                        self.unusable.push((name, e));
+                    }
+                }
+            }
+        }
+
+        // a decoy that doesn't load stands in for nothing, and nothing is served
+        if let (Some(decoy), true) = (&decoy, duress) {
+            let storage = &self.storage;
+            self.saved_passwords.retain_mut(|password| {
+                let stand_in = match storage.retrieve(decoy) {
+                    Ok(Some((data, _))) => SavedPassword::from_bytes(data).ok(),
+                    _ => None,
+                };
+                match stand_in {
+                    Some(mut stand_in) => {
+                        password.stand_in(&mut stand_in);
+                        true
+                    }
+                    None => false,
+                }
+            });
+            self.standing_in = Some(decoy.clone());
+        }
+
+        if !silent {
+            diag(
+                Diag::Detail,
+                format_args!(
+                    "Loaded {} saved password configurations",
+                    self.saved_passwords.len()
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn save_password(&mut self, password: &SavedPassword) -> io::Result<()> {
+        password.check_storable()?;
+        if self.standing_in.is_some()
+            && self.saved_passwords.iter().any(|p| p.name == password.name)
+        {
+            return Ok(());
+        }
+        let bytes = Wiped(password.to_bytes());
+
+        self.storage
+            .store(password.name.clone(), password.description.clone(), &bytes)?;
+
+        Ok(())
+    }
+
+    pub fn add_password(&mut self, password: SavedPassword) -> io::Result<()> {
+        self.save_password(&password)?;
+
+        self.saved_passwords.push(password);
+
+        // to ensure ease of use, this checks is the first password and make it active
+        if self.saved_passwords.len() == 1 {
+            self.active_structure_idx = Some(0);
+        }
+
+        Ok(())
+    }
+
+    // counts a generation in memory only, rewriting the binary on every
+    // keystroke would be far too slow. flush_usage persists it later
+    pub fn record_use(&mut self, idx: usize) {
+        if let Some(password) = self.saved_passwords.get_mut(idx) {
+            password.use_count = password.use_count.saturating_add(1);
+            if !self.pending_usage.contains(&idx) {
+                self.pending_usage.push(idx);
+            }
+        }
+    }
+
+    // term and io modes only read the vault, so saving the counter is best
+    // effort when the browser host currently owns it
+    pub fn persist_usage(&mut self) {
+        if self.pending_usage.is_empty() {
+            return;
+        }
+
+        // storage nothing else can reach has nobody to lock out
+        let _lock = match self.storage.lock_path() {
+            Some(path) => match InstanceLock::acquire(path, InstanceLock::WRITE_WAIT) {
+                Ok(lock) => Some(lock),
+                Err(e) => {
+                    diag(
+                        Diag::Warning,
+                        format_args!("Warning: Use counter not saved: {}", e),
+                    );
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        if let Err(e) = self.flush_usage() {
+            diag(
+                Diag::Warning,
+                format_args!("Warning: Could not save use counter: {}", e),
+            );
+        }
+    }
+
+    pub fn flush_usage(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending_usage);
+        if self.standing_in.is_some() {
+            return Ok(());
+        }
+
+        for idx in pending {
+            if let Some(password) = self.saved_passwords.get(idx) {
+                password.check_storable()?;
+                let bytes = Wiped(password.to_bytes());
+                self.storage
+                    .store(password.name.clone(), password.description.clone(), &bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// compares without stopping at the first difference, so the time taken says
+// nothing about where two byte strings diverge. only the lengths leak
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b) {
+        diff |= x ^ y;
+    }
+    std::hint::black_box(diff) == 0
+}
+
+/// Fills `buf` from the operating system's random number generator:
+/// `/dev/urandom` on unix, `RtlGenRandom` on Windows. Fails where there is none.
+pub fn os_random(buf: &mut [u8]) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Read;
+        File::open("/dev/urandom")?.read_exact(buf)
+    }
+
+    #[cfg(windows)]
+    {
+        #[link(name = "advapi32")]
+        extern "system" {
+            #[link_name = "SystemFunction036"]
+            fn RtlGenRandom(buffer: *mut u8, length: u32) -> u8;
+        }
+
+        for chunk in buf.chunks_mut(u32::MAX as usize) {
+            if unsafe { RtlGenRandom(chunk.as_mut_ptr(), chunk.len() as u32) } == 0 {
+                return Err(io::Error::other("RtlGenRandom failed"));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = buf;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "No random number generator on this platform",
+        ))
+    }
+}
+
+// volatile writes so the compiler can't drop the zeroing as a dead store
+// right before a deallocation. only meant for plain data where all-zero is valid
+pub fn zero_memory<T: Copy>(data: &mut [T]) {
+    let len = std::mem::size_of_val(data);
+    let ptr = data.as_mut_ptr() as *mut u8;
+    unsafe {
+        for i in 0..len {
+            std::ptr::write_volatile(ptr.add(i), 0);
+        }
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+// zeroes the whole allocation, including spare capacity that may still
+// hold bytes from earlier pushes, then empties the buffer
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl<T: Copy> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        zero_memory(self);
+        zero_memory(self.spare_capacity_mut());
+        self.clear();
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        unsafe { self.as_mut_vec().wipe() }
+    }
+}
+
+// holds a transient secret and wipes it on drop, so `?` and early returns
+// in the io loops can't leave phrase bytes or output in freed heap pages
+pub struct Wiped<T: Wipe>(pub T);
+
+impl<T: Wipe> std::ops::Deref for Wiped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> std::ops::DerefMut for Wiped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Wipe> Drop for Wiped<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+// stable error codes for the json-io protocol and the CLI exit status. the extension
+// vendors this list (see --list-error-codes), so entries are only ever appended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    MissingDomain,
+    TableFull,
+    NotInPreview,
+    SessionLocked,
+    BadRequest,
+    UnsupportedType,
+    MessageTooLarge,
+    StorageFailure,
+    NoConfiguration,
+    VaultInUse,
+    RateLimited,
+    UnknownAccount,
+    PreviewActive,
+    AccountExists,
+    ConfirmMismatch,
+    UnknownDomain,
+    WeakPhrase,
+    NoTerminal,
+    IdleTimeout,
+    UnknownProfile,
+    ScriptFailed,
+    RecoveryInvalid,
+    BufferTooSmall,
+    CannotType,
+    NotDeterministic,
+    UnusableConfiguration,
+    VaultLocked,
+    Usage,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 28] = [
+        ErrorCode::MissingDomain,
+        ErrorCode::TableFull,
+        ErrorCode::NotInPreview,
+        ErrorCode::SessionLocked,
+        ErrorCode::BadRequest,
+        ErrorCode::UnsupportedType,
+        ErrorCode::MessageTooLarge,
+        ErrorCode::StorageFailure,
+        ErrorCode::NoConfiguration,
+        ErrorCode::VaultInUse,
+        ErrorCode::RateLimited,
+        ErrorCode::UnknownAccount,
+        ErrorCode::PreviewActive,
+        ErrorCode::AccountExists,
+        ErrorCode::ConfirmMismatch,
+        ErrorCode::UnknownDomain,
+        ErrorCode::WeakPhrase,
+        ErrorCode::NoTerminal,
+        ErrorCode::IdleTimeout,
+        ErrorCode::UnknownProfile,
+        ErrorCode::ScriptFailed,
+        ErrorCode::RecoveryInvalid,
+        ErrorCode::BufferTooSmall,
+        ErrorCode::CannotType,
+        ErrorCode::NotDeterministic,
+        ErrorCode::UnusableConfiguration,
+        ErrorCode::VaultLocked,
+        ErrorCode::Usage,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::MissingDomain => "missing_domain",
+            ErrorCode::TableFull => "table_full",
+            ErrorCode::NotInPreview => "not_in_preview",
+            ErrorCode::SessionLocked => "session_locked",
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::UnsupportedType => "unsupported_type",
+            ErrorCode::MessageTooLarge => "message_too_large",
+            ErrorCode::StorageFailure => "storage_failure",
+            ErrorCode::NoConfiguration => "no_configuration",
+            ErrorCode::VaultInUse => "vault_in_use",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::UnknownAccount => "unknown_account",
+            ErrorCode::PreviewActive => "preview_active",
+            ErrorCode::AccountExists => "account_exists",
+            ErrorCode::ConfirmMismatch => "confirm_mismatch",
+            ErrorCode::UnknownDomain => "unknown_domain",
+            ErrorCode::WeakPhrase => "weak_phrase",
+            ErrorCode::NoTerminal => "no_terminal",
+            ErrorCode::IdleTimeout => "idle_timeout",
+            ErrorCode::UnknownProfile => "unknown_profile",
+            ErrorCode::ScriptFailed => "script_failed",
+            ErrorCode::RecoveryInvalid => "recovery_invalid",
+            ErrorCode::BufferTooSmall => "buffer_too_small",
+            ErrorCode::CannotType => "cannot_type",
+            ErrorCode::NotDeterministic => "not_deterministic",
+            ErrorCode::UnusableConfiguration => "unusable_configuration",
+            ErrorCode::VaultLocked => "vault_locked",
+            ErrorCode::Usage => "usage",
+        }
+    }
+
+    // the failures a script checks for most get the low statuses: 2 for a command line
+    // that didn't parse, as shells and getopt have it, then no configuration, unknown
+    // domain, storage and a full table. the rest count up from 10, clear of 101
+    // (panic) and 128+ (signals), which main() already uses
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCode::Usage => 2,
+            ErrorCode::NoConfiguration => 3,
+            ErrorCode::UnknownDomain => 4,
+            ErrorCode::StorageFailure => 5,
+            ErrorCode::TableFull => 6,
+            ErrorCode::MissingDomain => 10,
+            ErrorCode::NotInPreview => 12,
+            ErrorCode::SessionLocked => 13,
+            ErrorCode::BadRequest => 14,
+            ErrorCode::UnsupportedType => 15,
+            ErrorCode::MessageTooLarge => 16,
+            ErrorCode::VaultInUse => 19,
+            ErrorCode::RateLimited => 20,
+            ErrorCode::UnknownAccount => 21,
+            ErrorCode::PreviewActive => 22,
+            ErrorCode::AccountExists => 23,
+            ErrorCode::ConfirmMismatch => 24,
+            ErrorCode::WeakPhrase => 26,
+            ErrorCode::NoTerminal => 27,
+            ErrorCode::IdleTimeout => 28,
+            ErrorCode::UnknownProfile => 29,
+            ErrorCode::ScriptFailed => 30,
+            ErrorCode::RecoveryInvalid => 31,
+            ErrorCode::BufferTooSmall => 32,
+            ErrorCode::CannotType => 33,
+            ErrorCode::NotDeterministic => 34,
+            ErrorCode::UnusableConfiguration => 35,
+            ErrorCode::VaultLocked => 36,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::MissingDomain => "The request needs a domain and none was given",
+            ErrorCode::TableFull => "All 512 domain slots are in use",
+            ErrorCode::NotInPreview => "There is no counter preview to commit or cancel",
+            ErrorCode::SessionLocked => "The session locked after being idle, activate again",
+            ErrorCode::BadRequest => "The request or argument could not be understood",
+            ErrorCode::UnsupportedType => "This binary does not know the message type",
+            ErrorCode::MessageTooLarge => "The message is larger than the host accepts",
+            ErrorCode::StorageFailure => "Reading or writing the vault failed",
+            ErrorCode::NoConfiguration => "No configuration has been created yet",
+            ErrorCode::VaultInUse => "Another process holds the vault lock",
+            ErrorCode::RateLimited => "Too many requests, slow down or activate again",
+            ErrorCode::UnknownAccount => "No configuration has that name",
+            ErrorCode::PreviewActive => {
+                "The domain is in a counter preview, commit or cancel it first"
+            }
+            ErrorCode::AccountExists => "A configuration with that name already exists",
+            ErrorCode::ConfirmMismatch => "The phrase typed to confirm produced a different output",
+            ErrorCode::UnknownDomain => "The domain is not registered",
+            ErrorCode::WeakPhrase => "The setup phrase is below the minimum length or variety",
+            ErrorCode::NoTerminal => "--tty-prompt has no terminal to prompt on",
+            ErrorCode::IdleTimeout => "Nothing was typed for the --timeout, the screen was cleared",
+            ErrorCode::UnknownProfile => "No profile has that name",
+            ErrorCode::ScriptFailed => "A scenario's expectation did not match",
+            ErrorCode::RecoveryInvalid => {
+                "A recovery sheet line failed its check, or the passphrase is wrong"
+            }
+            ErrorCode::BufferTooSmall => "The caller's buffer is too small for the output",
+            ErrorCode::CannotType => {
+                "--type found no way to type here, or the layout lacks characters of the output"
+            }
+            ErrorCode::NotDeterministic => {
+                "This build generates other output than the golden vectors expect"
+            }
+            ErrorCode::UnusableConfiguration => {
+                "The configuration is damaged and can't generate, see the message for why"
+            }
+            ErrorCode::VaultLocked => {
+                "The vault has an unlock secret, and it wasn't given or was wrong"
+            }
+            ErrorCode::Usage => "The command line could not be parsed, see --help",
+        }
+    }
+
+    pub fn error(self, message: impl Into<String>) -> io::Error {
+        io::Error::other(CodedError {
+            code: self,
+            message: message.into(),
+        })
+    }
+
+    pub fn from_io(e: &io::Error) -> Self {
+        if let Some(coded) = e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<CodedError>())
+        {
+            return coded.code;
+        }
+        match e.kind() {
+            io::ErrorKind::WouldBlock => ErrorCode::VaultInUse,
+            io::ErrorKind::InvalidInput => ErrorCode::BadRequest,
+            _ => ErrorCode::StorageFailure,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CodedError {
+    code: ErrorCode,
+    message: String,
+}
+
+impl std::fmt::Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+// ghost navigation: walks the structure through the domain hash and counter without
+// emitting anything, so each domain+counter starts generating from its own position
+pub fn ghost_navigate(structure: &mut StructureSystem, domain_hash: &[u8; 64], counter: u16) {
+    for &hash_byte in &domain_hash[..8] {
+        let _ = structure.transform_char(hash_byte as u32, 0);
+    }
+
+    // Use counter as both direct value and derived values for more entropy
+    let counter_u32 = counter as u32;
+    let _ = structure.transform_char(counter_u32, 0);
+    let _ = structure.transform_char(counter_u32.wrapping_mul(7), 0);
+    let _ = structure.transform_char(counter_u32.wrapping_add(13), 0);
+}
+
+/// One golden vector: a fixed structure taken through domain hashing, ghost
+/// navigation and typing, and the hash its output has on the platform the
+/// vectors were generated on.
+pub struct DeterminismVector {
+    pub name: &'static str,
+    pub motion: Motion,
+    pub transform: Transform,
+    pub expected: u64,
+}
+
+/// The golden vectors, one per [`Motion`] under [`Transform::V1`] and one per later
+/// [`Transform`]. A build that computes other hashes for them would generate
+/// other passwords, see [`DeterminismVector::compute`].
+pub const DETERMINISM_VECTORS: [DeterminismVector; 8] = [
+    DeterminismVector {
+        name: "f64 motion",
+        motion: Motion::Float,
+        transform: Transform::V1,
+        expected: 0xa4d5dccb32af453f,
+    },
+    DeterminismVector {
+        name: "fixed-point motion",
+        motion: Motion::Fixed,
+        transform: Transform::V1,
+        expected: 0xba8cdacb18c4bcbc,
+    },
+    DeterminismVector {
+        name: "fixed-point motion, transform v2",
+        motion: Motion::Fixed,
+        transform: Transform::V2,
+        expected: 0x921105905b828ad6,
+    },
+    DeterminismVector {
+        name: "fixed-point motion, transform v3",
+        motion: Motion::Fixed,
+        transform: Transform::V3,
+        expected: 0x35d847b2ffea39c3,
+    },
+    DeterminismVector {
+        name: "fixed-point motion, transform v4",
+        motion: Motion::Fixed,
+        transform: Transform::V4,
+        expected: 0xeab447e3aa34ce2e,
+    },
+    DeterminismVector {
+        name: "fixed-point motion, transform v5",
+        motion: Motion::Fixed,
+        transform: Transform::V5,
+        expected: 0xd33c5081794a04e4,
+    },
+    DeterminismVector {
+        name: "fixed-point motion, transform v6",
+        motion: Motion::Fixed,
+        transform: Transform::V6,
+        expected: 0xc251bbf2a6b6511a,
+    },
+    DeterminismVector {
+        name: "fixed-point motion, transform v7",
+        motion: Motion::Fixed,
+        transform: Transform::V7,
+        expected: 0xc251bbf2a6b6511a,
+    },
+];
+
+impl DeterminismVector {
+    /// The vector's hash on this platform. The structure is stored and loaded
+    /// again first, so the bytes in a vault are covered too, and the input is typed
+    /// once straight through the structure and once through [`generate_keystroke`]
+    /// with its feedback chain.
+    pub fn compute(&self) -> u64 {
+        const DOMAINS: [(&str, u16); 3] = [("example.com", 0), ("example.com", 3), ("a.co", 1)];
+        const INPUT: &str = "correct horse battery staple 0123456789 ~!";
+
+        let mut structure = StructureSystem::new(0x5EED_F00D, 7, 17);
+        structure.set_character_set((33..127).collect());
+        structure.motion = self.motion;
+        structure.transform = self.transform;
+        let mut config = match StructureSystem::from_bytes(&structure.to_bytes()) {
+            Ok(structure_system) => SavedPassword {
+                name: String::new(),
+                description: String::new(),
+                structure_system,
+                created_date: 0,
+                extra_chars_count: 2,
+                use_count: 0,
+                normalization: Normalization::None,
+                fingerprint: None,
+                setup_mode: None,
+                rekeyed_from: None,
+            },
+            Err(_) => return 0,
+        };
+
+        // FNV-1a over the domain hashes and every output code, little-endian
+        let mut hash = 0xcbf29ce484222325u64;
+        let mut absorb = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        };
+
+        let mut output = Vec::new();
+        let mut typed = String::new();
+        let mut feedbacks = Vec::new();
+        for (domain, counter) in DOMAINS {
+            let structure = &mut config.structure_system;
+            let domain_hash = structure.hash_domain(domain);
+            absorb(&domain_hash);
+
+            structure.full_reset();
+            ghost_navigate(structure, &domain_hash, counter);
+            for ch in INPUT.chars() {
+                output.clear();
+                structure.transform_char_into(ch as u32, 2, &mut output);
+                for code in &output {
+                    absorb(&code.to_le_bytes());
+                }
+            }
+
+            config.structure_system.full_reset();
+            ghost_navigate(&mut config.structure_system, &domain_hash, counter);
+            feedbacks.clear();
+            for ch in INPUT.chars() {
+                generate_keystroke_into(&mut config, &mut feedbacks, ch as u32, &mut typed);
+                absorb(typed.as_bytes());
+            }
+        }
+        hash
+    }
+}
+
+/// Hashes of what a configuration generates from built-in synthetic input, one per
+/// stage, stored with it so a copy of the vault on another machine can be checked
+/// against where it was made. None of the input is anything the configuration is
+/// really used for, so a fingerprint gives away no password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The synthetic domains' hashes.
+    pub domain_hash: u64,
+    /// Where ghost navigation leaves the structure for each domain and counter.
+    pub navigation: u64,
+    /// The output of typing the synthetic phrase from there.
+    pub output: u64,
+}
+
+impl Fingerprint {
+    const DOMAINS: [(&'static str, u16); 3] = [
+        ("fingerprint.invalid", 0),
+        ("fingerprint.invalid", 4),
+        ("second.fingerprint.invalid", 1),
+    ];
+    const PHRASE: &'static str = "a synthetic phrase to fingerprint with, 0123456789 ~!";
+
+    /// The fingerprint `config` has in this build, generating from it as stored and
+    /// loaded again. None when its record doesn't decode.
+    pub fn of(config: &SavedPassword) -> Option<Self> {
+        let mut config = SavedPassword::from_bytes_without_points(&config.to_bytes()).ok()?;
+        // FNV-1a, one running hash per stage
+        let absorb = |hash: &mut u64, bytes: &[u8]| {
+            for &byte in bytes {
+                *hash = (*hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        };
+        let mut fingerprint = Fingerprint {
+            domain_hash: 0xcbf29ce484222325,
+            navigation: 0xcbf29ce484222325,
+            output: 0xcbf29ce484222325,
+        };
+
+        let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+        for (domain, counter) in Self::DOMAINS {
+            let structure = &mut config.structure_system;
+            let domain_hash = structure.hash_domain(domain);
+            absorb(&mut fingerprint.domain_hash, &domain_hash);
+
+            let mut session = SessionState::empty();
+            session.active_domain_hash = Some(domain_hash);
+            session.active_counter = counter;
+            session.initialized = true;
+            restart_session(&session, structure);
+            let position = structure
+                .current_position
+                .hash_position(structure.original_seed, structure.motion);
+            absorb(&mut fingerprint.navigation, &position.to_le_bytes());
+            absorb(
+                &mut fingerprint.navigation,
+                &[structure.accumulated_path_memory],
+            );
+
+            generation.clear();
+            for ch in Self::PHRASE.chars() {
+                absorb(
+                    &mut fingerprint.output,
+                    generation.step(&session, &mut config, ch as u32).as_bytes(),
+                );
+            }
+            session.scrub();
+        }
+        Some(fingerprint)
+    }
+
+    /// The first stage where this fingerprint differs from `stored`. The stages
+    /// after it are computed from it, so they differ too whatever their own cause.
+    pub fn diverged_stage(&self, stored: &Fingerprint) -> Option<&'static str> {
+        if self.domain_hash != stored.domain_hash {
+            Some("domain hash")
+        } else if self.navigation != stored.navigation {
+            Some("ghost navigation")
+        } else if self.output != stored.output {
+            Some("character output")
+        } else {
+            None
+        }
+    }
+}
+
+/// What `inspect` tells about a configuration: the shape of its geometry, for
+/// telling why generation behaves as it does. Never a coordinate, the seed or
+/// anything else that generates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    pub name: String,
+    pub created_date: u64,
+    /// The format the record was stored in, 0 for one from before versioning.
+    pub format_version: u16,
+    /// None for a record from before setup modes were stored.
+    pub setup_mode: Option<SetupMode>,
+    pub normalization: Normalization,
+    pub dimensions: usize,
+    pub coordinate_range: i32,
+    pub character_set: usize,
+    /// The preset the character set is, see [`pool::name_of`].
+    pub pool: &'static str,
+    /// How many of the character set's codes fall in each of
+    /// [`Inspection::CODE_POINT_RANGES`], in that order.
+    pub code_point_ranges: Vec<(&'static str, usize)>,
+    pub active_points: usize,
+    /// The lowest and highest coordinate of each dimension a walk stays within.
+    pub structure_bounds: (Vec<f64>, Vec<f64>),
+    pub base_step_size: f64,
+    pub step_variance: f64,
+    pub extra_chars_count: usize,
+}
+
+impl Inspection {
+    /// Each range's name and its first and last code point. Together they cover
+    /// every code point.
+    pub const CODE_POINT_RANGES: [(&'static str, u32, u32); 6] = [
+        ("ascii", 0x0000, 0x007F),
+        ("latin", 0x0080, 0x024F),
+        ("other_scripts", 0x0250, 0x1FFF),
+        ("symbols", 0x2000, 0x2BFF),
+        ("cjk_and_other_bmp", 0x2C00, 0xFFFF),
+        ("supplementary", 0x1_0000, 0x10_FFFF),
+    ];
+
+    pub fn of(config: &SavedPassword, format_version: u16) -> Self {
+        let structure = &config.structure_system;
+        let code_point_ranges = Self::CODE_POINT_RANGES
+            .iter()
+            .map(|&(name, first, last)| {
+                let count = structure
+                    .character_set
+                    .iter()
+                    .filter(|&&code| (first..=last).contains(&code))
+                    .count();
+                (name, count)
+            })
+            .collect();
+
+        Inspection {
+            name: config.name.clone(),
+            created_date: config.created_date,
+            format_version,
+            setup_mode: config.setup_mode,
+            normalization: config.normalization,
+            dimensions: structure.dimensions,
+            coordinate_range: structure.coordinate_range,
+            character_set: structure.character_set.len(),
+            pool: pool::name_of(&structure.character_set),
+            code_point_ranges,
+            active_points: structure.active_points.len(),
+            structure_bounds: structure.structure_bounds.clone(),
+            base_step_size: structure.base_step_size,
+            step_variance: structure.step_variance,
+            extra_chars_count: config.extra_chars_count,
+        }
+    }
+
+    /// One JSON object, the shape json-io's INSPECT and `inspect --json` answer with.
+    pub fn to_json(&self) -> String {
+        // a damaged record may decode to NaN, which JSON has no number for
+        let number = |value: f64| {
+            if value.is_finite() {
+                value.to_string()
+            } else {
+                "null".to_string()
+            }
+        };
+        let list = |values: &[f64]| {
+            values
+                .iter()
+                .map(|&value| number(value))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let ranges: Vec<String> = self
+            .code_point_ranges
+            .iter()
+            .map(|(name, count)| format!("\"{}\":{}", name, count))
+            .collect();
+
+        format!(
+            "{{\"name\":\"{}\",\"created_date\":{},\"format_version\":{},\"setup_mode\":{},\"normalization\":\"{}\",\"dimensions\":{},\"coordinate_range\":{},\"character_set\":{{\"pool\":\"{}\",\"length\":{},\"ranges\":{{{}}}}},\"active_points\":{},\"structure_bounds\":{{\"min\":[{}],\"max\":[{}]}},\"base_step_size\":{},\"step_variance\":{},\"extra_chars\":{}}}",
+            escape_json(&self.name),
+            self.created_date,
+            self.format_version,
+            self.setup_mode
+                .map_or("null".to_string(), |mode| format!("\"{}\"", mode.name())),
+            self.normalization.name(),
+            self.dimensions,
+            self.coordinate_range,
+            self.pool,
+            self.character_set,
+            ranges.join(","),
+            self.active_points,
+            list(&self.structure_bounds.0),
+            list(&self.structure_bounds.1),
+            number(self.base_step_size),
+            number(self.step_variance),
+            self.extra_chars_count
+        )
+    }
+}
+
+// a rough strength figure for generated output: length times log2 of the pool implied
+// by the character classes present. only counts are kept, never the characters
+#[derive(Default)]
+pub struct OutputEstimate {
+    classes: u8,
+    pub length: usize,
+}
+
+impl OutputEstimate {
+    // (bit, pool size). ASCII is split the usual way, the rest by code point range
+    const CLASSES: [(u8, u32); 8] = [
+        (1, 26),           // a-z
+        (1 << 1, 26),      // A-Z
+        (1 << 2, 10),      // 0-9
+        (1 << 3, 33),      // printable ASCII symbols and space
+        (1 << 4, 464),     // U+0080..U+024F, Latin-1 and Latin Extended
+        (1 << 5, 1456),    // U+0250..U+07FF, the rest of two byte UTF-8
+        (1 << 6, 61440),   // U+0800..U+FFFF, the rest of the BMP less surrogates
+        (1 << 7, 1048576), // supplementary planes
+    ];
+
+    pub fn of(output: &str) -> Self {
+        let mut estimate = OutputEstimate::default();
+        for ch in output.chars() {
+            estimate.add(ch);
+        }
+        estimate
+    }
+
+    fn add(&mut self, ch: char) {
+        let bit = match ch as u32 {
+            0x61..=0x7A => 1,
+            0x41..=0x5A => 1 << 1,
+            0x30..=0x39 => 1 << 2,
+            0x00..=0x7F => 1 << 3,
+            0x80..=0x24F => 1 << 4,
+            0x250..=0x7FF => 1 << 5,
+            0x800..=0xFFFF => 1 << 6,
+            _ => 1 << 7,
+        };
+        self.classes |= bit;
+        self.length += 1;
+    }
+
+    pub fn entropy_bits(&self) -> u32 {
+        let pool: u32 = Self::CLASSES
+            .iter()
+            .filter(|&&(bit, _)| self.classes & bit != 0)
+            .map(|&(_, size)| size)
+            .sum();
+        if pool < 2 {
+            return 0;
+        }
+        (self.length as f64 * (pool as f64).log2()) as u32
+    }
+}
+
+// a domain's max_length and char_types applied to generated output, the same way the
+// extension's normalizePassword does, so the terminal and the browser agree
+#[derive(Clone, Copy)]
+pub struct OutputRules {
+    pub max_length: u16, // 0 = unlimited
+    pub char_types: u8,  // DomainSlot bits, 127 = everything
+    // nothing is mapped onto a confusable character
+    pub no_confusables: bool,
+    // held in the session, before these are applied, see shape_password
+    pub positions: Positions,
+}
+
+impl OutputRules {
+    pub const NONE: Self = OutputRules {
+        max_length: 0,
+        char_types: 127,
+        no_confusables: false,
+        positions: Positions::NONE,
+    };
+
+    // (bit, characters) for the ASCII classes, in the order the extension joins them
+    const ASCII_CLASSES: [(u8, &'static str); 5] = [
+        (1, "abcdefghijklmnopqrstuvwxyz"),
+        (1 << 1, "ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+        (1 << 2, "0123456789"),
+        (1 << 3, "!@#$%^&*"),
+        (1 << 4, "()_+-=[]{}|;:,.<>?~`'\"\\/"),
+    ];
+    const DIGITS: u8 = 1 << 2;
+    const EMOJIS: u8 = 1 << 5;
+    const EXTENDED_UNICODE: u8 = 1 << 6;
+
+    // the ranges normalizer.js counts as emoji
+    fn is_emoji(code: u32) -> bool {
+        matches!(
+            code,
+            0x1F600..=0x1F64F
+                | 0x1F300..=0x1F5FF
+                | 0x1F680..=0x1F6FF
+                | 0x1F900..=0x1F9FF
+                | 0x2600..=0x26FF
+                | 0x2700..=0x27BF
+                | 0x1F000..=0x1F02F
+                | 0x1F0A0..=0x1F0FF
+                | 0x1FA70..=0x1FAFF
+        )
+    }
+
+    // the ASCII disallowed characters map onto
+    fn allowed(&self) -> Wiped<Vec<char>> {
+        let types = self.char_types & 127;
+        Wiped(
+            Self::ASCII_CLASSES
+                .iter()
+                .filter(|&&(bit, _)| types & bit != 0)
+                .flat_map(|&(_, chars)| chars.chars())
+                .filter(|&ch| !(self.no_confusables && pool::is_confusable(ch as u32)))
+                .collect::<Vec<char>>(),
+        )
+    }
+
+    // whether apply leaves `ch` as it is
+    fn keeps(&self, allowed: &[char], ch: char) -> bool {
+        let types = self.char_types & 127;
+        if types == 127 || types == 0 {
+            return true;
+        }
+
+        let code = ch as u32;
+        if Self::is_emoji(code) {
+            types & Self::EMOJIS != 0
+        } else if code > 127 {
+            types & Self::EXTENDED_UNICODE != 0
+        } else {
+            allowed.contains(&ch)
+        }
+    }
+
+    // disallowed characters map onto the allowed ASCII by code point, or are dropped
+    // when only emoji and extended unicode are allowed. then the cut at max_length
+    pub fn apply(&self, output: &str) -> Wiped<String> {
+        let allowed = self.allowed();
+
+        let mut result = Wiped(String::with_capacity(output.len()));
+        for ch in output.chars() {
+            if self.keeps(&allowed, ch) {
+                result.push(ch);
+            } else if !allowed.is_empty() {
+                result.push(allowed[ch as usize % allowed.len()]);
+            }
+        }
+
+        if self.max_length > 0 {
+            if let Some((cut, _)) = result.char_indices().nth(self.max_length as usize) {
+                drop(Wiped(result.split_off(cut)));
+            }
+        }
+        result
+    }
+}
+
+// a PIN digit from a keystroke's output: the first character whose place in the
+// character set is below the largest multiple of ten, taken mod 10. the places above
+// are passed over, or the low digits would come up more often than the rest
+pub fn pin_digit(character_set: &[u32], output: &str) -> Option<char> {
+    let unbiased = character_set.len() - character_set.len() % 10;
+
+    output.chars().find_map(|ch| {
+        let place = character_set.iter().position(|&code| code == ch as u32)?;
+        (place < unbiased).then(|| char::from(b'0' + (place % 10) as u8))
+    })
+}
+
+// the words of a passphrase, 11 bits each, out of keystroke outputs. every character
+// adds the bits of its place in the character set, as many as the largest power of
+// two within the set's size holds, and places above that are passed over as in
+// pin_digit. a chunk past the end of a shorter wordlist is passed over the same way.
+// leftover bits carry to the next keystroke
+pub struct PassphraseBits {
+    pending: u64,
+    pending_bits: u32,
+    place_bits: u32,
+}
+
+impl PassphraseBits {
+    const WORD_BITS: u32 = 11;
+
+    pub fn new(character_set: &[u32]) -> Self {
+        PassphraseBits {
+            pending: 0,
+            pending_bits: 0,
+            place_bits: character_set.len().max(1).ilog2().min(32),
+        }
+    }
+
+    pub fn push(&mut self, character_set: &[u32], ch: char) {
+        let Some(place) = character_set.iter().position(|&code| code == ch as u32) else {
+            return;
+        };
+        if self.place_bits == 0 || place >> self.place_bits != 0 {
+            return;
+        }
+        self.pending = (self.pending << self.place_bits) | place as u64;
+        self.pending_bits += self.place_bits;
+    }
+
+    pub fn next_word(&mut self) -> Option<&'static str> {
+        let words = wordlist();
+        while self.pending_bits >= Self::WORD_BITS {
+            self.pending_bits -= Self::WORD_BITS;
+            let index = (self.pending >> self.pending_bits) as usize & 0x7FF;
+            self.pending &= (1u64 << self.pending_bits) - 1;
+            if let Some(word) = words.get(index) {
+                return Some(word);
+            }
+        }
+        None
+    }
+}
+
+impl Drop for PassphraseBits {
+    fn drop(&mut self) {
+        self.pending = 0;
+        self.pending_bits = 0;
+    }
+}
+
+// the passphrase wordlist, 2048 words for a chunk each. a build with the
+// custom-wordlist feature reads the file VOID_VAULT_WORDLIST names instead: one word
+// per line, or diceware lines ending in the word, of which the first 2048 count
+#[cfg(not(feature = "custom-wordlist"))]
+const WORDLIST_TEXT: &str = include_str!("wordlist.txt");
+#[cfg(feature = "custom-wordlist")]
+const WORDLIST_TEXT: &str = include_str!(env!("VOID_VAULT_WORDLIST"));
+
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| {
+        WORDLIST_TEXT
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .take(1 << PassphraseBits::WORD_BITS)
+            .collect()
+    })
+}
+
+// one keystroke of the generator. the keycode is offset by the feedback so far, then
+// it and the feedback trail are replayed from the start position, see Transform
+pub fn generate_keystroke(
+    saved_password: &mut SavedPassword,
+    feedbacks: &mut Vec<u64>,
+    keycode: u32,
+) -> Wiped<String> {
+    let mut output = Wiped(String::new());
+    generate_keystroke_into(saved_password, feedbacks, keycode, &mut output);
+    output
+}
+
+// generate_keystroke writing over the caller's buffer, so a loop over a line keeps
+// one output instead of allocating a string and a step sequence per keystroke
+pub fn generate_keystroke_into(
+    saved_password: &mut SavedPassword,
+    feedbacks: &mut Vec<u64>,
+    keycode: u32,
+    output: &mut String,
+) {
+    let transform = saved_password.structure_system.transform;
+    saved_password.structure_system.reset_position();
+    output.wipe();
+
+    for input_code in transform.navigation_sequence(keycode, feedbacks) {
+        saved_password.structure_system.transform_char_with(
+            input_code,
+            saved_password.extra_chars_count,
+            |code| output.extend(char::from_u32(code)),
+        );
+    }
+
+    let feedback = transform.feedback(feedbacks, output.chars().map(|ch| ch as u32));
+    feedbacks.push(feedback);
+}
+
+// generate_keystroke over the raw walk: the same feedback chain, fed by the u32s
+// themselves instead of character codes. overwrites `output`
+fn generate_raw_keystroke(
+    saved_password: &mut SavedPassword,
+    feedbacks: &mut Vec<u64>,
+    keycode: u32,
+    output: &mut Vec<u32>,
+) {
+    let transform = saved_password.structure_system.transform;
+    saved_password.structure_system.reset_position();
+    output.wipe();
+
+    for input_code in transform.navigation_sequence(keycode, feedbacks) {
+        saved_password.structure_system.transform_char_raw_into(
+            input_code,
+            saved_password.extra_chars_count,
+            output,
+        );
+    }
+
+    let feedback = transform.feedback(feedbacks, output.iter().copied());
+    feedbacks.push(feedback);
+}
+
+// --format and GENERATE's "format": byte_count bytes of the raw walk from wherever
+// the structure stands. each u32 goes out little-endian, the last keystroke's bytes
+// make the output, and keystrokes of keycode 0 follow until there are enough, the
+// way hash_domain pads a short domain
+pub fn generate_raw_output(
+    saved_password: &mut SavedPassword,
+    keycodes: &[u32],
+    byte_count: usize,
+) -> Wiped<Vec<u8>> {
+    let mut feedbacks = Wiped(Vec::<u64>::with_capacity(256));
+    let mut bytes = Wiped(Vec::<u8>::with_capacity(byte_count + 4096));
+    let mut values = Wiped(Vec::<u32>::new());
+
+    for &keycode in keycodes {
+        generate_raw_keystroke(saved_password, &mut feedbacks, keycode, &mut values);
+        bytes.wipe();
+        for value in values.iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    while bytes.len() < byte_count {
+        generate_raw_keystroke(saved_password, &mut feedbacks, 0, &mut values);
+        for value in values.iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    bytes.truncate(byte_count);
+    bytes
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RawFormat {
+    Hex,
+    Base64,
+    Raw,
+}
+
+impl RawFormat {
+    pub const DEFAULT_BYTES: usize = 32;
+    pub const MAX_BYTES: usize = 1024;
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "hex" => Some(RawFormat::Hex),
+            "base64" => Some(RawFormat::Base64),
+            "raw" => Some(RawFormat::Raw),
+            _ => None,
+        }
+    }
+
+    // lowercase hex, or standard base64 with padding. raw bytes have no text form
+    pub fn encode(self, bytes: &[u8]) -> Wiped<String> {
+        const BASE64: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut text = Wiped(String::with_capacity(bytes.len() * 2 + 4));
+        match self {
+            RawFormat::Hex => {
+                for byte in bytes {
+                    text.push(char::from_digit((byte >> 4) as u32, 16).unwrap_or('0'));
+                    text.push(char::from_digit((byte & 0xF) as u32, 16).unwrap_or('0'));
+                }
+            }
+            RawFormat::Base64 => {
+                for chunk in bytes.chunks(3) {
+                    let group = (chunk[0] as u32) << 16
+                        | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+                        | *chunk.get(2).unwrap_or(&0) as u32;
+                    for i in 0..4 {
+                        if i <= chunk.len() {
+                            text.push(BASE64[(group >> (18 - 6 * i)) as usize & 63] as char);
+                        } else {
+                            text.push('=');
+                        }
+                    }
+                }
+            }
+            RawFormat::Raw => {}
+        }
+        text
+    }
+}
+
+// the domain a session generates for. the binary keeps one in a static, an embedder
+// one per handle, see ffi
+pub struct SessionState {
+    pub active_domain_hash: Option<[u8; 64]>,
+    pub saved_counter: u16,
+    pub active_counter: u16,
+    pub is_preview_mode: bool,
+    pub initialized: bool,
+    // what the active domain generates, or --words asked for
+    pub fixed_output: FixedOutput,
+    // the active domain's rules, which its passwords are shaped to, see
+    // shape_password
+    pub rules: OutputRules,
+}
+
+impl SessionState {
+    pub const fn empty() -> Self {
+        SessionState {
+            active_domain_hash: None,
+            saved_counter: 0,
+            active_counter: 0,
+            is_preview_mode: false,
+            initialized: false,
+            fixed_output: FixedOutput::Password,
+            rules: OutputRules::NONE,
+        }
+    }
+
+    // whether `domain_hash` is the active domain's, compared in constant time
+    pub fn is_active_domain(&self, domain_hash: &[u8; 64]) -> bool {
+        self.active_domain_hash
+            .as_ref()
+            .is_some_and(|active| constant_time_eq(active, domain_hash))
+    }
+
+    // overwrites the domain hash in place before dropping it, then forgets the rest
+    pub fn scrub(&mut self) {
+        if let Some(hash) = self.active_domain_hash.as_mut() {
+            zero_memory(hash);
+        }
+        *self = SessionState::empty();
+    }
+}
+
+// back to the session start: the ghost navigated domain position in the browser, the
+// origin otherwise
+pub fn restart_session(session: &SessionState, structure: &mut StructureSystem) {
+    structure.full_reset();
+
+    if session.initialized {
+        if let Some(ref domain_hash) = session.active_domain_hash {
+            ghost_navigate(structure, domain_hash, session.active_counter);
+        }
+    }
+}
+
+// rebuilds the state after `keycodes` from the session start (the ghost navigated
+// domain position in the browser, the origin otherwise) and returns the last output.
+// on a PIN domain it returns the PIN so far instead: a digit per keystroke, and the
+// keystrokes after the last digit change nothing. a passphrase works the same way,
+// see PassphraseBits
+pub fn replay_keystrokes(
+    session: &SessionState,
+    saved_password: &mut SavedPassword,
+    feedbacks: &mut Vec<u64>,
+    keycodes: &[u32],
+) -> Wiped<String> {
+    restart_session(session, &mut saved_password.structure_system);
+    feedbacks.wipe();
+
+    match session.fixed_output {
+        FixedOutput::Pin(digits) => {
+            let mut pin = Wiped(String::new());
+            let mut output = Wiped(String::new());
+            for &keycode in keycodes {
+                if pin.len() == digits as usize {
+                    break;
+                }
+                generate_keystroke_into(saved_password, feedbacks, keycode, &mut output);
+                pin.extend(pin_digit(
+                    &saved_password.structure_system.character_set,
+                    &output,
+                ));
+            }
+            return pin;
+        }
+        FixedOutput::Words(words) => {
+            let mut passphrase = Wiped(String::new());
+            let mut bits = PassphraseBits::new(&saved_password.structure_system.character_set);
+            let mut count = 0;
+            let mut output = Wiped(String::new());
+            for &keycode in keycodes {
+                if count == words {
+                    break;
+                }
+                generate_keystroke_into(saved_password, feedbacks, keycode, &mut output);
+                for ch in output.chars() {
+                    bits.push(&saved_password.structure_system.character_set, ch);
+                    while count < words {
+                        let Some(word) = bits.next_word() else {
+                            break;
+                        };
+                        if count > 0 {
+                            passphrase.push('-');
+                        }
+                        passphrase.push_str(word);
+                        count += 1;
+                    }
+                }
+            }
+            return passphrase;
+        }
+        FixedOutput::Password => {}
+    }
+
+    let mut output_chars = Wiped(String::new());
+    for &keycode in keycodes {
+        generate_keystroke_into(saved_password, feedbacks, keycode, &mut output_chars);
+    }
+    shape_password(
+        &saved_password.structure_system.character_set,
+        &session.rules,
+        &mut output_chars,
+    );
+    output_chars
+}
+
+// a password keystroke's output as the domain's rules have it: confusable characters
+// moved on, then the characters at its ends held to its positions. after the
+// feedback was taken, so the walk and what later keystrokes generate stay the same
+// with the rules on or off
+fn shape_password(character_set: &[u32], rules: &OutputRules, output: &mut String) {
+    if rules.no_confusables {
+        avoid_confusables(character_set, output);
+    }
+    if !rules.positions.is_none() {
+        hold_positions(character_set, rules, output);
+    }
+}
+
+// a password keystroke's output with each confusable character moved on to the next
+// one in the set that isn't
+fn avoid_confusables(character_set: &[u32], output: &mut String) {
+    if !output.chars().any(|ch| pool::is_confusable(ch as u32)) {
+        return;
+    }
+    let remapped = Wiped(
+        output
+            .chars()
+            .filter_map(|ch| char::from_u32(pool::avoid_confusable(character_set, ch as u32)))
+            .collect::<String>(),
+    );
+    output.wipe();
+    output.push_str(&remapped);
+}
+
+// the first and last character moved on through the set until they fit the
+// positions. the last is the one max_length cuts at, and they only move to
+// characters OutputRules::apply keeps as they are, so the cut and the character
+// types leave both ends as they were held
+fn hold_positions(character_set: &[u32], rules: &OutputRules, output: &mut String) {
+    let positions = rules.positions;
+    let allowed = rules.allowed();
+    let lands = |ch: char| {
+        rules.keeps(&allowed, ch) && !(rules.no_confusables && pool::is_confusable(ch as u32))
+    };
+    let hold = |ch: char, fits: &dyn Fn(char) -> bool| {
+        if fits(ch) && lands(ch) {
+            return ch;
+        }
+        let code = pool::spread_to(character_set, ch as u32, |code| {
+            char::from_u32(code).is_some_and(|c| fits(c) && lands(c))
+        });
+        char::from_u32(code).unwrap_or(ch)
+    };
+
+    let mut chars = Wiped(output.chars().collect::<Vec<char>>());
+    let Some(mut last) = chars.len().checked_sub(1) else {
+        return;
+    };
+    if rules.max_length > 0 {
+        last = last.min(rules.max_length as usize - 1);
+    }
+
+    if positions.holds_first() {
+        chars[0] = hold(chars[0], &|ch| positions.fits_first(ch));
+    }
+    if positions.holds_last() {
+        chars[last] = if last == 0 {
+            hold(chars[0], &|ch| {
+                positions.fits_first(ch) && positions.fits_last(ch)
+            })
+        } else {
+            hold(chars[last], &|ch| positions.fits_last(ch))
+        };
+    }
+
+    output.wipe();
+    output.extend(chars.iter());
+}
+
+/// Where the front ends that type through a [`GenerationSession`] differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationOptions {
+    /// Folds a combining mark into what was typed before it as the configuration's
+    /// [`Normalization`] does, regenerating as if the precomposed character had
+    /// been typed. Off where the input was normalized as a whole beforehand.
+    pub compose_marks: bool,
+}
+
+impl GenerationOptions {
+    /// What the browser and embedders type with: keystrokes one at a time, from
+    /// the ghost navigated domain start.
+    pub const KEYSTROKES: Self = GenerationOptions {
+        compose_marks: true,
+    };
+}
+
+/// One phrase being typed: the keycodes so far and the feedback chain between
+/// them, and the per-keystroke pipeline every mode generates through. The
+/// [`SessionState`] says where generation starts and what it produces, the
+/// structure it moves is the configuration's own.
+pub struct GenerationSession {
+    pub options: GenerationOptions,
+    feedbacks: Wiped<Vec<u64>>,
+    typed_keycodes: Wiped<Vec<u32>>,
+}
+
+impl GenerationSession {
+    pub fn new(options: GenerationOptions) -> Self {
+        let generation = GenerationSession {
+            options,
+            feedbacks: Wiped(Vec::with_capacity(256)),
+            typed_keycodes: Wiped(Vec::with_capacity(256)),
+        };
+        harden::lock_buffer(&generation.feedbacks);
+        harden::lock_buffer(&generation.typed_keycodes);
+        generation
+    }
+
+    /// Nothing typed yet, so the next keystroke is the one a use counts on.
+    pub fn is_fresh(&self) -> bool {
+        self.feedbacks.is_empty()
+    }
+
+    /// The keycodes typed so far, composition applied.
+    pub fn typed(&self) -> &[u32] {
+        &self.typed_keycodes
+    }
+
+    /// Forgets what was typed. The structure is the caller's to restart.
+    pub fn clear(&mut self) {
+        self.feedbacks.wipe();
+        self.typed_keycodes.wipe();
+    }
+
+    /// One more keystroke, returning the output as it now stands: what the
+    /// keystroke generated, or on a PIN or passphrase domain the PIN or passphrase
+    /// so far, replayed from the session start.
+    pub fn step(
+        &mut self,
+        session: &SessionState,
+        config: &mut SavedPassword,
+        keycode: u32,
+    ) -> Wiped<String> {
+        if self.options.compose_marks {
+            if config.normalization.push(&mut self.typed_keycodes, keycode) {
+                return self.replay(session, config);
+            }
+        } else {
+            self.typed_keycodes.push(keycode);
+        }
+        if session.fixed_output != FixedOutput::Password {
+            return self.replay(session, config);
+        }
+        let mut output = generate_keystroke(config, &mut self.feedbacks, keycode);
+        shape_password(
+            &config.structure_system.character_set,
+            &session.rules,
+            &mut output,
+        );
+        output
+    }
+
+    /// Takes back the last keystroke. The feedback chain makes output order
+    /// dependent, so the rest are replayed from the session start. A backspace in
+    /// any mode is this: "abcd", backspace, "e" ends where "abce" does.
+    pub fn undo(&mut self, session: &SessionState, config: &mut SavedPassword) -> Wiped<String> {
+        self.typed_keycodes.pop();
+        self.replay(session, config)
+    }
+
+    /// Rebuilds the state after what was typed, see [`replay_keystrokes`].
+    pub fn replay(&mut self, session: &SessionState, config: &mut SavedPassword) -> Wiped<String> {
+        replay_keystrokes(session, config, &mut self.feedbacks, &self.typed_keycodes)
+    }
+
+    /// Replaces what was typed with `keycodes`, taken as already composed, and
+    /// replays them.
+    pub fn retype(
+        &mut self,
+        session: &SessionState,
+        config: &mut SavedPassword,
+        keycodes: &[u32],
+    ) -> Wiped<String> {
+        self.typed_keycodes.wipe();
+        self.typed_keycodes.extend_from_slice(keycodes);
+        self.replay(session, config)
+    }
+}
+
+/// One of the behavioral test sequences, typed into a throwaway structure, and the
+/// hash its outputs had where the sequences were pinned.
+pub struct SelfTest {
+    pub name: &'static str,
+    pub sequence: &'static str,
+    pub expected: u64,
+}
+
+/// The behavioral test sequences, in ascending order. A refactor that changes any
+/// of their hashes changes the passwords generated, see [`SelfTest::compute`].
+pub const SELF_TESTS: [SelfTest; 7] = [
+    SelfTest {
+        name: "zeroing transient memory checking",
+        sequence: "29213914",
+        expected: 0x61b5a91c4af588e7,
+    },
+    SelfTest {
+        name: "transient dynamic memory checking",
+        sequence: "999517725",
+        expected: 0xd11ddfb3d4740414,
+    },
+    SelfTest {
+        name: "incremental link path testing",
+        sequence: "0843213126",
+        expected: 0x4cbbf34976c4e198,
+    },
+    SelfTest {
+        name: "recursive end-to-end complexity checking",
+        sequence: "9332187235",
+        expected: 0x9428fdb094a279f1,
+    },
+    SelfTest {
+        name: "unstable folding of the geometry testing",
+        sequence: "5001019899912",
+        expected: 0x97fb18a31a5a06e4,
+    },
+    SelfTest {
+        name: "additive traversal and consistency testing",
+        sequence: "64221220322204",
+        expected: 0xc24cffd26bc7a88f,
+    },
+    SelfTest {
+        name: "multiple new geometry mutation pattern testing",
+        sequence: "110883422694685420",
+        expected: 0x8a857188771769ea,
+    },
+];
+
+impl SelfTest {
+    /// The sequence's hash in this build. A structure from a fixed seed and
+    /// character set types it at a domain through [`GenerationSession::step`],
+    /// undoes the last keystroke, then types it all again from a restart. Every
+    /// output along the way goes into the hash.
+    pub fn compute(&self) -> u64 {
+        let mut structure_system = StructureSystem::new(0x5E1F_7E57, 7, 17);
+        structure_system.motion = Motion::Fixed;
+        structure_system.transform = Transform::V4;
+        structure_system.set_character_set((33..127).collect());
+        let mut config = SavedPassword {
+            name: String::new(),
+            description: String::new(),
+            structure_system,
+            created_date: 0,
+            extra_chars_count: 3,
+            use_count: 0,
+            // the sequences' hashes were pinned with it
+            normalization: Normalization::V1,
+            fingerprint: None,
+            setup_mode: None,
+            rekeyed_from: None,
+        };
+
+        let mut session = SessionState::empty();
+        session.active_domain_hash = Some(config.structure_system.hash_domain("self-test.example"));
+        session.initialized = true;
+
+        // FNV-1a over the outputs' UTF-8
+        let mut hash = 0xcbf29ce484222325u64;
+        let mut absorb = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        };
+
+        let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+        for round in 0..2 {
+            restart_session(&session, &mut config.structure_system);
+            generation.clear();
+            for ch in self.sequence.chars() {
+                absorb(generation.step(&session, &mut config, ch as u32).as_bytes());
+            }
+            if round == 0 {
+                absorb(generation.undo(&session, &mut config).as_bytes());
+            }
+        }
+        hash
+    }
+}
+
+// (base, combining mark, composed) for Latin-1 Supplement and Latin Extended-A,
+// sorted by base then mark so lookups can binary search. this is the fixed v1 set
+// of Normalization::V1 and never changes: it composes one mark onto the character
+// before it and nothing more, stacked marks, Latin Extended-B and Additional are
+// left as typed. nfc::COMPOSITIONS is the complete table V2 uses
+const NFC_COMPOSITIONS: [(u32, u32, u32); 161] = [
+    (0x0041, 0x0300, 0x00C0), // À
+    (0x0041, 0x0301, 0x00C1), // Á
+    (0x0041, 0x0302, 0x00C2), // Â
+    (0x0041, 0x0303, 0x00C3), // Ã
+    (0x0041, 0x0304, 0x0100), // Ā
+    (0x0041, 0x0306, 0x0102), // Ă
+    (0x0041, 0x0308, 0x00C4), // Ä
+    (0x0041, 0x030A, 0x00C5), // Å
+    (0x0041, 0x0328, 0x0104), // Ą
+    (0x0043, 0x0301, 0x0106), // Ć
+    (0x0043, 0x0302, 0x0108), // Ĉ
+    (0x0043, 0x0307, 0x010A), // Ċ
+    (0x0043, 0x030C, 0x010C), // Č
+    (0x0043, 0x0327, 0x00C7), // Ç
+    (0x0044, 0x030C, 0x010E), // Ď
+    (0x0045, 0x0300, 0x00C8), // È
+    (0x0045, 0x0301, 0x00C9), // É
+    (0x0045, 0x0302, 0x00CA), // Ê
+    (0x0045, 0x0304, 0x0112), // Ē
+    (0x0045, 0x0306, 0x0114), // Ĕ
+    (0x0045, 0x0307, 0x0116), // Ė
+    (0x0045, 0x0308, 0x00CB), // Ë
+    (0x0045, 0x030C, 0x011A), // Ě
+    (0x0045, 0x0328, 0x0118), // Ę
+    (0x0047, 0x0302, 0x011C), // Ĝ
+    (0x0047, 0x0306, 0x011E), // Ğ
+    (0x0047, 0x0307, 0x0120), // Ġ
+    (0x0047, 0x0327, 0x0122), // Ģ
+    (0x0048, 0x0302, 0x0124), // Ĥ
+    (0x0049, 0x0300, 0x00CC), // Ì
+    (0x0049, 0x0301, 0x00CD), // Í
+    (0x0049, 0x0302, 0x00CE), // Î
+    (0x0049, 0x0303, 0x0128), // Ĩ
+    (0x0049, 0x0304, 0x012A), // Ī
+    (0x0049, 0x0306, 0x012C), // Ĭ
+    (0x0049, 0x0307, 0x0130), // İ
+    (0x0049, 0x0308, 0x00CF), // Ï
+    (0x0049, 0x0328, 0x012E), // Į
+    (0x004A, 0x0302, 0x0134), // Ĵ
+    (0x004B, 0x0327, 0x0136), // Ķ
+    (0x004C, 0x0301, 0x0139), // Ĺ
+    (0x004C, 0x030C, 0x013D), // Ľ
+    (0x004C, 0x0327, 0x013B), // Ļ
+    (0x004E, 0x0301, 0x0143), // Ń
+    (0x004E, 0x0303, 0x00D1), // Ñ
+    (0x004E, 0x030C, 0x0147), // Ň
+    (0x004E, 0x0327, 0x0145), // Ņ
+    (0x004F, 0x0300, 0x00D2), // Ò
+    (0x004F, 0x0301, 0x00D3), // Ó
+    (0x004F, 0x0302, 0x00D4), // Ô
+    (0x004F, 0x0303, 0x00D5), // Õ
+    (0x004F, 0x0304, 0x014C), // Ō
+    (0x004F, 0x0306, 0x014E), // Ŏ
+    (0x004F, 0x0308, 0x00D6), // Ö
+    (0x004F, 0x030B, 0x0150), // Ő
+    (0x0052, 0x0301, 0x0154), // Ŕ
+    (0x0052, 0x030C, 0x0158), // Ř
+    (0x0052, 0x0327, 0x0156), // Ŗ
+    (0x0053, 0x0301, 0x015A), // Ś
+    (0x0053, 0x0302, 0x015C), // Ŝ
+    (0x0053, 0x030C, 0x0160), // Š
+    (0x0053, 0x0327, 0x015E), // Ş
+    (0x0054, 0x030C, 0x0164), // Ť
+    (0x0054, 0x0327, 0x0162), // Ţ
+    (0x0055, 0x0300, 0x00D9), // Ù
+    (0x0055, 0x0301, 0x00DA), // Ú
+    (0x0055, 0x0302, 0x00DB), // Û
+    (0x0055, 0x0303, 0x0168), // Ũ
+    (0x0055, 0x0304, 0x016A), // Ū
+    (0x0055, 0x0306, 0x016C), // Ŭ
+    (0x0055, 0x0308, 0x00DC), // Ü
+    (0x0055, 0x030A, 0x016E), // Ů
+    (0x0055, 0x030B, 0x0170), // Ű
+    (0x0055, 0x0328, 0x0172), // Ų
+    (0x0057, 0x0302, 0x0174), // Ŵ
+    (0x0059, 0x0301, 0x00DD), // Ý
+    (0x0059, 0x0302, 0x0176), // Ŷ
+    (0x0059, 0x0308, 0x0178), // Ÿ
+    (0x005A, 0x0301, 0x0179), // Ź
+    (0x005A, 0x0307, 0x017B), // Ż
+    (0x005A, 0x030C, 0x017D), // Ž
+    (0x0061, 0x0300, 0x00E0), // à
+    (0x0061, 0x0301, 0x00E1), // á
+    (0x0061, 0x0302, 0x00E2), // â
+    (0x0061, 0x0303, 0x00E3), // ã
+    (0x0061, 0x0304, 0x0101), // ā
+    (0x0061, 0x0306, 0x0103), // ă
+    (0x0061, 0x0308, 0x00E4), // ä
+    (0x0061, 0x030A, 0x00E5), // å
+    (0x0061, 0x0328, 0x0105), // ą
+    (0x0063, 0x0301, 0x0107), // ć
+    (0x0063, 0x0302, 0x0109), // ĉ
+    (0x0063, 0x0307, 0x010B), // ċ
+    (0x0063, 0x030C, 0x010D), // č
+    (0x0063, 0x0327, 0x00E7), // ç
+    (0x0064, 0x030C, 0x010F), // ď
+    (0x0065, 0x0300, 0x00E8), // è
+    (0x0065, 0x0301, 0x00E9), // é
+    (0x0065, 0x0302, 0x00EA), // ê
+    (0x0065, 0x0304, 0x0113), // ē
+    (0x0065, 0x0306, 0x0115), // ĕ
+    (0x0065, 0x0307, 0x0117), // ė
+    (0x0065, 0x0308, 0x00EB), // ë
+    (0x0065, 0x030C, 0x011B), // ě
+    (0x0065, 0x0328, 0x0119), // ę
+    (0x0067, 0x0302, 0x011D), // ĝ
+    (0x0067, 0x0306, 0x011F), // ğ
+    (0x0067, 0x0307, 0x0121), // ġ
+    (0x0067, 0x0327, 0x0123), // ģ
+    (0x0068, 0x0302, 0x0125), // ĥ
+    (0x0069, 0x0300, 0x00EC), // ì
+    (0x0069, 0x0301, 0x00ED), // í
+    (0x0069, 0x0302, 0x00EE), // î
+    (0x0069, 0x0303, 0x0129), // ĩ
+    (0x0069, 0x0304, 0x012B), // ī
+    (0x0069, 0x0306, 0x012D), // ĭ
+    (0x0069, 0x0308, 0x00EF), // ï
+    (0x0069, 0x0328, 0x012F), // į
+    (0x006A, 0x0302, 0x0135), // ĵ
+    (0x006B, 0x0327, 0x0137), // ķ
+    (0x006C, 0x0301, 0x013A), // ĺ
+    (0x006C, 0x030C, 0x013E), // ľ
+    (0x006C, 0x0327, 0x013C), // ļ
+    (0x006E, 0x0301, 0x0144), // ń
+    (0x006E, 0x0303, 0x00F1), // ñ
+    (0x006E, 0x030C, 0x0148), // ň
+    (0x006E, 0x0327, 0x0146), // ņ
+    (0x006F, 0x0300, 0x00F2), // ò
+    (0x006F, 0x0301, 0x00F3), // ó
+    (0x006F, 0x0302, 0x00F4), // ô
+    (0x006F, 0x0303, 0x00F5), // õ
+    (0x006F, 0x0304, 0x014D), // ō
+    (0x006F, 0x0306, 0x014F), // ŏ
+    (0x006F, 0x0308, 0x00F6), // ö
+    (0x006F, 0x030B, 0x0151), // ő
+    (0x0072, 0x0301, 0x0155), // ŕ
+    (0x0072, 0x030C, 0x0159), // ř
+    (0x0072, 0x0327, 0x0157), // ŗ
+    (0x0073, 0x0301, 0x015B), // ś
+    (0x0073, 0x0302, 0x015D), // ŝ
+    (0x0073, 0x030C, 0x0161), // š
+    (0x0073, 0x0327, 0x015F), // ş
+    (0x0074, 0x030C, 0x0165), // ť
+    (0x0074, 0x0327, 0x0163), // ţ
+    (0x0075, 0x0300, 0x00F9), // ù
+    (0x0075, 0x0301, 0x00FA), // ú
+    (0x0075, 0x0302, 0x00FB), // û
+    (0x0075, 0x0303, 0x0169), // ũ
+    (0x0075, 0x0304, 0x016B), // ū
+    (0x0075, 0x0306, 0x016D), // ŭ
+    (0x0075, 0x0308, 0x00FC), // ü
+    (0x0075, 0x030A, 0x016F), // ů
+    (0x0075, 0x030B, 0x0171), // ű
+    (0x0075, 0x0328, 0x0173), // ų
+    (0x0077, 0x0302, 0x0175), // ŵ
+    (0x0079, 0x0301, 0x00FD), // ý
+    (0x0079, 0x0302, 0x0177), // ŷ
+    (0x0079, 0x0308, 0x00FF), // ÿ
+    (0x007A, 0x0301, 0x017A), // ź
+    (0x007A, 0x0307, 0x017C), // ż
+    (0x007A, 0x030C, 0x017E), // ž
+];
+
+pub fn nfc_compose(base: u32, mark: u32) -> Option<u32> {
+    NFC_COMPOSITIONS
+        .binary_search_by(|&(b, m, _)| (b, m).cmp(&(base, mark)))
+        .ok()
+        .map(|idx| NFC_COMPOSITIONS[idx].2)
+}
+
+// Normalization::V1 for the input we generate from: a base followed by a combining
+// mark becomes the precomposed character, so "e" + U+0301 and "é" produce the same
+// password. setup phrases keep to it whatever the configuration normalizes with,
+// as the structures rebuilt from them have to come out the same
+pub fn nfc_normalize(keycodes: &mut Vec<u32>) {
+    let mut len = 0;
+    for idx in 0..keycodes.len() {
+        let keycode = keycodes[idx];
+        if len > 0 {
+            if let Some(composed) = nfc_compose(keycodes[len - 1], keycode) {
+                keycodes[len - 1] = composed;
+                continue;
+            }
+        }
+        keycodes[len] = keycode;
+        len += 1;
+    }
+    zero_memory(&mut keycodes[len..]);
+    keycodes.truncate(len);
+}
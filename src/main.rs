@@ -18,3440 +18,10461 @@
 // wish to use this software without the restrictions of the AGPL-3.0.
 // Contact: Maui_The_Magnificent@proton.me
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::hash::{Hash, Hasher};
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Clone)]
-enum ProcessMessage {
-    BinaryUpdated(PathBuf),
-    ShutdownChild,
-    ChildReady,
-    BinaryUpdateComplete,
-}
+use void_vault::*;
 
-struct BinaryStorageManager {
-    executable_path: PathBuf,
-    in_memory_cache: HashMap<String, Vec<u8>>,
-    metadata_cache: HashMap<String, String>,
-    binary_modified: bool,
-    parent_mode: bool,
-    message_tx: Option<Sender<ProcessMessage>>,
-}
+#[allow(static_mut_refs)]
+static mut SESSION: SessionState = SessionState::empty();
 
-impl Clone for BinaryStorageManager {
-    fn clone(&self) -> Self {
-        BinaryStorageManager {
-            executable_path: self.executable_path.clone(),
-            in_memory_cache: self.in_memory_cache.clone(),
-            metadata_cache: self.metadata_cache.clone(),
-            binary_modified: self.binary_modified,
-            parent_mode: self.parent_mode,
-            message_tx: self.message_tx.clone(),
-        }
-    }
+// the binary's one session to read from. writes go through addr_of_mut!(SESSION)
+fn session() -> &'static SessionState {
+    unsafe { &*std::ptr::addr_of!(SESSION) }
 }
 
-impl BinaryStorageManager {
-    // markers are unless reserved, in the zone at the end.
-    // they are also based only on executable properties that won't change
-    fn generate_markers(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
-        // and now we read the binary header to generate portable markers
-        let mut header_bytes = Vec::new();
-        if let Ok(mut file) = File::open(&self.executable_path) {
-            let mut buffer = [0u8; 1024];
-            if let Ok(n) = file.read(&mut buffer) {
-                header_bytes.extend_from_slice(&buffer[..n]);
-            }
-        }
-
-        // use only the binary header hash for the marker seed
-        let header_seed = {
-            let mut header_hasher = std::collections::hash_map::DefaultHasher::new();
-            header_bytes.hash(&mut header_hasher);
-            header_hasher.finish()
-        };
-
-        let mut rng_state = header_seed;
+// set on the child of a parent under duress
+const CHILD_DURESS_VAR: &str = "VOID_VAULT_CHILD_DURESS";
 
-        let generate_marker = |prefix: &[u8], length: usize, rng: &mut u64| -> Vec<u8> {
-            let mut marker = Vec::with_capacity(prefix.len() + length + 1);
-            marker.extend_from_slice(prefix);
+// extra_args are handed on to the child, which is where setup and the interactive
+// session run
+fn run_parent_process(auto_exit: bool, extra_args: &[String]) -> io::Result<()> {
+    diag(Diag::Note, format_args!("Starting Void Vault..."));
+    diag(Diag::Note, format_args!("Maximized and unending void"));
+    diag(Diag::Note, format_args!("Reapplied inside the geometry"));
+    diag(Diag::Note, format_args!("zero password setup"));
 
-            for _ in 0..length {
-                *rng = rng
-                    .wrapping_mul(6364136223846793005)
-                    .wrapping_add(1442695040888963407);
-                marker.push((*rng % 255) as u8);
-            }
-
-            marker.push(0);
-            marker
-        };
+    let listener = ProcessListener::bind()?;
 
-        let section_marker = generate_marker(b"\0SM", 24, &mut rng_state);
-        let start_marker = generate_marker(b"\0ST", 16, &mut rng_state);
-        let end_marker = generate_marker(b"\0EN", 16, &mut rng_state);
-        let name_marker = generate_marker(b"\0NM", 8, &mut rng_state);
-        let desc_marker = generate_marker(b"\0DS", 8, &mut rng_state);
-
-        (
-            section_marker,
-            start_marker,
-            end_marker,
-            name_marker,
-            desc_marker,
-        )
+    let executable_path = std::env::current_exe()?;
+    let mut child_args = vec![
+        "--child-process".to_string(),
+        "--ipc".to_string(),
+        listener.address()?,
+    ];
+    if auto_exit {
+        child_args.push("--auto-exit".to_string());
     }
-
-    fn new(parent_mode: bool, tx: Option<Sender<ProcessMessage>>) -> io::Result<Self> {
-        let executable_path = std::env::current_exe()?;
-
-        let mut manager = BinaryStorageManager {
-            executable_path,
-            in_memory_cache: HashMap::new(),
-            metadata_cache: HashMap::new(),
-            binary_modified: false,
-            parent_mode,
-            message_tx: tx,
-        };
-
-        if manager.ensure_end_marker()? {
-            manager.binary_modified = true;
-
-            if !parent_mode {
-                manager.signal_binary_update()?;
-            }
-        }
-
-        manager.load_all_passwords()?;
-
-        Ok(manager)
+    if ALLOW_WEAK_PHRASE.load(Ordering::SeqCst) {
+        child_args.push("--i-know-what-im-doing".to_string());
     }
-
-    fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-        if needle.len() > haystack.len() {
-            return None;
-        }
-
-        for i in 0..=haystack.len() - needle.len() {
-            if haystack[i..i + needle.len()] == needle[..] {
-                return Some(i);
-            }
-        }
-        None
+    if QUIET.load(Ordering::SeqCst) {
+        child_args.push("--quiet".to_string());
+    }
+    if VERBOSE.load(Ordering::SeqCst) {
+        child_args.push("--verbose".to_string());
+    }
+    if NO_PERSIST.load(Ordering::SeqCst) {
+        child_args.push("--no-persist".to_string());
+    }
+    if NO_HARDEN.load(Ordering::SeqCst) {
+        child_args.push("--no-harden".to_string());
+    }
+    if let Some(profile) = PROFILE.get() {
+        child_args.push("--profile".to_string());
+        child_args.push(profile.name.clone());
+    }
+    let timeout_secs = INPUT_TIMEOUT_SECS.load(Ordering::SeqCst);
+    if timeout_secs != DEFAULT_INPUT_TIMEOUT_SECS {
+        child_args.push("--timeout".to_string());
+        child_args.push(timeout_secs.to_string());
     }
-    // really important, as without it, the binary would break
-    fn ensure_end_marker(&self) -> io::Result<bool> {
-        let (section_marker, _, _, _, _) = self.generate_markers();
+    child_args.extend(extra_args.iter().cloned());
 
-        let file = match File::open(&self.executable_path) {
-            Ok(f) => f,
-            Err(e) => {
-                println!("WARNING: Could not open executable: {}", e);
-                return Err(e);
-            }
-        };
+    let mut command = Command::new(&executable_path);
+    command
+        .args(&child_args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    listener.configure(&mut command);
+    // not an argument, which anyone on the machine could read
+    if DURESS.load(Ordering::SeqCst) {
+        command.env(CHILD_DURESS_VAR, "1");
+    }
 
-        let file_size = file.metadata()?.len();
+    let mut child = command.spawn()?;
 
-        if file_size < section_marker.len() as u64 {
-            return self.append_end_marker();
+    let mut channel = match listener.accept(&mut child) {
+        Ok(channel) => channel,
+        Err(e) => {
+            diag(
+                Diag::Warning,
+                format_args!("Child never reached out: {}", e),
+            );
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
         }
+    };
 
-        let mut file = File::open(&self.executable_path)?;
-        let mut end_bytes = vec![0u8; section_marker.len()];
-        file.seek(SeekFrom::End(-(section_marker.len() as i64)))?;
-        file.read_exact(&mut end_bytes)?;
-
-        if end_bytes == section_marker {
-            return Ok(false);
-        } else {
-            return self.append_end_marker();
+    match channel.recv() {
+        Ok(ProcessMessage::ChildReady) => {
+            diag(Diag::Note, format_args!("Child birthed and ready"));
+        }
+        _ => {
+            diag(
+                Diag::Warning,
+                format_args!("Failed to receive ready signal from child, it clawled back in"),
+            );
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::other(
+                "The child brings dishonor to the family by failing initialization",
+            ));
         }
     }
 
-    fn append_end_marker(&self) -> io::Result<bool> {
-        let (section_marker, _, _, _, _) = self.generate_markers();
-        let temp_path = self.executable_path.with_extension("new");
-
-        let mut original = File::open(&self.executable_path)?;
-        let mut buffer = Vec::new();
-        original.read_to_end(&mut buffer)?;
-
-        let mut new_file = File::create(&temp_path)?;
-        new_file.write_all(&buffer)?;
-        new_file.write_all(&section_marker)?;
-
-        // Also append domain table marker + empty table during initial setup
-        new_file.write_all(DOMAIN_TABLE_START_MARKER)?;
-        let empty_table = vec![0u8; std::mem::size_of::<DomainTable>()];
-        new_file.write_all(&empty_table)?;
+    // the child closing its end of the socket (exit or crash) ends the loop
+    loop {
+        match channel.recv() {
+            Ok(ProcessMessage::BinaryUpdated(new_binary_path)) => {
+                diag(
+                    Diag::Note,
+                    format_args!(
+                        "Received newaged binary update notification from child: {}",
+                        new_binary_path.display()
+                    ),
+                );
 
-        drop(original);
-        drop(new_file);
+                thread::sleep(std::time::Duration::from_millis(100));
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let metadata = fs::metadata(&self.executable_path)?;
-            let mode = metadata.permissions().mode();
-            let mut perms = fs::metadata(&temp_path)?.permissions();
-            perms.set_mode(mode);
-            fs::set_permissions(&temp_path, perms)?;
+                if channel.send(&ProcessMessage::BinaryUpdateComplete).is_err() {
+                    diag(
+                        Diag::Warning,
+                        format_args!(
+                            "Failed to use dope slang and signal binary update completion to child"
+                        ),
+                    );
+                    break;
+                }
+            }
+            Ok(ProcessMessage::ShutdownChild) => {
+                diag(
+                    Diag::Note,
+                    format_args!("Child sent a requested to be unborn"),
+                );
+                break;
+            }
+            Ok(_) => {}
+            Err(_) => {
+                break;
+            }
         }
-
-        let backup_path = self.executable_path.with_extension("bak");
-        fs::rename(&self.executable_path, &backup_path)?;
-        fs::rename(&temp_path, &self.executable_path)?;
-
-        return Ok(true);
     }
-    // instead of allowing for multiple passwords and such,
-    // this slimed down version stores one multidimentional structure and its
-    // zones.
-    fn load_all_passwords(&mut self) -> io::Result<()> {
-        self.in_memory_cache.clear();
-        self.metadata_cache.clear();
-
-        let (section_marker, start_marker, end_marker, name_marker, desc_marker) =
-            self.generate_markers();
 
-        let file = match File::open(&self.executable_path) {
-            Ok(f) => f,
-            Err(e) => {
-                println!("WARNING: Could not open executable: {}", e);
-                return Ok(());
+    match child.wait() {
+        Ok(status) => {
+            diag(
+                Diag::Note,
+                format_args!("Child exited with status: {}", status),
+            );
+            // the session ran in the child, so its status is ours
+            if let Some(code) = status.code().filter(|&code| code != 0) {
+                EXIT_CODE.store(code, Ordering::SeqCst);
             }
-        };
-
-        let file_size = file.metadata()?.len();
-
-        if file_size < section_marker.len() as u64 {
-            return Ok(());
         }
-
-        let mut file = File::open(&self.executable_path)?;
-        let mut end_bytes = vec![0u8; section_marker.len()];
-        file.seek(SeekFrom::End(-(section_marker.len() as i64)))?;
-
-        if let Err(e) = file.read_exact(&mut end_bytes) {
-            println!("WARNING: Failed to read end of binary: {}", e);
-            return Ok(());
+        Err(e) => {
+            diag(
+                Diag::Warning,
+                format_args!("Error waiting for child: {}", e),
+            );
+            return Err(e);
         }
+    }
 
-        if end_bytes != section_marker {
-            return Ok(());
-        }
-        // new buffer to load said geometric structure
-        let mut file = File::open(&self.executable_path)?;
-        let mut buffer = Vec::new();
+    Ok(())
+}
 
-        if let Err(e) = file.read_to_end(&mut buffer) {
-            println!("WARNING: Failed: {}", e);
-            return Ok(());
-        }
+// what to exit with when run succeeds anyway: the status of a child that failed,
+// passed on, or the findings of doctor. set once, read by main
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
 
-        let section_end_pos = buffer.len();
-        let section_start_pos = section_end_pos - section_marker.len();
+// what setup creates: the first configuration, or next to existing ones another one
+// (--add <name>) or a new geometry under an existing name (--replace)
+enum SetupTarget {
+    First,
+    Add(String),
+    Replace(usize),
+}
 
-        let search_begin = if section_start_pos > 10 * 1024 * 1024 {
-            section_start_pos - 10 * 1024 * 1024
-        } else {
-            0
-        };
+fn setup_target(password_manager: &PasswordManager, args: &[String]) -> io::Result<SetupTarget> {
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|pos| args.get(pos + 1))
+            .map(String::as_str)
+    };
 
-        let mut current_pos = search_begin;
+    if let Some(name) = value("--add") {
+        if password_manager
+            .saved_passwords
+            .iter()
+            .any(|p| p.name == name)
+        {
+            return Err(ErrorCode::AccountExists
+                .error(format!("A configuration named '{}' already exists", name)));
+        }
+        Ok(SetupTarget::Add(name.to_string()))
+    } else if args.iter().any(|arg| arg == "--replace") {
+        Ok(SetupTarget::Replace(select_account(
+            password_manager,
+            value("--account"),
+        )?))
+    } else if password_manager.saved_passwords.is_empty() {
+        Ok(SetupTarget::First)
+    } else {
+        Err(ErrorCode::AccountExists.error(
+            "This binary already has a configuration. Use setup --add <name> to create another one, or setup --replace to start it over",
+        ))
+    }
+}
 
-        let mut password_positions = Vec::new();
+fn create_password_setup(
+    name: &str,
+    description: &str,
+    geometry: &Geometry,
+    keycodes: &[u32],
+    reproducible: bool,
+) -> Result<SavedPassword, std::io::Error> {
+    use std::io::{self, Write};
+
+    println!("\n=== PASSWORD SETUP PHASE ===");
+    println!("This is a one-time setup to create your configuration.");
+    println!("Type your sequence naturally.");
+    println!("Press ESC when finished.");
+
+    let raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::Raw)?;
+
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let mut buffer = [0; 1];
+    let mut last_keypress_time = std::time::Instant::now();
+
+    // every key the structure will be stepped with, with its timing. the structure is
+    // only built once the phrase is done, its seed comes from it
+    let mut keystrokes: Wiped<Vec<(u32, u64, u64)>> = Wiped(Vec::new());
+    // where each character of the phrase starts in keystrokes, so a backspace takes
+    // back everything it was typed with, a composed mark's base too
+    let mut starts: Vec<usize> = Vec::new();
+    let mut current_input = Wiped(String::new());
+    let mut display_input = Wiped(String::new());
+    let mut display_count: usize = 0;
+    let mut decoder = Utf8Decoder::new();
+    // the gap before each kept keystroke, the first one excluded (that is just
+    // how long the prompt was read)
+    let mut gaps_ms: Vec<u64> = Vec::new();
+
+    let quality = |display_input: &str, gaps_ms: &[u64]| {
+        let phrase: Vec<u32> = display_input.chars().map(|ch| ch as u32).collect();
+        PhraseQuality::of(&phrase, gaps_ms)
+    };
 
-        while current_pos < section_start_pos {
-            match Self::find_pattern(&buffer[current_pos..section_start_pos], &start_marker) {
-                Some(offset) => {
-                    let start_pos = current_pos + offset;
-                    password_positions.push(start_pos);
-                    current_pos = start_pos + start_marker.len();
-                }
-                None => break,
-            }
-        }
+    println!("\nStart typing your sequence now:");
 
-        for &start_pos in &password_positions {
-            let current_pos = start_pos + start_marker.len();
+    loop {
+        match read_exact_interruptible(&mut stdin, &mut buffer) {
+            Ok(_) => {
+                // whole characters, not the bytes of their UTF-8 encoding
+                let mut keycode = match decoder.push(buffer[0]) {
+                    Some(ch) => ch as u32,
+                    None => continue,
+                };
 
-            let name_marker_pos =
-                match Self::find_pattern(&buffer[current_pos..section_start_pos], &name_marker) {
-                    Some(offset) => current_pos + offset + name_marker.len(),
-                    None => {
-                        println!(
-                            "WARNING: Start marker without NAME marker at position {}",
-                            start_pos
-                        );
-                        continue;
+                if keycode == 27 {
+                    let quality = quality(&display_input, &gaps_ms);
+                    if quality.meets_minimum() || ALLOW_WEAK_PHRASE.load(Ordering::SeqCst) {
+                        println!("\nSetup complete!");
+                        break;
                     }
-                };
 
-            let name_end_pos = match buffer[name_marker_pos..section_start_pos]
-                .iter()
-                .position(|&b| b == 0)
-            {
-                Some(offset) => name_marker_pos + offset,
-                None => {
                     println!(
-                        "WARNING: Name without end marker at position {}",
-                        name_marker_pos
+                        "\n\nThe phrase is too weak to build a vault from, it needs {}.",
+                        quality.shortfall()
+                    );
+                    println!("Keep typing to continue, ESC again once it is long enough.");
+                    print!(
+                        "\r{} characters typed ({}): {}",
+                        display_count,
+                        quality.indicator(),
+                        display_input.as_str()
                     );
+                    io::stdout().flush()?;
                     continue;
                 }
-            };
 
-            // your geometric name
-            let name_bytes = &buffer[name_marker_pos..name_end_pos];
-            let name = match std::str::from_utf8(name_bytes) {
-                Ok(s) => s.trim().to_string(),
-                Err(_) => {
-                    println!(
-                        "WARNING: Invalid UTF-8 in name at position {}",
-                        name_marker_pos
-                    );
-                    continue;
+                // raw mode delivers Ctrl+C as a byte instead of a signal
+                if keycode == 3 {
+                    request_shutdown(SIGINT);
+                    drop(raw_mode);
+                    println!("\nSetup aborted.");
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "Setup aborted"));
                 }
-            };
 
-            let data_start = name_end_pos + 1;
+                let now = std::time::Instant::now();
+                let duration = now.duration_since(last_keypress_time);
+                let timing_ms = duration.as_millis() as u64;
+                last_keypress_time = now;
 
-            let end_pos_marker =
-                match Self::find_pattern(&buffer[data_start..section_start_pos], &end_marker) {
-                    Some(offset) => data_start + offset,
-                    None => {
-                        println!("WARNING: No end marker found after name '{}'", name);
-                        continue;
-                    }
-                };
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_millis() as u64;
 
-            let data_size = end_pos_marker - data_start;
-            if data_size <= 0 {
-                println!("WARNING: Empty data section for '{}'", name);
-                continue;
-            }
+                if keycode == 8 || keycode == 127 {
+                    if !display_input.is_empty() {
+                        if let Some(start) = starts.pop() {
+                            keystrokes.truncate(start);
+                        }
+                        current_input.pop();
+                        display_input.pop();
+                        gaps_ms.pop();
+                        display_count = display_count.saturating_sub(1);
+                        print!(
+                            "\r{}                              \r{} characters typed ({}): {}",
+                            " ".repeat(50 + 2 * display_count),
+                            display_count,
+                            quality(&display_input, &gaps_ms).indicator(),
+                            display_input.as_str()
+                        );
+                        io::stdout().flush()?;
+                    }
+                } else {
+                    // the phrase keeps to the v1 set whatever the configuration generates
+                    // with: create-account rebuilds a structure from it
+                    let composed = display_input
+                        .chars()
+                        .last()
+                        .and_then(|previous| nfc_compose(previous as u32, keycode));
+                    if let Some(composed) = composed {
+                        keycode = composed;
+                    }
 
-            if data_size > 10 * 1024 * 1024 {
-                println!(
-                    "WARNING: Data section too large for '{}': {} bytes",
-                    name, data_size
-                );
-                continue;
-            }
+                    if !keycodes.contains(&keycode) {
+                        continue;
+                    }
 
-            let desc_marker_start = end_pos_marker + end_marker.len();
+                    if let Some(c) = char::from_u32(keycode) {
+                        if composed.is_some() {
+                            current_input.pop();
+                            display_input.pop();
+                        }
+                        current_input.push(c);
+                        display_input.push(c);
+                    }
 
-            let desc_marker_pos = match Self::find_pattern(
-                &buffer[desc_marker_start..section_start_pos],
-                &desc_marker,
-            ) {
-                Some(offset) => desc_marker_start + offset + desc_marker.len(),
-                None => {
-                    println!("WARNING: End marker without DESC marker for '{}'", name);
-                    continue;
-                }
-            };
+                    if composed.is_none() {
+                        if display_count > 0 {
+                            gaps_ms.push(timing_ms);
+                        }
+                        display_count += 1;
+                        starts.push(keystrokes.len());
+                    }
 
-            let desc_end_pos = match buffer[desc_marker_pos..section_start_pos]
-                .iter()
-                .position(|&b| b == 0)
-            {
-                Some(offset) => desc_marker_pos + offset,
-                None => {
-                    println!("WARNING: Description without end marker for '{}'", name);
-                    continue;
-                }
-            };
+                    keystrokes.push((keycode, timing_ms, timestamp));
 
-            let desc_bytes = &buffer[desc_marker_pos..desc_end_pos];
-            let description = match std::str::from_utf8(desc_bytes) {
-                Ok(s) => s.trim().to_string(),
-                Err(_) => {
-                    println!(
-                        "WARNING: Invalid UTF-8 in description at position {}",
-                        desc_marker_pos
+                    print!(
+                        "\r{} characters typed ({}): {}",
+                        display_count,
+                        quality(&display_input, &gaps_ms).indicator(),
+                        display_input.as_str()
                     );
-                    continue;
+                    io::stdout().flush()?;
                 }
-            };
-
-            let data = buffer[data_start..end_pos_marker].to_vec();
-            self.in_memory_cache.insert(name.clone(), data);
-            self.metadata_cache.insert(name.clone(), description);
-        }
-
-        Ok(())
-    }
-
-    fn store(&mut self, name: String, description: String, data: &[u8]) -> io::Result<()> {
-        self.in_memory_cache.insert(name.clone(), data.to_vec());
-        self.metadata_cache
-            .insert(name.clone(), description.clone());
-
-        let (section_marker, start_marker, end_marker, name_marker, desc_marker) =
-            self.generate_markers();
-
-        let temp_path = self.executable_path.with_extension("new");
-
-        let mut original = match File::open(&self.executable_path) {
-            Ok(f) => f,
-            Err(e) => {
-                println!("ERROR: Failed to open original executable: {}", e);
-                return Err(e);
             }
-        };
-
-        let mut original_buffer = Vec::new();
-        original.read_to_end(&mut original_buffer)?;
-
-        let has_section_marker = if original_buffer.len() >= section_marker.len() {
-            let start_idx = original_buffer.len() - section_marker.len();
-            original_buffer[start_idx..] == section_marker[..]
-        } else {
-            false
-        };
-
-        let section_start_pos = if has_section_marker {
-            original_buffer.len() - section_marker.len()
-        } else {
-            original_buffer.len()
-        };
-
-        let mut new_exe = match File::create(&temp_path) {
-            Ok(f) => f,
-            Err(e) => {
-                println!("ERROR: Failed to create temp binary: {}", e);
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                drop(raw_mode);
+                println!("\nSetup aborted.");
                 return Err(e);
             }
-        };
-
-        new_exe.write_all(&original_buffer[..section_start_pos])?;
-
-        for (existing_name, existing_data) in &self.in_memory_cache {
-            if existing_name == &name {
-                continue;
+            Err(e) => {
+                println!("\nError reading from stdin: {}", e);
+                break;
             }
-
-            let existing_description = self
-                .metadata_cache
-                .get(existing_name)
-                .cloned()
-                .unwrap_or_else(|| "No description".to_string());
-
-            new_exe.write_all(&start_marker)?;
-
-            new_exe.write_all(&name_marker)?;
-            new_exe.write_all(existing_name.as_bytes())?;
-            new_exe.write_all(b"\0")?;
-
-            new_exe.write_all(existing_data)?;
-
-            new_exe.write_all(&end_marker)?;
-
-            new_exe.write_all(&desc_marker)?;
-            new_exe.write_all(existing_description.as_bytes())?;
-            new_exe.write_all(b"\0")?;
         }
+    }
+    drop(raw_mode);
+
+    println!("Building the structure from your phrase...");
+    let phrase = Wiped(
+        display_input
+            .chars()
+            .map(|ch| ch as u32)
+            .collect::<Vec<u32>>(),
+    );
+    // --reproducible builds what create-account builds: the phrase as it stands,
+    // timing left out
+    let (seed, keystrokes) = if reproducible {
+        let untimed = phrase.iter().map(|&keycode| (keycode, 0, 0)).collect();
+        (phrase_seed(&phrase, &[]), Wiped(untimed))
+    } else {
+        (phrase_seed(&phrase, &gaps_ms), keystrokes)
+    };
+    let mut structure_system = build_structure(seed, geometry, keycodes, &keystrokes);
+    structure_system.set_name(name.to_string());
 
-        new_exe.write_all(&start_marker)?;
+    let (created_date, setup_mode) = if reproducible {
+        (0, SetupMode::Reproducible)
+    } else {
+        let created_date = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("You a time traveler? Time went backwards")
+            .as_secs();
+        (created_date, SetupMode::Timed)
+    };
 
-        new_exe.write_all(&name_marker)?;
-        new_exe.write_all(name.as_bytes())?;
-        new_exe.write_all(b"\0")?;
+    let mut saved_password = SavedPassword {
+        name: name.to_string(),
+        description: description.to_string(),
+        structure_system,
+        created_date,
+        extra_chars_count: geometry.extra_chars_count,
+        use_count: 0,
+        normalization: Normalization::CURRENT,
+        fingerprint: None,
+        setup_mode: Some(setup_mode),
+        rekeyed_from: None,
+    };
+    saved_password.fingerprint = Fingerprint::of(&saved_password);
 
-        new_exe.write_all(data)?;
+    println!("\n\n✓ Configuration created successfully!");
+    println!("You typed {} characters for setup.", display_count);
 
-        new_exe.write_all(&end_marker)?;
+    Ok(saved_password)
+}
 
-        new_exe.write_all(&desc_marker)?;
-        new_exe.write_all(description.as_bytes())?;
-        new_exe.write_all(b"\0")?;
+fn run_simple_setup(
+    password_manager: &mut PasswordManager,
+    auto_exit: bool,
+    target: SetupTarget,
+    geometry: &Geometry,
+    pool: &Pool,
+    reproducible: bool,
+) -> io::Result<()> {
+    println!();
+    println!("╔════════════════════════════════════════════════════════════════════╗");
+    println!("║                     WELCOME TO THE VOID VAULT                      ║");
+    println!("║                         First-Time Setup                           ║");
+    println!("╚════════════════════════════════════════════════════════════════════╝\n");
 
-        new_exe.write_all(&section_marker)?;
+    println!("The Void Vault creates secure passwords for all your accounts.");
+    println!("Setup takes about 1 minute.\n");
 
-        drop(original);
-        drop(new_exe);
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!("Create Your Void Vault");
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!(
+        "\nType a phrase of at least {} characters, using at least {} different ones.",
+        PhraseQuality::MIN_LENGTH,
+        PhraseQuality::MIN_DISTINCT
+    );
+    println!("This phrase will create your unique vault.\n");
+    println!("Instructions:");
+    println!("  • Type any phrase, sentence, or random characters");
+    println!("  • Longer is better, setup won't finish on a shorter or repetitive one");
+    println!("  • Type naturally - your rhythm adds uniqueness");
+    println!("  • This phrase is ONLY for setup, not for generating passwords");
+    println!("  • Press ESC when finished\n");
+    println!(
+        "Note: Your passwords will draw from the {} pool of {} characters.",
+        pool.name,
+        pool.codes.len()
+    );
+    println!("      The browser extension will handle website requirements.\n");
+    if reproducible {
+        println!("WARNING: --reproducible leaves out how you type. Only the phrase itself goes");
+        println!("         into the vault, so anyone who learns the phrase can rebuild it and");
+        println!("         every password it generates. Without --reproducible your rhythm");
+        println!("         is mixed in as well, and the phrase alone is not enough.");
+        println!("         Use it only for a phrase nobody else can guess or read.\n");
+    }
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let metadata = fs::metadata(&self.executable_path)?;
-            let mode = metadata.permissions().mode();
-            let mut perms = fs::metadata(&temp_path)?.permissions();
-            perms.set_mode(mode);
-            fs::set_permissions(&temp_path, perms)?;
+    let (name, description) = match &target {
+        SetupTarget::First => ("main".to_string(), "Primary configuration".to_string()),
+        SetupTarget::Add(name) => (name.clone(), "Added with setup".to_string()),
+        SetupTarget::Replace(idx) => {
+            let existing = &password_manager.saved_passwords[*idx];
+            println!(
+                "This replaces the configuration '{}'. The passwords it generates now can't be generated again.",
+                existing.name
+            );
+            println!("Press Ctrl+C now to keep it.\n");
+            (existing.name.clone(), existing.description.clone())
         }
+    };
 
-        let backup_path = self.executable_path.with_extension("bak");
+    println!("Press Enter to begin...");
+    let mut ready = String::new();
+    read_line_interruptible(&mut ready)?;
+
+    let saved_password =
+        create_password_setup(&name, &description, geometry, &pool.codes, reproducible)?;
+    let points = saved_password.structure_system.active_point_count();
+    let capped = saved_password.structure_system.points_capped();
+    // taken before the store, which replaces the binary current_exe names
+    let exe_path = vault_path()?;
+
+    match target {
+        SetupTarget::Replace(idx) => {
+            password_manager.save_password(&saved_password)?;
+            password_manager.saved_passwords[idx] = saved_password;
+            password_manager.active_structure_idx = Some(idx);
+        }
+        SetupTarget::First | SetupTarget::Add(_) => {
+            password_manager.add_password(saved_password)?;
+            password_manager.active_structure_idx =
+                Some(password_manager.saved_passwords.len() - 1);
+        }
+    }
+    seal_code(&exe_path, false)?;
 
-        match fs::rename(&self.executable_path, &backup_path) {
-            Ok(_) => {}
-            Err(e) => {
-                println!("ERROR: Failed to create backup: {}", e);
-                return Err(e);
-            }
-        }
+    println!("\n══════════════════════════════════════════════════════════════════════");
+    println!("✓ SETUP COMPLETE!");
+    println!("══════════════════════════════════════════════════════════════════════");
+    println!("\nYour password system is ready!");
+    println!("Geometry: {}, {} pool", geometry, pool.name);
+    if capped {
+        println!(
+            "Structure: {} active points, stopped at the limit of {} (--max-points)\n",
+            points, geometry.max_points
+        );
+    } else {
+        println!("Structure: {} active points\n", points);
+    }
+    println!("How to use the Void Vault:");
+    println!("  1. Go to any website login page");
+    println!("  2. Click the password field");
+    println!("  3. Press Ctrl+Shift+S in your browser");
+    println!("  4. Type your password phrase");
+    println!("  5. Press Enter\n");
+    println!("⚠️  IMPORTANT: Backup this file to a safe place!");
+    println!("    Without it, you cannot access your passwords.\n");
 
-        match fs::rename(&temp_path, &self.executable_path) {
-            Ok(_) => {}
-            Err(e) => {
-                println!("ERROR: Failed to replace binary: {}", e);
+    if !auto_exit {
+        println!("Press Enter to continue...");
+        let mut cont = String::new();
+        read_line_interruptible(&mut cont)?;
+    }
 
-                let _ = fs::rename(&backup_path, &self.executable_path);
-                return Err(e);
-            }
-        }
+    Ok(())
+}
 
-        self.binary_modified = true;
+// the pool a new configuration draws from: a preset by name, or "custom" with the
+// codes of a --pool-file
+struct Pool {
+    name: String,
+    codes: Vec<u32>,
+}
 
-        if !self.parent_mode {
-            self.signal_binary_update()?;
-        }
+impl Pool {
+    // --pool-file, else --pool (or create-account's older --charset), else the default
+    fn from_args(args: &[String]) -> io::Result<Self> {
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|pos| args.get(pos + 1))
+        };
 
-        Ok(())
+        if let Some(path) = value("--pool-file") {
+            if value("--pool").is_some() || value("--charset").is_some() {
+                return Err(ErrorCode::Usage
+                    .error("--pool-file brings its own pool, it can't be combined with --pool"));
+            }
+            let text = fs::read_to_string(path).map_err(|e| {
+                ErrorCode::BadRequest.error(format!("Could not read the pool file {}: {}", path, e))
+            })?;
+            return Ok(Pool {
+                name: "custom".to_string(),
+                codes: pool::parse(&text)?,
+            });
+        }
+
+        let name = value("--pool")
+            .or_else(|| value("--charset"))
+            .map_or(pool::DEFAULT, String::as_str);
+        let codes = pool::preset(name).ok_or_else(|| {
+            ErrorCode::Usage.error(format!(
+                "Unknown pool '{}', use {} or --pool-file <path>",
+                name,
+                pool::PRESETS.join(", ")
+            ))
+        })?;
+        Ok(Pool {
+            name: name.to_string(),
+            codes,
+        })
     }
-    // finds the geometry
-    fn retrieve(&self, name: &str) -> io::Result<Option<(Vec<u8>, String)>> {
-        if let Some(data) = self.in_memory_cache.get(name) {
-            let description = self
-                .metadata_cache
-                .get(name)
-                .cloned()
-                .unwrap_or_else(|| "No description".to_string());
+}
 
-            return Ok(Some((data.clone(), description)));
-        }
+// what a setup phrase gives the geometry to work with. a short phrase or one key
+// held down builds a structure that is trivially reproduced
+struct PhraseQuality {
+    length: usize,
+    distinct: usize,
+    // spread of the gaps between keystrokes, None without timing (create-account)
+    rhythm_ms: Option<u64>,
+}
 
-        Ok(None)
-    }
+impl PhraseQuality {
+    const MIN_LENGTH: usize = 40;
+    const MIN_DISTINCT: usize = 12;
 
-    fn list_all(&self) -> Vec<(String, String)> {
-        let mut all_passwords = Vec::new();
+    fn of(phrase: &[u32], gaps_ms: &[u64]) -> Self {
+        let mut distinct = phrase.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
 
-        for name in self.in_memory_cache.keys() {
-            let description = self
-                .metadata_cache
-                .get(name)
-                .cloned()
-                .unwrap_or_else(|| "No description".to_string());
+        let rhythm_ms = if gaps_ms.len() < 2 {
+            None
+        } else {
+            let mean = gaps_ms.iter().sum::<u64>() as f64 / gaps_ms.len() as f64;
+            let variance = gaps_ms
+                .iter()
+                .map(|&gap| (gap as f64 - mean).powi(2))
+                .sum::<f64>()
+                / gaps_ms.len() as f64;
+            Some(variance.sqrt() as u64)
+        };
 
-            all_passwords.push((name.clone(), description));
+        PhraseQuality {
+            length: phrase.len(),
+            distinct: distinct.len(),
+            rhythm_ms,
         }
+    }
 
-        all_passwords
+    fn meets_minimum(&self) -> bool {
+        self.length >= Self::MIN_LENGTH && self.distinct >= Self::MIN_DISTINCT
     }
-    // ensures the binary gets updated
-    fn signal_binary_update(&self) -> io::Result<()> {
-        if let Some(tx) = &self.message_tx {
-            if let Err(e) = tx.send(ProcessMessage::BinaryUpdated(self.executable_path.clone())) {
-                println!("Failed to signal binary update: {}", e);
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Failed to signal binary update",
-                ));
-            }
+
+    fn label(&self) -> &'static str {
+        if !self.meets_minimum() {
+            "weak"
+        } else if self.length >= 60 && self.distinct >= 20 {
+            "strong"
+        } else {
+            "fair"
         }
-        Ok(())
     }
-}
 
-// Domain table marker for binary layout
-const DOMAIN_TABLE_START_MARKER: &[u8] = b"__DOMAIN_TABLE_START__";
+    // for the status line, e.g. "fair, 14 distinct, rhythm ±85ms"
+    fn indicator(&self) -> String {
+        match self.rhythm_ms {
+            Some(rhythm) => format!(
+                "{}, {} distinct, rhythm ±{}ms",
+                self.label(),
+                self.distinct,
+                rhythm
+            ),
+            None => format!("{}, {} distinct", self.label(), self.distinct),
+        }
+    }
 
-// Domain slot entry (69 bytes total: 64 + 2 + 2 + 1)
-#[derive(Clone, Copy)]
-struct DomainSlot {
-    domain_hash: [u8; 64], // Geometric hash of domain name
-    counter: u16,          // Password version counter (0-65535)
-    max_length: u16,       // Maximum password length (0 = unlimited)
-    char_types: u8,        // Bit flags for allowed character types
-}
-
-impl DomainSlot {
-    const EMPTY: Self = DomainSlot {
-        domain_hash: [0u8; 64],
-        counter: 0,
-        max_length: 0,
-        char_types: 127, // All 7 character types enabled by default
-    };
+    // what is missing before the minimum is met
+    fn shortfall(&self) -> String {
+        let mut missing = Vec::new();
+        if self.length < Self::MIN_LENGTH {
+            missing.push(format!(
+                "{} more characters (at least {})",
+                Self::MIN_LENGTH - self.length,
+                Self::MIN_LENGTH
+            ));
+        }
+        if self.distinct < Self::MIN_DISTINCT {
+            missing.push(format!(
+                "{} more different characters (at least {})",
+                Self::MIN_DISTINCT - self.distinct,
+                Self::MIN_DISTINCT
+            ));
+        }
+        missing.join(" and ")
+    }
+}
 
-    fn is_empty(&self) -> bool {
-        self.domain_hash == [0u8; 64]
+// --i-know-what-im-doing: setup accepts phrases below the PhraseQuality minimum.
+// for tests and throwaway vaults
+static ALLOW_WEAK_PHRASE: AtomicBool = AtomicBool::new(false);
+
+// PBKDF2 rounds between a setup phrase and the seed of the structure built from it
+const PHRASE_SEED_ROUNDS: u32 = 50_000;
+
+// the geometry's seed, stretched out of the setup phrase and the gaps between its
+// keystrokes (none for create-account, so the same phrase and options rebuild the
+// same configuration). setups used to take the wall-clock second, which anyone can
+// narrow down to a few million guesses. the seed is stored with the structure, so
+// the ones built before keep theirs
+fn phrase_seed(phrase: &[u32], gaps_ms: &[u64]) -> u64 {
+    let mut passphrase = Wiped(String::new());
+    passphrase.extend(phrase.iter().filter_map(|&keycode| char::from_u32(keycode)));
+    let mut salt = Wiped(b"void-vault setup seed".to_vec());
+    for gap in gaps_ms {
+        salt.extend_from_slice(&gap.to_le_bytes());
     }
+
+    let derived = stretch_passphrase(passphrase.as_bytes(), &salt, PHRASE_SEED_ROUNDS, 8);
+    u64::from_le_bytes(derived[..8].try_into().unwrap())
 }
 
-struct DomainTable {
-    slots: [DomainSlot; 512],
+// the shape of a new geometry, from setup's and create-account's --dimensions,
+// --range, --step, --variance and --extra-chars. without them it is the one every
+// configuration got before they existed. all of it is stored with the structure,
+// but for --max-points, which only bounds the building of it
+struct Geometry {
+    dimensions: usize,
+    coordinate_range: i32,
+    base_step_size: f64,
+    step_variance: f64,
+    // output characters per input, past the first
+    extra_chars_count: usize,
+    max_points: usize,
 }
 
-impl DomainTable {
-    const fn new() -> Self {
-        DomainTable {
-            slots: [DomainSlot::EMPTY; 512],
-        }
+impl Geometry {
+    fn from_args(args: &[String]) -> io::Result<Self> {
+        let dimensions = option_in_range(args, "--dimensions", 3..=MAX_DIMENSIONS, 7)?;
+        Ok(Geometry {
+            dimensions,
+            coordinate_range: option_in_range(args, "--range", 5..=100, 10 + dimensions as i32)?,
+            base_step_size: option_in_range(args, "--step", 0.5..=10.0, 3.0)?,
+            step_variance: option_in_range(args, "--variance", 0.0..=10.0, 2.0)?,
+            extra_chars_count: option_in_range(args, "--extra-chars", 0..=31, 7)?,
+            max_points: option_in_range(
+                args,
+                "--max-points",
+                10_000..=100_000_000,
+                DEFAULT_MAX_POINTS,
+            )?,
+        })
     }
+}
 
-    // Find slot index for a domain hash
-    fn find_slot_by_hash(hash: &[u8; 64]) -> Option<usize> {
-        unsafe {
-            let table = &*std::ptr::addr_of!(DOMAIN_TABLE);
-            table
-                .slots
-                .iter()
-                .position(|slot| !slot.is_empty() && slot.domain_hash == *hash)
-        }
+impl std::fmt::Display for Geometry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} dimensions, range {}, step {} ± {}, {} extra chars",
+            self.dimensions,
+            self.coordinate_range,
+            self.base_step_size,
+            self.step_variance,
+            self.extra_chars_count
+        )
     }
+}
 
-    fn get_counter(domain: &str, structure: &mut StructureSystem) -> Option<u16> {
-        let hash = structure.hash_domain(domain);
+// the value after `flag` in `args`, `default` when it isn't given
+fn option_in_range<T>(
+    args: &[String],
+    flag: &str,
+    range: std::ops::RangeInclusive<T>,
+    default: T,
+) -> io::Result<T>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    let Some(pos) = args.iter().position(|arg| arg == flag) else {
+        return Ok(default);
+    };
+    args.get(pos + 1)
+        .and_then(|value| value.parse().ok())
+        .filter(|value| range.contains(value))
+        .ok_or_else(|| {
+            ErrorCode::Usage.error(format!(
+                "{} must be {}-{}",
+                flag,
+                range.start(),
+                range.end()
+            ))
+        })
+}
 
-        Self::find_slot_by_hash(&hash).map(|idx| unsafe { DOMAIN_TABLE.slots[idx].counter })
+// a new structure from its seed, grown and then stepped through the setup
+// keystrokes as (keycode, gap in ms, timestamp)
+fn build_structure(
+    seed: u64,
+    geometry: &Geometry,
+    keycodes: &[u32],
+    keystrokes: &[(u32, u64, u64)],
+) -> StructureSystem {
+    let mut structure_system =
+        StructureSystem::new(seed, geometry.dimensions, geometry.coordinate_range);
+    structure_system.base_step_size = geometry.base_step_size;
+    structure_system.step_variance = geometry.step_variance;
+    structure_system.max_points = geometry.max_points;
+    structure_system.set_character_set(keycodes.to_vec());
+    structure_system.generate_structure(&[], keycodes);
+
+    structure_system.reset_position();
+    for &(keycode, timing_ms, timestamp) in keystrokes {
+        structure_system.modify_with_timing(keycode, timing_ms, timestamp);
+        let _ = Wiped(structure_system.transform_char(keycode, geometry.extra_chars_count));
     }
+    structure_system.full_reset();
+    structure_system
+}
 
-    fn set_counter(
-        domain: &str,
-        counter: u16,
-        structure: &mut StructureSystem,
-    ) -> Result<(), &'static str> {
-        let hash = structure.hash_domain(domain);
+// rekey: a copy of a configuration that outputs differently, see SavedPassword::rekey.
+// the domains stay with the original until domains migrate moves them
+fn run_rekey(account: &str, name: Option<&str>, args: &[String]) -> io::Result<()> {
+    let given = |flag: &str| args.iter().any(|arg| arg == flag);
+    let extra_chars = if given("--extra-chars") {
+        Some(Geometry::from_args(args)?.extra_chars_count)
+    } else {
+        None
+    };
+    let pool = if given("--pool") || given("--pool-file") {
+        Some(Pool::from_args(args)?)
+    } else {
+        None
+    };
+    if extra_chars.is_none() && pool.is_none() {
+        return Err(ErrorCode::Usage.error(
+            "rekey needs --extra-chars, --pool or --pool-file, a copy without them generates the same passwords",
+        ));
+    }
 
-        unsafe {
-            if let Some(idx) = Self::find_slot_by_hash(&hash) {
-                let table = &mut *std::ptr::addr_of_mut!(DOMAIN_TABLE);
-                table.slots[idx].counter = counter;
-                return Ok(());
-            }
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    let mut password_manager = PasswordManager::new(false, None, true)?;
+    let idx = select_account(&password_manager, Some(account))?;
+    let original = &password_manager.saved_passwords[idx];
+    let lineage = original.structure_system.lineage;
+
+    // before Transform::V4 a domain hash is read off a walk over the character set,
+    // so another pool would leave every registered domain behind
+    if pool.is_some()
+        && matches!(
+            original.structure_system.transform,
+            Transform::V1 | Transform::V2 | Transform::V3
+        )
+    {
+        return Err(ErrorCode::BadRequest.error(format!(
+            "'{}' keys its domains by its character set, so only --extra-chars can change",
+            account
+        )));
+    }
 
-            // Find first empty slot
-            let table = &*std::ptr::addr_of!(DOMAIN_TABLE);
-            if let Some(idx) = table.slots.iter().position(|s| s.is_empty()) {
-                let table = &mut *std::ptr::addr_of_mut!(DOMAIN_TABLE);
-                table.slots[idx] = DomainSlot {
-                    domain_hash: hash,
-                    counter,
-                    max_length: 0,   // 0 = unlimited, if you need to.
-                    char_types: 127, // Default: all types enabled
-                };
-                Ok(())
-            } else {
-                Err("Domain table full (512 slots)")
-            }
+    // main, main-v2, main-v3 ...
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => {
+            let suffix = format!("-v{}", lineage as u16 + 1);
+            let base = match original.name.strip_suffix(&suffix) {
+                Some(base) if lineage > 0 => base,
+                _ => original.name.as_str(),
+            };
+            format!("{}-v{}", base, lineage as u16 + 2)
         }
+    };
+    if name.is_empty() {
+        return Err(ErrorCode::Usage.error("The configuration needs a name"));
     }
-
-    fn increment_counter(
-        domain: &str,
-        structure: &mut StructureSystem,
-    ) -> Result<u16, &'static str> {
-        let current = Self::get_counter(domain, structure).unwrap_or(0);
-        let new_counter = current.saturating_add(1);
-        Self::set_counter(domain, new_counter, structure)?;
-        Ok(new_counter)
+    if password_manager
+        .saved_passwords
+        .iter()
+        .any(|p| p.name == name)
+    {
+        return Err(ErrorCode::AccountExists
+            .error(format!("A configuration named '{}' already exists", name)));
     }
 
-    // get password rules for domain
-    fn get_rules(domain: &str, structure: &mut StructureSystem) -> Option<(u16, u8)> {
-        let hash = structure.hash_domain(domain);
-
-        Self::find_slot_by_hash(&hash).map(|idx| unsafe {
-            let slot = &DOMAIN_TABLE.slots[idx];
-            (slot.max_length, slot.char_types)
-        })
+    let extra_chars = extra_chars.unwrap_or(original.extra_chars_count);
+    let character_set = pool.as_ref().map(|pool| pool.codes.clone());
+    let Some(mut rekeyed) = original.rekey(&name, extra_chars, character_set) else {
+        return Err(ErrorCode::BadRequest.error(format!(
+            "'{}' is re-key {} of its setup, the most there can be",
+            account, MAX_LINEAGE
+        )));
+    };
+    // a reproducible one stays reproducible, nothing in it says when it was made
+    if rekeyed.setup_mode != Some(SetupMode::Reproducible) {
+        rekeyed.created_date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("You a time traveler? Time went backwards")
+            .as_secs();
+    }
+    let pool_name = pool::name_of(&rekeyed.structure_system.character_set);
+    password_manager.add_password(rekeyed)?;
+    seal_code(&exe_path, false)?;
+
+    if json_output() {
+        println!(
+            "{{\"name\":\"{}\",\"rekeyed_from\":\"{}\",\"extra_chars\":{},\"pool\":\"{}\",\"lineage\":{},\"status\":\"created\"}}",
+            escape_json(&name),
+            escape_json(account),
+            extra_chars,
+            pool_name,
+            lineage + 1
+        );
+    } else {
+        println!(
+            "Created '{}' from '{}': {} extra chars, {} pool",
+            name, account, extra_chars, pool_name
+        );
+        println!(
+            "Domains registered with '{}' keep generating with it. domains migrate <domain> --account {} moves one over",
+            account, name
+        );
     }
+    Ok(())
+}
 
-    // Set password rules for domain
-    // Creates new entry if domain doesn't exist
-    // returns error if table is full (all 512 slots used. If this happens, rethink your life)
-    fn set_rules(
-        domain: &str,
-        max_length: u16,
-        char_types: u8,
-        structure: &mut StructureSystem,
-    ) -> Result<(), &'static str> {
-        let hash = structure.hash_domain(domain);
+// create-account: the setup without a terminal. the phrase comes from stdin or a
+// file and stands in for the keystroke timing, which makes the result reproducible
+// by anyone who has the phrase
+fn run_create_account(name: &str, args: &[String]) -> io::Result<()> {
+    let mut phrase_file: Option<String> = None;
+    let geometry = Geometry::from_args(args)?;
+    let mut description = "Created without keystroke timing".to_string();
+    let mut force = false;
 
-        unsafe {
-            // Try to find existing slot
-            if let Some(idx) = Self::find_slot_by_hash(&hash) {
-                let table = &mut *std::ptr::addr_of_mut!(DOMAIN_TABLE);
-                table.slots[idx].max_length = max_length;
-                table.slots[idx].char_types = char_types;
-                return Ok(());
+    let mut i = 2;
+    while i < args.len() {
+        let value = args.get(i + 1).map(String::as_str).unwrap_or("");
+        match args[i].as_str() {
+            "--phrase-file" => phrase_file = Some(value.to_string()),
+            "--description" => description = value.to_string(),
+            "--force" => {
+                force = true;
+                i += 1;
+                continue;
             }
-
-            let table = &*std::ptr::addr_of!(DOMAIN_TABLE);
-            if let Some(idx) = table.slots.iter().position(|s| s.is_empty()) {
-                let table = &mut *std::ptr::addr_of_mut!(DOMAIN_TABLE);
-                table.slots[idx] = DomainSlot {
-                    domain_hash: hash,
-                    counter: 0, // New domain starts at counter 0
-                    max_length,
-                    char_types,
-                };
-                Ok(())
-            } else {
-                Err("Domain table full (512 slots)")
+            _ => {
+                i += 1;
+                continue;
             }
         }
+        i += 2;
     }
 
-    fn save_to_binary(path: &std::path::Path) -> io::Result<()> {
-        let mut file = File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-        drop(file);
-
-        let mut marker_pos = None;
-        let search_start = if buffer.len() > 10 * 1024 * 1024 {
-            buffer.len() - 10 * 1024 * 1024
-        } else {
-            0
-        };
+    if name.is_empty() {
+        return Err(ErrorCode::Usage.error("The configuration needs a name"));
+    }
+    let pool = Pool::from_args(args)?;
+    let keycodes = &pool.codes;
 
-        for i in (search_start..buffer.len().saturating_sub(DOMAIN_TABLE_START_MARKER.len())).rev()
-        {
-            if &buffer[i..i + DOMAIN_TABLE_START_MARKER.len()] == DOMAIN_TABLE_START_MARKER {
-                marker_pos = Some(i);
-                break;
-            }
+    let mut raw = Wiped(Vec::new());
+    match &phrase_file {
+        Some(path) => {
+            File::open(path)?.read_to_end(&mut raw)?;
         }
+        None => {
+            io::stdin().read_to_end(&mut raw)?;
+        }
+    }
+    while raw.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+        raw.pop();
+    }
+    let mut phrase = Wiped(
+        String::from_utf8_lossy(&raw)
+            .chars()
+            .map(|ch| ch as u32)
+            .collect::<Vec<u32>>(),
+    );
+    // v1, as setup composes the phrase
+    Normalization::V1.apply(&mut phrase);
+    phrase.retain(|keycode| keycodes.contains(keycode));
+
+    if phrase.is_empty() {
+        return Err(ErrorCode::BadRequest.error("The setup phrase is empty"));
+    }
+    let quality = PhraseQuality::of(&phrase, &[]);
+    if !quality.meets_minimum() && !ALLOW_WEAK_PHRASE.load(Ordering::SeqCst) {
+        return Err(ErrorCode::WeakPhrase.error(format!(
+            "The phrase is too weak, it needs {}. --i-know-what-im-doing skips this check",
+            quality.shortfall()
+        )));
+    }
 
-        let marker_pos = marker_pos.ok_or_else(|| {
-            io::Error::new(io::ErrorKind::NotFound, "Domain table marker not found")
-        })?;
-
-        let table_offset = marker_pos + DOMAIN_TABLE_START_MARKER.len();
-        let table_size = std::mem::size_of::<DomainTable>();
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    let mut password_manager = PasswordManager::new(false, None, true)?;
+
+    let existing = password_manager
+        .saved_passwords
+        .iter()
+        .position(|p| p.name == name);
+    if existing.is_some() && !force {
+        return Err(ErrorCode::AccountExists.error(format!(
+            "A configuration named '{}' already exists, use --force to replace it",
+            name
+        )));
+    }
 
-        unsafe {
-            let table_bytes = std::slice::from_raw_parts(
-                std::ptr::addr_of!(DOMAIN_TABLE) as *const u8,
-                table_size,
-            );
-            buffer[table_offset..table_offset + table_size].copy_from_slice(table_bytes);
+    let mut warnings = vec![
+        "This configuration is built without keystroke timing. Anyone with the same phrase and options can rebuild it.".to_string(),
+    ];
+    if !quality.meets_minimum() {
+        warnings.push(format!(
+            "The phrase is weak ({}), it needs {}.",
+            quality.indicator(),
+            quality.shortfall()
+        ));
+    }
+    // with --json the warnings travel in the result instead
+    if !json_output() {
+        for warning in &warnings {
+            diag(Diag::Warning, format_args!("Warning: {}", warning));
         }
+    }
 
-        let temp_path = path.with_extension("new");
-        let mut new_file = File::create(&temp_path)?;
-        new_file.write_all(&buffer)?;
-        drop(new_file);
+    // the same per-key steps as the interactive setup, with a fixed zero timing
+    let keystrokes = Wiped(
+        phrase
+            .iter()
+            .map(|&keycode| (keycode, 0, 0))
+            .collect::<Vec<(u32, u64, u64)>>(),
+    );
+    let mut structure_system =
+        build_structure(phrase_seed(&phrase, &[]), &geometry, keycodes, &keystrokes);
+    structure_system.set_name(name.to_string());
+    let points = structure_system.active_point_count();
+    let capped = structure_system.points_capped();
+
+    // no creation time either, it would be all that tells two builds apart
+    let mut saved_password = SavedPassword {
+        name: name.to_string(),
+        description,
+        structure_system,
+        created_date: 0,
+        extra_chars_count: geometry.extra_chars_count,
+        use_count: 0,
+        normalization: Normalization::CURRENT,
+        fingerprint: None,
+        setup_mode: Some(SetupMode::Reproducible),
+        rekeyed_from: None,
+    };
+    saved_password.fingerprint = Fingerprint::of(&saved_password);
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let metadata = fs::metadata(path)?;
-            let mode = metadata.permissions().mode();
-            let mut perms = fs::metadata(&temp_path)?.permissions();
-            perms.set_mode(mode);
-            fs::set_permissions(&temp_path, perms)?;
+    match existing {
+        Some(idx) => {
+            password_manager.save_password(&saved_password)?;
+            password_manager.saved_passwords[idx] = saved_password;
         }
+        None => password_manager.add_password(saved_password)?,
+    }
+    seal_code(&exe_path, false)?;
 
-        let backup_path = path.with_extension("bak");
-        fs::rename(path, &backup_path)?;
-        fs::rename(&temp_path, path)?;
-
-        Ok(())
+    if json_output() {
+        let warnings: Vec<String> = warnings
+            .iter()
+            .map(|w| format!("\"{}\"", escape_json(w)))
+            .collect();
+        println!(
+            "{{\"name\":\"{}\",\"dimensions\":{},\"range\":{},\"step\":{},\"variance\":{},\"extra_chars\":{},\"charset\":\"{}\",\"active_points\":{},\"points_capped\":{},\"replaced\":{},\"warnings\":[{}],\"status\":\"created\"}}",
+            escape_json(name),
+            geometry.dimensions,
+            geometry.coordinate_range,
+            geometry.base_step_size,
+            geometry.step_variance,
+            geometry.extra_chars_count,
+            escape_json(&pool.name),
+            points,
+            capped,
+            existing.is_some(),
+            warnings.join(",")
+        );
+    } else if !QUIET.load(Ordering::SeqCst) {
+        println!(
+            "Created configuration '{}' ({}, {} pool, {} active points{})",
+            name,
+            geometry,
+            pool.name,
+            points,
+            if capped {
+                ", stopped at --max-points"
+            } else {
+                ""
+            }
+        );
     }
+    Ok(())
+}
 
-    fn load_from_binary(path: &std::path::Path) -> io::Result<()> {
-        let mut file = File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+// recovery sheets. only what generation depends on goes on the sheet: each
+// configuration's seed, dimensions, step sizes and character set (as runs of code
+// points), and the domain table's used slots. the rest of a structure is rebuilt by
+// StructureSystem::new the same way loading a vault rebuilds it
 
-        let mut marker_pos = None;
-        let search_start = if buffer.len() > 10 * 1024 * 1024 {
-            buffer.len() - 10 * 1024 * 1024
-        } else {
-            0
-        };
+// SHA-256, for the passphrase stretching, the sheet's cipher and its checksums
+#[derive(Clone)]
+struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; 64],
+    buffered: usize,
+    length: u64,
+}
 
-        for i in (search_start..buffer.len().saturating_sub(DOMAIN_TABLE_START_MARKER.len())).rev()
-        {
-            if &buffer[i..i + DOMAIN_TABLE_START_MARKER.len()] == DOMAIN_TABLE_START_MARKER {
-                marker_pos = Some(i);
-                break;
-            }
+impl Sha256 {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+
+    fn new() -> Self {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            buffer: [0; 64],
+            buffered: 0,
+            length: 0,
         }
+    }
 
-        if let Some(marker_pos) = marker_pos {
-            let table_size = std::mem::size_of::<DomainTable>();
-
-            if buffer.len() >= marker_pos + DOMAIN_TABLE_START_MARKER.len() + table_size {
-                let table_data = &buffer[marker_pos + DOMAIN_TABLE_START_MARKER.len()
-                    ..marker_pos + DOMAIN_TABLE_START_MARKER.len() + table_size];
+    fn digest(data: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hasher.finish()
+    }
 
-                unsafe {
-                    std::ptr::copy_nonoverlapping(
-                        table_data.as_ptr(),
-                        std::ptr::addr_of_mut!(DOMAIN_TABLE) as *mut u8,
-                        table_size,
-                    );
-                }
+    fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let take = (64 - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered == 64 {
+                self.compress();
+                self.buffered = 0;
             }
         }
-
-        Ok(())
     }
-}
 
-struct SessionState {
-    active_domain_hash: Option<[u8; 64]>,
-    saved_counter: u16,
-    active_counter: u16,
-    is_preview_mode: bool,
-    initialized: bool,
-}
+    fn finish(mut self) -> [u8; 32] {
+        let bit_length = self.length.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.buffered != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_length.to_be_bytes());
 
-impl SessionState {
-    const fn empty() -> Self {
-        SessionState {
-            active_domain_hash: None,
-            saved_counter: 0,
-            active_counter: 0,
-            is_preview_mode: false,
-            initialized: false,
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
         }
+        digest
     }
-}
-
-#[allow(static_mut_refs)]
-static mut DOMAIN_TABLE: DomainTable = DomainTable::new();
-
-#[allow(static_mut_refs)]
-static mut SESSION: SessionState = SessionState::empty();
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-struct StructurePoint {
-    coordinates: Vec<i32>,
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in self.buffer.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let mut v = self.state;
+        for (k, word) in Self::K.iter().zip(w.iter()) {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let choice = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7]
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(*k)
+                .wrapping_add(*word);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let majority = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(majority);
+
+            v[7] = v[6];
+            v[6] = v[5];
+            v[5] = v[4];
+            v[4] = v[3].wrapping_add(t1);
+            v[3] = v[2];
+            v[2] = v[1];
+            v[1] = v[0];
+            v[0] = t1.wrapping_add(t2);
+        }
+        for (state, value) in self.state.iter_mut().zip(v) {
+            *state = state.wrapping_add(value);
+        }
+        w.fill(0);
+    }
 }
 
-impl StructurePoint {
-    fn new(dimensions: usize) -> Self {
-        StructurePoint {
-            coordinates: vec![0; dimensions],
-        }
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        zero_memory(&mut self.buffer[..]);
+        zero_memory(&mut self.state[..]);
     }
+}
 
-    fn from_seed(seed: u64, dimensions: usize, range: i32) -> Self {
-        let mut point = StructurePoint::new(dimensions);
-        let mut rng_state = seed;
+#[derive(Clone)]
+struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
 
-        for i in 0..dimensions {
-            rng_state = rng_state
-                .wrapping_mul(6364136223846793005)
-                .wrapping_add(1442695040888963407);
-            let value = ((rng_state % (range as u64 * 2)) as i32) - range;
-            point.coordinates[i] = value;
+impl HmacSha256 {
+    fn new(key: &[u8]) -> Self {
+        let mut block = [0u8; 64];
+        if key.len() > 64 {
+            block[..32].copy_from_slice(&Sha256::digest(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
         }
 
-        point
+        let mut inner = Sha256::new();
+        let mut outer = Sha256::new();
+        inner.update(&block.map(|b| b ^ 0x36));
+        outer.update(&block.map(|b| b ^ 0x5c));
+        zero_memory(&mut block[..]);
+        HmacSha256 { inner, outer }
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&(self.coordinates.len() as u32).to_ne_bytes());
-        for &coord in &self.coordinates {
-            bytes.extend_from_slice(&coord.to_ne_bytes());
-        }
-        bytes
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
     }
 
-    fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), &'static str> {
-        if bytes.len() < 4 {
-            return Err("Invalid data: not enough bytes for StructurePoint");
-        }
-
-        let coord_count = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
-        let required_bytes = 4 + (coord_count * 4);
-        if bytes.len() < required_bytes {
-            return Err("Invalid data: not enough bytes for coordinates");
-        }
+    fn finish(self) -> [u8; 32] {
+        let HmacSha256 { inner, mut outer } = self;
+        outer.update(&inner.finish());
+        outer.finish()
+    }
+}
 
-        let mut coordinates = Vec::with_capacity(coord_count);
-        for i in 0..coord_count {
-            let start = 4 + (i * 4);
-            coordinates.push(i32::from_ne_bytes([
-                bytes[start],
-                bytes[start + 1],
-                bytes[start + 2],
-                bytes[start + 3],
-            ]));
+// PBKDF2-HMAC-SHA256, length bytes of it
+fn stretch_passphrase(
+    passphrase: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    length: usize,
+) -> Wiped<Vec<u8>> {
+    let keyed = HmacSha256::new(passphrase);
+    let mut derived = Wiped(Vec::with_capacity(length.next_multiple_of(32)));
+
+    for block in 1..=length.div_ceil(32) as u32 {
+        let mut mac = keyed.clone();
+        mac.update(salt);
+        mac.update(&block.to_be_bytes());
+        let mut u = mac.finish();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut mac = keyed.clone();
+            mac.update(&u);
+            u = mac.finish();
+            for (t, u) in t.iter_mut().zip(u) {
+                *t ^= u;
+            }
         }
-
-        Ok((StructurePoint { coordinates }, required_bytes))
+        derived.extend_from_slice(&t);
+        zero_memory(&mut u[..]);
+        zero_memory(&mut t[..]);
     }
+    derived.truncate(length);
+    derived
 }
-#[derive(Debug, Clone)]
-struct ContinuousPosition {
-    coordinates: Vec<f64>,
+
+// HMAC-SHA256 of a block counter as the keystream, so encrypting and decrypting are
+// the same xor
+fn recovery_keystream(key: &[u8], data: &mut [u8]) {
+    for (block, chunk) in data.chunks_mut(32).enumerate() {
+        let mut mac = HmacSha256::new(key);
+        mac.update(&(block as u64).to_be_bytes());
+        let mut stream = mac.finish();
+        for (byte, key_byte) in chunk.iter_mut().zip(stream) {
+            *byte ^= key_byte;
+        }
+        zero_memory(&mut stream[..]);
+    }
 }
 
-// links all previous positions together
-impl ContinuousPosition {
-    fn new(dimensions: usize) -> Self {
-        ContinuousPosition {
-            coordinates: vec![0.0; dimensions],
+// RFC 4648 base32 without padding. no 0, 1 or 8 to mistake for O, I or B
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let mut pending = 0u32;
+    let mut pending_bits = 0;
+    for &byte in bytes {
+        pending = (pending << 8) | byte as u32;
+        pending_bits += 8;
+        while pending_bits >= 5 {
+            pending_bits -= 5;
+            text.push(BASE32[(pending >> pending_bits) as usize & 31] as char);
         }
     }
+    if pending_bits > 0 {
+        text.push(BASE32[(pending << (5 - pending_bits)) as usize & 31] as char);
+    }
+    text
+}
 
-    fn hash_position(&self, seed: u64) -> u64 {
-        let mut hash = seed;
-        for &coord in &self.coordinates {
-            let fixed = (coord * 1000.0) as i64;
-            hash = hash.wrapping_mul(31).wrapping_add(fixed as u64);
+// None at the first character outside the alphabet. lowercase is taken as upper
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 5 / 8);
+    let mut pending = 0u32;
+    let mut pending_bits = 0;
+    for ch in text.bytes() {
+        let value = BASE32.iter().position(|&c| c == ch.to_ascii_uppercase())?;
+        pending = (pending << 5) | value as u32;
+        pending_bits += 5;
+        if pending_bits >= 8 {
+            pending_bits -= 8;
+            bytes.push((pending >> pending_bits) as u8);
         }
-        hash
     }
+    Some(bytes)
 }
-#[derive(Clone)]
-struct StructureSystem {
-    //multiple active and interactable dimensions
-    dimensions: usize,
-    //active as in navigational lighthouses
-    active_points: HashSet<StructurePoint>,
-    //unifying the geometry translation with movement
-    char_to_point: HashMap<u32, StructurePoint>,
-    //range field for movement
-    coordinate_range: i32,
-    //initialization seed for the geometry
-    original_seed: u64,
-    //the name of the unique geometry
-    name: String,
-    //zoned coordinate pool for multi-dimensional treversal
-    character_set: Vec<u32>,
 
-    current_position: ContinuousPosition,
-    structure_bounds: (Vec<f64>, Vec<f64>),
-    base_step_size: f64,
-    step_variance: f64,
-
-    accumulated_path_memory: u8,
+// cursor over a decrypted sheet, little-endian throughout
+struct RecoveryReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
 }
 
-impl StructureSystem {
-    fn new(seed: u64, dimensions: usize, range: i32) -> Self {
-        StructureSystem {
-            dimensions,
-            active_points: HashSet::new(),
-            char_to_point: HashMap::new(),
-            coordinate_range: range,
-            original_seed: seed,
-            name: String::from("default"),
-            character_set: Vec::new(),
-            current_position: ContinuousPosition::new(dimensions),
-            structure_bounds: (vec![-30.0; dimensions], vec![30.0; dimensions]),
-            base_step_size: 3.0,
-            step_variance: 2.0,
-            accumulated_path_memory: 0,
-        }
+impl<'a> RecoveryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        let end = self.offset + len;
+        let taken = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or("The recovery data ends early")?;
+        self.offset = end;
+        Ok(taken)
     }
 
-    fn reset_position(&mut self) {
-        self.accumulated_path_memory = 0;
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
     }
 
-    fn full_reset(&mut self) {
-        self.current_position = ContinuousPosition::new(self.dimensions);
-        self.accumulated_path_memory = 0;
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
     }
 
-    fn transform_char(&mut self, keycode: u32, extra_chars_count: usize) -> Vec<u32> {
-        let (direction, distance) = self.calculate_movement(keycode);
-        let start_position = self.current_position.clone();
-
-        self.update_position(&direction, distance);
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
 
-        let output_chars = self.generate_output_from_path(
-            &start_position,
-            &direction,
-            distance,
-            extra_chars_count,
-        );
+    fn u64(&mut self) -> Result<u64, &'static str> {
+        let b = self.take(8)?;
+        Ok(u64::from_le_bytes([
+            b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7],
+        ]))
+    }
 
-        output_chars
+    fn text(&mut self) -> Result<String, &'static str> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| "Invalid UTF-8 in the recovery data")
     }
+}
 
-    fn calculate_movement(&self, keycode: u32) -> (Vec<f64>, f64) {
-        let position_hash = self.current_position.hash_position(self.original_seed);
-        let movement_seed = self.original_seed ^ position_hash ^ (keycode as u64);
+struct RecoverySheet;
+
+impl RecoverySheet {
+    const MAGIC: &'static [u8; 4] = b"VVR1";
+    const SALT_LEN: usize = 16;
+    const MAC_LEN: usize = 32;
+    const ITERATIONS: u32 = 200_000;
+    const MIN_PASSPHRASE: usize = 12;
+    // characters per sheet line, six groups of five
+    const LINE_CHARS: usize = 30;
+
+    // what generation needs, see the comment above Sha256
+    fn serialize(passwords: &[SavedPassword], slots: &[DomainSlot]) -> Wiped<Vec<u8>> {
+        let mut bytes = Wiped(Vec::with_capacity(4096));
+        let text = |bytes: &mut Vec<u8>, text: &str| {
+            bytes.extend_from_slice(&(text.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        };
 
-        let direction = self.generate_direction(movement_seed);
-        let distance = self.generate_distance(movement_seed);
+        bytes.push(passwords.len() as u8);
+        for password in passwords {
+            let structure = &password.structure_system;
+            text(&mut bytes, &password.name);
+            text(&mut bytes, &password.description);
+            bytes.extend_from_slice(&password.created_date.to_le_bytes());
+            bytes.push(password.extra_chars_count as u8);
+            bytes.push(password.normalization.to_byte());
+            bytes.push(structure.dimensions as u8);
+            bytes.extend_from_slice(&structure.coordinate_range.to_le_bytes());
+            bytes.extend_from_slice(&structure.original_seed.to_le_bytes());
+            bytes.extend_from_slice(&structure.base_step_size.to_bits().to_le_bytes());
+            bytes.extend_from_slice(&structure.step_variance.to_bits().to_le_bytes());
+
+            let mut runs: Vec<(u32, u32)> = Vec::new();
+            for &code in &structure.character_set {
+                match runs.last_mut() {
+                    Some((start, len)) if *start + *len == code => *len += 1,
+                    _ => runs.push((code, 1)),
+                }
+            }
+            bytes.extend_from_slice(&(runs.len() as u16).to_le_bytes());
+            for (start, len) in runs {
+                bytes.extend_from_slice(&start.to_le_bytes());
+                bytes.extend_from_slice(&len.to_le_bytes());
+            }
+        }
 
-        (direction, distance)
+        bytes.extend_from_slice(&(slots.len() as u16).to_le_bytes());
+        for slot in slots {
+            bytes.extend_from_slice(&slot.domain_hash);
+            bytes.extend_from_slice(&slot.counter.to_le_bytes());
+            bytes.extend_from_slice(&slot.max_length.to_le_bytes());
+            bytes.push(slot.char_types);
+            bytes.push(slot.fixed_output);
+        }
+
+        // each configuration's Motion, after everything older sheets hold
+        for password in passwords {
+            bytes.push(match password.structure_system.motion {
+                Motion::Float => 0,
+                Motion::Fixed => 1,
+            });
+        }
+        // and its Transform, after those
+        for password in passwords {
+            bytes.push(match password.structure_system.transform {
+                Transform::V1 => 1,
+                Transform::V2 => 2,
+                Transform::V3 => 3,
+                Transform::V4 => 4,
+                Transform::V5 => 5,
+                Transform::V6 => 6,
+                Transform::V7 => 7,
+            });
+        }
+        // and how it was set up, after those
+        for password in passwords {
+            bytes.push(match password.setup_mode {
+                None => 0,
+                Some(SetupMode::Timed) => 1,
+                Some(SetupMode::Reproducible) => 2,
+            });
+        }
+        // and which re-key it is, of what, after those
+        for password in passwords {
+            bytes.push(password.structure_system.lineage);
+            text(&mut bytes, password.rekeyed_from.as_deref().unwrap_or(""));
+        }
+        bytes
     }
 
-    fn generate_direction(&self, seed: u64) -> Vec<f64> {
-        let mut direction = vec![0.0; self.dimensions];
-        let mut rng_state = seed;
+    fn deserialize(bytes: &[u8]) -> Result<(Vec<SavedPassword>, Vec<DomainSlot>), &'static str> {
+        let mut reader = RecoveryReader { bytes, offset: 0 };
+
+        let mut passwords = Vec::new();
+        for _ in 0..reader.u8()? {
+            let name = reader.text()?;
+            let description = reader.text()?;
+            let created_date = reader.u64()?;
+            let extra_chars_count = reader.u8()? as usize;
+            let normalization = Normalization::from_byte(reader.u8()?)
+                .ok_or("The recovery data holds an unknown normalization")?;
+            let dimensions = reader.u8()? as usize;
+            let coordinate_range = reader.u32()? as i32;
+            let seed = reader.u64()?;
+            let base_step_size = f64::from_bits(reader.u64()?);
+            let step_variance = f64::from_bits(reader.u64()?);
+
+            let mut character_set = Vec::new();
+            for _ in 0..reader.u16()? {
+                let start = reader.u32()?;
+                let len = reader.u32()?;
+                character_set.extend(start..start.saturating_add(len));
+            }
+            if dimensions == 0 || character_set.is_empty() {
+                return Err("The recovery data holds an empty configuration");
+            }
+            if dimensions > MAX_DIMENSIONS {
+                return Err("The recovery data holds a configuration with too many dimensions");
+            }
 
-        for i in 0..self.dimensions {
-            rng_state = rng_state
-                .wrapping_mul(6364136223846793005)
-                .wrapping_add(1442695040888963407);
-            direction[i] = (rng_state as f64 / u64::MAX as f64) * 2.0 - 1.0;
+            let mut structure_system = StructureSystem::new(seed, dimensions, coordinate_range);
+            structure_system.set_character_set(character_set);
+            structure_system.base_step_size = base_step_size;
+            structure_system.step_variance = step_variance;
+            structure_system.set_name(name.clone());
+
+            passwords.push(SavedPassword {
+                name,
+                description,
+                structure_system,
+                created_date,
+                extra_chars_count,
+                use_count: 0,
+                normalization,
+                // not on the sheet, the first verify-determinism stores one
+                fingerprint: None,
+                // read after the domains, see below
+                setup_mode: None,
+                rekeyed_from: None,
+            });
+        }
+
+        let mut slots = Vec::new();
+        for _ in 0..reader.u16()? {
+            let mut domain_hash = [0u8; 64];
+            domain_hash.copy_from_slice(reader.take(64)?);
+            slots.push(DomainSlot {
+                domain_hash,
+                counter: reader.u16()?,
+                max_length: reader.u16()?,
+                char_types: reader.u8()?,
+                fixed_output: reader.u8()?,
+            });
+        }
+
+        // sheets from before fixed-point motion end here, and moved in f64
+        for password in passwords.iter_mut() {
+            password.structure_system.motion = match reader.u8() {
+                Ok(0) | Err(_) => Motion::Float,
+                Ok(1) => Motion::Fixed,
+                Ok(_) => return Err("The recovery data holds an unknown kind of motion"),
+            };
         }
-
-        let magnitude: f64 = direction.iter().map(|x| x * x).sum::<f64>().sqrt();
-        if magnitude > 0.0 {
-            for value in &mut direction {
-                *value /= magnitude;
+        // and these before the second transform
+        for password in passwords.iter_mut() {
+            password.structure_system.transform = match reader.u8() {
+                Ok(1) | Err(_) => Transform::V1,
+                Ok(2) => Transform::V2,
+                Ok(3) => Transform::V3,
+                Ok(4) => Transform::V4,
+                Ok(5) => Transform::V5,
+                Ok(6) => Transform::V6,
+                Ok(7) => Transform::V7,
+                Ok(_) => return Err("The recovery data holds an unknown transform"),
+            };
+        }
+        // and these before setup modes were recorded
+        for password in passwords.iter_mut() {
+            password.setup_mode = match reader.u8() {
+                Ok(0) | Err(_) => None,
+                Ok(1) => Some(SetupMode::Timed),
+                Ok(2) => Some(SetupMode::Reproducible),
+                Ok(_) => return Err("The recovery data holds an unknown setup mode"),
+            };
+        }
+        // and these before re-keys
+        for password in passwords.iter_mut() {
+            let Ok(lineage) = reader.u8() else {
+                break;
+            };
+            if lineage > MAX_LINEAGE {
+                return Err("The recovery data holds a configuration re-keyed too often");
             }
+            password.structure_system.lineage = lineage;
+            password.rekeyed_from = Some(reader.text()?).filter(|name| !name.is_empty());
         }
-
-        direction
+        Ok((passwords, slots))
     }
 
-    fn generate_distance(&self, seed: u64) -> f64 {
-        let mut rng_state = seed;
-        rng_state = rng_state
-            .wrapping_mul(6364136223846793005)
-            .wrapping_add(1442695040888963407);
-
-        let variance = (rng_state as f64 / u64::MAX as f64) * 2.0 - 1.0;
-        self.base_step_size + variance * self.step_variance
+    // magic, salt, iteration count, the encrypted payload, then a MAC over all of it
+    fn seal(payload: &[u8], passphrase: &[u8]) -> Vec<u8> {
+        let salt = random_bytes(Self::SALT_LEN);
+
+        // the sheet's cipher key, then its MAC key
+        let keys = stretch_passphrase(passphrase, &salt, Self::ITERATIONS, 64);
+        let mut sealed = Vec::with_capacity(payload.len() + 64);
+        sealed.extend_from_slice(Self::MAGIC);
+        sealed.extend_from_slice(&salt);
+        sealed.extend_from_slice(&Self::ITERATIONS.to_le_bytes());
+        let start = sealed.len();
+        sealed.extend_from_slice(payload);
+        recovery_keystream(&keys[..32], &mut sealed[start..]);
+
+        let mut mac = HmacSha256::new(&keys[32..]);
+        mac.update(&sealed);
+        sealed.extend_from_slice(&mac.finish());
+        sealed
     }
 
-    fn update_position(&mut self, direction: &Vec<f64>, distance: f64) {
-        for i in 0..self.dimensions {
-            let mut new_coord = self.current_position.coordinates[i] + direction[i] * distance;
-
-            let (min_bound, max_bound) = (self.structure_bounds.0[i], self.structure_bounds.1[i]);
-
-            if new_coord < min_bound {
-                new_coord = min_bound + (min_bound - new_coord);
-            } else if new_coord > max_bound {
-                new_coord = max_bound - (new_coord - max_bound);
-            }
+    fn open(sealed: &[u8], passphrase: &[u8]) -> io::Result<Wiped<Vec<u8>>> {
+        let header = Self::MAGIC.len() + Self::SALT_LEN + 4;
+        if sealed.len() < header + Self::MAC_LEN || &sealed[..4] != Self::MAGIC {
+            return Err(ErrorCode::RecoveryInvalid.error("This is not a Void Vault recovery sheet"));
+        }
+        let salt = &sealed[4..4 + Self::SALT_LEN];
+        let iterations = u32::from_le_bytes([
+            sealed[header - 4],
+            sealed[header - 3],
+            sealed[header - 2],
+            sealed[header - 1],
+        ]);
+        let (body, expected) = sealed.split_at(sealed.len() - Self::MAC_LEN);
 
-            self.current_position.coordinates[i] = new_coord;
+        let keys = stretch_passphrase(passphrase, salt, iterations, 64);
+        let mut mac = HmacSha256::new(&keys[32..]);
+        mac.update(body);
+        if !constant_time_eq(&mac.finish(), expected) {
+            return Err(ErrorCode::RecoveryInvalid
+                .error("The passphrase is wrong, or the sheet was copied wrongly"));
         }
 
-        let coord_sum: i64 = self
-            .current_position
-            .coordinates
-            .iter()
-            .map(|&coord| coord as i64)
-            .sum();
+        let mut payload = Wiped(body[header..].to_vec());
+        recovery_keystream(&keys[..32], &mut payload);
+        Ok(payload)
+    }
 
-        self.accumulated_path_memory = self.accumulated_path_memory.wrapping_add(coord_sum as u8);
+    // two base32 characters of SHA-256 over the line number and the line
+    fn line_check(number: usize, data: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{:03}", number).as_bytes());
+        hasher.update(data.as_bytes());
+        let digest = hasher.finish();
+        let bits = (digest[0] as usize) << 2 | (digest[1] as usize) >> 6;
+        [BASE32[bits >> 5], BASE32[bits & 31]]
+            .iter()
+            .map(|&b| b as char)
+            .collect()
     }
 
-    fn generate_output_from_path(
-        &self,
-        start: &ContinuousPosition,
-        direction: &Vec<f64>,
-        distance: f64,
-        extra_chars_count: usize,
-    ) -> Vec<u32> {
-        let mut output = Vec::new();
-        let total_chars = extra_chars_count + 1;
+    fn render(sealed: &[u8], summary: &str, qr: bool) -> io::Result<String> {
+        let data = base32_encode(sealed);
+        let lines: Vec<&str> = data
+            .as_bytes()
+            .chunks(Self::LINE_CHARS)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap_or(""))
+            .collect();
+
+        let mut sheet = String::new();
+        sheet.push_str("VOID VAULT RECOVERY SHEET\n\n");
+        sheet.push_str(summary);
+        sheet.push_str("\n\n");
+        sheet.push_str("Restore with: void_vault import-recovery <this file>\n");
+        sheet.push_str("and the passphrase chosen when it was made. Without that passphrase\n");
+        sheet.push_str("the sheet is useless, with it anyone can rebuild your passwords.\n");
+        sheet.push_str("To type it back in, copy each line: its number, six groups of five\n");
+        sheet.push_str("characters (A-Z and 2-7) and the two character check at the end.\n\n");
+
+        for (i, line) in lines.iter().enumerate() {
+            let number = i + 1;
+            let groups: Vec<&str> = line
+                .as_bytes()
+                .chunks(5)
+                .map(|group| std::str::from_utf8(group).unwrap_or(""))
+                .collect();
+            sheet.push_str(&format!(
+                "{:03}  {:<35}  {}\n",
+                number,
+                groups.join(" "),
+                Self::line_check(number, line)
+            ));
+        }
+        sheet.push_str(&format!("END {:03}\n", lines.len()));
 
-        for i in 0..total_chars {
-            let fraction = i as f64 / total_chars as f64;
-
-            let mut path_position = start.clone();
-            for dim in 0..self.dimensions {
-                path_position.coordinates[dim] += direction[dim] * distance * fraction;
+        if qr {
+            let payload = format!(
+                "{}:{}",
+                std::str::from_utf8(Self::MAGIC).unwrap_or(""),
+                data
+            );
+            match QrCode::encode(payload.as_bytes()) {
+                Some(code) => {
+                    sheet.push_str("\nThe same data as a QR code. Its text can be given to\n");
+                    sheet.push_str("import-recovery in place of the lines above:\n\n");
+                    sheet.push_str(&code.render());
+                }
+                None => diag(
+                    Diag::Warning,
+                    format_args!(
+                        "Warning: Too much data for a QR code, the sheet has the lines only"
+                    ),
+                ),
             }
-
-            let char_seed = path_position.hash_position(self.original_seed);
-            let base_char_idx = (char_seed % self.character_set.len() as u64) as usize;
-
-            let final_char = self.apply_path_memory_to_character(base_char_idx);
-            output.push(final_char);
-        }
-
-        output
-    }
-
-    fn apply_path_memory_to_character(&self, base_char_index: usize) -> u32 {
-        if self.character_set.is_empty() {
-            return 0;
         }
-
-        let final_index = if self.accumulated_path_memory % 2 == 0 {
-            (base_char_index + 1) % self.character_set.len()
-        } else {
-            (base_char_index + self.character_set.len() - 1) % self.character_set.len()
-        };
-
-        self.character_set[final_index]
+        Ok(sheet)
     }
 
-    // Scrambles domain name using geometric structure
-    // Returns deterministic 64-byte identifier
-    fn hash_domain(&mut self, domain: &str) -> [u8; 64] {
-        let saved_position = self.current_position.clone();
-        let saved_seed = self.original_seed;
-        let saved_memory = self.accumulated_path_memory;
-
-        const DOMAIN_HASH_SEED: u64 = 0x444F4D41494E5F48;
-        self.original_seed = DOMAIN_HASH_SEED;
-        self.full_reset();
-
-        let mut hash_bytes = Vec::with_capacity(64);
-
-        for ch in domain.chars() {
-            let keycode = ch as u32;
-
-            let output_codes = self.transform_char(keycode, 7);
-
-            for &code in &output_codes {
-                hash_bytes.push((code % 256) as u8);
-
-                if hash_bytes.len() >= 64 {
-                    break;
-                }
+    // the sheet's lines, or the text of its QR code (VVR1: then the data), back to
+    // bytes. a line whose check fails, or a missing one, is named by its number
+    fn parse(text: &str) -> io::Result<Vec<u8>> {
+        let magic = std::str::from_utf8(Self::MAGIC).unwrap_or("");
+        if let Some(start) = text.find(&format!("{}:", magic)) {
+            let data: String = text[start + magic.len() + 1..]
+                .chars()
+                .filter(|ch| !ch.is_whitespace())
+                .collect();
+            let bytes = base32_decode(&data).ok_or_else(|| {
+                ErrorCode::RecoveryInvalid
+                    .error("The scanned text has characters base32 doesn't use")
+            })?;
+            return Ok(bytes);
+        }
+
+        let mut data = String::new();
+        let mut expected_lines = None;
+        let mut next = 1;
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            let Some(first) = words.next() else {
+                continue;
+            };
+            if first == "END" {
+                expected_lines = words.next().and_then(|n| n.parse::<usize>().ok());
+                break;
             }
+            let Ok(number) = first.parse::<usize>() else {
+                continue;
+            };
 
-            if hash_bytes.len() >= 64 {
-                break;
+            let mut groups: Vec<&str> = words.collect();
+            let check = groups.pop().unwrap_or("");
+            let line_data: String = groups.concat().to_ascii_uppercase();
+            if number != next {
+                return Err(ErrorCode::RecoveryInvalid.error(format!("Line {} is missing", next)));
             }
+            if !check.eq_ignore_ascii_case(&Self::line_check(number, &line_data)) {
+                return Err(ErrorCode::RecoveryInvalid.error(format!(
+                    "Line {} doesn't match its check, copy it again",
+                    number
+                )));
+            }
+            data.push_str(&line_data);
+            next += 1;
         }
 
-        // Pad if domain was very short (e.g., "a.co")
-        while hash_bytes.len() < 64 {
-            let padding_codes = self.transform_char(0, 7);
-            for &code in &padding_codes {
-                hash_bytes.push((code % 256) as u8);
-                if hash_bytes.len() >= 64 {
-                    break;
-                }
+        match expected_lines {
+            Some(count) if count == next - 1 => {}
+            Some(count) => {
+                return Err(ErrorCode::RecoveryInvalid
+                    .error(format!("Line {} is missing, the sheet has {}", next, count)));
+            }
+            None => {
+                return Err(ErrorCode::RecoveryInvalid
+                    .error("The END line is missing, the sheet may be cut short"));
             }
         }
+        base32_decode(&data).ok_or_else(|| {
+            ErrorCode::RecoveryInvalid.error("The sheet has characters base32 doesn't use")
+        })
+    }
+}
 
-        self.original_seed = saved_seed;
-        self.current_position = saved_position;
-        self.accumulated_path_memory = saved_memory;
+// a file's contents, or a line from stdin, not echoed on a terminal. asked twice
+// with `confirm`: export-recovery's passphrase is the one thing the sheet can't
+// recover, and a forgotten unlock secret locks the vault
+fn read_secret(label: &str, secret_file: Option<&str>, confirm: bool) -> io::Result<Wiped<String>> {
+    if let Some(path) = secret_file {
+        let mut raw = Wiped(Vec::new());
+        File::open(path)?.read_to_end(&mut raw)?;
+        while raw.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+            raw.pop();
+        }
+        return Ok(Wiped(String::from_utf8_lossy(&raw).into_owned()));
+    }
 
-        let mut result = [0u8; 64];
-        result.copy_from_slice(&hash_bytes[..64]);
-        result
+    let mut stderr = io::stderr();
+    let secret = read_secret_line(&mut stderr, label)?;
+    if confirm {
+        let again = read_secret_line(&mut stderr, "Once more")?;
+        if !constant_time_eq(secret.as_bytes(), again.as_bytes()) {
+            return Err(ErrorCode::ConfirmMismatch.error("The two entries did not match"));
+        }
     }
+    Ok(secret)
+}
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+// a line from stdin after `label` on `prompt`, not echoed on a terminal
+fn read_secret_line(prompt: &mut dyn Write, label: &str) -> io::Result<Wiped<String>> {
+    let _raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::Cbreak)?;
+    write!(prompt, "{}: ", label)?;
+    prompt.flush()?;
+    let chars = read_io_line(&mut io::stdin(), &mut Utf8Decoder::new())?;
+    writeln!(prompt)?;
+    Ok(Wiped(
+        chars.iter().filter_map(|&c| char::from_u32(c)).collect(),
+    ))
+}
 
-        bytes.extend_from_slice(&(self.dimensions as u32).to_ne_bytes());
-        bytes.extend_from_slice(&self.coordinate_range.to_ne_bytes());
-        bytes.extend_from_slice(&self.original_seed.to_ne_bytes());
+// salts, from the randomly keyed std hasher
+fn random_bytes(len: usize) -> Vec<u8> {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
 
-        let name_bytes = self.name.as_bytes();
-        bytes.extend_from_slice(&(name_bytes.len() as u32).to_ne_bytes());
-        bytes.extend_from_slice(name_bytes);
+    let mut bytes = Vec::with_capacity(len.next_multiple_of(8));
+    while bytes.len() < len {
+        let random = RandomState::new().hash_one((std::process::id(), bytes.len()));
+        bytes.extend_from_slice(&random.to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
 
-        bytes.extend_from_slice(&(self.character_set.len() as u32).to_ne_bytes());
-        for &code in &self.character_set {
-            bytes.extend_from_slice(&code.to_ne_bytes());
-        }
+// the unlock secret is stretched with a salt of its own and nothing else, so its
+// verifier can't be turned into anything about a structure
+const UNLOCK_ROUNDS: u32 = 100_000;
+const MIN_UNLOCK_SECRET: usize = 4;
+// wrong secrets in a row before each further try waits 1s, then 2s, 4s ... capped
+// at about four minutes
+const UNLOCK_FREE_TRIES: u16 = 3;
+
+fn unlock_delay(failures: u16) -> std::time::Duration {
+    if failures < UNLOCK_FREE_TRIES {
+        return std::time::Duration::ZERO;
+    }
+    std::time::Duration::from_secs(1 << (failures - UNLOCK_FREE_TRIES).min(8))
+}
 
-        bytes.extend_from_slice(&(self.active_points.len() as u32).to_ne_bytes());
-        for point in &self.active_points {
-            let point_bytes = point.to_bytes();
-            bytes.extend(point_bytes);
-        }
+fn unlock_verifier(secret: &[u8], salt: [u8; 16]) -> UnlockVerifier {
+    let stretched = stretch_passphrase(secret, &salt, UNLOCK_ROUNDS, 32);
+    let mut verifier = [0u8; 32];
+    verifier.copy_from_slice(&stretched);
+    UnlockVerifier {
+        salt,
+        verifier,
+        failures: 0,
+    }
+}
 
-        bytes.extend_from_slice(&(self.char_to_point.len() as u32).to_ne_bytes());
-        for (&key, point) in &self.char_to_point {
-            bytes.extend_from_slice(&key.to_ne_bytes());
-            let point_bytes = point.to_bytes();
-            bytes.extend(point_bytes);
-        }
+fn unlock_matches(unlock: &UnlockVerifier, secret: &[u8]) -> bool {
+    let mut candidate = unlock_verifier(secret, unlock.salt);
+    let matches = constant_time_eq(&candidate.verifier, &unlock.verifier);
+    zero_memory(&mut candidate.verifier);
+    matches
+}
 
-        bytes.extend_from_slice(&self.base_step_size.to_ne_bytes());
-        bytes.extend_from_slice(&self.step_variance.to_ne_bytes());
+// the vault's unlock secret and the decoy's, which opens it under duress. each is in
+// its own table, so the one of the two not loaded is read from the vault
+fn unlock_verifiers(
+    exe_path: &std::path::Path,
+) -> io::Result<(Option<UnlockVerifier>, Option<UnlockVerifier>)> {
+    let mut other = DomainTable::new();
+    if TableRegion::active() == TableRegion::Real {
+        other.load_region(exe_path, TableRegion::Decoy)?;
+        let unlock = DomainTable::shared().get_unlock();
+        Ok((unlock, other.get_unlock()))
+    } else {
+        other.load_region(exe_path, TableRegion::Real)?;
+        let decoy = DomainTable::shared().get_unlock();
+        Ok((other.get_unlock(), decoy))
+    }
+}
 
-        bytes.extend_from_slice(&self.accumulated_path_memory.to_ne_bytes());
+// Some(false) for the unlock secret, Some(true) for the decoy's, None for neither.
+// two secrets are always stretched, so the time taken doesn't tell them apart
+fn opened_by(
+    unlock: &UnlockVerifier,
+    decoy: Option<&UnlockVerifier>,
+    secret: &[u8],
+) -> Option<bool> {
+    let right = unlock_matches(unlock, secret);
+    let duress = unlock_matches(decoy.unwrap_or(unlock), secret) && decoy.is_some();
+    match (right, duress) {
+        (true, _) => Some(false),
+        (false, true) => Some(true),
+        (false, false) => None,
+    }
+}
 
-        bytes
+// the decoy's unlock secret was given: from here on the process works on the decoy's
+// table, and the configurations it loads are the decoy standing in
+fn enter_duress(exe_path: &std::path::Path) -> io::Result<()> {
+    if !DURESS.swap(true, Ordering::SeqCst) {
+        DomainTable::shared().load_from_binary(exe_path)?;
     }
+    Ok(())
+}
 
-    fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
-        if bytes.len() < 16 {
-            return Err("Invalid data: not enough bytes for StructureSystem");
-        }
+// changes `region`'s table and, when `change` says it did, writes it to the vault.
+// that's the loaded table when it's the region's, so it doesn't go stale, else one
+// read for the purpose. the caller holds the vault lock
+fn change_table(
+    exe_path: &std::path::Path,
+    region: TableRegion,
+    change: impl FnOnce(&mut DomainTable) -> Result<bool, DomainTableError>,
+) -> io::Result<()> {
+    let mut shared;
+    let mut other = DomainTable::new();
+    let table: &mut DomainTable = if region == TableRegion::active() {
+        shared = DomainTable::shared();
+        &mut shared
+    } else {
+        other.load_region(exe_path, region)?;
+        &mut other
+    };
+    if change(table).map_err(|e| ErrorCode::TableFull.error(e.to_string()))? {
+        table.save_region(exe_path, region)?;
+    }
+    Ok(())
+}
 
-        let mut offset = 0;
+// clears the count of wrong secrets after a right one, or adds one, in the real
+// table and the vault. the caller holds the vault lock
+fn count_unlock_attempt(exe_path: &std::path::Path, right: bool) -> io::Result<()> {
+    change_table(exe_path, TableRegion::Real, |table| {
+        let Some(mut unlock) = table.get_unlock() else {
+            return Ok(false);
+        };
+        let failures = if right {
+            0
+        } else {
+            unlock.failures.saturating_add(1)
+        };
+        if failures == unlock.failures {
+            return Ok(false);
+        }
+        unlock.failures = failures;
+        table.set_unlock(Some(unlock)).map(|_| true)
+    })
+}
 
-        let dimensions = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
+// set once require_unlock has been given a right secret, which does for the process
+static VAULT_UNLOCKED: AtomicBool = AtomicBool::new(false);
 
-        let coordinate_range = i32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]);
-        offset += 4;
-
-        let original_seed = u64::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-            bytes[offset + 4],
-            bytes[offset + 5],
-            bytes[offset + 6],
-            bytes[offset + 7],
-        ]);
-        offset += 8;
+// before term, io and the interactive session generate anything: the unlock secret,
+// when the vault has one, as the next line of stdin. a terminal gets the free tries
+// in a row, piped input one. every wrong one is counted in the vault, so starting
+// over doesn't reset the delay. the decoy's secret opens it just the same, under duress
+fn require_unlock(prompt: &mut dyn Write) -> io::Result<()> {
+    use std::io::IsTerminal;
 
-        if bytes.len() < offset + 4 {
-            return Err("Invalid data: not enough bytes for name data");
-        }
-        let name_len = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
+    if VAULT_UNLOCKED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let exe_path = vault_path()?;
+    DomainTable::shared().load_from_binary(&exe_path)?;
+    let tries = if io::stdin().is_terminal() {
+        UNLOCK_FREE_TRIES
+    } else {
+        1
+    };
 
-        if bytes.len() < offset + name_len {
-            return Err("Invalid data: not enough bytes for nameu");
-        }
-        let name = match String::from_utf8(bytes[offset..offset + name_len].to_vec()) {
-            Ok(s) => s,
-            Err(_) => return Err("Invalid UTF-8 in name"),
+    for _ in 0..tries {
+        let (Some(unlock), decoy) = unlock_verifiers(&exe_path)? else {
+            return Ok(());
         };
-        offset += name_len;
-
-        if bytes.len() < offset + 4 {
-            return Err("Invalid data: not enough bytes for character set you selected for");
-        }
-        let char_set_len = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
-
-        let mut character_set = Vec::with_capacity(char_set_len);
-        for _ in 0..char_set_len {
-            if bytes.len() < offset + 4 {
-                return Err("Invalid data: not enough bytes for character INI");
-            }
-            let code = u32::from_ne_bytes([
-                bytes[offset],
-                bytes[offset + 1],
-                bytes[offset + 2],
-                bytes[offset + 3],
-            ]);
-            character_set.push(code);
-            offset += 4;
-        }
-
-        if bytes.len() < offset + 4 {
-            return Err("Invalid data: not enough bytes for active points count");
+        let delay = unlock_delay(unlock.failures);
+        if !delay.is_zero() {
+            writeln!(
+                prompt,
+                "{} wrong unlock secrets in a row, waiting {}s",
+                unlock.failures,
+                delay.as_secs()
+            )?;
+            thread::sleep(delay);
+        }
+
+        let secret = read_secret_line(prompt, "Unlock secret")?;
+        let opened = opened_by(&unlock, decoy.as_ref(), secret.as_bytes());
+        let counted =
+            InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT).and_then(|_lock| {
+                DomainTable::shared().load_from_binary(&exe_path)?;
+                count_unlock_attempt(&exe_path, opened.is_some())
+            });
+        if let Err(e) = counted {
+            log(
+                LogLevel::Warn,
+                format_args!("unlock: attempt not counted: {}", e),
+            );
         }
-        let active_points_count = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
-
-        let mut active_points = HashSet::new();
-        for _ in 0..active_points_count {
-            if offset >= bytes.len() {
-                return Err("Invalid data: not enough bytes for active point Z");
-            }
-            match StructurePoint::from_bytes(&bytes[offset..]) {
-                Ok((point, bytes_read)) => {
-                    active_points.insert(point);
-                    offset += bytes_read;
-                }
-                Err(e) => return Err(e),
+        if let Some(duress) = opened {
+            if duress {
+                enter_duress(&exe_path)?;
             }
+            VAULT_UNLOCKED.store(true, Ordering::SeqCst);
+            return Ok(());
         }
+        writeln!(prompt, "Wrong unlock secret")?;
+    }
+    Err(ErrorCode::VaultLocked.error("The unlock secret was wrong"))
+}
 
-        if bytes.len() < offset + 4 {
-            return Err("Invalid data: not enough bytes for char_to_point count");
-        }
-        let mapping_count = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
+// the current unlock secret, before anything that takes it changes the vault, counted
+// like any other try. only the unlock secret itself will do, not the decoy's
+fn require_current_unlock(
+    exe_path: &std::path::Path,
+    unlock: &UnlockVerifier,
+    secret_file: Option<&str>,
+) -> io::Result<()> {
+    let delay = unlock_delay(unlock.failures);
+    if !delay.is_zero() {
+        diag(
+            Diag::Note,
+            format_args!(
+                "{} wrong unlock secrets in a row, waiting {}s",
+                unlock.failures,
+                delay.as_secs()
+            ),
+        );
+        thread::sleep(delay);
+    }
+    let current = read_secret("Current unlock secret", secret_file, false)?;
+    let right = unlock_matches(unlock, current.as_bytes());
+    count_unlock_attempt(exe_path, right)?;
+    if !right {
+        return Err(ErrorCode::VaultLocked.error("The unlock secret was wrong"));
+    }
+    Ok(())
+}
 
-        let mut char_to_point = HashMap::new();
-        for _ in 0..mapping_count {
-            if bytes.len() < offset + 4 {
-                return Err("Invalid data: not enough bytes for keycode");
-            }
-            let keycode = u32::from_ne_bytes([
-                bytes[offset],
-                bytes[offset + 1],
-                bytes[offset + 2],
-                bytes[offset + 3],
-            ]);
-            offset += 4;
+// set-unlock and remove-unlock, with --decoy the decoy's secret. changing or removing
+// an unlock secret takes the current one first, and the decoy's only goes with one
+fn run_set_unlock(set: bool, decoy: bool, secret_file: Option<&str>) -> io::Result<()> {
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    DomainTable::shared().load_from_binary(&exe_path)?;
+
+    let (stored_unlock, stored_decoy) = unlock_verifiers(&exe_path)?;
+    let changing = if decoy { stored_decoy } else { stored_unlock };
+    match stored_unlock {
+        // with --secret-file, the file holds the current secret and stdin the new one
+        Some(unlock) => require_current_unlock(&exe_path, &unlock, secret_file)?,
+        None if decoy => {
+            return Err(ErrorCode::BadRequest
+                .error("The decoy's unlock secret needs an unlock secret, set that first"));
+        }
+        None => {}
+    }
+    if !set && changing.is_none() {
+        return Err(ErrorCode::BadRequest.error("The vault has no unlock secret to remove"));
+    }
 
-            if offset >= bytes.len() {
-                return Err("Invalid data: not enough bytes for point");
-            }
-            match StructurePoint::from_bytes(&bytes[offset..]) {
-                Ok((point, bytes_read)) => {
-                    char_to_point.insert(keycode, point);
-                    offset += bytes_read;
-                }
-                Err(e) => return Err(e),
-            }
+    let unlock = if set {
+        let secret_file = secret_file.filter(|_| stored_unlock.is_none());
+        let secret = read_secret("New unlock secret", secret_file, secret_file.is_none())?;
+        if secret.chars().count() < MIN_UNLOCK_SECRET {
+            return Err(ErrorCode::BadRequest.error(format!(
+                "The unlock secret needs at least {} characters",
+                MIN_UNLOCK_SECRET
+            )));
+        }
+        // one secret can't open both
+        let other = if decoy { stored_unlock } else { stored_decoy };
+        if other.is_some_and(|other| unlock_matches(&other, secret.as_bytes())) {
+            return Err(
+                ErrorCode::BadRequest.error("The unlock secret and the decoy's have to differ")
+            );
         }
+        let mut salt = [0u8; 16];
+        salt.copy_from_slice(&random_bytes(16));
+        Some(unlock_verifier(secret.as_bytes(), salt))
+    } else {
+        None
+    };
+    let region = if decoy {
+        TableRegion::Decoy
+    } else {
+        TableRegion::Real
+    };
+    change_table(&exe_path, region, |table| {
+        table.set_unlock(unlock).map(|_| true)
+    })?;
+    // without the unlock secret there is nothing for the decoy's to stand in for
+    if !set && !decoy && stored_decoy.is_some() {
+        change_table(&exe_path, TableRegion::Decoy, |table| {
+            table.set_unlock(None).map(|_| true)
+        })?;
+    }
 
-        let (base_step_size, step_variance) = if bytes.len() >= offset + 16 {
-            let base_step = f64::from_ne_bytes([
-                bytes[offset],
-                bytes[offset + 1],
-                bytes[offset + 2],
-                bytes[offset + 3],
-                bytes[offset + 4],
-                bytes[offset + 5],
-                bytes[offset + 6],
-                bytes[offset + 7],
-            ]);
-            offset += 8;
-
-            let step_var = f64::from_ne_bytes([
-                bytes[offset],
-                bytes[offset + 1],
-                bytes[offset + 2],
-                bytes[offset + 3],
-                bytes[offset + 4],
-                bytes[offset + 5],
-                bytes[offset + 6],
-                bytes[offset + 7],
-            ]);
-            offset += 8;
-            (base_step, step_var)
-        } else {
-            (3.0, 2.0)
-        };
+    let status = if set { "set" } else { "removed" };
+    if json_output() {
+        println!("{{\"unlock\":\"{}\",\"status\":\"success\"}}", status);
+    } else {
+        println!("Unlock secret {}", status);
+    }
+    Ok(())
+}
 
-        let accumulated_path_memory = if bytes.len() >= offset + 1 {
-            bytes[offset]
-        } else {
-            0
-        };
+// what the startup check found: None for a vault never sealed, else whether its
+// code is still what it was sealed with. json-io reports it with VERSION
+static CODE_SEAL: std::sync::OnceLock<Option<bool>> = std::sync::OnceLock::new();
+
+// at startup, against the real table whatever the mode. a vault whose code changed
+// still works, export-recovery included, but says so loudly every time
+fn check_code_seal(exe_path: &std::path::Path) {
+    let mut table = Box::new(DomainTable::new());
+    let checked = table
+        .load_region(exe_path, TableRegion::Real)
+        .and_then(|_| table.check_code_seal(exe_path));
+    let sealed = match checked {
+        Ok(sealed) => sealed,
+        Err(e) => {
+            log(
+                LogLevel::Warn,
+                format_args!("integrity: code seal not checked: {}", e),
+            );
+            None
+        }
+    };
+    if sealed == Some(false) {
+        diag(
+            Diag::Warning,
+            format_args!(
+                "WARNING: THE CODE OF {} HAS CHANGED since its vault was sealed. Unless you upgraded it, something else modified it: don't type your phrase into it. export-recovery still rescues the vault, and after an upgrade accept-new-binary trusts the new code",
+                exe_path.display()
+            ),
+        );
+    }
+    let _ = CODE_SEAL.set(sealed);
+}
 
-        Ok(StructureSystem {
-            dimensions,
-            active_points,
-            char_to_point,
-            coordinate_range,
-            original_seed,
-            name,
-            character_set,
-            current_position: ContinuousPosition::new(dimensions),
-            structure_bounds: (vec![-30.0; dimensions], vec![30.0; dimensions]),
-            base_step_size,
-            step_variance,
-            accumulated_path_memory,
-        })
+// seals the vault to the code it sits behind, see DomainTable::check_code_seal. at
+// the end of a setup only a vault never sealed is, accept-new-binary replaces the
+// seal. the caller holds the vault lock where there is one to hold
+fn seal_code(exe_path: &std::path::Path, replace: bool) -> io::Result<()> {
+    let Some(digest) = DomainTable::code_digest(exe_path)? else {
+        return Ok(());
+    };
+    // change_table saves the shared table whole, it must not hold a stale one
+    if TableRegion::active() == TableRegion::Real {
+        DomainTable::shared().load_from_binary(exe_path)?;
     }
+    change_table(exe_path, TableRegion::Real, |table| {
+        if !replace && table.code_seal().is_some() {
+            return Ok(false);
+        }
+        table.set_code_seal(Some(digest)).map(|_| true)
+    })
+}
 
-    fn set_character_set(&mut self, character_set: Vec<u32>) {
-        self.character_set = character_set;
+// accept-new-binary: trusts the code the vault now sits behind, after an upgrade.
+// with an unlock secret it takes that first
+fn run_accept_new_binary(secret_file: Option<&str>) -> io::Result<()> {
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    if let (Some(unlock), _) = unlock_verifiers(&exe_path)? {
+        require_current_unlock(&exe_path, &unlock, secret_file)?;
+    }
+    if DomainTable::code_digest(&exe_path)?.is_none() {
+        return Err(ErrorCode::NoConfiguration.error("This binary has no vault to seal yet"));
     }
+    seal_code(&exe_path, true)?;
 
-    fn generate_structure(&mut self, initial_password: &[char], keycodes: &[u32]) {
-        self.set_character_set(keycodes.to_vec());
+    if json_output() {
+        println!("{{\"status\":\"sealed\"}}");
+    } else {
+        println!("The vault now trusts the code of {}", exe_path.display());
+    }
+    Ok(())
+}
 
-        let center = StructurePoint::new(self.dimensions);
-        self.active_points.insert(center.clone());
+// set-decoy and remove-decoy: which configuration stands in for the others under
+// duress, and otherwise isn't listed. with an unlock secret it takes that first
+fn run_set_decoy(name: Option<&str>, secret_file: Option<&str>) -> io::Result<()> {
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    DomainTable::shared().load_from_binary(&exe_path)?;
 
-        for &keycode in keycodes {
-            let point = StructurePoint::from_seed(
-                self.original_seed ^ (keycode as u64),
-                self.dimensions,
-                self.coordinate_range,
+    if let (Some(unlock), _) = unlock_verifiers(&exe_path)? {
+        require_current_unlock(&exe_path, &unlock, secret_file)?;
+    }
+    if let Some(name) = name {
+        let names = BinaryStorageManager::open(exe_path.clone(), false, None)?.list_all();
+        if !names.iter().any(|(stored, _)| stored == name) {
+            return Err(
+                ErrorCode::UnknownAccount.error(format!("No configuration named '{}'", name))
             );
-
-            self.char_to_point.insert(keycode, point.clone());
         }
+        if names.len() < 2 {
+            return Err(ErrorCode::BadRequest
+                .error("The decoy needs another configuration to stand in for"));
+        }
+    }
+    change_table(&exe_path, TableRegion::Decoy, |table| {
+        table.set_decoy(name).map(|_| true)
+    })?;
 
-        if !initial_password.is_empty() {
-            let mut current_point = center.clone();
+    if json_output() {
+        let decoy = name.map_or("null".to_string(), |name| {
+            format!("\"{}\"", escape_json(name))
+        });
+        println!("{{\"decoy\":{},\"status\":\"success\"}}", decoy);
+    } else {
+        match name {
+            Some(name) => println!("Decoy set to {}", name),
+            None => println!("Decoy removed"),
+        }
+    }
+    Ok(())
+}
 
-            for (i, &ch) in initial_password.iter().enumerate() {
-                let ch_code = ch as u32;
+// overwrites and removes the .bak and .new copies beside the vault, which a store
+// only does for the backup it supersedes
+fn run_shred_backups() -> io::Result<()> {
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+
+    let mut bytes = 0;
+    let leftovers = shred::leftovers(&exe_path);
+    for (path, size) in &leftovers {
+        shred::shred_file(path).map_err(|e| {
+            ErrorCode::StorageFailure.error(format!("Can't shred {}: {}", path.display(), e))
+        })?;
+        bytes += size;
+    }
 
-                let point = if let Some(point) = self.char_to_point.get(&ch_code).cloned() {
-                    point
-                } else {
-                    let new_point = StructurePoint::from_seed(
-                        self.original_seed ^ (ch_code as u64),
-                        self.dimensions,
-                        self.coordinate_range,
-                    );
-                    self.char_to_point.insert(ch_code, new_point.clone());
-                    new_point
-                };
+    if json_output() {
+        println!(
+            "{{\"shredded\":{},\"bytes\":{},\"status\":\"success\"}}",
+            leftovers.len(),
+            bytes
+        );
+    } else if leftovers.is_empty() {
+        println!("No backups to shred");
+    } else {
+        println!("Shredded {} files, {} bytes", leftovers.len(), bytes);
+    }
+    Ok(())
+}
 
-                self.create_path(&current_point, &point);
+fn run_vault_stats() -> io::Result<()> {
+    let exe_path = vault_path()?;
+    let size = fs::metadata(&exe_path)?.len();
+    let leftovers = shred::leftovers(&exe_path);
+    let backup_bytes: u64 = leftovers.iter().map(|(_, size)| size).sum();
 
-                if i < initial_password.len() - 1 {
-                    self.create_structure_feature(&point, ch_code, i as u64);
-                }
+    if json_output() {
+        let backups: Vec<String> = leftovers
+            .iter()
+            .map(|(path, size)| {
+                format!(
+                    "{{\"path\":\"{}\",\"size\":{}}}",
+                    escape_json(&path.to_string_lossy()),
+                    size
+                )
+            })
+            .collect();
+        println!(
+            "{{\"vault\":\"{}\",\"size\":{},\"backups\":[{}],\"backup_count\":{},\"backup_bytes\":{}}}",
+            escape_json(&exe_path.to_string_lossy()),
+            size,
+            backups.join(","),
+            leftovers.len(),
+            backup_bytes
+        );
+        return Ok(());
+    }
 
-                current_point = point;
-            }
+    println!("Vault:   {} ({} bytes)", exe_path.display(), size);
+    println!("Backups: {} files, {} bytes", leftovers.len(), backup_bytes);
+    for (path, size) in &leftovers {
+        println!("  {}  {} bytes", path.display(), size);
+    }
+    if !leftovers.is_empty() && shred::copy_on_write(&exe_path) {
+        println!("On a copy-on-write filesystem, shredding them is best effort");
+    }
+    Ok(())
+}
 
-            println!("generated with {} events", self.active_points.len());
-        } else {
-            println!("No initial phrase provided, building on a basic one");
+// everything doctor checks, the vault's file and what only this process sees
+fn doctor_findings(vault: &std::path::Path) -> io::Result<Vec<doctor::Finding>> {
+    let mut findings = doctor::check_vault(vault)?;
+    findings.extend(doctor::check_manifests());
+    findings.extend(
+        permission_exposures()
+            .into_iter()
+            .map(|(path, exposure)| doctor::Finding::Exposed(path, exposure)),
+    );
+    if CODE_SEAL.get() == Some(&Some(false)) {
+        findings.push(doctor::Finding::CodeChanged(vault.to_path_buf()));
+    }
+    Ok(findings)
+}
 
-            self.create_basic_structure(keycodes);
+// doctor: every check, read only, as a numbered list with what to do about each.
+// --fix offers the fixes that are safe one at a time, then checks again. exits with
+// doctor::exit_code of what is left
+fn run_doctor(fix: bool) -> io::Result<()> {
+    let vault = vault_path()?;
+    let mut findings = doctor_findings(&vault)?;
+
+    let mut fixed = 0;
+    if fix && findings.iter().any(doctor::Finding::fixable) {
+        let executable = std::env::current_exe()?;
+        let _instance_lock = InstanceLock::acquire(&vault, InstanceLock::WRITE_WAIT)?;
+        for finding in findings.iter().filter(|f| f.fixable()) {
+            eprint!("{}\n{}? [y/N] ", finding.problem(), finding.suggestion());
+            io::stderr().flush()?;
+            let mut answer = String::new();
+            if read_line_interruptible(&mut answer)? == 0 {
+                break;
+            }
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                continue;
+            }
+            match finding.fix(&executable) {
+                Ok(()) => fixed += 1,
+                Err(e) => diag(Diag::Warning, format_args!("Warning: Not fixed: {}", e)),
+            }
         }
-
-        self.calculate_structure_bounds();
+        findings = doctor_findings(&vault)?;
     }
 
-    fn calculate_structure_bounds(&mut self) {
-        let mut min_coords = vec![f64::INFINITY; self.dimensions];
-        let mut max_coords = vec![f64::NEG_INFINITY; self.dimensions];
-
-        for point in &self.active_points {
-            for i in 0..self.dimensions {
-                min_coords[i] = min_coords[i].min(point.coordinates[i] as f64);
-                max_coords[i] = max_coords[i].max(point.coordinates[i] as f64);
+    let locked = InstanceLock::is_held(&vault);
+    let code = doctor::exit_code(&findings);
+    if json_output() {
+        let entries: Vec<String> = findings
+            .iter()
+            .map(|finding| {
+                format!(
+                    "{{\"check\":\"{}\",\"bit\":{},\"problem\":\"{}\",\"fix\":\"{}\",\"automatic\":{}}}",
+                    finding.code(),
+                    finding.bit(),
+                    escape_json(&finding.problem()),
+                    escape_json(finding.suggestion()),
+                    finding.fixable()
+                )
+            })
+            .collect();
+        println!(
+            "{{\"vault\":\"{}\",\"locked\":{},\"memory_locked\":{},\"findings\":[{}],\"fixed\":{},\"exit_code\":{}}}",
+            escape_json(&vault.to_string_lossy()),
+            locked,
+            HARDENING.get().is_some_and(|hardening| hardening.memory_locked),
+            entries.join(","),
+            fixed,
+            code
+        );
+    } else {
+        println!("Vault: {}", vault.display());
+        println!(
+            "Lock:  {}",
+            if locked {
+                "held by another process (the browser host or a daemon)"
+            } else {
+                "free"
             }
+        );
+        if let Some(warning) = memory_lock_warning() {
+            println!("{}", warning);
         }
-
-        let padding = 10.0;
-        for i in 0..self.dimensions {
-            min_coords[i] -= padding;
-            max_coords[i] += padding;
+        if fixed > 0 {
+            println!("Fixed {} of what was found", fixed);
+        }
+        if findings.is_empty() {
+            println!("No problems found");
+        }
+        for (number, finding) in findings.iter().enumerate() {
+            println!("{}. {}", number + 1, finding.problem());
+            println!("   {}", finding.suggestion());
         }
-
-        self.structure_bounds = (min_coords, max_coords);
     }
 
-    fn create_path(&mut self, start: &StructurePoint, end: &StructurePoint) {
-        let steps = 5;
-        for step in 0..=steps {
-            let mut new_point = StructurePoint::new(self.dimensions);
-
-            for dim in 0..self.dimensions {
-                let start_coord = start.coordinates[dim];
-                let end_coord = end.coordinates[dim];
-                let coord = start_coord + (end_coord - start_coord) * step / steps;
-                new_point.coordinates[dim] = coord;
-            }
+    EXIT_CODE.store(code, Ordering::SeqCst);
+    Ok(())
+}
 
-            self.active_points.insert(new_point);
-        }
+fn run_export_recovery(path: &str, qr: bool, passphrase_file: Option<&str>) -> io::Result<()> {
+    let exe_path = vault_path()?;
+    DomainTable::shared().load_from_binary(&exe_path)?;
+    let password_manager = PasswordManager::new(false, None, true)?;
+    if password_manager.saved_passwords.is_empty() {
+        return Err(ErrorCode::NoConfiguration.error("There is no configuration to export"));
     }
-    // deterministic structure creation, to ensure complex high dimensional internal structures
-    fn create_structure_feature(&mut self, center: &StructurePoint, char_code: u32, index: u64) {
-        let feature_size = 10 + (char_code % 20) as usize;
 
-        let feature_type = (char_code + index as u32) % 5;
-        let feature_seed = self.original_seed ^ (char_code as u64) ^ index;
+    let slots: Vec<DomainSlot> = DomainTable::shared()
+        .slots()
+        .iter()
+        .filter(|s| !s.is_empty())
+        .copied()
+        .collect();
+    let payload = RecoverySheet::serialize(&password_manager.saved_passwords, &slots);
+
+    let passphrase = read_secret("Recovery passphrase", passphrase_file, true)?;
+    if passphrase.chars().count() < RecoverySheet::MIN_PASSPHRASE {
+        return Err(ErrorCode::BadRequest.error(format!(
+            "The passphrase needs at least {} characters",
+            RecoverySheet::MIN_PASSPHRASE
+        )));
+    }
+    let sealed = RecoverySheet::seal(&payload, passphrase.as_bytes());
+
+    let names: Vec<&str> = password_manager
+        .saved_passwords
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect();
+    let domains = slots.iter().filter(|s| !s.is_reserved()).count();
+    let summary = format!("Configurations: {}\nDomains: {}", names.join(", "), domains);
+    let sheet = RecoverySheet::render(&sealed, &summary, qr)?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .map_err(|e| ErrorCode::StorageFailure.error(format!("Can't create {}: {}", path, e)))?
+        .write_all(sheet.as_bytes())?;
+
+    let lines = base32_encode(&sealed)
+        .len()
+        .div_ceil(RecoverySheet::LINE_CHARS);
+    if json_output() {
+        println!(
+            "{{\"path\":\"{}\",\"configurations\":{},\"domains\":{},\"lines\":{},\"status\":\"exported\"}}",
+            escape_json(path),
+            names.len(),
+            domains,
+            lines
+        );
+    } else {
+        println!(
+            "Wrote the recovery sheet to {}: {} lines, {} configuration(s), {} domain(s)",
+            path,
+            lines,
+            names.len(),
+            domains
+        );
+        println!("Print it, keep it safe, then delete the file");
+    }
+    Ok(())
+}
 
-        match feature_type {
-            0 => self.create_deterministic_spike(center, feature_size, feature_seed),
-            1 => self.create_deterministic_blob(center, feature_size, feature_seed),
-            2 => self.create_deterministic_ring(center, feature_size, feature_seed),
-            3 => self.create_deterministic_spiral(center, feature_size, feature_seed),
-            _ => self.create_deterministic_scatter(center, feature_size, feature_seed),
+fn run_import_recovery(path: &str, passphrase_file: Option<&str>) -> io::Result<()> {
+    let mut text = String::new();
+    if path == "-" {
+        if passphrase_file.is_none() {
+            return Err(ErrorCode::Usage
+                .error("With the sheet on stdin the passphrase needs --passphrase-file"));
         }
+        io::stdin().read_to_string(&mut text)?;
+    } else {
+        File::open(path)?.read_to_string(&mut text)?;
+    }
+    let sealed = RecoverySheet::parse(&text)?;
+
+    let passphrase = read_secret("Recovery passphrase", passphrase_file, false)?;
+    let payload = RecoverySheet::open(&sealed, passphrase.as_bytes())?;
+    let (passwords, slots) =
+        RecoverySheet::deserialize(&payload).map_err(|e| ErrorCode::RecoveryInvalid.error(e))?;
+
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    let mut password_manager = PasswordManager::new(false, None, true)?;
+    if !password_manager.saved_passwords.is_empty() {
+        return Err(ErrorCode::AccountExists.error(
+            "This vault already has configurations, import into a fresh copy of the binary",
+        ));
     }
 
-    fn create_deterministic_spike(&mut self, center: &StructurePoint, size: usize, seed: u64) {
-        let mut direction = StructurePoint::new(self.dimensions);
-        let mut rng_state = seed;
-
-        for dim in 0..self.dimensions {
-            rng_state = rng_state
-                .wrapping_mul(6364136223846793005)
-                .wrapping_add(1442695040888963407);
-            direction.coordinates[dim] = ((rng_state % 3) as i32) - 1;
-        }
+    let names: Vec<String> = passwords.iter().map(|p| p.name.clone()).collect();
+    for password in passwords {
+        password_manager.add_password(password)?;
+    }
 
-        for i in 1..=size as i32 {
-            let mut point = center.clone();
-            for dim in 0..self.dimensions {
-                point.coordinates[dim] += direction.coordinates[dim] * i;
-            }
-            self.active_points.insert(point);
-        }
+    let mut table = DomainTable::shared();
+    table.load_from_binary(&exe_path)?;
+    table.slots_mut().fill(DomainSlot::EMPTY);
+    for (slot, restored) in table.slots_mut().iter_mut().zip(&slots) {
+        *slot = *restored;
+    }
+    // the sheet's seal is of the binary it was made from
+    if let Some(digest) = DomainTable::code_digest(&exe_path)? {
+        table
+            .set_code_seal(Some(digest))
+            .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
     }
+    table.mark_dirty();
+    table.save_to_binary(&exe_path)?;
+    drop(table);
 
-    fn create_deterministic_blob(&mut self, center: &StructurePoint, size: usize, seed: u64) {
-        let mut rng_state = seed;
+    let domains = slots.iter().filter(|s| !s.is_reserved()).count();
+    if json_output() {
+        let names: Vec<String> = names
+            .iter()
+            .map(|n| format!("\"{}\"", escape_json(n)))
+            .collect();
+        println!(
+            "{{\"configurations\":[{}],\"domains\":{},\"status\":\"imported\"}}",
+            names.join(","),
+            domains
+        );
+    } else {
+        println!("Restored {} and {} domain(s)", names.join(", "), domains);
+    }
+    Ok(())
+}
 
-        for _i in 0..size {
-            let mut point = center.clone();
+// domain is --use-domain-counter's, for the interactive session
+fn run_child_process(args: &[String], auto_exit: bool, domain: Option<&str>) -> io::Result<()> {
+    diag(Diag::Note, format_args!("Starting the Void Vault"));
 
-            for dim in 0..self.dimensions {
-                rng_state = rng_state
-                    .wrapping_mul(6364136223846793005)
-                    .wrapping_add(1442695040888963407);
-                let offset = (rng_state % 5) as i32 - 2;
-                point.coordinates[dim] += offset;
+    // without --ipc we were started by hand and have no parent to talk to
+    let channel = match args.iter().position(|arg| arg == "--ipc") {
+        Some(pos) if pos + 1 < args.len() => match ProcessChannel::connect(&args[pos + 1]) {
+            Ok(channel) => Some(Arc::new(Mutex::new(channel))),
+            Err(e) => {
+                diag(
+                    Diag::Warning,
+                    format_args!("Failed to reach the parent process: {}", e),
+                );
+                None
             }
+        },
+        _ => None,
+    };
 
-            self.active_points.insert(point);
+    let send_to_parent = |message: ProcessMessage| -> io::Result<()> {
+        if let Some(channel) = &channel {
+            channel
+                .lock()
+                .map_err(|_| io::Error::other("Process channel lock poisoned"))?
+                .send(&message)?;
         }
-    }
-
-    fn create_deterministic_ring(&mut self, center: &StructurePoint, size: usize, seed: u64) {
-        let radius = (size / 3) as i32;
-        let mut rng_state = seed;
-
-        for i in 0..size {
-            let angle = (i as f64 / size as f64) * 2.0 * std::f64::consts::PI;
-            let mut point = center.clone();
-
-            let dims = self.dimensions.min(3);
-
-            if dims >= 2 {
-                point.coordinates[0] += (radius as f64 * angle.cos()) as i32;
-                point.coordinates[1] += (radius as f64 * angle.sin()) as i32;
+        Ok(())
+    };
 
-                if dims >= 3 {
-                    rng_state = rng_state
-                        .wrapping_mul(6364136223846793005)
-                        .wrapping_add(1442695040888963407);
-                    point.coordinates[2] += (rng_state % 5) as i32 - 2;
-                }
-            }
+    send_to_parent(ProcessMessage::ChildReady).unwrap_or_else(|_| {
+        diag(
+            Diag::Warning,
+            format_args!("Failed to say it is ready to be a parent"),
+        );
+    });
+
+    // an unreadable vault is not an empty one. offering setup here would have the
+    // user build a second vault, or believe the first one is gone
+    let mut password_manager =
+        PasswordManager::new(false, channel.clone(), false).map_err(|e| {
+            ErrorCode::StorageFailure.error(format!(
+                "Could not read the configurations stored in this binary: {}. Nothing was changed",
+                e
+            ))
+        })?;
 
-            self.active_points.insert(point);
+    if args.iter().any(|arg| arg == "--setup") {
+        let target = setup_target(&password_manager, args)?;
+        let geometry = Geometry::from_args(args)?;
+        let pool = Pool::from_args(args)?;
+        let reproducible = args.iter().any(|arg| arg == "--reproducible");
+        run_simple_setup(
+            &mut password_manager,
+            auto_exit,
+            target,
+            &geometry,
+            &pool,
+            reproducible,
+        )?;
+        if !auto_exit {
+            run_interactive_mode(&mut password_manager, domain)?;
         }
+    } else if password_manager.saved_passwords.is_empty() {
+        return Err(ErrorCode::NoConfiguration.error(
+            "No configuration found in this binary. Run it with setup (or --setup) to create one",
+        ));
+    } else if !auto_exit {
+        run_interactive_mode(&mut password_manager, domain)?;
     }
 
-    fn create_deterministic_spiral(&mut self, center: &StructurePoint, size: usize, seed: u64) {
-        let mut rng_state = seed;
-
-        for i in 1..=size {
-            let angle = (i as f64 / 4.0) * std::f64::consts::PI;
-            let radius = i as i32 / 2;
-            let mut point = center.clone();
+    send_to_parent(ProcessMessage::ShutdownChild).unwrap_or_else(|_| {
+        diag(
+            Diag::Warning,
+            format_args!("Failed to suggest to the parent to abort itself"),
+        );
+    });
 
-            let dims = self.dimensions.min(3);
+    diag(Diag::Note, format_args!("Exiting program."));
+    Ok(())
+}
 
-            if dims >= 2 {
-                point.coordinates[0] += (radius as f64 * angle.cos()) as i32;
-                point.coordinates[1] += (radius as f64 * angle.sin()) as i32;
+// the interactive session's commands. each goes alone on a line, as :exit, :lock,
+// :switch <name>, :use [<domain>] or :domains, or without the colon after Enter on
+// an empty line. anything else, colon or not, is a phrase
+enum SessionCommand {
+    Exit,
+    Lock,
+    Switch(String),
+    // the domain whose counter phrases start at, none for the origin
+    Use(Option<String>),
+    Domains,
+}
 
-                if dims >= 3 {
-                    rng_state = rng_state
-                        .wrapping_mul(6364136223846793005)
-                        .wrapping_add(1442695040888963407);
-                    let z_offset = (rng_state % 3) as i32 + (i as i32 / 3);
-                    point.coordinates[2] += z_offset;
-                }
+impl SessionCommand {
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim().split_once(' ') {
+            Some(("switch", name)) if !name.trim().is_empty() => {
+                Some(SessionCommand::Switch(name.trim().to_string()))
             }
-
-            for dim in 3..self.dimensions {
-                rng_state = rng_state
-                    .wrapping_mul(6364136223846793005)
-                    .wrapping_add(1442695040888963407);
-                let offset = (rng_state % 7) as i32 - 3;
-                point.coordinates[dim] += offset;
+            Some(("use", domain)) if !domain.trim().is_empty() => {
+                Some(SessionCommand::Use(Some(domain.trim().to_string())))
             }
-
-            self.active_points.insert(point);
+            Some(_) => None,
+            None => match line.trim() {
+                "exit" | "quit" => Some(SessionCommand::Exit),
+                "lock" => Some(SessionCommand::Lock),
+                "use" => Some(SessionCommand::Use(None)),
+                "domains" => Some(SessionCommand::Domains),
+                _ => None,
+            },
         }
     }
+}
 
-    fn create_deterministic_scatter(&mut self, center: &StructurePoint, size: usize, seed: u64) {
-        let mut rng_state = seed;
+// :lock. the screen is cleared and nothing goes on until Enter. false when input
+// ended instead
+fn lock_session() -> io::Result<bool> {
+    print!("\x1B[2J\x1B[H");
+    println!("Locked. Press Enter to resume.");
+    io::stdout().flush()?;
 
-        for _i in 0..size {
-            let mut point = center.clone();
+    let _raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::Cbreak)?;
+    let mut buffer = [0u8; 1];
+    loop {
+        match io::stdin().read(&mut buffer) {
+            Ok(0) => return Ok(false),
+            Ok(_) if buffer[0] == b'\n' || buffer[0] == b'\r' => return Ok(true),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => check_shutdown()?,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// domain starts every phrase at its counter, as --use-domain-counter does in term
+// and io, until :use changes it
+fn run_interactive_mode(
+    password_manager: &mut PasswordManager,
+    domain: Option<&str>,
+) -> io::Result<()> {
+    // toggled with Tab, and kept from one phrase to the next
+    let mut hidden = false;
+    let mut domain = domain.map(str::to_string);
+
+    loop {
+        println!("\n=== VOID VAULT ===");
+        let (structure_name, _description, _extra_chars) =
+            if let Some(idx) = password_manager.active_structure_idx {
+                if idx < password_manager.saved_passwords.len() {
+                    let structure = &password_manager.saved_passwords[idx];
+                    (
+                        structure.name.clone(),
+                        structure.description.clone(),
+                        structure.extra_chars_count,
+                    )
+                } else {
+                    (
+                        "No active password configuration".to_string(),
+                        "".to_string(),
+                        0,
+                    )
+                }
+            } else {
+                (
+                    "No active password configuration".to_string(),
+                    "".to_string(),
+                    0,
+                )
+            };
 
-            for dim in 0..self.dimensions {
-                rng_state = rng_state
-                    .wrapping_mul(6364136223846793005)
-                    .wrapping_add(1442695040888963407);
-                let offset = (rng_state % 11) as i32 - 5;
-                point.coordinates[dim] += offset;
+        // read again for every phrase, so a counter changed meanwhile is the one used
+        let counter = match (&domain, password_manager.active_structure_idx) {
+            (Some(domain), Some(idx)) if idx < password_manager.saved_passwords.len() => {
+                DomainTable::shared().load_from_binary(&vault_path()?)?;
+                let structure = &mut password_manager.saved_passwords[idx].structure_system;
+                Some(activate_domain_session(structure, domain, false).0)
+            }
+            _ => {
+                unsafe {
+                    (*std::ptr::addr_of_mut!(SESSION)).scrub();
+                }
+                None
             }
+        };
 
-            self.active_points.insert(point);
-        }
-    }
+        match (&domain, counter) {
+            (Some(domain), Some(counter)) => println!(
+                "Active configuration: {}, at {} v{}",
+                structure_name, domain, counter
+            ),
+            _ => println!("Active configuration: {}", structure_name),
+        }
+        println!("\nEnter your password phrase. Tab hides or shows the generated password.");
+        println!("Commands go alone on a line: :exit, :lock, :switch <name>, :use [<domain>],");
+        println!(":domains (or press Enter on an empty line, then type the command).");
+
+        // the same pipeline as term, so a phrase gives what it gives there
+        let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+        let mut decoder = Utf8Decoder::new();
+        let mut current = Wiped(String::new());
+        // what was typed, only to recognize a command
+        let mut typed = Wiped(String::new());
+        let mut command: Option<SessionCommand> = None;
+        let mut command_prompt = false;
+        let mut quit = false;
 
-    fn create_basic_structure(&mut self, keycodes: &[u32]) {
-        for &keycode in keycodes {
-            let point = StructurePoint::from_seed(
-                self.original_seed ^ keycode as u64,
-                self.dimensions,
-                self.coordinate_range,
-            );
-            self.char_to_point.insert(keycode, point.clone());
+        if let Some(idx) = password_manager.active_structure_idx {
+            if idx < password_manager.saved_passwords.len() {
+                let saved_password = &mut password_manager.saved_passwords[idx];
+                restart_session(session(), &mut saved_password.structure_system);
 
-            self.active_points.insert(point.clone());
+                // keys one at a time and unechoed, so the phrase never shows on screen
+                let raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::CbreakNonBlocking)?;
 
-            let feature_seed = self.original_seed ^ (keycode as u64);
-            let feature_type = feature_seed % 5;
+                println!("\nGenerated password:");
 
-            match feature_type {
-                0 => self.create_deterministic_spike(&point, 5, feature_seed),
-                1 => self.create_deterministic_blob(&point, 6, feature_seed),
-                2 => self.create_deterministic_ring(&point, 7, feature_seed),
-                3 => self.create_deterministic_spiral(&point, 8, feature_seed),
-                _ => self.create_deterministic_scatter(&point, 5, feature_seed),
-            }
-        }
+                loop {
+                    let mut buffer = [0u8; 1];
+                    match read_key_or_idle(&mut buffer, raw_mode.is_some(), &mut io::stdout()) {
+                        Ok(0) => {
+                            quit = true;
+                            break;
+                        }
+                        Ok(_) => {
+                            let byte = buffer[0];
 
-        let points: Vec<_> = self.char_to_point.values().cloned().collect();
-        let limit = points.len().min(30);
+                            if byte == b'\n' || byte == b'\r' {
+                                if typed.is_empty() {
+                                    command_prompt = true;
+                                } else {
+                                    command =
+                                        typed.strip_prefix(':').and_then(SessionCommand::parse);
+                                }
+                                break;
+                            }
 
-        for i in 0..limit {
-            if i + 1 < points.len() {
-                self.create_path(&points[i], &points[(i + 1) % points.len()]);
-            }
-        }
-    }
+                            // redraws what is there already, masked or not
+                            if byte == b'\t' {
+                                hidden = !hidden;
+                                print!("\r                                                            \r");
+                                if hidden {
+                                    print!("{}", masked_output(&current));
+                                } else {
+                                    print!("{}", current.as_str());
+                                }
+                                let _ = io::stdout().flush();
+                                continue;
+                            }
 
-    fn modify_with_timing(&mut self, keycode: u32, timing_ms: u64, timestamp: u64) {
-        if let Some(point) = self.char_to_point.get(&keycode).cloned() {
-            let mut timing_point = point.clone();
+                            // takes back the last character, like term and the browser
+                            if byte == 127 || byte == 8 {
+                                decoder.clear();
+                                if typed.pop().is_some() {
+                                    current = generation.undo(session(), saved_password);
+                                    print!("\r                                                            \r");
+                                    if hidden {
+                                        print!("{}", masked_output(&current));
+                                    } else {
+                                        print!("{}", current.as_str());
+                                    }
+                                    let _ = io::stdout().flush();
+                                }
+                                continue;
+                            }
 
-            let mod_seed = self.original_seed ^ keycode as u64 ^ timing_ms ^ (timestamp % 1000);
+                            if let Some(ch) = decoder.push(byte) {
+                                if !ch.is_control() {
+                                    typed.push(ch);
 
-            let is_forward = timing_ms % 2 == 0;
+                                    print!("\r                                                            \r");
+                                    let _ = io::stdout().flush();
 
-            for dim in 0..self.dimensions {
-                let modifier = ((timing_ms + dim as u64) % 5) as i32 - 2;
+                                    current = generation.step(session(), saved_password, ch as u32);
 
-                if is_forward {
-                    timing_point.coordinates[dim] += modifier;
-                } else {
-                    timing_point.coordinates[dim] -= modifier;
+                                    if hidden {
+                                        print!("{}", masked_output(&current));
+                                    } else {
+                                        print!("{}", current.as_str());
+                                    }
+                                    let _ = io::stdout().flush();
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            saved_password.structure_system.full_reset();
+                            return Err(e);
+                        }
+                    }
                 }
-            }
 
-            self.active_points.insert(timing_point.clone());
+                // a command generated like any phrase while it was typed, so take it
+                // back off
+                if command.is_some() {
+                    print!("\r                                                            \r");
+                }
+                println!();
+                if hidden && !current.is_empty() && command.is_none() && !quit {
+                    confirm_reveal(&current)?;
+                }
+                generation.clear();
+                current.wipe();
+                typed.wipe();
+
+                saved_password.structure_system.full_reset();
+            }
+        }
 
-            let feature_type = mod_seed % 3;
+        if quit {
+            return Ok(());
+        }
 
-            if feature_type == 0 {
-                self.create_deterministic_blob(&timing_point, 3, mod_seed);
-            } else if feature_type == 1 {
-                self.create_deterministic_spike(&timing_point, 3, mod_seed);
-            } else {
-                self.create_deterministic_scatter(&timing_point, 3, mod_seed);
+        if command_prompt {
+            print!(":");
+            io::stdout().flush()?;
+            let mut line = Wiped(String::new());
+            if read_line_interruptible(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                command = SessionCommand::parse(line.strip_prefix(':').unwrap_or(line));
+                if command.is_none() {
+                    println!(
+                        "Unknown command '{}', try exit, lock, switch <name>, use [<domain>] or domains",
+                        line
+                    );
+                }
             }
         }
-    }
 
-    fn set_name(&mut self, name: String) {
-        self.name = name;
+        match command {
+            Some(SessionCommand::Exit) => return Ok(()),
+            Some(SessionCommand::Lock) if !lock_session()? => return Ok(()),
+            Some(SessionCommand::Switch(name)) => {
+                match password_manager
+                    .saved_passwords
+                    .iter()
+                    .position(|p| p.name == name)
+                {
+                    Some(idx) => password_manager.active_structure_idx = Some(idx),
+                    None => println!("No configuration named '{}'", name),
+                }
+            }
+            Some(SessionCommand::Use(name)) => domain = name,
+            Some(SessionCommand::Domains) => {
+                if let Some(idx) = password_manager.active_structure_idx {
+                    run_domain_menu(password_manager, idx)?;
+                }
+            }
+            Some(SessionCommand::Lock) | None => {}
+        }
     }
 }
 
-#[derive(Clone)]
-struct SavedPassword {
-    name: String,
-    description: String,
-    structure_system: StructureSystem,
-    created_date: u64,
-    extra_chars_count: usize,
-}
+// :domains in the interactive session. holds the vault lock while open, so the
+// browser host can't write the table underneath it, and saves once on the way out
+fn run_domain_menu(password_manager: &mut PasswordManager, idx: usize) -> io::Result<()> {
+    let exe_path = vault_path()?;
+    let _instance_lock = match InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT) {
+        Ok(lock) => lock,
+        Err(e) => {
+            println!("Can't manage domains right now: {}", e);
+            return Ok(());
+        }
+    };
+    DomainTable::shared().load_from_binary(&exe_path)?;
 
-impl SavedPassword {
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    println!("Domain commands: list, get <domain>, set <domain> <n>, increment <domain>,");
+    println!("rollback <domain>, rules <domain> <max_length> <char_types> [no-confusables]");
+    println!(
+        "[starts-with-letter] [no-leading-symbol] [no-trailing-symbol], delete <domain>, done"
+    );
 
-        let name_bytes = self.name.as_bytes();
-        bytes.extend_from_slice(&(name_bytes.len() as u32).to_ne_bytes());
-        bytes.extend_from_slice(name_bytes);
+    loop {
+        print!("domains> ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if read_line_interruptible(&mut line)? == 0 {
+            println!();
+            break;
+        }
 
-        let desc_bytes = self.description.as_bytes();
-        bytes.extend_from_slice(&(desc_bytes.len() as u32).to_ne_bytes());
-        bytes.extend_from_slice(desc_bytes);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let structure = &mut password_manager.saved_passwords[idx].structure_system;
+        let counter = |value: &str| {
+            value
+                .parse::<u16>()
+                .map_err(|_| "Counter must be 0-65535".to_string())
+        };
 
-        bytes.extend_from_slice(&self.created_date.to_ne_bytes());
-        bytes.extend_from_slice(&(self.extra_chars_count as u32).to_ne_bytes());
+        let result: Result<String, String> = match words.as_slice() {
+            [] => continue,
+            ["done" | "exit" | "back"] => break,
+            ["list"] => {
+                DomainTable::shared().print_list();
+                continue;
+            }
+            ["get", domain] => {
+                let table = DomainTable::shared();
+                match table.get_counter(domain, structure) {
+                    Some(c) => {
+                        let (max_length, char_types) =
+                            table.get_rules(domain, structure).unwrap_or((0, 127));
+                        Ok(format!(
+                            "{}: v{}, max length {}, char types {}",
+                            domain, c, max_length, char_types
+                        ))
+                    }
+                    None => Err(format!("{} is not registered", domain)),
+                }
+            }
+            ["set", domain, value] => counter(value).and_then(|c| {
+                DomainTable::shared()
+                    .set_counter(domain, c, structure)
+                    .map(|_| format!("{}: v{}", domain, c))
+                    .map_err(|e| e.to_string())
+            }),
+            ["increment", domain] => DomainTable::shared()
+                .increment_counter(domain, structure)
+                .map(|c| format!("{}: v{}", domain, c))
+                .map_err(|e| e.to_string()),
+            ["rollback", domain] => {
+                let mut table = DomainTable::shared();
+                match table.get_counter(domain, structure) {
+                    Some(0) => Err(format!("{} is already at v0", domain)),
+                    Some(c) => table
+                        .set_counter(domain, c - 1, structure)
+                        .map(|_| format!("{}: v{}", domain, c - 1))
+                        .map_err(|e| e.to_string()),
+                    None => Err(format!("{} is not registered", domain)),
+                }
+            }
+            ["rules", domain, max_length, char_types, ref flags @ ..]
+                if rule_flags(flags).is_some() =>
+            {
+                let (no_confusables, positions) = rule_flags(flags).unwrap_or_default();
+                match (max_length.parse::<u16>(), char_types.parse::<u8>()) {
+                    (Ok(max_length), Ok(char_types))
+                        if char_types <= 127 && max_length <= DomainSlot::LENGTH =>
+                    {
+                        let mut table = DomainTable::shared();
+                        table
+                            .set_rules(domain, max_length, char_types, structure)
+                            .and_then(|()| {
+                                table.set_no_confusables(domain, no_confusables, structure)
+                            })
+                            .and_then(|()| table.set_positions(domain, positions, structure))
+                            .map(|_| {
+                                format!(
+                                    "{}: max length {}, char types {}{}",
+                                    domain,
+                                    max_length,
+                                    char_types,
+                                    rules_text(no_confusables, positions)
+                                )
+                            })
+                            .map_err(|e| e.to_string())
+                    }
+                    _ => Err(
+                        "Rules are a max length (0-4095, 0 for none) and char types (0-127)"
+                            .to_string(),
+                    ),
+                }
+            }
+            ["delete", domain] => {
+                if DomainTable::shared().remove(domain, structure) {
+                    Ok(format!("{} removed", domain))
+                } else {
+                    Err(format!("{} is not registered", domain))
+                }
+            }
+            _ => Err(format!("Unknown domain command '{}'", line.trim())),
+        };
 
-        let structure_bytes = self.structure_system.to_bytes();
-        bytes.extend_from_slice(&(structure_bytes.len() as u32).to_ne_bytes());
-        bytes.extend(structure_bytes);
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(message) => println!("Error: {}", message),
+        }
+    }
 
-        bytes
+    if DomainTable::shared().is_dirty() {
+        DomainTable::shared().save_to_binary(&exe_path)?;
+        println!("Domain table saved");
     }
+    Ok(())
+}
 
-    fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
-        let mut offset = 0;
+// signal number that asked us to stop, 0 while running normally
+static SHUTDOWN_SIGNAL: AtomicI32 = AtomicI32::new(0);
 
-        let name_len = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
+const SIGINT: i32 = 2;
+#[cfg(unix)]
+const SIGTERM: i32 = 15;
 
-        if bytes.len() < offset + name_len {
-            return Err("Invalid data: not enough bytes for name");
-        }
-        let name = match String::from_utf8(bytes[offset..offset + name_len].to_vec()) {
-            Ok(s) => s,
-            Err(_) => return Err("Invalid UTF-8 in name"),
-        };
-        offset += name_len;
+fn request_shutdown(signal: i32) {
+    SHUTDOWN_SIGNAL.store(signal, Ordering::SeqCst);
+}
 
-        if bytes.len() < offset + 4 {
-            return Err("Invalid data: not enough bytes for description length");
-        }
-        let desc_len = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
+fn shutdown_requested() -> bool {
+    SHUTDOWN_SIGNAL.load(Ordering::SeqCst) != 0
+}
 
-        if bytes.len() < offset + desc_len {
-            return Err("Invalid data: not enough bytes for description");
-        }
-        let description = match String::from_utf8(bytes[offset..offset + desc_len].to_vec()) {
-            Ok(s) => s,
-            Err(_) => return Err("Invalid UTF-8 in description"),
-        };
-        offset += desc_len;
-
-        if bytes.len() < offset + 8 {
-            return Err("Invalid data: not enough bytes for created date");
-        }
-        let created_date = u64::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-            bytes[offset + 4],
-            bytes[offset + 5],
-            bytes[offset + 6],
-            bytes[offset + 7],
-        ]);
-        offset += 8;
-
-        let mut extra_chars_count = 3;
-
-        if bytes.len() >= offset + 4 {
-            extra_chars_count = u32::from_ne_bytes([
-                bytes[offset],
-                bytes[offset + 1],
-                bytes[offset + 2],
-                bytes[offset + 3],
-            ]) as usize;
-            offset += 4;
-        } else {
-            println!("Warning: Using default value for extra_chars_count");
-        }
+// the conventional 128 + signal number
+fn shutdown_exit_code() -> Option<i32> {
+    match SHUTDOWN_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(128 + signal),
+    }
+}
 
-        if bytes.len() < offset + 4 {
-            return Err("Invalid data: not enough bytes for Structure system length");
-        }
-        let structure_len = u32::from_ne_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]) as usize;
-        offset += 4;
+// io loops call this when a read comes back interrupted, turning a pending
+// signal into an error that unwinds through the normal cleanup paths
+fn check_shutdown() -> io::Result<()> {
+    if shutdown_requested() {
+        Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "Interrupted by signal",
+        ))
+    } else {
+        Ok(())
+    }
+}
 
-        if bytes.len() < offset + structure_len {
-            return Err("Invalid data: not enough bytes for Structure system");
+// like read_exact, but gives up on EINTR when a shutdown was requested
+// instead of silently retrying and blocking again
+fn read_exact_interruptible<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => check_shutdown()?,
+            Err(e) => return Err(e),
         }
-        let structure_system = StructureSystem::from_bytes(&bytes[offset..offset + structure_len])?;
-
-        Ok(SavedPassword {
-            name,
-            description,
-            structure_system,
-            created_date,
-            extra_chars_count,
-        })
     }
+    Ok(())
 }
 
-#[derive(Clone)]
-pub struct PasswordManager {
-    saved_passwords: Vec<SavedPassword>,
-    storage: BinaryStorageManager,
-    active_structure_idx: Option<usize>,
-}
-
-impl PasswordManager {
-    fn new(
-        parent_mode: bool,
-        tx: Option<Sender<ProcessMessage>>,
-        _rx: Option<Receiver<ProcessMessage>>,
-        silent: bool,
-    ) -> io::Result<Self> {
-        let storage = BinaryStorageManager::new(parent_mode, tx)?;
-
-        let mut manager = PasswordManager {
-            saved_passwords: Vec::new(),
-            storage,
-            active_structure_idx: None,
-        };
-
-        manager.load_all_passwords(silent)?;
+// --timeout: how long term mode and the interactive session wait on a key before
+// clearing the screen and exiting. 0 waits forever
+const DEFAULT_INPUT_TIMEOUT_SECS: u64 = 600;
+static INPUT_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_INPUT_TIMEOUT_SECS);
+
+// one key into buffer. non_blocking is a terminal in CbreakNonBlocking, where an
+// empty read means nothing was typed yet; otherwise it is the end of input, the only
+// time this returns 0. a Windows console blocks either way, so it waits unbounded.
+// once the timeout passes with no key the screen is cleared and IdleTimeout returned,
+// the caller resetting whatever it had loaded
+fn read_key_or_idle(
+    buffer: &mut [u8; 1],
+    non_blocking: bool,
+    screen: &mut dyn Write,
+) -> io::Result<usize> {
+    let timeout_secs = INPUT_TIMEOUT_SECS.load(Ordering::SeqCst);
+    let waiting_since = std::time::Instant::now();
 
-        if !manager.saved_passwords.is_empty() {
-            manager.active_structure_idx = Some(0);
-            if !silent {
-                eprintln!("Active Structure: {}", manager.saved_passwords[0].name);
+    loop {
+        match io::stdin().read(buffer) {
+            Ok(0) if non_blocking => {
+                // Ctrl+C arrives as SIGINT, main restores the terminal on the way out
+                check_shutdown()?;
+                if timeout_secs > 0 && waiting_since.elapsed().as_secs() >= timeout_secs {
+                    write!(screen, "\x1B[2J\x1B[3J\x1B[H")?;
+                    screen.flush()?;
+                    return Err(ErrorCode::IdleTimeout.error(format!(
+                        "No input for {}s, the screen was cleared",
+                        timeout_secs
+                    )));
+                }
+                thread::sleep(std::time::Duration::from_millis(10));
             }
+            Ok(n) => return Ok(n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => check_shutdown()?,
+            Err(e) => return Err(e),
         }
-
-        Ok(manager)
     }
+}
 
-    fn load_all_passwords(&mut self, silent: bool) -> io::Result<()> {
-        self.saved_passwords.clear();
-
-        let password_entries = self.storage.list_all();
+fn read_line_interruptible(line: &mut String) -> io::Result<usize> {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let mut bytes = Vec::new();
+    let mut byte = [0u8; 1];
 
-        for (name, description) in password_entries {
-            if let Ok(Some((data, _))) = self.storage.retrieve(&name) {
-                match SavedPassword::from_bytes(&data) {
-                    Ok(mut password) => {
-                        password.description = description;
-                        self.saved_passwords.push(password);
-                    }
-                    Err(e) => {
-                        println!("Error loading structure '{}': {}", name, e);
-                    }
+    loop {
+        match stdin.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) => {
+                bytes.push(byte[0]);
+                if byte[0] == b'\n' {
+                    break;
                 }
             }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => check_shutdown()?,
+            Err(e) => return Err(e),
         }
+    }
 
-        if !silent {
-            eprintln!(
-                "Loaded {} saved password configurations",
-                self.saved_passwords.len()
-            );
-        }
+    line.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(bytes.len())
+}
 
-        Ok(())
+// try_lock so a panic while the lock is held can't deadlock the hook
+fn restore_terminal_if_raw() {
+    if let Ok(mut global) = SAVED_TERMINAL.try_lock() {
+        if let Some(saved) = global.take() {
+            let _ = terminal_restore(&saved);
+        }
     }
+}
 
-    fn save_password(&mut self, password: &SavedPassword) -> io::Result<()> {
-        let bytes = password.to_bytes();
+// last things to do before leaving because of a signal or panic
+fn emergency_cleanup() {
+    restore_terminal_if_raw();
 
-        self.storage
-            .store(password.name.clone(), password.description.clone(), &bytes)?;
+    // a panic while the table was held leaves it locked, and waiting would hang the exit
+    let Some(mut table) = DomainTable::try_shared() else {
+        return;
+    };
+    if table.is_dirty() {
+        if let Ok(exe_path) = vault_path() {
+            if let Err(e) = table.save_to_binary(&exe_path) {
+                diag(
+                    Diag::Warning,
+                    format_args!("Warning: Could not save domain table: {}", e),
+                );
+            }
+        }
+    }
+}
 
-        Ok(())
+#[cfg(unix)]
+extern "C" fn handle_shutdown_signal(signal: i32) {
+    extern "C" {
+        fn _exit(status: i32) -> !;
     }
 
-    fn add_password(&mut self, password: SavedPassword) -> io::Result<()> {
-        self.save_password(&password)?;
+    // a second Ctrl+C means the graceful path is stuck, leave right away
+    if SHUTDOWN_SIGNAL.swap(signal, Ordering::SeqCst) != 0 {
+        unsafe { _exit(128 + signal) };
+    }
+}
 
-        self.saved_passwords.push(password);
+#[cfg(unix)]
+fn install_shutdown_handlers() {
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn siginterrupt(sig: i32, flag: i32) -> i32;
+    }
 
-        // to ensure ease of use, this checks is the first password and make it active
-        if self.saved_passwords.len() == 1 {
-            self.active_structure_idx = Some(0);
+    for sig in [SIGINT, SIGTERM] {
+        unsafe {
+            signal(sig, handle_shutdown_signal);
+            // make blocking reads return EINTR so the io loops notice the signal
+            siginterrupt(sig, 1);
         }
+    }
 
-        Ok(())
+    install_panic_hook();
+}
+
+// the console handler runs on its own thread while the main thread may sit
+// in a blocking console read, so it cleans up and exits from there
+#[cfg(windows)]
+extern "system" fn handle_console_ctrl(ctrl_type: u32) -> i32 {
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    const CTRL_CLOSE_EVENT: u32 = 2;
+
+    match ctrl_type {
+        CTRL_C_EVENT | CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT => {
+            request_shutdown(SIGINT);
+            emergency_cleanup();
+            std::process::exit(128 + SIGINT);
+        }
+        _ => 0,
     }
+}
 
-    fn create_password_setup(
-        name: &str,
-        description: &str,
-        structure_system: &mut StructureSystem,
-        keycodes: &[u32],
-        extra_chars_count: usize,
-    ) -> Result<SavedPassword, std::io::Error> {
-        use std::io::{self, Read, Write};
+#[cfg(windows)]
+fn install_shutdown_handlers() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+    }
 
-        println!("\n=== PASSWORD SETUP PHASE ===");
-        println!("This is a one-time setup to create your configuration.");
-        println!("Type your sequence naturally.");
-        println!("Press ESC when finished.");
+    unsafe {
+        SetConsoleCtrlHandler(handle_console_ctrl, 1);
+    }
 
-        setup_raw_mode();
+    install_panic_hook();
+}
 
-        let stdin = io::stdin();
-        let mut stdin = stdin.lock();
-        let mut buffer = [0; 1];
-        let mut last_keypress_time = std::time::Instant::now();
+// put the terminal back before the panic message is printed,
+// unwinding then wipes the transient buffers on its way out
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal_if_raw();
+        default_hook(info);
+    }));
+}
 
-        structure_system.reset_position();
+// how far a RawModeGuard takes the terminal
+#[derive(Clone, Copy)]
+enum TerminalMode {
+    // no line editing, echo, signals or output processing, what setup needs
+    Raw,
+    // keys arrive one at a time without echo, Ctrl+C still raises SIGINT
+    Cbreak,
+    // like Cbreak, but reads return straight away when nothing was typed
+    CbreakNonBlocking,
+}
 
-        let mut collected_chars = Vec::new();
-        let mut current_input = String::new();
-        let mut display_input = String::new();
-        let mut display_count = 0;
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Termios {
+    c_iflag: u32,
+    c_oflag: u32,
+    c_cflag: u32,
+    c_lflag: u32,
+    c_line: u8,
+    c_cc: [u8; 32],
+    c_ispeed: u32,
+    c_ospeed: u32,
+}
 
-        println!("\nStart typing your sequence now:");
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Termios {
+    c_iflag: u64,
+    c_oflag: u64,
+    c_cflag: u64,
+    c_lflag: u64,
+    c_cc: [u8; 20],
+    c_ispeed: u64,
+    c_ospeed: u64,
+}
 
-        loop {
-            match stdin.read_exact(&mut buffer) {
-                Ok(_) => {
-                    let keycode = buffer[0] as u32;
+// the BSDs share the classic layout with 32-bit flags
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "macos", target_os = "ios"))
+))]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Termios {
+    c_iflag: u32,
+    c_oflag: u32,
+    c_cflag: u32,
+    c_lflag: u32,
+    c_cc: [u8; 20],
+    c_ispeed: u32,
+    c_ospeed: u32,
+}
 
-                    if keycode == 27 {
-                        println!("\nSetup complete!");
-                        break;
-                    }
+#[cfg(target_os = "linux")]
+mod termios_consts {
+    pub type TcFlag = u32;
+    pub const ECHO: TcFlag = 0o000010;
+    pub const ICANON: TcFlag = 0o000002;
+    pub const VMIN: usize = 6;
+    pub const VTIME: usize = 5;
+}
 
-                    let now = std::time::Instant::now();
-                    let duration = now.duration_since(last_keypress_time);
-                    let timing_ms = duration.as_millis() as u64;
-                    last_keypress_time = now;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod termios_consts {
+    pub type TcFlag = u64;
+    pub const ECHO: TcFlag = 0x0000_0008;
+    pub const ICANON: TcFlag = 0x0000_0100;
+    pub const VMIN: usize = 16;
+    pub const VTIME: usize = 17;
+}
 
-                    let timestamp = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .expect("Time went backwards")
-                        .as_millis() as u64;
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "macos", target_os = "ios"))
+))]
+mod termios_consts {
+    pub type TcFlag = u32;
+    pub const ECHO: TcFlag = 0x0000_0008;
+    pub const ICANON: TcFlag = 0x0000_0100;
+    pub const VMIN: usize = 16;
+    pub const VTIME: usize = 17;
+}
 
-                    if keycode == 8 || keycode == 127 {
-                        if !display_input.is_empty() {
-                            display_input.pop();
-                            if display_count > 0 {
-                                display_count -= 1;
-                            }
-                            print!(
-                                "\r{}                              \r{} characters typed: {}",
-                                " ".repeat(50),
-                                display_count,
-                                display_input
-                            );
-                            io::stdout().flush()?;
-                        }
+#[cfg(unix)]
+extern "C" {
+    fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+    fn tcsetattr(fd: i32, optional_actions: i32, termios: *const Termios) -> i32;
+    fn cfmakeraw(termios: *mut Termios);
+}
 
-                        structure_system.modify_with_timing(keycode, timing_ms, timestamp);
-                        let output_chars =
-                            structure_system.transform_char(keycode, extra_chars_count);
-                        collected_chars
-                            .extend(output_chars.iter().filter_map(|&code| char::from_u32(code)));
-                    } else if keycodes.contains(&keycode) {
-                        if let Some(c) = char::from_u32(keycode) {
-                            current_input.push(c);
-                            display_input.push(c);
-                        }
+#[cfg(unix)]
+type SavedTerminal = Termios;
 
-                        display_count += 1;
+#[cfg(windows)]
+type SavedTerminal = u32;
 
-                        structure_system.modify_with_timing(keycode, timing_ms, timestamp);
+// the settings to go back to, kept globally so the panic hook and the
+// shutdown path can restore them even when no guard is in reach
+static SAVED_TERMINAL: Mutex<Option<SavedTerminal>> = Mutex::new(None);
 
-                        let output_chars =
-                            structure_system.transform_char(keycode, extra_chars_count);
-                        collected_chars
-                            .extend(output_chars.iter().filter_map(|&code| char::from_u32(code)));
+#[cfg(unix)]
+fn terminal_enter(mode: TerminalMode) -> io::Result<SavedTerminal> {
+    const STDIN_FD: i32 = 0;
+    const TCSANOW: i32 = 0;
 
-                        print!("\r{} characters typed: {}", display_count, display_input);
-                        io::stdout().flush()?;
-                    }
-                }
-                Err(e) => {
-                    println!("\nError reading from stdin: {}", e);
-                    break;
-                }
-            }
+    unsafe {
+        let mut original = std::mem::zeroed::<Termios>();
+        if tcgetattr(STDIN_FD, &mut original) != 0 {
+            return Err(io::Error::last_os_error());
         }
-        #[cfg(unix)]
-        restore_terminal();
 
-        structure_system.set_name(name.to_string());
+        let mut settings = original;
+        match mode {
+            TerminalMode::Raw => cfmakeraw(&mut settings),
+            TerminalMode::Cbreak | TerminalMode::CbreakNonBlocking => {
+                settings.c_lflag &= !(termios_consts::ICANON | termios_consts::ECHO);
 
-        structure_system.full_reset();
+                let min = if let TerminalMode::Cbreak = mode {
+                    1
+                } else {
+                    0
+                };
+                settings.c_cc[termios_consts::VMIN] = min;
+                settings.c_cc[termios_consts::VTIME] = 0;
+            }
+        }
 
-        let created_date = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("You a time traveler? Time went backwards")
-            .as_secs();
+        if tcsetattr(STDIN_FD, TCSANOW, &settings) != 0 {
+            return Err(io::Error::last_os_error());
+        }
 
-        let saved_password = SavedPassword {
-            name: name.to_string(),
-            description: description.to_string(),
-            structure_system: structure_system.clone(),
-            created_date,
-            extra_chars_count,
-        };
-
-        println!("\n\n✓ Configuration created successfully!");
-        println!("You typed {} characters for setup.", display_count);
-
-        Ok(saved_password)
+        Ok(original)
     }
 }
 
-fn run_parent_process(auto_exit: bool) -> io::Result<()> {
-    println!("Starting Void Vault...");
-    println!("Maximized and unending void");
-    println!("Reapplied inside the geometry");
-    println!("zero password setup");
-
-    let (tx_to_child, _rx_from_parent) = mpsc::channel();
-    let (_tx_to_parent, rx_from_child) = mpsc::channel();
+#[cfg(unix)]
+fn terminal_restore(saved: &SavedTerminal) -> io::Result<()> {
+    const STDIN_FD: i32 = 0;
+    const TCSANOW: i32 = 0;
 
-    let executable_path = std::env::current_exe()?;
-    let mut child_args = vec!["--child-process".to_string()];
-    if auto_exit {
-        child_args.push("--auto-exit".to_string());
+    if unsafe { tcsetattr(STDIN_FD, TCSANOW, saved) } != 0 {
+        return Err(io::Error::last_os_error());
     }
+    Ok(())
+}
 
-    let mut child = Command::new(&executable_path)
-        .args(&child_args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()?;
+#[cfg(windows)]
+#[allow(non_snake_case)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetStdHandle(nStdHandle: u32) -> *mut std::ffi::c_void;
+    fn GetConsoleMode(hConsoleHandle: *mut std::ffi::c_void, lpMode: *mut u32) -> i32;
+    fn SetConsoleMode(hConsoleHandle: *mut std::ffi::c_void, dwMode: u32) -> i32;
+}
 
-    match rx_from_child.recv() {
-        Ok(ProcessMessage::ChildReady) => {
-            println!("Child birthed and ready");
-        }
-        _ => {
-            println!("Failed to receive ready signal from child, it clawled back in");
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "The child brings dishonor to the family by failing initialization",
-            ));
-        }
+#[cfg(windows)]
+fn console_input_handle() -> io::Result<*mut std::ffi::c_void> {
+    const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6_u32;
+
+    let handle = unsafe { GetStdHandle(STD_INPUT_HANDLE) };
+    if handle.is_null() {
+        return Err(io::Error::other("Failed to get stdin handle"));
     }
+    Ok(handle)
+}
 
-    loop {
-        match rx_from_child.recv() {
-            Ok(ProcessMessage::BinaryUpdated(_new_binary_path)) => {
-                println!("Received newaged binary update notification from child");
+#[cfg(windows)]
+fn terminal_enter(mode: TerminalMode) -> io::Result<SavedTerminal> {
+    // Windows Console API constants
+    const ENABLE_LINE_INPUT: u32 = 0x0002;
+    const ENABLE_ECHO_INPUT: u32 = 0x0004;
+    const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+    const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
 
-                thread::sleep(std::time::Duration::from_millis(100));
+    let handle = console_input_handle()?;
 
-                tx_to_child
-                    .send(ProcessMessage::BinaryUpdateComplete)
-                    .unwrap_or_else(|_| {
-                        println!(
-                            "Failed to use dope slang and signal binary update completion to child"
-                        );
-                    });
-            }
-            Ok(ProcessMessage::ShutdownChild) => {
-                println!("Child sent a requested to be unborn");
-                break;
-            }
-            Err(_) => {
-                break;
-            }
-            _ => {}
+    unsafe {
+        let mut original: u32 = 0;
+        if GetConsoleMode(handle, &mut original) == 0 {
+            return Err(io::Error::last_os_error());
         }
 
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                println!("Child exited with status: {}", status);
-                break;
-            }
-            Ok(None) => {}
-            Err(e) => {
-                println!("Error checking the child, prognosis: {}", e);
-                break;
-            }
+        let mut settings = original & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT);
+        // processed input turns Ctrl+C into a console control event
+        if let TerminalMode::Raw = mode {
+            settings &= !ENABLE_PROCESSED_INPUT;
         }
-    }
+        settings |= ENABLE_VIRTUAL_TERMINAL_INPUT;
 
-    match child.wait() {
-        Ok(status) => {
-            println!("Child exited with status: {}", status);
-        }
-        Err(e) => {
-            println!("Error waiting for child: {}", e);
+        if SetConsoleMode(handle, settings) == 0 {
+            return Err(io::Error::last_os_error());
         }
-    }
 
-    Ok(())
+        Ok(original)
+    }
 }
 
-fn run_simple_setup(
-    password_manager: &mut PasswordManager,
-    seed: u64,
-    auto_exit: bool,
-) -> io::Result<()> {
-    println!("");
-    println!("╔════════════════════════════════════════════════════════════════════╗");
-    println!("║                     WELCOME TO THE VOID VAULT                      ║");
-    println!("║                         First-Time Setup                           ║");
-    println!("╚════════════════════════════════════════════════════════════════════╝\n");
-
-    println!("The Void Vault creates secure passwords for all your accounts.");
-    println!("Setup takes about 1 minute.\n");
-
-    // 8 output characters per input
-    let extra_chars_count = 7;
-
-    // The full UTF-8 character set
-    let mut keycodes = Vec::new();
-    keycodes.extend(32..127); // ASCII printable
-    keycodes.extend(161..1024); // Extended Latin, Greek, Cyrillic, etc.
-    keycodes.extend(1024..5000); // CJK, Arabic, Hebrew, etc.
-    keycodes.extend(8192..8500); // Various symbols
-    keycodes.extend(9000..9500); // More symbols
-    keycodes.extend(128512..128591); // Emoji
-
-    println!("══════════════════════════════════════════════════════════════════════");
-    println!("Create Your Void Vault");
-    println!("══════════════════════════════════════════════════════════════════════");
-    println!("\nType a phrase of at least 40 characters.");
-    println!("This phrase will create your unique vault.\n");
-    println!("Instructions:");
-    println!("  • Type any phrase, sentence, or random characters");
-    println!("  • At least 40 characters recommended (longer = better)");
-    println!("  • Type naturally - your rhythm adds uniqueness");
-    println!("  • This phrase is ONLY for setup, not for generating passwords");
-    println!("  • Press ESC when finished\n");
-    println!("Note: Your passwords will use the full UTF-8 character set.");
-    println!("      The browser extension will handle website requirements.\n");
-
-    println!("Press Enter to begin...");
-    let mut ready = String::new();
-    io::stdin().read_line(&mut ready)?;
-
-    let dimensions = 7;
-    let coordinate_range = 10 + dimensions as i32;
-    let mut structure_system = StructureSystem::new(seed, dimensions as usize, coordinate_range);
-    structure_system.set_character_set(keycodes.clone());
-
-    let initial_password: Vec<char> = Vec::new();
-    structure_system.generate_structure(&initial_password, &keycodes);
-
-    let saved_password = PasswordManager::create_password_setup(
-        "main",
-        "Primary configuration",
-        &mut structure_system,
-        &keycodes,
-        extra_chars_count,
-    )?;
-
-    password_manager.add_password(saved_password)?;
-    password_manager.active_structure_idx = Some(0);
-
-    println!("\n══════════════════════════════════════════════════════════════════════");
-    println!("✓ SETUP COMPLETE!");
-    println!("══════════════════════════════════════════════════════════════════════");
-    println!("\nYour password system is ready!\n");
-    println!("How to use the Void Vault:");
-    println!("  1. Go to any website login page");
-    println!("  2. Click the password field");
-    println!("  3. Press Ctrl+Shift+S in your browser");
-    println!("  4. Type your password phrase");
-    println!("  5. Press Enter\n");
-    println!("⚠️  IMPORTANT: Backup this file to a safe place!");
-    println!("    Without it, you cannot access your passwords.\n");
+#[cfg(windows)]
+fn terminal_restore(saved: &SavedTerminal) -> io::Result<()> {
+    let handle = console_input_handle()?;
 
-    if !auto_exit {
-        println!("Press Enter to continue...");
-        let mut cont = String::new();
-        io::stdin().read_line(&mut cont)?;
+    if unsafe { SetConsoleMode(handle, *saved) } == 0 {
+        return Err(io::Error::last_os_error());
     }
-
     Ok(())
 }
 
-fn run_child_process(auto_exit: bool) -> io::Result<()> {
-    println!("Starting the Void Vault");
+// the terminal's width right now, 80 when there is none to ask. cheap enough to
+// ask on every redraw, which is how a resize gets noticed
+#[cfg(unix)]
+fn terminal_columns() -> usize {
+    #[repr(C)]
+    struct Winsize {
+        ws_row: u16,
+        ws_col: u16,
+        ws_xpixel: u16,
+        ws_ypixel: u16,
+    }
 
-    let (tx_to_parent, _rx_from_child) = mpsc::channel();
-    let (_tx_to_child, rx_from_parent) = mpsc::channel();
+    extern "C" {
+        fn ioctl(fd: i32, request: std::ffi::c_ulong, ...) -> i32;
+    }
 
-    tx_to_parent
-        .send(ProcessMessage::ChildReady)
-        .unwrap_or_else(|_| {
-            println!("Failed to say it is ready to be a parent");
-        });
+    #[cfg(target_os = "linux")]
+    const TIOCGWINSZ: std::ffi::c_ulong = 0x5413;
+    #[cfg(not(target_os = "linux"))]
+    const TIOCGWINSZ: std::ffi::c_ulong = 0x4008_7468;
+
+    // stdin is the terminal term mode reads from, stderr the usual fallback
+    for fd in [0, 2, 1] {
+        let mut size = Winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        if unsafe { ioctl(fd, TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            return size.ws_col as usize;
+        }
+    }
+    80
+}
 
-    let start = SystemTime::now();
-    let since_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Do you own a hot tub? Time went backwards");
-    let seed = since_epoch.as_secs();
+#[cfg(windows)]
+#[allow(non_snake_case)]
+fn terminal_columns() -> usize {
+    #[repr(C)]
+    struct ConsoleScreenBufferInfo {
+        size: [i16; 2],
+        cursor_position: [i16; 2],
+        attributes: u16,
+        window: [i16; 4],
+        maximum_window_size: [i16; 2],
+    }
 
-    let mut password_manager = match PasswordManager::new(
-        false,
-        Some(tx_to_parent.clone()),
-        Some(rx_from_parent),
-        false,
-    ) {
-        Ok(manager) => manager,
-        Err(e) => {
-            println!("Error initializing password manager: {}", e);
-            println!("passwords won't be saved");
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleScreenBufferInfo(
+            hConsoleOutput: *mut std::ffi::c_void,
+            lpConsoleScreenBufferInfo: *mut ConsoleScreenBufferInfo,
+        ) -> i32;
+    }
 
-            let storage = match BinaryStorageManager::new(false, Some(tx_to_parent.clone())) {
-                Ok(s) => s,
-                Err(_) => {
-                    println!("Cannot initialize storage. Exiting.");
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "Storage initialization failed",
-                    ));
-                }
-            };
+    const STD_OUTPUT_HANDLE: u32 = 0xFFFFFFF5_u32;
 
-            PasswordManager {
-                saved_passwords: Vec::new(),
-                storage,
-                active_structure_idx: None,
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut info = std::mem::zeroed::<ConsoleScreenBufferInfo>();
+        if !handle.is_null() && GetConsoleScreenBufferInfo(handle, &mut info) != 0 {
+            // left, top, right, bottom
+            let columns = info.window[2] - info.window[0] + 1;
+            if columns > 0 {
+                return columns as usize;
             }
         }
-    };
+    }
+    80
+}
 
-    if password_manager.active_structure_idx.is_some() {
-        if !auto_exit {
-            run_interactive_mode(&mut password_manager)?;
-        }
-    } else if password_manager.saved_passwords.is_empty() {
-        if let Ok(_) = run_simple_setup(&mut password_manager, seed, auto_exit) {
-            if password_manager.active_structure_idx.is_some() && !auto_exit {
-                run_interactive_mode(&mut password_manager)?;
-            }
-        }
-    } else {
-        password_manager.active_structure_idx = Some(0);
-        if !auto_exit {
-            run_interactive_mode(&mut password_manager)?;
+// columns a character takes up in a terminal: none for combining marks, two for the
+// East Asian wide ranges and most emoji. close enough to keep a line on one row
+fn char_columns(ch: char) -> usize {
+    match ch as u32 {
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F => {
+            0
         }
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
     }
-
-    tx_to_parent
-        .send(ProcessMessage::ShutdownChild)
-        .unwrap_or_else(|_| {
-            println!("Failed to suggest to the parent to abort itself");
-        });
-
-    println!("Exiting program.");
-    Ok(())
 }
 
-fn run_interactive_mode(password_manager: &mut PasswordManager) -> io::Result<()> {
-    loop {
-        println!("\n=== VOID VAULT ===");
-        let (structure_name, _description, _extra_chars) =
-            if let Some(idx) = password_manager.active_structure_idx {
-                if idx < password_manager.saved_passwords.len() {
-                    let structure = &password_manager.saved_passwords[idx];
-                    (
-                        structure.name.clone(),
-                        structure.description.clone(),
-                        structure.extra_chars_count,
-                    )
-                } else {
-                    (
-                        "No active password configuration".to_string(),
-                        "".to_string(),
-                        0,
-                    )
-                }
-            } else {
-                (
-                    "No active password configuration".to_string(),
-                    "".to_string(),
-                    0,
-                )
-            };
+// puts the terminal back exactly as it was found when dropped,
+// including when unwinding out of a failed read
+struct RawModeGuard {
+    saved: SavedTerminal,
+}
 
-        println!("Active configuration: {}", structure_name);
-        println!("\nEnter your password phrase (or 'exit' to quit):");
+impl RawModeGuard {
+    fn enable(mode: TerminalMode) -> io::Result<Self> {
+        let saved = terminal_enter(mode)?;
 
-        let mut stdin = io::stdin();
-        let mut feedbacks: Vec<u8> = Vec::new();
+        if let Ok(mut global) = SAVED_TERMINAL.lock() {
+            // with nested guards the outermost settings are the ones to return to
+            if global.is_none() {
+                *global = Some(saved);
+            }
+        }
 
-        if let Some(idx) = password_manager.active_structure_idx {
-            if idx < password_manager.saved_passwords.len() {
-                let saved_password = &mut password_manager.saved_passwords[idx];
+        Ok(RawModeGuard { saved })
+    }
 
-                println!("\nGenerated password:");
+    // piped input has no terminal to configure, that is not an error
+    fn enable_if_terminal(mode: TerminalMode) -> io::Result<Option<Self>> {
+        use std::io::IsTerminal;
 
-                loop {
-                    let mut buffer = [0u8; 1];
-                    match stdin.read(&mut buffer) {
-                        Ok(0) => break,
-                        Ok(_) => {
-                            let byte = buffer[0];
+        if !io::stdin().is_terminal() {
+            return Ok(None);
+        }
+        Self::enable(mode).map(Some)
+    }
+}
 
-                            if byte == b'\n' || byte == b'\r' {
-                                break;
-                            }
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = terminal_restore(&self.saved);
 
-                            if byte == b'e' && feedbacks.is_empty() {
-                                // might be typing "exit", but continue processing normally
-                            }
+        if let Ok(mut global) = SAVED_TERMINAL.lock() {
+            *global = None;
+        }
+    }
+}
 
-                            if let Some(ch) = char::from_u32(byte as u32) {
-                                if !ch.is_control() {
-                                    let keycode = ch as u32;
+// "ö" or an emoji is one keystroke with its code point, as it is in the browser
+struct Utf8Decoder {
+    bytes: [u8; 4],
+    len: usize,
+}
 
-                                    let feedback_offset: u32 =
-                                        feedbacks.iter().map(|&fb| fb as u32).sum();
-                                    let modified_keycode = keycode.wrapping_add(feedback_offset);
+impl Utf8Decoder {
+    fn new() -> Self {
+        Utf8Decoder {
+            bytes: [0; 4],
+            len: 0,
+        }
+    }
 
-                                    let mut navigation_sequence = vec![modified_keycode];
-                                    for &fb in feedbacks.iter().rev() {
-                                        navigation_sequence.push(fb as u32);
-                                    }
+    // returns a character once its last byte arrives; malformed sequences are dropped
+    fn push(&mut self, byte: u8) -> Option<char> {
+        if self.len > 0 && byte & 0xC0 != 0x80 {
+            self.clear();
+        }
 
-                                    print!("\r                                                            \r");
-                                    let _ = io::stdout().flush();
+        self.bytes[self.len] = byte;
+        self.len += 1;
 
-                                    saved_password.structure_system.reset_position();
-                                    let mut output_sum = 0u64;
-
-                                    for &input_code in &navigation_sequence {
-                                        let output_chars =
-                                            saved_password.structure_system.transform_char(
-                                                input_code,
-                                                saved_password.extra_chars_count,
-                                            );
-
-                                        for &code in &output_chars {
-                                            if let Some(character) = char::from_u32(code) {
-                                                print!("{}", character);
-                                                let _ = io::stdout().flush();
-                                                output_sum = output_sum.wrapping_add(code as u64);
-                                            }
-                                        }
-                                    }
+        let expected = match self.bytes[0] {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => {
+                self.clear();
+                return None;
+            }
+        };
 
-                                    let feedback = (output_sum % 256) as u8;
-                                    feedbacks.push(feedback);
-                                }
-                            }
-                        }
-                        Err(e) => return Err(e),
-                    }
-                }
+        if self.len < expected {
+            return None;
+        }
 
-                println!();
-                feedbacks.clear();
+        let decoded = std::str::from_utf8(&self.bytes[..self.len])
+            .ok()
+            .and_then(|s| s.chars().next());
+        self.clear();
+        decoded
+    }
 
-                saved_password.structure_system.full_reset();
-            }
-        }
+    fn clear(&mut self) {
+        zero_memory(&mut self.bytes);
+        self.len = 0;
     }
 }
 
-fn zero_memory<T>(data: &mut [T]) {
-    unsafe {
-        std::ptr::write_bytes(data.as_mut_ptr(), 0, data.len());
+impl Drop for Utf8Decoder {
+    fn drop(&mut self) {
+        self.clear();
     }
 }
 
-#[cfg(unix)]
-fn setup_raw_mode() {
-    use std::process::Command;
-    let _ = Command::new("stty").args(&["raw", "-echo"]).status();
+// what hidden output shows in place of the password: always the same width, so
+// neither the characters nor how wide they render reach the screen
+fn masked_output(output: &str) -> String {
+    format!("{} {} characters", "*".repeat(16), output.chars().count())
 }
 
-#[cfg(unix)]
-fn restore_terminal() {
-    use std::process::Command;
-    let _ = Command::new("stty").args(&["cooked", "echo"]).status();
-}
+// the end of a hidden generation: the password is shown only after an explicit
+// Enter, any other key (or EOF) discards it. expects an unechoed blocking terminal
+fn confirm_reveal(output: &str) -> io::Result<()> {
+    println!("\nPress Enter to reveal the password, any other key to discard it.");
+    io::stdout().flush()?;
 
-#[cfg(unix)]
-fn enable_raw_mode() -> io::Result<()> {
-    Command::new("stty")
-        .args(&["-icanon", "-echo", "min", "0", "time", "0"])
-        .stdin(Stdio::inherit())
-        .status()?;
-    Ok(())
-}
+    let mut buffer = [0u8; 1];
+    let revealed = match io::stdin().read(&mut buffer) {
+        Ok(1) => buffer[0] == b'\n' || buffer[0] == b'\r',
+        Ok(_) => false,
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+            check_shutdown()?;
+            false
+        }
+        Err(e) => return Err(e),
+    };
 
-#[cfg(unix)]
-fn disable_raw_mode() -> io::Result<()> {
-    Command::new("stty")
-        .args(&["icanon", "echo"])
-        .stdin(Stdio::inherit())
-        .status()?;
+    if revealed {
+        println!("{}", output);
+    } else {
+        println!("Discarded.");
+    }
     Ok(())
 }
 
-#[cfg(windows)]
-fn setup_raw_mode() {
-    let _ = enable_raw_mode();
-}
-
-#[cfg(windows)]
-fn restore_terminal() {
-    let _ = disable_raw_mode();
+// a small QR code encoder for --qr: one byte mode segment behind a UTF-8 ECI header,
+// error correction level M, versions 1-40. follows ISO/IEC 18004 closely enough for
+// phone cameras, nothing more. modules are true for dark
+struct QrCode {
+    size: usize,
+    modules: Wiped<Vec<bool>>,
+    is_function: Vec<bool>,
 }
 
-#[cfg(windows)]
-fn enable_raw_mode() -> io::Result<()> {
-    #[allow(non_snake_case)]
-    #[cfg(windows)]
-    {
-        use std::os::windows::io::AsRawHandle;
-
-        // Windows Console API constants
-        const ENABLE_LINE_INPUT: u32 = 0x0002;
-        const ENABLE_ECHO_INPUT: u32 = 0x0004;
-        const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
-        const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+impl QrCode {
+    // per version, level M only
+    const ECC_CODEWORDS_PER_BLOCK: [usize; 41] = [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ];
+    const ERROR_CORRECTION_BLOCKS: [usize; 41] = [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ];
+
+    // None when the data doesn't fit even version 40
+    fn encode(data: &[u8]) -> Option<QrCode> {
+        let version = (1..=40).find(|&version| {
+            let count_bits = if version <= 9 { 8 } else { 16 };
+            // ECI mode and designator, byte mode and count, then the data
+            data.len() < (1 << count_bits)
+                && 4 + 8 + 4 + count_bits + data.len() * 8 <= Self::data_codewords(version) * 8
+        })?;
 
-        unsafe {
-            #[link(name = "kernel32")]
-            extern "system" {
-                fn GetStdHandle(nStdHandle: u32) -> *mut std::ffi::c_void;
-                fn GetConsoleMode(hConsoleHandle: *mut std::ffi::c_void, lpMode: *mut u32) -> i32;
-                fn SetConsoleMode(hConsoleHandle: *mut std::ffi::c_void, dwMode: u32) -> i32;
+        let capacity = Self::data_codewords(version) * 8;
+        let mut bits = Wiped(Vec::<bool>::with_capacity(capacity));
+        let push = |bits: &mut Vec<bool>, value: usize, len: usize| {
+            for i in (0..len).rev() {
+                bits.push((value >> i) & 1 != 0);
             }
-
-            const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6_u32;
-
-            let handle = GetStdHandle(STD_INPUT_HANDLE);
-            if handle.is_null() {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Failed to get stdin handle",
-                ));
+        };
+        push(&mut bits, 0b0111, 4);
+        push(&mut bits, 26, 8);
+        push(&mut bits, 0b0100, 4);
+        push(&mut bits, data.len(), if version <= 9 { 8 } else { 16 });
+        for &byte in data {
+            push(&mut bits, byte as usize, 8);
+        }
+        let terminator = (capacity - bits.len()).min(4);
+        push(&mut bits, 0, terminator);
+        let padding = (8 - bits.len() % 8) % 8;
+        push(&mut bits, 0, padding);
+
+        let mut codewords = Wiped(
+            bits.chunks(8)
+                .map(|byte| byte.iter().fold(0u8, |acc, &bit| acc << 1 | bit as u8))
+                .collect::<Vec<u8>>(),
+        );
+        for &pad in [0xEC, 0x11].iter().cycle() {
+            if codewords.len() >= capacity / 8 {
+                break;
             }
+            codewords.push(pad);
+        }
 
-            let mut mode: u32 = 0;
-            if GetConsoleMode(handle, &mut mode) == 0 {
-                return Err(io::Error::last_os_error());
+        let size = version * 4 + 17;
+        let mut qr = QrCode {
+            size,
+            modules: Wiped(vec![false; size * size]),
+            is_function: vec![false; size * size],
+        };
+        qr.draw_function_patterns(version);
+        let all_codewords = Wiped(Self::add_error_correction(version, &codewords));
+        qr.draw_codewords(&all_codewords);
+
+        // the mask with the lowest penalty, like any other encoder would pick
+        let mut best = (0, usize::MAX);
+        for mask in 0..8 {
+            qr.apply_mask(mask);
+            qr.draw_format_bits(mask);
+            let penalty = qr.penalty();
+            if penalty < best.1 {
+                best = (mask, penalty);
             }
+            qr.apply_mask(mask);
+        }
+        qr.apply_mask(best.0);
+        qr.draw_format_bits(best.0);
+        Some(qr)
+    }
 
-            mode &= !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT);
-            mode |= ENABLE_VIRTUAL_TERMINAL_INPUT;
-
-            if SetConsoleMode(handle, mode) == 0 {
-                return Err(io::Error::last_os_error());
+    fn raw_data_modules(version: usize) -> usize {
+        let mut result = (16 * version + 128) * version + 64;
+        if version >= 2 {
+            let alignments = version / 7 + 2;
+            result -= (25 * alignments - 10) * alignments - 55;
+            if version >= 7 {
+                result -= 36;
             }
         }
+        result
     }
 
-    Ok(())
-}
-
-#[cfg(windows)]
-fn disable_raw_mode() -> io::Result<()> {
-    #[allow(non_snake_case)]
-    #[cfg(windows)]
-    {
-        use std::os::windows::io::AsRawHandle;
+    fn data_codewords(version: usize) -> usize {
+        Self::raw_data_modules(version) / 8
+            - Self::ECC_CODEWORDS_PER_BLOCK[version] * Self::ERROR_CORRECTION_BLOCKS[version]
+    }
 
-        const ENABLE_LINE_INPUT: u32 = 0x0002;
-        const ENABLE_ECHO_INPUT: u32 = 0x0004;
-        const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
 
-        unsafe {
-            #[link(name = "kernel32")]
-            extern "system" {
-                fn GetStdHandle(nStdHandle: u32) -> *mut std::ffi::c_void;
-                fn GetConsoleMode(hConsoleHandle: *mut std::ffi::c_void, lpMode: *mut u32) -> i32;
-                fn SetConsoleMode(hConsoleHandle: *mut std::ffi::c_void, dwMode: u32) -> i32;
-            }
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.is_function[y * self.size + x] = true;
+    }
 
-            const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6_u32;
+    fn alignment_positions(version: usize) -> Vec<usize> {
+        if version == 1 {
+            return Vec::new();
+        }
+        let count = version / 7 + 2;
+        let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+        let mut positions = vec![6];
+        let mut position = version * 4 + 17 - 7;
+        for _ in 0..count - 1 {
+            positions.insert(1, position);
+            position -= step;
+        }
+        positions
+    }
 
-            let handle = GetStdHandle(STD_INPUT_HANDLE);
-            if handle.is_null() {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "Failed to get stdin handle",
-                ));
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4i32..=4 {
+                for dx in -4i32..=4 {
+                    let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                    if (0..size as i32).contains(&x) && (0..size as i32).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        self.set_function(x as usize, y as usize, distance != 2 && distance != 4);
+                    }
+                }
             }
+        }
 
-            let mut mode: u32 = 0;
-            if GetConsoleMode(handle, &mut mode) == 0 {
-                return Err(io::Error::last_os_error());
+        let positions = Self::alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &cx) in positions.iter().enumerate() {
+            for (j, &cy) in positions.iter().enumerate() {
+                // the three corners are taken by finder patterns
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let (x, y) = ((cx as i32 + dx) as usize, (cy as i32 + dy) as usize);
+                        self.set_function(x, y, dx.abs().max(dy.abs()) != 1);
+                    }
+                }
             }
+        }
 
-            mode |= ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT;
+        // reserved now, the real bits go in once the mask is chosen
+        self.draw_format_bits(0);
 
-            if SetConsoleMode(handle, mode) == 0 {
-                return Err(io::Error::last_os_error());
+        if version >= 7 {
+            let mut remainder = version;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+            }
+            let bits = version << 12 | remainder;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, dark);
+                self.set_function(b, a, dark);
             }
         }
     }
 
-    Ok(())
-}
+    fn draw_format_bits(&mut self, mask: usize) {
+        // level M is 00
+        let data = mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        let size = self.size;
 
-fn run_terminal_mode(args: &[String]) -> io::Result<()> {
-    let mut account_name: Option<String> = None;
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
 
-    let mut i = 2;
-    while i < args.len() {
-        if args[i] == "--account" && i + 1 < args.len() {
-            account_name = Some(args[i + 1].clone());
-            i += 2;
-        } else {
-            i += 1;
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
         }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
     }
 
-    let mut password_manager = PasswordManager::new(false, None, None, false)?;
-
-    let saved_password_idx = if let Some(name) = &account_name {
-        password_manager
-            .saved_passwords
-            .iter()
-            .position(|p| &p.name == name)
-    } else {
-        if let Some(idx) = password_manager.active_structure_idx {
-            if idx < password_manager.saved_passwords.len() {
-                Some(idx)
-            } else {
-                None
+    fn add_error_correction(version: usize, data: &[u8]) -> Vec<u8> {
+        let blocks_count = Self::ERROR_CORRECTION_BLOCKS[version];
+        let ecc_len = Self::ECC_CODEWORDS_PER_BLOCK[version];
+        let raw_codewords = Self::raw_data_modules(version) / 8;
+        let short_blocks = blocks_count - raw_codewords % blocks_count;
+        let short_len = raw_codewords / blocks_count;
+        let divisor = gf_divisor(ecc_len);
+
+        let mut blocks: Vec<Wiped<Vec<u8>>> = Vec::with_capacity(blocks_count);
+        let mut start = 0;
+        for i in 0..blocks_count {
+            let len = short_len - ecc_len + usize::from(i >= short_blocks);
+            let mut block = Wiped(data[start..start + len].to_vec());
+            start += len;
+            let ecc = gf_remainder(&block, &divisor);
+            // a placeholder so short and long blocks line up when interleaving
+            if i < short_blocks {
+                block.push(0);
             }
-        } else if !password_manager.saved_passwords.is_empty() {
-            Some(0)
-        } else {
-            None
+            block.extend(ecc);
+            blocks.push(block);
         }
-    };
 
-    let saved_password_idx = match saved_password_idx {
-        Some(idx) => idx,
-        None => {
-            eprintln!("Error: No password configuration found");
-            return Ok(());
+        let mut result = Vec::with_capacity(raw_codewords);
+        for i in 0..blocks[0].len() {
+            for (j, block) in blocks.iter().enumerate() {
+                if i != short_len - ecc_len || j >= short_blocks {
+                    result.push(block[i]);
+                }
+            }
         }
-    };
-
-    #[cfg(unix)]
-    enable_raw_mode()?;
-
-    let mut feedbacks: Vec<u8> = Vec::new();
-
-    println!("Type your input (press Enter when done, Backspace to reset):");
-    print!("\r");
-    io::stdout().flush()?;
-
-    loop {
-        let mut buffer = [0u8; 1];
-        let mut stdin = io::stdin();
+        result
+    }
 
-        match stdin.read(&mut buffer) {
-            Ok(0) => {
-                std::thread::sleep(std::time::Duration::from_millis(10));
-                continue;
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size as i32 - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
             }
-            Ok(_) => {
-                let byte = buffer[0];
-
-                match byte {
-                    b'\n' | b'\r' => {
-                        break;
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right as usize - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.is_function[y * size + x] && i < codewords.len() * 8 {
+                        self.modules[y * size + x] = (codewords[i >> 3] >> (7 - (i & 7))) & 1 != 0;
+                        i += 1;
                     }
-                    127 | 8 => {
-                        feedbacks.clear();
+                }
+            }
+            right -= 2;
+        }
+    }
 
-                        print!("\r                                                            \r");
-                        io::stdout().flush()?;
+    // applying the same mask twice undoes it
+    fn apply_mask(&mut self, mask: usize) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                if invert && !self.is_function[index] {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
 
-                        password_manager.saved_passwords[saved_password_idx]
-                            .structure_system
-                            .full_reset();
-                    }
-                    3 => {
-                        #[cfg(unix)]
-                        disable_raw_mode()?;
-                        println!();
-                        return Ok(());
+    // the four penalty rules of the standard, summed
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        let finder_like = [
+            [
+                true, false, true, true, true, false, true, false, false, false, false,
+            ],
+            [
+                false, false, false, false, true, false, true, true, true, false, true,
+            ],
+        ];
+
+        for transpose in [false, true] {
+            let at = |a: usize, b: usize| {
+                if transpose {
+                    self.get(a, b)
+                } else {
+                    self.get(b, a)
+                }
+            };
+            for line in 0..size {
+                let mut run = 1;
+                for i in 1..size {
+                    if at(line, i) == at(line, i - 1) {
+                        run += 1;
+                        if run == 5 {
+                            penalty += 3;
+                        } else if run > 5 {
+                            penalty += 1;
+                        }
+                    } else {
+                        run = 1;
                     }
-                    _ => {
-                        if let Some(ch) = char::from_u32(byte as u32) {
-                            if !ch.is_control() {
-                                let mut keycode = ch as u32;
-
-                                unsafe {
-                                    if SESSION.initialized {
-                                        keycode =
-                                            keycode.wrapping_add(SESSION.active_counter as u32);
-                                    }
-                                }
-
-                                let saved_password =
-                                    &mut password_manager.saved_passwords[saved_password_idx];
-
-                                // Ofset keycode by sum of all feedbacks
-                                let feedback_offset: u32 =
-                                    feedbacks.iter().map(|&fb| fb as u32).sum();
-                                let modified_keycode = keycode.wrapping_add(feedback_offset);
-
-                                let mut navigation_sequence = vec![modified_keycode];
-                                for &fb in feedbacks.iter().rev() {
-                                    navigation_sequence.push(fb as u32);
-                                }
-
-                                print!("\x1B[50A\r\x1B[0J");
-                                io::stdout().flush()?;
-
-                                saved_password.structure_system.reset_position();
-
-                                let mut output_sum = 0u64;
-                                for &input_code in &navigation_sequence {
-                                    let output_chars =
-                                        saved_password.structure_system.transform_char(
-                                            input_code,
-                                            saved_password.extra_chars_count,
-                                        );
-
-                                    for &code in &output_chars {
-                                        if let Some(character) = char::from_u32(code) {
-                                            print!("{}", character);
-                                            io::stdout().flush()?;
-                                            output_sum = output_sum.wrapping_add(code as u64);
-                                        }
-                                    }
-                                }
-
-                                let feedback = (output_sum % 256) as u8;
-                                feedbacks.push(feedback);
-                            }
+                }
+                for i in 0..size.saturating_sub(10) {
+                    for pattern in &finder_like {
+                        if (0..11).all(|k| at(line, i + k) == pattern[k]) {
+                            penalty += 40;
                         }
                     }
                 }
             }
-            Err(e) => {
-                #[cfg(unix)]
-                disable_raw_mode()?;
-                return Err(e);
-            }
         }
-    }
 
-    #[cfg(unix)]
-    disable_raw_mode()?;
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.get(x, y);
+                if dark == self.get(x + 1, y)
+                    && dark == self.get(x, y + 1)
+                    && dark == self.get(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
 
-    println!();
-    Ok(())
-}
+        let dark = self.modules.iter().filter(|&&dark| dark).count();
+        let total = size * size;
+        // steps of 5% away from half dark
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty + deviation.div_ceil(total).saturating_sub(1) * 10
+    }
 
-fn run_io_mode(args: &[String]) -> io::Result<()> {
-    let mut account_name: Option<String> = None;
+    // two module rows per line with half blocks, light modules drawn as blocks so it
+    // reads the right way round on a dark terminal. includes the 4 module quiet zone
+    fn render(&self) -> Wiped<String> {
+        const QUIET: usize = 4;
+        let full = self.size + QUIET * 2;
+        let light = |x: usize, y: usize| {
+            x < QUIET
+                || y < QUIET
+                || x >= self.size + QUIET
+                || y >= self.size + QUIET
+                || !self.get(x - QUIET, y - QUIET)
+        };
 
-    let mut i = 2;
-    while i < args.len() {
-        if args[i] == "--account" && i + 1 < args.len() {
-            account_name = Some(args[i + 1].clone());
-            i += 2;
-        } else {
-            i += 1;
+        let mut out = Wiped(String::with_capacity(full * full * 2));
+        for y in (0..full).step_by(2) {
+            for x in 0..full {
+                let top = light(x, y);
+                let bottom = y + 1 >= full || light(x, y + 1);
+                out.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
         }
+        out
     }
+}
 
-    let mut password_manager = PasswordManager::new(false, None, None, false)?;
+// multiplication in GF(2^8) modulo the QR polynomial x^8 + x^4 + x^3 + x^2 + 1
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
 
-    let saved_password_idx = if let Some(name) = &account_name {
-        password_manager
-            .saved_passwords
-            .iter()
-            .position(|p| &p.name == name)
-    } else {
-        if let Some(idx) = password_manager.active_structure_idx {
-            if idx < password_manager.saved_passwords.len() {
-                Some(idx)
-            } else {
-                None
+// the Reed-Solomon generator polynomial of the given degree, highest term implied
+fn gf_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
             }
-        } else if !password_manager.saved_passwords.is_empty() {
-            Some(0)
-        } else {
-            None
         }
-    };
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
 
-    let saved_password_idx = match saved_password_idx {
-        Some(idx) => idx,
-        None => {
-            eprintln!("Error: No password configuration found");
-            return Ok(());
+fn gf_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_multiply(y, factor);
         }
-    };
+    }
+    result
+}
 
-    let mut stdin = io::stdin();
-    let mut feedbacks: Vec<u8> = Vec::new();
-    let mut input_chars: Vec<u32> = Vec::new();
+// characters a phone is likely to mangle or drop when it turns the scanned bytes
+// back into text: controls, noncharacters and private use code points
+fn qr_unsafe_char(output: &str) -> Option<char> {
+    output.chars().find(|&ch| {
+        let code = ch as u32;
+        ch.is_control()
+            || (0xFDD0..=0xFDEF).contains(&code)
+            || code & 0xFFFE == 0xFFFE
+            || (0xE000..=0xF8FF).contains(&code)
+            || code >= 0xF0000
+    })
+}
 
-    // VERY IMPORTANT
-    // test sequences used for behavioral testing, in acending order, should be:
-    // 1. 29213914 = zeroing trancient memory checking
-    // 2. 999517725 = trancient dynamic memory checking
-    // 3. 0843213126 = incremental link path testing
-    // 4. 9332187235 = recursive end-to-end complexity checking
-    // 5. 5001019899912 = unstable folding of the geometry testing
-    // 6. 64221220322204 = additive. you use this one to test treversal & consisticy
-    // 7. 110883422694685420 = multiple new geometry mutation pattern testing
+// --qr for term and io: the output as a QR code, or why it can't be one
+fn print_qr(out: &mut dyn Write, output: &str) -> io::Result<()> {
+    if let Some(ch) = qr_unsafe_char(output) {
+        return Err(ErrorCode::BadRequest.error(format!(
+            "The output contains U+{:04X}, which doesn't survive a QR code reliably",
+            ch as u32
+        )));
+    }
+    let qr = QrCode::encode(output.as_bytes())
+        .ok_or_else(|| ErrorCode::BadRequest.error("The output is too long for a QR code"))?;
+    write!(out, "{}", qr.render().as_str())?;
+    out.flush()
+}
 
-    loop {
-        let mut buffer = [0u8; 1];
-        match stdin.read(&mut buffer) {
-            Ok(0) => break,
-            Ok(_) => {
-                let byte = buffer[0];
+// --tty-prompt: the controlling terminal, so prompts and progress stay out of a piped
+// stdout and answers don't come from a redirected stdin. without one this fails
+// straight away rather than waiting for input that can't arrive
+struct PromptTerminal {
+    output: File,
+    input: File,
+}
 
-                if byte == b'\n' || byte == b'\r' {
-                    break;
-                }
+impl PromptTerminal {
+    fn open() -> io::Result<Self> {
+        let no_terminal = |path: &str, e: io::Error| {
+            ErrorCode::NoTerminal.error(format!(
+                "There is no terminal to prompt on, {} can't be opened: {}",
+                path, e
+            ))
+        };
 
-                if let Some(ch) = char::from_u32(byte as u32) {
-                    if !ch.is_control() {
-                        input_chars.push(ch as u32);
-                    }
-                }
-            }
-            Err(e) => return Err(e),
+        #[cfg(unix)]
+        {
+            let output = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/tty")
+                .map_err(|e| no_terminal("/dev/tty", e))?;
+            let input = output.try_clone()?;
+            Ok(PromptTerminal { output, input })
+        }
+
+        #[cfg(windows)]
+        {
+            let output = fs::OpenOptions::new()
+                .write(true)
+                .open("CONOUT$")
+                .map_err(|e| no_terminal("CONOUT$", e))?;
+            let input = File::open("CONIN$").map_err(|e| no_terminal("CONIN$", e))?;
+            Ok(PromptTerminal { output, input })
         }
     }
+}
 
-    let saved_password = &mut password_manager.saved_passwords[saved_password_idx];
+// askpass: the domain, when --domain isn't given. ssh-add and sudo -A pass nothing
+// but the prompt
+const ASKPASS_DOMAIN_VAR: &str = "VOID_VAULT_ASKPASS_DOMAIN";
 
-    for i in 0..input_chars.len() {
-        let mut keycode = input_chars[i];
+// askpass: io with the phrase typed on the terminal, unechoed, after the caller's
+// prompt. ssh-add and sudo -A usually leave stdin pointing elsewhere, so on unix the
+// terminal takes its place. the password alone goes to stdout, any failure exits
+// non-zero, which the caller takes as cancelled
+fn run_askpass(prompt: Option<&str>, io_args: &[String]) -> io::Result<()> {
+    let mut terminal = PromptTerminal::open()?;
 
-        unsafe {
-            if SESSION.initialized {
-                keycode = keycode.wrapping_add(SESSION.active_counter as u32);
-            }
+    #[cfg(unix)]
+    if !std::io::IsTerminal::is_terminal(&io::stdin()) {
+        use std::os::fd::AsRawFd;
+
+        extern "C" {
+            fn dup2(old: i32, new: i32) -> i32;
+        }
+        if unsafe { dup2(terminal.input.as_raw_fd(), 0) } < 0 {
+            return Err(io::Error::last_os_error());
         }
+    }
 
-        // Offset keycode by sum of all feedbacks so far
-        let feedback_offset: u32 = feedbacks.iter().map(|&fb| fb as u32).sum();
-        let modified_keycode = keycode.wrapping_add(feedback_offset);
+    let _raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::Cbreak)?;
+    write!(
+        terminal.output,
+        "{} ",
+        prompt.map(str::trim_end).unwrap_or("Void Vault phrase:")
+    )?;
+    terminal.output.flush()?;
+    let generated = run_io_mode(io_args);
+    writeln!(terminal.output)?;
+    generated
+}
 
-        let mut navigation_sequence = vec![modified_keycode];
-        for &fb in feedbacks.iter().rev() {
-            navigation_sequence.push(fb as u32);
-        }
+// the final output for --tty-prompt and io: on stdout with nothing else, ended by a
+// NUL with --print0
+fn emit_output(output: &str, terminator: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{}{}", output, terminator)?;
+    stdout.flush()
+}
 
-        saved_password.structure_system.reset_position();
-        let mut output_sum = 0u64;
+// --exec: the output goes to the command as one write, on its stdin or with --fd on
+// that inherited descriptor. never on its command line or in its environment, where
+// any process can read it. the command's exit status becomes ours
+fn deliver_to_command(command: &[String], fd: Option<i32>, output: &[u8]) -> io::Result<()> {
+    let mut child = Command::new(&command[0]);
+    child.args(&command[1..]);
+    let not_started =
+        |e: io::Error| io::Error::new(e.kind(), format!("Could not run {}: {}", command[0], e));
 
-        for &input_code in &navigation_sequence {
-            let output_chars = saved_password
-                .structure_system
-                .transform_char(input_code, saved_password.extra_chars_count);
+    let status = match fd {
+        None => {
+            let mut child = child.stdin(Stdio::piped()).spawn().map_err(not_started)?;
+            if let Some(mut stdin) = child.stdin.take() {
+                // a command that exits without reading is its own business
+                match stdin.write_all(output) {
+                    Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+                    _ => {}
+                }
+            }
+            child.wait()?
+        }
+        #[cfg(unix)]
+        Some(target) => {
+            use std::os::fd::AsRawFd;
+            use std::os::unix::process::CommandExt;
+
+            extern "C" {
+                fn dup2(old: i32, new: i32) -> i32;
+                fn fcntl(fd: i32, cmd: i32, ...) -> i32;
+            }
+            const F_SETFD: i32 = 2;
 
-            for &code in &output_chars {
-                output_sum = output_sum.wrapping_add(code as u64);
+            // written before the command starts, a password fits the pipe's buffer
+            let (reader, mut writer) = io::pipe()?;
+            writer.write_all(output)?;
+            drop(writer);
 
-                if i == input_chars.len() - 1 {
-                    if let Some(character) = char::from_u32(code) {
-                        print!("{}", character);
+            let source = reader.as_raw_fd();
+            unsafe {
+                child.pre_exec(move || {
+                    // dup2 leaves the copy open across exec. already in place, the
+                    // close-on-exec flag has to come off instead
+                    let moved = if source == target {
+                        fcntl(target, F_SETFD, 0)
+                    } else {
+                        dup2(source, target)
+                    };
+                    if moved < 0 {
+                        return Err(io::Error::last_os_error());
                     }
-                }
+                    Ok(())
+                });
             }
+            let status = child.spawn().map_err(not_started)?.wait()?;
+            drop(reader);
+            status
+        }
+        #[cfg(not(unix))]
+        Some(_) => {
+            return Err(ErrorCode::Usage.error("--fd is only available on unix"));
         }
+    };
 
-        let feedback = (output_sum % 256) as u8;
-        feedbacks.push(feedback);
+    if let Some(code) = status.code().filter(|&code| code != 0) {
+        EXIT_CODE.store(code, Ordering::SeqCst);
     }
+    Ok(())
+}
 
-    zero_memory(&mut input_chars);
-    zero_memory(&mut feedbacks);
+// --type: the output typed into whatever window has focus once the countdown ends,
+// for native apps and VM consoles the extension can't reach
+#[derive(Clone, Copy)]
+struct TypeOptions {
+    countdown_secs: u64,
+    delay_ms: u64,
+}
 
-    io::stdout().flush()?;
+impl TypeOptions {
+    const DEFAULT_COUNTDOWN_SECS: u64 = 3;
+    const DEFAULT_DELAY_MS: u64 = 20;
 
-    Ok(())
+    // --countdown <s> and --type-delay <ms> from a mode's arguments, None without --type
+    fn from_args(args: &[String]) -> io::Result<Option<Self>> {
+        if !args.iter().any(|arg| arg == "--type") {
+            if args
+                .iter()
+                .any(|arg| arg == "--countdown" || arg == "--type-delay")
+            {
+                return Err(ErrorCode::Usage.error("--countdown and --type-delay go with --type"));
+            }
+            return Ok(None);
+        }
+        let value = |name: &str, default: u64, error: &str| -> io::Result<u64> {
+            match args.iter().position(|arg| arg == name) {
+                Some(i) => args
+                    .get(i + 1)
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&n| n <= 60_000)
+                    .ok_or_else(|| ErrorCode::Usage.error(error.to_string())),
+                None => Ok(default),
+            }
+        };
+        Ok(Some(TypeOptions {
+            countdown_secs: value(
+                "--countdown",
+                Self::DEFAULT_COUNTDOWN_SECS,
+                "--countdown needs a number of seconds",
+            )?,
+            delay_ms: value(
+                "--type-delay",
+                Self::DEFAULT_DELAY_MS,
+                "--type-delay needs a number of milliseconds",
+            )?,
+        }))
+    }
 }
 
-fn extract_json_string(message: &str, key: &str) -> String {
-    let search = format!("\"{}\":\"", key);
-    if let Some(start) = message.find(&search) {
-        let start_idx = start + search.len();
-        if let Some(end) = message[start_idx..].find('"') {
-            return message[start_idx..start_idx + end].to_string();
+// refuses before anything is typed when the layout can't produce every character,
+// naming the ones it can't rather than typing something else in their place
+fn check_typable(output: &str, layout: &str, typable: impl Fn(char) -> bool) -> io::Result<()> {
+    let mut missing: Vec<char> = Vec::new();
+    for ch in output.chars() {
+        if !typable(ch) && !missing.contains(&ch) {
+            missing.push(ch);
         }
     }
-    String::new()
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let listed: Vec<String> = missing
+        .iter()
+        .map(|&ch| format!("{} (U+{:04X})", ch, ch as u32))
+        .collect();
+    Err(ErrorCode::CannotType.error(format!(
+        "The {} keyboard layout can't type {}, nothing was typed",
+        layout,
+        listed.join(", ")
+    )))
 }
 
-fn extract_json_number(message: &str, key: &str) -> u64 {
-    let search = format!("\"{}\":", key);
-    if let Some(start) = message.find(&search) {
-        let start_idx = start + search.len();
-        if let Some(end) = message[start_idx..].find(&[',', '}'][..]) {
-            if let Ok(val) = message[start_idx..start_idx + end].trim().parse() {
-                return val;
-            }
-        }
+fn type_output(output: &str, options: TypeOptions, out: &mut dyn Write) -> io::Result<()> {
+    // the typing tools send keysyms through the layout, and printable ASCII is what
+    // every layout they'd find can produce
+    #[cfg(not(windows))]
+    check_typable(output, &typing_layout(), |ch| {
+        ch == ' ' || ch.is_ascii_graphic()
+    })?;
+
+    for remaining in (1..=options.countdown_secs).rev() {
+        write!(out, "\rSwitching focus in {}… ", remaining)?;
+        out.flush()?;
+        thread::sleep(std::time::Duration::from_secs(1));
     }
-    0
+    if options.countdown_secs > 0 {
+        writeln!(out, "\r\x1B[2KTyping")?;
+    }
+
+    #[cfg(windows)]
+    return type_with_send_input(output, options.delay_ms);
+    #[cfg(not(windows))]
+    return type_with_tool(output, options.delay_ms);
 }
 
-fn run_json_io_mode(args: &[String]) -> io::Result<()> {
-    let mut account_name: Option<String> = None;
+// the layout name for messages: setxkbmap's on X11, XKB_DEFAULT_LAYOUT otherwise
+#[cfg(not(windows))]
+fn typing_layout() -> String {
+    let queried = Command::new("setxkbmap")
+        .arg("-query")
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .and_then(|query| {
+            String::from_utf8_lossy(&query.stdout)
+                .lines()
+                .find_map(|line| {
+                    line.strip_prefix("layout:")
+                        .map(|name| name.trim().to_string())
+                })
+        });
+    queried
+        .or_else(|| std::env::var("XKB_DEFAULT_LAYOUT").ok())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "current".to_string())
+}
 
-    let mut i = 2;
-    while i < args.len() {
-        if args[i] == "--account" && i + 1 < args.len() {
-            account_name = Some(args[i + 1].clone());
-            i += 2;
-        } else {
-            i += 1;
+// wtype (or wl-type) on Wayland, xdotool on X11. the text goes to them on stdin,
+// where their command line would show it to every process
+#[cfg(not(windows))]
+fn type_with_tool(output: &str, delay_ms: u64) -> io::Result<()> {
+    let delay = delay_ms.to_string();
+    let mut tools: Vec<(&str, Vec<&str>)> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(("wtype", vec!["-d", &delay, "-"]));
+        tools.push(("wl-type", vec!["-d", &delay, "-"]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        tools.push(("xdotool", vec!["type", "--delay", &delay, "--file", "-"]));
+    }
+
+    for (tool, args) in &tools {
+        let mut child = match Command::new(tool).args(args).stdin(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(output.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(ErrorCode::CannotType.error(format!("{} failed with {}", tool, status)));
         }
+        return Ok(());
     }
 
-    let mut password_manager = PasswordManager::new(false, None, None, true)?;
+    Err(ErrorCode::CannotType.error(if tools.is_empty() {
+        "--type needs a Wayland or X11 session".to_string()
+    } else {
+        let names: Vec<&str> = tools.iter().map(|(tool, _)| *tool).collect();
+        format!("--type needs one of {} installed", names.join(", "))
+    }))
+}
 
-    // Load domain table from binary on startup
-    let exe_path = std::env::current_exe()?;
-    if let Err(e) = DomainTable::load_from_binary(&exe_path) {
-        eprintln!("Warning: Could not load domain table: {}", e);
+// SendInput with the virtual key and scan code the focused window's layout maps
+// each character to, shift and all, which VM consoles take where unicode packets
+// don't get through
+#[cfg(windows)]
+fn type_with_send_input(output: &str, delay_ms: u64) -> io::Result<()> {
+    #[repr(C)]
+    struct KeybdInput {
+        vk: u16,
+        scan: u16,
+        flags: u32,
+        time: u32,
+        extra_info: usize,
     }
 
-    let saved_password_idx = if let Some(name) = &account_name {
-        password_manager
-            .saved_passwords
-            .iter()
-            .position(|p| &p.name == name)
-    } else {
-        if let Some(idx) = password_manager.active_structure_idx {
-            if idx < password_manager.saved_passwords.len() {
-                Some(idx)
-            } else {
-                None
-            }
-        } else if !password_manager.saved_passwords.is_empty() {
-            Some(0)
-        } else {
-            None
-        }
-    };
-
-    let saved_password_idx = match saved_password_idx {
-        Some(idx) => idx,
-        None => {
-            return Ok(());
-        }
-    };
+    // INPUT is a tagged union sized by its mouse variant, 8 bytes past the keyboard one
+    #[repr(C)]
+    struct Input {
+        kind: u32,
+        ki: KeybdInput,
+        padding: [u8; 8],
+    }
 
-    password_manager.saved_passwords[saved_password_idx]
-        .structure_system
-        .reset_position();
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetForegroundWindow() -> *mut std::ffi::c_void;
+        fn GetWindowThreadProcessId(hwnd: *mut std::ffi::c_void, process_id: *mut u32) -> u32;
+        fn GetKeyboardLayout(thread_id: u32) -> isize;
+        fn VkKeyScanExW(ch: u16, hkl: isize) -> i16;
+        fn MapVirtualKeyExW(code: u32, map_type: u32, hkl: isize) -> u32;
+        fn SendInput(count: u32, inputs: *const Input, size: i32) -> u32;
+    }
 
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let mut feedbacks: Vec<u8> = Vec::new();
+    const INPUT_KEYBOARD: u32 = 1;
+    const KEYEVENTF_KEYUP: u32 = 0x0002;
+    const MAPVK_VK_TO_VSC: u32 = 0;
+    const VK_SHIFT: u16 = 0x10;
+    const VK_CONTROL: u16 = 0x11;
+    const VK_MENU: u16 = 0x12;
 
-    loop {
-        let mut length_bytes = [0u8; 4];
-        if stdin.read_exact(&mut length_bytes).is_err() {
-            break;
+    let layout = unsafe {
+        let window = GetForegroundWindow();
+        GetKeyboardLayout(GetWindowThreadProcessId(window, std::ptr::null_mut()))
+    };
+    // a character outside the BMP has no key of its own
+    let key_for = |ch: char| -> Option<(u16, u8)> {
+        let unit = u16::try_from(ch as u32).ok()?;
+        let scan = unsafe { VkKeyScanExW(unit, layout) };
+        if scan == -1 {
+            return None;
         }
+        Some(((scan as u16) & 0xFF, ((scan as u16) >> 8) as u8))
+    };
+    check_typable(output, &format!("{:04x}", layout as usize & 0xFFFF), |ch| {
+        key_for(ch).is_some()
+    })?;
+
+    let event = |vk: u16, up: bool| Input {
+        kind: INPUT_KEYBOARD,
+        ki: KeybdInput {
+            vk,
+            scan: unsafe { MapVirtualKeyExW(vk as u32, MAPVK_VK_TO_VSC, layout) } as u16,
+            flags: if up { KEYEVENTF_KEYUP } else { 0 },
+            time: 0,
+            extra_info: 0,
+        },
+        padding: [0; 8],
+    };
 
-        let message_length = u32::from_le_bytes(length_bytes) as usize;
-
-        let mut message_buffer = vec![0u8; message_length];
-        if stdin.read_exact(&mut message_buffer).is_err() {
-            break;
+    for ch in output.chars() {
+        let Some((vk, modifiers)) = key_for(ch) else {
+            continue;
+        };
+        let held: Vec<u16> = [(1, VK_SHIFT), (2, VK_CONTROL), (4, VK_MENU)]
+            .iter()
+            .filter(|&&(bit, _)| modifiers & bit != 0)
+            .map(|&(_, key)| key)
+            .collect();
+
+        let mut inputs: Vec<Input> = held.iter().map(|&key| event(key, false)).collect();
+        inputs.push(event(vk, false));
+        inputs.push(event(vk, true));
+        inputs.extend(held.iter().rev().map(|&key| event(key, true)));
+
+        let sent = unsafe {
+            SendInput(
+                inputs.len() as u32,
+                inputs.as_ptr(),
+                std::mem::size_of::<Input>() as i32,
+            )
+        };
+        if sent as usize != inputs.len() {
+            return Err(io::Error::last_os_error());
         }
+        thread::sleep(std::time::Duration::from_millis(delay_ms));
+    }
+    Ok(())
+}
+
+// the session ACTIVATE sets up for the domain, or with preview the one
+// ACTIVATE_PREVIEW does, one counter ahead of the saved one. generation starts from
+// it through restart_session, so every mode generates what the extension would. the
+// table is the caller's to load. returns the counter and the domain's rules
+fn activate_domain_session(
+    structure: &mut StructureSystem,
+    domain: &str,
+    preview: bool,
+) -> (u16, OutputRules) {
+    let counter = DomainTable::shared()
+        .get_counter(domain, structure)
+        .unwrap_or(0);
+    let (rules, fixed_output) = DomainTable::shared().session_rules(domain, structure);
+    let domain_hash = structure.hash_domain(domain);
 
-        let message = match String::from_utf8(message_buffer) {
-            Ok(s) => s,
-            Err(_) => continue,
+    unsafe {
+        let session = &mut *std::ptr::addr_of_mut!(SESSION);
+        session.scrub();
+        session.active_domain_hash = Some(domain_hash);
+        session.saved_counter = counter;
+        session.active_counter = if preview {
+            counter.saturating_add(1)
+        } else {
+            counter
         };
+        session.is_preview_mode = preview;
+        session.initialized = true;
+        session.fixed_output = fixed_output;
+        session.rules = rules;
+    }
 
-        if message.contains("\"type\"") {
-            if message.contains("\"INIT\"") {
-                password_manager.saved_passwords[saved_password_idx]
-                    .structure_system
-                    .full_reset();
+    (counter, rules)
+}
 
-                feedbacks.clear();
+// --domain for term and io: the domain's session, see activate_domain_session.
+// returns the domain's rules
+fn activate_cli_domain(
+    saved_password: &mut SavedPassword,
+    domain: &str,
+    preview: bool,
+) -> io::Result<OutputRules> {
+    DomainTable::shared().load_from_binary(&vault_path()?)?;
+    let (_, rules) = activate_domain_session(&mut saved_password.structure_system, domain, preview);
+    Ok(rules)
+}
 
-                let response = "{\"status\":\"ready\"}";
-                let response_length = response.len() as u32;
-                stdout.write_all(&response_length.to_le_bytes())?;
-                stdout.write_all(response.as_bytes())?;
-                stdout.flush()?;
-                continue;
-            } else if message.contains("\"RESET\"") {
-                password_manager.saved_passwords[saved_password_idx]
-                    .structure_system
-                    .full_reset();
+// --length, --words and --domain, shared by term and io. --words and --length win
+// over the domain's own
+fn cli_output_rules(
+    saved_password: &mut SavedPassword,
+    domain: Option<&str>,
+    length: Option<&str>,
+    words: Option<&str>,
+    preview: bool,
+) -> io::Result<OutputRules> {
+    let mut rules = match domain {
+        Some(domain) => activate_cli_domain(saved_password, domain, preview)?,
+        None => OutputRules::NONE,
+    };
 
-                feedbacks.clear();
+    if let Some(words) = words {
+        let words = words
+            .parse()
+            .ok()
+            .filter(|n| WORD_COUNTS.contains(n))
+            .ok_or_else(|| ErrorCode::Usage.error("--words must be 3-12"))?;
+        unsafe {
+            SESSION.fixed_output = FixedOutput::Words(words);
+        }
+        rules = OutputRules {
+            max_length: 0,
+            char_types: FixedOutput::WORD_CHAR_TYPES,
+            no_confusables: false,
+            positions: Positions::NONE,
+        };
+    }
 
-                // Note: RESET only clears geometry and feedbacks, does NOT exit preview mode
-                // Preview mode state is preserved so user can retype with same counter
+    if let Some(length) = length {
+        rules.max_length = length
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| ErrorCode::Usage.error("--length must be 1-65535"))?;
+        // the last character the positions hold is the one the cut leaves last
+        unsafe {
+            SESSION.rules.max_length = rules.max_length;
+        }
+    }
+    Ok(rules)
+}
 
-                unsafe {
-                    if SESSION.initialized {
-                        if let Some(ref domain_hash) = SESSION.active_domain_hash {
-                            let structure = &mut password_manager.saved_passwords
-                                [saved_password_idx]
-                                .structure_system;
-
-                            for i in 0..8 {
-                                let hash_byte = domain_hash[i] as u32;
-                                let _ = structure.transform_char(hash_byte, 0);
-                            }
+// COMMIT_INCREMENT for the CLI: writes the counter and saves, then leaves preview
+// the way the json-io session does. without a counter it commits the one a preview
+// would have shown, the saved counter plus one. the table is reloaded under the lock
+// so a preview left waiting at its prompt doesn't write back a stale table
+fn commit_cli_counter(
+    structure: &mut StructureSystem,
+    domain: &str,
+    counter: Option<u16>,
+) -> io::Result<u16> {
+    let exe_path = vault_path()?;
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+    DomainTable::shared().load_from_binary(&exe_path)?;
+
+    let counter = counter.unwrap_or_else(|| {
+        DomainTable::shared()
+            .get_counter(domain, structure)
+            .unwrap_or(0)
+            .saturating_add(1)
+    });
+    DomainTable::shared()
+        .set_counter(domain, counter, structure)
+        .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+    DomainTable::shared().save_to_binary(&exe_path)?;
 
-                            let counter_u32 = SESSION.active_counter as u32;
-                            let _ = structure.transform_char(counter_u32, 0);
-                            let _ = structure.transform_char(counter_u32.wrapping_mul(7), 0);
-                            let _ = structure.transform_char(counter_u32.wrapping_add(13), 0);
-                        }
-                    }
-                }
+    unsafe {
+        let session = &mut *std::ptr::addr_of_mut!(SESSION);
+        if session.is_active_domain(&structure.hash_domain(domain)) {
+            session.saved_counter = counter;
+            session.active_counter = counter;
+            session.is_preview_mode = false;
+        }
+    }
+    Ok(counter)
+}
 
-                let response = "{\"status\":\"reset\"}";
-                let response_length = response.len() as u32;
-                stdout.write_all(&response_length.to_le_bytes())?;
-                stdout.write_all(response.as_bytes())?;
-                stdout.flush()?;
-                continue;
-            } else if message.contains("\"FINALIZE\"") {
-                feedbacks.clear();
-                unsafe {
-                    SESSION.initialized = false;
-                    SESSION.active_domain_hash = None;
-                }
-                break;
-            } else if message.contains("\"GET_COUNTER\"") {
-                let domain = extract_json_string(&message, "domain");
+fn run_terminal_mode(args: &[String]) -> io::Result<()> {
+    let mut account_name: Option<String> = None;
+    let mut hidden = false;
+    let mut confirm = false;
+    let mut length: Option<String> = None;
+    let mut words: Option<String> = None;
+    let mut domain: Option<String> = None;
+    let mut qr = false;
+    let mut show = false;
+    let mut tty_prompt = false;
+    let mut print0 = false;
 
-                let response = if !domain.is_empty() {
-                    let structure =
-                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
-                    match DomainTable::get_counter(&domain, structure) {
-                        Some(counter) => format!("{{\"counter\":{}}}", counter),
-                        None => "{\"counter\":null}".to_string(),
-                    }
-                } else {
-                    "{\"error\":\"Missing domain\"}".to_string()
-                };
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--account" && i + 1 < args.len() {
+            account_name = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--tty-prompt" {
+            tty_prompt = true;
+            i += 1;
+        } else if args[i] == "--print0" {
+            print0 = true;
+            i += 1;
+        } else if args[i] == "--hidden" {
+            hidden = true;
+            i += 1;
+        } else if args[i] == "--length" && i + 1 < args.len() {
+            length = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--words" && i + 1 < args.len() {
+            words = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--domain" && i + 1 < args.len() {
+            domain = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--confirm" {
+            confirm = true;
+            i += 1;
+        } else if args[i] == "--qr" {
+            qr = true;
+            i += 1;
+        } else if args[i] == "--show" {
+            show = true;
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
 
-                let response_length = response.len() as u32;
-                stdout.write_all(&response_length.to_le_bytes())?;
-                stdout.write_all(response.as_bytes())?;
-                stdout.flush()?;
-                continue;
-            } else if message.contains("\"ACTIVATE\"") && !message.contains("\"ACTIVATE_PREVIEW\"")
-            {
-                let domain = extract_json_string(&message, "domain");
+    // without --tty-prompt the screen is stdout, and there is no separate output to end
+    if print0 && !tty_prompt {
+        return Err(ErrorCode::Usage.error("--print0 needs --tty-prompt in term"));
+    }
+    let type_options = TypeOptions::from_args(args)?;
+    if type_options.is_some() && (qr || print0) {
+        return Err(ErrorCode::Usage.error("--type can't be combined with --qr or --print0"));
+    }
+    let mut terminal = if tty_prompt {
+        Some(PromptTerminal::open()?)
+    } else {
+        None
+    };
+    let mut stdout = io::stdout();
+    let out: &mut dyn Write = match terminal.as_mut() {
+        Some(terminal) => &mut terminal.output,
+        None => &mut stdout,
+    };
+    require_unlock(out)?;
 
-                if !domain.is_empty() {
-                    let structure =
-                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
+    // after the unlock secret, which may have been the decoy's
+    let mut password_manager = PasswordManager::new(false, None, false)?;
 
-                    let counter = match DomainTable::get_counter(&domain, structure) {
-                        Some(c) => c,
-                        None => {
-                            if let Err(e) = DomainTable::set_counter(&domain, 0, structure) {
-                                let response = format!("{{\"error\":\"{}\"}}", e);
-                                let response_length = response.len() as u32;
-                                stdout.write_all(&response_length.to_le_bytes())?;
-                                stdout.write_all(response.as_bytes())?;
-                                stdout.flush()?;
-                                continue;
-                            }
-                            if let Err(e) = DomainTable::save_to_binary(&exe_path) {
-                                eprintln!("Warning: Could not save domain table: {}", e);
-                            }
-                            0
-                        }
-                    };
+    let saved_password_idx = domain_account(
+        &mut password_manager,
+        account_name.as_deref(),
+        domain.as_deref(),
+    )?;
 
-                    let (max_length, char_types) =
-                        DomainTable::get_rules(&domain, structure).unwrap_or((0, 127)); // Default: unlimited length, all types enabled
+    let rules = cli_output_rules(
+        &mut password_manager.saved_passwords[saved_password_idx],
+        domain.as_deref(),
+        length.as_deref(),
+        words.as_deref(),
+        false,
+    )?;
 
-                    // Hash domain and store in session
-                    let domain_hash = structure.hash_domain(&domain);
+    let raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::CbreakNonBlocking)?;
 
-                    unsafe {
-                        SESSION.active_domain_hash = Some(domain_hash);
-                        SESSION.saved_counter = counter;
-                        SESSION.active_counter = counter;
-                        SESSION.is_preview_mode = false;
-                        SESSION.initialized = true;
-                    }
+    // while confirming neither entry can be seen, so a typo can't be read off the
+    // first. --qr keeps the plaintext off screen unless --show asks for it
+    let display = if confirm || (qr && !show) {
+        TermDisplay::Sealed
+    } else if show && !hidden {
+        TermDisplay::Shown
+    } else {
+        TermDisplay::Masked
+    };
+
+    if display == TermDisplay::Sealed {
+        writeln!(out, "Type your input (Enter when done, Backspace to undo):")?;
+    } else {
+        writeln!(
+            out,
+            "Type your input (Enter when done, Backspace to undo, Tab to peek):"
+        )?;
+    }
+    out.flush()?;
+
+    let output = read_term_phrase(
+        &mut password_manager,
+        saved_password_idx,
+        display,
+        true,
+        rules,
+        out,
+    )?;
 
-                    structure.full_reset();
-                    feedbacks.clear();
+    if confirm {
+        write!(out, "\r\nType it again to confirm:\n")?;
+        out.flush()?;
+        let again = read_term_phrase(
+            &mut password_manager,
+            saved_password_idx,
+            TermDisplay::Sealed,
+            false,
+            rules,
+            out,
+        )?;
+
+        if !constant_time_eq(output.as_bytes(), again.as_bytes()) {
+            drop(raw_mode);
+            writeln!(out)?;
+            password_manager.persist_usage();
+            return Err(ErrorCode::ConfirmMismatch.error("The two entries did not match"));
+        }
+    }
 
-                    // Ghost navigation: Navigate through geometry using domain hash + counter
-                    // This ensures each domain+counter combination starts from a unique position
-                    // WITHOUT producing any output characters
+    drop(raw_mode);
 
-                    for i in 0..8 {
-                        let hash_byte = domain_hash[i] as u32;
-                        let _ = structure.transform_char(hash_byte, 0);
-                    }
+    writeln!(out)?;
+    password_manager.persist_usage();
+    if let Some(options) = type_options {
+        if !output.is_empty() {
+            type_output(&output, options, out)?;
+        }
+        return Ok(());
+    }
+    if qr && !output.is_empty() {
+        if show {
+            writeln!(out, "{}", output.as_str())?;
+        }
+        print_qr(out, &output)?;
+    } else if tty_prompt {
+        // the password goes to stdout below, not the terminal
+    } else if hidden && !output.is_empty() {
+        let _raw_mode = RawModeGuard::enable_if_terminal(TerminalMode::Cbreak)?;
+        confirm_reveal(&output)?;
+    } else {
+        println!("{}", output.as_str());
+    }
 
-                    // Use counter as both direct value and derived values for more entropy
-                    let counter_u32 = counter as u32;
-                    let _ = structure.transform_char(counter_u32, 0);
-                    let _ = structure.transform_char(counter_u32.wrapping_mul(7), 0);
-                    let _ = structure.transform_char(counter_u32.wrapping_add(13), 0);
+    if tty_prompt {
+        emit_output(&output, if print0 { "\0" } else { "\n" })?;
+    }
+    Ok(())
+}
 
-                    // Now we're at a unique position in 7D space for this domain+counter
-                    // Subsequent user input will generate from this position
+// how term mode's status line shows the output while it is typed
+#[derive(Clone, Copy, PartialEq)]
+enum TermDisplay {
+    // a • per character, Tab peeks at the output itself
+    Masked,
+    // masked with no peeking: while confirming, or for a QR code without --show
+    Sealed,
+    // the output itself (--show), Tab masks it
+    Shown,
+}
 
-                    let response = format!("{{\"saved_counter\":{},\"active_counter\":{},\"max_length\":{},\"char_types\":{},\"status\":\"ready\"}}", counter, counter, max_length, char_types);
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
-                } else {
-                    let response = "{\"error\":\"Missing domain\"}";
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
-                }
-                continue;
-            } else if message.contains("\"ACTIVATE_PREVIEW\"") {
-                let domain = extract_json_string(&message, "domain");
+// term mode's single line of progress, redrawn in place. it is cut to the terminal's
+// width so it never wraps, a revealed output keeping its end (the part that changed)
+fn render_status_line(out: &mut dyn Write, output: &str, revealed: bool) -> io::Result<()> {
+    let count = output.chars().count();
+    let counter = format!("  {} characters", count);
+    let budget = terminal_columns().saturating_sub(1 + counter.len()).max(8);
+
+    let mut line = Wiped(String::new());
+    if revealed {
+        let mut used = 0;
+        let mut start = output.len();
+        for (i, ch) in output.char_indices().rev() {
+            if used + char_columns(ch) > budget - 1 {
+                break;
+            }
+            used += char_columns(ch);
+            start = i;
+        }
+        if start > 0 {
+            line.push('…');
+        }
+        line.push_str(&output[start..]);
+    } else {
+        let dots = count.min(budget);
+        line.push_str(&"•".repeat(dots));
+    }
 
-                if !domain.is_empty() {
-                    let structure =
-                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
+    write!(out, "\r\x1B[2K{}{}", line.as_str(), counter)?;
+    out.flush()
+}
 
-                    let saved_counter = DomainTable::get_counter(&domain, structure).unwrap_or(0);
-                    let preview_counter = saved_counter.saturating_add(1);
+// one phrase typed in term mode, from a fresh start, up to Enter. returns the final
+// output with the rules applied. expects the non-blocking cbreak terminal
+// run_terminal_mode sets up
+fn read_term_phrase(
+    password_manager: &mut PasswordManager,
+    saved_password_idx: usize,
+    display: TermDisplay,
+    record_use: bool,
+    rules: OutputRules,
+    out: &mut dyn Write,
+) -> io::Result<Wiped<String>> {
+    // --domain and --use-domain-counter start from the ghost navigated position, like
+    // the browser
+    let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    let mut decoder = Utf8Decoder::new();
+    let mut last_output = Wiped(String::new());
+    let mut revealed = display == TermDisplay::Shown;
+    // once a phrase, however much of it is backspaced
+    let mut use_recorded = !record_use;
+    use std::io::IsTerminal;
+    let non_blocking = io::stdin().is_terminal();
+
+    restart_session(
+        session(),
+        &mut password_manager.saved_passwords[saved_password_idx].structure_system,
+    );
+    render_status_line(out, &last_output, revealed)?;
 
-                    let (max_length, char_types) =
-                        DomainTable::get_rules(&domain, structure).unwrap_or((0, 127));
+    loop {
+        let mut buffer = [0u8; 1];
 
-                    let domain_hash = structure.hash_domain(&domain);
+        match read_key_or_idle(&mut buffer, non_blocking, out) {
+            Ok(0) => break,
+            Ok(_) => {
+                let byte = buffer[0];
 
-                    unsafe {
-                        SESSION.active_domain_hash = Some(domain_hash);
-                        SESSION.saved_counter = saved_counter;
-                        SESSION.active_counter = preview_counter;
-                        SESSION.is_preview_mode = true;
-                        SESSION.initialized = true;
+                match byte {
+                    b'\n' | b'\r' => {
+                        break;
                     }
+                    b'\t' if display != TermDisplay::Sealed => {
+                        revealed = !revealed;
+                        render_status_line(out, &last_output, revealed)?;
+                    }
+                    // takes back the last character, the output is what the rest
+                    // give typed again
+                    127 | 8 => {
+                        decoder.clear();
+                        if generation.typed().is_empty() {
+                            continue;
+                        }
+                        let output_chars = generation.undo(
+                            session(),
+                            &mut password_manager.saved_passwords[saved_password_idx],
+                        );
+                        let output_chars = rules.apply(&output_chars);
 
-                    structure.full_reset();
-                    feedbacks.clear();
-
-                    for i in 0..8 {
-                        let hash_byte = domain_hash[i] as u32;
-                        let _ = structure.transform_char(hash_byte, 0);
+                        last_output.wipe();
+                        last_output.push_str(&output_chars);
+                        render_status_line(out, &last_output, revealed)?;
                     }
+                    _ => {
+                        if let Some(ch) = decoder.push(byte) {
+                            if !ch.is_control() {
+                                if !use_recorded {
+                                    password_manager.record_use(saved_password_idx);
+                                    use_recorded = true;
+                                }
 
-                    let counter_u32 = preview_counter as u32;
-                    let _ = structure.transform_char(counter_u32, 0);
-                    let _ = structure.transform_char(counter_u32.wrapping_mul(7), 0);
-                    let _ = structure.transform_char(counter_u32.wrapping_add(13), 0);
+                                let output_chars = generation.step(
+                                    session(),
+                                    &mut password_manager.saved_passwords[saved_password_idx],
+                                    ch as u32,
+                                );
+                                let output_chars = rules.apply(&output_chars);
 
-                    let response = format!("{{\"saved_counter\":{},\"active_counter\":{},\"max_length\":{},\"char_types\":{},\"status\":\"preview\"}}", saved_counter, preview_counter, max_length, char_types);
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
-                } else {
-                    let response = "{\"error\":\"Missing domain\"}";
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
+                                last_output.wipe();
+                                last_output.push_str(&output_chars);
+                                render_status_line(out, &last_output, revealed)?;
+                            }
+                        }
+                    }
                 }
-                continue;
-            } else if message.contains("\"SET_COUNTER\"") {
-                let domain = extract_json_string(&message, "domain");
-                let counter = extract_json_number(&message, "counter");
+            }
+            Err(e) => {
+                password_manager.saved_passwords[saved_password_idx]
+                    .structure_system
+                    .full_reset();
+                return Err(e);
+            }
+        }
+    }
 
-                if !domain.is_empty() {
-                    let structure =
-                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
+    Ok(last_output)
+}
 
-                    match DomainTable::set_counter(&domain, counter as u16, structure) {
-                        Ok(()) => {
-                            if let Err(e) = DomainTable::save_to_binary(&exe_path) {
-                                eprintln!("Warning: Could not save domain table: {}", e);
-                            }
+// one line of --io input as code points, backspaces applied and the other control
+// characters dropped
+fn read_io_line(stdin: &mut impl Read, decoder: &mut Utf8Decoder) -> io::Result<Wiped<Vec<u32>>> {
+    let mut input_chars = Wiped(Vec::<u32>::with_capacity(256));
+    decoder.clear();
 
-                            let domain_hash = structure.hash_domain(&domain);
-                            unsafe {
-                                let session = &*std::ptr::addr_of!(SESSION);
-                                if session.active_domain_hash.as_ref() == Some(&domain_hash) {
-                                    let session = &mut *std::ptr::addr_of_mut!(SESSION);
-                                    session.saved_counter = counter as u16;
-                                    session.active_counter = counter as u16;
-                                    session.is_preview_mode = false;
-                                    structure.full_reset();
-                                    feedbacks.clear();
-                                }
-                            }
+    loop {
+        let mut buffer = [0u8; 1];
+        match stdin.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(_) => {
+                let byte = buffer[0];
 
-                            let response = "{\"status\":\"success\"}";
-                            let response_length = response.len() as u32;
-                            stdout.write_all(&response_length.to_le_bytes())?;
-                            stdout.write_all(response.as_bytes())?;
-                            stdout.flush()?;
-                        }
-                        Err(e) => {
-                            let response = format!("{{\"error\":\"{}\"}}", e);
-                            let response_length = response.len() as u32;
-                            stdout.write_all(&response_length.to_le_bytes())?;
-                            stdout.write_all(response.as_bytes())?;
-                            stdout.flush()?;
-                        }
-                    }
-                } else {
-                    let response = "{\"error\":\"Missing domain\"}";
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
+                if byte == b'\n' || byte == b'\r' {
+                    break;
                 }
-                continue;
-            } else if message.contains("\"SET_RULES\"") {
-                let domain = extract_json_string(&message, "domain");
-                let max_length = extract_json_number(&message, "max_length") as u16;
-                let char_types = extract_json_number(&message, "char_types") as u8;
-
-                if !domain.is_empty() {
-                    let structure =
-                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
 
-                    match DomainTable::set_rules(&domain, max_length, char_types, structure) {
-                        Ok(()) => {
-                            if let Err(e) = DomainTable::save_to_binary(&exe_path) {
-                                eprintln!("Warning: Could not save domain table: {}", e);
-                            }
+                // a backspace takes back the character before it
+                if byte == 127 || byte == 8 {
+                    decoder.clear();
+                    input_chars.pop();
+                    continue;
+                }
 
-                            let response = "{\"status\":\"success\"}";
-                            let response_length = response.len() as u32;
-                            stdout.write_all(&response_length.to_le_bytes())?;
-                            stdout.write_all(response.as_bytes())?;
-                            stdout.flush()?;
-                        }
-                        Err(e) => {
-                            let response = format!("{{\"error\":\"{}\"}}", e);
-                            let response_length = response.len() as u32;
-                            stdout.write_all(&response_length.to_le_bytes())?;
-                            stdout.write_all(response.as_bytes())?;
-                            stdout.flush()?;
-                        }
+                if let Some(ch) = decoder.push(byte) {
+                    if !ch.is_control() {
+                        input_chars.push(ch as u32);
                     }
-                } else {
-                    let response = "{\"error\":\"Missing domain\"}";
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
                 }
-                continue;
-            } else if message.contains("\"COMMIT_INCREMENT\"") {
-                let domain = extract_json_string(&message, "domain");
-
-                if !domain.is_empty() {
-                    unsafe {
-                        if SESSION.is_preview_mode {
-                            let structure = &mut password_manager.saved_passwords
-                                [saved_password_idx]
-                                .structure_system;
-
-                            if let Err(e) =
-                                DomainTable::set_counter(&domain, SESSION.active_counter, structure)
-                            {
-                                let response = format!("{{\"error\":\"{}\"}}", e);
-                                let response_length = response.len() as u32;
-                                stdout.write_all(&response_length.to_le_bytes())?;
-                                stdout.write_all(response.as_bytes())?;
-                                stdout.flush()?;
-                                continue;
-                            }
+            }
+            Err(e) => return Err(e),
+        }
+    }
 
-                            if let Err(e) = DomainTable::save_to_binary(&exe_path) {
-                                eprintln!("Warning: Could not save domain table: {}", e);
-                            }
+    Ok(input_chars)
+}
 
-                            let session = &mut *std::ptr::addr_of_mut!(SESSION);
-                            let active = session.active_counter;
-                            session.saved_counter = active;
-                            session.is_preview_mode = false;
+// the --io pipeline over a whole line, returning what the last keystroke produced.
+// that is the whole password, every keystroke generates it anew from the feedback
+// of all before it, and what term and the extension show too. the line is
+// normalized as a whole before it gets here
+fn generate_io_output(saved_password: &mut SavedPassword, input_chars: &[u32]) -> Wiped<String> {
+    let mut generation = GenerationSession::new(GenerationOptions {
+        compose_marks: false,
+    });
+    let mut output = Wiped(String::new());
+    for &keycode in input_chars {
+        output = generation.step(session(), saved_password, keycode);
+    }
+    output
+}
 
-                            let response =
-                                format!("{{\"counter\":{},\"status\":\"committed\"}}", active);
-                            let response_length = response.len() as u32;
-                            stdout.write_all(&response_length.to_le_bytes())?;
-                            stdout.write_all(response.as_bytes())?;
-                            stdout.flush()?;
-                        } else {
-                            let response = "{\"error\":\"Not in preview mode\"}";
-                            let response_length = response.len() as u32;
-                            stdout.write_all(&response_length.to_le_bytes())?;
-                            stdout.write_all(response.as_bytes())?;
-                            stdout.flush()?;
-                        }
-                    }
-                } else {
-                    let response = "{\"error\":\"Missing domain\"}";
-                    let response_length = response.len() as u32;
-                    stdout.write_all(&response_length.to_le_bytes())?;
-                    stdout.write_all(response.as_bytes())?;
-                    stdout.flush()?;
-                }
-                continue;
-            } else if message.contains("\"CANCEL_PREVIEW\"") {
-                unsafe {
-                    let session = &mut *std::ptr::addr_of_mut!(SESSION);
-                    if session.is_preview_mode {
-                        let saved = session.saved_counter;
-                        session.active_counter = saved;
-                        session.is_preview_mode = false;
+fn run_io_mode(args: &[String]) -> io::Result<()> {
+    let mut account_name: Option<String> = None;
+    let mut confirm = false;
+    let mut length: Option<String> = None;
+    let mut words: Option<String> = None;
+    let mut domain: Option<String> = None;
+    let mut qr = false;
+    let mut show = false;
+    let mut preview = false;
+    let mut tty_prompt = false;
+    let mut print0 = false;
+    let mut pin = false;
+    let mut format: Option<String> = None;
+    let mut bytes: Option<String> = None;
+    let mut exec: Option<Vec<String>> = None;
+    let mut fd: Option<String> = None;
 
-                        password_manager.saved_passwords[saved_password_idx]
-                            .structure_system
-                            .full_reset();
-                        feedbacks.clear();
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--account" && i + 1 < args.len() {
+            account_name = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--tty-prompt" {
+            tty_prompt = true;
+            i += 1;
+        } else if args[i] == "--print0" {
+            print0 = true;
+            i += 1;
+        } else if args[i] == "--preview" {
+            preview = true;
+            i += 1;
+        } else if args[i] == "--pin" {
+            pin = true;
+            i += 1;
+        } else if args[i] == "--format" && i + 1 < args.len() {
+            format = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--bytes" && i + 1 < args.len() {
+            bytes = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--length" && i + 1 < args.len() {
+            length = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--words" && i + 1 < args.len() {
+            words = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--domain" && i + 1 < args.len() {
+            domain = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--confirm" {
+            confirm = true;
+            i += 1;
+        } else if args[i] == "--qr" {
+            qr = true;
+            i += 1;
+        } else if args[i] == "--exec" && i + 1 < args.len() {
+            // the rest is the command and its arguments
+            exec = Some(args[i + 1..].to_vec());
+            break;
+        } else if args[i] == "--fd" && i + 1 < args.len() {
+            fd = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--show" {
+            show = true;
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    let format = match format.as_deref() {
+        Some(name) => Some(
+            RawFormat::parse(name)
+                .ok_or_else(|| ErrorCode::Usage.error("--format is hex, base64 or raw"))?,
+        ),
+        None => None,
+    };
+    let byte_count = match bytes.as_deref() {
+        Some(_) if format.is_none() => {
+            return Err(ErrorCode::Usage.error("--bytes goes with --format"));
+        }
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|n| (1..=RawFormat::MAX_BYTES).contains(n))
+            .ok_or_else(|| ErrorCode::Usage.error("--bytes must be 1-1024"))?,
+        None => RawFormat::DEFAULT_BYTES,
+    };
+    if format.is_some() && (length.is_some() || words.is_some() || pin) {
+        return Err(ErrorCode::Usage.error("--format can't be combined with --length or --words"));
+    }
+    if format == Some(RawFormat::Raw) && qr {
+        return Err(ErrorCode::Usage.error("--format raw has no QR code, use hex or base64"));
+    }
+    if exec.is_some() && (qr || print0) {
+        return Err(ErrorCode::Usage.error("--exec can't be combined with --qr or --print0"));
+    }
+    // what follows --exec belongs to the command
+    let own_args = &args[..args
+        .iter()
+        .position(|arg| arg == "--exec")
+        .unwrap_or(args.len())];
+    let type_options = TypeOptions::from_args(own_args)?;
+    if type_options.is_some() && (qr || print0 || exec.is_some() || format.is_some()) {
+        return Err(ErrorCode::Usage
+            .error("--type can't be combined with --qr, --print0, --exec or --format"));
+    }
+    let fd = match fd.as_deref() {
+        Some(_) if exec.is_none() => {
+            return Err(ErrorCode::Usage.error("--fd goes with --exec"));
+        }
+        Some(value) => Some(
+            value
+                .parse::<i32>()
+                .ok()
+                .filter(|&n| n > 2)
+                .ok_or_else(|| ErrorCode::Usage.error("--fd must be 3 or more"))?,
+        ),
+        None => None,
+    };
+
+    // prompts (preview's) go to stderr, or the terminal with --tty-prompt, which then
+    // also answers them
+    let (mut terminal_output, mut terminal_input) = if tty_prompt {
+        let terminal = PromptTerminal::open()?;
+        (Some(terminal.output), Some(terminal.input))
+    } else {
+        (None, None)
+    };
+    let mut stderr = io::stderr();
+    let prompt: &mut dyn Write = match terminal_output.as_mut() {
+        Some(output) => output,
+        None => &mut stderr,
+    };
+    require_unlock(prompt)?;
+
+    // after the unlock secret, which may have been the decoy's
+    let mut password_manager = PasswordManager::new(false, None, false)?;
+
+    let saved_password_idx = domain_account(
+        &mut password_manager,
+        account_name.as_deref(),
+        domain.as_deref(),
+    )?;
+
+    let mut stdin = io::stdin();
+    let mut decoder = Utf8Decoder::new();
+
+    // VERY IMPORTANT
+    // the test sequences used for behavioral testing are SELF_TESTS in lib.rs, with
+    // their pinned hashes. self-test (or SELF_TEST over json-io) runs them
+
+    let mut input_chars = read_io_line(&mut stdin, &mut decoder)?;
+
+    password_manager.saved_passwords[saved_password_idx]
+        .normalization
+        .apply(&mut input_chars);
+
+    if !input_chars.is_empty() {
+        password_manager.record_use(saved_password_idx);
+    }
+
+    let saved_password = &mut password_manager.saved_passwords[saved_password_idx];
+    let rules = cli_output_rules(
+        saved_password,
+        domain.as_deref(),
+        length.as_deref(),
+        words.as_deref(),
+        preview,
+    )?;
+    if pin && unsafe { SESSION.fixed_output }.pin_length() == 0 {
+        let domain = domain.as_deref().unwrap_or("");
+        return Err(ErrorCode::BadRequest.error(format!(
+            "{} has no PIN length, set one with: domains pin {} <n>",
+            domain, domain
+        )));
+    }
+
+    let (saved_counter, preview_counter) = unsafe {
+        let session = &*std::ptr::addr_of!(SESSION);
+        (session.saved_counter, session.active_counter)
+    };
+    if preview {
+        writeln!(
+            prompt,
+            "PREVIEW: generating v{} of {}. The counter is NOT saved yet, the domain stays at v{} until you commit",
+            preview_counter,
+            domain.as_deref().unwrap_or(""),
+            saved_counter
+        )?;
+    }
+
+    if let Some(format) = format {
+        restart_session(session(), &mut saved_password.structure_system);
+        let output = generate_raw_output(saved_password, &input_chars, byte_count);
+
+        if confirm {
+            let mut again = read_io_line(&mut stdin, &mut decoder)?;
+            saved_password.normalization.apply(&mut again);
+            restart_session(session(), &mut saved_password.structure_system);
+            let again = generate_raw_output(saved_password, &again, byte_count);
+
+            if !constant_time_eq(&output, &again) {
+                password_manager.persist_usage();
+                return Err(ErrorCode::ConfirmMismatch.error("The two entries did not match"));
+            }
+        }
+
+        let terminator = if print0 { "\0" } else { "" };
+        if let Some(command) = &exec {
+            match format {
+                RawFormat::Raw => deliver_to_command(command, fd, &output)?,
+                _ => deliver_to_command(command, fd, format.encode(&output).as_bytes())?,
+            }
+        } else if format == RawFormat::Raw {
+            let mut stdout = io::stdout();
+            stdout.write_all(&output)?;
+            stdout.write_all(terminator.as_bytes())?;
+            stdout.flush()?;
+        } else {
+            let text = format.encode(&output);
+            if qr && tty_prompt {
+                print_qr(prompt, &text)?;
+                emit_output(&text, terminator)?;
+            } else if qr {
+                if show {
+                    println!("{}", text.as_str());
+                }
+                print_qr(&mut io::stdout(), &text)?;
+            } else {
+                emit_output(&text, terminator)?;
+            }
+        }
+        password_manager.persist_usage();
+        return Ok(());
+    }
+
+    restart_session(session(), &mut saved_password.structure_system);
+    let output = rules.apply(&generate_io_output(saved_password, &input_chars));
+    // a keystroke gives at most one digit, or a word every so often, so a short line
+    // leaves a PIN or passphrase short
+    let fixed_output = unsafe { SESSION.fixed_output };
+    let (produced, wanted) = fixed_output.progress(&output);
+    if produced < wanted {
+        password_manager.persist_usage();
+        let unit = if fixed_output.pin_length() > 0 {
+            "digits"
+        } else {
+            "words"
+        };
+        return Err(ErrorCode::BadRequest.error(format!(
+            "The line gave {} of the {} {}, type a longer one",
+            produced, wanted, unit
+        )));
+    }
+
+    // the second line gets the same treatment, from the state the first one started in
+    if confirm {
+        let mut again = read_io_line(&mut stdin, &mut decoder)?;
+        saved_password.normalization.apply(&mut again);
+        restart_session(session(), &mut saved_password.structure_system);
+        let again = rules.apply(&generate_io_output(saved_password, &again));
+
+        if !constant_time_eq(output.as_bytes(), again.as_bytes()) {
+            password_manager.persist_usage();
+            return Err(ErrorCode::ConfirmMismatch.error("The two entries did not match"));
+        }
+    }
+
+    let terminator = if print0 { "\0" } else { "" };
+    if let Some(options) = type_options {
+        type_output(&output, options, prompt)?;
+    } else if let Some(command) = &exec {
+        deliver_to_command(command, fd, output.as_bytes())?;
+    } else if qr && tty_prompt {
+        print_qr(prompt, &output)?;
+        emit_output(&output, terminator)?;
+    } else if qr {
+        if show {
+            println!("{}", output.as_str());
+        }
+        print_qr(&mut io::stdout(), &output)?;
+    } else {
+        emit_output(&output, terminator)?;
+    }
+    let estimate = OutputEstimate::of(&output);
+
+    diag(
+        Diag::Detail,
+        format_args!(
+            "entropy_bits: {} length: {}",
+            estimate.entropy_bits(),
+            estimate.length
+        ),
+    );
+
+    // the answer is the next line of stdin (the terminal's with --tty-prompt), anything
+    // but yes (or EOF) cancels
+    if let (true, Some(domain)) = (preview, domain.as_deref()) {
+        write!(
+            prompt,
+            "\nCommit v{} for {}? [y/N] ",
+            preview_counter, domain
+        )?;
+        prompt.flush()?;
+        let answer = match terminal_input.as_mut() {
+            Some(input) => read_io_line(input, &mut decoder)?,
+            None => read_io_line(&mut stdin, &mut decoder)?,
+        };
+        let answer: String = answer.iter().filter_map(|&c| char::from_u32(c)).collect();
+
+        if matches!(answer.trim(), "y" | "Y" | "yes") {
+            let structure =
+                &mut password_manager.saved_passwords[saved_password_idx].structure_system;
+            let committed = commit_cli_counter(structure, domain, Some(preview_counter))?;
+            writeln!(prompt, "Committed: {} is now at v{}", domain, committed)?;
+        } else {
+            unsafe {
+                let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                session.active_counter = session.saved_counter;
+                session.is_preview_mode = false;
+            }
+            writeln!(prompt, "Cancelled: {} stays at v{}", domain, saved_counter)?;
+        }
+    }
+
+    password_manager.persist_usage();
+
+    Ok(())
+}
+
+// {"error":{"code":..,"message":..}} plus any extra fields the caller appends
+// the inspection of the configuration `name`, for the inspect command and INSPECT
+fn inspect<S: VaultStorage>(
+    password_manager: &PasswordManager<S>,
+    name: &str,
+) -> io::Result<Inspection> {
+    let Some(idx) = password_manager
+        .saved_passwords
+        .iter()
+        .position(|p| p.name == name)
+    else {
+        if let Some((_, e)) = password_manager.find_unusable(Some(name)) {
+            return Err(ErrorCode::UnusableConfiguration
+                .error(format!("The configuration is damaged: {}", e)));
+        }
+        return Err(ErrorCode::UnknownAccount.error(format!("No configuration named '{}'", name)));
+    };
+    let format_version = password_manager.format_version(idx).unwrap_or(0);
+    Ok(Inspection::of(
+        &password_manager.saved_passwords[idx],
+        format_version,
+    ))
+}
+
+fn error_json(code: ErrorCode, message: &str, extra: &str) -> String {
+    format!(
+        "{{\"error\":{{\"code\":\"{}\",\"message\":\"{}\"}}{}}}",
+        code.code(),
+        escape_json(message),
+        extra
+    )
+}
+
+// formats seconds since the epoch as "YYYY-MM-DD HH:MM UTC"
+fn format_unix_date(secs: u64) -> String {
+    // reproducible configurations keep no creation time
+    if secs == 0 {
+        return "not recorded".to_string();
+    }
+    let days = (secs / 86400) as i64;
+    let seconds_of_day = secs % 86400;
+
+    // civil-from-days, proleptic gregorian calendar
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60
+    )
+}
+
+fn extract_json_string(message: &str, key: &str) -> String {
+    let search = format!("\"{}\":\"", key);
+    if let Some(start) = message.find(&search) {
+        let start_idx = start + search.len();
+        if let Some(end) = message[start_idx..].find('"') {
+            return message[start_idx..start_idx + end].to_string();
+        }
+    }
+    String::new()
+}
+
+// like extract_json_string, but decodes escapes since free text (a whole phrase)
+// can hold quotes, backslashes and \u escaped characters
+fn extract_json_text(message: &str, key: &str) -> Wiped<String> {
+    let search = format!("\"{}\":\"", key);
+    let mut text = Wiped(String::new());
+
+    if let Some(start) = message.find(&search) {
+        let mut chars = message[start + search.len()..].chars();
+        let mut high_surrogate: Option<u32> = None;
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('u') => {
+                        let mut unit = 0u32;
+                        for _ in 0..4 {
+                            let digit = chars.next().and_then(|c| c.to_digit(16)).unwrap_or(0);
+                            unit = (unit << 4) | digit;
+                        }
+                        if (0xD800..0xDC00).contains(&unit) {
+                            high_surrogate = Some(unit);
+                        } else if (0xDC00..0xE000).contains(&unit) {
+                            if let Some(high) = high_surrogate.take() {
+                                let code = 0x10000 + ((high - 0xD800) << 10) + (unit - 0xDC00);
+                                text.extend(char::from_u32(code));
+                            }
+                        } else {
+                            text.extend(char::from_u32(unit));
+                        }
+                    }
+                    Some(other) => text.push(other),
+                    None => break,
+                },
+                _ => text.push(ch),
+            }
+        }
+    }
+    text
+}
+
+fn extract_json_bool(message: &str, key: &str) -> Option<bool> {
+    let search = format!("\"{}\":", key);
+    let start = message.find(&search)? + search.len();
+    let rest = message[start..].trim_start();
+    if rest.starts_with("true") {
+        Some(true)
+    } else if rest.starts_with("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// a domain's positional rules as the fields of a reply reporting its rules
+fn positions_json(positions: Positions) -> String {
+    format!(
+        "\"starts_with_letter\":{},\"no_leading_symbol\":{},\"no_trailing_symbol\":{}",
+        positions.starts_with_letter, positions.no_leading_symbol, positions.no_trailing_symbol
+    )
+}
+
+// the positional rules `message` gives, the rest as they are in `positions`
+fn json_positions(message: &str, positions: Positions) -> Positions {
+    Positions {
+        starts_with_letter: extract_json_bool(message, "starts_with_letter")
+            .unwrap_or(positions.starts_with_letter),
+        no_leading_symbol: extract_json_bool(message, "no_leading_symbol")
+            .unwrap_or(positions.no_leading_symbol),
+        no_trailing_symbol: extract_json_bool(message, "no_trailing_symbol")
+            .unwrap_or(positions.no_trailing_symbol),
+    }
+}
+
+// the toggles after a domain's max length and char types, as they are printed
+fn rules_text(no_confusables: bool, positions: Positions) -> String {
+    [
+        (no_confusables, ", no confusables"),
+        (positions.starts_with_letter, ", starts with a letter"),
+        (positions.no_leading_symbol, ", no leading symbol"),
+        (positions.no_trailing_symbol, ", no trailing symbol"),
+    ]
+    .iter()
+    .filter(|(on, _)| *on)
+    .map(|(_, text)| *text)
+    .collect()
+}
+
+// the toggles the interactive session's and a scenario's rules take after the
+// numbers, by name. None on one it doesn't know
+fn rule_flags(words: &[&str]) -> Option<(bool, Positions)> {
+    let mut no_confusables = false;
+    let mut positions = Positions::NONE;
+    for word in words {
+        match *word {
+            "no-confusables" => no_confusables = true,
+            "starts-with-letter" => positions.starts_with_letter = true,
+            "no-leading-symbol" => positions.no_leading_symbol = true,
+            "no-trailing-symbol" => positions.no_trailing_symbol = true,
+            _ => return None,
+        }
+    }
+    Some((no_confusables, positions))
+}
+
+// the optional "id" of a request, kept as raw json (string or number) so it can be
+// echoed back verbatim
+fn extract_json_id(message: &str) -> Option<String> {
+    let start = message.find("\"id\":")? + "\"id\":".len();
+    let rest = message[start..].trim_start();
+
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let mut escaped = false;
+        for (i, ch) in quoted.char_indices() {
+            match ch {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => return Some(rest[..i + 2].to_string()),
+                _ => escaped = false,
+            }
+        }
+        None
+    } else {
+        let end = rest.find(&[',', '}'][..])?;
+        let raw = rest[..end].trim();
+        let numeric = !raw.is_empty()
+            && raw
+                .chars()
+                .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'));
+        if numeric || raw == "null" {
+            Some(raw.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+// when the message being answered arrived, for the debug log's timings
+static REQUEST_STARTED: Mutex<Option<std::time::Instant>> = Mutex::new(None);
+
+// what a keystroke's reply is held to: it goes out at the first multiple of this
+// after the message arrived, so how long generating took (the characters it made,
+// or how much an undo replayed) doesn't show in when the browser gets it
+const KEYSTROKE_QUANTUM: std::time::Duration = std::time::Duration::from_millis(20);
+
+// --no-padding: answer keystrokes as soon as they are generated, for benchmarking
+static NO_PADDING: AtomicBool = AtomicBool::new(false);
+
+fn pad_keystroke_reply() {
+    if NO_PADDING.load(Ordering::SeqCst) {
+        return;
+    }
+    let Some(started) = REQUEST_STARTED.lock().ok().and_then(|started| *started) else {
+        return;
+    };
+    let quanta = started.elapsed().as_nanos() / KEYSTROKE_QUANTUM.as_nanos() + 1;
+    let due = started + KEYSTROKE_QUANTUM * quanta as u32;
+    // Instant is monotonic, a clock change can't stretch or skip this
+    std::thread::sleep(due.saturating_duration_since(std::time::Instant::now()));
+}
+
+// message types the host knows. anything else is logged as "unsupported" so a
+// stray field can't smuggle content into the log
+const JSON_IO_MESSAGE_TYPES: [&str; 28] = [
+    "VERSION",
+    "PING",
+    "INIT",
+    "RESET",
+    "UNDO_CHAR",
+    "FINALIZE",
+    "GENERATE",
+    "GENERATE_USERNAME",
+    "GET_COUNTER",
+    "GET_RULES",
+    "PEEK_COUNTER",
+    "ACTIVATE",
+    "ACTIVATE_PREVIEW",
+    "REGISTER_DOMAIN",
+    "SET_COUNTER",
+    "SET_RULES",
+    "SET_PIN",
+    "SET_WORDS",
+    "COMMIT_INCREMENT",
+    "SET_TIMEOUT",
+    "LIST_ACCOUNTS",
+    "SELECT_ACCOUNT",
+    "CANCEL_PREVIEW",
+    "KEEPALIVE",
+    "CONFIRM",
+    "SELF_TEST",
+    "UNLOCK",
+    "INSPECT",
+];
+
+// frames one json-io reply for the browser, with the request id as its first field.
+// replies that don't answer a request carry "id":null
+fn send_response(output: &mut impl Write, id: Option<&str>, json: &str) -> io::Result<()> {
+    let body = json.strip_prefix('{').unwrap_or(json);
+    let separator = if body.starts_with('}') { "" } else { "," };
+    let response = Wiped(format!(
+        "{{\"id\":{}{}{}",
+        id.unwrap_or("null"),
+        separator,
+        body
+    ));
+
+    let response_length = response.len() as u32;
+    output.write_all(&response_length.to_le_bytes())?;
+    output.write_all(response.as_bytes())?;
+    output.flush()?;
+
+    // only our own error codes are read back out of the reply, never its content
+    let status = if json.starts_with("{\"error\"") {
+        extract_json_string(json, "code")
+    } else {
+        "ok".to_string()
+    };
+    let started = REQUEST_STARTED.lock().ok().and_then(|started| *started);
+    if let Some(started) = started {
+        log(
+            LogLevel::Debug,
+            format_args!(
+                "json-io: replied {} after {}us",
+                status,
+                started.elapsed().as_micros()
+            ),
+        );
+    }
+    Ok(())
+}
+
+fn extract_json_number(message: &str, key: &str) -> u64 {
+    let search = format!("\"{}\":", key);
+    if let Some(start) = message.find(&search) {
+        let start_idx = start + search.len();
+        if let Some(end) = message[start_idx..].find(&[',', '}'][..]) {
+            if let Ok(val) = message[start_idx..start_idx + end].trim().parse() {
+                return val;
+            }
+        }
+    }
+    0
+}
+
+// generated usernames, letters only unless the domain asks for digits as well
+const DEFAULT_USERNAME_LENGTH: usize = 10;
+const USERNAME_LENGTHS: std::ops::RangeInclusive<usize> = 4..=32;
+
+// how long an activated session may sit unused before it locks itself
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 5 * 60;
+
+// how long the host waits for any message, KEEPALIVE included, before exiting on
+// its own. keeps hosts orphaned by a crashed browser from piling up
+const DEFAULT_HOST_EXIT_SECS: u64 = 10 * 60;
+
+// bumped whenever the json-io message set or a reply shape changes
+const PROTOCOL_VERSION: u32 = 22;
+
+// what the stdin reader thread hands the json-io loop
+enum JsonFrame {
+    Message(Wiped<Vec<u8>>),
+    Empty,
+    TooLarge,
+    // EOF, a read error, or EOF partway through a message
+    Closed,
+    // nothing arrived within the host exit interval
+    Idle,
+}
+
+// frames tagged with the client they came from: 0 is the browser on stdin, the
+// daemon numbers its connections from 1
+type JsonFrames = std::sync::mpsc::Receiver<(u64, JsonFrame)>;
+
+// reads length-prefixed frames off stdin on its own thread, so the loop can give
+// up waiting without having to interrupt a blocked read
+fn spawn_json_reader() -> JsonFrames {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::spawn(move || read_json_frames(io::stdin(), 0, sender));
+    receiver
+}
+
+// one client's frames until its stream ends. a too large one ends it as well
+fn read_json_frames(
+    mut input: impl Read,
+    client: u64,
+    sender: std::sync::mpsc::Sender<(u64, JsonFrame)>,
+) {
+    loop {
+        let mut length_bytes = [0u8; 4];
+        if read_exact_interruptible(&mut input, &mut length_bytes).is_err() {
+            let _ = sender.send((client, JsonFrame::Closed));
+            return;
+        }
+
+        let message_length = u32::from_le_bytes(length_bytes) as usize;
+
+        let frame = if message_length == 0 {
+            JsonFrame::Empty
+        } else if message_length > MAX_JSON_MESSAGE_LEN {
+            // the framing can't be trusted past a bad prefix, so stop reading
+            let _ = sender.send((client, JsonFrame::TooLarge));
+            let _ = sender.send((client, JsonFrame::Closed));
+            return;
+        } else {
+            let mut message_buffer = Wiped(vec![0u8; message_length]);
+            if read_exact_interruptible(&mut input, &mut message_buffer.0).is_err() {
+                log(
+                    LogLevel::Warn,
+                    format_args!("json-io: stream ended mid-message"),
+                );
+                let _ = sender.send((client, JsonFrame::Closed));
+                return;
+            }
+            JsonFrame::Message(message_buffer)
+        };
+
+        if sender.send((client, frame)).is_err() {
+            return;
+        }
+    }
+}
+
+// waits for the next frame, checking for a shutdown signal in between since the
+// signal may land on the reader thread instead. 0 never times out. the end of
+// everything (a signal, the timeout) comes as client 0's
+fn receive_frame(frames: &JsonFrames, exit_after_secs: u64) -> (u64, JsonFrame) {
+    let started = std::time::Instant::now();
+    loop {
+        if shutdown_requested() {
+            return (0, JsonFrame::Closed);
+        }
+        if exit_after_secs > 0 && started.elapsed().as_secs() >= exit_after_secs {
+            return (0, JsonFrame::Idle);
+        }
+        match frames.recv_timeout(std::time::Duration::from_millis(250)) {
+            Ok(frame) => return frame,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return (0, JsonFrame::Closed),
+        }
+    }
+}
+
+// where json-io replies go: stdout for the browser, or for the daemon the connection
+// the request came in on. a client that went away is skipped rather than failing the
+// loop, so one closed connection can't take the others down with it
+enum JsonReplies {
+    Stdout(io::Stdout),
+    Clients {
+        streams: Arc<Mutex<HashMap<u64, DaemonStream>>>,
+        current: u64,
+    },
+}
+
+impl JsonReplies {
+    fn select(&mut self, client: u64) {
+        if let JsonReplies::Clients { current, .. } = self {
+            *current = client;
+        }
+    }
+
+    fn forget(&mut self, client: u64) {
+        if let JsonReplies::Clients { streams, .. } = self {
+            if let Ok(mut streams) = streams.lock() {
+                streams.remove(&client);
+            }
+        }
+    }
+}
+
+impl Write for JsonReplies {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            JsonReplies::Stdout(stdout) => stdout.write(buf),
+            JsonReplies::Clients { streams, current } => {
+                let Ok(mut streams) = streams.lock() else {
+                    return Ok(buf.len());
+                };
+                if let Some(stream) = streams.get_mut(current) {
+                    if stream.write_all(buf).is_err() {
+                        streams.remove(current);
+                    }
+                }
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            JsonReplies::Stdout(stdout) => stdout.flush(),
+            JsonReplies::Clients { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+type DaemonStream = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type DaemonStream = std::net::TcpStream;
+
+// where the daemon for this vault (each profile has its own) can be found: its socket
+// on unix, in a directory only we can enter. windows gets a file with the loopback
+// port and a token, since anyone local can connect to a port
+fn daemon_address() -> io::Result<PathBuf> {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    vault_path()?.hash(&mut hasher);
+    let name = format!("{:016x}", hasher.finish());
+
+    let dir = config_dir()?.join("daemon");
+    #[cfg(unix)]
+    return Ok(dir.join(name + ".sock"));
+    #[cfg(windows)]
+    return Ok(dir.join(name + ".addr"));
+}
+
+struct DaemonListener {
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+    #[cfg(windows)]
+    listener: std::net::TcpListener,
+    #[cfg(windows)]
+    token: String,
+    address: PathBuf,
+}
+
+impl DaemonListener {
+    #[cfg(unix)]
+    fn bind() -> io::Result<Self> {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        let address = daemon_address()?;
+        if let Some(dir) = address.parent() {
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)?;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+        if DaemonStream::connect(&address).is_ok() {
+            return Err(ErrorCode::VaultInUse.error("A daemon is already serving this vault"));
+        }
+        // nothing answers on it, so it was left behind by a daemon that didn't exit cleanly
+        let _ = fs::remove_file(&address);
+
+        let listener = std::os::unix::net::UnixListener::bind(&address)?;
+        Ok(DaemonListener { listener, address })
+    }
+
+    #[cfg(windows)]
+    fn bind() -> io::Result<Self> {
+        let address = daemon_address()?;
+        if let Some(dir) = address.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if DaemonClient::connect().is_some() {
+            return Err(ErrorCode::VaultInUse.error("A daemon is already serving this vault"));
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let token = handshake_token()?;
+        fs::write(&address, format!("{} {}", listener.local_addr()?, token))?;
+        Ok(DaemonListener {
+            listener,
+            token,
+            address,
+        })
+    }
+
+    // the next client. on windows it has to open with the token from the address file
+    fn accept(&self) -> io::Result<DaemonStream> {
+        let (stream, _) = self.listener.accept()?;
+        // a client that stops reading can only hold the others up this long
+        stream.set_write_timeout(Some(std::time::Duration::from_secs(5)))?;
+
+        #[cfg(windows)]
+        {
+            stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+            let mut token = [0u8; 32];
+            (&stream).read_exact(&mut token)?;
+            if !constant_time_eq(&token, self.token.as_bytes()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "the client did not present the daemon's token",
+                ));
+            }
+            stream.set_read_timeout(None)?;
+        }
+        Ok(stream)
+    }
+}
+
+// a connection to this vault's daemon, for --via-daemon's requests or to relay the
+// browser's frames when json-io finds one running
+struct DaemonClient {
+    stream: DaemonStream,
+    next_id: u64,
+}
+
+impl DaemonClient {
+    // None when no daemon answers for this vault
+    fn connect() -> Option<Self> {
+        let address = daemon_address().ok()?;
+
+        #[cfg(unix)]
+        let stream = DaemonStream::connect(&address).ok()?;
+        #[cfg(windows)]
+        let stream = {
+            let published = fs::read_to_string(&address).ok()?;
+            let (port, token) = published.trim().split_once(' ')?;
+            let mut stream = DaemonStream::connect(port).ok()?;
+            stream.write_all(token.as_bytes()).ok()?;
+            stream
+        };
+
+        Some(DaemonClient { stream, next_id: 1 })
+    }
+
+    // sends one json-io message and waits for its reply. an {"error":...} reply comes
+    // back as that error, with the daemon's code and message
+    fn request(&mut self, message: &str) -> io::Result<Wiped<String>> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let body = message.strip_prefix('{').unwrap_or(message);
+        let framed = Wiped(format!("{{\"id\":{},{}", id, body));
+        self.stream
+            .write_all(&(framed.len() as u32).to_le_bytes())?;
+        self.stream.write_all(framed.as_bytes())?;
+        self.stream.flush()?;
+
+        let answer = format!("{{\"id\":{},", id);
+        let failure = format!("{{\"id\":{},\"error\":", id);
+        loop {
+            let mut length_bytes = [0u8; 4];
+            read_exact_interruptible(&mut self.stream, &mut length_bytes)?;
+            let length = u32::from_le_bytes(length_bytes) as usize;
+            if length > MAX_JSON_MESSAGE_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The daemon sent an oversized reply",
+                ));
+            }
+            let mut bytes = Wiped(vec![0u8; length]);
+            read_exact_interruptible(&mut self.stream, &mut bytes.0)?;
+            let reply = match String::from_utf8(std::mem::take(&mut bytes.0)) {
+                Ok(reply) => Wiped(reply),
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "The daemon sent a reply that is not UTF-8",
+                    ))
+                }
+            };
+
+            // "id":null replies answer nothing we asked
+            if !reply.starts_with(&answer) {
+                continue;
+            }
+            if reply.starts_with(&failure) {
+                let code = extract_json_string(&reply, "code");
+                let code = ErrorCode::ALL
+                    .into_iter()
+                    .find(|known| known.code() == code)
+                    .unwrap_or(ErrorCode::BadRequest);
+                return Err(code.error(extract_json_text(&reply, "message").to_string()));
+            }
+            return Ok(reply);
+        }
+    }
+
+    // joins json-io's stdin and stdout to the daemon, passing frames through as they
+    // are, until either side closes
+    fn relay(self) -> io::Result<()> {
+        let mut upstream = self.stream.try_clone()?;
+        thread::spawn(move || {
+            let _ = io::copy(&mut io::stdin(), &mut upstream);
+            // the browser is gone, so the daemon drops this client and closes its end
+            let _ = upstream.shutdown(std::net::Shutdown::Write);
+        });
+
+        let mut downstream = self.stream;
+        let mut stdout = io::stdout();
+        let mut buffer = [0u8; 8192];
+        loop {
+            let read = match downstream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            stdout.write_all(&buffer[..read])?;
+            stdout.flush()?;
+            zero_memory(&mut buffer[..read]);
+        }
+        Ok(())
+    }
+}
+
+// the whole seconds given to --idle-timeout or --exit-after
+fn seconds_value(flag: &str, value: &str) -> io::Result<u64> {
+    value
+        .parse()
+        .map_err(|_| usage_error(format!("{} takes whole seconds, not '{}'", flag, value)))
+}
+
+// --daemon: one json-io host for every client of the vault's socket, so the vault is
+// loaded once and all writes to it go through here. each connection gets its own
+// sessions. runs until a signal, or --exit-after seconds without a message
+fn run_daemon_mode(args: &[String]) -> io::Result<()> {
+    InstanceLock::set_holder("the daemon");
+    let mut account_name: Option<String> = None;
+    let mut idle_timeout_secs = DEFAULT_IDLE_TIMEOUT_SECS;
+    let mut exit_after_secs = 0;
+
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--account" && i + 1 < args.len() {
+            account_name = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--idle-timeout" && i + 1 < args.len() {
+            idle_timeout_secs = seconds_value(&args[i], &args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--exit-after" && i + 1 < args.len() {
+            exit_after_secs = seconds_value(&args[i], &args[i + 1])?;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    let listener = DaemonListener::bind()?;
+    let address = listener.address.clone();
+    let streams: Arc<Mutex<HashMap<u64, DaemonStream>>> = Arc::new(Mutex::new(HashMap::new()));
+    let (sender, frames) = std::sync::mpsc::channel();
+
+    let accepted = Arc::clone(&streams);
+    thread::spawn(move || {
+        let mut next_client = 1u64;
+        loop {
+            let stream = match listener.accept() {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log(
+                        LogLevel::Warn,
+                        format_args!("daemon: refused a connection: {}", e),
+                    );
+                    thread::sleep(std::time::Duration::from_millis(100));
+                    continue;
+                }
+            };
+            let reader = match stream.try_clone() {
+                Ok(reader) => reader,
+                Err(_) => continue,
+            };
+
+            let client = next_client;
+            next_client += 1;
+            if let Ok(mut streams) = accepted.lock() {
+                streams.insert(client, stream);
+            }
+            let sender = sender.clone();
+            thread::spawn(move || read_json_frames(reader, client, sender));
+            log(
+                LogLevel::Debug,
+                format_args!("daemon: client {} connected", client),
+            );
+        }
+    });
+
+    diag(
+        Diag::Note,
+        format_args!("Daemon listening on {}", address.display()),
+    );
+    let served = serve_json_io(
+        account_name.as_deref(),
+        idle_timeout_secs,
+        exit_after_secs,
+        frames,
+        JsonReplies::Clients {
+            streams,
+            current: 0,
+        },
+    );
+    let _ = fs::remove_file(&address);
+    served
+}
+
+// --via-daemon: the daemon to send the command to, or None to run it directly, when
+// none is listening or the command (or one of its options) has no json-io equivalent
+fn daemon_for(cli: &Cli) -> io::Result<Option<DaemonClient>> {
+    let covered = match &cli.command {
+        CliCommand::Io => {
+            let mut options = cli.command_args.iter();
+            let mut covered = true;
+            while let Some(option) = options.next() {
+                match option.as_str() {
+                    "--domain" | "--length" => {
+                        options.next();
+                    }
+                    "--print0" => {}
+                    _ => covered = false,
+                }
+            }
+            covered
+        }
+        CliCommand::Domains(
+            DomainsCommand::Get(_)
+            | DomainsCommand::Set(..)
+            | DomainsCommand::Pin(..)
+            | DomainsCommand::Words(..)
+            | DomainsCommand::Rules(..),
+        ) => true,
+        _ => false,
+    };
+    // the daemon writes for all its clients, one of them can't opt out with --no-persist.
+    // and a locked vault is unlocked on the terminal, which only running directly does.
+    // it works on the real table, so under duress it isn't asked either
+    let locked = DomainTable::shared()
+        .load_from_binary(&vault_path()?)
+        .is_ok()
+        && DomainTable::shared().get_unlock().is_some();
+    if !covered
+        || locked
+        || DURESS.load(Ordering::SeqCst)
+        || cli.use_domain_counter.is_some()
+        || NO_PERSIST.load(Ordering::SeqCst)
+    {
+        diag(
+            Diag::Detail,
+            format_args!("--via-daemon does not cover this, running directly"),
+        );
+        return Ok(None);
+    }
+
+    let Some(mut daemon) = DaemonClient::connect() else {
+        diag(
+            Diag::Detail,
+            format_args!("No daemon is running, running directly"),
+        );
+        return Ok(None);
+    };
+    // the daemon serves one configuration, --account has to name that one
+    if let Some(account) = &cli.account {
+        let version = daemon.request("{\"type\":\"VERSION\"}")?;
+        if extract_json_text(&version, "account").as_str() != account {
+            diag(
+                Diag::Detail,
+                format_args!("The daemon serves another configuration, running directly"),
+            );
+            return Ok(None);
+        }
+    }
+    Ok(Some(daemon))
+}
+
+// io over the daemon: the line goes to GENERATE, at the domain's session when there
+// is one, with the same rules and checks applied to what comes back
+fn run_io_via_daemon(daemon: &mut DaemonClient, options: &[String]) -> io::Result<()> {
+    let option = |name: &str| {
+        options
+            .iter()
+            .position(|arg| arg == name)
+            .and_then(|i| options.get(i + 1))
+    };
+    let print0 = options.iter().any(|arg| arg == "--print0");
+
+    let input_chars = read_io_line(&mut io::stdin(), &mut Utf8Decoder::new())?;
+    let text = Wiped(
+        input_chars
+            .iter()
+            .filter_map(|&keycode| char::from_u32(keycode))
+            .collect::<String>(),
+    );
+
+    let (mut rules, fixed_output) = match option("--domain") {
+        Some(domain) => {
+            let reply = daemon.request(&format!(
+                "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\",\"register\":false}}",
+                escape_json(domain)
+            ))?;
+            let fixed_output = match (
+                extract_json_number(&reply, "pin_length") as u8,
+                extract_json_number(&reply, "word_count") as u8,
+            ) {
+                (0, 0) => FixedOutput::Password,
+                (0, words) => FixedOutput::Words(words),
+                (digits, _) => FixedOutput::Pin(digits),
+            };
+            let rules = OutputRules {
+                max_length: extract_json_number(&reply, "max_length") as u16,
+                char_types: extract_json_number(&reply, "char_types") as u8,
+                no_confusables: extract_json_bool(&reply, "no_confusables") == Some(true),
+                positions: json_positions(&reply, Positions::NONE),
+            };
+            (rules, fixed_output)
+        }
+        None => (OutputRules::NONE, FixedOutput::Password),
+    };
+    if let Some(length) = option("--length") {
+        rules.max_length = length
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| ErrorCode::Usage.error("--length must be 1-65535"))?;
+    }
+
+    let reply = daemon.request(&Wiped(format!(
+        "{{\"type\":\"GENERATE\",\"text\":\"{}\"}}",
+        escape_json(&text)
+    )))?;
+    let output = rules.apply(&extract_json_text(&reply, "output"));
+
+    let (produced, wanted) = fixed_output.progress(&output);
+    if produced < wanted {
+        let unit = if fixed_output.pin_length() > 0 {
+            "digits"
+        } else {
+            "words"
+        };
+        return Err(ErrorCode::BadRequest.error(format!(
+            "The line gave {} of the {} {}, type a longer one",
+            produced, wanted, unit
+        )));
+    }
+
+    emit_output(&output, if print0 { "\0" } else { "" })?;
+    let estimate = OutputEstimate::of(&output);
+    diag(
+        Diag::Detail,
+        format_args!(
+            "entropy_bits: {} length: {}",
+            estimate.entropy_bits(),
+            estimate.length
+        ),
+    );
+    Ok(())
+}
+// how many tabs can keep a parked session in one host before the least recently
+// used one is dropped
+const MAX_TAB_SESSIONS: usize = 16;
+
+// the state of a tab that is not the one currently talking to the host. the structure
+// position isn't stored, it is rebuilt by replaying what was typed on the way back
+struct TabSession {
+    session: SessionState,
+    generation: GenerationSession,
+    // the configuration its domain generates with, see PasswordManager::resolve_domain
+    saved_password_idx: usize,
+    last_used: u64,
+}
+
+impl Drop for TabSession {
+    fn drop(&mut self) {
+        self.session.scrub();
+    }
+}
+
+// chrome never sends more than this to a host, so a larger prefix is a corrupt stream
+const MAX_JSON_MESSAGE_LEN: usize = 1024 * 1024;
+
+fn run_json_io_mode(args: &[String]) -> io::Result<()> {
+    InstanceLock::set_holder("the browser host");
+    let mut account_name: Option<String> = None;
+    let mut idle_timeout_secs = DEFAULT_IDLE_TIMEOUT_SECS;
+    let mut exit_after_secs = DEFAULT_HOST_EXIT_SECS;
+
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--account" && i + 1 < args.len() {
+            account_name = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--idle-timeout" && i + 1 < args.len() {
+            idle_timeout_secs = seconds_value(&args[i], &args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--exit-after" && i + 1 < args.len() {
+            exit_after_secs = seconds_value(&args[i], &args[i + 1])?;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    // a running daemon owns the vault, the browser becomes one more of its clients
+    if let Some(daemon) = DaemonClient::connect() {
+        log(
+            LogLevel::Info,
+            format_args!("json-io: relaying to the daemon"),
+        );
+        return daemon.relay();
+    }
+
+    serve_json_io(
+        account_name.as_deref(),
+        idle_timeout_secs,
+        exit_after_secs,
+        spawn_json_reader(),
+        JsonReplies::Stdout(io::stdout()),
+    )
+}
+
+// the json-io protocol over `frames`, answering through `replies`: the browser's one
+// session for json-io, every connected client's for the daemon
+fn serve_json_io(
+    account_name: Option<&str>,
+    mut idle_timeout_secs: u64,
+    exit_after_secs: u64,
+    frames: JsonFrames,
+    mut replies: JsonReplies,
+) -> io::Result<()> {
+    let exe_path = vault_path()?;
+
+    // held until we return, so CLI writes can't race the extension's session
+    let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::HOST_WAIT)?;
+
+    let mut password_manager = PasswordManager::new(false, None, true)?;
+
+    // Load domain table from binary on startup
+    if let Err(e) = DomainTable::shared().load_from_binary(&exe_path) {
+        diag(
+            Diag::Warning,
+            format_args!("Warning: Could not load domain table: {}", e),
+        );
+    }
+
+    let mut rate_limiter = RateLimiter::new(DomainTable::shared().get_rate_limits());
+
+    // with an unlock secret nothing is generated until UNLOCK gives it or the decoy's,
+    // and the idle lock asks for it again. wrong ones from before still count toward
+    // the delay. the host holds the vault, so neither secret changes while it runs
+    let (stored_unlock, decoy_unlock) = unlock_verifiers(&exe_path)?;
+    let mut unlock_failures = stored_unlock.map_or(0, |u| u.failures);
+    let mut vault_unlocked = stored_unlock.is_none();
+    let mut unlock_retry_at = std::time::Instant::now() + unlock_delay(unlock_failures);
+
+    log(
+        LogLevel::Info,
+        format_args!(
+            "json-io: host started, protocol {}, idle timeout {}s, exit after {}s",
+            PROTOCOL_VERSION, idle_timeout_secs, exit_after_secs
+        ),
+    );
+
+    // SELECT_ACCOUNT can move this, the native messaging manifest can't pass --account
+    // the one picked, and the one generating, which for a domain registered before a
+    // rekey is the configuration it was registered with
+    let mut account_idx = select_account(&password_manager, account_name)?;
+    let mut saved_password_idx = account_idx;
+
+    password_manager.saved_passwords[saved_password_idx]
+        .structure_system
+        .reset_position();
+
+    let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    // messages carrying a "session" id get their own state, the rest share ""
+    let mut active_session = String::new();
+    let mut tab_sessions: HashMap<String, TabSession> = HashMap::new();
+    let mut session_tick = 0u64;
+    let mut last_message = std::time::Instant::now();
+    let mut session_locked = false;
+    // first entry of a CONFIRM, held only until the retyped one is checked against it
+    let mut confirm_reference: Option<Wiped<String>> = None;
+
+    // a failed write means the browser is gone too, which still has to reach the
+    // teardown below rather than returning past it
+    let outcome = (|| -> io::Result<()> {
+        loop {
+            let (client, frame) = receive_frame(&frames, exit_after_secs);
+            replies.select(client);
+
+            let message_buffer = match frame {
+                JsonFrame::Message(mut buffer) => std::mem::take(&mut buffer.0),
+                JsonFrame::Empty => {
+                    let response = error_json(ErrorCode::BadRequest, "Empty message", "");
+                    send_response(&mut replies, None, &response)?;
+                    continue;
+                }
+                JsonFrame::TooLarge => {
+                    let response = error_json(
+                        ErrorCode::MessageTooLarge,
+                        "Message exceeds the native messaging limit",
+                        &format!(",\"limit\":{}", MAX_JSON_MESSAGE_LEN),
+                    );
+                    send_response(&mut replies, None, &response)?;
+                    // a daemon client's stream closes right after, see below
+                    if client == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                // a daemon client going away takes its sessions along, the rest carry on
+                JsonFrame::Closed if client != 0 => {
+                    let prefix = format!("{}:", client);
+                    tab_sessions.retain(|key, _| !key.starts_with(&prefix));
+                    if active_session.starts_with(&prefix) {
+                        password_manager.saved_passwords[saved_password_idx]
+                            .structure_system
+                            .full_reset();
+                        generation.clear();
+                        unsafe {
+                            let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                            session.scrub();
+                        }
+                        active_session.clear();
+                        confirm_reference = None;
+                    }
+                    replies.forget(client);
+                    log(
+                        LogLevel::Debug,
+                        format_args!("json-io: client {} disconnected", client),
+                    );
+                    continue;
+                }
+                JsonFrame::Closed => {
+                    log(LogLevel::Info, format_args!("json-io: input closed"));
+                    break;
+                }
+                JsonFrame::Idle => {
+                    log(
+                        LogLevel::Info,
+                        format_args!("json-io: no message for {}s", exit_after_secs),
+                    );
+                    break;
+                }
+            };
+
+            let message = match String::from_utf8(message_buffer) {
+                Ok(s) => Wiped(s),
+                Err(e) => {
+                    let mut bytes = e.into_bytes();
+                    log(
+                        LogLevel::Warn,
+                        format_args!(
+                            "json-io: dropped a {} byte message that is not UTF-8",
+                            bytes.len()
+                        ),
+                    );
+                    bytes.wipe();
+                    continue;
+                }
+            };
+            let id = extract_json_id(&message);
+
+            if let Ok(mut started) = REQUEST_STARTED.lock() {
+                *started = Some(std::time::Instant::now());
+            }
+            let message_type = extract_json_string(&message, "type");
+            let kind = if message_type.is_empty() {
+                if message.contains("\"charCode\"") {
+                    "keystroke"
+                } else {
+                    "untyped"
+                }
+            } else {
+                JSON_IO_MESSAGE_TYPES
+                    .iter()
+                    .find(|&&known| known == message_type)
+                    .copied()
+                    .unwrap_or("unsupported")
+            };
+            log(LogLevel::Debug, format_args!("json-io: received {}", kind));
+
+            let is_activate =
+                message.contains("\"ACTIVATE\"") || message.contains("\"ACTIVATE_PREVIEW\"");
+            // FINALIZE is always let through so a locked out tab can still tear down
+            let decision = if message.contains("\"FINALIZE\"") {
+                RateDecision::Allowed
+            } else {
+                rate_limiter.check(is_activate)
+            };
+            match decision {
+                RateDecision::Allowed => {}
+                RateDecision::Limited => {
+                    log(
+                        LogLevel::Info,
+                        format_args!("json-io: rate limited a message"),
+                    );
+                    let response = error_json(
+                        ErrorCode::RateLimited,
+                        "Too many requests",
+                        ",\"retry_after\":1,\"activate\":false",
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                }
+                RateDecision::LockedOut { retry_after } => {
+                    log(
+                        LogLevel::Warn,
+                        format_args!("json-io: locked out, retry after {}s", retry_after),
+                    );
+                    // whatever was probing gets no further with the current session
+                    unsafe {
+                        let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                        session.scrub();
+                    }
+                    password_manager.saved_passwords[saved_password_idx]
+                        .structure_system
+                        .full_reset();
+                    generation.clear();
+                    tab_sessions.clear();
+                    confirm_reference = None;
+
+                    let response = error_json(
+                        ErrorCode::RateLimited,
+                        "Too many requests, activate again",
+                        &format!(",\"retry_after\":{},\"activate\":true", retry_after),
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                }
+            }
+
+            // answered before the idle lock so it neither counts as activity nor gets refused
+            if message_type == "KEEPALIVE" {
+                send_response(&mut replies, id.as_deref(), "{\"status\":\"alive\"}")?;
+                continue;
+            }
+
+            // 0 disables the idle lock entirely
+            let idle = last_message.elapsed();
+            last_message = std::time::Instant::now();
+
+            if idle_timeout_secs > 0 && idle.as_secs() >= idle_timeout_secs && !session_locked {
+                unsafe {
+                    let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                    session.scrub();
+                }
+                password_manager.saved_passwords[saved_password_idx]
+                    .structure_system
+                    .full_reset();
+                generation.clear();
+                tab_sessions.clear();
+                confirm_reference = None;
+                session_locked = true;
+                vault_unlocked = stored_unlock.is_none();
+                log(
+                    LogLevel::Info,
+                    format_args!("json-io: session locked after {}s idle", idle.as_secs()),
+                );
+            }
+
+            if message_type == "UNLOCK" {
+                let response = match stored_unlock {
+                    Some(unlock) if !vault_unlocked => {
+                        let now = std::time::Instant::now();
+                        if now < unlock_retry_at {
+                            let retry_after = (unlock_retry_at - now).as_secs_f64().ceil() as u64;
+                            error_json(
+                                ErrorCode::VaultLocked,
+                                "Too many wrong unlock secrets",
+                                &format!(",\"retry_after\":{}", retry_after),
+                            )
+                        } else {
+                            let secret = extract_json_text(&message, "secret");
+                            let opened =
+                                opened_by(&unlock, decoy_unlock.as_ref(), secret.as_bytes());
+                            let right = opened.is_some();
+                            if let Err(e) = count_unlock_attempt(&exe_path, right) {
+                                log(
+                                    LogLevel::Warn,
+                                    format_args!("json-io: unlock attempt not counted: {}", e),
+                                );
+                            }
+                            unlock_failures = if right {
+                                0
+                            } else {
+                                unlock_failures.saturating_add(1)
+                            };
+                            let failures = unlock_failures;
+                            let delay = unlock_delay(failures);
+                            // from when the check finished, which took a while itself
+                            unlock_retry_at = std::time::Instant::now() + delay;
+                            vault_unlocked = right;
+                            // nothing was generated while locked, so the decoy can
+                            // take over the configurations without anything to carry
+                            if opened == Some(true) && !DURESS.load(Ordering::SeqCst) {
+                                enter_duress(&exe_path)?;
+                                password_manager = PasswordManager::new(false, None, true)?;
+                                password_manager.saved_passwords[saved_password_idx]
+                                    .structure_system
+                                    .reset_position();
+                            }
+                            if right {
+                                log(LogLevel::Info, format_args!("json-io: vault unlocked"));
+                                "{\"status\":\"success\",\"locked\":false}".to_string()
+                            } else {
+                                log(
+                                    LogLevel::Warn,
+                                    format_args!(
+                                        "json-io: wrong unlock secret, {} in a row",
+                                        failures
+                                    ),
+                                );
+                                error_json(
+                                    ErrorCode::VaultLocked,
+                                    "Wrong unlock secret",
+                                    &format!(",\"retry_after\":{}", delay.as_secs()),
+                                )
+                            }
+                        }
+                    }
+                    _ => "{\"status\":\"success\",\"locked\":false}".to_string(),
+                };
+                send_response(&mut replies, id.as_deref(), &response)?;
+                continue;
+            }
+
+            // VERSION and PING only describe the host, FINALIZE lets a tab tear down
+            if !vault_unlocked && !matches!(message_type.as_str(), "VERSION" | "PING" | "FINALIZE")
+            {
+                let response = error_json(ErrorCode::VaultLocked, "Unlock the vault first", "");
+                send_response(&mut replies, id.as_deref(), &response)?;
+                continue;
+            }
+
+            if session_locked {
+                // VERSION and PING only describe the host, so they still answer while locked
+                let allowed = message.contains("\"ACTIVATE\"")
+                    || message.contains("\"ACTIVATE_PREVIEW\"")
+                    || message.contains("\"FINALIZE\"")
+                    || message.contains("\"SET_TIMEOUT\"")
+                    || message.contains("\"VERSION\"")
+                    || message.contains("\"PING\"");
+
+                if !allowed {
+                    let response = error_json(
+                        ErrorCode::SessionLocked,
+                        "Session locked after being idle",
+                        "",
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                }
+            }
+
+            // a daemon client's sessions are its own, whatever it calls them
+            let session_key = match client {
+                0 => extract_json_string(&message, "session"),
+                client => format!("{}:{}", client, extract_json_string(&message, "session")),
+            };
+            session_tick += 1;
+
+            if session_key != active_session {
+                unsafe {
+                    let session = &mut *std::ptr::addr_of_mut!(SESSION);
+
+                    if session.initialized || !generation.typed().is_empty() {
+                        let parked = TabSession {
+                            session: std::mem::replace(session, SessionState::empty()),
+                            generation: std::mem::replace(
+                                &mut generation,
+                                GenerationSession::new(GenerationOptions::KEYSTROKES),
+                            ),
+                            saved_password_idx,
+                            last_used: session_tick,
+                        };
+                        tab_sessions.insert(active_session.clone(), parked);
+                    } else {
+                        session.scrub();
+                        generation.clear();
+                    }
+
+                    saved_password_idx = account_idx;
+                    if let Some(mut restored) = tab_sessions.remove(&session_key) {
+                        *session = std::mem::replace(&mut restored.session, SessionState::empty());
+                        std::mem::swap(&mut generation, &mut restored.generation);
+                        saved_password_idx = restored.saved_password_idx;
+                    }
+                }
+
+                while tab_sessions.len() > MAX_TAB_SESSIONS {
+                    let oldest = tab_sessions
+                        .iter()
+                        .min_by_key(|(_, parked)| parked.last_used)
+                        .map(|(key, _)| key.clone());
+                    match oldest {
+                        Some(key) => tab_sessions.remove(&key),
+                        None => break,
+                    };
+                }
+
+                // back to where this tab left off. a confirm belongs to the tab that started it
+                let saved_password = &mut password_manager.saved_passwords[saved_password_idx];
+                let _ = generation.replay(session(), saved_password);
+                active_session = session_key;
+                confirm_reference = None;
+                log(
+                    LogLevel::Debug,
+                    format_args!(
+                        "json-io: switched tab session, {} parked",
+                        tab_sessions.len()
+                    ),
+                );
+            }
+
+            if message.contains("\"type\"") {
+                if message.contains("\"VERSION\"") {
+                    // whether the code is still what the vault was sealed with
+                    let integrity = match CODE_SEAL.get() {
+                        Some(Some(true)) => "sealed",
+                        Some(Some(false)) => "changed",
+                        _ => "unsealed",
+                    };
+                    // what others could do to the files, for the extension to warn about
+                    let permissions: Vec<String> = permission_exposures()
+                        .iter()
+                        .map(|(path, exposure)| {
+                            format!(
+                                "{{\"path\":\"{}\",\"exposure\":\"{}\"}}",
+                                escape_json(&path.to_string_lossy()),
+                                exposure.code()
+                            )
+                        })
+                        .collect();
+                    let response = format!(
+                    "{{\"version\":\"{}\",\"protocol\":{},\"account\":\"{}\",\"capabilities\":{{\"supports_rules_enforcement\":false,\"supports_preview\":true,\"supports_idle_lock\":true,\"supports_list_accounts\":true,\"supports_generate\":true,\"supports_register_domain\":true,\"supports_undo\":true,\"supports_sessions\":true,\"supports_keepalive\":true,\"supports_select_account\":true,\"supports_peek_counter\":true,\"supports_confirm\":true,\"supports_no_persist\":true,\"supports_username\":true,\"supports_pin\":true,\"supports_words\":true,\"supports_raw_format\":true,\"supports_self_test\":true,\"supports_unlock\":true,\"supports_inspect\":true,\"supports_no_confusables\":true,\"supports_positions\":true}},\"locked\":{},\"permissions\":[{}],\"integrity\":\"{}\",\"status\":\"{}\"}}",
+                    env!("CARGO_PKG_VERSION"),
+                    PROTOCOL_VERSION,
+                    escape_json(&password_manager.saved_passwords[saved_password_idx].name),
+                    !vault_unlocked,
+                    permissions.join(","),
+                    integrity,
+                    if integrity == "changed" { "degraded" } else { "ok" }
+                );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"PING\"") {
+                    let response = "{\"status\":\"pong\"}";
+                    send_response(&mut replies, id.as_deref(), response)?;
+                    continue;
+                } else if message.contains("\"SELF_TEST\"") {
+                    // a throwaway structure, so the session typing here is untouched
+                    let results: Vec<(&str, bool)> = SELF_TESTS
+                        .iter()
+                        .map(|test| (test.name, test.compute() == test.expected))
+                        .collect();
+                    let sequences: Vec<String> = results
+                        .iter()
+                        .map(|(name, ok)| format!("{{\"name\":\"{}\",\"ok\":{}}}", name, ok))
+                        .collect();
+                    let response = format!(
+                        "{{\"status\":\"success\",\"passed\":{},\"sequences\":[{}]}}",
+                        results.iter().all(|(_, ok)| *ok),
+                        sequences.join(",")
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"INIT\"") {
+                    password_manager.saved_passwords[saved_password_idx]
+                        .structure_system
+                        .full_reset();
+
+                    generation.clear();
+
+                    let response = "{\"status\":\"ready\"}";
+                    send_response(&mut replies, id.as_deref(), response)?;
+                    continue;
+                } else if message.contains("\"RESET\"") {
+                    generation.clear();
+
+                    // Note: RESET only clears geometry and feedbacks, does NOT exit preview mode
+                    // Preview mode state is preserved so user can retype with same counter
+                    restart_session(
+                        session(),
+                        &mut password_manager.saved_passwords[saved_password_idx].structure_system,
+                    );
+
+                    let response = "{\"status\":\"reset\"}";
+                    send_response(&mut replies, id.as_deref(), response)?;
+                    continue;
+                } else if message.contains("\"UNDO_CHAR\"") {
+                    let output_chars = generation.undo(
+                        session(),
+                        &mut password_manager.saved_passwords[saved_password_idx],
+                    );
+
+                    // as a keystroke's reply, plus how many keystrokes are left
+                    let escaped = Wiped(escape_json(&output_chars));
+                    let estimate = OutputEstimate::of(&output_chars);
+                    let response = Wiped(format!(
+                        "{{\"output\":\"{}\",\"entropy_bits\":{},\"length\":{},\"typed\":{}}}",
+                        escaped.as_str(),
+                        estimate.entropy_bits(),
+                        estimate.length,
+                        generation.typed().len()
+                    ));
+                    pad_keystroke_reply();
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"CONFIRM\"") {
+                    let action = extract_json_string(&message, "action");
+
+                    let response = match action.as_str() {
+                        // keeps what was typed so far, then starts over like RESET
+                        "start" => {
+                            let saved_password =
+                                &mut password_manager.saved_passwords[saved_password_idx];
+                            confirm_reference = Some(generation.replay(session(), saved_password));
+
+                            generation.clear();
+                            restart_session(session(), &mut saved_password.structure_system);
+                            "{\"status\":\"confirm_started\"}".to_string()
+                        }
+                        // neither output goes back, only whether they agree
+                        "check" => match confirm_reference.take() {
+                            Some(reference) => {
+                                let retyped = generation.replay(
+                                    session(),
+                                    &mut password_manager.saved_passwords[saved_password_idx],
+                                );
+                                if constant_time_eq(reference.as_bytes(), retyped.as_bytes()) {
+                                    "{\"match\":true,\"status\":\"confirmed\"}".to_string()
+                                } else {
+                                    "{\"match\":false,\"status\":\"mismatch\"}".to_string()
+                                }
+                            }
+                            None => error_json(
+                                ErrorCode::BadRequest,
+                                "No confirm in progress, start one first",
+                                "",
+                            ),
+                        },
+                        "cancel" => {
+                            confirm_reference = None;
+                            "{\"status\":\"confirm_cancelled\"}".to_string()
+                        }
+                        _ => error_json(
+                            ErrorCode::BadRequest,
+                            "CONFIRM needs an action of start, check or cancel",
+                            "",
+                        ),
+                    };
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"FINALIZE\"") {
+                    let response = "{\"status\":\"finalized\"}";
+
+                    // a tab finalizing its own session leaves the other tabs running, as
+                    // a daemon client leaves the daemon
+                    if client != 0 || (!active_session.is_empty() && !tab_sessions.is_empty()) {
+                        password_manager.saved_passwords[saved_password_idx]
+                            .structure_system
+                            .full_reset();
+                        generation.clear();
+                        unsafe {
+                            let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                            session.scrub();
+                        }
+                        send_response(&mut replies, id.as_deref(), response)?;
+                        continue;
+                    }
+
+                    // the teardown after the loop scrubs the session, same as on EOF
+                    send_response(&mut replies, id.as_deref(), response)?;
+                    break;
+                } else if message.contains("\"GET_COUNTER\"") {
+                    let domain = extract_json_string(&message, "domain");
+
+                    let response = if !domain.is_empty() {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+                        match DomainTable::shared().get_counter(&domain, structure) {
+                            Some(counter) => format!("{{\"counter\":{}}}", counter),
+                            None => "{\"counter\":null}".to_string(),
+                        }
+                    } else {
+                        error_json(ErrorCode::MissingDomain, "Missing domain", "")
+                    };
+
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"GET_RULES\"") {
+                    // the rules a session on the domain generates under, as ACTIVATE
+                    // reports them, without activating it
+                    let domain = extract_json_string(&message, "domain");
+
+                    let response = if !domain.is_empty() {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+                        let (rules, fixed_output) =
+                            DomainTable::shared().session_rules(&domain, structure);
+
+                        format!("{{\"max_length\":{},\"char_types\":{},\"pin_length\":{},\"word_count\":{},\"no_confusables\":{},{}}}", rules.max_length, rules.char_types, fixed_output.pin_length(), fixed_output.word_count(), rules.no_confusables, positions_json(rules.positions))
+                    } else {
+                        error_json(ErrorCode::MissingDomain, "Missing domain", "")
+                    };
+
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"PEEK_COUNTER\"") {
+                    // what ACTIVATE_PREVIEW would report, without leaving preview state behind.
+                    // hash_domain puts the structure back where it found it
+                    let domain = extract_json_string(&message, "domain");
+
+                    let response = if !domain.is_empty() {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+                        let saved_counter = DomainTable::shared().get_counter(&domain, structure);
+                        let (max_length, char_types) = DomainTable::shared()
+                            .get_rules(&domain, structure)
+                            .unwrap_or((0, 127));
+                        let counter = saved_counter.unwrap_or(0);
+
+                        format!("{{\"saved_counter\":{},\"next_counter\":{},\"max_length\":{},\"char_types\":{},\"registered\":{}}}", counter, counter.saturating_add(1), max_length, char_types, saved_counter.is_some())
+                    } else {
+                        error_json(ErrorCode::MissingDomain, "Missing domain", "")
+                    };
+
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"ACTIVATE\"")
+                    && !message.contains("\"ACTIVATE_PREVIEW\"")
+                {
+                    let domain = extract_json_string(&message, "domain");
+                    // "register":false previews an unknown domain at counter 0 without using a slot
+                    let register = extract_json_bool(&message, "register").unwrap_or(true);
+                    // "persist":false turns this host ephemeral until it exits, there is no
+                    // going back within the process
+                    if extract_json_bool(&message, "persist") == Some(false) {
+                        NO_PERSIST.store(true, Ordering::SeqCst);
+                    }
+
+                    if !domain.is_empty() {
+                        // a domain registered before a rekey keeps generating with the
+                        // configuration it was registered with until it is migrated
+                        saved_password_idx = password_manager.resolve_domain(
+                            account_idx,
+                            &domain,
+                            &DomainTable::shared(),
+                        );
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+
+                        let mut created = false;
+                        let saved_counter = DomainTable::shared().get_counter(&domain, structure);
+                        let counter = match saved_counter {
+                            Some(c) => c,
+                            None if !register => 0,
+                            None => {
+                                if let Err(e) =
+                                    DomainTable::shared().set_counter(&domain, 0, structure)
+                                {
+                                    let response =
+                                        error_json(ErrorCode::TableFull, &e.to_string(), "");
+                                    send_response(&mut replies, id.as_deref(), &response)?;
+                                    continue;
+                                }
+                                if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                    diag(
+                                        Diag::Warning,
+                                        format_args!("Warning: Could not save domain table: {}", e),
+                                    );
+                                }
+                                created = true;
+                                0
+                            }
+                        };
+
+                        // Default: unlimited length, all types enabled
+                        let (rules, fixed_output) =
+                            DomainTable::shared().session_rules(&domain, structure);
+
+                        // Hash domain and store in session
+                        let domain_hash = structure.hash_domain(&domain);
+
+                        unsafe {
+                            SESSION.active_domain_hash = Some(domain_hash);
+                            SESSION.saved_counter = counter;
+                            SESSION.active_counter = counter;
+                            SESSION.is_preview_mode = false;
+                            SESSION.initialized = true;
+                            SESSION.fixed_output = fixed_output;
+                            SESSION.rules = rules;
+                        }
+                        session_locked = false;
+
+                        generation.clear();
+
+                        // Ghost navigation: Navigate through geometry using domain hash + counter
+                        // This ensures each domain+counter combination starts from a unique position
+                        // WITHOUT producing any output characters
+
+                        restart_session(session(), structure);
+
+                        // Now we're at a unique position in 7D space for this domain+counter
+                        // Subsequent user input will generate from this position
+
+                        // the rules as the session holds its output to them
+                        let response = format!("{{\"saved_counter\":{},\"active_counter\":{},\"max_length\":{},\"char_types\":{},\"pin_length\":{},\"word_count\":{},\"no_confusables\":{},{},\"created\":{},\"status\":\"ready\"}}", counter, counter, rules.max_length, rules.char_types, fixed_output.pin_length(), fixed_output.word_count(), rules.no_confusables, positions_json(rules.positions), created);
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    } else {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    }
+                    continue;
+                } else if message.contains("\"ACTIVATE_PREVIEW\"") {
+                    let domain = extract_json_string(&message, "domain");
+
+                    if !domain.is_empty() {
+                        // a domain registered before a rekey keeps generating with the
+                        // configuration it was registered with until it is migrated
+                        saved_password_idx = password_manager.resolve_domain(
+                            account_idx,
+                            &domain,
+                            &DomainTable::shared(),
+                        );
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+
+                        let saved_counter = DomainTable::shared()
+                            .get_counter(&domain, structure)
+                            .unwrap_or(0);
+                        let preview_counter = saved_counter.saturating_add(1);
+
+                        let (rules, fixed_output) =
+                            DomainTable::shared().session_rules(&domain, structure);
+
+                        let domain_hash = structure.hash_domain(&domain);
+
+                        unsafe {
+                            SESSION.active_domain_hash = Some(domain_hash);
+                            SESSION.saved_counter = saved_counter;
+                            SESSION.active_counter = preview_counter;
+                            SESSION.is_preview_mode = true;
+                            SESSION.initialized = true;
+                            SESSION.fixed_output = fixed_output;
+                            SESSION.rules = rules;
+                        }
+                        session_locked = false;
+
+                        generation.clear();
+                        restart_session(session(), structure);
+
+                        let response = format!("{{\"saved_counter\":{},\"active_counter\":{},\"max_length\":{},\"char_types\":{},\"pin_length\":{},\"word_count\":{},\"no_confusables\":{},{},\"status\":\"preview\"}}", saved_counter, preview_counter, rules.max_length, rules.char_types, fixed_output.pin_length(), fixed_output.word_count(), rules.no_confusables, positions_json(rules.positions));
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    } else {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    }
+                    continue;
+                } else if message.contains("\"REGISTER_DOMAIN\"") {
+                    let domain = extract_json_string(&message, "domain");
+
+                    if !domain.is_empty() {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+
+                        if DomainTable::shared()
+                            .get_counter(&domain, structure)
+                            .is_some()
+                        {
+                            let response = "{\"created\":false,\"status\":\"registered\"}";
+                            send_response(&mut replies, id.as_deref(), response)?;
+                        } else if let Err(e) =
+                            DomainTable::shared().set_counter(&domain, 0, structure)
+                        {
+                            let response = error_json(ErrorCode::TableFull, &e.to_string(), "");
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                        } else {
+                            if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                diag(
+                                    Diag::Warning,
+                                    format_args!("Warning: Could not save domain table: {}", e),
+                                );
+                            }
+                            let response = "{\"created\":true,\"status\":\"registered\"}";
+                            send_response(&mut replies, id.as_deref(), response)?;
+                        }
+                    } else {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    }
+                    continue;
+                } else if message.contains("\"SET_COUNTER\"") {
+                    let domain = extract_json_string(&message, "domain");
+                    let counter = extract_json_number(&message, "counter") as u16;
+
+                    if !domain.is_empty() {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+                        let domain_hash = structure.hash_domain(&domain);
+
+                        // a later COMMIT_INCREMENT would write the previewed counter straight
+                        // over this one
+                        let previewing = unsafe {
+                            let session = &*std::ptr::addr_of!(SESSION);
+                            session.is_preview_mode && session.is_active_domain(&domain_hash)
+                        };
+                        if previewing {
+                            let response = error_json(
+                                ErrorCode::PreviewActive,
+                                "Domain is in a counter preview",
+                                "",
+                            );
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                            continue;
+                        }
+
+                        let updated =
+                            DomainTable::shared().set_counter(&domain, counter, structure);
+
+                        match updated {
+                            Ok(()) => {
+                                if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                    diag(
+                                        Diag::Warning,
+                                        format_args!("Warning: Could not save domain table: {}", e),
+                                    );
+                                }
+
+                                // whatever was typed belonged to the old counter, start the
+                                // domain over from the new one
+                                let active_affected = unsafe {
+                                    let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                                    if session.is_active_domain(&domain_hash) {
+                                        session.saved_counter = counter;
+                                        session.active_counter = counter;
+                                        true
+                                    } else {
+                                        false
+                                    }
+                                };
+                                if active_affected {
+                                    generation.clear();
+                                    restart_session(session(), structure);
+                                }
+
+                                // parked tabs are rebuilt from their counters when they come
+                                // back, so moving the counters is enough. a parked preview
+                                // keeps previewing the new counter's successor
+                                let mut parked_affected = 0;
+                                for parked in tab_sessions.values_mut() {
+                                    if parked.session.is_active_domain(&domain_hash) {
+                                        parked.session.saved_counter = counter;
+                                        parked.session.active_counter =
+                                            if parked.session.is_preview_mode {
+                                                counter.saturating_add(1)
+                                            } else {
+                                                counter
+                                            };
+                                        parked.generation.clear();
+                                        parked_affected += 1;
+                                    }
+                                }
+
+                                let response = format!(
+                                    "{{\"active_session_affected\":{},\"parked_sessions_affected\":{},\"status\":\"success\"}}",
+                                    active_affected, parked_affected
+                                );
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                            Err(e) => {
+                                let response = error_json(ErrorCode::TableFull, &e.to_string(), "");
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                        }
+                    } else {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    }
+                    continue;
+                } else if message.contains("\"SET_RULES\"") {
+                    let domain = extract_json_string(&message, "domain");
+                    let max_length = extract_json_number(&message, "max_length") as u16;
+                    let char_types = extract_json_number(&message, "char_types") as u8;
+
+                    if !domain.is_empty() {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+
+                        // "no_confusables" and the positional rules given change those
+                        // toggles, the others stay
+                        let mut table = DomainTable::shared();
+                        let positions =
+                            json_positions(&message, table.positions(&domain, structure));
+                        let updated = table
+                            .set_rules(&domain, max_length, char_types, structure)
+                            .and_then(|()| match extract_json_bool(&message, "no_confusables") {
+                                Some(on) => table.set_no_confusables(&domain, on, structure),
+                                None => Ok(()),
+                            })
+                            .and_then(|()| table.set_positions(&domain, positions, structure))
+                            .map(|()| table.session_rules(&domain, structure));
+                        drop(table);
+                        match updated {
+                            Ok((rules, _)) => {
+                                if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                    diag(
+                                        Diag::Warning,
+                                        format_args!("Warning: Could not save domain table: {}", e),
+                                    );
+                                }
+
+                                let response = format!(
+                                    "{{\"no_confusables\":{},{},\"status\":\"success\"}}",
+                                    rules.no_confusables,
+                                    positions_json(rules.positions)
+                                );
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                            Err(e) => {
+                                let code = match e {
+                                    DomainTableError::Full => ErrorCode::TableFull,
+                                    DomainTableError::NotPassword => ErrorCode::BadRequest,
+                                };
+                                let response = error_json(code, &e.to_string(), "");
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                        }
+                    } else {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    }
+                    continue;
+                } else if message.contains("\"SET_PIN\"") {
+                    let domain = extract_json_string(&message, "domain");
+                    let pin_length = extract_json_number(&message, "pin_length");
+
+                    if domain.is_empty() {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    } else if pin_length != 0 && !PIN_LENGTHS.contains(&(pin_length.min(255) as u8))
+                    {
+                        let response =
+                            error_json(ErrorCode::BadRequest, "pin_length must be 0 or 4-16", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    } else {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+
+                        let fixed_output = match pin_length {
+                            0 => FixedOutput::Password,
+                            digits => FixedOutput::Pin(digits as u8),
+                        };
+                        let updated = DomainTable::shared().set_fixed_output(
+                            &domain,
+                            fixed_output,
+                            structure,
+                        );
+                        match updated {
+                            Ok(()) => {
+                                if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                    diag(
+                                        Diag::Warning,
+                                        format_args!("Warning: Could not save domain table: {}", e),
+                                    );
+                                }
+
+                                let response = format!(
+                                    "{{\"pin_length\":{},\"status\":\"success\"}}",
+                                    pin_length
+                                );
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                            Err(e) => {
+                                let response = error_json(ErrorCode::TableFull, &e.to_string(), "");
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                        }
+                    }
+                    continue;
+                } else if message.contains("\"SET_WORDS\"") {
+                    let domain = extract_json_string(&message, "domain");
+                    let word_count = extract_json_number(&message, "word_count");
+
+                    if domain.is_empty() {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    } else if word_count != 0 && !WORD_COUNTS.contains(&(word_count.min(255) as u8))
+                    {
+                        let response =
+                            error_json(ErrorCode::BadRequest, "word_count must be 0 or 3-12", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    } else {
+                        let structure = &mut password_manager.saved_passwords[saved_password_idx]
+                            .structure_system;
+
+                        let fixed_output = match word_count {
+                            0 => FixedOutput::Password,
+                            words => FixedOutput::Words(words as u8),
+                        };
+                        let updated = DomainTable::shared().set_fixed_output(
+                            &domain,
+                            fixed_output,
+                            structure,
+                        );
+                        match updated {
+                            Ok(()) => {
+                                if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                    diag(
+                                        Diag::Warning,
+                                        format_args!("Warning: Could not save domain table: {}", e),
+                                    );
+                                }
+
+                                let response = format!(
+                                    "{{\"word_count\":{},\"status\":\"success\"}}",
+                                    word_count
+                                );
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                            Err(e) => {
+                                let response = error_json(ErrorCode::TableFull, &e.to_string(), "");
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                        }
+                    }
+                    continue;
+                } else if message.contains("\"COMMIT_INCREMENT\"") {
+                    let domain = extract_json_string(&message, "domain");
+
+                    if !domain.is_empty() {
+                        unsafe {
+                            if SESSION.is_preview_mode {
+                                let structure = &mut password_manager.saved_passwords
+                                    [saved_password_idx]
+                                    .structure_system;
+
+                                if let Err(e) = DomainTable::shared().set_counter(
+                                    &domain,
+                                    SESSION.active_counter,
+                                    structure,
+                                ) {
+                                    let response =
+                                        error_json(ErrorCode::TableFull, &e.to_string(), "");
+                                    send_response(&mut replies, id.as_deref(), &response)?;
+                                    continue;
+                                }
+
+                                if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                                    diag(
+                                        Diag::Warning,
+                                        format_args!("Warning: Could not save domain table: {}", e),
+                                    );
+                                }
+
+                                let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                                let active = session.active_counter;
+                                session.saved_counter = active;
+                                session.is_preview_mode = false;
+
+                                let response =
+                                    format!("{{\"counter\":{},\"status\":\"committed\"}}", active);
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            } else {
+                                let response =
+                                    error_json(ErrorCode::NotInPreview, "Not in preview mode", "");
+                                send_response(&mut replies, id.as_deref(), &response)?;
+                            }
+                        }
+                    } else {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                    }
+                    continue;
+                } else if message.contains("\"SET_TIMEOUT\"") {
+                    idle_timeout_secs = extract_json_number(&message, "seconds");
+
+                    let response = format!(
+                        "{{\"timeout\":{},\"status\":\"timeout_set\"}}",
+                        idle_timeout_secs
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"LIST_ACCOUNTS\"") {
+                    let accounts: Vec<String> = password_manager
+                    .saved_passwords
+                    .iter()
+                    .map(|p| {
+                        format!(
+                            "{{\"name\":\"{}\",\"description\":\"{}\",\"created_date\":{},\"created\":\"{}\",\"extra_chars_count\":{},\"use_count\":{}}}",
+                            escape_json(&p.name),
+                            escape_json(&p.description),
+                            p.created_date,
+                            format_unix_date(p.created_date),
+                            p.extra_chars_count,
+                            p.use_count
+                        )
+                    })
+                    .collect();
+
+                    let response = format!("{{\"accounts\":[{}]}}", accounts.join(","));
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"INSPECT\"") {
+                    // the active configuration unless one is named
+                    let name = match extract_json_string(&message, "name") {
+                        name if name.is_empty() => password_manager.saved_passwords
+                            [saved_password_idx]
+                            .name
+                            .clone(),
+                        name => name,
+                    };
+                    let response = match inspect(&password_manager, &name) {
+                        Ok(inspection) => inspection.to_json(),
+                        Err(e) => error_json(
+                            ErrorCode::from_io(&e),
+                            &e.to_string(),
+                            &format!(",\"name\":\"{}\"", escape_json(&name)),
+                        ),
+                    };
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"SELECT_ACCOUNT\"") {
+                    let name = extract_json_string(&message, "name");
+
+                    let Some(new_idx) = password_manager
+                        .saved_passwords
+                        .iter()
+                        .position(|p| p.name == name)
+                    else {
+                        if let Some((_, e)) = password_manager.find_unusable(Some(&name)) {
+                            let response = error_json(
+                                ErrorCode::UnusableConfiguration,
+                                &format!("The configuration is damaged: {}", e),
+                                &format!(",\"name\":\"{}\"", escape_json(&name)),
+                            );
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                            continue;
+                        }
+                        let response = error_json(
+                            ErrorCode::UnknownAccount,
+                            "No configuration with that name",
+                            &format!(",\"name\":\"{}\"", escape_json(&name)),
+                        );
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                        continue;
+                    };
+
+                    password_manager.saved_passwords[saved_password_idx]
+                        .structure_system
+                        .full_reset();
+                    generation.clear();
+                    // parked tabs would replay their input through the wrong configuration
+                    tab_sessions.clear();
+                    confirm_reference = None;
+
+                    account_idx = new_idx;
+                    saved_password_idx = new_idx;
+                    let structure =
+                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
+
+                    // the domain stays active, only at its saved counter: a preview belonged
+                    // to the old configuration
+                    let response = unsafe {
+                        let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                        let domain_hash =
+                            session.active_domain_hash.filter(|_| session.initialized);
+                        let saved_counter = session.saved_counter;
+                        session.scrub();
+
+                        match domain_hash {
+                            Some(mut domain_hash) => {
+                                session.active_domain_hash = Some(domain_hash);
+                                session.saved_counter = saved_counter;
+                                session.active_counter = saved_counter;
+                                session.initialized = true;
+                                zero_memory(&mut domain_hash);
+                                format!(
+                                    "{{\"account\":\"{}\",\"saved_counter\":{},\"active_counter\":{},\"status\":\"account_selected\"}}",
+                                    escape_json(&name),
+                                    saved_counter,
+                                    saved_counter
+                                )
+                            }
+                            None => format!(
+                                "{{\"account\":\"{}\",\"status\":\"account_selected\"}}",
+                                escape_json(&name)
+                            ),
+                        }
+                    };
+                    restart_session(session(), structure);
+                    log(
+                        LogLevel::Info,
+                        format_args!("json-io: selected another account"),
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"GENERATE_USERNAME\"") {
+                    // a walk of its own at the domain's saved counter, the password
+                    // session is left exactly where it was
+                    let domain = extract_json_string(&message, "domain");
+                    let length = match extract_json_number(&message, "length") {
+                        0 => DEFAULT_USERNAME_LENGTH,
+                        n => n as usize,
+                    };
+
+                    if domain.is_empty() {
+                        let response = error_json(ErrorCode::MissingDomain, "Missing domain", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                        continue;
+                    }
+                    if !USERNAME_LENGTHS.contains(&length) {
+                        let response =
+                            error_json(ErrorCode::BadRequest, "Username length must be 4-32", "");
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                        continue;
+                    }
+
+                    let structure =
+                        &mut password_manager.saved_passwords[saved_password_idx].structure_system;
+
+                    // "digits" given is remembered for the domain, otherwise the stored
+                    // toggle applies
+                    if let Some(digits) = extract_json_bool(&message, "digits") {
+                        if let Err(e) =
+                            DomainTable::shared().set_username_digits(&domain, digits, structure)
+                        {
+                            let response = error_json(ErrorCode::TableFull, &e.to_string(), "");
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                            continue;
+                        }
+                        if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+                            diag(
+                                Diag::Warning,
+                                format_args!("Warning: Could not save domain table: {}", e),
+                            );
+                        }
+                    }
+
+                    let digits = DomainTable::shared().username_digits(&domain, structure);
+                    let counter = DomainTable::shared()
+                        .get_counter(&domain, structure)
+                        .unwrap_or(0);
+                    let domain_hash = structure.hash_domain(&domain);
+                    let username =
+                        Wiped(structure.derive_username(&domain_hash, counter, length, digits));
+
+                    let response = Wiped(format!(
+                        "{{\"username\":\"{}\",\"counter\":{},\"digits\":{}}}",
+                        username.as_str(),
+                        counter,
+                        digits
+                    ));
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"GENERATE\"") {
+                    let text = extract_json_text(&message, "text");
+                    let format = extract_json_string(&message, "format");
+
+                    // "format" answers from the session start with bytes of the raw walk,
+                    // then puts the typed state back, so it can come between keystrokes
+                    if !format.is_empty() {
+                        let format = RawFormat::parse(&format).filter(|&f| f != RawFormat::Raw);
+                        let byte_count = match extract_json_number(&message, "bytes") {
+                            0 => RawFormat::DEFAULT_BYTES,
+                            n => n as usize,
+                        };
+                        let Some(format) = format.filter(|_| byte_count <= RawFormat::MAX_BYTES)
+                        else {
+                            let response = error_json(
+                                ErrorCode::BadRequest,
+                                "format must be hex or base64, bytes 1-1024",
+                                "",
+                            );
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                            continue;
+                        };
+
+                        let saved_password =
+                            &mut password_manager.saved_passwords[saved_password_idx];
+                        let mut keycodes = Wiped(
+                            text.chars()
+                                .map(|ch| ch as u32)
+                                .filter(|&keycode| keycode != 0)
+                                .collect::<Vec<u32>>(),
+                        );
+                        saved_password.normalization.apply(&mut keycodes);
+
+                        restart_session(session(), &mut saved_password.structure_system);
+                        let output = generate_raw_output(saved_password, &keycodes, byte_count);
+                        generation.replay(session(), saved_password);
+
+                        let response = Wiped(format!(
+                            "{{\"output\":\"{}\",\"format\":\"{}\",\"bytes\":{}}}",
+                            format.encode(&output).as_str(),
+                            if format == RawFormat::Hex {
+                                "hex"
+                            } else {
+                                "base64"
+                            },
+                            byte_count
+                        ));
+                        send_response(&mut replies, id.as_deref(), &response)?;
+                        continue;
+                    }
+
+                    if generation.is_fresh() && !text.is_empty() {
+                        password_manager.record_use(saved_password_idx);
+                    }
+
+                    // same keycodes the extension would send one by one: the code point of
+                    // each character
+                    let mut keycodes = Wiped(
+                        text.chars()
+                            .map(|ch| ch as u32)
+                            .filter(|&keycode| keycode != 0)
+                            .collect::<Vec<u32>>(),
+                    );
+                    password_manager.saved_passwords[saved_password_idx]
+                        .normalization
+                        .apply(&mut keycodes);
+
+                    let mut outputs = Wiped(String::new());
+                    let mut last = Wiped(String::new());
+                    let mut estimate = OutputEstimate::default();
+                    for &keycode in keycodes.iter() {
+                        let output_chars = generation.step(
+                            session(),
+                            &mut password_manager.saved_passwords[saved_password_idx],
+                            keycode,
+                        );
+                        last.wipe();
+                        last.push_str(&escape_json(&output_chars));
+                        estimate = OutputEstimate::of(&output_chars);
+
+                        if !outputs.is_empty() {
+                            outputs.push(',');
+                        }
+                        outputs.push('"');
+                        outputs.push_str(&last);
+                        outputs.push('"');
+                    }
+
+                    let response = Wiped(format!(
+                        "{{\"output\":\"{}\",\"outputs\":[{}],\"entropy_bits\":{},\"length\":{}}}",
+                        last.as_str(),
+                        outputs.as_str(),
+                        estimate.entropy_bits(),
+                        estimate.length
+                    ));
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                } else if message.contains("\"CANCEL_PREVIEW\"") {
+                    unsafe {
+                        let session = &mut *std::ptr::addr_of_mut!(SESSION);
+                        if session.is_preview_mode {
+                            let saved = session.saved_counter;
+                            session.active_counter = saved;
+                            session.is_preview_mode = false;
+
+                            generation.clear();
+                            restart_session(
+                                session,
+                                &mut password_manager.saved_passwords[saved_password_idx]
+                                    .structure_system,
+                            );
+
+                            let response =
+                                format!("{{\"counter\":{},\"status\":\"cancelled\"}}", saved);
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                        } else {
+                            let response =
+                                error_json(ErrorCode::NotInPreview, "Not in preview mode", "");
+                            send_response(&mut replies, id.as_deref(), &response)?;
+                        }
+                    }
+                    continue;
+                } else {
+                    // keystrokes never carry a type, so anything else here is from a newer extension
+                    let response = error_json(
+                        ErrorCode::UnsupportedType,
+                        "Unsupported message type",
+                        &format!(
+                            ",\"type\":\"{}\",\"protocol\":{}",
+                            escape_json(&extract_json_string(&message, "type")),
+                            PROTOCOL_VERSION
+                        ),
+                    );
+                    send_response(&mut replies, id.as_deref(), &response)?;
+                    continue;
+                }
+            }
+
+            let keycode = extract_json_number(&message, "charCode") as u32;
+
+            if keycode > 0 {
+                if generation.is_fresh() {
+                    password_manager.record_use(saved_password_idx);
+                }
+
+                let output_chars = generation.step(
+                    session(),
+                    &mut password_manager.saved_passwords[saved_password_idx],
+                    keycode,
+                );
+                let escaped = Wiped(escape_json(&output_chars));
+                let estimate = OutputEstimate::of(&output_chars);
+
+                let response = Wiped(format!(
+                    "{{\"output\":\"{}\",\"entropy_bits\":{},\"length\":{}}}",
+                    escaped.as_str(),
+                    estimate.entropy_bits(),
+                    estimate.length
+                ));
+
+                pad_keystroke_reply();
+                send_response(&mut replies, id.as_deref(), &response)?;
+            } else {
+                log(
+                    LogLevel::Debug,
+                    format_args!("json-io: ignored a message with neither type nor charCode"),
+                );
+            }
+        }
+        Ok(())
+    })();
+
+    log(LogLevel::Info, format_args!("json-io: host exiting"));
+
+    // FINALIZE or the browser closing the pipe: leave nothing of the session behind
+    password_manager.saved_passwords[saved_password_idx]
+        .structure_system
+        .full_reset();
+    generation.clear();
+    tab_sessions.clear();
+    unsafe {
+        let session = &mut *std::ptr::addr_of_mut!(SESSION);
+        session.scrub();
+    }
+
+    if DomainTable::shared().is_dirty() {
+        if let Err(e) = DomainTable::shared().save_to_binary(&exe_path) {
+            diag(
+                Diag::Warning,
+                format_args!("Warning: Could not save domain table: {}", e),
+            );
+        }
+    }
+
+    if let Err(e) = password_manager.flush_usage() {
+        diag(
+            Diag::Warning,
+            format_args!("Warning: Could not save use counter: {}", e),
+        );
+    }
+
+    // the browser closing its end of stdout is an ordinary way to go
+    match outcome {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+// the scratch vault a script runs against, in a directory of its own that goes away
+// afterwards. the children started for it find the vault as the "scenario" profile,
+// through a config dir pointing there
+struct ScenarioRun {
+    dir: PathBuf,
+    host: Option<(Child, std::process::ChildStdin, std::process::ChildStdout)>,
+    last_reply: String,
+    output: Wiped<String>,
+    // the geometry options the next setup-phrase passes to create-account
+    geometry: Vec<String>,
+}
+
+impl ScenarioRun {
+    fn create() -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let dir = std::env::temp_dir().join(format!(
+            "void_vault-script-{}-{}",
+            std::process::id(),
+            nanos
+        ));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        }
+        #[cfg(not(unix))]
+        fs::create_dir(&dir)?;
+
+        let run = ScenarioRun {
+            dir,
+            host: None,
+            last_reply: String::new(),
+            output: Wiped(String::new()),
+            geometry: Vec::new(),
+        };
+
+        let vault = run.dir.join("scenario.vault");
+        create_profile_vault(&vault, "scenario")?;
+        fs::create_dir(run.dir.join("void_vault"))?;
+        fs::write(
+            run.dir.join("void_vault").join("profiles"),
+            format!("scenario={}\n", vault.display()),
+        )?;
+
+        Ok(run)
+    }
+
+    fn command(&self, args: &[&str]) -> io::Result<Command> {
+        let mut command = Command::new(std::env::current_exe()?);
+
+        #[cfg(windows)]
+        command.env("APPDATA", &self.dir);
+        #[cfg(not(windows))]
+        command.env("XDG_CONFIG_HOME", &self.dir);
+
+        command
+            .env("VOID_VAULT_PROFILE", "scenario")
+            .arg("--quiet")
+            .args(args);
+        Ok(command)
+    }
+
+    // create-account reading the phrase from stdin. the host holds the vault lock,
+    // so it is stopped first
+    fn setup(&mut self, phrase: &str) -> io::Result<()> {
+        self.stop_host()?;
+
+        let mut child = self
+            .command(&["--i-know-what-im-doing", "create-account", "main"])?
+            .args(&self.geometry)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(phrase.as_bytes())?;
+        }
+
+        let result = child.wait_with_output()?;
+        if !result.status.success() {
+            return Err(io::Error::other(format!(
+                "setup-phrase failed: {}",
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    // set-unlock with the secret in a file, the host stopped first like for setup
+    fn set_unlock(&mut self, secret: &str) -> io::Result<()> {
+        self.stop_host()?;
+
+        let secret_file = self.dir.join("unlock");
+        fs::write(&secret_file, secret)?;
+        let result = self
+            .command(&["set-unlock", "--secret-file"])?
+            .arg(&secret_file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()?;
+        fs::remove_file(&secret_file)?;
+        if !result.status.success() {
+            return Err(io::Error::other(format!(
+                "set-unlock failed: {}",
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    // one message to the host, started on first use, and its reply
+    fn send(&mut self, message: &str) -> io::Result<()> {
+        if self.host.is_none() {
+            let mut child = self
+                .command(&["json-io"])?
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()?;
+            let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+                return Err(io::Error::other("The json-io host has no pipes"));
+            };
+            self.host = Some((child, stdin, stdout));
+        }
+        let Some((_, stdin, stdout)) = self.host.as_mut() else {
+            return Ok(());
+        };
+
+        stdin.write_all(&(message.len() as u32).to_le_bytes())?;
+        stdin.write_all(message.as_bytes())?;
+        stdin.flush()?;
+
+        let mut length_bytes = [0u8; 4];
+        stdout.read_exact(&mut length_bytes)?;
+        let mut reply = vec![0u8; u32::from_le_bytes(length_bytes) as usize];
+        stdout.read_exact(&mut reply)?;
+
+        self.last_reply = String::from_utf8_lossy(&reply).into_owned();
+        if self.last_reply.contains("\"output\":\"") {
+            self.output = extract_json_text(&self.last_reply, "output");
+        }
+        Ok(())
+    }
+
+    // closing its stdin is the host's cue to save and exit
+    fn stop_host(&mut self) -> io::Result<()> {
+        if let Some((mut child, stdin, stdout)) = self.host.take() {
+            drop(stdin);
+            drop(stdout);
+            child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ScenarioRun {
+    fn drop(&mut self) {
+        let _ = self.stop_host();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// FNV-1a of the output's UTF-8, for expect-output-hash. stable across builds and
+// platforms, which the std hashers don't promise
+fn output_hash(output: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in output.as_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// script <file>: a scenario run start to finish against a scratch vault, through
+// the json-io host the browser talks to. one command per line, # starts a comment,
+// and a command's argument is the rest of the line as it is:
+//   setup-phrase <phrase>                 create the configuration
+//   geometry <options>                    --dimensions, --range and so on for it
+//   set-unlock <secret>                   give the vault an unlock secret
+//   activate <domain> | preview <domain> | commit <domain> | cancel
+//   set-rules <domain> <max_length> <char_types> [no-confusables] [starts-with-letter]
+//             [no-leading-symbol] [no-trailing-symbol]
+//   type <text> | backspace | reset       keystrokes, UNDO_CHAR, RESET
+//   send <json>                           any other message, as it is
+//   restart                               the next message starts a new host
+//   expect-output <text> | expect-output-hash <hex> | expect-length <n>
+//   expect <fragment>                     the last reply contains it
+// the first expectation that doesn't hold ends the run with a ScriptFailed
+fn run_script(path: &str) -> io::Result<()> {
+    let script = fs::read_to_string(path)?;
+    let mut run = ScenarioRun::create()?;
+    let mut passed = 0;
+
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let at = format!("{}:{}", path, number + 1);
+
+        let failed = |what: &str, expected: &str, actual: &str| {
+            ErrorCode::ScriptFailed.error(format!(
+                "{}: {} does not hold\n  expected: {}\n  actual:   {}",
+                at, what, expected, actual
+            ))
+        };
+
+        match command {
+            "setup-phrase" => run
+                .setup(argument)
+                .map_err(|e| ErrorCode::ScriptFailed.error(format!("{}: {}", at, e)))?,
+            "geometry" => {
+                run.geometry = argument.split_whitespace().map(String::from).collect();
+            }
+            "set-unlock" => run
+                .set_unlock(argument)
+                .map_err(|e| ErrorCode::ScriptFailed.error(format!("{}: {}", at, e)))?,
+            "activate" | "preview" => {
+                let kind = if command == "activate" {
+                    "ACTIVATE"
+                } else {
+                    "ACTIVATE_PREVIEW"
+                };
+                run.send(&format!(
+                    "{{\"type\":\"{}\",\"domain\":\"{}\"}}",
+                    kind,
+                    escape_json(argument)
+                ))?;
+                run.output.wipe();
+            }
+            "commit" => run.send(&format!(
+                "{{\"type\":\"COMMIT_INCREMENT\",\"domain\":\"{}\"}}",
+                escape_json(argument)
+            ))?,
+            "cancel" => {
+                run.send("{\"type\":\"CANCEL_PREVIEW\"}")?;
+                run.output.wipe();
+            }
+            "set-rules" => {
+                let words: Vec<&str> = argument.split_whitespace().collect();
+                let (domain, max_length, char_types, (no_confusables, positions)) = match words[..]
+                {
+                    [domain, max_length, char_types, ref flags @ ..] => match rule_flags(flags) {
+                        Some(flags) => (domain, max_length, char_types, flags),
+                        None => {
+                            return Err(ErrorCode::BadRequest.error(format!(
+                                "{}: set-rules knows no-confusables, starts-with-letter, \
+                                         no-leading-symbol and no-trailing-symbol",
+                                at
+                            )))
+                        }
+                    },
+                    _ => {
+                        return Err(ErrorCode::BadRequest.error(format!(
+                            "{}: set-rules takes a domain, a max length, char types and \
+                                 optionally its toggles",
+                            at
+                        )))
+                    }
+                };
+                let (Ok(max_length), Ok(char_types)) =
+                    (max_length.parse::<u16>(), char_types.parse::<u8>())
+                else {
+                    return Err(ErrorCode::BadRequest.error(format!(
+                        "{}: set-rules needs numbers for the max length and char types",
+                        at
+                    )));
+                };
+                run.send(&format!(
+                    "{{\"type\":\"SET_RULES\",\"domain\":\"{}\",\"max_length\":{},\"char_types\":{},\"no_confusables\":{},{}}}",
+                    escape_json(domain),
+                    max_length,
+                    char_types,
+                    no_confusables,
+                    positions_json(positions)
+                ))?;
+            }
+            "type" => {
+                for ch in argument.chars() {
+                    run.send(&format!("{{\"charCode\":{}}}", ch as u32))?;
+                }
+            }
+            "backspace" => run.send("{\"type\":\"UNDO_CHAR\"}")?,
+            "reset" => {
+                run.send("{\"type\":\"RESET\"}")?;
+                run.output.wipe();
+            }
+            "send" => run.send(argument)?,
+            "restart" => run.stop_host()?,
+            "expect-output" => {
+                if run.output.as_str() != argument {
+                    return Err(failed("expect-output", argument, &run.output));
+                }
+                passed += 1;
+            }
+            "expect-output-hash" => {
+                let actual = output_hash(&run.output);
+                if actual != argument.trim() {
+                    return Err(failed("expect-output-hash", argument.trim(), &actual));
+                }
+                passed += 1;
+            }
+            "expect-length" => {
+                let actual = run.output.chars().count().to_string();
+                if actual != argument.trim() {
+                    return Err(failed("expect-length", argument.trim(), &actual));
+                }
+                passed += 1;
+            }
+            "expect" => {
+                if !run.last_reply.contains(argument) {
+                    return Err(failed("expect", argument, &run.last_reply));
+                }
+                passed += 1;
+            }
+            other => {
+                return Err(ErrorCode::BadRequest
+                    .error(format!("{}: unknown script command '{}'", at, other)));
+            }
+        }
+    }
+
+    if json_output() {
+        println!(
+            "{{\"script\":\"{}\",\"expectations\":{},\"status\":\"passed\"}}",
+            escape_json(path),
+            passed
+        );
+    } else {
+        println!("{}: {} expectations passed", path, passed);
+    }
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    install_shutdown_handlers();
+    init_logging();
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let result = match std::panic::catch_unwind(|| run(&args)) {
+        Ok(result) => result,
+        Err(_) => {
+            emergency_cleanup();
+            std::process::exit(101);
+        }
+    };
+
+    if let Some(code) = shutdown_exit_code() {
+        emergency_cleanup();
+        std::process::exit(code);
+    }
+
+    if let Err(e) = result {
+        let code = ErrorCode::from_io(&e);
+        if json_output() {
+            eprintln!("{}", error_json(code, &e.to_string(), ""));
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        // the lock is held by the daemon, not a browser host, when one is up
+        if matches!(code, ErrorCode::VaultInUse) && DaemonClient::connect().is_some() {
+            diag(
+                Diag::Note,
+                format_args!("A daemon owns this vault, --via-daemon sends the command to it"),
+            );
+        }
+        std::process::exit(code.exit_code());
+    }
+
+    let exit_code = EXIT_CODE.load(Ordering::SeqCst);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+
+    Ok(())
+}
+
+const USAGE: &str = "\
+Usage: void_vault [global options] [command]
+
+Commands:
+  (none)                        Interactive session. With stdin a pipe or a file,
+                                io. The browser's json-io is chosen only when its
+                                own arguments come along: chrome-extension://...
+                                from Chrome, the manifest's path and the
+                                extension's id from Firefox
+  setup [--add <name> | --replace] [--reproducible] [<setup options>]
+                                Create the first configuration, then exit. With one
+                                already there, --add creates another and --replace
+                                starts the --account one (or the first) over
+                                --reproducible leaves keystroke timing out, so the
+                                same phrase and options rebuild it exactly. Anyone
+                                with the phrase can then rebuild it too
+  term [--hidden] [--confirm] [--length <n>] [--words <n>] [--domain <domain>]
+       [--qr [--show]] [--tty-prompt [--print0]]
+       [--type [--countdown <s>] [--type-delay <ms>]]
+                                Generate in raw terminal mode. The output is masked
+                                while typing, Tab peeks and --show displays it live.
+                                --hidden shows the final password only on request
+                                --tty-prompt shows everything on the terminal and
+                                prints only the password, on stdout
+                                --type types the password as key presses into the
+                                window focused after a countdown (3s by default),
+                                --type-delay ms apart (20). Uses wtype on Wayland,
+                                xdotool on X11 and SendInput on Windows, and types
+                                nothing if the keyboard layout lacks a character
+  io [--confirm] [--length <n>] [--words <n>] [--domain <domain>] [--qr [--show]]
+     [--tty-prompt] [--print0] [--format hex|base64|raw [--bytes <n>]]
+     [--exec <command> [<args>...] [--fd <n>]]
+     [--type [--countdown <s>] [--type-delay <ms>]]
+                                Generate from one line of stdin and print the
+                                password the extension shows for it
+                                --qr prints the password as a QR code instead,
+                                --show prints the text as well
+                                --confirm takes the phrase twice and only prints
+                                the password if both produce the same output
+                                --length stops the output at n characters
+                                --words makes it a passphrase of n words (3-12)
+                                --format prints n bytes (32 by default) of the
+                                walk itself instead, never mapped to characters,
+                                for deriving keys
+                                --domain generates what the extension would for
+                                the domain, its counter and length/character rules
+                                --tty-prompt puts prompts and the QR code on the
+                                terminal instead, --print0 ends the password with NUL
+                                --exec runs the command (everything after it) and
+                                writes the password to its stdin instead, or with
+                                --fd to descriptor n (unix), never its arguments
+                                or environment. Exits with the command's status
+                                --type types the password into the focused window
+                                instead, see term
+  askpass [<prompt>] [--domain <domain>]
+                                The SSH_ASKPASS convention, for ssh-add and sudo
+                                -A: shows the prompt and reads the phrase on the
+                                terminal, prints the password for the domain in
+                                VOID_VAULT_ASKPASS_DOMAIN (or --domain) on stdout.
+                                With that variable set, SSH_ASKPASS can name the
+                                binary itself
+  preview <domain> [--confirm] [--length <n>] [--qr [--show]] [--tty-prompt] [--print0]
+                                io for the domain at its next counter, without
+                                saving it. Asks on stderr whether to commit, the
+                                answer is the next line of stdin (of the terminal
+                                with --tty-prompt)
+  json-io [--idle-timeout <s>] [--exit-after <s>]
+                                Browser native messaging host. With a daemon
+                                running it relays to the daemon instead
+  daemon [--idle-timeout <s>] [--exit-after <s>]
+                                Load the vault once and serve json-io to every
+                                client of a per-user socket (a loopback port on
+                                Windows), each with its own sessions. Writes to
+                                the vault all go through it. Runs until stopped,
+                                or for --exit-after seconds without a message
+  accounts                      List stored configurations
+  inspect <name>                Show the shape of a configuration's geometry:
+                                dimensions, character set, bounds and step size.
+                                Never its coordinates or seed
+  create-account <name> [--phrase-file <path>] [<setup options>]
+                 [--description <text>] [--force]
+                                Create a configuration from a phrase on stdin,
+                                without keystroke timing, like setup --reproducible
+  rekey <name> [<new name>] [--extra-chars <n>] [--pool <name> | --pool-file <path>]
+                                Copy the configuration, with its structure and
+                                setup, as a new one (<name>-v2 by default) that
+                                outputs n characters per input or draws from
+                                another pool. The old one stays, and so do its
+                                domains until they are migrated
+  domains list                  List registered domains (hashes only)
+  domains get <domain>          Show a domain's counter
+  domains set <domain> <n>      Set a domain's counter
+  domains increment <domain>    Bump a domain's counter by one
+  domains commit <domain>       Commit the counter preview would show
+  domains pin <domain> <n>      Make the domain a PIN of n digits (4-16), 0 turns
+                                it back into a password
+  domains words <domain> <n>    Make the domain a passphrase of n words (3-12),
+                                0 turns it back into a password
+  domains rules <domain> <max_length> <char_types> [--no-confusables on|off]
+        [--starts-with-letter on|off] [--no-leading-symbol on|off]
+        [--no-trailing-symbol on|off]
+                                Set a domain's max length (0 for none) and char
+                                types (0-127). --no-confusables on keeps 0, O, 1,
+                                l, I and their lookalikes out of its passwords.
+                                The others hold its passwords' first character to
+                                an ASCII letter or to a letter or digit, and its
+                                last, where the max length cuts, to a letter or
+                                digit. A toggle not given stays
+  domains migrate <domain>      Move a domain registered before a rekey over to
+                                the --account re-key, at its next counter. Until
+                                then it generates with the configuration it was
+                                registered with, whichever of the two is used
+  rate-limit <per_sec> <burst> <strikes>
+                                Set the json-io rate limit
+  set-unlock [--secret-file <path>] [--decoy]
+                                Set or change the unlock secret: until it is given,
+                                term, io, username, export-recovery and the
+                                interactive session prompt for it (as the first
+                                line of stdin) and json-io answers nothing but an
+                                UNLOCK message. After 3 wrong ones in a row each
+                                try waits 1s, 2s, 4s ... Changing it asks for the
+                                current one first, which --secret-file then holds.
+                                --decoy sets the decoy's secret instead, which
+                                opens the vault the same way but as --duress
+  remove-unlock [--secret-file <path>] [--decoy]
+                                Remove the unlock secret, given the current one.
+                                The decoy's goes with it
+  set-decoy <name> [--secret-file <path>]
+                                Make the configuration the decoy. It is left out
+                                of every listing, and under --duress it generates
+                                for all the others, which keep their names, with
+                                a domain table of its own. Takes the unlock
+                                secret first when there is one
+  remove-decoy [--secret-file <path>]
+                                Stop having a decoy
+  accept-new-binary [--secret-file <path>]
+                                Trust the code the vault now sits behind, after
+                                an upgrade. The vault is sealed with a hash of the
+                                code at setup, and every start warns when that no
+                                longer matches. Takes the unlock secret first
+                                when there is one
+  shred-backups                 Overwrite with zeros and remove the vault's .bak
+                                and any .new copy. Every write keeps the vault as
+                                it was as .bak and shreds the one before
+  vault-stats                   Show the vault's size and its backup files
+  doctor [--fix]                Check for what leaves an installation printing
+                                nothing: a vault missing its markers or domain
+                                table, a .bak newer than the vault, a vault that
+                                can't be written, a browser manifest starting a
+                                binary that moved, and what others could change.
+                                Only reads. --fix offers the safe fixes one at a
+                                time. Exits 0 when nothing is found, otherwise 64
+                                plus 1 (vault), 2 (backup), 4 (unwritable),
+                                8 (manifest) and 16 (exposed) for what was found
+  profiles [list]               List the profiles, * marking the selected one
+  profiles add <name> [<path>]  Register a profile. An existing file (another copy
+                                of the binary) is used as it is, otherwise a new
+                                vault file is made, in the config dir by default
+  pin <domain> [--confirm] [--qr [--show]] [--tty-prompt] [--print0]
+                                io for a domain set up with domains pin. One digit
+                                per keystroke, the rest of the line is ignored
+  username <domain> [--length <n>] [--digits on|off]
+                                Generate the domain's username, 10 letters by
+                                default. --digits sets whether four digits follow,
+                                remembered for the domain
+  script <file>                 Run a scenario against a scratch vault through the
+                                json-io host: setup-phrase, activate, type,
+                                expect-output-hash and so on, one per line. See
+                                tests/scenarios for examples
+  export-recovery <path> [--qr] [--passphrase-file <path>]
+                                Write a printable recovery sheet: the
+                                configurations and domain table, encrypted with a
+                                passphrase, as numbered lines with a check each.
+                                --qr adds a QR code of the same data
+  import-recovery <path> [--passphrase-file <path>]
+                                Rebuild the vault from a sheet, typed in or the
+                                text of its QR code (- reads stdin), into a fresh
+                                copy of the binary
+  error-codes                   List the error codes and exit statuses. Every
+                                failure exits with its code's status, 0 is success
+  verify-determinism            Run the built-in golden vectors and fail if this
+                                build's output differs from where they were made.
+                                With a configuration, also check the fingerprint
+                                stored with it, or store one on the first run
+  self-test                     Type the behavioral test sequences into a throwaway
+                                structure and fail if any output has drifted
+
+Setup options, for setup and create-account:
+  --dimensions <n>              Dimensions of the structure, 3-16 (default 7)
+  --range <n>                   How far points lie from the center, 5-100
+                                (default 10 + dimensions)
+  --step <x>                    Base step per keystroke, 0.5-10 (default 3)
+  --variance <x>                How much a step varies around it, 0-10 (default 2)
+  --max-points <n>              Stop adding points to the structure at n,
+                                10000-100000000 (default 2000000). The same phrase
+                                stops at the same place, for low-memory machines
+  --extra-chars <n>             Output characters per input past the first,
+                                0-31 (default 7)
+  --pool <name>                 The characters output is drawn from: ascii,
+                                latin, extended (Latin, Greek and Cyrillic),
+                                full-unicode (full without unassigned, invisible
+                                or combining characters) or full (the default).
+                                create-account's older --charset does the same
+  --pool-file <path>            A custom pool, one hex code point or first-last
+                                range a line. Each has to be an assigned,
+                                printable character
+  Stored with the configuration. inspect shows what one was made with
+
+Global options:
+  --account <name>              Use this configuration instead of the first
+  --profile <name>              Keep configurations and domains in the profile's
+                                vault instead of this executable. The
+                                VOID_VAULT_PROFILE variable does the same
+  --use-domain-counter <domain> Start term, io and the interactive session where
+                                the extension starts for the domain at its
+                                counter, so the output matches the extension's
+                                byte for byte (--domain also applies the domain's
+                                length and character rules). In the session,
+                                :use <domain> changes the domain and :use alone
+                                drops it. Earlier builds offset each key by the
+                                counter instead, which gave other passwords
+  --quiet, -q                   Leave out informational messages. Warnings and
+                                all other diagnostics go to stderr either way
+  --verbose                     Add load details, io's entropy estimate, and
+                                whether memory could be locked
+  --timeout <s>                 Clear the screen and exit when term or the
+                                interactive session gets no key for this long.
+                                Default 600, 0 waits forever
+  --json                        Print management command results as JSON, and
+                                errors as JSON on stderr. term, io and the
+                                interactive session are unaffected
+  --auto-exit                   Exit after setup instead of going interactive
+  --via-daemon                  Send io (with --domain, --length, --print0) and
+                                domains get/set/pin/words to the running daemon
+                                instead of loading the vault. Without one, or
+                                for anything else, the command runs directly
+  --no-persist                  Never write to the binary: new domains, counters
+                                and use counts only last until the process exits.
+                                VOID_VAULT_LOG=info lists the writes left out
+  --duress                      Work on the decoy, see set-decoy. Nothing says so
+  --fix-perms                   Restrict the executable and the vault to their
+                                owner (mode 700, or an owner-only DACL) when
+                                others can write them. Without it the warning
+                                about that asks on a terminal, in setup, term and
+                                the interactive session
+  --no-harden                   Leave core dumps, debugger access and swapping as
+                                the system has them. For debugging
+  --no-padding                  Have json-io answer keystrokes as soon as they are
+                                generated instead of on a fixed 20ms beat. For
+                                benchmarking, it lets timing show what was typed
+  --i-know-what-im-doing        Let setup and create-account accept a phrase
+                                shorter than 40 characters or with fewer than 12
+                                different ones
+  --help, -h                    Show this help
+  --version, -V                 Show the version
+
+Backspace takes back the last character typed, in setup, term, io, the interactive
+session and json-io's UNDO_CHAR: what follows is what typing without it gives.
+
+The older spellings --setup, --term, --io, --json-io, --list, --inspect,
+--list-domains, --create-account, --get-counter, --set-counter, --increment-counter, --set-rules,
+--set-rate-limit, --rekey, --migrate-domain,
+--list-error-codes, --profile-list, --profile-add, --script, --username, --pin,
+--export-recovery, --import-recovery, --verify-determinism, --self-test,
+--set-unlock, --remove-unlock, --set-decoy, --remove-decoy, --shred-backups,
+--vault-stats, --doctor and --accept-new-binary still work.
+";
+
+// what verify-determinism found for a configuration's stored Fingerprint
+enum FingerprintCheck {
+    // none was stored, this build's is now
+    Stored,
+    Matched,
+    // the first stage that differs
+    Diverged(&'static str),
+}
+
+// verify-determinism and self-test: (name, expected, actual) per hash, ok or
+// MISMATCH for each, and a failure if any differ. verify-determinism adds the
+// fingerprint of the configuration it checked, by name
+fn report_golden_hashes(
+    key: &str,
+    what: &str,
+    results: &[(&str, u64, u64)],
+    fingerprint: Option<&(String, FingerprintCheck)>,
+) -> io::Result<()> {
+    let mismatches = results
+        .iter()
+        .filter(|(_, expected, actual)| expected != actual)
+        .count();
+
+    if json_output() {
+        let entries: Vec<String> = results
+            .iter()
+            .map(|(name, expected, actual)| {
+                format!(
+                    "{{\"name\":\"{}\",\"expected\":\"{:016x}\",\"actual\":\"{:016x}\",\"ok\":{}}}",
+                    name,
+                    expected,
+                    actual,
+                    expected == actual
+                )
+            })
+            .collect();
+        let fingerprint = match fingerprint {
+            Some((account, check)) => format!(
+                ",\"fingerprint\":{{\"account\":\"{}\",{}}}",
+                escape_json(account),
+                match check {
+                    FingerprintCheck::Stored => "\"status\":\"stored\"".to_string(),
+                    FingerprintCheck::Matched => "\"status\":\"ok\"".to_string(),
+                    FingerprintCheck::Diverged(stage) =>
+                        format!("\"status\":\"mismatch\",\"stage\":\"{}\"", stage),
+                }
+            ),
+            None => String::new(),
+        };
+        println!("{{\"{}\":[{}]{}}}", key, entries.join(","), fingerprint);
+    } else {
+        for (name, expected, actual) in results {
+            if expected == actual {
+                println!("ok        {}", name);
+            } else {
+                println!(
+                    "MISMATCH  {}: expected {:016x}, got {:016x}",
+                    name, expected, actual
+                );
+            }
+        }
+        match fingerprint {
+            Some((account, FingerprintCheck::Stored)) => println!(
+                "stored    fingerprint of '{}', later runs compare against it",
+                account
+            ),
+            Some((account, FingerprintCheck::Matched)) => {
+                println!("ok        fingerprint of '{}'", account)
+            }
+            Some((account, FingerprintCheck::Diverged(stage))) => println!(
+                "MISMATCH  fingerprint of '{}': diverges at the {}",
+                account, stage
+            ),
+            None => {}
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(ErrorCode::NotDeterministic.error(format!(
+            "{} of {} {} differ, this build would generate other passwords",
+            mismatches,
+            results.len(),
+            what
+        )));
+    }
+    if let Some((account, FingerprintCheck::Diverged(stage))) = fingerprint {
+        return Err(ErrorCode::NotDeterministic.error(format!(
+            "'{}' generates differently here than where its fingerprint was stored, starting at the {}",
+            account, stage
+        )));
+    }
+    Ok(())
+}
+
+// the fingerprint of the --account configuration, or the first, against the one
+// stored with it. the first check of one stored without stores this build's. None
+// when the vault has no configuration to check
+fn verify_fingerprint(account: Option<&str>) -> io::Result<Option<(String, FingerprintCheck)>> {
+    let config = match table_configuration(account) {
+        Ok(config) => config,
+        Err(e) if ErrorCode::from_io(&e) == ErrorCode::NoConfiguration => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    // it loaded, so it decodes again
+    let Some(fingerprint) = Fingerprint::of(&config) else {
+        return Ok(None);
+    };
+
+    let check = match config.fingerprint {
+        Some(stored) => match fingerprint.diverged_stage(&stored) {
+            Some(stage) => FingerprintCheck::Diverged(stage),
+            None => FingerprintCheck::Matched,
+        },
+        None => {
+            // stored from the whole record, the one above was read without points
+            let exe_path = vault_path()?;
+            let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+            let mut password_manager = PasswordManager::new(false, None, true)?;
+            let idx = select_account(&password_manager, Some(&config.name))?;
+            let mut saved_password = password_manager.saved_passwords.remove(idx);
+            saved_password.fingerprint = Some(fingerprint);
+            password_manager.save_password(&saved_password)?;
+            FingerprintCheck::Stored
+        }
+    };
+    Ok(Some((config.name.clone(), check)))
+}
+
+// management commands print one JSON document instead of prose, and errors go to
+// stderr as the same {"error":...} object json-io uses. set while parsing so that
+// usage errors come out as JSON too
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::SeqCst)
+}
+
+enum DomainsCommand {
+    List,
+    Get(String),
+    Set(String, String),
+    Increment(String),
+    Commit(String),
+    Pin(String, String),
+    Words(String, String),
+    Rules(String, String, String),
+    Migrate(String),
+}
+
+enum ProfilesCommand {
+    List,
+    Add(String, Option<String>),
+}
+
+enum CliCommand {
+    Interactive,
+    Setup,
+    Term,
+    Io,
+    Preview(String),
+    Pin(String),
+    JsonIo,
+    Daemon,
+    Askpass(Option<String>),
+    ChildProcess,
+    Accounts,
+    Inspect(String),
+    CreateAccount(String),
+    // the configuration to re-key, and the name of the re-key when given
+    Rekey(String, Option<String>),
+    ErrorCodes,
+    VerifyDeterminism,
+    SelfTest,
+    Domains(DomainsCommand),
+    Profiles(ProfilesCommand),
+    Script(String),
+    ExportRecovery(String),
+    ImportRecovery(String),
+    Username(String),
+    RateLimit(String, String, String),
+    // true sets or changes the unlock secret, false removes it
+    Unlock(bool),
+    // the configuration to mark as the decoy, None to unmark it
+    Decoy(Option<String>),
+    ShredBackups,
+    VaultStats,
+    AcceptNewBinary,
+    // --fix offers the fixes it can make
+    Doctor(bool),
+    Help,
+    Version,
+}
+
+struct Cli {
+    command: CliCommand,
+    account: Option<String>,
+    profile: Option<String>,
+    use_domain_counter: Option<String>,
+    quiet: bool,
+    auto_exit: bool,
+    via_daemon: bool,
+    // the command's own options, e.g. --idle-timeout for json-io, passed along as given
+    command_args: Vec<String>,
+}
+
+fn usage_error(message: String) -> io::Error {
+    ErrorCode::Usage.error(format!("{}. Run with --help for usage", message))
+}
+
+// global options may go anywhere. the command is the first other word, either a
+// subcommand name or one of the old --flag spellings
+fn parse_cli(args: &[String]) -> io::Result<Cli> {
+    let mut cli = Cli {
+        command: CliCommand::Interactive,
+        account: None,
+        profile: None,
+        use_domain_counter: None,
+        quiet: false,
+        auto_exit: false,
+        via_daemon: false,
+        command_args: Vec::new(),
+    };
+    let mut command: Option<&str> = None;
+    let mut positionals: Vec<String> = Vec::new();
+    let mut given_options: Vec<&str> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| usage_error(format!("{} needs a value", arg)))
+        };
+
+        match arg {
+            "--account" => {
+                cli.account = Some(value()?);
+                i += 1;
+            }
+            "--profile" => {
+                cli.profile = Some(value()?);
+                i += 1;
+            }
+            "--use-domain-counter" => {
+                cli.use_domain_counter = Some(value()?);
+                i += 1;
+            }
+            "--timeout" => {
+                let seconds = value()?
+                    .parse::<u64>()
+                    .map_err(|_| usage_error("--timeout needs a number of seconds".to_string()))?;
+                INPUT_TIMEOUT_SECS.store(seconds, Ordering::SeqCst);
+                i += 1;
+            }
+            "--quiet" | "-q" => cli.quiet = true,
+            "--verbose" => VERBOSE.store(true, Ordering::SeqCst),
+            "--no-persist" => NO_PERSIST.store(true, Ordering::SeqCst),
+            "--no-padding" => NO_PADDING.store(true, Ordering::SeqCst),
+            "--no-harden" => NO_HARDEN.store(true, Ordering::SeqCst),
+            "--fix-perms" => FIX_PERMS.store(true, Ordering::SeqCst),
+            "--duress" => DURESS.store(true, Ordering::SeqCst),
+            "--json" => JSON_OUTPUT.store(true, Ordering::SeqCst),
+            "--auto-exit" => cli.auto_exit = true,
+            "--via-daemon" => cli.via_daemon = true,
+            "--i-know-what-im-doing" => ALLOW_WEAK_PHRASE.store(true, Ordering::SeqCst),
+            // everything after it is the command to run and its arguments
+            "--exec" => {
+                if i + 1 >= args.len() {
+                    return Err(usage_error("--exec needs a command".to_string()));
+                }
+                given_options.push("--exec");
+                cli.command_args.extend(args[i..].iter().cloned());
+                break;
+            }
+            "--help" | "-h" => {
+                return Ok(Cli {
+                    command: CliCommand::Help,
+                    ..cli
+                })
+            }
+            "--version" | "-V" => {
+                return Ok(Cli {
+                    command: CliCommand::Version,
+                    ..cli
+                })
+            }
+            _ if command.is_none() && !arg.starts_with('-') && is_command_name(arg) => {
+                command = Some(arg)
+            }
+            _ if command.is_none() && is_command_alias(arg) => command = Some(arg),
+            // options belonging to a command, checked once the command is known
+            _ if command_option(arg).is_some() => {
+                given_options.push(arg);
+                cli.command_args.push(arg.to_string());
+                if command_option(arg) == Some(true) {
+                    cli.command_args.push(value()?);
+                    i += 1;
+                }
+            }
+            // a lone - is a positional, stdin for import-recovery
+            _ if arg.starts_with('-') && arg != "-" && !arg.starts_with("--parent-window=") => {
+                return Err(usage_error(format!("Unknown option '{}'", arg)));
+            }
+            _ => positionals.push(arg.to_string()),
+        }
+        i += 1;
+    }
+
+    let allowed: &[&str] = match command {
+        Some("term" | "--term") => &[
+            "--hidden",
+            "--confirm",
+            "--length",
+            "--words",
+            "--domain",
+            "--qr",
+            "--show",
+            "--tty-prompt",
+            "--print0",
+            "--type",
+            "--countdown",
+            "--type-delay",
+        ],
+        Some("io" | "--io") => &[
+            "--confirm",
+            "--length",
+            "--words",
+            "--format",
+            "--bytes",
+            "--domain",
+            "--qr",
+            "--show",
+            "--tty-prompt",
+            "--print0",
+            "--exec",
+            "--fd",
+            "--type",
+            "--countdown",
+            "--type-delay",
+        ],
+        Some("askpass" | "--askpass") => &["--domain"],
+        Some("preview" | "--preview") => &[
+            "--confirm",
+            "--length",
+            "--qr",
+            "--show",
+            "--tty-prompt",
+            "--print0",
+        ],
+        Some("pin" | "--pin") => &["--confirm", "--qr", "--show", "--tty-prompt", "--print0"],
+        Some("json-io" | "--json-io" | "daemon" | "--daemon") => {
+            &["--idle-timeout", "--exit-after"]
+        }
+        Some("username" | "--username") => &["--length", "--digits"],
+        Some("domains" | "--set-rules") => &RULE_TOGGLES,
+        Some("export-recovery" | "--export-recovery") => &["--qr", "--passphrase-file"],
+        Some("import-recovery" | "--import-recovery") => &["--passphrase-file"],
+        Some("set-unlock" | "--set-unlock" | "remove-unlock" | "--remove-unlock") => {
+            &["--secret-file", "--decoy"]
+        }
+        Some(
+            "set-decoy"
+            | "--set-decoy"
+            | "remove-decoy"
+            | "--remove-decoy"
+            | "accept-new-binary"
+            | "--accept-new-binary",
+        ) => &["--secret-file"],
+        Some("setup" | "--setup") => &[
+            "--add",
+            "--replace",
+            "--dimensions",
+            "--range",
+            "--step",
+            "--variance",
+            "--max-points",
+            "--extra-chars",
+            "--pool",
+            "--pool-file",
+            "--reproducible",
+        ],
+        Some("--child-process") => &[
+            "--ipc",
+            "--setup",
+            "--add",
+            "--replace",
+            "--dimensions",
+            "--range",
+            "--step",
+            "--variance",
+            "--max-points",
+            "--extra-chars",
+            "--pool",
+            "--pool-file",
+            "--reproducible",
+        ],
+        Some("rekey" | "--rekey") => &["--extra-chars", "--pool", "--pool-file"],
+        Some("doctor" | "--doctor") => &["--fix"],
+        Some("create-account" | "--create-account") => &[
+            "--phrase-file",
+            "--dimensions",
+            "--range",
+            "--step",
+            "--variance",
+            "--max-points",
+            "--extra-chars",
+            "--pool",
+            "--pool-file",
+            "--charset",
+            "--description",
+            "--force",
+        ],
+        _ => &[],
+    };
+    if let Some(option) = given_options.iter().find(|a| !allowed.contains(a)) {
+        return Err(usage_error(format!(
+            "'{}' does not apply to {}",
+            option,
+            command.unwrap_or("the interactive session")
+        )));
+    }
+    if given_options.contains(&"--add") && given_options.contains(&"--replace") {
+        return Err(usage_error(
+            "--add and --replace can't be combined".to_string(),
+        ));
+    }
+    if cli.use_domain_counter.is_some() && given_options.contains(&"--domain") {
+        return Err(usage_error(
+            "--domain and --use-domain-counter can't be combined".to_string(),
+        ));
+    }
+    if cli.use_domain_counter.is_some() && matches!(command, Some("preview" | "--preview")) {
+        return Err(usage_error(
+            "preview and --use-domain-counter can't be combined".to_string(),
+        ));
+    }
+
+    let arity = |positionals: &[String], n: usize| -> io::Result<()> {
+        if positionals.len() == n {
+            Ok(())
+        } else {
+            Err(usage_error(format!(
+                "{} takes {} argument(s), got {}",
+                command.unwrap_or("void_vault"),
+                n,
+                positionals.len()
+            )))
+        }
+    };
+
+    cli.command = match command {
+        None => {
+            // the browser starts the host with its own arguments (the extension
+            // origin, --parent-window= on Windows, the manifest path on Firefox)
+            if positionals.len() == 1 && std::env::var_os(ASKPASS_DOMAIN_VAR).is_some() {
+                // SSH_ASKPASS pointing straight at the binary, called with the prompt
+                CliCommand::Askpass(positionals.pop())
+            } else if is_native_messaging_mode(&positionals) {
+                CliCommand::JsonIo
+            } else if let Some(word) = positionals.first() {
+                return Err(usage_error(format!("Unknown command '{}'", word)));
+            } else if stdin_is_redirected() {
+                // `void_vault < phrase.txt`, or a pipe from a script: the phrase, as io
+                // reads it
+                CliCommand::Io
+            } else {
+                CliCommand::Interactive
+            }
+        }
+        Some("setup" | "--setup") => {
+            arity(&positionals, 0)?;
+            CliCommand::Setup
+        }
+        Some("term" | "--term") => {
+            arity(&positionals, 0)?;
+            CliCommand::Term
+        }
+        Some("io" | "--io") => {
+            arity(&positionals, 0)?;
+            CliCommand::Io
+        }
+        Some("preview" | "--preview") => {
+            arity(&positionals, 1)?;
+            CliCommand::Preview(positionals.remove(0))
+        }
+        Some("pin" | "--pin") => {
+            arity(&positionals, 1)?;
+            CliCommand::Pin(positionals.remove(0))
+        }
+        Some("json-io" | "--json-io") => {
+            arity(&positionals, 0)?;
+            CliCommand::JsonIo
+        }
+        Some("askpass" | "--askpass") => {
+            if positionals.len() > 1 {
+                return Err(usage_error(format!(
+                    "askpass takes an optional prompt, got {} arguments",
+                    positionals.len()
+                )));
+            }
+            CliCommand::Askpass(positionals.pop())
+        }
+        Some("daemon" | "--daemon") => {
+            arity(&positionals, 0)?;
+            CliCommand::Daemon
+        }
+        Some("--child-process") => CliCommand::ChildProcess,
+        Some("accounts" | "--list") => {
+            arity(&positionals, 0)?;
+            CliCommand::Accounts
+        }
+        Some("inspect" | "--inspect") => {
+            arity(&positionals, 1)?;
+            CliCommand::Inspect(positionals.remove(0))
+        }
+        Some("create-account" | "--create-account") => {
+            arity(&positionals, 1)?;
+            CliCommand::CreateAccount(positionals.remove(0))
+        }
+        Some("rekey" | "--rekey") => {
+            if positionals.is_empty() || positionals.len() > 2 {
+                return Err(usage_error(format!(
+                    "rekey takes a configuration and an optional new name, got {} argument(s)",
+                    positionals.len()
+                )));
+            }
+            let account = positionals.remove(0);
+            CliCommand::Rekey(account, positionals.pop())
+        }
+        Some("error-codes" | "--list-error-codes") => {
+            arity(&positionals, 0)?;
+            CliCommand::ErrorCodes
+        }
+        Some("verify-determinism" | "--verify-determinism") => {
+            arity(&positionals, 0)?;
+            CliCommand::VerifyDeterminism
+        }
+        Some("self-test" | "--self-test") => {
+            arity(&positionals, 0)?;
+            CliCommand::SelfTest
+        }
+        Some("--list-domains") => {
+            arity(&positionals, 0)?;
+            CliCommand::Domains(DomainsCommand::List)
+        }
+        Some("--get-counter") => {
+            arity(&positionals, 1)?;
+            CliCommand::Domains(DomainsCommand::Get(positionals.remove(0)))
+        }
+        Some("--set-counter") => {
+            arity(&positionals, 2)?;
+            let domain = positionals.remove(0);
+            CliCommand::Domains(DomainsCommand::Set(domain, positionals.remove(0)))
+        }
+        Some("--set-rules") => {
+            arity(&positionals, 3)?;
+            let domain = positionals.remove(0);
+            let max_length = positionals.remove(0);
+            CliCommand::Domains(DomainsCommand::Rules(
+                domain,
+                max_length,
+                positionals.remove(0),
+            ))
+        }
+        Some("--increment-counter") => {
+            arity(&positionals, 1)?;
+            CliCommand::Domains(DomainsCommand::Increment(positionals.remove(0)))
+        }
+        Some("--commit-counter") => {
+            arity(&positionals, 1)?;
+            CliCommand::Domains(DomainsCommand::Commit(positionals.remove(0)))
+        }
+        Some("--migrate-domain") => {
+            arity(&positionals, 1)?;
+            CliCommand::Domains(DomainsCommand::Migrate(positionals.remove(0)))
+        }
+        Some("domains") => {
+            let action = if positionals.is_empty() {
+                "list".to_string()
+            } else {
+                positionals.remove(0)
+            };
+            if let Some(toggle) = RULE_TOGGLES
+                .iter()
+                .find(|toggle| action != "rules" && given_options.contains(toggle))
+            {
+                return Err(usage_error(format!(
+                    "'{}' does not apply to domains {}",
+                    toggle, action
+                )));
+            }
+            match action.as_str() {
+                "list" => {
+                    arity(&positionals, 0)?;
+                    CliCommand::Domains(DomainsCommand::List)
+                }
+                "get" => {
+                    arity(&positionals, 1)?;
+                    CliCommand::Domains(DomainsCommand::Get(positionals.remove(0)))
+                }
+                "set" => {
+                    arity(&positionals, 2)?;
+                    let domain = positionals.remove(0);
+                    CliCommand::Domains(DomainsCommand::Set(domain, positionals.remove(0)))
+                }
+                "increment" => {
+                    arity(&positionals, 1)?;
+                    CliCommand::Domains(DomainsCommand::Increment(positionals.remove(0)))
+                }
+                "commit" => {
+                    arity(&positionals, 1)?;
+                    CliCommand::Domains(DomainsCommand::Commit(positionals.remove(0)))
+                }
+                "pin" => {
+                    arity(&positionals, 2)?;
+                    let domain = positionals.remove(0);
+                    CliCommand::Domains(DomainsCommand::Pin(domain, positionals.remove(0)))
+                }
+                "words" => {
+                    arity(&positionals, 2)?;
+                    let domain = positionals.remove(0);
+                    CliCommand::Domains(DomainsCommand::Words(domain, positionals.remove(0)))
+                }
+                "rules" => {
+                    arity(&positionals, 3)?;
+                    let domain = positionals.remove(0);
+                    let max_length = positionals.remove(0);
+                    CliCommand::Domains(DomainsCommand::Rules(
+                        domain,
+                        max_length,
+                        positionals.remove(0),
+                    ))
+                }
+                "migrate" => {
+                    arity(&positionals, 1)?;
+                    CliCommand::Domains(DomainsCommand::Migrate(positionals.remove(0)))
+                }
+                other => {
+                    return Err(usage_error(format!("Unknown domains action '{}'", other)));
+                }
+            }
+        }
+        Some("--profile-list") => {
+            arity(&positionals, 0)?;
+            CliCommand::Profiles(ProfilesCommand::List)
+        }
+        Some("--profile-add") => {
+            if positionals.is_empty() || positionals.len() > 2 {
+                return Err(usage_error(format!(
+                    "--profile-add takes a name and an optional path, got {} argument(s)",
+                    positionals.len()
+                )));
+            }
+            let name = positionals.remove(0);
+            CliCommand::Profiles(ProfilesCommand::Add(name, positionals.pop()))
+        }
+        Some("profiles") => {
+            let action = if positionals.is_empty() {
+                "list".to_string()
+            } else {
+                positionals.remove(0)
+            };
+            match action.as_str() {
+                "list" => {
+                    arity(&positionals, 0)?;
+                    CliCommand::Profiles(ProfilesCommand::List)
+                }
+                "add" => {
+                    if positionals.is_empty() || positionals.len() > 2 {
+                        return Err(usage_error(format!(
+                            "profiles add takes a name and an optional path, got {} argument(s)",
+                            positionals.len()
+                        )));
+                    }
+                    let name = positionals.remove(0);
+                    CliCommand::Profiles(ProfilesCommand::Add(name, positionals.pop()))
+                }
+                other => {
+                    return Err(usage_error(format!("Unknown profiles action '{}'", other)));
+                }
+            }
+        }
+        Some("username" | "--username") => {
+            arity(&positionals, 1)?;
+            CliCommand::Username(positionals.remove(0))
+        }
+        Some("export-recovery" | "--export-recovery") => {
+            arity(&positionals, 1)?;
+            CliCommand::ExportRecovery(positionals.remove(0))
+        }
+        Some("import-recovery" | "--import-recovery") => {
+            arity(&positionals, 1)?;
+            CliCommand::ImportRecovery(positionals.remove(0))
+        }
+        Some("script" | "--script") => {
+            arity(&positionals, 1)?;
+            CliCommand::Script(positionals.remove(0))
+        }
+        Some("rate-limit" | "--set-rate-limit") => {
+            arity(&positionals, 3)?;
+            let per_second = positionals.remove(0);
+            let burst = positionals.remove(0);
+            CliCommand::RateLimit(per_second, burst, positionals.remove(0))
+        }
+        Some("set-unlock" | "--set-unlock") => {
+            arity(&positionals, 0)?;
+            CliCommand::Unlock(true)
+        }
+        Some("remove-unlock" | "--remove-unlock") => {
+            arity(&positionals, 0)?;
+            CliCommand::Unlock(false)
+        }
+        Some("set-decoy" | "--set-decoy") => {
+            arity(&positionals, 1)?;
+            CliCommand::Decoy(Some(positionals.remove(0)))
+        }
+        Some("remove-decoy" | "--remove-decoy") => {
+            arity(&positionals, 0)?;
+            CliCommand::Decoy(None)
+        }
+        Some("shred-backups" | "--shred-backups") => {
+            arity(&positionals, 0)?;
+            CliCommand::ShredBackups
+        }
+        Some("vault-stats" | "--vault-stats") => {
+            arity(&positionals, 0)?;
+            CliCommand::VaultStats
+        }
+        Some("accept-new-binary" | "--accept-new-binary") => {
+            arity(&positionals, 0)?;
+            CliCommand::AcceptNewBinary
+        }
+        Some("doctor" | "--doctor") => {
+            arity(&positionals, 0)?;
+            CliCommand::Doctor(given_options.contains(&"--fix"))
+        }
+        Some("help") => CliCommand::Help,
+        Some(other) => return Err(usage_error(format!("Unknown command '{}'", other))),
+    };
+
+    Ok(cli)
+}
+
+// Some(true) for options that take a value
+// the on|off options of domains rules
+const RULE_TOGGLES: [&str; 4] = [
+    "--no-confusables",
+    "--starts-with-letter",
+    "--no-leading-symbol",
+    "--no-trailing-symbol",
+];
+
+fn command_option(arg: &str) -> Option<bool> {
+    match arg {
+        "--force" | "--hidden" | "--replace" | "--setup" | "--tty-prompt" | "--print0"
+        | "--confirm" | "--qr" | "--show" | "--type" | "--decoy" | "--reproducible" | "--fix" => {
+            Some(false)
+        }
+        "--idle-timeout"
+        | "--exit-after"
+        | "--ipc"
+        | "--phrase-file"
+        | "--dimensions"
+        | "--extra-chars"
+        | "--charset"
+        | "--description"
+        | "--length"
+        | "--domain"
+        | "--add"
+        | "--digits"
+        | "--words"
+        | "--format"
+        | "--bytes"
+        | "--passphrase-file"
+        | "--fd"
+        | "--countdown"
+        | "--type-delay"
+        | "--secret-file"
+        | "--range"
+        | "--step"
+        | "--variance"
+        | "--max-points"
+        | "--pool"
+        | "--pool-file"
+        | "--no-confusables"
+        | "--starts-with-letter"
+        | "--no-leading-symbol"
+        | "--no-trailing-symbol" => Some(true),
+        _ => None,
+    }
+}
+
+fn is_command_name(arg: &str) -> bool {
+    matches!(
+        arg,
+        "setup"
+            | "term"
+            | "io"
+            | "preview"
+            | "pin"
+            | "json-io"
+            | "daemon"
+            | "askpass"
+            | "accounts"
+            | "inspect"
+            | "create-account"
+            | "rekey"
+            | "domains"
+            | "profiles"
+            | "script"
+            | "export-recovery"
+            | "import-recovery"
+            | "username"
+            | "rate-limit"
+            | "set-unlock"
+            | "remove-unlock"
+            | "set-decoy"
+            | "remove-decoy"
+            | "shred-backups"
+            | "vault-stats"
+            | "accept-new-binary"
+            | "doctor"
+            | "error-codes"
+            | "verify-determinism"
+            | "self-test"
+            | "help"
+    )
+}
+
+fn is_command_alias(arg: &str) -> bool {
+    matches!(
+        arg,
+        "--setup"
+            | "--term"
+            | "--io"
+            | "--json-io"
+            | "--daemon"
+            | "--askpass"
+            | "--child-process"
+            | "--list"
+            | "--inspect"
+            | "--create-account"
+            | "--list-domains"
+            | "--get-counter"
+            | "--set-counter"
+            | "--set-rules"
+            | "--increment-counter"
+            | "--commit-counter"
+            | "--preview"
+            | "--pin"
+            | "--set-rate-limit"
+            | "--set-unlock"
+            | "--remove-unlock"
+            | "--set-decoy"
+            | "--remove-decoy"
+            | "--shred-backups"
+            | "--vault-stats"
+            | "--accept-new-binary"
+            | "--doctor"
+            | "--list-error-codes"
+            | "--verify-determinism"
+            | "--self-test"
+            | "--profile-list"
+            | "--profile-add"
+            | "--script"
+            | "--username"
+            | "--export-recovery"
+            | "--import-recovery"
+    )
+}
+
+// the configuration --account names, or the first one
+fn select_account(password_manager: &PasswordManager, account: Option<&str>) -> io::Result<usize> {
+    // one that's there but damaged is refused as such, not generated from
+    let missing = |account: Option<&str>| match password_manager.find_unusable(account) {
+        Some((name, e)) => unusable_configuration(name, e),
+        None if password_manager.saved_passwords.is_empty() => {
+            ErrorCode::NoConfiguration.error("No geometry found. Please create one first.")
+        }
+        None => ErrorCode::UnknownAccount.error(format!(
+            "No configuration named '{}'",
+            account.unwrap_or_default()
+        )),
+    };
+    if password_manager.saved_passwords.is_empty() {
+        return Err(missing(account));
+    }
+    match account {
+        Some(name) => password_manager
+            .saved_passwords
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| missing(Some(name))),
+        None => Ok(0),
+    }
+}
+
+// the --account configuration, or for a domain the re-key of it the domain still
+// generates with, see PasswordManager::resolve_domain
+fn domain_account(
+    password_manager: &mut PasswordManager,
+    account: Option<&str>,
+    domain: Option<&str>,
+) -> io::Result<usize> {
+    let idx = select_account(password_manager, account)?;
+    let Some(domain) = domain else {
+        return Ok(idx);
+    };
+    DomainTable::shared().load_from_binary(&vault_path()?)?;
+    Ok(password_manager.resolve_domain(idx, domain, &DomainTable::shared()))
+}
+
+fn unusable_configuration(name: &str, e: &DecodeError) -> io::Error {
+    ErrorCode::UnusableConfiguration.error(format!(
+        "Configuration '{}' is damaged and can't be used: {}",
+        name, e
+    ))
+}
+
+// the configuration select_account would pick, read without its structure's points:
+// the commands that only look up or change the domain table just hash a domain
+fn table_configuration(account: Option<&str>) -> io::Result<SavedPassword> {
+    let exe_path = vault_path()?;
+    let mut decoys = DomainTable::new();
+    decoys.load_region(&exe_path, TableRegion::Decoy)?;
+    let storage = BinaryStorageManager::open(exe_path, false, None)?;
+    let duress = DURESS.load(Ordering::SeqCst);
+    PasswordManager::load_one_with_decoy(&storage, account, &decoys, duress).ok_or_else(|| {
+        let names = storage.list_all();
+        // listed but not loaded: the record is there and didn't decode
+        let damaged = names
+            .iter()
+            .find(|(name, _)| account.is_none_or(|account| account == name))
+            .and_then(|(name, _)| {
+                let (data, _) = storage.retrieve(name).ok()??;
+                let e = SavedPassword::from_bytes_without_points(data).err()?;
+                Some(unusable_configuration(name, &e))
+            });
+        match (damaged, account) {
+            (Some(e), _) => e,
+            (None, Some(name)) if !names.is_empty() => {
+                ErrorCode::UnknownAccount.error(format!("No configuration named '{}'", name))
+            }
+            _ => ErrorCode::NoConfiguration.error("No geometry found. Please create one first."),
+        }
+    })
+}
+
+// --no-harden: skip harden::apply, for a debugger or a core dump to look at
+static NO_HARDEN: AtomicBool = AtomicBool::new(false);
+
+// what harden_process managed, once it has run
+static HARDENING: OnceLock<harden::Hardening> = OnceLock::new();
+
+// no core dumps or tracing, and memory locked where the limit allows. a part that
+// doesn't take is a warning, not a reason to refuse to run. the lock limit is the
+// same on every run though, so that one is said at setup, by doctor and with
+// --verbose, not each time the browser starts the host
+fn harden_process() {
+    let hardening = *HARDENING.get_or_init(harden::apply);
+    if !hardening.no_core_dumps {
+        diag(
+            Diag::Warning,
+            format_args!(
+                "Warning: Core dumps could not be turned off, a crash may write secrets to disk"
+            ),
+        );
+    }
+    if let Some(warning) = memory_lock_warning() {
+        diag(Diag::Detail, format_args!("{}", warning));
+    }
+}
+
+// why not all memory is locked, when a lock limit is to blame. Windows has no lock
+// limit to raise, only the typing buffers are ever locked there
+fn memory_lock_warning() -> Option<String> {
+    let hardening = HARDENING.get()?;
+    match (hardening.memory_locked, harden::memlock_limit()) {
+        (false, Some(limit)) => Some(format!(
+            "Warning: Memory could not be locked under a lock limit of {} KiB, only the typing buffers are kept out of swap. ulimit -l unlimited lifts this",
+            limit / 1024
+        )),
+        _ => None,
+    }
+}
+
+// --fix-perms: restrict the executable and the vault to their owner without asking
+static FIX_PERMS: AtomicBool = AtomicBool::new(false);
+
+// the executable, and the vault when a profile keeps it elsewhere, with what others
+// can do to them. both are code or secrets for whoever can replace them
+fn permission_exposures() -> Vec<(PathBuf, perms::Exposure)> {
+    let mut paths: Vec<PathBuf> = std::env::current_exe().into_iter().collect();
+    if let Ok(vault) = vault_path() {
+        if !paths.contains(&vault) {
+            paths.push(vault);
+        }
+    }
+    paths
+        .into_iter()
+        .flat_map(|path| {
+            perms::check(&path)
+                .into_iter()
+                .map(move |exposure| (path.clone(), exposure))
+        })
+        .collect()
+}
+
+// warns about each exposure, then restricts the files that are writable with
+// --fix-perms, or once the user agrees on a terminal. never asks where stdin isn't
+// a person's, as in json-io under the browser
+fn check_permissions(may_ask: bool) -> io::Result<()> {
+    use std::io::IsTerminal;
+
+    let exposures = permission_exposures();
+    for (path, exposure) in &exposures {
+        match exposure {
+            perms::Exposure::FileWritable => diag(
+                Diag::Warning,
+                format_args!(
+                    "WARNING: {} can be written by other users, who could replace it with code that records what you type. --fix-perms restricts it to you",
+                    path.display()
+                ),
+            ),
+            perms::Exposure::DirectoryWritable => diag(
+                Diag::Warning,
+                format_args!(
+                    "WARNING: {} is in a directory other users can write, who could put their own file in its place. Move it, or remove their write access to the directory",
+                    path.display()
+                ),
+            ),
+        }
+    }
+
+    let writable: Vec<&PathBuf> = exposures
+        .iter()
+        .filter(|(_, exposure)| *exposure == perms::Exposure::FileWritable)
+        .map(|(path, _)| path)
+        .collect();
+    if writable.is_empty() {
+        return Ok(());
+    }
+    let mut fix = FIX_PERMS.load(Ordering::SeqCst);
+    if !fix && may_ask && io::stdin().is_terminal() && io::stderr().is_terminal() {
+        eprint!("Restrict them to you now? [y/N] ");
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        read_line_interruptible(&mut answer)?;
+        fix = matches!(answer.trim(), "y" | "Y" | "yes");
+    }
+    if !fix {
+        return Ok(());
+    }
+
+    for path in writable {
+        match perms::restrict(path) {
+            Ok(()) => diag(
+                Diag::Note,
+                format_args!("Restricted {} to its owner", path.display()),
+            ),
+            Err(e) => diag(
+                Diag::Warning,
+                format_args!("Warning: Could not restrict {}: {}", path.display(), e),
+            ),
+        }
+    }
+    Ok(())
+}
 
-                        if let Some(ref domain_hash) = SESSION.active_domain_hash {
-                            let structure = &mut password_manager.saved_passwords
-                                [saved_password_idx]
-                                .structure_system;
+fn run(args: &[String]) -> io::Result<()> {
+    let cli = parse_cli(args)?;
+    // a JSON document on stdout has no room for chatter
+    QUIET.store(cli.quiet || json_output(), Ordering::SeqCst);
 
-                            for i in 0..8 {
-                                let hash_byte = domain_hash[i] as u32;
-                                let _ = structure.transform_char(hash_byte, 0);
-                            }
+    if !NO_HARDEN.load(Ordering::SeqCst)
+        && !matches!(cli.command, CliCommand::Help | CliCommand::Version)
+    {
+        harden_process();
+    }
 
-                            let counter_u32 = saved as u32;
-                            let _ = structure.transform_char(counter_u32, 0);
-                            let _ = structure.transform_char(counter_u32.wrapping_mul(7), 0);
-                            let _ = structure.transform_char(counter_u32.wrapping_add(13), 0);
-                        }
+    let profile = cli.profile.clone().or_else(|| {
+        std::env::var("VOID_VAULT_PROFILE")
+            .ok()
+            .filter(|name| !name.is_empty())
+    });
+    if let Some(name) = &profile {
+        if !matches!(
+            cli.command,
+            CliCommand::Profiles(_) | CliCommand::Help | CliCommand::Version
+        ) {
+            select_profile(name)?;
+        }
+    }
+    // the interactive child's parent has checked already, doctor reports them itself
+    if !matches!(
+        cli.command,
+        CliCommand::Help | CliCommand::Version | CliCommand::ChildProcess | CliCommand::Doctor(_)
+    ) {
+        check_permissions(matches!(
+            cli.command,
+            CliCommand::Interactive | CliCommand::Setup | CliCommand::Term
+        ))?;
+    }
 
-                        let response =
-                            format!("{{\"counter\":{},\"status\":\"cancelled\"}}", saved);
-                        let response_length = response.len() as u32;
-                        stdout.write_all(&response_length.to_le_bytes())?;
-                        stdout.write_all(response.as_bytes())?;
-                        stdout.flush()?;
-                    } else {
-                        let response = "{\"error\":\"Not in preview mode\"}";
-                        let response_length = response.len() as u32;
-                        stdout.write_all(&response_length.to_le_bytes())?;
-                        stdout.write_all(response.as_bytes())?;
-                        stdout.flush()?;
-                    }
-                }
-                continue;
+    // a copy of the vault left by a write that died before its rename, and whether
+    // the code is still what the vault was sealed with. the profile commands have no
+    // vault to go with, the interactive child's parent has checked
+    if !matches!(
+        cli.command,
+        CliCommand::Profiles(_) | CliCommand::Help | CliCommand::Version
+    ) {
+        if let Ok(exe_path) = vault_path() {
+            // doctor only looks
+            if !matches!(cli.command, CliCommand::Doctor(_)) {
+                shred::remove_stale_copy(&exe_path);
+            }
+            if !matches!(cli.command, CliCommand::ChildProcess) {
+                check_code_seal(&exe_path);
             }
         }
+    }
+
+    let mut daemon = if cli.via_daemon {
+        daemon_for(&cli)?
+    } else {
+        None
+    };
 
-        let keycode = extract_json_number(&message, "charCode") as u32;
+    match &cli.command {
+        CliCommand::Help => {
+            print!("{}", USAGE);
+            return Ok(());
+        }
+        CliCommand::Version => {
+            if json_output() {
+                println!(
+                    "{{\"version\":\"{}\",\"protocol\":{}}}",
+                    env!("CARGO_PKG_VERSION"),
+                    PROTOCOL_VERSION
+                );
+            } else {
+                println!("void_vault {}", env!("CARGO_PKG_VERSION"));
+            }
+            return Ok(());
+        }
+        CliCommand::Accounts => {
+            let password_manager = PasswordManager::new(false, None, true)?;
+            if password_manager.saved_passwords.is_empty() {
+                return Err(
+                    ErrorCode::NoConfiguration.error("No geometry found. Please create one first.")
+                );
+            }
 
-        if keycode > 0 {
-            let saved_password = &mut password_manager.saved_passwords[saved_password_idx];
+            if json_output() {
+                let entries: Vec<String> = password_manager
+                    .saved_passwords
+                    .iter()
+                    .map(|password| {
+                        format!(
+                            "{{\"name\":\"{}\",\"description\":\"{}\",\"created\":{},\"extra_chars\":{},\"pool\":\"{}\",\"use_count\":{},\"rekeyed_from\":{}}}",
+                            escape_json(&password.name),
+                            escape_json(&password.description),
+                            password.created_date,
+                            password.extra_chars_count,
+                            pool::name_of(&password.structure_system.character_set),
+                            password.use_count,
+                            password
+                                .rekeyed_from
+                                .as_deref()
+                                .map_or("null".to_string(), |name| format!("\"{}\"", escape_json(name)))
+                        )
+                    })
+                    .collect();
+                println!(
+                    "{{\"configurations\":[{}],\"total\":{}}}",
+                    entries.join(","),
+                    entries.len()
+                );
+                return Ok(());
+            }
 
-            // Offset keycode by sum of all feedbacks
-            let feedback_offset: u32 = feedbacks.iter().map(|&fb| fb as u32).sum();
-            let modified_keycode = keycode.wrapping_add(feedback_offset);
+            println!("Stored configurations:\n");
+            for password in &password_manager.saved_passwords {
+                println!("{}", password.name);
+                println!("  Description: {}", password.description);
+                println!("  Created:     {}", format_unix_date(password.created_date));
+                println!("  Extra chars: {}", password.extra_chars_count);
+                println!(
+                    "  Pool:        {}",
+                    pool::name_of(&password.structure_system.character_set)
+                );
+                println!("  Used:        {} times", password.use_count);
+                if let Some(original) = &password.rekeyed_from {
+                    println!(
+                        "  Re-key of:   {} (re-key {} of its setup)",
+                        original, password.structure_system.lineage
+                    );
+                }
+            }
+            println!(
+                "\nTotal: {} configurations",
+                password_manager.saved_passwords.len()
+            );
+            return Ok(());
+        }
+        CliCommand::Inspect(name) => {
+            let password_manager = PasswordManager::new(false, None, true)?;
+            let inspection = inspect(&password_manager, name)?;
 
-            let mut navigation_sequence = vec![modified_keycode];
-            for &fb in feedbacks.iter().rev() {
-                navigation_sequence.push(fb as u32);
+            if json_output() {
+                println!("{}", inspection.to_json());
+                return Ok(());
             }
 
-            saved_password.structure_system.reset_position();
-            let mut output_sum = 0u64;
-            let mut output_codes = Vec::new();
+            let (min, max) = &inspection.structure_bounds;
+            println!("{}", inspection.name);
+            println!(
+                "  Created:          {}",
+                format_unix_date(inspection.created_date)
+            );
+            println!("  Format version:   {}", inspection.format_version);
+            println!(
+                "  Setup:            {}",
+                inspection
+                    .setup_mode
+                    .map_or("not recorded", |mode| mode.name())
+            );
+            println!("  Normalization:    {}", inspection.normalization.name());
+            println!("  Dimensions:       {}", inspection.dimensions);
+            println!("  Coordinate range: {}", inspection.coordinate_range);
+            println!(
+                "  Character set:    {} characters, {} pool",
+                inspection.character_set, inspection.pool
+            );
+            for (range, count) in &inspection.code_point_ranges {
+                println!("    {:<18}{}", range, count);
+            }
+            println!("  Active points:    {}", inspection.active_points);
+            println!("  Bounds:");
+            for (dimension, (low, high)) in min.iter().zip(max).enumerate() {
+                println!("    {:<18}{} to {}", dimension, low, high);
+            }
+            println!("  Base step size:   {}", inspection.base_step_size);
+            println!("  Step variance:    {}", inspection.step_variance);
+            println!("  Extra chars:      {}", inspection.extra_chars_count);
+            return Ok(());
+        }
+        CliCommand::Profiles(ProfilesCommand::List) => {
+            let registry = ProfileRegistry::load()?;
+
+            if json_output() {
+                let entries: Vec<String> = registry
+                    .profiles
+                    .iter()
+                    .map(|(name, vault)| {
+                        format!(
+                            "{{\"name\":\"{}\",\"path\":\"{}\",\"active\":{}}}",
+                            escape_json(name),
+                            escape_json(&vault.to_string_lossy()),
+                            profile.as_deref() == Some(name.as_str())
+                        )
+                    })
+                    .collect();
+                println!(
+                    "{{\"profiles\":[{}],\"total\":{}}}",
+                    entries.join(","),
+                    entries.len()
+                );
+                return Ok(());
+            }
 
-            for &input_code in &navigation_sequence {
-                let codes = saved_password
-                    .structure_system
-                    .transform_char(input_code, saved_password.extra_chars_count);
+            if registry.profiles.is_empty() {
+                println!("No profiles yet, add one with: profiles add <name>");
+                return Ok(());
+            }
+            println!("Profiles:\n");
+            for (name, vault) in &registry.profiles {
+                let marker = if profile.as_deref() == Some(name.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {}  {}", marker, name, vault.display());
+            }
+            return Ok(());
+        }
+        CliCommand::Profiles(ProfilesCommand::Add(name, path)) => {
+            if !is_profile_name(name) {
+                return Err(ErrorCode::Usage
+                    .error("A profile name is up to 64 letters, digits, '-' and '_'"));
+            }
+            let mut registry = ProfileRegistry::load()?;
+            if registry.find(name).is_some() {
+                return Err(ErrorCode::BadRequest
+                    .error(format!("A profile named '{}' already exists", name)));
+            }
 
-                for &code in &codes {
-                    output_sum = output_sum.wrapping_add(code as u64);
-                    output_codes.push(code);
+            // an existing file is used as it is, e.g. another copy of the binary
+            // carrying its own vault. anything else becomes a new vault file
+            let vault = match path {
+                Some(path) => std::path::absolute(path)?,
+                None => config_dir()?.join(format!("{}.vault", name)),
+            };
+            let created = !vault.exists();
+            if created {
+                if let Some(dir) = vault.parent() {
+                    fs::create_dir_all(dir)?;
                 }
+                create_profile_vault(&vault, name)?;
             }
 
-            let feedback = (output_sum % 256) as u8;
-            feedbacks.push(feedback);
+            registry.profiles.push((name.clone(), vault.clone()));
+            registry.save()?;
 
-            let output_chars: String = output_codes
+            if json_output() {
+                println!(
+                    "{{\"profile\":\"{}\",\"path\":\"{}\",\"status\":\"{}\"}}",
+                    escape_json(name),
+                    escape_json(&vault.to_string_lossy()),
+                    if created { "created" } else { "registered" }
+                );
+            } else if created {
+                println!(
+                    "Added profile {} with a new vault at {}",
+                    name,
+                    vault.display()
+                );
+                println!(
+                    "Run it with --profile {} setup to create its first configuration",
+                    name
+                );
+            } else {
+                println!("Added profile {} using {}", name, vault.display());
+            }
+            return Ok(());
+        }
+        CliCommand::Script(path) => return run_script(path),
+        CliCommand::ExportRecovery(path) | CliCommand::ImportRecovery(path) => {
+            let passphrase_file = cli
+                .command_args
                 .iter()
-                .filter_map(|&code| char::from_u32(code))
-                .collect();
+                .position(|arg| arg == "--passphrase-file")
+                .and_then(|i| cli.command_args.get(i + 1))
+                .map(String::as_str);
+            if matches!(cli.command, CliCommand::ExportRecovery(_)) {
+                require_unlock(&mut io::stderr())?;
+                let qr = cli.command_args.iter().any(|arg| arg == "--qr");
+                return run_export_recovery(path, qr, passphrase_file);
+            }
+            return run_import_recovery(path, passphrase_file);
+        }
+        CliCommand::Username(domain) => {
+            require_unlock(&mut io::stderr())?;
+            let option = |name: &str| {
+                cli.command_args
+                    .iter()
+                    .position(|arg| arg == name)
+                    .and_then(|i| cli.command_args.get(i + 1))
+            };
+            let length = match option("--length") {
+                Some(value) => value
+                    .parse()
+                    .ok()
+                    .filter(|n| USERNAME_LENGTHS.contains(n))
+                    .ok_or_else(|| ErrorCode::Usage.error("--length must be 4-32"))?,
+                None => DEFAULT_USERNAME_LENGTH,
+            };
+            let digits = match option("--digits").map(String::as_str) {
+                Some("on") => Some(true),
+                Some("off") => Some(false),
+                Some(_) => return Err(ErrorCode::Usage.error("--digits is on or off")),
+                None => None,
+            };
+
+            let exe_path = vault_path()?;
+            // only setting --digits writes
+            let _instance_lock = match digits {
+                Some(_) => Some(InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?),
+                None => None,
+            };
+            DomainTable::shared().load_from_binary(&exe_path)?;
 
-            let mut response = String::from("{\"output\":\"");
+            let mut config = table_configuration(cli.account.as_deref())?;
+            let structure = &mut config.structure_system;
 
-            for ch in output_chars.chars() {
-                match ch {
-                    '"' => response.push_str("\\\""),
-                    '\\' => response.push_str("\\\\"),
-                    '\n' => response.push_str("\\n"),
-                    '\r' => response.push_str("\\r"),
-                    '\t' => response.push_str("\\t"),
-                    _ => response.push(ch),
-                }
+            if let Some(digits) = digits {
+                DomainTable::shared()
+                    .set_username_digits(domain, digits, structure)
+                    .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+                DomainTable::shared().save_to_binary(&exe_path)?;
             }
-            response.push_str("\"}");
+            let digits = DomainTable::shared().username_digits(domain, structure);
+            let counter = DomainTable::shared()
+                .get_counter(domain, structure)
+                .unwrap_or(0);
+            let domain_hash = structure.hash_domain(domain);
+            let username = Wiped(structure.derive_username(&domain_hash, counter, length, digits));
 
-            let response_length = response.len() as u32;
-            stdout.write_all(&response_length.to_le_bytes())?;
-            stdout.write_all(response.as_bytes())?;
-            stdout.flush()?;
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"username\":\"{}\",\"counter\":{},\"digits\":{}}}",
+                    escape_json(domain),
+                    username.as_str(),
+                    counter,
+                    digits
+                );
+            } else {
+                println!("{}", username.as_str());
+            }
+            return Ok(());
         }
-    }
+        CliCommand::ErrorCodes => {
+            if json_output() {
+                let entries: Vec<String> = ErrorCode::ALL
+                    .iter()
+                    .map(|code| {
+                        format!(
+                            "{{\"code\":\"{}\",\"exit_code\":{},\"description\":\"{}\"}}",
+                            code.code(),
+                            code.exit_code(),
+                            escape_json(code.description())
+                        )
+                    })
+                    .collect();
+                println!("{{\"error_codes\":[{}]}}", entries.join(","));
+                return Ok(());
+            }
 
-    Ok(())
-}
+            // code, exit status and meaning, tab separated so the extension can vendor it
+            for code in ErrorCode::ALL {
+                println!(
+                    "{}\t{}\t{}",
+                    code.code(),
+                    code.exit_code(),
+                    code.description()
+                );
+            }
+            return Ok(());
+        }
+        CliCommand::VerifyDeterminism => {
+            let results: Vec<(&str, u64, u64)> = DETERMINISM_VECTORS
+                .iter()
+                .map(|vector| (vector.name, vector.expected, vector.compute()))
+                .collect();
+            let fingerprint = verify_fingerprint(cli.account.as_deref())?;
+            return report_golden_hashes(
+                "vectors",
+                "golden vectors",
+                &results,
+                fingerprint.as_ref(),
+            );
+        }
+        CliCommand::SelfTest => {
+            let results: Vec<(&str, u64, u64)> = SELF_TESTS
+                .iter()
+                .map(|test| (test.name, test.expected, test.compute()))
+                .collect();
+            return report_golden_hashes("sequences", "self-test sequences", &results, None);
+        }
+        CliCommand::Domains(DomainsCommand::List) => {
+            let exe_path = vault_path()?;
+            DomainTable::shared().load_from_binary(&exe_path)?;
+
+            if json_output() {
+                let mut entries = Vec::new();
+                for (i, slot) in DomainTable::shared().slots().iter().enumerate() {
+                    if !slot.is_empty() && !slot.is_reserved() {
+                        let hex: String = slot.domain_hash[..16]
+                            .iter()
+                            .map(|b| format!("{:02x}", b))
+                            .collect();
+                        entries.push(format!(
+                            "{{\"slot\":{},\"hash_prefix\":\"{}\",\"counter\":{},\"max_length\":{},\"char_types\":{},\"username_digits\":{},\"no_confusables\":{},{},\"pin_length\":{},\"word_count\":{},\"lineage\":{}}}",
+                            i,
+                            hex,
+                            slot.counter,
+                            slot.max_length & DomainSlot::LENGTH,
+                            slot.char_types & !DomainSlot::USERNAME_DIGITS,
+                            slot.char_types & DomainSlot::USERNAME_DIGITS != 0,
+                            slot.max_length & DomainSlot::NO_CONFUSABLES != 0,
+                            positions_json(slot.positions()),
+                            FixedOutput::from_byte(slot.fixed_output).pin_length(),
+                            FixedOutput::from_byte(slot.fixed_output).word_count(),
+                            slot.lineage()
+                        ));
+                    }
+                }
+                println!(
+                    "{{\"domains\":[{}],\"total\":{}}}",
+                    entries.join(","),
+                    entries.len()
+                );
+                return Ok(());
+            }
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+            DomainTable::shared().print_list();
+            return Ok(());
+        }
+        CliCommand::Domains(DomainsCommand::Get(domain)) => {
+            let not_registered =
+                || ErrorCode::UnknownDomain.error(format!("{} is not registered", domain));
+
+            let (counter, max_length, char_types, fixed_output, no_confusables, positions) =
+                match daemon.as_mut() {
+                    Some(daemon) => {
+                        // ACTIVATE without registering has the PIN and passphrase shape too
+                        let peek = daemon.request(&format!(
+                            "{{\"type\":\"PEEK_COUNTER\",\"domain\":\"{}\"}}",
+                            escape_json(domain)
+                        ))?;
+                        if extract_json_bool(&peek, "registered") != Some(true) {
+                            return Err(not_registered());
+                        }
+                        let reply = daemon.request(&format!(
+                            "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\",\"register\":false}}",
+                            escape_json(domain)
+                        ))?;
+                        let fixed_output = match (
+                            extract_json_number(&reply, "pin_length") as u8,
+                            extract_json_number(&reply, "word_count") as u8,
+                        ) {
+                            (0, 0) => FixedOutput::Password,
+                            (0, words) => FixedOutput::Words(words),
+                            (digits, _) => FixedOutput::Pin(digits),
+                        };
+                        (
+                            extract_json_number(&reply, "saved_counter") as u16,
+                            extract_json_number(&reply, "max_length") as u16,
+                            extract_json_number(&reply, "char_types") as u8,
+                            fixed_output,
+                            extract_json_bool(&reply, "no_confusables") == Some(true),
+                            json_positions(&reply, Positions::NONE),
+                        )
+                    }
+                    None => {
+                        let exe_path = vault_path()?;
+                        DomainTable::shared().load_from_binary(&exe_path)?;
+
+                        let mut config = table_configuration(cli.account.as_deref())?;
+                        let structure = &mut config.structure_system;
+
+                        let counter = DomainTable::shared()
+                            .get_counter(domain, structure)
+                            .ok_or_else(not_registered)?;
+                        let (max_length, char_types) = DomainTable::shared()
+                            .get_rules(domain, structure)
+                            .unwrap_or((0, 127));
+                        let fixed_output =
+                            DomainTable::shared().get_fixed_output(domain, structure);
+                        let no_confusables =
+                            DomainTable::shared().no_confusables(domain, structure);
+                        let positions = DomainTable::shared().positions(domain, structure);
+                        (
+                            counter,
+                            max_length,
+                            char_types,
+                            fixed_output,
+                            no_confusables,
+                            positions,
+                        )
+                    }
+                };
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"counter\":{},\"max_length\":{},\"char_types\":{},\"no_confusables\":{},{},\"pin_length\":{},\"word_count\":{}}}",
+                    escape_json(domain),
+                    counter,
+                    max_length,
+                    char_types,
+                    no_confusables,
+                    positions_json(positions),
+                    fixed_output.pin_length(),
+                    fixed_output.word_count()
+                );
+            } else {
+                println!("{}: v{}", domain, counter);
+            }
+            return Ok(());
+        }
+        CliCommand::Domains(DomainsCommand::Set(domain, counter)) => {
+            let counter: u16 = counter
+                .parse()
+                .map_err(|_| ErrorCode::Usage.error("Counter must be 0-65535"))?;
+
+            if let Some(daemon) = daemon.as_mut() {
+                daemon.request(&format!(
+                    "{{\"type\":\"SET_COUNTER\",\"domain\":\"{}\",\"counter\":{}}}",
+                    escape_json(domain),
+                    counter
+                ))?;
+            } else {
+                let exe_path = vault_path()?;
+                let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+                DomainTable::shared().load_from_binary(&exe_path)?;
 
-    if args.len() > 1 && args[1] == "--list-domains" {
-        let exe_path = std::env::current_exe()?;
-        DomainTable::load_from_binary(&exe_path)?;
+                let mut config = table_configuration(cli.account.as_deref())?;
+                let structure = &mut config.structure_system;
 
-        let mut count = 0;
-        unsafe {
-            println!("Registered domains (hashes only - domain names cannot be reversed):\n");
-            let table = &*std::ptr::addr_of!(DOMAIN_TABLE);
-            for (i, slot) in table.slots.iter().enumerate() {
-                if !slot.is_empty() {
-                    let hex: String = slot.domain_hash[..16]
-                        .iter()
-                        .map(|b| format!("{:02x}", b))
-                        .collect();
-                    println!("Slot {}: {}... → v{}", i, hex, slot.counter);
-                    count += 1;
-                }
+                DomainTable::shared()
+                    .set_counter(domain, counter, structure)
+                    .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+                DomainTable::shared().save_to_binary(&exe_path)?;
+            }
+
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"counter\":{},\"status\":\"success\"}}",
+                    escape_json(domain),
+                    counter
+                );
+            } else {
+                println!("Set {} to v{}", domain, counter);
             }
+            return Ok(());
         }
-        println!("\nTotal: {} domains registered", count);
-        return Ok(());
-    } else if args.len() > 2 && args[1] == "--get-counter" {
-        let domain = &args[2];
-        let exe_path = std::env::current_exe()?;
-        DomainTable::load_from_binary(&exe_path)?;
-
-        let mut password_manager = PasswordManager::new(false, None, None, true)?;
-        if password_manager.saved_passwords.is_empty() {
-            eprintln!("Error: No geometry found. Please create one first.");
+        CliCommand::Domains(DomainsCommand::Increment(domain)) => {
+            let exe_path = vault_path()?;
+            let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+            DomainTable::shared().load_from_binary(&exe_path)?;
+
+            let mut config = table_configuration(cli.account.as_deref())?;
+            let structure = &mut config.structure_system;
+
+            let new_counter = DomainTable::shared()
+                .increment_counter(domain, structure)
+                .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+            DomainTable::shared().save_to_binary(&exe_path)?;
+
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"counter\":{},\"status\":\"success\"}}",
+                    escape_json(domain),
+                    new_counter
+                );
+            } else {
+                println!("{}: v{}", domain, new_counter);
+            }
             return Ok(());
         }
+        CliCommand::Domains(DomainsCommand::Migrate(domain)) => {
+            let exe_path = vault_path()?;
+            let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+            DomainTable::shared().load_from_binary(&exe_path)?;
 
-        let structure = &mut password_manager.saved_passwords[0].structure_system;
+            let mut config = table_configuration(cli.account.as_deref())?;
+            let structure = &mut config.structure_system;
 
-        match DomainTable::get_counter(domain, structure) {
-            Some(c) => println!("{}: v{}", domain, c),
-            None => println!("{}: not found", domain),
+            let mut table = DomainTable::shared();
+            match table.lineage(domain, structure) {
+                None => {
+                    return Err(
+                        ErrorCode::UnknownDomain.error(format!("{} is not registered", domain))
+                    )
+                }
+                Some(lineage) if lineage == structure.lineage => {
+                    return Err(ErrorCode::BadRequest.error(format!(
+                        "{} already generates with '{}'",
+                        domain, config.name
+                    )))
+                }
+                Some(_) => {}
+            }
+            let counter = table
+                .migrate(domain, structure)
+                .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?
+                .unwrap_or(0);
+            table.save_to_binary(&exe_path)?;
+            drop(table);
+
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"account\":\"{}\",\"counter\":{},\"status\":\"migrated\"}}",
+                    escape_json(domain),
+                    escape_json(&config.name),
+                    counter
+                );
+            } else {
+                println!("{}: v{} with '{}'", domain, counter, config.name);
+            }
+            return Ok(());
         }
-        return Ok(());
-    } else if args.len() > 3 && args[1] == "--set-counter" {
-        let domain = &args[2];
-        let counter: u16 = args[3]
-            .parse()
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Counter must be 0-65535"))?;
+        CliCommand::Domains(DomainsCommand::Commit(domain)) => {
+            let mut config = table_configuration(cli.account.as_deref())?;
+            let structure = &mut config.structure_system;
 
-        let exe_path = std::env::current_exe()?;
-        DomainTable::load_from_binary(&exe_path)?;
+            let counter = commit_cli_counter(structure, domain, None)?;
 
-        let mut password_manager = PasswordManager::new(false, None, None, true)?;
-        if password_manager.saved_passwords.is_empty() {
-            eprintln!("Error: No geometry found. Please create one first.");
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"counter\":{},\"status\":\"committed\"}}",
+                    escape_json(domain),
+                    counter
+                );
+            } else {
+                println!("{}: v{} (committed)", domain, counter);
+            }
             return Ok(());
         }
+        CliCommand::Domains(DomainsCommand::Pin(domain, pin_length)) => {
+            let pin_length: u8 = pin_length
+                .parse()
+                .ok()
+                .filter(|n| *n == 0 || PIN_LENGTHS.contains(n))
+                .ok_or_else(|| ErrorCode::Usage.error("A PIN is 4-16 digits, 0 for none"))?;
+
+            if let Some(daemon) = daemon.as_mut() {
+                daemon.request(&format!(
+                    "{{\"type\":\"SET_PIN\",\"domain\":\"{}\",\"pin_length\":{}}}",
+                    escape_json(domain),
+                    pin_length
+                ))?;
+            } else {
+                let exe_path = vault_path()?;
+                let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+                DomainTable::shared().load_from_binary(&exe_path)?;
 
-        let structure = &mut password_manager.saved_passwords[0].structure_system;
+                let mut config = table_configuration(cli.account.as_deref())?;
+                let structure = &mut config.structure_system;
 
-        DomainTable::set_counter(domain, counter, structure)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        DomainTable::save_to_binary(&exe_path)?;
+                let fixed_output = match pin_length {
+                    0 => FixedOutput::Password,
+                    digits => FixedOutput::Pin(digits),
+                };
+                DomainTable::shared()
+                    .set_fixed_output(domain, fixed_output, structure)
+                    .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+                DomainTable::shared().save_to_binary(&exe_path)?;
+            }
 
-        println!("Set {} to v{}", domain, counter);
-        return Ok(());
-    } else if args.len() > 2 && args[1] == "--increment-counter" {
-        let domain = &args[2];
-        let exe_path = std::env::current_exe()?;
-        DomainTable::load_from_binary(&exe_path)?;
-
-        let mut password_manager = PasswordManager::new(false, None, None, true)?;
-        if password_manager.saved_passwords.is_empty() {
-            eprintln!("Error: No geometry found. Please create one first.");
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"pin_length\":{},\"status\":\"success\"}}",
+                    escape_json(domain),
+                    pin_length
+                );
+            } else if pin_length > 0 {
+                println!("{}: {} digit PIN", domain, pin_length);
+            } else {
+                println!("{}: password", domain);
+            }
             return Ok(());
         }
+        CliCommand::Domains(DomainsCommand::Words(domain, word_count)) => {
+            let word_count: u8 = word_count
+                .parse()
+                .ok()
+                .filter(|n| *n == 0 || WORD_COUNTS.contains(n))
+                .ok_or_else(|| ErrorCode::Usage.error("A passphrase is 3-12 words, 0 for none"))?;
+
+            if let Some(daemon) = daemon.as_mut() {
+                daemon.request(&format!(
+                    "{{\"type\":\"SET_WORDS\",\"domain\":\"{}\",\"word_count\":{}}}",
+                    escape_json(domain),
+                    word_count
+                ))?;
+            } else {
+                let exe_path = vault_path()?;
+                let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+                DomainTable::shared().load_from_binary(&exe_path)?;
 
-        let structure = &mut password_manager.saved_passwords[0].structure_system;
+                let mut config = table_configuration(cli.account.as_deref())?;
+                let structure = &mut config.structure_system;
 
-        let new_counter = DomainTable::increment_counter(domain, structure)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        DomainTable::save_to_binary(&exe_path)?;
+                let fixed_output = match word_count {
+                    0 => FixedOutput::Password,
+                    words => FixedOutput::Words(words),
+                };
+                DomainTable::shared()
+                    .set_fixed_output(domain, fixed_output, structure)
+                    .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+                DomainTable::shared().save_to_binary(&exe_path)?;
+            }
 
-        println!("{}: v{}", domain, new_counter);
-        return Ok(());
-    }
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"word_count\":{},\"status\":\"success\"}}",
+                    escape_json(domain),
+                    word_count
+                );
+            } else if word_count > 0 {
+                println!("{}: {} word passphrase", domain, word_count);
+            } else {
+                println!("{}: password", domain);
+            }
+            return Ok(());
+        }
+        CliCommand::Domains(DomainsCommand::Rules(domain, max_length, char_types)) => {
+            let max_length: u16 = max_length
+                .parse()
+                .ok()
+                .filter(|n| *n <= DomainSlot::LENGTH)
+                .ok_or_else(|| ErrorCode::Usage.error("A max length is 0-4095"))?;
+            let char_types: u8 = char_types
+                .parse()
+                .ok()
+                .filter(|n| *n <= 127)
+                .ok_or_else(|| ErrorCode::Usage.error("Char types are 0-127"))?;
+            // each toggle on or off, or None to keep the one the domain has
+            let toggle = |name: &str| -> io::Result<Option<bool>> {
+                let option = cli
+                    .command_args
+                    .iter()
+                    .position(|arg| arg == name)
+                    .and_then(|i| cli.command_args.get(i + 1));
+                match option.map(String::as_str) {
+                    Some("on") => Ok(Some(true)),
+                    Some("off") => Ok(Some(false)),
+                    Some(_) => Err(ErrorCode::Usage.error(format!("{} is on or off", name))),
+                    None => Ok(None),
+                }
+            };
+            let no_confusables = toggle("--no-confusables")?;
+            let starts_with_letter = toggle("--starts-with-letter")?;
+            let no_leading_symbol = toggle("--no-leading-symbol")?;
+            let no_trailing_symbol = toggle("--no-trailing-symbol")?;
+            let given_positions = |positions: Positions| Positions {
+                starts_with_letter: starts_with_letter.unwrap_or(positions.starts_with_letter),
+                no_leading_symbol: no_leading_symbol.unwrap_or(positions.no_leading_symbol),
+                no_trailing_symbol: no_trailing_symbol.unwrap_or(positions.no_trailing_symbol),
+            };
 
-    if let Some(domain_counter_pos) = args.iter().position(|arg| arg == "--use-domain-counter") {
-        if args.len() > domain_counter_pos + 1 {
-            let domain = &args[domain_counter_pos + 1];
-            let exe_path = std::env::current_exe()?;
-            DomainTable::load_from_binary(&exe_path)?;
+            // the toggles the domain has after, given or kept
+            let (no_confusables, positions) = if let Some(daemon) = daemon.as_mut() {
+                let toggles: String = [
+                    ("no_confusables", no_confusables),
+                    ("starts_with_letter", starts_with_letter),
+                    ("no_leading_symbol", no_leading_symbol),
+                    ("no_trailing_symbol", no_trailing_symbol),
+                ]
+                .iter()
+                .filter_map(|(name, on)| on.map(|on| format!(",\"{}\":{}", name, on)))
+                .collect();
+                let reply = daemon.request(&format!(
+                    "{{\"type\":\"SET_RULES\",\"domain\":\"{}\",\"max_length\":{},\"char_types\":{}{}}}",
+                    escape_json(domain),
+                    max_length,
+                    char_types,
+                    toggles
+                ))?;
+                (
+                    extract_json_bool(&reply, "no_confusables") == Some(true),
+                    json_positions(&reply, Positions::NONE),
+                )
+            } else {
+                let exe_path = vault_path()?;
+                let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+                DomainTable::shared().load_from_binary(&exe_path)?;
+
+                let mut config = table_configuration(cli.account.as_deref())?;
+                let structure = &mut config.structure_system;
+
+                let mut table = DomainTable::shared();
+                let positions = given_positions(table.positions(domain, structure));
+                table
+                    .set_rules(domain, max_length, char_types, structure)
+                    .and_then(|()| match no_confusables {
+                        Some(on) => table.set_no_confusables(domain, on, structure),
+                        None => Ok(()),
+                    })
+                    .and_then(|()| table.set_positions(domain, positions, structure))
+                    .map_err(|e| match e {
+                        DomainTableError::Full => ErrorCode::TableFull.error(e.to_string()),
+                        DomainTableError::NotPassword => ErrorCode::BadRequest.error(e.to_string()),
+                    })?;
+                table.save_to_binary(&exe_path)?;
+                (
+                    table.no_confusables(domain, structure),
+                    table.positions(domain, structure),
+                )
+            };
 
-            let mut password_manager = PasswordManager::new(false, None, None, true)?;
-            if password_manager.saved_passwords.is_empty() {
-                eprintln!("Error: No geometry found. Please create one first.");
-                return Ok(());
+            if json_output() {
+                println!(
+                    "{{\"domain\":\"{}\",\"max_length\":{},\"char_types\":{},\"no_confusables\":{},{},\"status\":\"success\"}}",
+                    escape_json(domain),
+                    max_length,
+                    char_types,
+                    no_confusables,
+                    positions_json(positions)
+                );
+            } else {
+                println!(
+                    "{}: max length {}, char types {}{}",
+                    domain,
+                    max_length,
+                    char_types,
+                    rules_text(no_confusables, positions)
+                );
             }
+            return Ok(());
+        }
+        CliCommand::RateLimit(per_second, burst, strikes) => {
+            let parse = |value: &str| {
+                value
+                    .parse()
+                    .map_err(|_| ErrorCode::Usage.error("Rate limits must be 0-65535"))
+            };
+            let limits = RateLimits {
+                per_second: parse(per_second)?,
+                burst: parse(burst)?,
+                lockout_strikes: strikes
+                    .parse()
+                    .map_err(|_| ErrorCode::Usage.error("Lockout strikes must be 0-255"))?,
+            };
 
-            let structure = &mut password_manager.saved_passwords[0].structure_system;
-            let counter = DomainTable::get_counter(domain, structure).unwrap_or(0);
-            let domain_hash = structure.hash_domain(domain);
+            let exe_path = vault_path()?;
+            let _instance_lock = InstanceLock::acquire(&exe_path, InstanceLock::WRITE_WAIT)?;
+            DomainTable::shared().load_from_binary(&exe_path)?;
 
-            unsafe {
-                let session = &mut *std::ptr::addr_of_mut!(SESSION);
-                session.active_domain_hash = Some(domain_hash);
-                session.saved_counter = counter;
-                session.active_counter = counter;
-                session.is_preview_mode = false;
-                session.initialized = true;
-            }
+            DomainTable::shared()
+                .set_rate_limits(limits)
+                .map_err(|e| ErrorCode::TableFull.error(e.to_string()))?;
+            DomainTable::shared().save_to_binary(&exe_path)?;
 
-            eprintln!("Using domain counter for '{}': v{}", domain, counter);
-        } else {
-            eprintln!("Error: --use-domain-counter requires a domain name");
+            if json_output() {
+                println!(
+                    "{{\"per_second\":{},\"burst\":{},\"lockout_strikes\":{},\"status\":\"success\"}}",
+                    limits.per_second, limits.burst, limits.lockout_strikes
+                );
+            } else {
+                println!(
+                    "Rate limit set to {}/s, burst {}, lockout after {} refusals",
+                    limits.per_second, limits.burst, limits.lockout_strikes
+                );
+            }
             return Ok(());
         }
+        CliCommand::ShredBackups => return run_shred_backups(),
+        CliCommand::VaultStats => return run_vault_stats(),
+        CliCommand::Doctor(fix) => return run_doctor(*fix),
+        CliCommand::Unlock(_) | CliCommand::Decoy(_) | CliCommand::AcceptNewBinary => {
+            let secret_file = cli
+                .command_args
+                .iter()
+                .position(|arg| arg == "--secret-file")
+                .and_then(|i| cli.command_args.get(i + 1))
+                .map(String::as_str);
+            if let CliCommand::AcceptNewBinary = cli.command {
+                return run_accept_new_binary(secret_file);
+            }
+            if let CliCommand::Decoy(name) = &cli.command {
+                return run_set_decoy(name.as_deref(), secret_file);
+            }
+            let decoy = cli.command_args.iter().any(|arg| arg == "--decoy");
+            return run_set_unlock(
+                matches!(cli.command, CliCommand::Unlock(true)),
+                decoy,
+                secret_file,
+            );
+        }
+        _ => {}
     }
 
-    let auto_exit = args.contains(&"--auto-exit".to_string());
+    // the interactive session is the child's, which starts at the counter of the
+    // configuration it has active
+    let interactive = matches!(
+        cli.command,
+        CliCommand::Interactive | CliCommand::ChildProcess
+    );
+    if let Some(domain) = cli.use_domain_counter.as_deref().filter(|_| !interactive) {
+        // the counter is the decoy's when the secret given is
+        require_unlock(&mut io::stderr())?;
+        let exe_path = vault_path()?;
+        DomainTable::shared().load_from_binary(&exe_path)?;
+
+        let mut config = table_configuration(cli.account.as_deref())?;
+        // the domain's session without its length and character rules
+        let (counter, _) = activate_domain_session(&mut config.structure_system, domain, false);
+
+        diag(
+            Diag::Note,
+            format_args!("Using domain counter for '{}': v{}", domain, counter),
+        );
+    }
 
-    if args.len() > 1 && args[1] == "--child-process" {
-        run_child_process(auto_exit)?;
-    } else if args.len() > 1 && args[1] == "--term" {
-        run_terminal_mode(&args)?;
-    } else if args.len() > 1 && args[1] == "--io" {
-        run_io_mode(&args)?;
-    } else if args.len() > 1 && args[1] == "--json-io" {
-        run_json_io_mode(&args)?;
-    } else if is_native_messaging_mode() {
-        // auto-detect browser native messaging (stdin is not a TTY)
-        run_json_io_mode(&args)?;
-    } else {
-        run_parent_process(auto_exit)?;
+    // the modes still read their options from argv, so hand them the old shape:
+    // program, mode flag, then the options
+    let mode_args = |flag: &str| -> Vec<String> {
+        let mut mode_args = vec![args[0].clone(), flag.to_string()];
+        if let Some(account) = &cli.account {
+            mode_args.push("--account".to_string());
+            mode_args.push(account.clone());
+        }
+        mode_args.extend(cli.command_args.iter().cloned());
+        mode_args
+    };
+
+    match cli.command {
+        CliCommand::ChildProcess => {
+            if std::env::var_os(CHILD_DURESS_VAR).is_some() {
+                DURESS.store(true, Ordering::SeqCst);
+            }
+            run_child_process(
+                &mode_args("--child-process"),
+                cli.auto_exit,
+                cli.use_domain_counter.as_deref(),
+            )
+        }
+        CliCommand::Term => run_terminal_mode(&mode_args("--term")),
+        CliCommand::Io => match daemon.as_mut() {
+            Some(daemon) => run_io_via_daemon(daemon, &cli.command_args),
+            None => run_io_mode(&mode_args("--io")),
+        },
+        CliCommand::Preview(ref domain) => {
+            let mut args = mode_args("--io");
+            args.extend([
+                "--domain".to_string(),
+                domain.clone(),
+                "--preview".to_string(),
+            ]);
+            run_io_mode(&args)
+        }
+        CliCommand::Pin(ref domain) => {
+            let mut args = mode_args("--io");
+            args.extend(["--domain".to_string(), domain.clone(), "--pin".to_string()]);
+            run_io_mode(&args)
+        }
+        CliCommand::JsonIo => run_json_io_mode(&mode_args("--json-io")),
+        CliCommand::Daemon => run_daemon_mode(&mode_args("--daemon")),
+        CliCommand::Askpass(ref prompt) => {
+            let mut args = mode_args("--io");
+            if !args.iter().any(|arg| arg == "--domain") {
+                if let Some(domain) = std::env::var(ASKPASS_DOMAIN_VAR)
+                    .ok()
+                    .filter(|domain| !domain.is_empty())
+                {
+                    args.extend(["--domain".to_string(), domain]);
+                }
+            }
+            run_askpass(prompt.as_deref(), &args)
+        }
+        CliCommand::CreateAccount(ref name) => {
+            run_create_account(name, &mode_args("--create-account"))
+        }
+        CliCommand::Rekey(ref account, ref name) => {
+            run_rekey(account, name.as_deref(), &cli.command_args)
+        }
+        CliCommand::Setup => {
+            // checked here as well, so a bad value stops before the setup screen
+            Geometry::from_args(&cli.command_args)?;
+            Pool::from_args(&cli.command_args)?;
+            if let Some(warning) = memory_lock_warning() {
+                diag(Diag::Warning, format_args!("{}", warning));
+            }
+            run_parent_process(true, &mode_args("--setup")[1..])
+        }
+        _ => {
+            require_unlock(&mut io::stderr())?;
+            let extra_args: Vec<String> = cli
+                .use_domain_counter
+                .iter()
+                .flat_map(|domain| ["--use-domain-counter".to_string(), domain.clone()])
+                .collect();
+            run_parent_process(cli.auto_exit, &extra_args)
+        }
     }
+}
 
-    Ok(())
+/// detects if we're being called by a browser for native messaging: its own
+/// arguments, with stdin a pipe. Chrome passes the extension's origin, Firefox the
+/// manifest's path and the extension's id. A pipe alone is a script's
+fn is_native_messaging_mode(positionals: &[String]) -> bool {
+    let origin = positionals
+        .iter()
+        .any(|arg| arg.starts_with("chrome-extension://"));
+    let manifest = positionals.len() == 2
+        && std::path::Path::new(&positionals[0])
+            .file_name()
+            .is_some_and(|name| *name == *format!("{}.json", doctor::HOST_NAME));
+    (origin || manifest) && stdin_is_redirected()
 }
 
-/// detects if we're being called by a browser for native messaging
+/// Check if stdin is a pipe or a file, not a terminal
 #[cfg(unix)]
-fn is_native_messaging_mode() -> bool {
+fn stdin_is_redirected() -> bool {
     use std::os::unix::fs::FileTypeExt;
 
     if let Ok(metadata) = fs::metadata("/dev/stdin") {
@@ -3462,9 +10483,9 @@ fn is_native_messaging_mode() -> bool {
     }
 }
 
-/// Windows version: Check if stdin is a pipe (not a console)
+/// Windows version: Check if stdin is a pipe or a file (not a console)
 #[cfg(windows)]
-fn is_native_messaging_mode() -> bool {
+fn stdin_is_redirected() -> bool {
     unsafe {
         #[link(name = "kernel32")]
         extern "system" {
@@ -3473,7 +10494,7 @@ fn is_native_messaging_mode() -> bool {
         }
 
         const STD_INPUT_HANDLE: u32 = 0xFFFFFFF6_u32;
-        const FILE_TYPE_CHAR: u32 = 0x0002;
+        const FILE_TYPE_DISK: u32 = 0x0001;
         const FILE_TYPE_PIPE: u32 = 0x0003;
 
         let handle = GetStdHandle(STD_INPUT_HANDLE);
@@ -3483,6 +10504,6 @@ fn is_native_messaging_mode() -> bool {
 
         let file_type = GetFileType(handle);
 
-        file_type == FILE_TYPE_PIPE
+        file_type == FILE_TYPE_PIPE || file_type == FILE_TYPE_DISK
     }
 }
@@ -0,0 +1,326 @@
+//! Checks that nobody but the owner can replace the vault binary. Whoever can write
+//! the file, or the directory it sits in, can swap the code for their own and have
+//! every phrase typed into it, or swap the vault section and read what it generates.
+//!
+//! On unix that's a group or world write bit on the file, or on a directory without
+//! the sticky bit, which keeps others from renaming and deleting what isn't theirs.
+//! On Windows it's a DACL that grants Everyone, Authenticated Users or Users a right
+//! to write, delete or change the permissions. [`restrict`] leaves the file to its
+//! owner alone: mode 0700, or a protected DACL with the current user's ACE only.
+
+use std::io;
+use std::path::Path;
+
+/// What others than the owner can do to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exposure {
+    /// Write or replace the file itself. [`restrict`] fixes this.
+    FileWritable,
+    /// Create, rename and delete files in its directory, so put another file in its
+    /// place. Left to the user, the directory isn't ours to change.
+    DirectoryWritable,
+}
+
+impl Exposure {
+    /// The name json-io reports it under.
+    pub fn code(self) -> &'static str {
+        match self {
+            Exposure::FileWritable => "file_writable",
+            Exposure::DirectoryWritable => "directory_writable",
+        }
+    }
+}
+
+#[cfg(windows)]
+#[repr(C)]
+struct Trustee {
+    multiple_trustee: *mut Trustee,
+    multiple_trustee_operation: u32,
+    trustee_form: u32,
+    trustee_type: u32,
+    name: *mut u16,
+}
+
+#[cfg(windows)]
+#[link(name = "advapi32")]
+extern "system" {
+    fn GetNamedSecurityInfoW(
+        name: *const u16,
+        object_type: u32,
+        info: u32,
+        owner: *mut *mut std::ffi::c_void,
+        group: *mut *mut std::ffi::c_void,
+        dacl: *mut *mut std::ffi::c_void,
+        sacl: *mut *mut std::ffi::c_void,
+        descriptor: *mut *mut std::ffi::c_void,
+    ) -> u32;
+    fn SetNamedSecurityInfoW(
+        name: *mut u16,
+        object_type: u32,
+        info: u32,
+        owner: *mut std::ffi::c_void,
+        group: *mut std::ffi::c_void,
+        dacl: *mut std::ffi::c_void,
+        sacl: *mut std::ffi::c_void,
+    ) -> u32;
+    fn CreateWellKnownSid(
+        sid_type: u32,
+        domain: *mut std::ffi::c_void,
+        sid: *mut std::ffi::c_void,
+        size: *mut u32,
+    ) -> i32;
+    fn BuildTrusteeWithSidW(trustee: *mut Trustee, sid: *mut std::ffi::c_void);
+    fn GetEffectiveRightsFromAclW(
+        acl: *mut std::ffi::c_void,
+        trustee: *const Trustee,
+        rights: *mut u32,
+    ) -> u32;
+    fn OpenProcessToken(
+        process: *mut std::ffi::c_void,
+        access: u32,
+        token: *mut *mut std::ffi::c_void,
+    ) -> i32;
+    fn GetTokenInformation(
+        token: *mut std::ffi::c_void,
+        class: u32,
+        info: *mut std::ffi::c_void,
+        length: u32,
+        returned: *mut u32,
+    ) -> i32;
+    fn GetLengthSid(sid: *mut std::ffi::c_void) -> u32;
+    fn InitializeAcl(acl: *mut std::ffi::c_void, length: u32, revision: u32) -> i32;
+    fn AddAccessAllowedAce(
+        acl: *mut std::ffi::c_void,
+        revision: u32,
+        mask: u32,
+        sid: *mut std::ffi::c_void,
+    ) -> i32;
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetCurrentProcess() -> *mut std::ffi::c_void;
+    fn CloseHandle(handle: *mut std::ffi::c_void) -> i32;
+    fn LocalFree(memory: *mut std::ffi::c_void) -> *mut std::ffi::c_void;
+}
+
+#[cfg(windows)]
+const SE_FILE_OBJECT: u32 = 1;
+#[cfg(windows)]
+const DACL_SECURITY_INFORMATION: u32 = 0x0000_0004;
+
+#[cfg(windows)]
+fn wide(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    path.as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+/// What others can do to `path`, and to the directory holding it. Empty when the
+/// owner is the only one who can change either, or when it can't be told.
+pub fn check(path: &Path) -> Vec<Exposure> {
+    let mut exposures = Vec::new();
+    if writable_by_others(path, false) {
+        exposures.push(Exposure::FileWritable);
+    }
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if writable_by_others(directory, true) {
+        exposures.push(Exposure::DirectoryWritable);
+    }
+    exposures
+}
+
+#[cfg(unix)]
+fn writable_by_others(path: &Path, directory: bool) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    const GROUP_OR_OTHER_WRITE: u32 = 0o022;
+    const STICKY: u32 = 0o1000;
+
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    let mode = metadata.permissions().mode();
+    mode & GROUP_OR_OTHER_WRITE != 0 && !(directory && mode & STICKY != 0)
+}
+
+#[cfg(windows)]
+fn writable_by_others(path: &Path, _directory: bool) -> bool {
+    // write, append, add a subdirectory, delete a child, delete, WRITE_DAC,
+    // WRITE_OWNER, and the generic rights an ACE may still carry unmapped
+    const WRITE_RIGHTS: u32 = 0x0000_0002
+        | 0x0000_0004
+        | 0x0000_0040
+        | 0x0001_0000
+        | 0x0004_0000
+        | 0x0008_0000
+        | 0x1000_0000
+        | 0x4000_0000;
+    const WIN_WORLD_SID: u32 = 1;
+    const WIN_AUTHENTICATED_USER_SID: u32 = 17;
+    const WIN_BUILTIN_USERS_SID: u32 = 27;
+
+    let name = wide(path);
+    unsafe {
+        let mut dacl = std::ptr::null_mut();
+        let mut descriptor = std::ptr::null_mut();
+        let status = GetNamedSecurityInfoW(
+            name.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut dacl,
+            std::ptr::null_mut(),
+            &mut descriptor,
+        );
+        if status != 0 {
+            return false;
+        }
+
+        // no DACL at all lets everyone do everything
+        let mut writable = dacl.is_null();
+        for sid_type in [
+            WIN_WORLD_SID,
+            WIN_AUTHENTICATED_USER_SID,
+            WIN_BUILTIN_USERS_SID,
+        ] {
+            if writable {
+                break;
+            }
+            // SECURITY_MAX_SID_SIZE, kept u32 aligned
+            let mut sid = [0u32; 17];
+            let mut size = std::mem::size_of_val(&sid) as u32;
+            if CreateWellKnownSid(
+                sid_type,
+                std::ptr::null_mut(),
+                sid.as_mut_ptr().cast(),
+                &mut size,
+            ) == 0
+            {
+                continue;
+            }
+            let mut trustee = Trustee {
+                multiple_trustee: std::ptr::null_mut(),
+                multiple_trustee_operation: 0,
+                trustee_form: 0,
+                trustee_type: 0,
+                name: std::ptr::null_mut(),
+            };
+            BuildTrusteeWithSidW(&mut trustee, sid.as_mut_ptr().cast());
+            let mut rights = 0u32;
+            if GetEffectiveRightsFromAclW(dacl, &trustee, &mut rights) == 0 {
+                writable = rights & WRITE_RIGHTS != 0;
+            }
+        }
+
+        LocalFree(descriptor);
+        writable
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn writable_by_others(_path: &Path, _directory: bool) -> bool {
+    false
+}
+
+/// Leaves `path` to its owner alone, see the module docs.
+#[cfg(unix)]
+pub fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(windows)]
+pub fn restrict(path: &Path) -> io::Result<()> {
+    const TOKEN_QUERY: u32 = 0x0008;
+    const TOKEN_USER: u32 = 1;
+    const ACL_REVISION: u32 = 2;
+    const FILE_ALL_ACCESS: u32 = 0x001F_01FF;
+    const PROTECTED_DACL_SECURITY_INFORMATION: u32 = 0x8000_0000;
+
+    let mut name = wide(path);
+    unsafe {
+        let mut token = std::ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // a TOKEN_USER, whose first field points at the SID stored after it
+        let mut user = [0u64; 64];
+        let mut returned = 0u32;
+        let queried = GetTokenInformation(
+            token,
+            TOKEN_USER,
+            user.as_mut_ptr().cast(),
+            std::mem::size_of_val(&user) as u32,
+            &mut returned,
+        );
+        let error = io::Error::last_os_error();
+        CloseHandle(token);
+        if queried == 0 {
+            return Err(error);
+        }
+        let sid = *(user.as_ptr() as *const *mut std::ffi::c_void);
+
+        // the ACL header, then one ACCESS_ALLOWED_ACE: its header and mask, then the SID
+        let length = (8 + 8 + GetLengthSid(sid)).next_multiple_of(4);
+        let mut acl = vec![0u32; length as usize / 4];
+        if InitializeAcl(acl.as_mut_ptr().cast(), length, ACL_REVISION) == 0
+            || AddAccessAllowedAce(acl.as_mut_ptr().cast(), ACL_REVISION, FILE_ALL_ACCESS, sid) == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let status = SetNamedSecurityInfoW(
+            name.as_mut_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            acl.as_mut_ptr().cast(),
+            std::ptr::null_mut(),
+        );
+        if status != 0 {
+            return Err(io::Error::from_raw_os_error(status as i32));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn restrict(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "File permissions can't be changed here",
+    ))
+}
+
+/// Whether this process can write `path`, a file or the directory the vault is
+/// rewritten in. Only asks, nothing is written.
+#[cfg(unix)]
+pub fn writable(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    extern "C" {
+        fn access(path: *const std::ffi::c_char, mode: i32) -> i32;
+    }
+    const W_OK: i32 = 2;
+
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { access(path.as_ptr(), W_OK) == 0 }
+}
+
+/// Whether this process can write `path`. Only the read-only attribute is looked
+/// at, a DACL denying the write still shows when the vault is next written.
+#[cfg(not(unix))]
+pub fn writable(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
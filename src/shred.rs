@@ -0,0 +1,210 @@
+//! Overwrites the copies of the vault its rewrites leave beside it. A store writes
+//! the whole binary to `<vault>.new` and renames it over the vault, which is kept as
+//! `<vault>.bak`: both hold every configuration and the domain table.
+//!
+//! One backup is kept. The one it supersedes is overwritten with zeros and unlinked
+//! before the next swap, a `.new` that never got renamed is removed the same way by
+//! the next process to start, see [`remove_stale_copy`], and [`leftovers`] lists
+//! what is there for `shred-backups` and `vault-stats`. Overwriting is best effort:
+//! on a copy-on-write filesystem the zeros go to new blocks and the old ones stay
+//! readable until reused, which is warned about once.
+
+use std::fs::{self, File};
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::{diag, log, Diag, InstanceLock, LogLevel, NO_PERSIST};
+
+// a .new younger than this may belong to a write still under way in a process that
+// doesn't hold the lock, as the interactive session's child
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+static WARNED_COPY_ON_WRITE: AtomicBool = AtomicBool::new(false);
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn statfs(path: *const std::ffi::c_char, buf: *mut std::ffi::c_long) -> i32;
+}
+
+/// The copies a rewrite of `vault` can leave: its `.bak` and a `.new` that was never
+/// renamed over it, with their sizes. Only those that are there.
+pub fn leftovers(vault: &Path) -> Vec<(PathBuf, u64)> {
+    ["bak", "new"]
+        .iter()
+        .map(|extension| vault.with_extension(extension))
+        .filter_map(|path| {
+            let metadata = fs::symlink_metadata(&path).ok()?;
+            metadata.is_file().then_some((path, metadata.len()))
+        })
+        .collect()
+}
+
+/// Whether `path` is on a filesystem that writes changes to new blocks, so that
+/// overwriting a file leaves its old contents on disk: btrfs, ZFS and bcachefs on
+/// Linux, and APFS, which is all macOS has left.
+pub fn copy_on_write(path: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        const BTRFS: u64 = 0x9123_683E;
+        const ZFS: u64 = 0x2FC1_2FC2;
+        const BCACHEFS: u64 = 0xCA45_1A4E;
+
+        let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        // f_type is the first field, the rest is room for whatever follows it
+        let mut buf: [std::ffi::c_long; 32] = [0; 32];
+        if unsafe { statfs(path.as_ptr(), buf.as_mut_ptr()) } != 0 {
+            return false;
+        }
+        // the magic numbers are u32, a long may carry them sign extended
+        let magic = buf[0] as u64 & 0xFFFF_FFFF;
+        matches!(magic, BTRFS | ZFS | BCACHEFS)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let _ = path;
+        true
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+fn warn_copy_on_write(path: &Path) {
+    if copy_on_write(path) && !WARNED_COPY_ON_WRITE.swap(true, Ordering::SeqCst) {
+        diag(
+            Diag::Warning,
+            format_args!(
+                "Warning: {} is on a copy-on-write filesystem, so overwritten backups of \
+                 the vault may stay readable on disk until their blocks are reused",
+                path.parent().unwrap_or(path).display()
+            ),
+        );
+    }
+}
+
+// the file is the text of a running program, ours or another copy's, which can't be
+// opened for writing. true while the first backup is this executable as it started
+fn in_use(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    const BUSY: i32 = 26; // ETXTBSY
+    #[cfg(windows)]
+    const BUSY: i32 = 32; // ERROR_SHARING_VIOLATION
+    #[cfg(not(any(unix, windows)))]
+    const BUSY: i32 = -1;
+
+    error.raw_os_error() == Some(BUSY)
+}
+
+/// Overwrites `path` with zeros, flushes that to disk and unlinks it. Ok(false) when
+/// there was nothing there.
+pub fn shred_file(path: &Path) -> io::Result<bool> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    // a link would have us overwrite whatever it points at
+    if !metadata.is_file() {
+        fs::remove_file(path)?;
+        return Ok(true);
+    }
+
+    warn_copy_on_write(path);
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let zeros = vec![0u8; 64 * 1024];
+    let mut left = metadata.len();
+    while left > 0 {
+        let chunk = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        left -= chunk as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+// a new copy of the vault, written and about to be renamed over it. its data is
+// flushed to disk and checked to be all there first, so the backup it supersedes is
+// only given up for a complete one. one that isn't is shredded again
+pub(crate) fn verify_copy(mut file: File, temp_path: &Path) -> io::Result<()> {
+    let checked = file.stream_position().and_then(|written| {
+        file.sync_all()?;
+        drop(file);
+        if fs::metadata(temp_path)?.len() == written {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The new copy of the vault was cut short",
+            ))
+        }
+    });
+    if checked.is_err() {
+        let _ = shred_file(temp_path);
+    }
+    checked
+}
+
+// the backup the next swap replaces. it is overwritten first, as the rename would
+// only unlink it. one that can't be, while it is a running executable, is left to
+// the rename, anything else is warned about and the swap goes ahead
+pub(crate) fn retire_backup(backup_path: &Path) {
+    match shred_file(backup_path) {
+        Ok(_) => {}
+        Err(e) if in_use(&e) => log(
+            LogLevel::Info,
+            format_args!("storage: previous backup is a running executable, not overwritten"),
+        ),
+        Err(e) => {
+            diag(
+                Diag::Warning,
+                format_args!("Warning: Could not overwrite the previous backup: {}", e),
+            );
+            log(
+                LogLevel::Warn,
+                format_args!("storage: overwriting previous backup failed: {}", e),
+            );
+        }
+    }
+}
+
+/// At startup: shreds a `.new` copy of `vault` left by a write that died before its
+/// rename. Only one older than a minute, and only when the vault's [`InstanceLock`]
+/// is free right away, so nothing waits on it and no write under way loses its copy.
+pub fn remove_stale_copy(vault: &Path) {
+    if NO_PERSIST.load(Ordering::SeqCst) {
+        return;
+    }
+    let temp_path = vault.with_extension("new");
+    let Ok(modified) = fs::symlink_metadata(&temp_path).and_then(|m| m.modified()) else {
+        return;
+    };
+    if modified.elapsed().map_or(true, |age| age < STALE_AFTER) {
+        return;
+    }
+    let Ok(_instance_lock) = InstanceLock::acquire(vault, Duration::ZERO) else {
+        return;
+    };
+
+    match shred_file(&temp_path) {
+        Ok(true) => log(
+            LogLevel::Info,
+            format_args!("storage: removed a stale copy left by an unfinished write"),
+        ),
+        Ok(false) => {}
+        Err(e) => log(
+            LogLevel::Warn,
+            format_args!("storage: removing a stale copy failed: {}", e),
+        ),
+    }
+}
@@ -0,0 +1,175 @@
+//! Plain exports for the wasm32-unknown-unknown build, so the extension can preview
+//! a password's length and strength in the page without a round trip per keystroke.
+//! The native host stays authoritative for the fill.
+//!
+//! There is no filesystem in a page, so a session is opened from a configuration
+//! record ([`SavedPassword::to_bytes`]), and [`vv_wasm_activate`] takes the counter
+//! and PIN or passphrase shape the host's ACTIVATE reply reported. Strings and
+//! output go through buffers from [`vv_wasm_alloc`]. Return codes are those of
+//! [`crate::ffi`]. The exports are ordinary functions in a native build too, which
+//! is how tests/wasm_determinism.rs compares the two.
+//!
+//! ```text
+//! cargo build --lib --release --target wasm32-unknown-unknown
+//! ```
+
+use crate::ffi::{failure, Typing};
+use crate::{
+    zero_memory, ErrorCode, FixedOutput, OutputRules, SavedPassword, PIN_LENGTHS, WORD_COUNTS,
+};
+
+/// A configuration and the session typed into it.
+pub struct WasmSession {
+    config: SavedPassword,
+    typing: Typing,
+}
+
+/// `len` bytes for the caller to fill, or NULL when `len` is 0.
+#[no_mangle]
+pub extern "C" fn vv_wasm_alloc(len: usize) -> *mut u8 {
+    if len == 0 {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+/// Zeroes and frees a buffer from [`vv_wasm_alloc`]. NULL is ignored.
+///
+/// # Safety
+///
+/// `ptr` must come from [`vv_wasm_alloc`] with the same `len`, and is not used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vv_wasm_free(ptr: *mut u8, len: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    let mut buffer = Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len));
+    zero_memory(&mut buffer);
+}
+
+/// A session on the configuration record in `config_ptr`, or NULL when it doesn't
+/// decode.
+///
+/// # Safety
+///
+/// `config_ptr` must be readable for `config_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn vv_wasm_open(
+    config_ptr: *const u8,
+    config_len: usize,
+) -> *mut WasmSession {
+    if config_ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    let bytes = std::slice::from_raw_parts(config_ptr, config_len);
+    match SavedPassword::from_bytes(bytes) {
+        Ok(mut config) => {
+            config.structure_system.reset_position();
+            Box::into_raw(Box::new(WasmSession {
+                config,
+                typing: Typing::new(),
+            }))
+        }
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Starts a session on the UTF-8 `domain` at `counter`. A non-zero `pin_length` or
+/// `word_count` makes it a PIN or passphrase domain, as ACTIVATE reported. Returns
+/// 0, or a negative error code.
+///
+/// # Safety
+///
+/// `session` must come from [`vv_wasm_open`] and not be closed, and `domain_ptr`
+/// must be readable for `domain_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn vv_wasm_activate(
+    session: *mut WasmSession,
+    domain_ptr: *const u8,
+    domain_len: usize,
+    counter: u16,
+    pin_length: u8,
+    word_count: u8,
+) -> i32 {
+    let Some(session) = session.as_mut() else {
+        return failure(ErrorCode::BadRequest);
+    };
+    if domain_ptr.is_null() || domain_len == 0 {
+        return failure(ErrorCode::MissingDomain);
+    }
+    let Ok(domain) = std::str::from_utf8(std::slice::from_raw_parts(domain_ptr, domain_len)) else {
+        return failure(ErrorCode::BadRequest);
+    };
+    let fixed_output = match (pin_length, word_count) {
+        (0, 0) => FixedOutput::Password,
+        (digits, 0) if PIN_LENGTHS.contains(&digits) => FixedOutput::Pin(digits),
+        (0, words) if WORD_COUNTS.contains(&words) => FixedOutput::Words(words),
+        _ => return failure(ErrorCode::BadRequest),
+    };
+
+    // a preview measures, the host's fill is what keeps confusables out and holds
+    // the positions
+    session.typing.activate(
+        &mut session.config,
+        domain,
+        counter,
+        fixed_output,
+        OutputRules::NONE,
+    );
+    0
+}
+
+/// Types the character `codepoint`, as [`crate::ffi::vv_feed_char`] does.
+///
+/// # Safety
+///
+/// `session` must come from [`vv_wasm_open`] and not be closed, and `out_ptr` must
+/// be writable for `out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn vv_wasm_feed_char(
+    session: *mut WasmSession,
+    codepoint: u32,
+    out_ptr: *mut u8,
+    out_len: usize,
+) -> i32 {
+    let Some(session) = session.as_mut() else {
+        return failure(ErrorCode::BadRequest);
+    };
+    match session
+        .typing
+        .feed(&mut session.config, codepoint, out_ptr, out_len)
+    {
+        Ok(written) => written as i32,
+        Err(code) => failure(code),
+    }
+}
+
+/// Forgets what was typed and goes back to the activated domain's position.
+///
+/// # Safety
+///
+/// `session` must come from [`vv_wasm_open`] and not be closed.
+#[no_mangle]
+pub unsafe extern "C" fn vv_wasm_reset(session: *mut WasmSession) -> i32 {
+    let Some(session) = session.as_mut() else {
+        return failure(ErrorCode::BadRequest);
+    };
+    session.typing.reset(&mut session.config);
+    0
+}
+
+/// Scrubs the session and frees it. NULL is ignored.
+///
+/// # Safety
+///
+/// `session` must come from [`vv_wasm_open`], or be NULL, and is not used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vv_wasm_close(session: *mut WasmSession) {
+    if session.is_null() {
+        return;
+    }
+    let mut session = Box::from_raw(session);
+    session.typing.scrub(&mut session.config);
+}
@@ -0,0 +1,136 @@
+// a backspace takes back the last character in every mode, so "abcd", backspace,
+// "e" gives what "abce" does. setup, term, io and json-io's UNDO_CHAR here through
+// the binary, scenarios/backspace.scenario pins UNDO_CHAR's outputs
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::{BinaryStorageManager, VaultStorage};
+
+// a reproducible setup, Enter to begin, the phrase, then ESC
+fn setup(vault: &Path, phrase: &[u8]) -> Vec<u8> {
+    std::fs::copy(env!("CARGO_BIN_EXE_void_vault"), vault).expect("copy binary");
+    let mut input = b"\n".to_vec();
+    input.extend_from_slice(phrase);
+    input.push(0x1b);
+    common::stdout(
+        vault,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        &input,
+    );
+
+    let storage = BinaryStorageManager::open(vault.to_path_buf(), true, None).expect("open");
+    let (bytes, _) = storage
+        .retrieve("main")
+        .expect("retrieve")
+        .expect("a configuration");
+    bytes.to_vec()
+}
+
+// the line term prints the output on once Enter ends the phrase
+fn last_line(stdout: &[u8]) -> String {
+    let stdout = String::from_utf8_lossy(stdout);
+    stdout
+        .trim_end()
+        .rsplit('\n')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+#[test]
+fn a_backspace_takes_back_the_last_character() {
+    let dir = common::scratch("backspace");
+    let vault = dir.join("vault");
+
+    let typed = setup(&vault, b"a phrase typed with a slip, abcd\x7fe and on");
+    let intended = setup(
+        &dir.join("intended"),
+        b"a phrase typed with a slip, abce and on",
+    );
+    assert_eq!(typed, intended);
+
+    let io = common::stdout(&vault, &["io"], b"abcd\x7fe\n");
+    assert!(!io.is_empty());
+    assert_eq!(io, common::stdout(&vault, &["io"], b"abce\n"));
+
+    let term = last_line(&common::stdout(&vault, &["term"], b"abcd\x7fe\n"));
+    assert!(!term.is_empty());
+    assert_eq!(
+        term,
+        last_line(&common::stdout(&vault, &["term"], b"abce\n"))
+    );
+    // a backspace before anything is typed takes back nothing
+    assert_eq!(
+        term,
+        last_line(&common::stdout(&vault, &["term"], b"\x7fabce\n"))
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// the escaped output a json-io reply carries
+fn output_of(reply: &str) -> &str {
+    let start = reply.find("\"output\":\"").expect("an output") + 10;
+    let mut escaped = false;
+    let end = reply[start..]
+        .char_indices()
+        .find(|&(_, ch)| {
+            let closes = ch == '"' && !escaped;
+            escaped = ch == '\\' && !escaped;
+            closes
+        })
+        .expect("a closing quote")
+        .0;
+    &reply[start..start + end]
+}
+
+#[test]
+fn undo_char_takes_back_the_last_keystroke() {
+    let dir = common::scratch("backspace_undo");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase to take keystrokes back from");
+
+    let key = |ch: char| common::frame(&format!("{{\"charCode\":{}}}", ch as u32));
+    let undo = common::frame("{\"type\":\"UNDO_CHAR\"}");
+    let reset = common::frame("{\"type\":\"RESET\"}");
+
+    for domain in [None, Some("undo.example")] {
+        let mut messages = Vec::new();
+        if let Some(domain) = domain {
+            messages.extend(common::frame(&format!(
+                "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\"}}",
+                domain
+            )));
+        }
+        // "abc" straight through, then "abc", UNDO_CHAR, "c"
+        messages.extend("abc".chars().flat_map(key));
+        messages.extend(reset.iter());
+        messages.extend("abc".chars().flat_map(key));
+        messages.extend(undo.iter());
+        messages.extend(key('c'));
+
+        let replies = common::replies(&common::stdout(&vault, &["json-io"], &messages));
+        let replies = &replies[usize::from(domain.is_some())..];
+        assert_eq!(replies.len(), 9, "{:?}", replies);
+        let (straight, retyped) = (&replies[..3], &replies[4..]);
+
+        assert!(!output_of(&straight[2]).is_empty());
+        // the undo lands on what "ab" gave, and "c" from there on what "abc" did
+        assert_eq!(
+            output_of(&retyped[3]),
+            output_of(&straight[1]),
+            "{:?}",
+            domain
+        );
+        assert_eq!(
+            output_of(&retyped[4]),
+            output_of(&straight[2]),
+            "{:?}",
+            domain
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,92 @@
+// a stored character set can come back from a damaged record with NULs, numbers
+// that aren't characters, or nothing at all. the first two are dropped on load, a
+// set too small to generate from refuses the whole configuration, and no output
+// ever carries a NUL
+
+mod common;
+
+use void_vault::{
+    DecodeError, ErrorCode, MemoryStorage, Normalization, PasswordManager, SavedPassword,
+    StructureSystem, VaultStorage, MIN_CHARACTER_SET,
+};
+
+fn record(character_set: Vec<u32>) -> Vec<u8> {
+    let mut structure_system = StructureSystem::new(31, 5, 17);
+    // straight into the field, set_character_set would already clean it
+    structure_system.character_set = character_set;
+    let mut saved = common::saved("charset", structure_system, 2);
+    saved.created_date = 1_700_000_000;
+    saved.normalization = Normalization::None;
+    saved.to_bytes()
+}
+
+#[test]
+fn codes_that_arent_characters_are_dropped() {
+    let mut codes: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    codes.extend([0, 0xD800, 0xDFFF, 0x110000, u32::MAX]);
+
+    let mut loaded = SavedPassword::from_bytes(&record(codes)).expect("record loads");
+    assert_eq!(loaded.structure_system.character_set.len(), 26);
+
+    loaded.structure_system.full_reset();
+    for key in 0..200u32 {
+        for code in loaded.structure_system.transform_char(key % 90 + 33, 2) {
+            assert!(
+                char::from_u32(code).unwrap().is_ascii_lowercase(),
+                "{}",
+                code
+            );
+        }
+    }
+}
+
+#[test]
+fn too_small_a_set_refuses_the_configuration() {
+    for codes in [vec![], vec![0, 0], vec!['a' as u32, 0xD800]] {
+        assert!(codes.len() <= MIN_CHARACTER_SET);
+        assert_eq!(
+            SavedPassword::from_bytes(&record(codes.clone())).err(),
+            Some(DecodeError::Invalid("character set")),
+            "{:?}",
+            codes
+        );
+        assert_eq!(
+            SavedPassword::from_bytes_without_points(&record(codes)).err(),
+            Some(DecodeError::Invalid("character set"))
+        );
+    }
+}
+
+#[test]
+fn an_empty_set_types_nothing() {
+    let mut structure = StructureSystem::new(8, 4, 17);
+    structure.set_character_set(vec![0, 0xD800]);
+    assert!(structure.character_set.is_empty());
+    for key in 33..127 {
+        assert_eq!(structure.transform_char(key, 3), Vec::<u32>::new());
+    }
+}
+
+#[test]
+fn the_manager_reports_what_it_left_out() {
+    let mut storage = MemoryStorage::default();
+    storage
+        .store(
+            "good".to_string(),
+            String::new(),
+            &record(('!'..='~').map(|c| c as u32).collect()),
+        )
+        .expect("store");
+    storage
+        .store("empty".to_string(), String::new(), &record(Vec::new()))
+        .expect("store");
+
+    let manager = PasswordManager::with_storage(storage, true).expect("load");
+    assert_eq!(manager.saved_passwords.len(), 1);
+    assert_eq!(
+        manager.find_unusable(Some("empty")),
+        Some(&("empty".to_string(), DecodeError::Invalid("character set")))
+    );
+    assert_eq!(manager.find_unusable(Some("good")), None);
+    assert!(ErrorCode::ALL.contains(&ErrorCode::UnusableConfiguration));
+}
@@ -0,0 +1,117 @@
+// the parent's side of the channel against the real child, started the way the parent
+// starts it: --child-process --ipc <address>. the child says it is ready, tells of
+// every rewrite of its binary and holds still until that is acknowledged, and a child
+// that dies before reaching out fails accept at once instead of after its timeout
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use void_vault::{ProcessChannel, ProcessListener, ProcessMessage};
+
+fn spawn_child(vault: &Path, listener: &ProcessListener, address: &str, args: &[&str]) -> Child {
+    let mut command = Command::new(vault);
+    command
+        .args(["--child-process", "--ipc", address])
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    listener.configure(&mut command);
+    command.spawn().expect("spawn the child")
+}
+
+// the child's next BinaryUpdated, left unanswered
+fn binary_updated(channel: &mut ProcessChannel) -> std::path::PathBuf {
+    match channel.recv().expect("a message from the child") {
+        ProcessMessage::BinaryUpdated(path) => path,
+        _ => panic!("expected BinaryUpdated"),
+    }
+}
+
+#[test]
+fn the_child_waits_for_every_binary_update_to_be_acknowledged() {
+    let dir = common::scratch("child_ipc");
+    let vault = common::vault_in(&dir);
+    let listener = ProcessListener::bind().expect("bind");
+    let address = listener.address().expect("address");
+    let mut child = spawn_child(
+        &vault,
+        &listener,
+        &address,
+        &[
+            "--i-know-what-im-doing",
+            "--setup",
+            "--reproducible",
+            "--auto-exit",
+        ],
+    );
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(b"\na phrase for the parent and its child\x1b")
+        .expect("type");
+
+    let mut channel = listener.accept(&mut child).expect("the child connects");
+    assert!(matches!(
+        channel.recv().expect("a message from the child"),
+        ProcessMessage::ChildReady
+    ));
+
+    // a fresh copy gets its section marker on opening, then setup stores the
+    // configuration: two rewrites, each held until the parent answers it
+    let vault = vault.canonicalize().expect("canonical path");
+    for _ in 0..2 {
+        let path = binary_updated(&mut channel);
+        assert_eq!(path.canonicalize().expect("canonical path"), vault);
+        thread::sleep(Duration::from_millis(300));
+        assert!(child.try_wait().expect("poll the child").is_none());
+        channel
+            .send(&ProcessMessage::BinaryUpdateComplete)
+            .expect("acknowledge");
+    }
+
+    assert!(matches!(
+        channel.recv().expect("a message from the child"),
+        ProcessMessage::ShutdownChild
+    ));
+    assert!(child.wait().expect("wait for the child").success());
+
+    // the configuration it stored is there for the next run
+    let accounts = common::stdout(&vault, &["accounts"], b"");
+    assert!(!accounts.is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_child_that_never_reaches_out_fails_accept_at_once() {
+    let dir = common::scratch("child_ipc_early_exit");
+    let vault = common::vault_in(&dir);
+    let listener = ProcessListener::bind().expect("bind");
+    // the child is told of an address nobody listens on, warns and goes on without a
+    // parent, and exits finding nothing stored
+    let nobody = dir.join("nobody-listens");
+    let mut child = spawn_child(&vault, &listener, nobody.to_str().expect("UTF-8 path"), &[]);
+
+    let started = Instant::now();
+    let error = match listener.accept(&mut child) {
+        Ok(_) => panic!("accepted a child that never connected"),
+        Err(error) => error,
+    };
+    assert!(
+        error.to_string().contains("Child exited before connecting"),
+        "{}",
+        error
+    );
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(!child.wait().expect("wait for the child").success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,75 @@
+// a vault is sealed with a hash of the code in front of it. a copy of the binary
+// with one byte of that code flipped has to fail the check, while stores and
+// domain table writes behind it leave the seal intact
+
+use std::path::Path;
+
+use void_vault::{BinaryStorageManager, DomainTable, StructureSystem, TableRegion, VaultStorage};
+
+#[test]
+fn a_flipped_code_byte_breaks_the_seal() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("code-seal");
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    let vault = dir.join("void_vault");
+    std::fs::copy(env!("CARGO_BIN_EXE_void_vault"), &vault).expect("copy binary");
+
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+    let digest = DomainTable::code_digest(&vault)
+        .expect("hash code")
+        .expect("a domain table");
+
+    let mut table = Box::new(DomainTable::new());
+    assert_eq!(table.check_code_seal(&vault).expect("check"), None);
+    table.set_code_seal(Some(digest)).expect("seal");
+    table
+        .save_region(&vault, TableRegion::Real)
+        .expect("save table");
+    assert_eq!(table.check_code_seal(&vault).expect("check"), Some(true));
+
+    storage
+        .store("main".to_string(), String::new(), &[3; 500])
+        .expect("store");
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+    table
+        .set_counter("example.com", 2, &mut structure)
+        .expect("set counter");
+    table
+        .save_region(&vault, TableRegion::Real)
+        .expect("save table");
+
+    let mut loaded = Box::new(DomainTable::new());
+    loaded
+        .load_region(&vault, TableRegion::Real)
+        .expect("load table");
+    assert_eq!(loaded.code_seal(), Some(digest));
+    assert_eq!(loaded.check_code_seal(&vault).expect("check"), Some(true));
+    // the seal is a reserved slot, not a domain
+    assert_eq!(
+        loaded
+            .slots()
+            .iter()
+            .filter(|slot| !slot.is_empty() && !slot.is_reserved())
+            .count(),
+        1
+    );
+
+    let mut bytes = std::fs::read(&vault).expect("read copy");
+    let middle = bytes.len() / 3;
+    bytes[middle] ^= 0x01;
+    std::fs::write(&vault, &bytes).expect("write copy");
+    assert_eq!(loaded.check_code_seal(&vault).expect("check"), Some(false));
+
+    // sealing again trusts the new code
+    let digest = DomainTable::code_digest(&vault)
+        .expect("hash code")
+        .expect("a domain table");
+    loaded.set_code_seal(Some(digest)).expect("reseal");
+    assert_eq!(loaded.check_code_seal(&vault).expect("check"), Some(true));
+
+    drop(storage);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,133 @@
+// what the integration tests share: a structure grown from a phrase, a configuration
+// around it and what it types, a scratch directory under target/, the vault binary
+// run there, and json-io's framing. every test file builds its own copy of this
+// module and uses only some of it
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use void_vault::{
+    GenerationOptions, GenerationSession, Normalization, SavedPassword, SessionState,
+    StructureSystem,
+};
+
+// the printable ASCII pool, '!' to '~'
+pub fn printable() -> Vec<u32> {
+    ('!'..='~').map(|c| c as u32).collect()
+}
+
+// a structure of `dimensions` over `pool`, grown from `phrase` the way setup grows one
+pub fn structure_for(phrase: &str, seed: u64, dimensions: usize, pool: &[u32]) -> StructureSystem {
+    let phrase: Vec<char> = phrase.chars().collect();
+    let mut structure = StructureSystem::new(seed, dimensions, 17);
+    structure.generate_structure(&phrase, pool);
+    structure
+}
+
+// a configuration of `structure_system` as setup leaves a new one: normalized input,
+// nothing generated yet, no fingerprint. a test sets whatever else it needs on it
+pub fn saved(
+    name: &str,
+    structure_system: StructureSystem,
+    extra_chars_count: usize,
+) -> SavedPassword {
+    SavedPassword {
+        name: name.to_string(),
+        description: String::new(),
+        structure_system,
+        created_date: 0,
+        extra_chars_count,
+        use_count: 0,
+        normalization: Normalization::CURRENT,
+        fingerprint: None,
+        setup_mode: None,
+        rekeyed_from: None,
+    }
+}
+
+// the output after each of `keycodes`, typed from a reset structure under `session`
+pub fn outputs(
+    session: &SessionState,
+    config: &mut SavedPassword,
+    keycodes: &[u32],
+) -> Vec<String> {
+    config.structure_system.full_reset();
+    let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    keycodes
+        .iter()
+        .map(|&keycode| generation.step(session, config, keycode).to_string())
+        .collect()
+}
+
+// target/<name>, emptied
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
+
+// a copy of the binary in `dir`, so its vault is the test's own
+pub fn vault_in(dir: &Path) -> PathBuf {
+    let vault = dir.join("vault");
+    std::fs::copy(env!("CARGO_BIN_EXE_void_vault"), &vault).expect("copy binary");
+    vault
+}
+
+// the vault run with `input` on stdin, whatever its exit
+pub fn run(vault: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(vault)
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("run the vault");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(input)
+        .expect("type");
+    child.wait_with_output().expect("wait for the vault")
+}
+
+// what a run that has to succeed printed
+pub fn stdout(vault: &Path, args: &[&str], input: &[u8]) -> Vec<u8> {
+    let output = run(vault, args, input);
+    assert!(output.status.success(), "{:?}", args);
+    output.stdout
+}
+
+// a reproducible setup of `vault`: Enter to begin, the phrase, then ESC
+pub fn setup(vault: &Path, phrase: &str) {
+    let input = format!("\n{}\x1b", phrase);
+    stdout(
+        vault,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        input.as_bytes(),
+    );
+}
+
+// one native messaging frame: the length, in native byte order, then the message
+pub fn frame(message: &str) -> Vec<u8> {
+    let mut frame = (message.len() as u32).to_ne_bytes().to_vec();
+    frame.extend_from_slice(message.as_bytes());
+    frame
+}
+
+// json-io's replies, unframed
+pub fn replies(mut stdout: &[u8]) -> Vec<String> {
+    let mut replies = Vec::new();
+    while stdout.len() >= 4 {
+        let length = u32::from_ne_bytes([stdout[0], stdout[1], stdout[2], stdout[3]]) as usize;
+        replies.push(String::from_utf8_lossy(&stdout[4..4 + length]).into_owned());
+        stdout = &stdout[4 + length..];
+    }
+    replies
+}
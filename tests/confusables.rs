@@ -0,0 +1,139 @@
+// a domain with no_confusables set never shows 0, O, 1, l, I or a lookalike from
+// another script. the remap is by place in the character set, so it repeats, and it
+// only touches output: the walk and the feedback chain are the same with it off
+
+mod common;
+
+use void_vault::{
+    pool, replay_keystrokes, DomainTable, OutputRules, Positions, SavedPassword, SessionState,
+    StructureSystem,
+};
+
+fn config(pool_name: &str) -> SavedPassword {
+    let codes = pool::preset(pool_name).expect("a preset");
+    let structure = common::structure_for(
+        "read it off one screen, type it on another",
+        77_001,
+        7,
+        &codes,
+    );
+    common::saved("confusables", structure, 7)
+}
+
+#[test]
+fn no_confusable_survives_a_large_sample() {
+    let keycodes: Vec<u32> = (0..2000u32).map(|i| 0x21 + (i * 37) % 0x5E).collect();
+    for pool_name in ["ascii", "extended", "full"] {
+        let mut config = config(pool_name);
+        let plain = common::outputs(&SessionState::empty(), &mut config, &keycodes);
+        let mut session = SessionState::empty();
+        session.rules.no_confusables = true;
+        let avoided = common::outputs(&session, &mut config, &keycodes);
+
+        // the sample is big enough to hit them without the rule
+        assert!(
+            plain
+                .concat()
+                .chars()
+                .any(|ch| pool::is_confusable(ch as u32)),
+            "{}",
+            pool_name
+        );
+        let confusable: Vec<char> = avoided
+            .concat()
+            .chars()
+            .filter(|&ch| pool::is_confusable(ch as u32))
+            .collect();
+        assert!(confusable.is_empty(), "{}: {:?}", pool_name, confusable);
+
+        // one character for one, and nothing else moves
+        for (plain, avoided) in plain.iter().zip(&avoided) {
+            assert_eq!(plain.chars().count(), avoided.chars().count());
+            for (p, a) in plain.chars().zip(avoided.chars()) {
+                if !pool::is_confusable(p as u32) {
+                    assert_eq!(p, a);
+                }
+            }
+        }
+
+        // replaying gives what stepping gave
+        let mut feedbacks = Vec::new();
+        let replayed = replay_keystrokes(&session, &mut config, &mut feedbacks, &keycodes);
+        assert_eq!(replayed.as_str(), avoided.last().expect("outputs"));
+    }
+}
+
+#[test]
+fn the_remap_walks_to_the_next_clear_character() {
+    let set: Vec<u32> = "a0OIbl1".chars().map(|c| c as u32).collect();
+    assert_eq!(pool::avoid_confusable(&set, '0' as u32), 'b' as u32);
+    assert_eq!(pool::avoid_confusable(&set, 'I' as u32), 'b' as u32);
+    // past the end it wraps to the start
+    assert_eq!(pool::avoid_confusable(&set, 'l' as u32), 'a' as u32);
+    assert_eq!(pool::avoid_confusable(&set, '1' as u32), 'a' as u32);
+    assert_eq!(pool::avoid_confusable(&set, 'b' as u32), 'b' as u32);
+
+    // nothing to go to, or not in the set at all
+    let only: Vec<u32> = "0O".chars().map(|c| c as u32).collect();
+    assert_eq!(pool::avoid_confusable(&only, 'O' as u32), 'O' as u32);
+    assert_eq!(pool::avoid_confusable(&only, 'l' as u32), 'l' as u32);
+
+    // Cyrillic and Greek lookalikes of Latin letters, fullwidth forms
+    for ch in ['а', 'о', 'Ο', 'ν', 'Ｏ', '−'] {
+        assert!(pool::is_confusable(ch as u32), "{}", ch);
+    }
+    for ch in ['a', 'b', 'Z', '7', 'ж', 'λ'] {
+        assert!(!pool::is_confusable(ch as u32), "{}", ch);
+    }
+}
+
+#[test]
+fn collapsed_characters_avoid_confusables_too() {
+    // digits only: 0 and 1 are out, everything lands on 2-9
+    let rules = OutputRules {
+        max_length: 0,
+        char_types: 1 << 2,
+        no_confusables: true,
+        positions: Positions::NONE,
+    };
+    let applied = rules.apply("abcdefghijklmnopqrstuvwxyz");
+    assert!(
+        applied.chars().all(|ch| ('2'..='9').contains(&ch)),
+        "{}",
+        *applied
+    );
+}
+
+#[test]
+fn the_toggle_is_kept_beside_the_rules() {
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+    let mut table = Box::new(DomainTable::new());
+
+    assert!(!table.no_confusables("example.com", &mut structure));
+    table
+        .set_no_confusables("example.com", true, &mut structure)
+        .expect("set");
+    assert!(table.no_confusables("example.com", &mut structure));
+    assert_eq!(table.get_counter("example.com", &mut structure), Some(0));
+
+    // the rules neither see the bit nor clear it
+    table
+        .set_rules("example.com", 24, 7, &mut structure)
+        .expect("rules");
+    assert_eq!(
+        table.get_rules("example.com", &mut structure),
+        Some((24, 7))
+    );
+    assert!(table.no_confusables("example.com", &mut structure));
+
+    table
+        .set_no_confusables("example.com", false, &mut structure)
+        .expect("clear");
+    assert!(!table.no_confusables("example.com", &mut structure));
+    assert_eq!(
+        table.get_rules("example.com", &mut structure),
+        Some((24, 7))
+    );
+}
@@ -0,0 +1,60 @@
+// domain hashes are compared with constant_time_eq, which looks at every byte
+// whatever it finds. checks it answers what == does, for a difference at each
+// position, and that the table lookups built on it still find every domain.
+// the timing side is `cargo bench --bench domain_hash_compare`
+
+use void_vault::{constant_time_eq, DomainTable, SessionState, StructureSystem};
+
+fn hash(seed: u8) -> [u8; 64] {
+    let mut hash = [0u8; 64];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = seed
+            .wrapping_mul(31)
+            .wrapping_add((i as u8).wrapping_mul(7))
+            | 1;
+    }
+    hash
+}
+
+#[test]
+fn answers_what_equality_does() {
+    let a = hash(3);
+    assert!(constant_time_eq(&a, &a));
+    for position in 0..64 {
+        for flip in [0x01u8, 0x80, 0xff] {
+            let mut b = a;
+            b[position] ^= flip;
+            assert_eq!(constant_time_eq(&a, &b), a == b, "byte {}", position);
+            assert!(!constant_time_eq(&a, &b));
+        }
+    }
+    assert!(!constant_time_eq(&a[..63], &a));
+    assert!(constant_time_eq(&[], &[]));
+}
+
+#[test]
+fn lookups_find_every_domain() {
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+
+    let mut table = Box::new(DomainTable::new());
+    for n in 0..40u16 {
+        let domain = format!("site{}.example", n);
+        table
+            .set_counter(&domain, n + 1, &mut structure)
+            .expect("set counter");
+    }
+    for n in 0..40u16 {
+        let domain = format!("site{}.example", n);
+        assert_eq!(table.get_counter(&domain, &mut structure), Some(n + 1));
+    }
+    assert_eq!(table.get_counter("unknown.example", &mut structure), None);
+
+    let mut session = SessionState::empty();
+    let active = structure.hash_domain("site7.example");
+    assert!(!session.is_active_domain(&active));
+    session.active_domain_hash = Some(active);
+    assert!(session.is_active_domain(&structure.hash_domain("site7.example")));
+    assert!(!session.is_active_domain(&structure.hash_domain("site8.example")));
+}
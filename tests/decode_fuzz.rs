@@ -0,0 +1,163 @@
+// corrupt configuration records have to come back as errors, never panics or
+// endless loops, so one bad entry is skipped instead of the whole vault refusing to
+// open. feeds every prefix of the records and the tests/corpus ones, then randomly
+// damaged copies, through both decoders
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::{
+    DecodeError, MemoryStorage, PasswordManager, SavedPassword, StructureSystem, VaultStorage,
+    MAX_CHARACTER_SET, MAX_DIMENSIONS,
+};
+
+fn saved(structure_system: StructureSystem) -> SavedPassword {
+    let mut saved = common::saved("fuzzed", structure_system, 3);
+    saved.description = "décrit".to_string();
+    saved.created_date = 1_700_000_000;
+    saved.use_count = 5;
+    saved
+}
+
+fn records() -> Vec<Vec<u8>> {
+    let pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+    let phrase: Vec<char> = "a phrase for the fuzzed records".chars().collect();
+
+    let mut grown = StructureSystem::new(11, 5, 17);
+    grown.generate_structure(&phrase, &pool);
+    let mut bare = StructureSystem::new(12, 3, 17);
+    bare.set_character_set(pool);
+
+    let mut records = vec![saved(grown).to_bytes(), saved(bare).to_bytes()];
+    records.extend(corpus().into_iter().map(|(_, bytes)| bytes));
+    records
+}
+
+// tests/corpus: records as written when it was committed, which later formats must
+// keep reading or refusing the same way, and some damaged on purpose
+fn corpus() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("corpus");
+    let mut files: Vec<(String, Vec<u8>)> = std::fs::read_dir(&dir)
+        .expect("read tests/corpus")
+        .map(|entry| {
+            let path = entry.expect("corpus entry").path();
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read(&path).expect("read corpus record"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn decode_both(bytes: &[u8]) {
+    let _ = SavedPassword::from_bytes(bytes);
+    let _ = SavedPassword::from_bytes_without_points(bytes);
+}
+
+#[test]
+fn every_prefix_decodes_or_errors() {
+    for record in records() {
+        for len in 0..record.len() {
+            decode_both(&record[..len]);
+        }
+    }
+}
+
+#[test]
+fn damaged_records_decode_or_error() {
+    // xorshift, so a failure reproduces
+    let mut state = 0x9E3779B97F4A7C15u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for record in records() {
+        for _ in 0..2000 {
+            let mut damaged = record.clone();
+            for _ in 0..1 + next() % 4 {
+                let at = (next() % damaged.len() as u64) as usize;
+                damaged[at] = match next() % 3 {
+                    0 => next() as u8,
+                    1 => 0xFF,
+                    _ => 0,
+                };
+            }
+            let cut = damaged.len() - (next() % 8) as usize;
+            decode_both(&damaged[..cut]);
+        }
+    }
+}
+
+#[test]
+fn a_bad_entry_is_skipped_on_load() {
+    let records = records();
+    let mut storage = MemoryStorage::default();
+    storage
+        .store("good".to_string(), String::new(), &records[0])
+        .expect("store");
+    storage
+        .store("cut".to_string(), String::new(), &records[0][..3])
+        .expect("store");
+    let mut huge = records[1].clone();
+    huge[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+    storage
+        .store("huge".to_string(), String::new(), &huge)
+        .expect("store");
+
+    let manager = PasswordManager::with_storage(storage, true).expect("load");
+    let names: Vec<&str> = manager
+        .saved_passwords
+        .iter()
+        .map(|password| password.name.as_str())
+        .collect();
+    assert_eq!(names, ["fuzzed"]);
+}
+
+#[test]
+fn corpus_reads_or_trips_its_limit() {
+    for (name, bytes) in corpus() {
+        let result = SavedPassword::from_bytes(&bytes).map(|_| ());
+        let without_points = SavedPassword::from_bytes_without_points(&bytes).map(|_| ());
+        match name.as_str() {
+            "bare" | "grown" | "legacy" => {
+                assert_eq!(result, Ok(()), "{}", name);
+                assert_eq!(without_points, Ok(()), "{}", name);
+            }
+            "too_many_dimensions" => assert_eq!(
+                result,
+                Err(DecodeError::OverLimit(
+                    "dimensions",
+                    40,
+                    MAX_DIMENSIONS as u64
+                ))
+            ),
+            "huge_character_set" => {
+                let Err(DecodeError::OverLimit("character set size", declared, limit)) = result
+                else {
+                    panic!("{}: {:?}", name, result);
+                };
+                assert_eq!(declared, u32::MAX as u64);
+                assert!(limit <= MAX_CHARACTER_SET as u64);
+            }
+            "huge_point_count" => {
+                assert!(
+                    matches!(
+                        result,
+                        Err(DecodeError::OverLimit("active point count", 0xFFFFFFF0, _))
+                    ),
+                    "{}: {:?}",
+                    name,
+                    result
+                );
+                assert_eq!(result, without_points);
+            }
+            other => panic!("tests/corpus/{}.record has no expectation", other),
+        }
+    }
+}
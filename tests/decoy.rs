@@ -0,0 +1,227 @@
+// the decoy keeps a domain table of its own, right after the real one, which has to
+// come and go without disturbing it. and a vault with a decoy lists only the others,
+// which under duress are the decoy under their own names: inspect can't tell them
+// from it but for the name
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::{
+    BinaryStorageManager, DomainTable, Inspection, MemoryStorage, PasswordManager, SavedPassword,
+    SetupMode, StructureSystem, TableRegion, VaultStorage,
+};
+
+fn saved(name: &str, seed: u64, extra_chars_count: usize) -> SavedPassword {
+    let phrase = format!("a phrase for {}", name);
+    let structure = common::structure_for(&phrase, seed, 7, &common::printable());
+    let mut saved = common::saved(name, structure, extra_chars_count);
+    saved.description = format!("{} description", name);
+    saved.created_date = 1_700_000_000;
+    saved
+}
+
+#[cfg(unix)]
+fn inode(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).expect("stat vault").ino()
+}
+
+#[test]
+fn the_decoy_table_sits_beside_the_real_one() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("decoy-table");
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    let vault = dir.join("vault");
+
+    let header: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+    std::fs::write(&vault, &header).expect("write scratch vault");
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+
+    let mut real = Box::new(DomainTable::new());
+    real.set_counter("example.com", 3, &mut structure)
+        .expect("set counter");
+    real.save_region(&vault, TableRegion::Real)
+        .expect("save real");
+
+    // never written, so it loads empty, whatever the table held before
+    let mut decoy = Box::new(DomainTable::new());
+    decoy
+        .set_counter("stale.example", 9, &mut structure)
+        .expect("set counter");
+    decoy
+        .load_region(&vault, TableRegion::Decoy)
+        .expect("load decoy");
+    assert_eq!(decoy.get_counter("stale.example", &mut structure), None);
+
+    decoy
+        .set_counter("example.com", 7, &mut structure)
+        .expect("set counter");
+    decoy.set_decoy(Some("shown")).expect("mark decoy");
+    decoy
+        .save_region(&vault, TableRegion::Decoy)
+        .expect("save decoy");
+
+    storage
+        .store("main".to_string(), String::new(), &[1; 500])
+        .expect("store");
+
+    let mut loaded = Box::new(DomainTable::new());
+    loaded
+        .load_region(&vault, TableRegion::Real)
+        .expect("load real");
+    assert_eq!(loaded.get_counter("example.com", &mut structure), Some(3));
+    assert!(!loaded.is_decoy("shown"));
+    loaded
+        .load_region(&vault, TableRegion::Decoy)
+        .expect("load decoy");
+    assert_eq!(loaded.get_counter("example.com", &mut structure), Some(7));
+    assert!(loaded.is_decoy("shown"));
+    assert!(!loaded.is_decoy("main"));
+    // the mark is a reserved slot, not a domain
+    assert_eq!(
+        loaded
+            .slots()
+            .iter()
+            .filter(|slot| !slot.is_empty() && !slot.is_reserved())
+            .count(),
+        1
+    );
+
+    // once there, both are patched where they are
+    #[cfg(unix)]
+    let before = inode(&vault);
+    decoy
+        .increment_counter("example.com", &mut structure)
+        .expect("increment");
+    decoy
+        .save_region(&vault, TableRegion::Decoy)
+        .expect("save decoy");
+    real.increment_counter("example.com", &mut structure)
+        .expect("increment");
+    real.save_region(&vault, TableRegion::Real)
+        .expect("save real");
+    #[cfg(unix)]
+    assert_eq!(inode(&vault), before);
+
+    loaded
+        .load_region(&vault, TableRegion::Real)
+        .expect("load real");
+    assert_eq!(loaded.get_counter("example.com", &mut structure), Some(4));
+    loaded
+        .load_region(&vault, TableRegion::Decoy)
+        .expect("load decoy");
+    assert_eq!(loaded.get_counter("example.com", &mut structure), Some(8));
+
+    drop(storage);
+    let reopened = BinaryStorageManager::open(vault.clone(), true, None).expect("reopen");
+    assert_eq!(reopened.list_all().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// the decoy, set up earlier and some other way than main
+fn shown() -> SavedPassword {
+    let mut shown = saved("shown", 1042, 2);
+    shown.created_date = 1_600_000_000;
+    shown.setup_mode = Some(SetupMode::Timed);
+    shown.use_count = 5;
+    shown
+}
+
+fn storage() -> MemoryStorage {
+    let mut storage = MemoryStorage::default();
+    for config in [saved("main", 42, 7), shown()] {
+        storage
+            .store(
+                config.name.clone(),
+                config.description.clone(),
+                &config.to_bytes(),
+            )
+            .expect("store");
+    }
+    storage
+}
+
+#[test]
+fn the_decoy_stands_in_only_under_duress() {
+    let mut decoys = Box::new(DomainTable::new());
+    decoys.set_decoy(Some("shown")).expect("mark decoy");
+
+    let manager = PasswordManager::with_decoy(storage(), true, &decoys, false).expect("load");
+    let names: Vec<&str> = manager
+        .saved_passwords
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect();
+    assert_eq!(names, ["main"]);
+    assert_eq!(
+        manager.saved_passwords[0].structure_system.original_seed,
+        42
+    );
+
+    let manager =
+        PasswordManager::with_decoy(storage(), true, &decoys, true).expect("load under duress");
+    let names: Vec<&str> = manager
+        .saved_passwords
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect();
+    assert_eq!(names, ["main"]);
+    let main = &manager.saved_passwords[0];
+    assert_eq!(main.structure_system.original_seed, 1042);
+    // the listing's own fields stay, the rest is the decoy's
+    assert_eq!(
+        (main.description.as_str(), main.use_count),
+        ("main description", 0)
+    );
+    assert_eq!(
+        (main.extra_chars_count, main.created_date, main.setup_mode),
+        (2, 1_600_000_000, Some(SetupMode::Timed))
+    );
+
+    // inspect reports the decoy field for field, under the name it stands in for
+    let shown = SavedPassword::from_bytes(&shown().to_bytes()).expect("decode");
+    let format_version = manager.format_version(0).expect("a format version");
+    assert_eq!(
+        Inspection::of(main, format_version).to_json(),
+        Inspection::of(&shown, format_version)
+            .to_json()
+            .replace("\"name\":\"shown\"", "\"name\":\"main\"")
+    );
+
+    let without_points = PasswordManager::load_one_with_decoy(&storage(), None, &decoys, true)
+        .expect("a configuration");
+    assert_eq!(without_points.name, "main");
+    assert_eq!(without_points.structure_system.original_seed, 1042);
+    assert!(
+        PasswordManager::load_one_with_decoy(&storage(), Some("shown"), &decoys, false).is_none()
+    );
+}
+
+#[test]
+fn a_lone_decoy_is_served_as_itself() {
+    let config = saved("shown", 1042, 2);
+    let mut decoys = Box::new(DomainTable::new());
+    decoys.set_decoy(Some("shown")).expect("mark decoy");
+
+    for duress in [false, true] {
+        let manager =
+            PasswordManager::with_decoy(storage_of(&config), true, &decoys, duress).expect("load");
+        assert_eq!(manager.saved_passwords.len(), 1);
+        assert_eq!(manager.saved_passwords[0].name, "shown");
+    }
+}
+
+fn storage_of(config: &SavedPassword) -> MemoryStorage {
+    let mut storage = MemoryStorage::default();
+    storage
+        .store(config.name.clone(), String::new(), &config.to_bytes())
+        .expect("store");
+    storage
+}
@@ -0,0 +1,172 @@
+// the golden vectors were generated on x86_64 linux. any target this runs on has to
+// compute the same hashes, or a vault copied there would generate other passwords
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::{
+    generate_keystroke, Motion, Normalization, SavedPassword, StructureSystem, Transform,
+    DETERMINISM_VECTORS, SELF_TESTS,
+};
+
+#[test]
+fn golden_vectors_match() {
+    for vector in &DETERMINISM_VECTORS {
+        assert_eq!(
+            vector.compute(),
+            vector.expected,
+            "{} differs on this target",
+            vector.name
+        );
+    }
+}
+
+#[test]
+fn self_test_sequences_match() {
+    for test in &SELF_TESTS {
+        assert_eq!(
+            test.compute(),
+            test.expected,
+            "{} ({}) drifted",
+            test.name,
+            test.sequence
+        );
+    }
+}
+
+#[test]
+fn new_structures_keep_fixed_point_motion_and_the_latest_transform() {
+    let mut structure = StructureSystem::new(7, 5, 17);
+    structure.set_character_set((33..127).collect());
+    assert_eq!(structure.motion, Motion::Fixed);
+    assert_eq!(structure.transform, Transform::V7);
+
+    let saved = common::saved("motion", structure, 0);
+    let loaded = SavedPassword::from_bytes(&saved.to_bytes()).expect("decode");
+    assert_eq!(loaded.structure_system.motion, Motion::Fixed);
+    assert_eq!(loaded.structure_system.transform, Transform::V7);
+}
+
+#[test]
+fn records_from_before_format_version_2_keep_transform_v1() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    for name in ["bare", "grown", "legacy"] {
+        let bytes = std::fs::read(corpus.join(format!("{}.record", name))).expect("read");
+        let mut loaded = SavedPassword::from_bytes(&bytes).expect("decode");
+        assert_eq!(loaded.structure_system.transform, Transform::V1, "{}", name);
+
+        // and stored again, they still generate the same
+        let mut again = SavedPassword::from_bytes(&loaded.to_bytes()).expect("decode again");
+        assert_eq!(again.structure_system.transform, Transform::V1);
+        for key in 33..127 {
+            assert_eq!(
+                loaded.structure_system.transform_char(key, 2),
+                again.structure_system.transform_char(key, 2)
+            );
+        }
+    }
+}
+
+#[test]
+fn the_transforms_generate_differently() {
+    let output = |transform| {
+        let mut structure = StructureSystem::new(99, 6, 17);
+        structure.set_character_set((33..127).collect());
+        structure.transform = transform;
+        (33..127)
+            .flat_map(|key| structure.transform_char(key, 1))
+            .collect::<Vec<u32>>()
+    };
+    assert_ne!(output(Transform::V1), output(Transform::V2));
+}
+
+#[test]
+fn transform_v3_chains_a_wide_feedback() {
+    let typed = |transform| {
+        let mut structure_system = StructureSystem::new(99, 6, 17);
+        structure_system.set_character_set((33..127).collect());
+        structure_system.transform = transform;
+        let mut config = common::saved("feedback", structure_system, 1);
+        config.normalization = Normalization::None;
+        let mut feedbacks = Vec::new();
+        let outputs: Vec<String> = "correct horse"
+            .chars()
+            .map(|ch| generate_keystroke(&mut config, &mut feedbacks, ch as u32).to_string())
+            .collect();
+        (outputs, feedbacks)
+    };
+
+    let (v2, v2_feedbacks) = typed(Transform::V2);
+    let (v3, v3_feedbacks) = typed(Transform::V3);
+    assert!(v2_feedbacks.iter().all(|&feedback| feedback < 256));
+    assert!(v3_feedbacks
+        .iter()
+        .any(|&feedback| feedback > u32::MAX as u64));
+    // the first keystroke has no feedback to differ by
+    assert_eq!(v2[0], v3[0]);
+    assert_ne!(v2[1..], v3[1..]);
+}
+
+#[test]
+fn transform_v4_mixes_the_whole_domain() {
+    let structure = |seed, transform| {
+        let mut structure = StructureSystem::new(seed, 6, 17);
+        structure.set_character_set((33..127).collect());
+        structure.transform = transform;
+        structure
+    };
+    let differing = |a: [u8; 64], b: [u8; 64]| a.iter().zip(&b).filter(|(a, b)| a != b).count();
+
+    let mut v4 = structure(99, Transform::V4);
+    let mail = v4.hash_domain("mail.example.com");
+    assert_eq!(mail, v4.hash_domain("mail.example.com"));
+    assert!(differing(mail, v4.hash_domain("maii.example.com")) > 56);
+    assert!(differing(mail, v4.hash_domain("mail.example.co")) > 56);
+    assert!(differing(v4.hash_domain("a.co"), v4.hash_domain("b.co")) > 56);
+    // and it is the structure's own
+    assert!(
+        differing(
+            mail,
+            structure(100, Transform::V4).hash_domain("mail.example.com")
+        ) > 56
+    );
+
+    // the structures before keep the walk their domain tables are keyed by
+    let mut v3 = structure(99, Transform::V3);
+    let walked = v3.hash_domain("mail.example.com");
+    assert_eq!(walked, v3.hash_domain("mail.example.com"));
+    assert_ne!(walked, mail);
+}
+
+#[test]
+fn transform_v6_samples_where_a_keystroke_lands() {
+    let raw = |transform, keycodes: &[u32], extra_chars| {
+        let mut structure = StructureSystem::new(99, 6, 17);
+        structure.set_character_set((33..127).collect());
+        structure.transform = transform;
+        keycodes
+            .iter()
+            .map(|&keycode| structure.transform_char_raw(keycode, extra_chars))
+            .collect::<Vec<Vec<u32>>>()
+    };
+
+    // the step is the same, only where along it is sampled moves: v6's last sample
+    // is the landing, where v5 took the first sample of the next keystroke
+    let v5 = raw(Transform::V5, &[65, 66], 2);
+    let v6 = raw(Transform::V6, &[65, 66], 2);
+    assert_eq!(v6[0][2], v5[1][0]);
+    assert_eq!(v6[0][1], v5[0][2]);
+    // and the first sample has the keycode in it
+    assert_ne!(v6[0][0], v5[0][1]);
+
+    // from the same position, v5's first character is the same whatever is typed
+    let first = |transform| {
+        (33..127)
+            .map(|keycode| raw(transform, &[keycode], 0)[0][0])
+            .collect::<std::collections::HashSet<u32>>()
+            .len()
+    };
+    assert_eq!(first(Transform::V5), 1);
+    assert_eq!(first(Transform::V6), 94);
+}
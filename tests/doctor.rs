@@ -0,0 +1,116 @@
+// doctor only reads, and says what it found in its exit code: 64 and a bit per kind
+// of finding. --fix mends a vault that lost its section marker and a browser
+// manifest starting a binary that moved, once each is agreed to
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use void_vault::doctor::{self, HOST_NAME};
+use void_vault::BinaryStorageManager;
+
+// the vault's exit code and stdout, stdin closed unless there is input
+fn run(vault: &Path, home: &Path, args: &[&str], input: Option<&[u8]>) -> (i32, String) {
+    let mut child = Command::new(vault)
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .env("HOME", home)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("run the vault");
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .expect("stdin")
+            .write_all(input)
+            .expect("answer");
+    }
+    let output = child.wait_with_output().expect("wait for the vault");
+    (
+        output.status.code().expect("an exit code"),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+#[test]
+fn doctor_finds_and_fixes_a_broken_installation() {
+    let dir = common::scratch("doctor");
+    let vault = common::vault_in(&dir);
+
+    // nothing set up yet
+    let (code, _) = run(&vault, &dir, &["doctor"], None);
+    assert_eq!(code, doctor::FOUND | doctor::VAULT);
+
+    let (code, _) = run(
+        &vault,
+        &dir,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        Some(b"\na phrase to set a vault up with, then break it\x1b"),
+    );
+    assert_eq!(code, 0);
+    let (code, stdout) = run(&vault, &dir, &["doctor"], None);
+    assert_eq!(code, 0, "{}", stdout);
+
+    // a byte after the section marker, and a manifest left behind by a move
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&vault)
+        .expect("open the vault");
+    file.write_all(b"x").expect("break the vault");
+    drop(file);
+    let hosts = dir.join(".config/chromium/NativeMessagingHosts");
+    std::fs::create_dir_all(&hosts).expect("create the manifest dir");
+    let manifest = hosts.join(format!("{}.json", HOST_NAME));
+    std::fs::write(
+        &manifest,
+        "{\n  \"name\": \"com.starwell.void_vault\",\n  \"path\": \"/moved/void_vault\",\n  \"type\": \"stdio\"\n}\n",
+    )
+    .expect("write the manifest");
+    let layout = BinaryStorageManager::probe(vault.clone()).expect("probe");
+    assert!(!layout.section_marker);
+    assert_eq!(layout.configurations, 1);
+
+    let (code, stdout) = run(&vault, &dir, &["--json", "doctor"], None);
+    assert_eq!(code, doctor::FOUND | doctor::VAULT | doctor::MANIFEST);
+    assert!(
+        stdout.contains("\"check\":\"section_marker\""),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("\"check\":\"manifest\""), "{}", stdout);
+    // only looked
+    assert!(
+        !BinaryStorageManager::probe(vault.clone())
+            .expect("probe")
+            .section_marker
+    );
+
+    let (code, stdout) = run(&vault, &dir, &["doctor", "--fix"], Some(b"y\ny\n"));
+    assert_eq!(code, 0, "{}", stdout);
+    assert!(
+        BinaryStorageManager::probe(vault.clone())
+            .expect("probe")
+            .section_marker
+    );
+    let manifest = std::fs::read_to_string(&manifest).expect("read the manifest");
+    assert!(
+        manifest.contains(&format!("\"path\": \"{}\"", vault.display())),
+        "{}",
+        manifest
+    );
+
+    let (code, stdout) = run(&vault, &dir, &["accounts"], None);
+    assert_eq!(code, 0);
+    assert!(stdout.contains("main"), "{}", stdout);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
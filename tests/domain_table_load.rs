@@ -0,0 +1,76 @@
+// the domain table commands read one configuration without its structure's points.
+// on a setup-sized structure that has to stay far below the 100ms a command may
+// take, hash domains exactly as the full load does, and never be stored back
+//
+//   cargo test --test domain_table_load -- --nocapture
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use void_vault::{MemoryStorage, PasswordManager, SavedPassword, StructureSystem, VaultStorage};
+
+// the "full" setup pool
+fn full_pool() -> Vec<u32> {
+    let mut pool = Vec::new();
+    pool.extend(32..127);
+    pool.extend(161..5000);
+    pool.extend(8192..8500);
+    pool.extend(9000..9500);
+    pool.extend(128512..128591);
+    pool
+}
+
+fn storage() -> MemoryStorage {
+    let pool = full_pool();
+    let mut structure = StructureSystem::new(0x5EED, 7, 17);
+    structure.generate_structure(&[], &pool);
+    for (i, ch) in "a setup phrase typed with some rhythm".chars().enumerate() {
+        structure.modify_with_timing(ch as u32, 90 + i as u64 * 13, 1_000 + i as u64);
+    }
+    structure.full_reset();
+
+    let config = common::saved("main", structure, 3);
+    let mut storage = MemoryStorage::default();
+    storage
+        .store("main".to_string(), String::new(), &config.to_bytes())
+        .expect("store");
+    storage
+}
+
+#[test]
+fn table_lookup_loads_quickly() {
+    let storage = storage();
+
+    let started = Instant::now();
+    let mut config =
+        PasswordManager::load_one_without_points(&storage, None).expect("a configuration");
+    let hash = config.structure_system.hash_domain("example.com");
+    let lazy = started.elapsed();
+
+    let (data, _) = storage.retrieve("main").unwrap().unwrap();
+    let started = Instant::now();
+    let mut full = SavedPassword::from_bytes(data).expect("full decode");
+    let full_hash = full.structure_system.hash_domain("example.com");
+    let eager = started.elapsed();
+
+    println!("without points {:?}, with them {:?}", lazy, eager);
+    assert_eq!(hash, full_hash);
+    assert!(lazy < Duration::from_millis(100), "took {:?}", lazy);
+    assert!(lazy < eager);
+}
+
+#[test]
+fn accounts_are_picked_by_name() {
+    let storage = storage();
+    assert!(PasswordManager::load_one_without_points(&storage, Some("main")).is_some());
+    assert!(PasswordManager::load_one_without_points(&storage, Some("other")).is_none());
+}
+
+#[test]
+fn a_configuration_without_points_is_not_stored() {
+    let storage = storage();
+    let config = PasswordManager::load_one_without_points(&storage, None).unwrap();
+    let mut manager = PasswordManager::with_storage(MemoryStorage::default(), true).unwrap();
+    assert!(manager.save_password(&config).is_err());
+}
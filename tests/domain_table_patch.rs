@@ -0,0 +1,87 @@
+// DomainTable::save_to_binary overwrites the table's region where it sits when the
+// vault can be opened for writing. interleaves those with store()'s full rewrites,
+// which move everything into a new file, and wants every counter and
+// configuration to survive both
+
+use std::path::Path;
+
+use void_vault::{BinaryStorageManager, DomainTable, StructureSystem, VaultStorage};
+
+#[cfg(unix)]
+fn inode(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).expect("stat vault").ino()
+}
+
+#[test]
+fn in_place_saves_interleave_with_full_rewrites() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("domain-table-patch");
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    let vault = dir.join("vault");
+
+    let header: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+    std::fs::write(&vault, &header).expect("write scratch vault");
+
+    // opening appends the section marker and an empty table
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+
+    let mut table = Box::new(DomainTable::new());
+    for round in 0..4u16 {
+        let domain = format!("round{}.example", round);
+        table
+            .set_counter(&domain, round + 10, &mut structure)
+            .expect("set counter");
+        table
+            .set_counter("every.example", round, &mut structure)
+            .expect("set counter");
+
+        #[cfg(unix)]
+        let before = inode(&vault);
+        table.save_to_binary(&vault).expect("save table");
+        assert!(!table.is_dirty());
+        #[cfg(unix)]
+        assert_eq!(inode(&vault), before, "round {} rewrote the file", round);
+
+        // a full rewrite appends after the table, which has to stay where it's found
+        storage
+            .store(
+                format!("config{}", round),
+                String::new(),
+                &vec![round as u8; 1000 + round as usize],
+            )
+            .expect("store");
+
+        let mut loaded = Box::new(DomainTable::new());
+        loaded.load_from_binary(&vault).expect("load table");
+        for earlier in 0..=round {
+            let domain = format!("round{}.example", earlier);
+            assert_eq!(
+                loaded.get_counter(&domain, &mut structure),
+                Some(earlier + 10)
+            );
+        }
+        assert_eq!(
+            loaded.get_counter("every.example", &mut structure),
+            Some(round)
+        );
+    }
+    drop(storage);
+
+    let reopened = BinaryStorageManager::open(vault.clone(), true, None).expect("reopen");
+    assert_eq!(reopened.list_all().len(), 4);
+    for round in 0..4u8 {
+        let (data, _) = reopened
+            .retrieve(&format!("config{}", round))
+            .expect("retrieve")
+            .expect("config is stored");
+        assert_eq!(data, vec![round; 1000 + round as usize].as_slice());
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
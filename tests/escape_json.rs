@@ -0,0 +1,129 @@
+// escape_json against a parser as strict as the JSON grammar: every code any pool
+// setup offers, and the controls no pool should hold but a custom one might, come
+// back out of the escaped string exactly as they went in, with no raw control
+// character and no lone surrogate on the way
+
+use void_vault::{escape_json, pool};
+
+// the string `json` is, quotes and all, or why it isn't one
+fn parse_string(json: &str) -> Result<String, String> {
+    let mut chars = json.chars();
+    if chars.next() != Some('"') {
+        return Err("no opening quote".to_string());
+    }
+
+    let hex4 = |chars: &mut std::str::Chars| -> Result<u32, String> {
+        let digits: String = chars.by_ref().take(4).collect();
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("bad \\u escape {:?}", digits));
+        }
+        Ok(u32::from_str_radix(&digits, 16).expect("hex"))
+    };
+
+    let mut parsed = String::new();
+    loop {
+        match chars.next().ok_or("no closing quote")? {
+            '"' => break,
+            '\\' => {
+                let escaped = match chars.next().ok_or("escape at the end")? {
+                    '"' => '"',
+                    '\\' => '\\',
+                    '/' => '/',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let unit = hex4(&mut chars)?;
+                        let code = match unit {
+                            0xD800..=0xDBFF => {
+                                if chars.next() != Some('\\') || chars.next() != Some('u') {
+                                    return Err(format!("lone high surrogate {:04x}", unit));
+                                }
+                                let low = hex4(&mut chars)?;
+                                if !(0xDC00..=0xDFFF).contains(&low) {
+                                    return Err(format!("{:04x} doesn't pair {:04x}", low, unit));
+                                }
+                                0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                            }
+                            0xDC00..=0xDFFF => {
+                                return Err(format!("lone low surrogate {:04x}", unit))
+                            }
+                            _ => unit,
+                        };
+                        char::from_u32(code).ok_or("not a character")?
+                    }
+                    other => return Err(format!("unknown escape \\{}", other)),
+                };
+                parsed.push(escaped);
+            }
+            c if (c as u32) < 0x20 => return Err(format!("raw control {:02x}", c as u32)),
+            c => parsed.push(c),
+        }
+    }
+    match chars.next() {
+        None => Ok(parsed),
+        Some(_) => Err("text after the closing quote".to_string()),
+    }
+}
+
+fn round_trip(value: &str) -> Result<String, String> {
+    parse_string(&format!("\"{}\"", escape_json(value)))
+}
+
+#[test]
+fn the_parser_is_strict() {
+    for bad in [
+        "\"\u{1}\"",
+        "\"\\ud83d\"",
+        "\"\\ude00\"",
+        "\"\\x41\"",
+        "\"\\u12\"",
+        "\"open",
+        "\"\"\"",
+    ] {
+        assert!(parse_string(bad).is_err(), "{:?}", bad);
+    }
+    assert_eq!(
+        parse_string("\"\\ud83d\\ude00\\/\""),
+        Ok("\u{1F600}/".to_string())
+    );
+}
+
+#[test]
+fn every_pool_code_round_trips() {
+    for name in pool::PRESETS {
+        let codes = pool::preset(name).expect("a preset");
+        let chars: Vec<char> = codes
+            .iter()
+            .map(|&code| char::from_u32(code).expect("a character"))
+            .collect();
+
+        for &ch in &chars {
+            let one = ch.to_string();
+            assert_eq!(round_trip(&one), Ok(one), "{} in {}", ch as u32, name);
+        }
+        // and run together, as an output is
+        let all: String = chars.iter().collect();
+        assert_eq!(round_trip(&all), Ok(all), "{}", name);
+    }
+}
+
+#[test]
+fn controls_and_separators_are_escaped() {
+    let awkward: String = (0..0x20)
+        .chain(0x7F..=0x9F)
+        .chain([0x2028, 0x2029, 0x22, 0x5C, 0x2F, 0xFEFF, 0x10FFFF])
+        .map(|code| char::from_u32(code).expect("a character"))
+        .collect();
+    assert_eq!(round_trip(&awkward), Ok(awkward.clone()));
+
+    // nothing Chrome's reader or a JavaScript string literal chokes on goes out raw,
+    // and what is past the BMP goes as a surrogate pair
+    let escaped = escape_json(&awkward);
+    assert!(escaped
+        .chars()
+        .all(|c| !c.is_control() && c != '\u{2028}' && c != '\u{2029}'));
+    assert!(escaped.chars().all(|c| (c as u32) <= 0xFFFF));
+}
@@ -0,0 +1,96 @@
+// the exit status scripts branch on, from a copy of the binary: 2 for a command line
+// that doesn't parse or gives a flag a value it can't take, 3 while there is no
+// configuration, 4 for a domain that isn't
+// registered, 0 once things work. error-codes lists the same numbers
+
+mod common;
+
+use std::path::Path;
+
+fn status(vault: &Path, args: &[&str]) -> i32 {
+    common::run(vault, args, b"")
+        .status
+        .code()
+        .expect("an exit code")
+}
+
+#[test]
+fn failures_exit_with_their_status() {
+    let dir = common::scratch("exit_codes");
+    let vault = common::vault_in(&dir);
+
+    // usage, with or without a vault
+    for args in [
+        &["--frobnicate"][..],
+        &["frobnicate"],
+        &["domains", "get"],
+        &["domains", "shuffle"],
+        &["domains", "set", "example.com", "many"],
+        &["--timeout", "soon", "io"],
+        &["inspect"],
+        &["io", "--fd", "x"],
+        &["io", "--format", "zip"],
+        &["json-io", "--idle-timeout", "5m"],
+        &["json-io", "--exit-after", "soon"],
+        &["daemon", "--exit-after", "-1"],
+    ] {
+        assert_eq!(status(&vault, args), 2, "{:?}", args);
+    }
+
+    // nothing set up yet
+    for args in [
+        &[][..],
+        &["accounts"],
+        &["term"],
+        &["io", "--domain", "example.com"],
+        &["json-io"],
+        &["domains", "get", "example.com"],
+        &["domains", "set", "example.com", "1"],
+    ] {
+        assert_eq!(status(&vault, args), 3, "{:?}", args);
+    }
+
+    common::setup(&vault, "a phrase for a vault that exits properly");
+    assert_eq!(status(&vault, &["accounts"]), 0);
+    assert_eq!(status(&vault, &["domains", "get", "example.com"]), 4);
+    assert_eq!(status(&vault, &["domains", "set", "example.com", "1"]), 0);
+    assert_eq!(status(&vault, &["domains", "get", "example.com"]), 0);
+    assert_eq!(status(&vault, &["--frobnicate"]), 2);
+
+    // values io and term only look at once the configuration is loaded
+    for args in [
+        &["io", "--length", "0"][..],
+        &["io", "--length", "abc"],
+        &["term", "--length", "0"],
+        &["io", "--words", "2"],
+        &["io", "--fd", "x", "--exec", "true"],
+        &["io", "--fd", "1", "--exec", "true"],
+        &["username", "example.com", "--length", "2"],
+        &["username", "example.com", "--digits", "maybe"],
+        &["domains", "pin", "example.com", "3"],
+    ] {
+        assert_eq!(status(&vault, args), 2, "{:?}", args);
+    }
+
+    let listed =
+        String::from_utf8(common::stdout(&vault, &["--json", "error-codes"], b"")).expect("UTF-8");
+    for (code, exit_code) in [
+        ("usage", 2),
+        ("no_configuration", 3),
+        ("unknown_domain", 4),
+        ("storage_failure", 5),
+        ("table_full", 6),
+    ] {
+        assert!(
+            listed.contains(&format!(
+                "{{\"code\":\"{}\",\"exit_code\":{},",
+                code, exit_code
+            )),
+            "{} in {}",
+            code,
+            listed
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
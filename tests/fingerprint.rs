@@ -0,0 +1,75 @@
+// a configuration's fingerprint hashes what it generates from synthetic input, a
+// stage at a time, and rides at the end of its record. records from before it have
+// none, and a copy that generates differently names the first stage that differs
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::{DecodeError, Fingerprint, Motion, SavedPassword, StructureSystem, Transform};
+
+fn saved(structure_system: StructureSystem) -> SavedPassword {
+    let mut saved = common::saved("fingerprinted", structure_system, 2);
+    saved.created_date = 1_700_000_000;
+    saved
+}
+
+fn structure() -> StructureSystem {
+    common::structure_for(
+        "a phrase for the fingerprinted structure",
+        42,
+        6,
+        &common::printable(),
+    )
+}
+
+#[test]
+fn the_fingerprint_is_stored_with_the_record() {
+    let mut config = saved(structure());
+    let fingerprint = Fingerprint::of(&config).expect("fingerprint");
+    assert_eq!(Fingerprint::of(&config), Some(fingerprint));
+
+    config.fingerprint = Some(fingerprint);
+    let bytes = config.to_bytes();
+    let loaded = SavedPassword::from_bytes(&bytes).expect("decode");
+    assert_eq!(loaded.fingerprint, Some(fingerprint));
+    // and the copy it loads as has the same one
+    assert_eq!(Fingerprint::of(&loaded), Some(fingerprint));
+
+    assert_eq!(
+        SavedPassword::from_bytes(&bytes[..bytes.len() - 3]).err(),
+        Some(DecodeError::Truncated("fingerprint"))
+    );
+}
+
+#[test]
+fn records_from_before_have_none() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    for name in ["bare", "grown", "legacy"] {
+        let bytes = std::fs::read(corpus.join(format!("{}.record", name))).expect("read");
+        let loaded = SavedPassword::from_bytes(&bytes).expect("decode");
+        assert_eq!(loaded.fingerprint, None, "{}", name);
+        assert!(Fingerprint::of(&loaded).is_some(), "{}", name);
+    }
+}
+
+#[test]
+fn the_first_stage_that_differs_is_named() {
+    let stored = Fingerprint::of(&saved(structure())).expect("fingerprint");
+    assert_eq!(stored.diverged_stage(&stored), None);
+
+    let mut config = saved(structure());
+    config.extra_chars_count = 3;
+    let output = Fingerprint::of(&config).expect("fingerprint");
+    assert_eq!(output.diverged_stage(&stored), Some("character output"));
+
+    let mut config = saved(structure());
+    config.structure_system.motion = Motion::Float;
+    let navigation = Fingerprint::of(&config).expect("fingerprint");
+    assert_eq!(navigation.diverged_stage(&stored), Some("ghost navigation"));
+
+    let mut config = saved(structure());
+    config.structure_system.transform = Transform::V3;
+    let domain_hash = Fingerprint::of(&config).expect("fingerprint");
+    assert_eq!(domain_hash.diverged_stage(&stored), Some("domain hash"));
+}
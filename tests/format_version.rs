@@ -0,0 +1,224 @@
+// configuration records and their structures start with a magic and a format
+// version. records from before that have neither and still read, through the
+// legacy decoder, and come back out versioned
+
+mod common;
+
+use void_vault::{
+    DecodeError, Motion, Normalization, SavedPassword, SetupMode, StructureSystem, FORMAT_VERSION,
+};
+
+fn saved(structure_system: StructureSystem) -> SavedPassword {
+    let mut saved = common::saved("versioned", structure_system, 2);
+    saved.description = "a description".to_string();
+    saved.created_date = 1_700_000_000;
+    saved.use_count = 9;
+    saved
+}
+
+fn outputs(config: &mut SavedPassword) -> Vec<u32> {
+    config.structure_system.full_reset();
+    (0..100u32)
+        .flat_map(|key| {
+            config
+                .structure_system
+                .transform_char(key % 90 + 33, config.extra_chars_count)
+        })
+        .collect()
+}
+
+// a record as written before versioning: no magic anywhere, no points, and the
+// optional trailing fields of the time all present
+fn legacy_record() -> Vec<u8> {
+    let mut structure = Vec::new();
+    structure.extend_from_slice(&5u32.to_le_bytes());
+    structure.extend_from_slice(&17i32.to_le_bytes());
+    structure.extend_from_slice(&1234u64.to_le_bytes());
+    structure.extend_from_slice(&7u32.to_le_bytes());
+    structure.extend_from_slice(b"default");
+    structure.extend_from_slice(&94u32.to_le_bytes());
+    for code in '!'..='~' {
+        structure.extend_from_slice(&(code as u32).to_le_bytes());
+    }
+    structure.extend_from_slice(&0u32.to_le_bytes());
+    structure.extend_from_slice(&0u32.to_le_bytes());
+    structure.extend_from_slice(&3.0f64.to_le_bytes());
+    structure.extend_from_slice(&2.0f64.to_le_bytes());
+    structure.push(0);
+
+    let mut record = Vec::new();
+    record.extend_from_slice(&6u32.to_le_bytes());
+    record.extend_from_slice(b"legacy");
+    record.extend_from_slice(&0u32.to_le_bytes());
+    record.extend_from_slice(&0u64.to_le_bytes());
+    record.extend_from_slice(&3u32.to_le_bytes());
+    record.extend_from_slice(&(structure.len() as u32).to_le_bytes());
+    record.extend(structure);
+    record.extend_from_slice(&4u64.to_le_bytes());
+    record.push(1);
+    record
+}
+
+fn position(bytes: &[u8], magic: &[u8]) -> usize {
+    bytes
+        .windows(magic.len())
+        .position(|w| w == magic)
+        .expect("magic is there")
+}
+
+#[test]
+fn version_one_round_trips() {
+    let pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+    let phrase: Vec<char> = "versioned records round trip".chars().collect();
+    let mut structure = StructureSystem::new(77, 6, 17);
+    structure.generate_structure(&phrase, &pool);
+    let bytes = saved(structure).to_bytes();
+    assert_eq!(&bytes[..4], b"VVcf");
+    assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), FORMAT_VERSION);
+
+    let mut loaded = SavedPassword::from_bytes(&bytes).expect("version 1 record");
+    assert_eq!(loaded.to_bytes(), bytes);
+    assert_eq!(loaded.name, "versioned");
+    assert_eq!(loaded.use_count, 9);
+    assert_eq!(loaded.normalization, Normalization::CURRENT);
+    assert_eq!(loaded.structure_system.motion, Motion::Fixed);
+
+    let mut again = SavedPassword::from_bytes(&loaded.to_bytes()).expect("record again");
+    assert_eq!(outputs(&mut loaded), outputs(&mut again));
+}
+
+#[test]
+fn legacy_records_read_and_come_back_versioned() {
+    let legacy = legacy_record();
+    let mut from_legacy = SavedPassword::from_bytes(&legacy).expect("legacy record");
+    assert_eq!(from_legacy.name, "legacy");
+    assert_eq!(from_legacy.extra_chars_count, 3);
+    assert_eq!(from_legacy.use_count, 4);
+    assert_eq!(from_legacy.normalization, Normalization::V1);
+    assert_eq!(from_legacy.structure_system.motion, Motion::Float);
+
+    let versioned = from_legacy.to_bytes();
+    assert_eq!(&versioned[..4], b"VVcf");
+    let mut reread = SavedPassword::from_bytes(&versioned).expect("re-encoded record");
+    assert_eq!(reread.structure_system.motion, Motion::Float);
+    assert_eq!(outputs(&mut reread), outputs(&mut from_legacy));
+
+    // a legacy record could end before its optional fields
+    let short = &legacy[..legacy.len() - 9];
+    let from_short = SavedPassword::from_bytes(short).expect("short legacy record");
+    assert_eq!(from_short.use_count, 0);
+    assert_eq!(from_short.normalization, Normalization::None);
+}
+
+#[test]
+fn newer_versions_are_refused_by_name() {
+    let mut structure = StructureSystem::new(5, 4, 17);
+    structure.set_character_set((33..127).collect());
+    let bytes = saved(structure).to_bytes();
+
+    let mut newer_record = bytes.clone();
+    newer_record[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        SavedPassword::from_bytes(&newer_record).err(),
+        Some(DecodeError::UnsupportedVersion(
+            "configuration",
+            FORMAT_VERSION + 1
+        ))
+    );
+
+    let mut newer_structure = bytes.clone();
+    let at = position(&bytes, b"VVst") + 4;
+    newer_structure[at..at + 2].copy_from_slice(&7u16.to_le_bytes());
+    let error = SavedPassword::from_bytes(&newer_structure).err();
+    assert_eq!(error, Some(DecodeError::UnsupportedVersion("structure", 7)));
+    assert!(error.unwrap().to_string().contains("version 7"));
+}
+
+#[test]
+fn versioned_records_must_be_complete() {
+    let mut structure = StructureSystem::new(5, 4, 17);
+    structure.set_character_set((33..127).collect());
+    let bytes = saved(structure).to_bytes();
+
+    // where a legacy record would fall back to a default, a versioned one is cut short
+    assert_eq!(
+        SavedPassword::from_bytes(&bytes[..bytes.len() - 1]).err(),
+        Some(DecodeError::Truncated("re-keyed from"))
+    );
+    assert_eq!(
+        SavedPassword::from_bytes(&bytes[..bytes.len() - 4]).err(),
+        Some(DecodeError::Truncated("re-keyed from"))
+    );
+    assert_eq!(
+        SavedPassword::from_bytes(&bytes[..bytes.len() - 5]).err(),
+        Some(DecodeError::Truncated("setup mode"))
+    );
+    assert_eq!(
+        SavedPassword::from_bytes(&bytes[..bytes.len() - 6]).err(),
+        Some(DecodeError::Truncated("normalization"))
+    );
+}
+
+#[test]
+fn version_2_records_end_before_the_setup_mode() {
+    let mut structure = StructureSystem::new(5, 4, 17);
+    structure.set_character_set((33..127).collect());
+    let mut bytes = saved(structure).to_bytes();
+    // the empty re-keyed from, then the setup mode
+    bytes.truncate(bytes.len() - 5);
+    bytes[4..6].copy_from_slice(&2u16.to_le_bytes());
+
+    let decoded = SavedPassword::from_bytes(&bytes).expect("version 2 record");
+    assert_eq!(decoded.setup_mode, None);
+    // the flag of its time
+    assert_eq!(decoded.normalization, Normalization::V1);
+}
+
+#[test]
+fn version_3_records_end_before_the_rekey_link() {
+    let mut structure = StructureSystem::new(5, 4, 17);
+    structure.set_character_set((33..127).collect());
+    let mut saved = saved(structure);
+    saved.setup_mode = Some(SetupMode::Reproducible);
+    let mut bytes = saved.to_bytes();
+    bytes.truncate(bytes.len() - 4);
+    bytes[4..6].copy_from_slice(&3u16.to_le_bytes());
+
+    let decoded = SavedPassword::from_bytes(&bytes).expect("version 3 record");
+    assert_eq!(decoded.setup_mode, Some(SetupMode::Reproducible));
+    assert_eq!(decoded.rekeyed_from, None);
+}
+
+#[test]
+fn the_normalization_byte_was_a_flag_before_version_5() {
+    let mut structure = StructureSystem::new(5, 4, 17);
+    structure.set_character_set((33..127).collect());
+    let mut saved = saved(structure);
+    let at = saved.to_bytes().len() - 6;
+
+    for normalization in [Normalization::None, Normalization::V1, Normalization::V2] {
+        saved.normalization = normalization;
+        let bytes = saved.to_bytes();
+        assert_eq!(bytes[at], normalization.to_byte());
+        let decoded = SavedPassword::from_bytes(&bytes).expect("record");
+        assert_eq!(decoded.normalization, normalization);
+
+        // version 4 had set or not: set was the v1 set
+        let mut version_4 = bytes.clone();
+        version_4[4..6].copy_from_slice(&4u16.to_le_bytes());
+        let decoded = SavedPassword::from_bytes(&version_4).expect("version 4 record");
+        let expected = match normalization {
+            Normalization::None => Normalization::None,
+            _ => Normalization::V1,
+        };
+        assert_eq!(decoded.normalization, expected);
+    }
+
+    // from version 5 on an unknown one is an error, not a flag
+    let mut unknown = saved.to_bytes();
+    unknown[at] = 3;
+    assert_eq!(
+        SavedPassword::from_bytes(&unknown).err(),
+        Some(DecodeError::Invalid("normalization"))
+    );
+}
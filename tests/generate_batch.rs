@@ -0,0 +1,120 @@
+// GENERATE runs a whole phrase through the loop the extension's keystrokes take one
+// at a time, and answers with the same bytes: its "outputs" are the replies to the
+// keystrokes, in order, and its "output" the last of them. at a domain and without
+// one, and after part of the phrase was typed key by key
+
+mod common;
+
+// the escaped JSON strings a reply holds in order, from the first after `key`
+fn strings_after(reply: &str, key: &str) -> Vec<String> {
+    let start = reply
+        .find(&format!("\"{}\":", key))
+        .unwrap_or_else(|| panic!("no {} in {}", key, reply))
+        + key.len()
+        + 3;
+    let mut strings = Vec::new();
+    let mut chars = reply[start..].chars();
+    let mut current: Option<String> = None;
+    while let Some(ch) = chars.next() {
+        match (&mut current, ch) {
+            (None, '"') => current = Some(String::new()),
+            (None, '[' | ',') => {}
+            (None, _) => break,
+            (Some(string), '\\') => {
+                string.push('\\');
+                string.push(chars.next().expect("an escaped character"));
+            }
+            (Some(_), '"') => strings.push(current.take().expect("a string")),
+            (Some(string), ch) => string.push(ch),
+        }
+    }
+    strings
+}
+
+fn activate(domain: Option<&str>) -> Vec<u8> {
+    domain
+        .map(|domain| {
+            common::frame(&format!(
+                "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\"}}",
+                domain
+            ))
+        })
+        .unwrap_or_default()
+}
+
+fn keystrokes(text: &str) -> Vec<u8> {
+    text.chars()
+        .flat_map(|ch| common::frame(&format!("{{\"charCode\":{}}}", ch as u32)))
+        .collect()
+}
+
+fn generate(text: &str) -> Vec<u8> {
+    common::frame(&format!("{{\"type\":\"GENERATE\",\"text\":\"{}\"}}", text))
+}
+
+fn reset() -> Vec<u8> {
+    common::frame("{\"type\":\"RESET\"}")
+}
+
+#[test]
+fn generate_answers_what_the_keystrokes_did() {
+    let dir = common::scratch("generate_batch");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase for typing all at once");
+
+    let phrases = ["x", "correct horse battery", "café 42 ÿ", "tabs\tand ~!@#"];
+    for domain in [None, Some("example.org")] {
+        // each phrase typed, then generated, then typed halfway and the rest generated,
+        // RESET before each
+        let mut messages = activate(domain);
+        for phrase in phrases {
+            let half = phrase.chars().count() / 2;
+            let head: String = phrase.chars().take(half).collect();
+            let tail: String = phrase.chars().skip(half).collect();
+            messages.extend(reset());
+            messages.extend(keystrokes(phrase));
+            messages.extend(reset());
+            messages.extend(generate(&phrase.replace('\t', "\\t")));
+            messages.extend(reset());
+            messages.extend(keystrokes(&head));
+            messages.extend(generate(&tail.replace('\t', "\\t")));
+        }
+        let replies = common::replies(&common::stdout(&vault, &["json-io"], &messages));
+        let mut replies = replies[usize::from(domain.is_some())..].iter();
+
+        for phrase in phrases {
+            let length = phrase.chars().count();
+            let half = length / 2;
+            let mut next = || replies.next().expect("a reply");
+
+            next();
+            let typed: Vec<String> = (0..length)
+                .map(|_| strings_after(next(), "output").remove(0))
+                .collect();
+
+            next();
+            let batch = next();
+            assert_eq!(
+                strings_after(batch, "outputs"),
+                typed,
+                "{:?} at {:?}",
+                phrase,
+                domain
+            );
+            assert_eq!(
+                strings_after(batch, "output")[0],
+                *typed.last().expect("outputs")
+            );
+
+            // where the keystrokes would have gone
+            next();
+            for _ in 0..half {
+                next();
+            }
+            assert_eq!(strings_after(next(), "outputs"), typed[half..]);
+        }
+        assert!(replies.next().is_none());
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
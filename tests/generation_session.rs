@@ -0,0 +1,135 @@
+// GenerationSession is the keystroke pipeline every mode types through. drives it
+// directly: the feedback chain, undo, composition and the PIN replay, each against
+// what the lower level functions give for the same input
+
+mod common;
+
+use void_vault::{
+    generate_keystroke, replay_keystrokes, FixedOutput, GenerationOptions, GenerationSession,
+    SavedPassword, SessionState,
+};
+
+const PLAIN: GenerationOptions = GenerationOptions {
+    compose_marks: false,
+};
+
+fn config() -> SavedPassword {
+    let structure = common::structure_for(
+        "a phrase for the generation session",
+        2024,
+        6,
+        &common::printable(),
+    );
+    common::saved("session", structure, 1)
+}
+
+fn typed(
+    generation: &mut GenerationSession,
+    session: &SessionState,
+    config: &mut SavedPassword,
+    text: &str,
+) -> Vec<String> {
+    text.chars()
+        .map(|ch| generation.step(session, config, ch as u32).to_string())
+        .collect()
+}
+
+#[test]
+fn steps_chain_like_generate_keystroke() {
+    let session = SessionState::empty();
+    let mut config = config();
+    let mut generation = GenerationSession::new(PLAIN);
+    assert!(generation.is_fresh());
+    let outputs = typed(&mut generation, &session, &mut config, "hunter2");
+    assert!(!generation.is_fresh());
+    assert_eq!(generation.typed().len(), 7);
+
+    config.structure_system.full_reset();
+    let mut feedbacks = Vec::new();
+    let expected: Vec<String> = "hunter2"
+        .chars()
+        .map(|ch| generate_keystroke(&mut config, &mut feedbacks, ch as u32).to_string())
+        .collect();
+    assert_eq!(outputs, expected);
+}
+
+#[test]
+fn undo_is_the_phrase_one_shorter() {
+    let session = SessionState::empty();
+    let mut config = config();
+    let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    let outputs = typed(&mut generation, &session, &mut config, "correct");
+
+    config.structure_system.full_reset();
+    let undone = generation.undo(&session, &mut config);
+    assert_eq!(undone.as_str(), outputs[5]);
+    assert_eq!(generation.typed().len(), 6);
+
+    // and typing on continues from there as if the last key never was
+    let next = generation.step(&session, &mut config, 'X' as u32);
+    config.structure_system.full_reset();
+    let mut fresh = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    assert_eq!(
+        typed(&mut fresh, &session, &mut config, "correcX")[6],
+        next.as_str()
+    );
+}
+
+#[test]
+fn marks_compose_only_when_asked() {
+    let session = SessionState::empty();
+    let mut config = config();
+
+    let mut composing = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    let decomposed = typed(&mut composing, &session, &mut config, "cafe\u{301}");
+    assert_eq!(composing.typed(), ['c', 'a', 'f', 'é'].map(|c| c as u32));
+
+    config.structure_system.full_reset();
+    let mut precomposed = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    let expected = typed(&mut precomposed, &session, &mut config, "café");
+    assert_eq!(decomposed.last(), expected.last());
+
+    config.structure_system.full_reset();
+    let mut plain = GenerationSession::new(PLAIN);
+    typed(&mut plain, &session, &mut config, "cafe\u{301}");
+    assert_eq!(plain.typed().len(), 5);
+}
+
+#[test]
+fn a_pin_domain_replays_the_digits_so_far() {
+    let mut session = SessionState::empty();
+    session.fixed_output = FixedOutput::Pin(4);
+    let mut config = config();
+    let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+
+    let pins = typed(&mut generation, &session, &mut config, "abcdefgh");
+    for (typed_count, pin) in pins.iter().enumerate() {
+        assert!(pin.chars().all(|c| c.is_ascii_digit()), "{}", pin);
+        assert!(pin.len() <= (typed_count + 1).min(4));
+    }
+    assert_eq!(pins.last().unwrap().len(), 4);
+
+    let mut feedbacks = Vec::new();
+    let keycodes: Vec<u32> = "abcdefgh".chars().map(|c| c as u32).collect();
+    let replayed = replay_keystrokes(&session, &mut config, &mut feedbacks, &keycodes);
+    assert_eq!(replayed.as_str(), pins[7]);
+}
+
+#[test]
+fn retype_puts_back_an_earlier_state() {
+    let session = SessionState::empty();
+    let mut config = config();
+    let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+    let outputs = typed(&mut generation, &session, &mut config, "abc");
+    let before = generation.typed().to_vec();
+
+    generation.step(&session, &mut config, 'z' as u32);
+    config.structure_system.full_reset();
+    let restored = generation.retype(&session, &mut config, &before);
+    assert_eq!(restored.as_str(), outputs[2]);
+    assert_eq!(generation.typed(), before);
+
+    generation.clear();
+    assert!(generation.is_fresh());
+    assert!(generation.typed().is_empty());
+}
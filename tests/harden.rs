@@ -0,0 +1,113 @@
+// harden::apply is what the binary runs before anything else. asks the system
+// afterwards whether the core limit and, on Linux, the dumpable flag really changed.
+// whether memory got locked depends on the lock limit of the machine running this,
+// and when it didn't, only setup, doctor and --verbose say so
+#![cfg(unix)]
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use void_vault::harden;
+
+#[repr(C)]
+struct Rlimit {
+    rlim_cur: u64,
+    rlim_max: u64,
+}
+
+extern "C" {
+    fn getrlimit(resource: i32, rlim: *mut Rlimit) -> i32;
+}
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn prctl(option: i32, ...) -> i32;
+}
+
+const RLIMIT_CORE: i32 = 4;
+
+#[test]
+fn core_dumps_and_tracing_are_off() {
+    let hardening = harden::apply();
+    assert!(hardening.no_core_dumps);
+
+    let mut core = Rlimit {
+        rlim_cur: 1,
+        rlim_max: 1,
+    };
+    assert_eq!(unsafe { getrlimit(RLIMIT_CORE, &mut core) }, 0);
+    assert_eq!((core.rlim_cur, core.rlim_max), (0, 0));
+
+    #[cfg(target_os = "linux")]
+    {
+        const PR_GET_DUMPABLE: i32 = 3;
+        assert!(hardening.not_dumpable);
+        assert_eq!(unsafe { prctl(PR_GET_DUMPABLE, 0u64, 0u64, 0u64, 0u64) }, 0);
+    }
+
+    // and applying it again changes nothing
+    assert_eq!(harden::apply(), hardening);
+    assert!(harden::memlock_limit().is_some());
+}
+
+// stdout and stderr of the vault run with `input`
+fn output(vault: &Path, args: &[&str], input: &[u8]) -> (String, String) {
+    let mut child = Command::new(vault)
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run the vault");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(input)
+        .expect("type");
+    let output = child.wait_with_output().expect("wait for the vault");
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn a_low_lock_limit_is_only_mentioned_when_asked() {
+    const WARNING: &str = "Memory could not be locked";
+    let dir = common::scratch("harden");
+    let vault = common::vault_in(&dir);
+
+    let (_, setup) = output(
+        &vault,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        b"\na phrase for a vault that warns once\x1b",
+    );
+    let (_, json_io) = output(&vault, &["json-io"], &common::frame("{\"type\":\"PING\"}"));
+    let (_, accounts) = output(&vault, &["accounts"], b"");
+    let (_, verbose) = output(&vault, &["--verbose", "accounts"], b"");
+    let (doctor, _) = output(&vault, &["doctor"], b"");
+    let (doctor_json, _) = output(&vault, &["--json", "doctor"], b"");
+
+    // the browser host starts with every browser, and a command run every day
+    assert!(!json_io.contains(WARNING), "{}", json_io);
+    assert!(!accounts.contains(WARNING), "{}", accounts);
+
+    let locked = doctor_json.contains("\"memory_locked\":true");
+    assert!(locked || doctor_json.contains("\"memory_locked\":false"));
+    for (what, said) in [("setup", setup), ("--verbose", verbose), ("doctor", doctor)] {
+        assert_eq!(
+            said.matches(WARNING).count(),
+            usize::from(!locked),
+            "{}: {}",
+            what,
+            said
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
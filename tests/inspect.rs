@@ -0,0 +1,59 @@
+// inspect reports the shape of a configuration and nothing it generates from. the
+// JSON is what browsers and scripts read, so its shape is pinned here whole
+
+mod common;
+
+use void_vault::{Inspection, SavedPassword, SetupMode, FORMAT_VERSION};
+
+fn saved() -> SavedPassword {
+    let pool: Vec<u32> = ('a'..='z')
+        .chain(['é', 'ж', '→', '𝄞'])
+        .map(|c| c as u32)
+        .collect();
+    let structure_system = common::structure_for("", 424_242, 5, &pool);
+    let mut saved = common::saved("work \"main\"", structure_system, 2);
+    saved.description = "not reported".to_string();
+    saved.created_date = 1_700_000_000;
+    saved.use_count = 9;
+    saved.setup_mode = Some(SetupMode::Timed);
+    saved
+}
+
+#[test]
+fn the_json_shape_is_stable() {
+    let config = saved();
+    let bytes = config.to_bytes();
+    let format_version = SavedPassword::format_version(&bytes).expect("versioned record");
+    assert_eq!(format_version, FORMAT_VERSION);
+
+    let inspection = Inspection::of(&config, format_version);
+    assert_eq!(
+        inspection.to_json(),
+        concat!(
+            r#"{"name":"work \"main\"","created_date":1700000000,"format_version":5,"setup_mode":"timed","#,
+            r#""normalization":"v2","#,
+            r#""dimensions":5,"coordinate_range":17,"#,
+            r#""character_set":{"pool":"custom","length":30,"ranges":{"ascii":26,"latin":1,"#,
+            r#""other_scripts":1,"symbols":1,"cjk_and_other_bmp":0,"supplementary":1}},"#,
+            r#""active_points":331,"#,
+            r#""structure_bounds":{"min":[-29,-30,-27,-30,-29],"max":[29,31,31,27,29]},"#,
+            r#""base_step_size":3,"step_variance":2,"extra_chars":2}"#
+        )
+    );
+}
+
+#[test]
+fn ranges_cover_the_whole_character_set() {
+    let inspection = Inspection::of(&saved(), FORMAT_VERSION);
+    let counted: usize = inspection.code_point_ranges.iter().map(|(_, n)| n).sum();
+    assert_eq!(counted, inspection.character_set);
+    assert_eq!(
+        inspection.code_point_ranges.len(),
+        Inspection::CODE_POINT_RANGES.len()
+    );
+}
+
+#[test]
+fn a_record_from_before_versioning_is_version_0() {
+    assert_eq!(SavedPassword::format_version(&[6, 0, 0, 0]).ok(), Some(0));
+}
@@ -0,0 +1,81 @@
+// the lock a writer waits on says who holds it: the browser host by name and pid, a
+// void_vault command as that, and a lock file from a build that only wrote its pid
+// as another Void Vault process
+#![cfg(unix)]
+
+mod common;
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use void_vault::InstanceLock;
+
+#[test]
+fn a_busy_vault_names_its_holder() {
+    let dir = common::scratch("instance_lock");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase for a vault two processes want");
+    let lock_file = vault.with_extension("lock");
+
+    // held by this process, which never named itself
+    let lock = InstanceLock::acquire(&vault, Duration::ZERO).expect("the lock");
+    let busy = InstanceLock::acquire(&vault, Duration::ZERO)
+        .err()
+        .expect("busy");
+    assert_eq!(
+        busy.to_string(),
+        format!(
+            "Vault is in use by a void_vault command (pid {})",
+            std::process::id()
+        )
+    );
+
+    // an older holder's lock file
+    std::fs::write(&lock_file, "4242").expect("write the lock file");
+    let busy = InstanceLock::acquire(&vault, Duration::ZERO)
+        .err()
+        .expect("busy");
+    assert_eq!(
+        busy.to_string(),
+        "Vault is in use by another Void Vault process (pid 4242)"
+    );
+    drop(lock);
+
+    // held by json-io for as long as its input is open
+    let mut host = Command::new(&vault)
+        .arg("json-io")
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("start json-io");
+    let started = Instant::now();
+    let holder = format!("{} the browser host", host.id());
+    while std::fs::read_to_string(&lock_file).ok().as_deref() != Some(holder.as_str()) {
+        assert!(started.elapsed() < Duration::from_secs(20), "no lock");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let output = Command::new(&vault)
+        .args(["domains", "set", "example.com", "1"])
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::null())
+        .output()
+        .expect("run the vault");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "Vault is in use by the browser host (pid {})",
+            host.id()
+        )),
+        "{}",
+        stderr
+    );
+
+    drop(host.stdin.take());
+    assert!(host.wait().expect("wait for json-io").success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
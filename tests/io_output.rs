@@ -0,0 +1,53 @@
+// io prints what the extension shows once the phrase is typed: the output of the
+// last keystroke, which every keystroke generates whole. checked against a json-io
+// session typing the same phrases, at a domain and without one
+
+mod common;
+
+use void_vault::escape_json;
+
+#[test]
+fn io_prints_what_the_extension_shows() {
+    let dir = common::scratch("io_output");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase to compare io with the extension");
+
+    for phrase in [
+        "x",
+        "correct horse",
+        "a longer phrase, with punctuation!",
+        "café 42",
+    ] {
+        for domain in [None, Some("example.org")] {
+            let mut args = vec!["io"];
+            let mut messages = Vec::new();
+            if let Some(domain) = domain {
+                args.extend(["--domain", domain]);
+                messages.extend(common::frame(&format!(
+                    "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\"}}",
+                    domain
+                )));
+            }
+            for ch in phrase.chars() {
+                messages.extend(common::frame(&format!("{{\"charCode\":{}}}", ch as u32)));
+            }
+
+            let io = common::stdout(&vault, &args, format!("{}\n", phrase).as_bytes());
+            let io = String::from_utf8(io).expect("UTF-8 output");
+            let io = io.trim_end();
+            assert!(io.chars().count() > 1, "{:?}", phrase);
+
+            let json_io = common::replies(&common::stdout(&vault, &["json-io"], &messages));
+            let typed = json_io.last().expect("a reply to the last keystroke");
+            assert!(
+                typed.contains(&format!("\"output\":\"{}\"", escape_json(io))),
+                "{:?} at {:?}: {}",
+                phrase,
+                domain,
+                typed
+            );
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
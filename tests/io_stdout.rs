@@ -0,0 +1,73 @@
+// io piped into another program: a vault holding a configuration that doesn't load
+// warns about it, and --verbose adds load details and the strength estimate, all on
+// stderr. stdout is the password alone, the same as a clean vault's
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use void_vault::{BinaryStorageManager, VaultStorage};
+
+const PHRASE: &str = "a phrase for a vault with a broken neighbour";
+
+// stdout and stderr of io typing `typed`
+fn io(vault: &Path, args: &[&str], typed: &str) -> (String, String) {
+    let mut child = Command::new(vault)
+        .arg("io")
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run io");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(format!("{}\n", typed).as_bytes())
+        .expect("type");
+    let output = child.wait_with_output().expect("wait for io");
+    assert!(output.status.success(), "{:?}", args);
+    (
+        String::from_utf8(output.stdout).expect("UTF-8 output"),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn warnings_stay_off_stdout() {
+    let dir = common::scratch("io_stdout");
+    let [clean, warning] = ["clean", "warning"].map(|name| {
+        std::fs::create_dir_all(dir.join(name)).expect("create dir");
+        common::vault_in(&dir.join(name))
+    });
+    common::setup(&clean, PHRASE);
+    common::setup(&warning, PHRASE);
+
+    // a second configuration that is only noise
+    let mut storage =
+        BinaryStorageManager::open(warning.clone(), false, None).expect("open the vault");
+    storage
+        .store("broken".to_string(), String::new(), &[0xA5; 97])
+        .expect("store the noise");
+    drop(storage);
+
+    for args in [
+        &["--account", "main"][..],
+        &["--account", "main", "--domain", "example.org"],
+        &["--verbose", "--account", "main", "--domain", "example.org"],
+    ] {
+        let (expected, _) = io(&clean, args, "correct horse");
+        let (stdout, stderr) = io(&warning, args, "correct horse");
+        assert!(stderr.contains("'broken'"), "{:?}: {}", args, stderr);
+
+        assert_eq!(stdout, expected, "{:?}", args);
+        assert!(stdout.chars().count() > 8);
+        assert!(!stdout.contains(char::is_control), "{:?}", stdout);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
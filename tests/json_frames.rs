@@ -0,0 +1,69 @@
+// json-io's framing against what a broken or hostile browser side could send: an
+// empty frame gets an error and the session goes on, a length past the native
+// messaging limit (0xFFFFFFFF among them) gets an error without anything being
+// allocated for it and ends the session, and a stream that ends inside a frame
+// ends it quietly. the process exits cleanly each time
+
+mod common;
+
+use std::path::Path;
+
+const PING: &str = "{\"type\":\"PING\"}";
+const PONG: &str = "{\"id\":null,\"status\":\"pong\"}";
+const LIMIT: u32 = 1024 * 1024;
+
+fn json_io(vault: &Path, input: &[u8]) -> Vec<String> {
+    let output = common::run(vault, &["json-io"], input);
+    assert!(output.status.success(), "{:?}", output.status);
+    common::replies(&output.stdout)
+}
+
+fn too_large() -> String {
+    format!(
+        "{{\"id\":null,\"error\":{{\"code\":\"message_too_large\",\"message\":\"Message exceeds the native messaging limit\"}},\"limit\":{}}}",
+        LIMIT
+    )
+}
+
+#[test]
+fn bad_frames_are_answered_or_end_the_session() {
+    let dir = common::scratch("json_frames");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase behind a careful reader");
+
+    // empty, then a PING that is still answered
+    let mut input = 0u32.to_ne_bytes().to_vec();
+    input.extend(common::frame(PING));
+    assert_eq!(
+        json_io(&vault, &input),
+        [
+            "{\"id\":null,\"error\":{\"code\":\"bad_request\",\"message\":\"Empty message\"}}",
+            PONG
+        ]
+    );
+
+    // too large, by a lot or by one byte: what follows isn't read
+    for length in [u32::MAX, LIMIT + 1] {
+        let mut input = length.to_ne_bytes().to_vec();
+        input.extend(common::frame(PING));
+        assert_eq!(json_io(&vault, &input), [too_large()], "{}", length);
+    }
+
+    // a frame right at the limit is read, the padding is only whitespace
+    let at_limit = PING.to_string() + &" ".repeat(LIMIT as usize - PING.len());
+    let mut input = common::frame(&at_limit);
+    input.extend(common::frame(PING));
+    assert_eq!(json_io(&vault, &input), [PONG, PONG]);
+
+    // the stream ends in a frame's body, or in its length
+    let mut truncated_body = common::frame(PING);
+    truncated_body.extend(100u32.to_ne_bytes());
+    truncated_body.extend(b"{\"type\":");
+    let mut truncated_length = common::frame(PING);
+    truncated_length.extend([5, 0]);
+    for input in [truncated_body, truncated_length] {
+        assert_eq!(json_io(&vault, &input), [PONG]);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
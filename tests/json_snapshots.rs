@@ -0,0 +1,106 @@
+// --json output of accounts, domains and verify-determinism, pinned byte for byte on a
+// reproducible vault. scripts and front-ends parse these, so a change to a shape has
+// to be a change to this file too. errors go to stderr as one object, with the exit
+// code of their kind
+
+mod common;
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// the exit code, stdout and the last line of stderr of a --json run
+fn json(vault: &Path, args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(vault)
+        .arg("--json")
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::null())
+        .output()
+        .expect("run the vault");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    (
+        output.status.code().expect("an exit code"),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr.lines().last().unwrap_or_default().to_string(),
+    )
+}
+
+fn snapshot(vault: &Path, args: &[&str], expected: &str) {
+    let (code, stdout, _) = json(vault, args);
+    assert_eq!(code, 0, "{:?}", args);
+    assert_eq!(stdout, format!("{}\n", expected), "{:?}", args);
+}
+
+#[test]
+fn json_output_keeps_its_shape() {
+    let dir = common::scratch("json_snapshots");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase to snapshot");
+
+    snapshot(
+        &vault,
+        &["accounts"],
+        "{\"configurations\":[{\"name\":\"main\",\"description\":\"Primary configuration\",\"created\":0,\"extra_chars\":7,\"pool\":\"full\",\"use_count\":0,\"rekeyed_from\":null}],\"total\":1}",
+    );
+
+    snapshot(&vault, &["domains"], "{\"domains\":[],\"total\":0}");
+    snapshot(
+        &vault,
+        &["domains", "set", "example.com", "3"],
+        "{\"domain\":\"example.com\",\"counter\":3,\"status\":\"success\"}",
+    );
+    snapshot(
+        &vault,
+        &["domains", "increment", "example.com"],
+        "{\"domain\":\"example.com\",\"counter\":4,\"status\":\"success\"}",
+    );
+    snapshot(
+        &vault,
+        &["domains", "commit", "example.com"],
+        "{\"domain\":\"example.com\",\"counter\":5,\"status\":\"committed\"}",
+    );
+    snapshot(
+        &vault,
+        &["domains", "rules", "example.org", "20", "7", "--no-confusables", "on"],
+        "{\"domain\":\"example.org\",\"max_length\":20,\"char_types\":7,\"no_confusables\":true,\"starts_with_letter\":false,\"no_leading_symbol\":false,\"no_trailing_symbol\":false,\"status\":\"success\"}",
+    );
+    snapshot(
+        &vault,
+        &["domains", "get", "example.org"],
+        "{\"domain\":\"example.org\",\"counter\":0,\"max_length\":20,\"char_types\":7,\"no_confusables\":true,\"starts_with_letter\":false,\"no_leading_symbol\":false,\"no_trailing_symbol\":false,\"pin_length\":0,\"word_count\":0}",
+    );
+    snapshot(
+        &vault,
+        &["domains"],
+        "{\"domains\":[\
+         {\"slot\":1,\"hash_prefix\":\"c13cb1db7b20ea5cd1419ef36adbdd47\",\"counter\":5,\"max_length\":0,\"char_types\":127,\"username_digits\":false,\"no_confusables\":false,\"starts_with_letter\":false,\"no_leading_symbol\":false,\"no_trailing_symbol\":false,\"pin_length\":0,\"word_count\":0,\"lineage\":0},\
+         {\"slot\":2,\"hash_prefix\":\"7eb168912546b7c564ec25d297cdac27\",\"counter\":0,\"max_length\":20,\"char_types\":7,\"username_digits\":false,\"no_confusables\":true,\"starts_with_letter\":false,\"no_leading_symbol\":false,\"no_trailing_symbol\":false,\"pin_length\":0,\"word_count\":0,\"lineage\":0}\
+         ],\"total\":2}",
+    );
+
+    snapshot(
+        &vault,
+        &["verify-determinism"],
+        "{\"vectors\":[\
+         {\"name\":\"f64 motion\",\"expected\":\"a4d5dccb32af453f\",\"actual\":\"a4d5dccb32af453f\",\"ok\":true},\
+         {\"name\":\"fixed-point motion\",\"expected\":\"ba8cdacb18c4bcbc\",\"actual\":\"ba8cdacb18c4bcbc\",\"ok\":true},\
+         {\"name\":\"fixed-point motion, transform v2\",\"expected\":\"921105905b828ad6\",\"actual\":\"921105905b828ad6\",\"ok\":true},\
+         {\"name\":\"fixed-point motion, transform v3\",\"expected\":\"35d847b2ffea39c3\",\"actual\":\"35d847b2ffea39c3\",\"ok\":true},\
+         {\"name\":\"fixed-point motion, transform v4\",\"expected\":\"eab447e3aa34ce2e\",\"actual\":\"eab447e3aa34ce2e\",\"ok\":true},\
+         {\"name\":\"fixed-point motion, transform v5\",\"expected\":\"d33c5081794a04e4\",\"actual\":\"d33c5081794a04e4\",\"ok\":true},\
+         {\"name\":\"fixed-point motion, transform v6\",\"expected\":\"c251bbf2a6b6511a\",\"actual\":\"c251bbf2a6b6511a\",\"ok\":true},\
+         {\"name\":\"fixed-point motion, transform v7\",\"expected\":\"c251bbf2a6b6511a\",\"actual\":\"c251bbf2a6b6511a\",\"ok\":true}\
+         ],\"fingerprint\":{\"account\":\"main\",\"status\":\"ok\"}}",
+    );
+
+    // an error is an object on stderr, nothing on stdout
+    let (code, stdout, stderr) = json(&vault, &["domains", "get", "nothing.example"]);
+    assert_eq!(code, 4);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        "{\"error\":{\"code\":\"unknown_domain\",\"message\":\"nothing.example is not registered\"}}"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,87 @@
+// under Transform::V5 a keystroke walks the same trail wherever it falls in the
+// phrase, so neither the output nor the time it takes tells how much came before.
+// the timing is each position's fastest of several runs, which keeps a busy
+// machine's scheduling out of the spread
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use void_vault::{GenerationOptions, GenerationSession, SavedPassword, SessionState, Transform};
+
+const PHRASE: &str = "a sixty-four character phrase typed one keystroke at a time, ok?";
+
+fn config(transform: Transform) -> SavedPassword {
+    let mut structure = common::structure_for(
+        "a phrase for the timing structure",
+        64,
+        7,
+        &common::printable(),
+    );
+    structure.transform = transform;
+    common::saved("timing", structure, 7)
+}
+
+// (output length, fastest time) for each keystroke of PHRASE
+fn keystrokes(transform: Transform, runs: usize) -> Vec<(usize, Duration)> {
+    let session = SessionState::empty();
+    let mut config = config(transform);
+    let mut fastest = vec![(0, Duration::MAX); PHRASE.chars().count()];
+
+    for _ in 0..runs {
+        config.structure_system.full_reset();
+        let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+        for (ch, (length, time)) in PHRASE.chars().zip(fastest.iter_mut()) {
+            let started = Instant::now();
+            let output = generation.step(&session, &mut config, ch as u32);
+            *time = (*time).min(started.elapsed());
+            *length = output.chars().count();
+        }
+    }
+    fastest
+}
+
+#[test]
+fn every_keystroke_walks_the_same_trail() {
+    assert_eq!(PHRASE.chars().count(), 64);
+    let lengths: Vec<usize> = keystrokes(Transform::V5, 1)
+        .into_iter()
+        .map(|(length, _)| length)
+        .collect();
+    assert!(lengths
+        .iter()
+        .all(|&length| length == (Transform::TRAIL + 1) * 8));
+
+    // where before, each keystroke walked once more than the last
+    let grown: Vec<usize> = keystrokes(Transform::V4, 1)
+        .into_iter()
+        .map(|(length, _)| length)
+        .collect();
+    assert_eq!(grown[0], 8);
+    assert_eq!(grown[63], 64 * 8);
+}
+
+#[test]
+fn keystroke_time_stays_flat() {
+    let times: Vec<f64> = keystrokes(Transform::V5, 7)
+        .into_iter()
+        .map(|(_, time)| time.as_secs_f64())
+        .collect();
+
+    let mean = times.iter().sum::<f64>() / times.len() as f64;
+    let variance = times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / times.len() as f64;
+    let spread = variance.sqrt() / mean;
+    assert!(
+        spread < 0.5,
+        "keystroke times spread {:.2} of their mean",
+        spread
+    );
+
+    let first = times[..16].iter().sum::<f64>();
+    let last = times[48..].iter().sum::<f64>();
+    assert!(
+        last < first * 1.5,
+        "the last 16 keystrokes took {:.2}x the first 16",
+        last / first
+    );
+}
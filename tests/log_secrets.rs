@@ -0,0 +1,199 @@
+// a whole session logged at debug level, to stderr and to VOID_VAULT_LOG_FILE: setup,
+// then a domain activated, its counter set, read and committed, a phrase typed with a
+// backspace and generated. no log line in either capture holds the domain, either
+// phrase, the counter, the domain's hash or any output
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use void_vault::{BinaryStorageManager, SavedPassword, VaultStorage};
+
+const SETUP_PHRASE: &str = "a quokka set this up in zanzibar";
+const DOMAIN: &str = "marmalade-lighthouse.example";
+const PHRASE: &str = "xylograph pemmican";
+const COUNTER: u16 = 4817;
+
+// the vault run logging at debug level into `log_file`: its stdout and its stderr
+fn logged(vault: &Path, log_file: &Path, args: &[&str], input: &[u8]) -> (Vec<u8>, String) {
+    let mut child = Command::new(vault)
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .env("VOID_VAULT_LOG", "debug")
+        .env("VOID_VAULT_LOG_FILE", log_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run the vault");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(input)
+        .expect("type");
+    let output = child.wait_with_output().expect("wait for the vault");
+    assert!(output.status.success(), "{:?}", args);
+    (
+        output.stdout,
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+// what a line says, without its timestamp and pid, and with the microseconds a reply
+// took left out, so a number found in it was put there by the message
+fn message_of(line: &str) -> String {
+    let message = line.split_once("]: ").map_or(line, |(_, message)| message);
+    match message.find(" after ") {
+        Some(at) if message.ends_with("us") => message[..at].to_string(),
+        _ => message.to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn a_debug_log_of_a_session_holds_no_secrets() {
+    let dir = common::scratch("log_secrets");
+    let vault = common::vault_in(&dir);
+    let log_file = dir.join("void_vault.log");
+
+    let (_, setup_stderr) = logged(
+        &vault,
+        &log_file,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        format!("\n{}\x1b", SETUP_PHRASE).as_bytes(),
+    );
+
+    let mut messages = Vec::new();
+    let mut send = |message: String| messages.extend(common::frame(&message));
+    send(format!(
+        "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\"}}",
+        DOMAIN
+    ));
+    send(format!(
+        "{{\"type\":\"SET_COUNTER\",\"domain\":\"{}\",\"counter\":{}}}",
+        DOMAIN, COUNTER
+    ));
+    send(format!(
+        "{{\"type\":\"GET_COUNTER\",\"domain\":\"{}\"}}",
+        DOMAIN
+    ));
+    send(format!(
+        "{{\"type\":\"ACTIVATE\",\"domain\":\"{}\"}}",
+        DOMAIN
+    ));
+    for ch in PHRASE.chars() {
+        send(format!("{{\"charCode\":{}}}", ch as u32));
+    }
+    send("{\"type\":\"UNDO_CHAR\"}".to_string());
+    send(format!(
+        "{{\"charCode\":{}}}",
+        PHRASE.chars().last().unwrap() as u32
+    ));
+    send("{\"type\":\"RESET\"}".to_string());
+    send(format!("{{\"type\":\"GENERATE\",\"text\":\"{}\"}}", PHRASE));
+    send(format!(
+        "{{\"type\":\"ACTIVATE_PREVIEW\",\"domain\":\"{}\"}}",
+        DOMAIN
+    ));
+    for ch in PHRASE.chars() {
+        send(format!("{{\"charCode\":{}}}", ch as u32));
+    }
+    send(format!(
+        "{{\"type\":\"COMMIT_INCREMENT\",\"domain\":\"{}\"}}",
+        DOMAIN
+    ));
+    send("{\"type\":\"FINALIZE\"}".to_string());
+    let (stdout, session_stderr) = logged(&vault, &log_file, &["json-io"], &messages);
+
+    let replies = common::replies(&stdout);
+    assert!(replies
+        .iter()
+        .any(|reply| reply.contains(&format!("\"counter\":{}", COUNTER))));
+    assert!(replies.iter().any(|reply| reply.contains("committed")));
+
+    // the outputs, from every reply that has one
+    let outputs: Vec<String> = replies
+        .iter()
+        .flat_map(|reply| reply.split("\"output").skip(1))
+        .filter_map(|rest| {
+            let rest = rest.split_once(":\"")?.1;
+            Some(rest[..rest.find('"')?].to_string())
+        })
+        .filter(|output| output.chars().count() >= 8)
+        .collect();
+    assert!(!outputs.is_empty());
+
+    // the domain's hash, under the vault's own structure
+    let storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+    let (bytes, _) = storage
+        .retrieve("main")
+        .expect("retrieve")
+        .expect("a configuration");
+    let mut saved = SavedPassword::from_bytes(bytes).expect("decode");
+    let domain_hash = saved.structure_system.hash_domain(DOMAIN);
+
+    let mut secrets: Vec<String> = vec![
+        SETUP_PHRASE.to_string(),
+        DOMAIN.to_string(),
+        "marmalade".to_string(),
+        PHRASE.to_string(),
+        "xylograph".to_string(),
+        "pemmican".to_string(),
+        hex(&domain_hash[..8]),
+        hex(&domain_hash[..8]).to_uppercase(),
+    ];
+    secrets.extend(
+        SETUP_PHRASE
+            .split(' ')
+            .filter(|word| word.len() > 4)
+            .map(String::from),
+    );
+    secrets.extend(outputs);
+
+    let file = std::fs::read_to_string(&log_file).expect("the log file");
+    for (name, capture) in [
+        ("stderr of setup", setup_stderr.as_str()),
+        ("stderr of json-io", session_stderr.as_str()),
+        ("log file", file.as_str()),
+    ] {
+        assert!(
+            capture.contains("DEBUG void_vault[") || name == "stderr of setup",
+            "{} isn't a debug log:\n{}",
+            name,
+            capture
+        );
+        for line in capture.lines().filter(|line| line.contains(" void_vault[")) {
+            let message = message_of(line);
+            for secret in &secrets {
+                assert!(
+                    !message.contains(secret.as_str()),
+                    "{} has {:?} in {:?}",
+                    name,
+                    secret,
+                    line
+                );
+            }
+            for counter in [COUNTER, COUNTER + 1] {
+                assert!(
+                    !message
+                        .split(|ch: char| !ch.is_ascii_digit())
+                        .any(|number| number == counter.to_string()),
+                    "{} has the counter in {:?}",
+                    name,
+                    line
+                );
+            }
+        }
+    }
+    // the file got the session's lines too
+    assert!(file.contains("json-io: received COMMIT_INCREMENT"));
+    assert!(file.contains("json-io: received keystroke"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,114 @@
+// without a command the binary is the browser's json-io host only when the browser's
+// own arguments come with a pipe: chrome-extension://... from Chrome, the manifest's
+// path and the extension's id from Firefox. A pipe or a file alone is a phrase, read
+// the way io reads it
+
+mod common;
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+fn run(vault: &Path, args: &[&str], stdin: Stdio, input: Option<&[u8]>) -> Output {
+    let mut child = Command::new(vault)
+        .args(args)
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("run the vault");
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .expect("stdin")
+            .write_all(input)
+            .expect("write stdin");
+    }
+    child.wait_with_output().expect("wait for the vault")
+}
+
+fn piped(vault: &Path, args: &[&str], input: &[u8]) -> Output {
+    run(vault, args, Stdio::piped(), Some(input))
+}
+
+// whether stdout is json-io's framed answer to VERSION
+fn answered_version(stdout: &[u8]) -> bool {
+    stdout.len() > 4
+        && u32::from_ne_bytes([stdout[0], stdout[1], stdout[2], stdout[3]]) as usize
+            <= stdout.len() - 4
+        && String::from_utf8_lossy(&stdout[4..]).contains("\"supports_self_test\"")
+}
+
+#[test]
+fn only_the_browsers_arguments_start_json_io() {
+    let dir = common::scratch("native_messaging");
+    let vault = common::vault_in(&dir);
+    let setup = piped(
+        &vault,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        b"\na phrase to ask which mode the binary is in\x1b",
+    );
+    assert!(setup.status.success());
+
+    let io = piped(&vault, &["io"], b"a phrase from a script\n");
+    assert!(io.status.success());
+    assert!(!io.stdout.is_empty());
+
+    // a pipe alone, and a file, are io's phrase
+    let pipe = piped(&vault, &[], b"a phrase from a script\n");
+    assert!(pipe.status.success());
+    assert_eq!(pipe.stdout, io.stdout);
+
+    let phrase = dir.join("phrase.txt");
+    std::fs::write(&phrase, b"a phrase from a script\n").expect("write the phrase");
+    let file = run(
+        &vault,
+        &[],
+        Stdio::from(File::open(&phrase).expect("open the phrase")),
+        None,
+    );
+    assert!(file.status.success());
+    assert_eq!(file.stdout, io.stdout);
+
+    // a phrase file that would read as a length prefix is still a phrase
+    let version = common::frame("{\"type\":\"VERSION\"}");
+    assert!(!answered_version(&piped(&vault, &[], &version).stdout));
+
+    // the browsers' arguments with their pipe
+    let chrome = piped(&vault, &["chrome-extension://abcdefghijklmnop/"], &version);
+    assert!(chrome.status.success());
+    assert!(answered_version(&chrome.stdout));
+
+    let manifest = dir.join("com.starwell.void_vault.json");
+    let firefox = piped(
+        &vault,
+        &[
+            manifest.to_str().expect("a UTF-8 path"),
+            "void_vault@starwell",
+        ],
+        &version,
+    );
+    assert!(firefox.status.success());
+    assert!(answered_version(&firefox.stdout));
+
+    // and json-io asked for by name
+    assert!(answered_version(
+        &piped(&vault, &["json-io"], &version).stdout
+    ));
+
+    // an origin without the pipe is no browser (/dev/null is a character device, as
+    // a terminal is)
+    let origin = run(
+        &vault,
+        &["chrome-extension://abcdefghijklmnop/"],
+        Stdio::null(),
+        None,
+    );
+    assert!(!origin.status.success());
+    assert!(origin.stdout.is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,161 @@
+// input normalization by version. v2 is NFC over the Latin blocks: Vietnamese and
+// pinyin, marks stacked in any order, and precomposed characters with more marks
+// typed after them all come out as NFC has them, typed whole or one keystroke at a
+// time. v1 stays the fixed set it always was, so its passwords stay the same
+
+mod common;
+
+use void_vault::{
+    nfc, GenerationOptions, GenerationSession, Normalization, SessionState, StructureSystem,
+};
+
+fn normalized(normalization: Normalization, text: &str) -> String {
+    let mut keycodes: Vec<u32> = text.chars().map(|ch| ch as u32).collect();
+    normalization.apply(&mut keycodes);
+    keycodes
+        .iter()
+        .map(|&code| char::from_u32(code).expect("a character"))
+        .collect()
+}
+
+// what `text` comes to typed one keystroke at a time
+fn typed(normalization: Normalization, text: &str) -> String {
+    let mut keycodes = Vec::new();
+    for ch in text.chars() {
+        normalization.push(&mut keycodes, ch as u32);
+    }
+    keycodes
+        .iter()
+        .map(|&code| char::from_u32(code).expect("a character"))
+        .collect()
+}
+
+// (typed, what NFC makes of it), checked against Python's unicodedata
+const NFC: [(&str, &str); 14] = [
+    // Vietnamese: ệ from its base and both marks in either order, or from a base
+    // that already has one of them
+    ("e\u{323}\u{302}", "\u{1EC7}"),
+    ("e\u{302}\u{323}", "\u{1EC7}"),
+    ("\u{EA}\u{323}", "\u{1EC7}"),
+    ("\u{1EB9}\u{302}", "\u{1EC7}"),
+    ("u\u{31B}\u{323}", "\u{1EF1}"),
+    (
+        "Tie\u{302}\u{301}ng Vie\u{323}\u{302}t",
+        "Ti\u{1EBF}ng Vi\u{1EC7}t",
+    ),
+    // pinyin
+    ("nu\u{308}\u{30C}", "n\u{1DA}"),
+    ("\u{FC}\u{304}", "\u{1D6}"),
+    ("Zho\u{304}ngwe\u{301}n", "Zh\u{14D}ngw\u{E9}n"),
+    // an ogonek and a macron, the ogonek sorted first
+    ("o\u{304}\u{328}", "\u{1ED}"),
+    ("o\u{328}\u{304}", "\u{1ED}"),
+    // a second mark of the same class stays, as does one nothing composes with
+    ("a\u{301}\u{301}", "\u{E1}\u{301}"),
+    ("A\u{308}\u{301}", "\u{C4}\u{301}"),
+    // the marks that decompose to others
+    ("A\u{341}", "\u{C1}"),
+];
+
+#[test]
+fn v2_is_nfc_for_the_latin_script() {
+    for (input, expected) in NFC {
+        assert_eq!(
+            normalized(Normalization::V2, input),
+            expected,
+            "{:?}",
+            input
+        );
+        assert_eq!(typed(Normalization::V2, input), expected, "{:?}", input);
+        // and NFC of NFC is itself
+        assert_eq!(normalized(Normalization::V2, expected), expected);
+    }
+}
+
+#[test]
+fn every_composition_comes_back_from_its_decomposition() {
+    // fully decomposed through the table itself
+    fn decompose(code: u32, out: &mut Vec<u32>) {
+        match nfc::COMPOSITIONS.iter().find(|entry| entry.2 == code) {
+            Some(&(base, mark, _)) => {
+                decompose(base, out);
+                out.push(mark);
+            }
+            None => out.push(code),
+        }
+    }
+
+    assert!(nfc::COMPOSITIONS
+        .windows(2)
+        .all(|w| (w[0].0, w[0].1) < (w[1].0, w[1].1)));
+    for &(_, _, composed) in nfc::COMPOSITIONS.iter() {
+        let mut keycodes = Vec::new();
+        decompose(composed, &mut keycodes);
+        assert!(keycodes.len() >= 2);
+
+        let mut whole = keycodes.clone();
+        Normalization::V2.apply(&mut whole);
+        assert_eq!(whole, [composed], "{:04X}", composed);
+
+        let mut pushed = Vec::new();
+        for &keycode in &keycodes {
+            Normalization::V2.push(&mut pushed, keycode);
+        }
+        assert_eq!(pushed, [composed], "{:04X}", composed);
+    }
+}
+
+#[test]
+fn marks_outside_the_block_and_other_scripts_pass_through() {
+    for text in [
+        "\u{438}\u{306}",
+        "\u{5D0}\u{5B8}",
+        "plain ascii",
+        "a\u{20DD}",
+    ] {
+        assert_eq!(normalized(Normalization::V2, text), text);
+        assert_eq!(typed(Normalization::V2, text), text);
+    }
+    assert_eq!(nfc::combining_class('a' as u32), 0);
+    assert_eq!(nfc::combining_class(0x0323), 220);
+}
+
+#[test]
+fn v1_is_the_fixed_set() {
+    assert_eq!(normalized(Normalization::V1, "e\u{301}"), "\u{E9}");
+    assert_eq!(typed(Normalization::V1, "e\u{301}"), "\u{E9}");
+    // stacked marks and Latin Extended Additional stay as typed
+    for text in ["e\u{323}\u{302}", "\u{EA}\u{323}", "\u{FC}\u{304}"] {
+        assert_eq!(normalized(Normalization::V1, text), text);
+    }
+    assert_eq!(normalized(Normalization::None, "e\u{301}"), "e\u{301}");
+}
+
+#[test]
+fn a_phrase_generates_the_same_however_its_marks_were_typed() {
+    let pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(2312, 6, 17);
+    structure.generate_structure(&[], &pool);
+    let mut config = common::saved("nfc", structure, 3);
+    config.normalization = Normalization::V2;
+
+    let mut generate = |text: &str| {
+        config.structure_system.full_reset();
+        let mut generation = GenerationSession::new(GenerationOptions::KEYSTROKES);
+        let mut output = String::new();
+        for ch in text.chars() {
+            output = generation
+                .step(&SessionState::empty(), &mut config, ch as u32)
+                .to_string();
+        }
+        output
+    };
+
+    let precomposed = generate("Ti\u{1EBF}ng Vi\u{1EC7}t");
+    assert_eq!(
+        generate("Tie\u{302}\u{301}ng Vie\u{323}\u{302}t"),
+        precomposed
+    );
+    assert_eq!(generate("Ti\u{EA}\u{301}ng Vi\u{EA}\u{323}t"), precomposed);
+    assert_ne!(generate("Tieng Viet"), precomposed);
+}
@@ -0,0 +1,37 @@
+// the strength figure json-io and io --verbose report, pinned for known outputs so
+// the formula can't drift without a test saying so: length times log2 of the pools
+// of the classes present, rounded down
+
+use void_vault::OutputEstimate;
+
+fn estimate(output: &str) -> (u32, usize) {
+    let estimate = OutputEstimate::of(output);
+    (estimate.entropy_bits(), estimate.length)
+}
+
+#[test]
+fn estimates_for_known_outputs() {
+    // nothing typed has no strength
+    assert_eq!(estimate(""), (0, 0));
+
+    // ASCII, the usual 26 + 26 + 10 + 33
+    assert_eq!(estimate("a"), (4, 1));
+    assert_eq!(estimate("aaaa"), (18, 4));
+    assert_eq!(estimate("password"), (37, 8));
+    assert_eq!(estimate("abc123"), (31, 6));
+    assert_eq!(estimate("aA1!"), (26, 4));
+    assert_eq!(estimate("Tr0ub4dor&3 xkcd"), (105, 16));
+
+    // past ASCII, by code point range
+    assert_eq!(estimate("é"), (8, 1));
+    assert_eq!(estimate("😀"), (20, 1));
+    assert_eq!(estimate("Aé😀"), (60, 3));
+    assert_eq!(estimate("a!ёשत"), (79, 5));
+}
+
+#[test]
+fn the_length_counts_characters_not_bytes() {
+    assert_eq!(OutputEstimate::of("ÿ😀").length, 2);
+    // which characters, not their order, set the pool
+    assert_eq!(estimate("a1"), estimate("1a"));
+}
@@ -0,0 +1,45 @@
+// a vault others can write is reported, and restricted to its owner on request. so
+// is a directory others can write, unless its sticky bit keeps them to their own
+#![cfg(unix)]
+
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use void_vault::perms::{self, Exposure};
+
+fn set_mode(path: &Path, mode: u32) {
+    std::fs::set_permissions(path, Permissions::from_mode(mode)).expect("chmod");
+}
+
+#[test]
+fn writable_files_and_directories_are_reported() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("perms");
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    set_mode(&dir, 0o755);
+    let vault = dir.join("vault");
+    std::fs::write(&vault, b"a vault").expect("write scratch vault");
+
+    set_mode(&vault, 0o755);
+    assert!(perms::check(&vault).is_empty());
+
+    set_mode(&vault, 0o666);
+    assert_eq!(perms::check(&vault), [Exposure::FileWritable]);
+    perms::restrict(&vault).expect("restrict");
+    let mode = std::fs::metadata(&vault)
+        .expect("stat")
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o7777, 0o700);
+    assert!(perms::check(&vault).is_empty());
+
+    set_mode(&dir, 0o777);
+    assert_eq!(perms::check(&vault), [Exposure::DirectoryWritable]);
+    set_mode(&dir, 0o1777);
+    assert!(perms::check(&vault).is_empty());
+
+    set_mode(&dir, 0o755);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,75 @@
+// the pools a configuration can draw from. full and latin stay the blocks setup
+// always took, so old recipes rebuild, while the curated presets and custom pools
+// hold nothing a password field or font might not show
+
+use void_vault::pool;
+
+#[test]
+fn presets_are_recognised_from_their_codes() {
+    for name in pool::PRESETS {
+        let codes = pool::preset(name).expect("a preset");
+        assert_eq!(pool::name_of(&codes), name);
+    }
+    assert_eq!(
+        pool::preset("ascii").expect("ascii"),
+        (32..127).collect::<Vec<u32>>()
+    );
+    // what every configuration made before pools had names drew from
+    assert_eq!(pool::preset("full").expect("full").len(), 5821);
+
+    let mut reordered = pool::preset("ascii").expect("ascii");
+    reordered.swap(0, 1);
+    assert_eq!(pool::name_of(&reordered), "custom");
+    assert!(pool::preset("cjk").is_none());
+}
+
+#[test]
+fn curated_presets_hold_only_printable_characters() {
+    for name in ["ascii", "extended", "full-unicode"] {
+        let codes = pool::preset(name).expect("a preset");
+        assert!(
+            codes.iter().all(|&code| pool::is_printable(code)),
+            "{}",
+            name
+        );
+    }
+    // the full pool takes its blocks whole, holes and all
+    let full = pool::preset("full").expect("full");
+    assert!(full.iter().any(|&code| !pool::is_printable(code)));
+    assert!(pool::preset("full-unicode").expect("full-unicode").len() < full.len());
+}
+
+#[test]
+fn printable_means_assigned_and_visible() {
+    for code in [0x20, 0x41, 0xE9, 0x416, 0x2192, 0x4E2D, 0x1F600, 0x1D11E] {
+        assert!(pool::is_printable(code), "U+{:04X}", code);
+    }
+    // a control, a soft hyphen, a combining acute, an unassigned Greek code, a
+    // no-break space, private use, a surrogate and past the last code point
+    for code in [0x7, 0xAD, 0x301, 0x378, 0xA0, 0xE000, 0xD800, 0x110000] {
+        assert!(!pool::is_printable(code), "U+{:04X}", code);
+    }
+}
+
+#[test]
+fn a_pool_file_lists_ranges() {
+    let codes = pool::parse(
+        "# lowercase, then a few arrows\n\
+         U+0061-U+007A\n\
+         0x2190 - 0x2193  # left, up, right, down\n\
+         \n\
+         61\n\
+         e9\n",
+    )
+    .expect("a valid pool");
+    assert_eq!(codes.len(), 26 + 4 + 1);
+    assert_eq!(codes[0], 'a' as u32);
+    assert_eq!(*codes.last().expect("a code"), 0xE9);
+
+    let error = pool::parse("41-5A\n370-37F\n").expect_err("U+0378 is unassigned");
+    assert!(error.to_string().contains("Line 2"), "{}", error);
+    assert!(error.to_string().contains("U+0378"), "{}", error);
+    assert!(pool::parse("5A-41").is_err());
+    assert!(pool::parse("zz").is_err());
+    assert!(pool::parse("# only a comment\n41").is_err());
+}
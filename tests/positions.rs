@@ -0,0 +1,201 @@
+// a domain's positional rules hold a password's first character to an ASCII letter,
+// or to a letter or digit, and its last, where max_length cuts, to a letter or digit.
+// only the ends move, onto characters char_types keeps, so the rules compose with the
+// cut and the types. io, json-io and GET_RULES agree on them, a PIN turns them down
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::{
+    escape_json, pool, replay_keystrokes, OutputRules, Positions, SavedPassword, SessionState,
+};
+
+fn config(pool_name: &str) -> SavedPassword {
+    let codes = pool::preset(pool_name).expect("a preset");
+    let structure = common::structure_for("a site that wants a letter first", 24_601, 7, &codes);
+    common::saved("positions", structure, 7)
+}
+
+#[test]
+fn the_ends_hold_through_the_cut_and_the_types() {
+    let keycodes: Vec<u32> = (0..400u32).map(|i| 0x21 + (i * 53) % 0x5E).collect();
+    let positions = [
+        Positions {
+            starts_with_letter: true,
+            no_leading_symbol: false,
+            no_trailing_symbol: true,
+        },
+        Positions {
+            starts_with_letter: false,
+            no_leading_symbol: true,
+            no_trailing_symbol: false,
+        },
+    ];
+    // everything, and lowercase, digits and !@#$%^&*
+    for char_types in [127, 1 | 1 << 2 | 1 << 3] {
+        for pool_name in ["ascii", "extended", "full"] {
+            for positions in positions {
+                let mut config = config(pool_name);
+                let plain_rules = OutputRules {
+                    max_length: 10,
+                    char_types,
+                    no_confusables: false,
+                    positions: Positions::NONE,
+                };
+                let rules = OutputRules {
+                    positions,
+                    ..plain_rules
+                };
+                let plain = common::outputs(&SessionState::empty(), &mut config, &keycodes);
+                let mut session = SessionState::empty();
+                session.rules = rules;
+                let held = common::outputs(&session, &mut config, &keycodes);
+
+                for (plain, held) in plain.iter().zip(&held) {
+                    let plain: Vec<char> = plain_rules.apply(plain).chars().collect();
+                    let held: Vec<char> = rules.apply(held).chars().collect();
+                    assert_eq!(plain.len(), held.len());
+                    assert!(held.len() <= 10);
+                    let last = held.len() - 1;
+
+                    assert!(positions.fits_first(held[0]), "{:?}", held);
+                    assert!(positions.fits_last(held[last]), "{:?}", held);
+                    if last > 1 {
+                        assert_eq!(plain[1..last], held[1..last]);
+                    }
+                }
+
+                // replaying gives what stepping gave
+                let mut feedbacks = Vec::new();
+                let replayed = replay_keystrokes(&session, &mut config, &mut feedbacks, &keycodes);
+                assert_eq!(replayed.as_str(), held.last().expect("outputs"));
+            }
+        }
+    }
+}
+
+#[test]
+fn moved_ends_spread_over_the_characters_that_fit() {
+    let set: Vec<u32> = "0123abcd!@#$".chars().map(|c| c as u32).collect();
+    let letter = |code: u32| char::from_u32(code).is_some_and(|c| c.is_ascii_alphabetic());
+    // by place in the set, not to the first letter after it
+    assert_eq!(pool::spread_to(&set, '0' as u32, letter), 'a' as u32);
+    assert_eq!(pool::spread_to(&set, '1' as u32, letter), 'b' as u32);
+    assert_eq!(pool::spread_to(&set, '!' as u32, letter), 'a' as u32);
+    assert_eq!(pool::spread_to(&set, '$' as u32, letter), 'd' as u32);
+    assert_eq!(pool::spread_to(&set, 'c' as u32, letter), 'c' as u32);
+
+    // nothing fits, or not in the set at all
+    assert_eq!(pool::spread_to(&set, '0' as u32, |_| false), '0' as u32);
+    assert_eq!(pool::spread_to(&set, 'z' as u32, letter), 'z' as u32);
+}
+
+fn output(vault: &Path, args: &[&str], input: &[u8]) -> String {
+    let output = common::run(vault, args, input);
+    assert!(output.status.success(), "{:?}", args);
+    String::from_utf8(output.stdout).expect("UTF-8 output")
+}
+
+#[test]
+fn every_front_end_holds_the_domains_ends() {
+    let dir = common::scratch("positions");
+    let vault = common::vault_in(&dir);
+    output(
+        &vault,
+        &["--i-know-what-im-doing", "setup", "--reproducible"],
+        b"\na phrase for holding the ends of passwords\x1b",
+    );
+    output(
+        &vault,
+        &[
+            "domains",
+            "rules",
+            "example.com",
+            "12",
+            "127",
+            "--starts-with-letter",
+            "on",
+            "--no-trailing-symbol",
+            "on",
+        ],
+        b"",
+    );
+
+    let io = output(&vault, &["io", "--domain", "example.com"], b"a phrase\n");
+    let io = io.trim_end();
+    let chars: Vec<char> = io.chars().collect();
+    assert_eq!(chars.len(), 12);
+    assert!(chars[0].is_ascii_alphabetic(), "{}", io);
+    assert!(chars[11].is_ascii_alphanumeric(), "{}", io);
+
+    // --length cuts elsewhere, and the last character is held there instead
+    let short = output(
+        &vault,
+        &["io", "--domain", "example.com", "--length", "7"],
+        b"a phrase\n",
+    );
+    let short: Vec<char> = short.trim_end().chars().collect();
+    assert_eq!(short.len(), 7);
+    assert_eq!(short[..6], chars[..6]);
+    assert!(short[6].is_ascii_alphanumeric(), "{:?}", short);
+
+    // the extension's ACTIVATE and GET_RULES report them, and its output, which the
+    // extension cuts, starts with io's
+    let resolved = "\"no_confusables\":false,\"starts_with_letter\":true,\"no_leading_symbol\":false,\"no_trailing_symbol\":true";
+    let mut messages = common::frame("{\"type\":\"GET_RULES\",\"domain\":\"example.com\"}");
+    messages.extend(common::frame(
+        "{\"type\":\"ACTIVATE\",\"domain\":\"example.com\"}",
+    ));
+    for ch in "a phrase".chars() {
+        messages.extend(common::frame(&format!("{{\"charCode\":{}}}", ch as u32)));
+    }
+    let json_io = common::replies(&common::run(&vault, &["json-io"], &messages).stdout);
+    assert!(json_io[0].contains(resolved), "{}", json_io[0]);
+    assert!(json_io[1].contains(resolved), "{}", json_io[1]);
+    let typed = json_io.last().expect("a reply to the last keystroke");
+    assert!(
+        typed.contains(&format!("\"output\":\"{}", escape_json(io))),
+        "{}",
+        typed
+    );
+
+    // a toggle not given stays, and SET_RULES sets them too
+    output(
+        &vault,
+        &["domains", "rules", "example.com", "12", "127"],
+        b"",
+    );
+    let messages = [
+        common::frame("{\"type\":\"SET_RULES\",\"domain\":\"example.com\",\"max_length\":12,\"char_types\":127,\"no_leading_symbol\":true}"),
+        common::frame("{\"type\":\"GET_RULES\",\"domain\":\"example.com\"}"),
+    ]
+    .concat();
+    let json_io = common::replies(&common::run(&vault, &["json-io"], &messages).stdout);
+    assert!(
+        json_io[1].contains(
+            "\"starts_with_letter\":true,\"no_leading_symbol\":true,\"no_trailing_symbol\":true"
+        ),
+        "{}",
+        json_io[1]
+    );
+
+    // a PIN has no letters to start with
+    output(&vault, &["domains", "pin", "bank.example", "6"], b"");
+    let pin = common::run(
+        &vault,
+        &[
+            "domains",
+            "rules",
+            "bank.example",
+            "0",
+            "127",
+            "--starts-with-letter",
+            "on",
+        ],
+        b"",
+    );
+    assert!(!pin.status.success());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,86 @@
+// the json-io host's rate limiter on a simulated clock. typing at 15 characters a
+// second, backspaces and all, never gets a message refused at the default limits,
+// however long it goes on. a page pumping messages does, and once locked out gets
+// nothing through but an ACTIVATE after the cool-down
+
+use std::time::{Duration, Instant};
+
+use void_vault::{RateDecision, RateLimiter, RateLimits};
+
+#[test]
+fn fifteen_characters_a_second_never_trip_it() {
+    let mut limiter = RateLimiter::new(RateLimits::defaults());
+    let keystroke = Duration::from_secs(1) / 15;
+    let mut now = Instant::now();
+    let mut sent = 0;
+
+    // a 40 character phrase after every ACTIVATE, a slip taken back with UNDO_CHAR
+    // every seventh character, and the next phrase right after, for a few minutes
+    for _ in 0..100 {
+        assert_eq!(limiter.check_at(now, true), RateDecision::Allowed);
+        for position in 0..40 {
+            now += keystroke;
+            assert_eq!(limiter.check_at(now, false), RateDecision::Allowed);
+            sent += 1;
+            if position % 7 == 6 {
+                for _ in 0..2 {
+                    now += keystroke;
+                    assert_eq!(limiter.check_at(now, false), RateDecision::Allowed);
+                    sent += 1;
+                }
+            }
+        }
+    }
+    assert_eq!(sent, 100 * (40 + 5 * 2));
+}
+
+#[test]
+fn a_flood_is_limited_then_locked_out() {
+    let limits = RateLimits::defaults();
+    let mut limiter = RateLimiter::new(limits);
+    let mut now = Instant::now();
+
+    let mut decisions = Vec::new();
+    for _ in 0..1000 {
+        now += Duration::from_millis(1);
+        decisions.push(limiter.check_at(now, false));
+    }
+    // the burst gets through, then the bucket runs dry
+    let allowed = decisions
+        .iter()
+        .take_while(|&&decision| decision == RateDecision::Allowed)
+        .count();
+    assert!(
+        allowed >= limits.burst as usize && allowed < 200,
+        "{}",
+        allowed
+    );
+    assert!(decisions.contains(&RateDecision::Limited));
+    let locked_out = decisions
+        .iter()
+        .position(|decision| matches!(decision, RateDecision::LockedOut { .. }))
+        .expect("a lockout");
+
+    // nothing gets through during the cool-down, ACTIVATE included
+    let RateDecision::LockedOut { retry_after } = decisions[locked_out] else {
+        unreachable!()
+    };
+    assert!(retry_after >= 1);
+    let mut now = now + Duration::from_millis(100);
+    assert!(matches!(
+        limiter.check_at(now, true),
+        RateDecision::LockedOut { .. }
+    ));
+
+    // after it, a keystroke still isn't let through, an ACTIVATE is
+    now += Duration::from_secs(600);
+    assert_eq!(
+        limiter.check_at(now, false),
+        RateDecision::LockedOut { retry_after: 0 }
+    );
+    assert_eq!(limiter.check_at(now, true), RateDecision::Allowed);
+    assert_eq!(
+        limiter.check_at(now + Duration::from_millis(100), false),
+        RateDecision::Allowed
+    );
+}
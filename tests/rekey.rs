@@ -0,0 +1,104 @@
+// a rekey clones a configuration with new output settings. domains registered with
+// the old one keep generating with it, by the lineage in their slot, until they are
+// migrated: then the counter moves on and the new one takes over
+
+mod common;
+
+use void_vault::{
+    replay_keystrokes, DomainTable, MemoryStorage, PasswordManager, SavedPassword, SessionState,
+    MAX_LINEAGE,
+};
+
+fn config() -> SavedPassword {
+    let structure = common::structure_for(
+        "the same phrase, a different output",
+        31_337,
+        7,
+        &common::printable(),
+    );
+    let mut saved = common::saved("main", structure, 2);
+    saved.created_date = 1_700_000_000;
+    saved.use_count = 4;
+    saved
+}
+
+fn output(config: &mut SavedPassword) -> String {
+    let keycodes: Vec<u32> = "hello world".chars().map(|c| c as u32).collect();
+    let mut feedbacks = Vec::new();
+    let output = replay_keystrokes(&SessionState::empty(), config, &mut feedbacks, &keycodes);
+    output.as_str().to_string()
+}
+
+#[test]
+fn a_rekey_keeps_the_setup_and_changes_the_output() {
+    let mut main = config();
+    let mut rekeyed = main.rekey("main-v2", 5, None).expect("rekey");
+    assert_eq!(rekeyed.name, "main-v2");
+    assert_eq!(rekeyed.rekeyed_from.as_deref(), Some("main"));
+    assert_eq!(rekeyed.structure_system.lineage, 1);
+    assert_eq!(
+        rekeyed.structure_system.original_seed,
+        main.structure_system.original_seed
+    );
+    assert_eq!(rekeyed.created_date, main.created_date);
+    assert_eq!(rekeyed.use_count, 0);
+    assert_ne!(output(&mut rekeyed), output(&mut main));
+
+    // the lineage and the link survive the round trip
+    let decoded = SavedPassword::from_bytes(&rekeyed.to_bytes()).expect("decode");
+    assert_eq!(decoded.structure_system.lineage, 1);
+    assert_eq!(decoded.rekeyed_from.as_deref(), Some("main"));
+    let decoded = SavedPassword::from_bytes(&main.to_bytes()).expect("decode");
+    assert_eq!(decoded.structure_system.lineage, 0);
+    assert_eq!(decoded.rekeyed_from, None);
+
+    // the lineage has three bits in a slot
+    let mut last = rekeyed;
+    while last.structure_system.lineage < MAX_LINEAGE {
+        last = last.rekey("again", 2, None).expect("rekey");
+    }
+    assert!(last.rekey("once more", 2, None).is_none());
+}
+
+#[test]
+fn domains_resolve_by_lineage_until_migrated() {
+    let mut manager =
+        PasswordManager::with_storage(MemoryStorage::default(), true).expect("empty vault");
+    let main = config();
+    let rekeyed = main.rekey("main-v2", 5, None).expect("rekey");
+    manager.add_password(main).expect("add");
+    manager.add_password(rekeyed).expect("add");
+
+    let mut table = Box::new(DomainTable::new());
+    {
+        let structure = &mut manager.saved_passwords[0].structure_system;
+        table
+            .set_counter("old.example", 3, structure)
+            .expect("register");
+        table
+            .set_rules("old.example", 20, 7, structure)
+            .expect("rules");
+    }
+    {
+        let structure = &mut manager.saved_passwords[1].structure_system;
+        table
+            .set_counter("new.example", 0, structure)
+            .expect("register");
+    }
+
+    // picking either, a domain goes to the one it was registered with
+    assert_eq!(manager.resolve_domain(1, "old.example", &table), 0);
+    assert_eq!(manager.resolve_domain(0, "new.example", &table), 1);
+    assert_eq!(manager.resolve_domain(1, "unknown.example", &table), 1);
+
+    // the rules don't see the lineage bits
+    let structure = &mut manager.saved_passwords[1].structure_system;
+    assert_eq!(table.lineage("old.example", structure), Some(0));
+    assert_eq!(table.get_rules("old.example", structure), Some((20, 7)));
+
+    assert_eq!(table.migrate("old.example", structure), Ok(Some(4)));
+    assert_eq!(table.lineage("old.example", structure), Some(1));
+    assert_eq!(table.get_counter("old.example", structure), Some(4));
+    assert_eq!(table.get_rules("old.example", structure), Some((20, 7)));
+    assert_eq!(manager.resolve_domain(0, "old.example", &table), 1);
+}
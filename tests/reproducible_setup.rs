@@ -0,0 +1,61 @@
+// setup --reproducible leaves keystroke timing and the creation time out, so two
+// setups typing the same phrase into two copies of the binary store the same bytes
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use void_vault::{BinaryStorageManager, SavedPassword, SetupMode, VaultStorage};
+
+fn setup(vault: &Path, phrase: &str) -> Vec<u8> {
+    std::fs::copy(env!("CARGO_BIN_EXE_void_vault"), vault).expect("copy binary");
+    let mut child = Command::new(vault)
+        .args(["--i-know-what-im-doing", "setup", "--reproducible"])
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("run setup");
+    // Enter to begin, the phrase, then ESC
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(format!("\n{}\x1b", phrase).as_bytes())
+        .expect("type the phrase");
+    assert!(child.wait().expect("wait for setup").success());
+
+    let storage = BinaryStorageManager::open(vault.to_path_buf(), true, None).expect("open");
+    let (bytes, _) = storage
+        .retrieve("main")
+        .expect("retrieve")
+        .expect("a configuration");
+    bytes.to_vec()
+}
+
+#[test]
+fn the_same_phrase_sets_up_the_same_configuration() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("reproducible-setup");
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    let phrase = "a phrase typed twice, on two machines, a year apart";
+
+    let first = setup(&dir.join("first"), phrase);
+    let second = setup(&dir.join("second"), phrase);
+    assert_eq!(first, second);
+
+    let saved = SavedPassword::from_bytes(&first).expect("decode");
+    assert_eq!(saved.setup_mode, Some(SetupMode::Reproducible));
+    assert_eq!(saved.created_date, 0);
+
+    // a different phrase is a different configuration
+    let other = setup(
+        &dir.join("other"),
+        "a phrase typed once, on one machine, today",
+    );
+    assert_ne!(first, other);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
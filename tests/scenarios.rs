@@ -0,0 +1,52 @@
+// every tests/scenarios/*.scenario run through `void_vault script`, which sets up a
+// throwaway vault of its own and fails on the first expectation that doesn't hold.
+// a new file there is picked up without touching this one
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[test]
+fn every_scenario_holds() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("scenarios");
+    let mut scenarios: Vec<_> = std::fs::read_dir(&dir)
+        .expect("list the scenarios")
+        .map(|entry| entry.expect("a scenario").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "scenario"))
+        .collect();
+    scenarios.sort();
+    assert!(!scenarios.is_empty());
+
+    // all at once, each script is a process with a vault of its own
+    let running: Vec<_> = scenarios
+        .iter()
+        .map(|scenario| {
+            let child = Command::new(env!("CARGO_BIN_EXE_void_vault"))
+                .arg("script")
+                .arg(scenario)
+                .env_remove("VOID_VAULT_PROFILE")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .expect("run the scenario");
+            (scenario, child)
+        })
+        .collect();
+
+    let failed: Vec<String> = running
+        .into_iter()
+        .filter_map(|(scenario, child)| {
+            let output = child.wait_with_output().expect("wait for the scenario");
+            (!output.status.success()).then(|| {
+                format!(
+                    "{}: {}",
+                    scenario.display(),
+                    String::from_utf8_lossy(&output.stderr).trim_end()
+                )
+            })
+        })
+        .collect();
+    assert!(failed.is_empty(), "{}", failed.join("\n"));
+}
@@ -0,0 +1,82 @@
+// every store keeps the vault as it was as .bak. the one before is overwritten with
+// zeros, not just unlinked, which a handle still open on it can read back. a .new
+// left by a write that never finished goes at the next start, once it's old enough
+
+mod common;
+
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+
+use void_vault::{shred, BinaryStorageManager, VaultStorage};
+
+fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = common::scratch(name);
+    let vault = dir.join("vault");
+    let header: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+    std::fs::write(&vault, &header).expect("write scratch vault");
+    vault
+}
+
+#[test]
+fn the_superseded_backup_is_overwritten() {
+    let vault = scratch("shred-backup");
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+    storage
+        .store("first".to_string(), String::new(), &[7; 500])
+        .expect("store");
+    storage
+        .store("second".to_string(), String::new(), &[9; 500])
+        .expect("store again");
+
+    let backup = vault.with_extension("bak");
+    let mut superseded = std::fs::File::open(&backup).expect("open backup");
+    storage
+        .store("third".to_string(), String::new(), &[5; 500])
+        .expect("store a third time");
+
+    let mut contents = Vec::new();
+    superseded.read_to_end(&mut contents).expect("read backup");
+    assert!(!contents.is_empty());
+    assert!(contents.iter().all(|&b| b == 0));
+
+    // one backup is kept, and no copy left over
+    let leftovers = shred::leftovers(&vault);
+    assert_eq!(leftovers.len(), 1);
+    assert_eq!(leftovers[0].0, backup);
+    assert_eq!(
+        leftovers[0].1,
+        std::fs::metadata(&backup).expect("stat backup").len()
+    );
+
+    assert!(shred::shred_file(&backup).expect("shred"));
+    assert!(!shred::shred_file(&backup).expect("shred again"));
+    assert!(shred::leftovers(&vault).is_empty());
+
+    drop(storage);
+    let reopened = BinaryStorageManager::open(vault.clone(), true, None).expect("reopen");
+    assert_eq!(reopened.list_all().len(), 3);
+
+    let _ = std::fs::remove_dir_all(vault.parent().expect("scratch dir"));
+}
+
+#[test]
+fn only_a_stale_copy_is_removed() {
+    let vault = scratch("shred-stale");
+    let copy = vault.with_extension("new");
+    std::fs::write(&copy, [1; 300]).expect("write copy");
+
+    // a write might still be under way
+    shred::remove_stale_copy(&vault);
+    assert!(copy.exists());
+
+    std::fs::File::options()
+        .write(true)
+        .open(&copy)
+        .and_then(|file| file.set_modified(SystemTime::now() - Duration::from_secs(3600)))
+        .expect("age copy");
+    shred::remove_stale_copy(&vault);
+    assert!(!copy.exists());
+    assert!(vault.exists());
+
+    let _ = std::fs::remove_dir_all(vault.parent().expect("scratch dir"));
+}
@@ -0,0 +1,44 @@
+// the storage markers come from the vault's first 1KB, which never changes, so a
+// whole open, store and load cycle should read that header exactly once
+
+use std::path::Path;
+
+use void_vault::{BinaryStorageManager, VaultStorage};
+
+#[test]
+fn store_load_cycle_reads_the_header_once() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("storage-markers");
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    let vault = dir.join("vault");
+
+    // something header-like to derive the markers from, then nothing stored yet
+    let header: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+    std::fs::write(&vault, &header).expect("write scratch vault");
+
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+    storage
+        .store("first".to_string(), "one".to_string(), b"some bytes")
+        .expect("store");
+    assert_eq!(storage.header_reads(), 1);
+
+    // the next store derives them again, from the file the first one wrote
+    storage
+        .store("second".to_string(), "two".to_string(), b"more bytes")
+        .expect("store again");
+    assert_eq!(storage.header_reads(), 2);
+    drop(storage);
+
+    let reopened = BinaryStorageManager::open(vault.clone(), true, None).expect("reopen");
+    let (data, description) = reopened
+        .retrieve("first")
+        .expect("retrieve")
+        .expect("first is stored");
+    assert_eq!(data, b"some bytes");
+    assert_eq!(description, "one");
+    assert_eq!(reopened.list_all().len(), 2);
+    assert_eq!(reopened.header_reads(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
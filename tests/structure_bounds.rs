@@ -0,0 +1,68 @@
+// a walk stays inside the structure's bounds. under Transform::V7 a step longer than
+// the box is wide reflects until it lands inside; the transforms before reflect once
+// and keep doing so, or their passwords would change
+
+mod common;
+
+use void_vault::{Inspection, Motion, StructureSystem, Transform};
+
+fn long_steps(motion: Motion, transform: Transform) -> StructureSystem {
+    let mut structure = StructureSystem::new(0xB0B, 5, 17);
+    structure.set_character_set((33..127).collect());
+    structure.motion = motion;
+    structure.transform = transform;
+    // the default bounds are 60 wide
+    structure.base_step_size = 150.0;
+    structure.step_variance = 40.0;
+    structure
+}
+
+// FNV-1a over the codes, to pin the outputs down
+fn typed(structure: &mut StructureSystem, in_bounds: &mut bool) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for keycode in (0..300u32).map(|i| 33 + (i * 7) % 94) {
+        for code in structure.transform_char(keycode, 2) {
+            hash = (hash ^ code as u64).wrapping_mul(0x100000001b3);
+        }
+        *in_bounds &= structure.position_in_bounds();
+    }
+    hash
+}
+
+#[test]
+fn a_step_longer_than_the_box_lands_inside_it() {
+    for (motion, v6_hash, v7_hash) in [
+        (Motion::Float, 0xf9f58ec81929124f, 0xdd4c73e583ab8670),
+        (Motion::Fixed, 0x19311e1864ab5d4c, 0x6a0807c92d595d25),
+    ] {
+        let mut in_bounds = true;
+        let v7 = typed(&mut long_steps(motion, Transform::V7), &mut in_bounds);
+        assert!(in_bounds, "{:?}", motion);
+        assert_eq!(v7, v7_hash, "{:?}", motion);
+
+        // the reflection before stays as it was
+        let mut in_bounds = true;
+        let v6 = typed(&mut long_steps(motion, Transform::V6), &mut in_bounds);
+        assert!(!in_bounds, "{:?}", motion);
+        assert_eq!(v6, v6_hash, "{:?}", motion);
+    }
+}
+
+#[test]
+fn timing_points_widen_the_bounds() {
+    let pool: Vec<u32> = (33..127).collect();
+    let mut config = common::saved("bounds", StructureSystem::new(0xB0B, 5, 120), 2);
+    config.structure_system.generate_structure(&[], &pool);
+    let (before_min, before_max) = Inspection::of(&config, 0).structure_bounds;
+
+    for (i, &keycode) in pool.iter().enumerate() {
+        config
+            .structure_system
+            .modify_with_timing(keycode, 80 + i as u64 * 7, 1_000 + i as u64);
+    }
+    let (after_min, after_max) = Inspection::of(&config, 0).structure_bounds;
+
+    assert!(after_min.iter().zip(&before_min).all(|(a, b)| a <= b));
+    assert!(after_max.iter().zip(&before_max).all(|(a, b)| a >= b));
+    assert!(after_min != before_min || after_max != before_max);
+}
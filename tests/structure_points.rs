@@ -0,0 +1,158 @@
+// active points are packed into inline i16 arrays and delta encoded when a
+// configuration is written. this checks a record in the old full-width encoding
+// still reads, and prints what packing saves on a large structure
+//
+//   cargo test --test structure_points -- --nocapture
+
+mod common;
+
+use void_vault::{Motion, SavedPassword, StructureSystem};
+
+fn saved(structure_system: StructureSystem) -> SavedPassword {
+    common::saved("points", structure_system, 3)
+}
+
+fn outputs(config: &mut SavedPassword) -> Vec<u32> {
+    (0..200u32)
+        .flat_map(|key| config.structure_system.transform_char(key % 90, 3))
+        .collect()
+}
+
+fn legacy_point(bytes: &mut Vec<u8>, coordinates: &[i32]) {
+    bytes.extend_from_slice(&(coordinates.len() as u32).to_le_bytes());
+    for coord in coordinates {
+        bytes.extend_from_slice(&coord.to_le_bytes());
+    }
+}
+
+// a configuration record as it was written before points were packed, with a
+// repeated point the old HashSet could never have held, which reading drops
+fn legacy_record() -> Vec<u8> {
+    let mut structure = Vec::new();
+    structure.extend_from_slice(&3u32.to_le_bytes());
+    structure.extend_from_slice(&13i32.to_le_bytes());
+    structure.extend_from_slice(&42u64.to_le_bytes());
+    structure.extend_from_slice(&7u32.to_le_bytes());
+    structure.extend_from_slice(b"default");
+    structure.extend_from_slice(&94u32.to_le_bytes());
+    for code in '!'..='~' {
+        structure.extend_from_slice(&(code as u32).to_le_bytes());
+    }
+
+    let points = [[0, 0, 0], [5, -3, 12], [-13, 7, 1], [5, -3, 12], [2, 2, -9]];
+    structure.extend_from_slice(&(points.len() as u32).to_le_bytes());
+    for point in &points {
+        legacy_point(&mut structure, point);
+    }
+    structure.extend_from_slice(&2u32.to_le_bytes());
+    structure.extend_from_slice(&('a' as u32).to_le_bytes());
+    legacy_point(&mut structure, &[5, -3, 12]);
+    structure.extend_from_slice(&('Z' as u32).to_le_bytes());
+    legacy_point(&mut structure, &[-13, 7, 1]);
+
+    structure.extend_from_slice(&3.0f64.to_le_bytes());
+    structure.extend_from_slice(&2.0f64.to_le_bytes());
+    structure.push(0);
+
+    let mut record = Vec::new();
+    record.extend_from_slice(&6u32.to_le_bytes());
+    record.extend_from_slice(b"legacy");
+    record.extend_from_slice(&0u32.to_le_bytes());
+    record.extend_from_slice(&0u64.to_le_bytes());
+    record.extend_from_slice(&3u32.to_le_bytes());
+    record.extend_from_slice(&(structure.len() as u32).to_le_bytes());
+    record.extend(structure);
+    record.extend_from_slice(&0u32.to_le_bytes());
+    record.push(1);
+    record
+}
+
+#[test]
+fn legacy_records_still_read() {
+    let legacy = legacy_record();
+    let mut from_legacy = SavedPassword::from_bytes(&legacy).expect("legacy record");
+    assert_eq!(from_legacy.structure_system.active_point_count(), 4);
+    assert_eq!(from_legacy.structure_system.motion, Motion::Float);
+
+    let packed = from_legacy.to_bytes();
+    assert!(packed.len() < legacy.len());
+    let mut from_packed = SavedPassword::from_bytes(&packed).expect("packed record");
+    assert_eq!(from_packed.to_bytes(), packed);
+    assert_eq!(outputs(&mut from_legacy), outputs(&mut from_packed));
+}
+
+#[test]
+fn packing_shrinks_a_large_structure() {
+    const DIMENSIONS: usize = 7;
+    let pool: Vec<u32> = (0x21..0x3000).collect();
+    let phrase: Vec<char> = "a phrase to grow the structure from".chars().collect();
+
+    let mut bare = StructureSystem::new(99, DIMENSIONS, 17);
+    bare.set_character_set(pool.clone());
+    let bare_len = saved(bare).to_bytes().len();
+
+    let mut structure = StructureSystem::new(99, DIMENSIONS, 17);
+    structure.generate_structure(&phrase, &pool);
+    let points = structure.active_point_count();
+    let packed_len = saved(structure).to_bytes().len();
+
+    // the old encoding: no marker, a u32 count and i32s per point, and a u32
+    // keycode ahead of each mapped point
+    let legacy_len =
+        bare_len - 4 + points * (4 + 4 * DIMENSIONS) + pool.len() * (4 + 4 + 4 * DIMENSIONS);
+    let packed_points = packed_len - bare_len;
+    let legacy_points = legacy_len - (bare_len - 4);
+
+    // per point in memory: the inline array, against a Vec header and its heap block
+    let packed_memory = std::mem::size_of::<[i16; void_vault::MAX_DIMENSIONS]>();
+    let legacy_memory = std::mem::size_of::<Vec<i32>>() + 4 * DIMENSIONS;
+
+    println!(
+        "{} active and {} mapped points in {} dimensions",
+        points,
+        pool.len(),
+        DIMENSIONS
+    );
+    println!(
+        "  serialized: {} bytes, {} with full-width points ({} against {} for the points)",
+        packed_len, legacy_len, packed_points, legacy_points
+    );
+    println!(
+        "  in memory:  {} bytes a point inline, {} in a Vec<i32> before allocator overhead",
+        packed_memory, legacy_memory
+    );
+
+    assert!(packed_points * 3 < legacy_points);
+    assert!(packed_memory < legacy_memory);
+}
+
+// past the ceiling points are dropped, but every draw still happens, so a capped
+// structure is the same on every machine and the walk after it isn't shifted
+#[test]
+fn the_point_ceiling_is_deterministic() {
+    let pool: Vec<u32> = (0x21..0x3000).collect();
+    let phrase: Vec<char> = "a phrase to grow the structure from".chars().collect();
+    let build = |max_points: usize| {
+        let mut structure = StructureSystem::new(99, 7, 17);
+        structure.max_points = max_points;
+        structure.generate_structure(&phrase, &pool);
+        for (i, &keycode) in pool.iter().take(200).enumerate() {
+            structure.modify_with_timing(keycode, 80 + i as u64 * 7, 1_000 + i as u64);
+        }
+        saved(structure)
+    };
+
+    let mut capped = build(400);
+    let mut again = build(400);
+    assert!(capped.structure_system.points_capped());
+    assert!(capped.structure_system.active_point_count() <= 400);
+    assert_eq!(capped.to_bytes(), again.to_bytes());
+    assert_eq!(outputs(&mut capped), outputs(&mut again));
+
+    let uncapped = build(void_vault::DEFAULT_MAX_POINTS);
+    assert!(!uncapped.structure_system.points_capped());
+    assert!(
+        uncapped.structure_system.active_point_count()
+            > capped.structure_system.active_point_count()
+    );
+}
@@ -0,0 +1,118 @@
+// Ctrl+C in term mode: the vault runs on a pseudo-terminal, takes it out of canonical
+// mode with echo off, and a SIGINT to its process group, as the terminal would send,
+// has it put the settings back as they were before it exits
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the layout of struct termios on Linux
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Termios {
+    c_iflag: u32,
+    c_oflag: u32,
+    c_cflag: u32,
+    c_lflag: u32,
+    c_line: u8,
+    c_cc: [u8; 32],
+    c_ispeed: u32,
+    c_ospeed: u32,
+}
+
+const ICANON: u32 = 0o000002;
+const ECHO: u32 = 0o000010;
+const O_RDWR: i32 = 0o2;
+const O_NOCTTY: i32 = 0o400;
+const SIGINT: i32 = 2;
+
+extern "C" {
+    fn posix_openpt(flags: i32) -> i32;
+    fn grantpt(fd: i32) -> i32;
+    fn unlockpt(fd: i32) -> i32;
+    fn ptsname(fd: i32) -> *const std::ffi::c_char;
+    fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+    fn kill(pid: i32, signal: i32) -> i32;
+}
+
+// a pseudo-terminal's two ends: the one the terminal emulator holds, and the one
+// the program reads as its terminal
+fn open_pty() -> (File, File) {
+    use std::os::fd::FromRawFd;
+
+    unsafe {
+        let master = posix_openpt(O_RDWR | O_NOCTTY);
+        assert!(master >= 0, "posix_openpt");
+        assert_eq!(grantpt(master), 0, "grantpt");
+        assert_eq!(unlockpt(master), 0, "unlockpt");
+        let name = CStr::from_ptr(ptsname(master))
+            .to_str()
+            .expect("UTF-8 name")
+            .to_string();
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(name)
+            .expect("open the terminal end");
+        (File::from_raw_fd(master), slave)
+    }
+}
+
+fn settings(terminal: &File) -> Termios {
+    unsafe {
+        let mut termios = std::mem::zeroed::<Termios>();
+        assert_eq!(tcgetattr(terminal.as_raw_fd(), &mut termios), 0);
+        termios
+    }
+}
+
+#[test]
+fn sigint_in_term_mode_restores_the_terminal() {
+    let dir = common::scratch("terminal_restore");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase to be interrupted while typing");
+
+    let (_master, terminal) = open_pty();
+    let before = settings(&terminal);
+    assert_ne!(before.c_lflag & (ICANON | ECHO), 0);
+
+    let mut child = Command::new(&vault)
+        .arg("term")
+        .env_remove("VOID_VAULT_PROFILE")
+        .stdin(terminal.try_clone().expect("the terminal end"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .expect("run term");
+
+    // term takes the terminal over once it is reading keys
+    let started = Instant::now();
+    while settings(&terminal).c_lflag & (ICANON | ECHO) != 0 {
+        assert!(
+            child.try_wait().expect("poll term").is_none(),
+            "term exited"
+        );
+        assert!(
+            started.elapsed() < Duration::from_secs(20),
+            "never left canonical mode"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    // the whole group, parent and child, as Ctrl+C in a terminal reaches it
+    assert_eq!(unsafe { kill(-(child.id() as i32), SIGINT) }, 0);
+    let status = child.wait().expect("wait for term");
+    assert_eq!(status.code(), Some(128 + SIGINT), "{:?}", status);
+
+    assert_eq!(settings(&terminal), before);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
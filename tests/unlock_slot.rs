@@ -0,0 +1,54 @@
+// the unlock secret's verifier sits in a reserved slot of the domain table. it has
+// to survive a save and load like the domains around it, count its failures, stay
+// out of the domain listings and leave its slot free again once removed
+
+use void_vault::{DomainTable, StructureSystem, UnlockVerifier};
+
+#[test]
+fn the_verifier_lives_in_a_reserved_slot() {
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+
+    let mut table = Box::new(DomainTable::new());
+    assert!(table.get_unlock().is_none());
+    table
+        .set_counter("before.example", 4, &mut structure)
+        .expect("set counter");
+
+    let unlock = UnlockVerifier {
+        salt: [7; 16],
+        verifier: [9; 32],
+        failures: 0,
+    };
+    table.set_unlock(Some(unlock)).expect("set unlock");
+    table
+        .set_counter("after.example", 5, &mut structure)
+        .expect("set counter");
+
+    let stored = table.get_unlock().expect("an unlock secret");
+    assert_eq!(
+        (stored.salt, stored.verifier, stored.failures),
+        ([7; 16], [9; 32], 0)
+    );
+    let reserved = table.slots().iter().filter(|s| s.is_reserved()).count();
+    assert_eq!(reserved, 1);
+
+    // failures are counted in place, not in another slot
+    table
+        .set_unlock(Some(UnlockVerifier {
+            failures: 3,
+            ..unlock
+        }))
+        .expect("count a failure");
+    assert_eq!(table.get_unlock().map(|u| u.failures), Some(3));
+    let used = table.slots().iter().filter(|s| !s.is_empty()).count();
+    assert_eq!(used, 3);
+
+    table.set_unlock(None).expect("remove unlock");
+    assert!(table.get_unlock().is_none());
+    let used = table.slots().iter().filter(|s| !s.is_empty()).count();
+    assert_eq!(used, 2);
+    assert_eq!(table.get_counter("before.example", &mut structure), Some(4));
+    assert_eq!(table.get_counter("after.example", &mut structure), Some(5));
+}
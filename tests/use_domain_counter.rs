@@ -0,0 +1,79 @@
+// --use-domain-counter starts io, term and the interactive session where the
+// browser's ACTIVATE does, ghost navigated to the domain at its counter, so the same
+// phrase gives the extension's output whichever is used. :use changes the session's
+// domain, :use alone drops it
+
+mod common;
+
+use std::path::Path;
+
+use void_vault::escape_json;
+
+fn run(vault: &Path, args: &[&str], input: &[u8]) -> String {
+    String::from_utf8(common::stdout(vault, args, input)).expect("UTF-8 output")
+}
+
+// the line term prints the output on once Enter ends the phrase
+fn last_line(stdout: &str) -> &str {
+    stdout.trim_end().rsplit('\n').next().unwrap_or("")
+}
+
+// what each line typed into the interactive session ended on, its line redrawn after
+// every keystroke. a command's is cleared
+fn session_outputs(stdout: &str) -> Vec<&str> {
+    stdout
+        .split("Generated password:\n")
+        .skip(1)
+        .map(|phrase| {
+            let line = phrase.split('\n').next().unwrap_or("");
+            line.rsplit('\r').next().unwrap_or("")
+        })
+        .collect()
+}
+
+#[test]
+fn every_mode_starts_at_the_domains_counter() {
+    let dir = common::scratch("use_domain_counter");
+    let vault = common::vault_in(&dir);
+    common::setup(&vault, "a phrase for counting domains in every mode");
+    run(&vault, &["domains", "set", "example.com", "3"], b"");
+
+    let counter = ["--use-domain-counter", "example.com"];
+    let io = run(&vault, &[&counter[..], &["io"]].concat(), b"a phrase\n");
+    let io = io.trim_end();
+    assert!(!io.is_empty());
+    let origin = run(&vault, &["io"], b"a phrase\n");
+    assert_ne!(io, origin.trim_end());
+
+    let term = run(&vault, &[&counter[..], &["term"]].concat(), b"a phrase\n");
+    assert_eq!(last_line(&term), io);
+
+    // the extension's own, and --domain's, which adds only the domain's rules
+    let mut messages = common::frame("{\"type\":\"ACTIVATE\",\"domain\":\"example.com\"}");
+    for ch in "a phrase".chars() {
+        messages.extend(common::frame(&format!("{{\"charCode\":{}}}", ch as u32)));
+    }
+    let json_io = common::replies(&common::stdout(&vault, &["json-io"], &messages));
+    let typed = json_io.last().expect("a reply to the last keystroke");
+    assert!(
+        typed.contains(&format!("\"output\":\"{}\"", escape_json(io))),
+        "{}",
+        typed
+    );
+    let domain = run(&vault, &["io", "--domain", "example.com"], b"a phrase\n");
+    assert_eq!(domain.trim_end(), io);
+
+    // the interactive session itself, which the parent otherwise starts
+    let session = run(
+        &vault,
+        &[&counter[..], &["--child-process"]].concat(),
+        b"a phrase\n:use\na phrase\n:exit\n",
+    );
+    let outputs = session_outputs(&session);
+    assert!(session.contains("Active configuration: main, at example.com v3"));
+    assert_eq!(outputs[0], io);
+    assert_eq!(outputs[1], "");
+    assert_eq!(outputs[2], origin.trim_end());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
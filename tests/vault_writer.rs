@@ -0,0 +1,89 @@
+// stores and domain table saves go through one writer, which reads the file afresh
+// each time: a store writes every configuration once however often it runs, and a
+// table written in between, the decoy's first one included, is kept by the next store
+
+use std::path::Path;
+
+use void_vault::{BinaryStorageManager, DomainTable, StructureSystem, TableRegion, VaultStorage};
+
+#[test]
+fn stores_and_table_saves_keep_each_others_writes() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("vault_writer");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    let vault = dir.join("vault");
+
+    let header: Vec<u8> = (0..4096u32).map(|i| (i * 37 % 251) as u8).collect();
+    std::fs::write(&vault, &header).expect("write scratch vault");
+
+    // the first open writes the section marker at the end and an empty table
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+    let layout = BinaryStorageManager::probe(vault.clone()).expect("probe");
+    assert!(layout.section_marker);
+    assert!(layout.domain_table);
+
+    // storing the same configuration again replaces it rather than adding a copy
+    storage
+        .store("main".to_string(), String::new(), &[7u8; 2000])
+        .expect("store");
+    let size = std::fs::metadata(&vault).expect("stat vault").len();
+    for _ in 0..3 {
+        storage
+            .store("main".to_string(), String::new(), &[7u8; 2000])
+            .expect("store again");
+        assert_eq!(std::fs::metadata(&vault).expect("stat vault").len(), size);
+    }
+
+    let pool: Vec<u32> = ('a'..='z').map(|c| c as u32).collect();
+    let mut structure = StructureSystem::new(42, 7, 17);
+    structure.generate_structure(&[], &pool);
+
+    // the decoy's region is new, so saving it rewrites the whole file
+    let mut real = Box::new(DomainTable::new());
+    real.set_counter("real.example", 5, &mut structure)
+        .expect("set counter");
+    real.save_region(&vault, TableRegion::Real)
+        .expect("save table");
+    let mut decoy = Box::new(DomainTable::new());
+    decoy
+        .set_counter("decoy.example", 9, &mut structure)
+        .expect("set counter");
+    decoy
+        .save_region(&vault, TableRegion::Decoy)
+        .expect("save decoy table");
+
+    storage
+        .store("second".to_string(), String::new(), &[3u8; 1500])
+        .expect("store");
+    drop(storage);
+
+    let layout = BinaryStorageManager::probe(vault.clone()).expect("probe");
+    assert!(layout.section_marker);
+    assert_eq!(layout.configurations, 2);
+
+    let mut loaded = Box::new(DomainTable::new());
+    loaded
+        .load_region(&vault, TableRegion::Real)
+        .expect("load table");
+    assert_eq!(loaded.get_counter("real.example", &mut structure), Some(5));
+    loaded
+        .load_region(&vault, TableRegion::Decoy)
+        .expect("load decoy table");
+    assert_eq!(loaded.get_counter("decoy.example", &mut structure), Some(9));
+
+    let reopened = BinaryStorageManager::open(vault.clone(), true, None).expect("reopen");
+    let (data, _) = reopened
+        .retrieve("main")
+        .expect("retrieve")
+        .expect("main is stored");
+    assert_eq!(data, [7u8; 2000].as_slice());
+    let (data, _) = reopened
+        .retrieve("second")
+        .expect("retrieve")
+        .expect("second is stored");
+    assert_eq!(data, [3u8; 1500].as_slice());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
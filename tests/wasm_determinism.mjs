@@ -0,0 +1,69 @@
+// the wasm half of tests/wasm_determinism.rs, run as
+//   node wasm_determinism.mjs <void_vault.wasm> <configuration record> <case>...
+// where a case is domain, counter, pin length, word count and input, tab separated.
+// prints what native_outputs() prints for the same cases
+
+import { readFileSync } from "node:fs";
+
+const [wasmPath, configPath, ...cases] = process.argv.slice(2);
+const { instance } = await WebAssembly.instantiate(readFileSync(wasmPath), {});
+const vv = instance.exports;
+
+const encoder = new TextEncoder();
+
+function copyIn(bytes) {
+  const ptr = vv.vv_wasm_alloc(bytes.length);
+  new Uint8Array(vv.memory.buffer, ptr, bytes.length).set(bytes);
+  return ptr;
+}
+
+function hex(bytes) {
+  return Array.from(bytes, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+const config = readFileSync(configPath);
+const configPtr = copyIn(config);
+const outLen = 4096;
+const outPtr = vv.vv_wasm_alloc(outLen);
+
+for (const line of cases) {
+  const [domain, counter, pinLength, wordCount, input] = line.split("\t");
+
+  const session = vv.vv_wasm_open(configPtr, config.length);
+  if (session === 0) {
+    throw new Error("configuration did not decode");
+  }
+  const domainBytes = encoder.encode(domain);
+  const domainPtr = copyIn(domainBytes);
+  const activated = vv.vv_wasm_activate(
+    session,
+    domainPtr,
+    domainBytes.length,
+    Number(counter),
+    Number(pinLength),
+    Number(wordCount),
+  );
+  vv.vv_wasm_free(domainPtr, domainBytes.length);
+  if (activated !== 0) {
+    throw new Error(`activate ${domain}: ${activated}`);
+  }
+
+  const outputs = [];
+  for (let round = 0; round < 2; round++) {
+    if (round === 1 && vv.vv_wasm_reset(session) !== 0) {
+      throw new Error(`reset ${domain}`);
+    }
+    for (const ch of input) {
+      const written = vv.vv_wasm_feed_char(session, ch.codePointAt(0), outPtr, outLen);
+      if (written < 0) {
+        throw new Error(`feed ${ch} on ${domain}: ${written}`);
+      }
+      outputs.push(hex(new Uint8Array(vv.memory.buffer, outPtr, written)));
+    }
+  }
+  vv.vv_wasm_close(session);
+  console.log(outputs.join(" "));
+}
+
+vv.vv_wasm_free(outPtr, outLen);
+vv.vv_wasm_free(configPtr, config.length);
@@ -0,0 +1,152 @@
+// feeds the same configuration and keystrokes to the native exports in src/wasm.rs
+// and to the wasm32 build of them under node, and wants every output byte for byte.
+// skipped with a note when the wasm32-unknown-unknown target or node isn't there
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use void_vault::wasm::{
+    vv_wasm_activate, vv_wasm_close, vv_wasm_feed_char, vv_wasm_open, vv_wasm_reset,
+};
+use void_vault::StructureSystem;
+
+// domain, counter, pin length, word count, what gets typed. the accented one has a
+// combining mark for NFC to fold, the long one walks far enough to stress the f64
+// position math
+const CASES: [(&str, u16, u8, u8, &str); 6] = [
+    ("example.com", 0, 0, 0, "hunter2"),
+    ("example.com", 3, 0, 0, "correct horse battery staple"),
+    ("bank.example", 0, 6, 0, "a pin please"),
+    ("mail.example", 1, 0, 5, "some words for a passphrase"),
+    ("accents.example", 0, 0, 0, "cafe\u{301} über 😀"),
+    (
+        "long.example",
+        7,
+        0,
+        0,
+        "The quick brown fox jumps over the lazy dog, then does it again 0123456789",
+    ),
+];
+
+fn config_bytes() -> Vec<u8> {
+    let pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+    let phrase: Vec<char> = "a determinism check phrase".chars().collect();
+    let mut structure = StructureSystem::new(7, 7, 17);
+    structure.generate_structure(&phrase, &pool);
+
+    common::saved("determinism", structure, 0).to_bytes()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// one line per case: the output after every keystroke, space separated, then the
+// output of typing it all again after a reset
+fn native_outputs(config: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut out = vec![0u8; 4096];
+
+    for (domain, counter, pin_length, word_count, input) in CASES {
+        unsafe {
+            let session = vv_wasm_open(config.as_ptr(), config.len());
+            assert!(!session.is_null(), "configuration did not decode");
+            let activated = vv_wasm_activate(
+                session,
+                domain.as_ptr(),
+                domain.len(),
+                counter,
+                pin_length,
+                word_count,
+            );
+            assert_eq!(activated, 0, "activate {}", domain);
+
+            let mut outputs = Vec::new();
+            for round in 0..2 {
+                if round == 1 {
+                    assert_eq!(vv_wasm_reset(session), 0);
+                }
+                for ch in input.chars() {
+                    let written =
+                        vv_wasm_feed_char(session, ch as u32, out.as_mut_ptr(), out.len());
+                    assert!(written >= 0, "feed {:?} on {}: {}", ch, domain, written);
+                    outputs.push(hex(&out[..written as usize]));
+                }
+            }
+            vv_wasm_close(session);
+            lines.push(outputs.join(" "));
+        }
+    }
+    lines
+}
+
+fn wasm_outputs(manifest_dir: &Path, config: &[u8]) -> Option<Vec<String>> {
+    let target_dir = manifest_dir.join("target").join("wasm-determinism");
+    let built = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["build", "--lib", "--release", "--target", "wasm32-unknown-unknown"])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status();
+    match built {
+        Ok(status) if status.success() => {}
+        _ => {
+            eprintln!("skipped: the wasm32-unknown-unknown build failed or the target is missing");
+            return None;
+        }
+    }
+    let wasm = target_dir.join("wasm32-unknown-unknown/release/void_vault.wasm");
+
+    let config_path: PathBuf = target_dir.join("determinism.config");
+    std::fs::write(&config_path, config).expect("write configuration");
+
+    let mut node = Command::new("node");
+    node.arg(manifest_dir.join("tests/wasm_determinism.mjs"))
+        .arg(&wasm)
+        .arg(&config_path);
+    for (domain, counter, pin_length, word_count, input) in CASES {
+        node.arg(format!(
+            "{}\t{}\t{}\t{}\t{}",
+            domain, counter, pin_length, word_count, input
+        ));
+    }
+    let output = match node.output() {
+        Ok(output) => output,
+        Err(_) => {
+            eprintln!("skipped: node is not installed");
+            return None;
+        }
+    };
+    let _ = std::fs::remove_file(&config_path);
+    assert!(
+        output.status.success(),
+        "node failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Some(
+        String::from_utf8(output.stdout)
+            .expect("node output is UTF-8")
+            .lines()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+#[test]
+fn wasm_matches_native() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = config_bytes();
+
+    let native = native_outputs(&config);
+    let Some(wasm) = wasm_outputs(manifest_dir, &config) else {
+        return;
+    };
+
+    assert_eq!(native.len(), wasm.len());
+    for ((case, native), wasm) in CASES.iter().zip(&native).zip(&wasm) {
+        assert_eq!(native, wasm, "{} at counter {} differs", case.0, case.1);
+    }
+}
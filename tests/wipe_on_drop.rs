@@ -0,0 +1,140 @@
+// what holds a phrase, a structure or generated output overwrites its heap buffers
+// before they go back to the allocator. a recording allocator looks through every
+// block freed while one drops for a sentinel put in it: a plain Vec leaves it there,
+// the vault's own types don't
+
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use void_vault::{BinaryStorageManager, StructureSystem, VaultStorage, Wipe, Wiped};
+
+// sixteen bytes that turn up nowhere by chance, and four code points as a character
+// set holds them
+const SENTINEL: &[u8; 16] = b"wipe-me-2296-<#>";
+const SENTINEL_CODES: [u32; 4] = [0x10F0A1, 0x10F0B2, 0x10F0C3, 0x10F0D4];
+
+struct Recording;
+
+thread_local! {
+    // only the thread dropping is looked at, the other tests run beside it
+    static ARMED: Cell<bool> = const { Cell::new(false) };
+    static FOUND: Cell<usize> = const { Cell::new(0) };
+}
+
+fn holds_sentinel(block: &[u8]) -> bool {
+    let code_bytes = SENTINEL_CODES.map(u32::to_ne_bytes);
+    block.windows(SENTINEL.len()).any(|window| {
+        window == SENTINEL
+            || window
+                .chunks(4)
+                .zip(&code_bytes)
+                .all(|(chunk, code)| chunk == code)
+    })
+}
+
+unsafe impl GlobalAlloc for Recording {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    // realloc goes through here too, by default: alloc, copy, dealloc
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ARMED.try_with(Cell::get).unwrap_or(false) {
+            let block = std::slice::from_raw_parts(ptr, layout.size());
+            if holds_sentinel(block) {
+                FOUND.with(|found| found.set(found.get() + 1));
+            }
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static RECORDING: Recording = Recording;
+
+// how many blocks freed while `drop` ran still held the sentinel
+fn freed_with_sentinel(drop: impl FnOnce()) -> usize {
+    FOUND.with(|found| found.set(0));
+    ARMED.with(|armed| armed.set(true));
+    drop();
+    ARMED.with(|armed| armed.set(false));
+    FOUND.with(Cell::get)
+}
+
+fn secret_bytes() -> Vec<u8> {
+    let mut bytes = vec![b'Z'; 100];
+    bytes.extend_from_slice(SENTINEL);
+    bytes.extend(vec![b'z'; 100]);
+    bytes
+}
+
+#[test]
+fn a_plain_vec_leaves_the_sentinel_behind() {
+    // the recording itself works, or the rest would pass without it
+    let plain = secret_bytes();
+    assert_eq!(freed_with_sentinel(|| drop(plain)), 1);
+
+    let mut chars = SENTINEL_CODES.to_vec();
+    chars.push(0x61);
+    assert_eq!(freed_with_sentinel(|| drop(chars)), 1);
+}
+
+#[test]
+fn wipe_clears_spare_capacity_too() {
+    // cut back, so the sentinel is past the length, in the spare capacity
+    let mut truncated = secret_bytes();
+    truncated.truncate(50);
+    truncated.wipe();
+    assert!(truncated.is_empty());
+    assert_eq!(freed_with_sentinel(|| drop(truncated)), 0);
+
+    let mut text = String::from_utf8(secret_bytes()).expect("UTF-8");
+    text.truncate(10);
+    let wiped = Wiped(text);
+    assert_eq!(freed_with_sentinel(|| drop(wiped)), 0);
+}
+
+#[test]
+fn structures_and_configurations_wipe_themselves() {
+    let name = String::from_utf8(SENTINEL.to_vec()).expect("UTF-8");
+    let mut pool: Vec<u32> = ('!'..='~').map(|c| c as u32).collect();
+    pool.extend(SENTINEL_CODES);
+    let mut structure = StructureSystem::new(2296, 5, 17);
+    structure.generate_structure(&[], &pool);
+    structure.set_name(name.clone());
+    assert!(structure
+        .character_set
+        .windows(4)
+        .any(|codes| codes == SENTINEL_CODES));
+    assert_eq!(freed_with_sentinel(|| drop(structure)), 0);
+
+    let mut structure = StructureSystem::new(2296, 5, 17);
+    structure.generate_structure(&[], &pool);
+    let mut saved = common::saved("plain", structure, 2);
+    saved.name = name.clone();
+    saved.description = name;
+    assert_eq!(freed_with_sentinel(|| drop(saved)), 0);
+}
+
+#[test]
+fn the_storage_cache_is_wiped() {
+    let dir = common::scratch("wipe_on_drop");
+    let vault = dir.join("vault");
+    let header: Vec<u8> = (0..4096u32).map(|i| (i * 29 % 251) as u8).collect();
+    std::fs::write(&vault, &header).expect("write scratch vault");
+
+    let mut storage = BinaryStorageManager::open(vault.clone(), true, None).expect("open");
+    storage
+        .store("main".to_string(), String::new(), &secret_bytes())
+        .expect("store");
+    let (cached, _) = storage
+        .retrieve("main")
+        .expect("retrieve")
+        .expect("main is stored");
+    assert!(holds_sentinel(cached));
+    assert_eq!(freed_with_sentinel(|| drop(storage)), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}